@@ -0,0 +1,163 @@
+//! Reader and builder hot paths on synthetic corpora.
+//!
+//! The corpora are generated with [`SyntheticCorpus`] into a temporary directory on
+//! the first run, so results are comparable across commits. Run a single group with
+//! e.g. `cargo bench --bench dictionary -- lookup`.
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use std::hint::black_box;
+use std::io::BufReader;
+use std::fs::File;
+use std::path::PathBuf;
+
+use mdx::builder::{make_index, BuilderConfig, SyntheticCorpus};
+use mdx::utils::compression::CompressionMethod;
+use mdx::utils::MdxHtmlRewriter;
+use mdx::{MdxReader, ZdbReader};
+
+const LOOKUP_ENTRY_COUNT: usize = 20_000;
+const DECODE_ENTRY_COUNT: usize = 5_000;
+const SAMPLE_COUNT: usize = 1_000;
+
+fn corpus_dir() -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("mdx_bench_{}", env!("CARGO_PKG_VERSION")));
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// Builds the corpus into `name` unless it exists, returns the path of the dictionary.
+fn build_corpus(name: &str, entry_count: usize, configure: impl FnOnce(&mut BuilderConfig)) -> PathBuf {
+    let path = corpus_dir().join(name);
+    if !path.exists() {
+        let mut config = BuilderConfig::default();
+        config.output_file = path.to_string_lossy().to_string();
+        config.default_sorting_locale = "en".to_string();
+        configure(&mut config);
+        SyntheticCorpus::new(entry_count).build(&mut config).unwrap();
+    }
+    path
+}
+
+fn open(path: &PathBuf) -> ZdbReader<BufReader<File>> {
+    ZdbReader::<BufReader<File>>::from_file(path, "", "").unwrap()
+}
+
+/// Keys spread over the whole corpus.
+fn sample_keys(entry_count: usize) -> Vec<String> {
+    let keys = SyntheticCorpus::new(entry_count).keys();
+    keys.iter().step_by(keys.len() / SAMPLE_COUNT).cloned().collect()
+}
+
+fn open_benchmark(c: &mut Criterion) {
+    let path = build_corpus("lookup.mdx", LOOKUP_ENTRY_COUNT, |config| config.bloom_filter = true);
+    c.bench_function("open", |b| b.iter(|| open(black_box(&path))));
+}
+
+fn lookup_benchmark(c: &mut Criterion) {
+    let path = build_corpus("lookup.mdx", LOOKUP_ENTRY_COUNT, |config| config.bloom_filter = true);
+    let mut reader = open(&path);
+    let keys = sample_keys(LOOKUP_ENTRY_COUNT);
+    let missing_keys: Vec<String> = keys.iter().map(|key| format!("{}zz", key)).collect();
+
+    let mut group = c.benchmark_group("lookup");
+    group.throughput(Throughput::Elements(keys.len() as u64));
+    group.bench_function("exact", |b| b.iter(|| {
+        for key in &keys {
+            black_box(reader.find_first_match(key, false, false, true).unwrap());
+        }
+    }));
+    group.bench_function("missing", |b| b.iter(|| {
+        for key in &missing_keys {
+            black_box(reader.find_first_match(key, false, false, true).unwrap());
+        }
+    }));
+    group.bench_function("bulk", |b| b.iter(|| {
+        let queries: Vec<&str> = keys.iter().map(String::as_str).collect();
+        black_box(reader.lookup_many(&queries).unwrap());
+    }));
+    group.bench_function("missing_bloom_filter", |b| b.iter(|| {
+        for key in &missing_keys {
+            if reader.may_contain(key).unwrap() {
+                black_box(reader.find_first_match(key, false, false, true).unwrap());
+            }
+        }
+    }));
+    group.finish();
+}
+
+fn prefix_scan_benchmark(c: &mut Criterion) {
+    let path = build_corpus("lookup.mdx", LOOKUP_ENTRY_COUNT, |config| config.bloom_filter = true);
+    let mut reader = open(&path);
+    let prefixes: Vec<String> = sample_keys(LOOKUP_ENTRY_COUNT).iter().map(|key| key.chars().take(3).collect()).collect();
+
+    let mut group = c.benchmark_group("prefix_scan");
+    group.throughput(Throughput::Elements(prefixes.len() as u64));
+    group.bench_function("similar_100", |b| b.iter(|| {
+        for prefix in &prefixes {
+            if let Some(key_index) = reader.find_first_match(prefix, true, false, false).unwrap() {
+                black_box(reader.get_similar_indexes(&key_index, true, 100).unwrap());
+            }
+        }
+    }));
+    group.finish();
+}
+
+fn content_decode_benchmark(c: &mut Criterion) {
+    let mut group = c.benchmark_group("content_decode");
+    for compression_method in [CompressionMethod::None, CompressionMethod::Lz4, CompressionMethod::Deflate, CompressionMethod::Lzma] {
+        for block_size in [16 * 1024, 64 * 1024, 256 * 1024] {
+            let name = format!("decode_{:?}_{}.mdx", compression_method, block_size);
+            let path = build_corpus(&name, DECODE_ENTRY_COUNT, |config| {
+                config.compression_method = compression_method;
+                config.preferred_content_block_size = block_size;
+            });
+            let mut reader = open(&path);
+            let total_length = std::fs::metadata(path.with_extension("txt")).unwrap().len();
+            group.throughput(Throughput::Bytes(total_length));
+            group.bench_with_input(BenchmarkId::new(format!("{:?}", compression_method), block_size), &block_size, |b, _| b.iter(|| {
+                for entry_no in 0..reader.get_entry_count() {
+                    let key_index = reader.get_index(entry_no as _).unwrap();
+                    black_box(reader.get_data(&key_index, false).unwrap());
+                }
+            }));
+        }
+    }
+    group.finish();
+}
+
+fn html_rewrite_benchmark(c: &mut Criterion) {
+    let html: String = SyntheticCorpus::new(500).entries().into_iter().map(|(_, html)| html).collect();
+    let mut group = c.benchmark_group("html_rewrite");
+    group.throughput(Throughput::Bytes(html.len() as u64));
+    group.bench_function("rewrite_html", |b| b.iter(|| MdxHtmlRewriter::rewrite_html(black_box(&html), 0).unwrap()));
+    group.finish();
+}
+
+fn fts_benchmark(c: &mut Criterion) {
+    let path = build_corpus("fts.mdx", DECODE_ENTRY_COUNT, |_| {});
+    if !path.with_extension("idx").exists() {
+        make_index(&path, None).unwrap();
+    }
+    let reader = MdxReader::from_url(&url::Url::from_file_path(&path).unwrap(), "").unwrap();
+    let queries: Vec<String> = sample_keys(DECODE_ENTRY_COUNT).into_iter().take(50).collect();
+
+    let mut group = c.benchmark_group("fts");
+    group.throughput(Throughput::Elements(queries.len() as u64));
+    group.bench_function("search_top20", |b| b.iter(|| {
+        for query in &queries {
+            black_box(reader.fts_search(query, 20).unwrap());
+        }
+    }));
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    open_benchmark,
+    lookup_benchmark,
+    prefix_scan_benchmark,
+    content_decode_benchmark,
+    html_rewrite_benchmark,
+    fts_benchmark
+);
+criterion_main!(benches);
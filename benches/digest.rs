@@ -0,0 +1,87 @@
+//! Content digests and the build-time effect of storing identical content once.
+//!
+//! The resource corpus mimics an MDD where many entries are copies of the same few
+//! files, like icons and sounds shared by entries. Run a single group with e.g.
+//! `cargo bench --bench digest -- dedup`, add `--features blake3` to include BLAKE3.
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use std::hint::black_box;
+use std::io::Cursor;
+
+use mdx::builder::{BuilderConfig, DataLoader, ZDBBuilder, ZdbRecord};
+use mdx::crypto::DigestAlgorithm;
+
+const RESOURCE_COUNT: usize = 2_000;
+const SHARED_RESOURCE_COUNT: usize = 20;
+const RESOURCE_SIZE: usize = 16 * 1024;
+
+struct RecordContentLoader;
+
+impl DataLoader for RecordContentLoader {
+    fn load_data(&mut self, entry: &ZdbRecord) -> mdx::Result<Vec<u8>> {
+        Ok(entry.content.as_bytes().to_vec())
+    }
+}
+
+fn algorithms() -> Vec<DigestAlgorithm> {
+    let mut algorithms = vec![DigestAlgorithm::FastHash];
+    if cfg!(feature = "blake3") {
+        algorithms.push(DigestAlgorithm::Blake3);
+    }
+    algorithms
+}
+
+/// Text that compresses about as well as typical resources, from a simple LCG.
+fn resource_content(seed: usize) -> String {
+    let mut state = seed as u64 * 6364136223846793005 + 1442695040888963407;
+    (0..RESOURCE_SIZE).map(|_| {
+        state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+        (b'a' + (state >> 59) as u8) as char
+    }).collect()
+}
+
+/// Half of the resources are copies of a few shared ones.
+fn resource_records() -> Vec<ZdbRecord> {
+    (0..RESOURCE_COUNT).map(|i| ZdbRecord {
+        key: format!("/res/{:05}.bin", i),
+        content: resource_content(if i % 2 == 0 { i % SHARED_RESOURCE_COUNT } else { i }),
+        ..Default::default()
+    }).collect()
+}
+
+fn digest_benchmark(c: &mut Criterion) {
+    let input = resource_content(0).repeat(64);
+    let mut group = c.benchmark_group("digest");
+    group.throughput(Throughput::Bytes(input.len() as u64));
+    for algorithm in algorithms() {
+        group.bench_function(algorithm.name(), |b| b.iter(|| algorithm.digest(black_box(input.as_bytes())).unwrap()));
+    }
+    group.finish();
+}
+
+fn dedup_benchmark(c: &mut Criterion) {
+    let records = resource_records();
+    let total_length: usize = records.iter().map(|record| record.content.len()).sum();
+    let mut group = c.benchmark_group("dedup");
+    group.sample_size(10);
+    group.throughput(Throughput::Bytes(total_length as u64));
+    let content_digests = std::iter::once(None).chain(algorithms().into_iter().map(Some));
+    for content_digest in content_digests {
+        let config = BuilderConfig {
+            default_sorting_locale: "en".to_string(),
+            content_type: "Binary".to_string(),
+            content_digest,
+            ..Default::default()
+        };
+        let name = content_digest.map_or("none", |algorithm| algorithm.name());
+        group.bench_with_input(BenchmarkId::new("build", name), &config, |b, config| b.iter(|| {
+            let mut writer = Cursor::new(Vec::new());
+            ZDBBuilder::build_records_to_writer(config, &mut writer, RecordContentLoader, records.clone(), None).unwrap();
+            black_box(writer.into_inner().len())
+        }));
+    }
+    group.finish();
+}
+
+criterion_group!(benches, digest_benchmark, dedup_benchmark);
+criterion_main!(benches);
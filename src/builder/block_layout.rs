@@ -0,0 +1,136 @@
+//! Strategies for grouping entries into content blocks.
+//!
+//! Entries are always stored in key order, since the length of an entry is the distance
+//! to the content of the next one, so a layout only decides where one block ends and the
+//! next begins. Readers decompress a whole block to return one entry, grouping entries
+//! that are read together makes the block cache more effective, e.g. when browsing
+//! similar words or loading the images of one page.
+//!
+//! The layout is recorded in the `ContentBlockLayout` header attribute for diagnostics,
+//! readers don't need it.
+//!
+//! # Examples
+//!
+//! ```
+//! use mdx::builder::ContentBlockLayout;
+//!
+//! let layout: ContentBlockLayout = serde_json::from_str(r#"{"KeyPrefix": 2}"#).unwrap();
+//! assert_eq!(layout.to_header_value(), "KeyPrefix:2");
+//! assert_eq!(ContentBlockLayout::from_header_value("KeyPrefix:2"), Some(layout));
+//! ```
+
+use serde::{Deserialize, Serialize};
+
+use crate::builder::data_loader::ZdbRecord;
+use crate::utils::mdd_key;
+
+/// How entries are grouped into content blocks, see the [module documentation](self).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum ContentBlockLayout {
+    /// Fill blocks up to the preferred content block size
+    #[default]
+    Size,
+    /// A fixed number of entries per block, whatever their size
+    Count(u32),
+    /// Keep keys sharing their first characters together, e.g. homographs and derived forms
+    ///
+    /// A block ends where the prefix changes once it holds half the preferred size,
+    /// and grows to at most twice the preferred size within a prefix.
+    KeyPrefix(u32),
+    /// Keep resources of the same type together, for MDD files
+    ///
+    /// A block ends where the file extension of the keys changes, or at the preferred size.
+    MediaType,
+}
+
+impl ContentBlockLayout {
+    /// Encodes the layout as the `ContentBlockLayout` header attribute, empty for [`Size`](Self::Size).
+    pub fn to_header_value(&self) -> String {
+        match self {
+            ContentBlockLayout::Size => String::new(),
+            ContentBlockLayout::Count(count) => format!("Count:{}", count),
+            ContentBlockLayout::KeyPrefix(len) => format!("KeyPrefix:{}", len),
+            ContentBlockLayout::MediaType => "MediaType".to_string(),
+        }
+    }
+
+    /// Parses the `ContentBlockLayout` header attribute, `None` if it's not recognized.
+    ///
+    /// An empty value is the [`Size`](Self::Size) layout of files without the attribute.
+    pub fn from_header_value(value: &str) -> Option<Self> {
+        match value.split_once(':') {
+            Some(("Count", count)) => count.parse().ok().map(ContentBlockLayout::Count),
+            Some(("KeyPrefix", len)) => len.parse().ok().map(ContentBlockLayout::KeyPrefix),
+            Some(_) => None,
+            None => match value {
+                "" | "Size" => Some(ContentBlockLayout::Size),
+                "MediaType" => Some(ContentBlockLayout::MediaType),
+                _ => None,
+            },
+        }
+    }
+
+    /// Checks the parameters of the layout.
+    pub fn validate(&self) -> Option<String> {
+        match self {
+            ContentBlockLayout::Count(0) => Some("content_block_layout Count must be greater than 0".to_string()),
+            ContentBlockLayout::KeyPrefix(0) => Some("content_block_layout KeyPrefix must be greater than 0".to_string()),
+            _ => None,
+        }
+    }
+
+    /// Whether the block holding `prev` ends before `next`.
+    ///
+    /// # Arguments
+    ///
+    /// * `block_len` - Length of the content in the block so far
+    /// * `block_entries` - Number of entries in the block so far, at least one
+    /// * `prev` - The last entry in the block
+    /// * `next` - The entry to be added
+    /// * `preferred_size` - The preferred content block size
+    pub(crate) fn ends_block_before(&self, block_len: usize, block_entries: usize, prev: &ZdbRecord, next: &ZdbRecord, preferred_size: usize) -> bool {
+        match self {
+            ContentBlockLayout::Size => block_len > preferred_size,
+            ContentBlockLayout::Count(count) => block_entries >= *count as usize,
+            ContentBlockLayout::KeyPrefix(len) => {
+                block_len > preferred_size * 2
+                    || (block_len >= preferred_size / 2 && key_prefix(&prev.key, *len) != key_prefix(&next.key, *len))
+            }
+            ContentBlockLayout::MediaType => {
+                block_len > preferred_size || mdd_key::extension(&prev.key) != mdd_key::extension(&next.key)
+            }
+        }
+    }
+}
+
+/// The first `len` characters of a key, in lower case so case variants stay together.
+fn key_prefix(key: &str, len: u32) -> String {
+    key.chars().take(len as usize).flat_map(char::to_lowercase).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(key: &str) -> ZdbRecord {
+        ZdbRecord { key: key.to_string(), ..Default::default() }
+    }
+
+    #[test]
+    fn test_block_boundaries() {
+        let (apple, apply, bank) = (record("apple"), record("Apply"), record("bank"));
+        assert!(!ContentBlockLayout::Size.ends_block_before(100, 50, &apple, &bank, 100));
+        assert!(ContentBlockLayout::Size.ends_block_before(101, 1, &apple, &bank, 100));
+        assert!(ContentBlockLayout::Count(2).ends_block_before(1, 2, &apple, &apply, 100));
+        assert!(!ContentBlockLayout::KeyPrefix(2).ends_block_before(150, 9, &apple, &apply, 100));
+        assert!(ContentBlockLayout::KeyPrefix(2).ends_block_before(50, 9, &apple, &bank, 100));
+        assert!(!ContentBlockLayout::KeyPrefix(2).ends_block_before(49, 9, &apple, &bank, 100));
+        assert!(ContentBlockLayout::KeyPrefix(2).ends_block_before(201, 9, &apple, &apply, 100));
+        assert!(ContentBlockLayout::MediaType.ends_block_before(1, 1, &record("/a.png"), &record("/a.mp3"), 100));
+        assert!(!ContentBlockLayout::MediaType.ends_block_before(1, 1, &record("/a.png"), &record("/b.PNG"), 100));
+        for layout in [ContentBlockLayout::Size, ContentBlockLayout::Count(8), ContentBlockLayout::KeyPrefix(3), ContentBlockLayout::MediaType] {
+            assert_eq!(ContentBlockLayout::from_header_value(&layout.to_header_value()), Some(layout));
+        }
+        assert_eq!(ContentBlockLayout::from_header_value("Count:x"), None);
+    }
+}
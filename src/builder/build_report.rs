@@ -0,0 +1,114 @@
+//! Summary of a finished build.
+//!
+//! Problems that don't stop a build, like files with undecodable names or entries
+//! without content, used to be written to the log only. They are collected as typed
+//! [`BuildWarning`]s in the [`BuildReport`] returned by the builder, so tools driving
+//! the build can list or reject them. Every warning is still logged when it's recorded.
+
+use std::fmt;
+
+use serde::Serialize;
+
+/// Stage of the build in which a warning was raised.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub enum BuildPhase {
+    /// Reading the entry list from the source
+    Loading,
+    /// Loading and writing the content of the entries
+    Content,
+}
+
+impl fmt::Display for BuildPhase {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BuildPhase::Loading => write!(f, "loading"),
+            BuildPhase::Content => write!(f, "content"),
+        }
+    }
+}
+
+/// Problem found during a build that didn't stop it.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind")]
+pub enum BuildWarning {
+    /// A file path isn't valid UTF-8, its key was generated with replacement characters
+    LossyPathKey { key: String, path: String },
+    /// A manifest entry of a source directory doesn't match any file
+    UnusedManifestEntry { path: String },
+    /// The last entry of an MDict source isn't terminated by `</>`, the file may be truncated
+    MissingEntryTerminator { key: String, line_no: u64 },
+    /// An entry has no content
+    EmptyContent { key: String },
+    /// The content of an entry can't be loaded, the entry was left out, see [`RecordErrorPolicy::Skip`](crate::builder::RecordErrorPolicy::Skip)
+    SkippedEntry { key: String, error: String },
+    /// The content of an entry can't be loaded and was replaced, see [`RecordErrorPolicy::Placeholder`](crate::builder::RecordErrorPolicy::Placeholder)
+    PlaceholderContent { key: String, error: String },
+    /// A line of the entry metadata file names a key without entries, see [`BuilderConfig::entry_meta_path`](crate::builder::BuilderConfig::entry_meta_path)
+    UnmatchedEntryMeta { key: String, line_no: u64 },
+    /// An `entry://` link of an entry has no target, see [`BuilderConfig::resolve_cross_references`](crate::builder::BuilderConfig::resolve_cross_references)
+    BrokenCrossReference { key: String, target: String },
+    /// The source has no entries, the dictionary was built empty
+    NoEntries,
+}
+
+impl BuildWarning {
+    /// Phase of the build the warning was raised in.
+    pub fn phase(&self) -> BuildPhase {
+        match self {
+            BuildWarning::LossyPathKey { .. } | BuildWarning::UnusedManifestEntry { .. } | BuildWarning::MissingEntryTerminator { .. }
+                | BuildWarning::UnmatchedEntryMeta { .. } | BuildWarning::NoEntries => BuildPhase::Loading,
+            BuildWarning::EmptyContent { .. } | BuildWarning::SkippedEntry { .. } | BuildWarning::PlaceholderContent { .. }
+                | BuildWarning::BrokenCrossReference { .. } => BuildPhase::Content,
+        }
+    }
+
+    /// Key of the entry the warning is about, if it concerns a single entry.
+    pub fn key(&self) -> Option<&str> {
+        match self {
+            BuildWarning::LossyPathKey { key, .. } | BuildWarning::MissingEntryTerminator { key, .. } | BuildWarning::EmptyContent { key }
+                | BuildWarning::SkippedEntry { key, .. } | BuildWarning::PlaceholderContent { key, .. }
+                | BuildWarning::UnmatchedEntryMeta { key, .. } | BuildWarning::BrokenCrossReference { key, .. } => Some(key),
+            BuildWarning::UnusedManifestEntry { .. } | BuildWarning::NoEntries => None,
+        }
+    }
+
+    /// Writes the warning to the log.
+    pub(crate) fn log(&self) {
+        log::warn!("{}", self);
+    }
+}
+
+impl fmt::Display for BuildWarning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BuildWarning::LossyPathKey { path, .. } => write!(f, "Path is not valid UTF-8, the key is generated lossily: {}", path),
+            BuildWarning::UnusedManifestEntry { path } => write!(f, "Manifest entry '{}' does not match any file", path),
+            BuildWarning::MissingEntryTerminator { key, line_no } => write!(f, "Entry '{}' ending at line {} is not terminated by </>", key, line_no),
+            BuildWarning::EmptyContent { key } => write!(f, "Entry '{}' has no content", key),
+            BuildWarning::SkippedEntry { key, error } => write!(f, "Entry '{}' was skipped, its content can't be loaded: {}", key, error),
+            BuildWarning::PlaceholderContent { key, error } => write!(f, "Entry '{}' has placeholder content, its content can't be loaded: {}", key, error),
+            BuildWarning::BrokenCrossReference { key, target } => write!(f, "Entry '{}' links to '{}', which is not an entry", key, target),
+            BuildWarning::UnmatchedEntryMeta { key, line_no } => write!(f, "Entry metadata for '{}' at line {} matches no entry", key, line_no),
+            BuildWarning::NoEntries => write!(f, "The source has no entries, the dictionary is empty"),
+        }
+    }
+}
+
+/// Result of a successful build.
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct BuildReport {
+    /// Number of entries written, including generated union entries
+    pub entry_count: u64,
+    /// Warnings in the order they were raised
+    pub warnings: Vec<BuildWarning>,
+}
+
+impl BuildReport {
+    /// Keys of the entries whose content couldn't be loaded, whether skipped or stored with placeholder content.
+    pub fn skipped_keys(&self) -> Vec<&str> {
+        self.warnings.iter()
+            .filter(|warning| matches!(warning, BuildWarning::SkippedEntry { .. } | BuildWarning::PlaceholderContent { .. }))
+            .filter_map(BuildWarning::key)
+            .collect()
+    }
+}
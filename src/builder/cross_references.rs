@@ -0,0 +1,133 @@
+//! Build-time resolution of cross-references between entries.
+//!
+//! HTML content links to other entries by key with `entry://key` URLs, which readers
+//! resolve with a key lookup on every click. With
+//! [`BuilderConfig::resolve_cross_references`](crate::builder::BuilderConfig::resolve_cross_references)
+//! the builder looks every target up once against the final key set and rewrites the
+//! link to `entryx://<entry number>`, which [`MdxHtmlRewriter`](crate::utils::MdxHtmlRewriter)
+//! already supports. Fragments are kept, `entry://key#sense` becomes `entryx://42#sense`.
+//!
+//! A target is resolved like an exact lookup by the reader: to the first entry whose key
+//! is equal to it under the sorting locale and key normalization of the dictionary.
+//! Targets without an entry are left unchanged and reported as
+//! [`BuildWarning::BrokenCrossReference`](crate::builder::BuildWarning::BrokenCrossReference).
+
+use std::cmp::Ordering;
+use std::sync::Arc;
+
+use lol_html::{element, HtmlRewriter, Settings};
+
+use crate::builder::data_loader::ZdbRecord;
+use crate::storage::key_block::EntryNo;
+use crate::utils::icu_wrapper::{shared_collator, UCollator};
+use crate::utils::key_normalization::{normalize_query, KeyNormalization};
+use crate::{Result, ZdbError};
+
+const ENTRY_SCHEME: &str = "entry://";
+
+/// Resolves `entry://` links to entry numbers, see the [module documentation](self).
+pub struct CrossReferenceResolver {
+    /// Normalized keys in entry order, which is the collation order
+    keys: Vec<String>,
+    collator: Arc<UCollator>,
+    normalization: KeyNormalization,
+}
+
+impl CrossReferenceResolver {
+    /// Creates a resolver for entries sorted by [`ZDBBuilder::prepare_key_index`](crate::builder::ZDBBuilder::prepare_key_index).
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the collator of the sorting locale can't be created.
+    pub fn new(entries: &[ZdbRecord], locale_id: &str, normalization: KeyNormalization) -> Result<Self> {
+        Ok(Self {
+            keys: entries.iter().map(|entry| normalize_query(&entry.key, &normalization).into_owned()).collect(),
+            collator: shared_collator(locale_id)?,
+            normalization,
+        })
+    }
+
+    /// Finds the number of the first entry with the key `target`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the keys can't be compared.
+    pub fn resolve(&self, target: &str) -> Result<Option<EntryNo>> {
+        let target = normalize_query(target, &self.normalization);
+        let (mut low, mut high) = (0, self.keys.len());
+        while low < high {
+            let mid = (low + high) / 2;
+            if self.collator.strcoll_utf8(&self.keys[mid], &target)? == Ordering::Less {
+                low = mid + 1;
+            } else {
+                high = mid;
+            }
+        }
+        match self.keys.get(low) {
+            Some(key) if self.collator.strcoll_utf8(key, &target)? == Ordering::Equal => Ok(Some(low as EntryNo)),
+            _ => Ok(None),
+        }
+    }
+
+    /// Rewrites the `entry://` links of the HTML content of one entry.
+    ///
+    /// # Arguments
+    ///
+    /// * `content` - UTF-8 encoded HTML content
+    /// * `broken` - Receives the targets that can't be resolved
+    ///
+    /// # Returns
+    ///
+    /// Returns the content with the resolvable links rewritten.
+    pub fn apply(&self, content: Vec<u8>, broken: &mut Vec<String>) -> Result<Vec<u8>> {
+        // Most entries have no links, they aren't parsed
+        if !content.windows(ENTRY_SCHEME.len()).any(|window| window.eq_ignore_ascii_case(ENTRY_SCHEME.as_bytes())) {
+            return Ok(content);
+        }
+        let mut output = Vec::with_capacity(content.len());
+        let mut rewriter = HtmlRewriter::new(
+            Settings {
+                element_content_handlers: vec![element!("*[href]", |el| {
+                    if let Some(href) = el.get_attribute("href")
+                        && let Some(resolved) = self.resolve_url(&href, broken)? {
+                        el.set_attribute("href", &resolved)?;
+                    }
+                    Ok(())
+                })],
+                ..Settings::default()
+            },
+            |chunk: &[u8]| output.extend_from_slice(chunk),
+        );
+        rewriter.write(&content).map_err(|e| ZdbError::general_error(format!("HTML rewriting error: {}", e)))?;
+        rewriter.end().map_err(|e| ZdbError::general_error(format!("HTML rewriting end error: {}", e)))?;
+        Ok(output)
+    }
+
+    /// Returns the `entryx://` URL of a link, `None` if it isn't an `entry://` link or its target has no entry.
+    fn resolve_url(&self, url: &str, broken: &mut Vec<String>) -> Result<Option<String>> {
+        let url = url.trim();
+        if url.len() < ENTRY_SCHEME.len() || !url[..ENTRY_SCHEME.len()].eq_ignore_ascii_case(ENTRY_SCHEME) {
+            return Ok(None);
+        }
+        let (target, fragment) = match url[ENTRY_SCHEME.len()..].split_once('#') {
+            Some((target, fragment)) => (target, Some(fragment)),
+            None => (&url[ENTRY_SCHEME.len()..], None),
+        };
+        // Decoded like the reader does, `entry://#fragment` links within the entry stay
+        let target = percent_encoding::percent_decode_str(target).decode_utf8_lossy();
+        let target = target.trim_start_matches('/');
+        if target.is_empty() {
+            return Ok(None);
+        }
+        match self.resolve(target)? {
+            Some(entry_no) => Ok(Some(match fragment {
+                Some(fragment) => format!("entryx://{}#{}", entry_no, fragment),
+                None => format!("entryx://{}", entry_no),
+            })),
+            None => {
+                broken.push(target.to_string());
+                Ok(None)
+            }
+        }
+    }
+}
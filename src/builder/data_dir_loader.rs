@@ -1,62 +1,377 @@
-use std::collections::LinkedList;
+//! Data loader for building MDD files from a directory tree.
+//!
+//! By default every file becomes one entry whose key is its path relative to the
+//! source directory, e.g. `img/cat.png` becomes `/img/cat.png`. An optional
+//! manifest file in the root of the directory can change that mapping:
+//!
+//! - `exclude`: glob patterns of files that are not packed
+//! - `files`: per-file settings keyed by relative path, with a replacement `key`,
+//!   additional `aliases` and a `content_type` (`text`, `html` or `binary`)
+//!
+//! The manifest is named `.manifest.json` or `.manifest.toml` and is never packed itself.
+//!
+//! ```toml
+//! exclude = ["*.psd", "drafts/**"]
+//!
+//! [files."img/cat.png"]
+//! key = "/.hidden/cat.png"
+//! aliases = ["/kitten.png"]
+//!
+//! [files."entry.html"]
+//! content_type = "html"
+//! ```
+
+use std::collections::{BTreeMap, HashMap, LinkedList};
 use std::fs;
 use std::path::{Path, PathBuf};
 
-use crate::builder::data_loader::{DataLoader, ZdbRecord};
-use crate::utils::io_utils::{scan_dir, windows_path_to_unix_path};
-use crate::utils::progress_report::{ProgressReportFn, ProgressState};
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use serde::{Deserialize, Serialize};
+
+use crate::builder::build_report::BuildWarning;
+use crate::builder::data_loader::{DataLoader, SourceMetadata, ZdbRecord};
+use crate::storage::meta_unit::{ContentType, ZdbVersion};
+use crate::utils::io_utils::{io_thread_count, parallel_map, scan_dir_with_options, windows_path_to_unix_path, ScanOptions};
+use crate::utils::mdd_key;
+use crate::utils::progress_report::{ProgressOptions, ProgressState};
 use crate::{Result, ZdbError};
 
+/// File names of the manifest, looked up in this order.
+pub const MANIFEST_FILE_NAMES: &[&str] = &[".manifest.json", ".manifest.toml"];
+
+/// Per-file settings of a directory manifest.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ManifestFileEntry {
+    /// Key used instead of the one derived from the file path
+    pub key: Option<String>,
+    /// Additional keys that resolve to the same file
+    pub aliases: Vec<String>,
+    /// Content type of the file, `None` uses the content type of the dictionary
+    pub content_type: Option<String>,
+}
+
+/// Manifest describing how the files of a directory are packed.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DirManifest {
+    /// Glob patterns, matched against relative paths with `/` separators
+    pub exclude: Vec<String>,
+    /// Per-file settings keyed by relative path with `/` separators
+    pub files: BTreeMap<String, ManifestFileEntry>,
+}
+
+impl DirManifest {
+    /// Parses a manifest in JSON format.
+    pub fn from_json(text: &str) -> Result<Self> {
+        Ok(serde_json::from_str(text)?)
+    }
+
+    /// Parses a manifest in TOML format.
+    pub fn from_toml(text: &str) -> Result<Self> {
+        Ok(toml::from_str(text)?)
+    }
+
+    /// Loads the manifest from the root of a directory.
+    ///
+    /// # Returns
+    ///
+    /// Returns the manifest and its file name, or `None` if the directory has no manifest.
+    pub fn load(source_dir: &Path) -> Result<Option<(Self, &'static str)>> {
+        for &file_name in MANIFEST_FILE_NAMES {
+            let manifest_path = source_dir.join(file_name);
+            if !manifest_path.is_file() {
+                continue;
+            }
+            let text = fs::read_to_string(&manifest_path)?;
+            let manifest = if file_name.ends_with(".toml") {
+                Self::from_toml(&text)?
+            } else {
+                Self::from_json(&text)?
+            };
+            return Ok(Some((manifest, file_name)));
+        }
+        Ok(None)
+    }
+
+    /// Compiles the exclusion patterns.
+    pub fn exclude_set(&self) -> Result<GlobSet> {
+        glob_set(&self.exclude, "exclusion")
+    }
+}
+
+/// Selection of the files packed from a directory source, set in the builder config.
+///
+/// Applies in addition to the manifest, a file is packed if it matches an `include`
+/// pattern (or there are none) and no `exclude` pattern of either.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DirScanConfig {
+    /// Follow symbolic links (default: true), links are skipped otherwise
+    pub follow_symlinks: bool,
+    /// Skip files and directories whose name starts with a dot (default: false)
+    pub skip_hidden: bool,
+    /// Glob patterns of the files to pack, matched against relative paths with `/` separators
+    pub include: Vec<String>,
+    /// Glob patterns of the files not to pack, matched like `include`
+    pub exclude: Vec<String>,
+}
+
+impl Default for DirScanConfig {
+    fn default() -> Self {
+        Self { follow_symlinks: true, skip_hidden: false, include: Vec::new(), exclude: Vec::new() }
+    }
+}
+
+impl DirScanConfig {
+    /// Compiles the inclusion patterns, `None` if all files are included.
+    pub fn include_set(&self) -> Result<Option<GlobSet>> {
+        if self.include.is_empty() {
+            return Ok(None);
+        }
+        glob_set(&self.include, "inclusion").map(Some)
+    }
+
+    /// Compiles the exclusion patterns.
+    pub fn exclude_set(&self) -> Result<GlobSet> {
+        glob_set(&self.exclude, "exclusion")
+    }
+}
+
+fn glob_set(patterns: &[String], kind: &str) -> Result<GlobSet> {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        let glob = Glob::new(pattern)
+            .map_err(|e| ZdbError::invalid_parameter(format!("Invalid {} pattern '{}': {}", kind, pattern, e)))?;
+        builder.add(glob);
+    }
+    builder.build()
+        .map_err(|e| ZdbError::invalid_parameter(format!("Invalid {} patterns: {}", kind, e)))
+}
+
+/// Number of files whose sizes are queried together while scanning.
+const SCAN_BATCH_SIZE: usize = 1024;
+/// Number of files read ahead per IO thread while loading content.
+const PREFETCH_FILES_PER_THREAD: usize = 16;
+
 /// DataDirLoader is a data loader that loads data from a directory.
+///
+/// File sizes and contents are read on several threads, see [`BuilderConfig::io_threads`](crate::builder::BuilderConfig::io_threads).
+/// Files are ordered by path, so the records don't depend on the order of the directory listing.
 pub struct DataDirLoader{
-    source_dir: String,
+    /// Paths of the packed files, indexed by the position of the records.
+    /// Kept as `PathBuf` since the lossy path in `ZdbRecord::content` may not be openable.
+    file_paths: Vec<PathBuf>,
+    /// Warnings raised while scanning the directory
+    pub warnings: Vec<BuildWarning>,
+    io_threads: usize,
+    /// Positions of the records in the order their content is loaded, see `DataLoader::prepare`
+    load_order: Vec<u64>,
+    /// Index into `load_order` of the first record not read ahead yet
+    next_load: usize,
+    /// Contents read ahead, by position
+    prefetched: HashMap<u64, Vec<u8>>,
+    /// Records not handed out by `records` yet
+    records: Vec<ZdbRecord>,
 }
 
 impl DataLoader for DataDirLoader{
+    fn metadata(&mut self) -> SourceMetadata {
+        SourceMetadata { warnings: std::mem::take(&mut self.warnings), ..Default::default() }
+    }
+
+    fn records(&mut self) -> Box<dyn Iterator<Item = Result<ZdbRecord>> + '_> {
+        Box::new(std::mem::take(&mut self.records).into_iter().map(Ok))
+    }
+
     fn load_data(&mut self, entry: &ZdbRecord) -> Result<Vec<u8>> {
-        let file_path = Path::new(&self.source_dir).join(&entry.content);
-        let data = fs::read(&file_path)?;
+        if let Some(data) = self.prefetched.remove(&entry.position) {
+            return Ok(data);
+        }
+        if self.io_threads > 1 {
+            self.read_ahead(entry.position)?;
+            if let Some(data) = self.prefetched.remove(&entry.position) {
+                return Ok(data);
+            }
+        }
+        let data = fs::read(self.file_path(entry.position)?)?;
         Ok(data)
     }
+
+    fn prepare(&mut self, entries: &[ZdbRecord]) -> Result<()> {
+        self.load_order = entries.iter()
+            .filter(|entry| entry.union_members.is_empty())
+            .map(|entry| entry.position)
+            .collect();
+        self.next_load = 0;
+        self.prefetched.clear();
+        Ok(())
+    }
 }
 
 
 
 impl DataDirLoader{
-    pub fn new(source_dir: &str, prog_rpt: Option<ProgressReportFn>) -> Result<(Self, Vec<ZdbRecord>)> {
+    /// Scans a directory for the files to pack.
+    ///
+    /// # Arguments
+    ///
+    /// * `source_dir` - Directory to pack, with an optional manifest in its root
+    /// * `scan_config` - Selection of the files to pack
+    /// * `io_threads` - Number of threads reading the files, `0` uses the number of CPUs
+    /// * `progress` - Optional progress reporter callback function, or [`ProgressOptions`] to report every file.
+    ///   Progress is reported in files, and in bytes of the files packed so far, the total size isn't known.
+    ///
+    /// # Returns
+    ///
+    /// Returns the loader, which hands out a record per key ordered by the path of the files, see [`DataLoader::records`].
+    pub fn new(source_dir: &str, scan_config: &DirScanConfig, io_threads: usize, progress: impl Into<ProgressOptions>) -> Result<Self> {
          // Scan for all files in the directory
          let dir_path = Path::new(&source_dir);
          let mut files = LinkedList::<PathBuf>::new();
          let pattern = regex::Regex::new(r".*").unwrap(); // Match all files
-         scan_dir(&dir_path, &pattern, true, &mut files)?; // recursive scan
-         
+         let scan_options = ScanOptions {
+             recursive: true,
+             follow_links: scan_config.follow_symlinks,
+             skip_hidden: scan_config.skip_hidden,
+         };
+         scan_dir_with_options(&dir_path, &pattern, &scan_options, &mut files)?;
+         // The order of the listing depends on the file system, sorting keeps the records reproducible
+         let mut files: Vec<PathBuf> = files.into_iter().collect();
+         files.sort();
+         let io_threads = io_thread_count(io_threads);
+
          log::debug!("Found {} files to pack", files.len());
-         let mut progress_state = ProgressState::new("DataDirLoader::new", files.len() as u64, 5, prog_rpt);
+         let mut progress_state = ProgressState::with_options("DataDirLoader::new", "Scanning source directory", files.len() as u64, 5, progress.into());
 
          let base_dir = dir_path.canonicalize()?;
+         let (manifest, manifest_file_name) = match DirManifest::load(&base_dir)? {
+             Some((manifest, file_name)) => (manifest, Some(file_name)),
+             None => (DirManifest::default(), None),
+         };
+         let exclude_set = manifest.exclude_set()?;
+         let config_exclude_set = scan_config.exclude_set()?;
+         let include_set = scan_config.include_set()?;
+         let mut unused_file_entries: Vec<&String> = manifest.files.keys().collect();
+
          let mut entry_records = Vec::<ZdbRecord>::with_capacity(files.len());
-         for (index, file_path) in files.iter().enumerate() {
-             let relative_path = file_path.strip_prefix(&base_dir)
-                 .map_err(|_| ZdbError::invalid_data_format(format!("Failed to create relative path: {}", file_path.display())))?;
-             
-             // Use forward slashes for MDD keys and prefix with backslash
-             let key = format!("/{}", windows_path_to_unix_path(&relative_path.to_string_lossy()));
-             
-            let record = ZdbRecord {
-                key: key.clone(),
-                content_offset_in_source: 0, // Will be set later during building
-                position: index as u64,
-                content: file_path.to_string_lossy().to_string(), // Store file path in content field
-                content_len: fs::metadata(&file_path)?.len(),
-                line_no: 0, //unused for mdd
-            };
-             entry_records.push(record);
-            if progress_state.report(index as u64) {
-                return Err(ZdbError::user_interrupted());
-            }
+         let mut file_paths = Vec::<PathBuf>::with_capacity(files.len());
+         let mut warnings = Vec::new();
+         let mut processed = 0;
+         let mut processed_bytes = 0;
+         for batch in files.chunks(SCAN_BATCH_SIZE) {
+             // Querying the sizes dominates scanning, especially on network file systems
+             let content_lens = parallel_map(batch, io_threads, |file_path| fs::metadata(file_path).map(|metadata| metadata.len()));
+             for (file_path, content_len) in batch.iter().zip(content_lens) {
+                 processed += 1;
+                 let relative_path = file_path.strip_prefix(&base_dir)
+                     .map_err(|_| ZdbError::invalid_data_format(format!("Failed to create relative path: {}", file_path.display())))?;
+                 let is_lossy = relative_path.to_str().is_none();
+                 let relative_path = windows_path_to_unix_path(&relative_path.to_string_lossy());
+                 if Some(relative_path.as_str()) == manifest_file_name || exclude_set.is_match(&relative_path)
+                     || config_exclude_set.is_match(&relative_path)
+                     || include_set.as_ref().is_some_and(|include_set| !include_set.is_match(&relative_path)) {
+                     continue;
+                 }
+
+                 let file_entry = manifest.files.get(&relative_path);
+                 if file_entry.is_some() {
+                     unused_file_entries.retain(|path| **path != relative_path);
+                 }
+                 let file_entry = file_entry.cloned().unwrap_or_default();
+                 let content_type = file_entry.content_type.as_deref().map(ContentType::from_str).transpose()?;
+
+                 let key = file_entry.key.unwrap_or_else(|| mdd_key::normalize(&relative_path, ZdbVersion::V3));
+                 if is_lossy {
+                     let warning = BuildWarning::LossyPathKey { key: key.clone(), path: file_path.display().to_string() };
+                     warning.log();
+                     warnings.push(warning);
+                 }
+                 let content_len = content_len?;
+                 processed_bytes += content_len;
+                 if progress_state.report_item(processed, processed_bytes, &key) {
+                     return Err(ZdbError::user_interrupted());
+                 }
+                 for key in std::iter::once(key).chain(file_entry.aliases) {
+                     entry_records.push(ZdbRecord {
+                         key,
+                         content_offset_in_source: 0, // Will be set later during building
+                         position: file_paths.len() as u64, // Index into file_paths
+                         content: file_path.to_string_lossy().to_string(), // Store file path in content field
+                         content_len,
+                         line_no: 0, //unused for mdd
+                         content_type: content_type.clone(),
+                         union_members: Vec::new(),
+                         no_compress: false,
+                     });
+                 }
+                 file_paths.push(file_path.clone());
+             }
+         }
+         for path in unused_file_entries {
+             let warning = BuildWarning::UnusedManifestEntry { path: path.clone() };
+             warning.log();
+             warnings.push(warning);
          }
-         Ok((DataDirLoader{
-            source_dir: source_dir.to_string(),
-         }, entry_records))
+         Ok(DataDirLoader{
+            file_paths,
+            warnings,
+            io_threads,
+            load_order: Vec::new(),
+            next_load: 0,
+            prefetched: HashMap::new(),
+            records: entry_records,
+         })
+    }
+
+    fn file_path(&self, position: u64) -> Result<&PathBuf> {
+        self.file_paths.get(position as usize)
+            .ok_or_else(|| ZdbError::invalid_parameter(format!("Invalid record position: {}", position)))
+    }
+
+    /// Reads the files of the records loaded next in parallel, starting at `position`.
+    ///
+    /// Does nothing if `position` isn't among the records still to be loaded.
+    fn read_ahead(&mut self, position: u64) -> Result<()> {
+        let Some(start) = self.load_order[self.next_load..].iter().position(|&pos| pos == position) else {
+            return Ok(());
+        };
+        let start = self.next_load + start;
+        let end = (start + self.io_threads * PREFETCH_FILES_PER_THREAD).min(self.load_order.len());
+        let mut positions = self.load_order[start..end].to_vec();
+        positions.sort_unstable();
+        positions.dedup();
+        let paths = positions.iter().map(|&pos| self.file_path(pos).cloned()).collect::<Result<Vec<PathBuf>>>()?;
+        let contents = parallel_map(&paths, self.io_threads, |path| fs::read(path));
+        self.prefetched.clear();
+        for (pos, content) in positions.into_iter().zip(contents) {
+            self.prefetched.insert(pos, content?);
+        }
+        self.next_load = end;
+        Ok(())
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_manifest_formats() {
+        let json = r#"{"exclude": ["*.psd"], "files": {"a/b.png": {"key": "/x/.b.png", "aliases": ["/c.png"], "content_type": "binary"}}}"#;
+        let toml = r#"
+exclude = ["*.psd"]
+[files."a/b.png"]
+key = "/x/.b.png"
+aliases = ["/c.png"]
+content_type = "binary"
+"#;
+        let manifest = DirManifest::from_json(json).unwrap();
+        assert_eq!(manifest, DirManifest::from_toml(toml).unwrap());
+        assert_eq!(manifest.files["a/b.png"].key.as_deref(), Some("/x/.b.png"));
+
+        let exclude_set = manifest.exclude_set().unwrap();
+        assert!(exclude_set.is_match("img/layer.psd"));
+        assert!(!exclude_set.is_match("img/layer.png"));
+    }
+}
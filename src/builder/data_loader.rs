@@ -2,13 +2,25 @@
 //!
 //! This module provides the core data structures and traits for loading
 //! dictionary entries from various sources during ZDB file construction.
+//! Downstream crates add source formats by implementing [`DataLoader`] and
+//! passing it to [`ZDBBuilder::build`](crate::builder::ZDBBuilder::build).
 
-use crate::Result;
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::BufReader;
 
-/// Maximum length of a dictionary keyword in bytes.
+use serde::{Deserialize, Serialize};
+
+use crate::builder::build_report::BuildWarning;
+use crate::{Result, ZdbError};
+use crate::readers::zdb_reader::ZdbReader;
+use crate::storage::key_block::EntryNo;
+use crate::storage::meta_unit::ContentType;
+
+/// Default maximum length of a dictionary keyword in bytes, see [`BuilderConfig::max_key_length`](crate::builder::BuilderConfig::max_key_length).
 pub const ZDB_MAX_KEYWORD_LENGTH: usize = 255;
 
-/// Maximum length of a single dictionary entry (64MB).
+/// Default maximum length of a single dictionary entry (64MB), see [`BuilderConfig::max_content_size`](crate::builder::BuilderConfig::max_content_size).
 pub const MAX_ENTRY_LEN: usize = 64 * 1024 * 1024;
 
 /// Represents a single dictionary record during the build process.
@@ -28,31 +40,115 @@ pub struct ZdbRecord {
     pub content: String,
     /// Length of the content in bytes
     pub content_len: u64,
-    /// Line number in the source file (for text-based sources)
+    /// Line number of the key in the source file (for text-based sources), 0 if unknown
     pub line_no: u64,
+    /// Content type of this entry, `None` uses the content type of the dictionary
+    pub content_type: Option<ContentType>,
+    /// Entry numbers of the members if this is a union entry created by the builder, empty otherwise
+    pub union_members: Vec<EntryNo>,
+    /// Store the content without compression, e.g. for already compressed media
+    pub no_compress: bool,
+}
+
+/// What the builder does with an entry whose content can't be loaded, e.g. a corrupt block of a ZDB source.
+///
+/// Configured as `"Fail"`, `"Skip"` or `{"Placeholder": "<p>Missing</p>"}`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RecordErrorPolicy {
+    /// Abort the build with the error of the entry
+    #[default]
+    Fail,
+    /// Leave the entry out of the output and record a [`BuildWarning::SkippedEntry`](crate::builder::BuildWarning::SkippedEntry)
+    ///
+    /// If the build has union entries, entries are kept with empty content instead, since
+    /// union entries refer to the entries following them by number.
+    Skip,
+    /// Store the given text as the content and record a [`BuildWarning::PlaceholderContent`](crate::builder::BuildWarning::PlaceholderContent)
+    Placeholder(String),
+}
+
+/// Settings of the dictionary found in the source, see [`DataLoader::metadata`].
+#[derive(Debug, Clone, Default)]
+pub struct SourceMetadata {
+    /// Sorting locale of the source, used if the config has none, empty if unknown
+    pub locale_id: String,
+    /// Labels and their expansions, replaced by the labels file of the config if there is one
+    pub labels: BTreeMap<String, String>,
+    /// Classes left out of entry previews, replaced by those of the config if it has any
+    pub preview_skip_classes: Vec<String>,
+    /// Warnings raised while reading the source, added to the build report
+    pub warnings: Vec<BuildWarning>,
 }
 
 /// Common interface for loading dictionary entry data from various sources.
 ///
 /// Implementations of this trait handle loading entry content from different
-/// source formats (MDX files, text files, directories, etc.).
+/// source formats (MDX files, text files, directories, etc.). The trait is
+/// object-safe, so loaders for formats of other crates can be passed to
+/// [`ZDBBuilder::build`](crate::builder::ZDBBuilder::build) as `Box<dyn DataLoader>`.
+///
+/// The builder consumes [`records`](Self::records) and calls [`metadata`](Self::metadata) once,
+/// then [`prepare`](Self::prepare), then [`load_data`](Self::load_data) for each entry.
 ///
 /// # Examples
 ///
 /// ```no_run
+/// use mdx::builder::{BuilderConfig, ZDBBuilder};
 /// use mdx::builder::data_loader::{DataLoader, ZdbRecord};
 /// use mdx::Result;
 ///
-/// struct MyDataLoader;
+/// struct MyDataLoader {
+///     entries: Vec<(String, String)>,
+/// }
 ///
 /// impl DataLoader for MyDataLoader {
+///     fn records(&mut self) -> Box<dyn Iterator<Item = Result<ZdbRecord>> + '_> {
+///         Box::new(self.entries.iter().enumerate().map(|(position, (key, content))| Ok(ZdbRecord {
+///             key: key.clone(),
+///             position: position as u64,
+///             content_len: content.len() as u64,
+///             ..Default::default()
+///         })))
+///     }
+///
 ///     fn load_data(&mut self, entry: &ZdbRecord) -> Result<Vec<u8>> {
 ///         // Load and return the entry content
-///         Ok(entry.content.as_bytes().to_vec())
+///         Ok(self.entries[entry.position as usize].1.as_bytes().to_vec())
 ///     }
 /// }
+///
+/// # fn main() -> Result<()> {
+/// let loader = MyDataLoader { entries: vec![("apple".to_string(), "<p>A fruit</p>".to_string())] };
+/// let mut config = BuilderConfig::default();
+/// config.output_file = "fruit.mdx".to_string();
+/// ZDBBuilder::build(&config, Box::new(loader), None)?;
+/// # Ok(())
+/// # }
 /// ```
 pub trait DataLoader {
+    /// Settings of the dictionary found in the source.
+    ///
+    /// Called once after [`records`](Self::records) is consumed, so warnings raised while
+    /// reading the records can be included. The default implementation returns empty metadata.
+    fn metadata(&mut self) -> SourceMetadata {
+        SourceMetadata::default()
+    }
+
+    /// The records of all entries of the source, in any order.
+    ///
+    /// Records are consumed one at a time, so a loader can read them while the iterator
+    /// advances instead of collecting them first, the builder sorts them.
+    /// `position` and `content` of a record are up to the loader, they identify the
+    /// content for [`load_data`](Self::load_data). `content_len` is used for block
+    /// sizes and progress reporting.
+    ///
+    /// The default implementation yields an error, it's only needed by
+    /// [`ZDBBuilder::build`](crate::builder::ZDBBuilder::build), records can also be passed to
+    /// [`ZDBBuilder::build_records_to_writer`](crate::builder::ZDBBuilder::build_records_to_writer).
+    fn records(&mut self) -> Box<dyn Iterator<Item = Result<ZdbRecord>> + '_> {
+        Box::new(std::iter::once(Err(ZdbError::invalid_parameter("The data loader doesn't list its records"))))
+    }
+
     /// Loads the content data for a given dictionary entry.
     ///
     /// # Arguments
@@ -67,9 +163,87 @@ pub trait DataLoader {
     ///
     /// Returns an error if the data cannot be loaded from the source.
     fn load_data(&mut self, entry: &ZdbRecord) -> Result<Vec<u8>>;
+
+    /// Called once before the content is loaded, with the entries in the order
+    /// [`load_data`](Self::load_data) will be called for them.
+    ///
+    /// Loaders can use it to read ahead, the default implementation does nothing.
+    fn prepare(&mut self, _entries: &[ZdbRecord]) -> Result<()> {
+        Ok(())
+    }
+
+    /// The ZDB file the entries are loaded from, if their content is loaded as stored.
+    ///
+    /// The builder then copies the compressed content blocks of this file instead of
+    /// loading and recompressing the content, as long as the entries keep their order,
+    /// see [`ZDBBuilder::copy_content_unit`](crate::builder::ZDBBuilder::copy_content_unit).
+    /// The default implementation returns `None`.
+    fn stored_source(&mut self) -> Option<&mut ZdbReader<BufReader<File>>> {
+        None
+    }
 }
 
+impl<T: DataLoader + ?Sized> DataLoader for Box<T> {
+    fn metadata(&mut self) -> SourceMetadata {
+        (**self).metadata()
+    }
+
+    fn records(&mut self) -> Box<dyn Iterator<Item = Result<ZdbRecord>> + '_> {
+        (**self).records()
+    }
 
+    fn load_data(&mut self, entry: &ZdbRecord) -> Result<Vec<u8>> {
+        (**self).load_data(entry)
+    }
 
+    fn prepare(&mut self, entries: &[ZdbRecord]) -> Result<()> {
+        (**self).prepare(entries)
+    }
+
+    fn stored_source(&mut self) -> Option<&mut ZdbReader<BufReader<File>>> {
+        (**self).stored_source()
+    }
+}
 
+/// Receives every entry as its content is written, for side outputs built in the same pass.
+///
+/// Word lists, frequency counts or an external search index can be produced while the
+/// dictionary is built instead of reading the built file afterwards, see
+/// [`ZDBBuilder::build_with_sink`](crate::builder::ZDBBuilder::build_with_sink).
+/// Closures taking a record and its content implement this trait.
+///
+/// # Examples
+///
+/// ```no_run
+/// use std::io::Write;
+/// use mdx::builder::{BuilderConfig, ZDBBuilder};
+///
+/// # fn main() -> mdx::Result<()> {
+/// let config = BuilderConfig::default();
+/// let mut word_list = std::fs::File::create("words.txt")?;
+/// ZDBBuilder::build_with_sink(&config, &mut |entry: &mdx::builder::ZdbRecord, _content: &[u8]| {
+///     writeln!(word_list, "{}", entry.key)?;
+///     Ok(())
+/// }, None)?;
+/// # Ok(())
+/// # }
+/// ```
+pub trait EntrySink {
+    /// Called for each entry in key order, union entries included.
+    ///
+    /// # Arguments
+    ///
+    /// * `entry` - The record of the entry
+    /// * `content` - The content as stored, after script filtering and in the dictionary's encoding
+    ///
+    /// # Errors
+    ///
+    /// An error aborts the build.
+    fn write_entry(&mut self, entry: &ZdbRecord, content: &[u8]) -> Result<()>;
+}
 
+impl<F: FnMut(&ZdbRecord, &[u8]) -> Result<()>> EntrySink for F {
+    fn write_entry(&mut self, entry: &ZdbRecord, content: &[u8]) -> Result<()> {
+        self(entry, content)
+    }
+}
@@ -0,0 +1,81 @@
+//! Loader of the supplemental TSV file with the metadata of the entries.
+//!
+//! Each line holds a key and its metadata, separated by tabs, shown as `\t` here:
+//!
+//! ```text
+//! # key\tfrequency rank\tpart of speech\ttags
+//! run\t120\tverb\tcommon
+//! runner\t4200\tnoun
+//! ```
+//!
+//! Only the key is required. The frequency rank is a number, 1 for the most frequent
+//! word. The part of speech is a name or abbreviation accepted by
+//! [`PartOfSpeech::from_name`]. Tags are comma-separated names, at most
+//! [`ENTRY_META_MAX_TAGS`] distinct ones per file. Lines starting with `#` and blank
+//! lines are skipped, and a later line for the same key replaces an earlier one.
+//! The metadata applies to every entry with the key.
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+
+use crate::storage::entry_meta_unit::{EntryMetaExt, PartOfSpeech, ENTRY_META_MAX_TAGS};
+use crate::{Result, ZdbError};
+
+/// Metadata of the entries by key, read from a TSV file.
+#[derive(Debug, Clone, Default)]
+pub struct EntryMetaTable {
+    /// Metadata and line number of every key
+    pub rows: HashMap<String, (EntryMetaExt, u64)>,
+    /// Names of the tags in the order of their bits
+    pub tag_names: Vec<String>,
+}
+
+impl EntryMetaTable {
+    /// Reads the table from a TSV file, see the [module documentation](self).
+    ///
+    /// # Errors
+    ///
+    /// Returns an `InvalidDataFormat` error naming the line if a field can't be parsed
+    /// or the file has too many distinct tags.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let mut table = Self::default();
+        let reader = BufReader::new(File::open(path.as_ref())?);
+        for (n, line) in reader.lines().enumerate() {
+            let line = line?;
+            let line = if n == 0 { line.trim_start_matches('\u{FEFF}') } else { line.as_str() };
+            if line.trim().is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let line_no = n as u64 + 1;
+            let mut fields = line.split('\t');
+            let key = fields.next().unwrap_or_default().trim();
+            if key.is_empty() {
+                return Err(ZdbError::invalid_data_format(format!("Entry metadata line {} has no key", line_no)));
+            }
+            let frequency_rank = match fields.next().map(str::trim).unwrap_or_default() {
+                "" => 0,
+                rank => rank.parse::<u32>()
+                    .map_err(|_| ZdbError::invalid_data_format(format!("Invalid frequency rank '{}' at entry metadata line {}", rank, line_no)))?,
+            };
+            let pos_name = fields.next().unwrap_or_default();
+            let pos = PartOfSpeech::from_name(pos_name)
+                .ok_or_else(|| ZdbError::invalid_data_format(format!("Unknown part of speech '{}' at entry metadata line {}", pos_name, line_no)))?;
+            let mut tags = 0u32;
+            for name in fields.next().unwrap_or_default().split(',').map(str::trim).filter(|name| !name.is_empty()) {
+                let bit = match table.tag_names.iter().position(|tag_name| tag_name == name) {
+                    Some(bit) => bit,
+                    None if table.tag_names.len() < ENTRY_META_MAX_TAGS => {
+                        table.tag_names.push(name.to_string());
+                        table.tag_names.len() - 1
+                    }
+                    None => return Err(ZdbError::invalid_data_format(format!("More than {} distinct tags at entry metadata line {}", ENTRY_META_MAX_TAGS, line_no))),
+                };
+                tags |= 1 << bit;
+            }
+            table.rows.insert(key.to_string(), (EntryMetaExt { tags, frequency_rank, pos }, line_no));
+        }
+        Ok(table)
+    }
+}
@@ -5,30 +5,142 @@
 
 use std::fs;
 use std::io;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use tantivy::directory::Directory;
 use tantivy::doc;
 use tantivy::schema::{Field, Schema, INDEXED, STORED, TEXT};
+use tantivy::tokenizer::TokenStream;
 use tantivy::{Index, TantivyDocument};
 use zip::write::FileOptions;
 use zip::{CompressionMethod, ZipWriter};
 
 use crate::storage::key_block::EntryNo;
 use crate::readers::mdx_reader::MdxReader;
+use crate::utils::atomic_output::AtomicOutput;
 use crate::utils::progress_report::{ProgressReportFn, ProgressState};
+use crate::utils::romanize::Romanization;
 use crate::{Result, ZdbError};
 
 const MDICT_INDEX_EXT: &str = "idx";
 
+/// Version of the index layout, raised whenever fields are added, removed or change their options.
+pub const FTS_SCHEMA_VERSION: u32 = 1;
+/// Name of the metadata document stored with the index files, see [`FtsIndexMetadata`].
+pub const FTS_METADATA_FILE: &str = "mdx_fts.json";
+/// Fields every index of the current schema version has.
+const FTS_FIELDS: [&str; 3] = ["entry_no", "key", "content"];
+/// Optional field holding the romanized keys, see [`FtsIndexOptions::romanization`].
+pub const FTS_ROMANIZED_KEY_FIELD: &str = "key_romanized";
+/// Optional field holding the words of multi-word keys, see [`FtsIndexOptions::headword_tokens`].
+pub const FTS_KEY_TOKENS_FIELD: &str = "key_tokens";
+
+/// Options of building an FTS index, see [`make_index_with_options`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FtsIndexOptions {
+    /// Also indexes the romanized form of each key, so searches with
+    /// [`FtsSearchOptions::match_romanized`](crate::readers::fts_searcher::FtsSearchOptions::match_romanized)
+    /// find e.g. 双 by "shuang", see [`Romanization`]
+    pub romanization: Option<Romanization>,
+    /// Also indexes the words of multi-word keys, e.g. "kick the bucket", so idioms can be
+    /// found by any of their words with [`MdxReader::search_headword_tokens`]
+    pub headword_tokens: bool,
+}
+
+/// Metadata document stored inside the index, identifying the schema and the indexed dictionary.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct FtsIndexMetadata {
+    /// Schema version the index was built with, see [`FTS_SCHEMA_VERSION`]
+    pub schema_version: u32,
+    /// Number of entries of the dictionary when it was indexed
+    pub entry_count: u64,
+    /// UUID of the indexed dictionary, empty for indexes of older versions
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub dict_uuid: String,
+    /// Name and version of the library that built the index
+    pub generator: String,
+    /// Romanization of the keys stored in the index, if any
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub romanization: Option<Romanization>,
+    /// Whether the words of multi-word keys are indexed
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub headword_tokens: bool,
+}
+
+impl FtsIndexMetadata {
+    fn new(entry_count: u64, dict_uuid: String, options: &FtsIndexOptions) -> Self {
+        Self {
+            schema_version: FTS_SCHEMA_VERSION,
+            entry_count,
+            dict_uuid,
+            generator: format!("{} {}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION")),
+            romanization: options.romanization,
+            headword_tokens: options.headword_tokens,
+        }
+    }
+
+    /// Reads the metadata document of an index and checks it can be searched by this version.
+    ///
+    /// # Arguments
+    ///
+    /// * `index` - The opened index
+    /// * `entry_count` - Number of entries of the dictionary the index belongs to
+    ///
+    /// # Errors
+    ///
+    /// Returns a `FtsIndexOutdated` error if the index has no metadata, e.g. it was built by
+    /// another tool or an older version, has another schema version or fields, or was built
+    /// for a dictionary with a different number of entries.
+    pub fn validate(index: &Index, entry_count: u64) -> Result<Self> {
+        let metadata = Self::read(index)?;
+        if metadata.schema_version != FTS_SCHEMA_VERSION {
+            return Err(ZdbError::fts_index_outdated(format!(
+                "schema version {} is not supported, expected {}", metadata.schema_version, FTS_SCHEMA_VERSION
+            )));
+        }
+        let schema = index.schema();
+        if let Some(field) = FTS_FIELDS.iter().find(|field| schema.get_field(field).is_err()) {
+            return Err(ZdbError::fts_index_outdated(format!("field '{}' is missing", field)));
+        }
+        if metadata.romanization.is_some() && schema.get_field(FTS_ROMANIZED_KEY_FIELD).is_err() {
+            return Err(ZdbError::fts_index_outdated(format!("field '{}' is missing", FTS_ROMANIZED_KEY_FIELD)));
+        }
+        if metadata.headword_tokens && schema.get_field(FTS_KEY_TOKENS_FIELD).is_err() {
+            return Err(ZdbError::fts_index_outdated(format!("field '{}' is missing", FTS_KEY_TOKENS_FIELD)));
+        }
+        if metadata.entry_count != entry_count {
+            return Err(ZdbError::fts_index_outdated(format!(
+                "the index has {} entries but the dictionary has {}", metadata.entry_count, entry_count
+            )));
+        }
+        Ok(metadata)
+    }
+
+    /// Reads the metadata document of an index without checking it.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `FtsIndexOutdated` error if the index has no metadata or it can't be parsed.
+    pub fn read(index: &Index) -> Result<Self> {
+        let data = index.directory().atomic_read(Path::new(FTS_METADATA_FILE))
+            .map_err(|_| ZdbError::fts_index_outdated("the index has no schema metadata, it was built by another tool or an older version"))?;
+        serde_json::from_slice(&data)
+            .map_err(|e| ZdbError::fts_index_outdated(format!("invalid schema metadata: {}", e)))
+    }
+}
+
 pub struct IndexFields {
     pub entry_no: Field,
     pub key: Field,
     pub content: Field,
+    pub key_romanized: Option<Field>,
+    pub key_tokens: Option<Field>,
 }
 
 /// Create a new Tantivy index for MDX full-text search
-fn init_index(index_dir_path: &PathBuf) -> Result<(Index, IndexFields)> {    
+fn init_index(index_dir_path: &PathBuf, options: &FtsIndexOptions) -> Result<(Index, IndexFields)> {    
     if index_dir_path.exists() {
         fs::remove_dir_all(&index_dir_path)?;
     }
@@ -47,6 +159,12 @@ fn init_index(index_dir_path: &PathBuf) -> Result<(Index, IndexFields)> {
     
     // Add content field (text, indexed only - no storage since it's HTML content)
     let content = schema_builder.add_text_field("content", TEXT);
+
+    // Add romanized key field (text, indexed only - the key itself is stored)
+    let key_romanized = options.romanization.map(|_| schema_builder.add_text_field(FTS_ROMANIZED_KEY_FIELD, TEXT));
+
+    // Add key tokens field (text, indexed only - filled for multi-word keys)
+    let key_tokens = options.headword_tokens.then(|| schema_builder.add_text_field(FTS_KEY_TOKENS_FIELD, TEXT));
     
     let schema = schema_builder.build();
     
@@ -57,6 +175,8 @@ fn init_index(index_dir_path: &PathBuf) -> Result<(Index, IndexFields)> {
         entry_no,
         key,
         content,
+        key_romanized,
+        key_tokens,
     };
     
     Ok((index, index_fields))
@@ -64,6 +184,25 @@ fn init_index(index_dir_path: &PathBuf) -> Result<(Index, IndexFields)> {
 
 /// Index an MDX database file into a Tantivy index using MdxReader
 pub fn make_index(file_path: &PathBuf, prog_rpt: Option<ProgressReportFn>) -> Result<()> {
+    make_index_with_options(file_path, &FtsIndexOptions::default(), prog_rpt)
+}
+
+/// Indexes an MDX database file like [`make_index`], with extra fields selected by `options`.
+///
+/// # Arguments
+///
+/// * `file_path` - Path of the MDX file, the index is written next to it
+/// * `options` - Options of the index, e.g. romanized keys
+/// * `prog_rpt` - Optional progress callback, may cancel the build
+///
+/// # Errors
+///
+/// Returns an `UnsupportedFeature` error if the romanization isn't supported by this
+/// build, see [`Romanization::check_supported`], or an error if indexing fails.
+pub fn make_index_with_options(file_path: &PathBuf, options: &FtsIndexOptions, prog_rpt: Option<ProgressReportFn>) -> Result<()> {
+    if let Some(romanization) = options.romanization {
+        romanization.check_supported()?;
+    }
     info!("Indexing MDX file: {}", file_path.display());
     
     // Create URL from file path and open with MdxReader
@@ -77,9 +216,23 @@ pub fn make_index(file_path: &PathBuf, prog_rpt: Option<ProgressReportFn>) -> Re
     
     let mut index_dir_path = file_path.clone();
     index_dir_path.set_extension("");
-    
+
+    let result = build_index(&mut mdx_reader, &index_dir_path, options, prog_rpt);
+    if result.is_err() && index_dir_path.exists() {
+        // Don't leave the work directory behind on error or user interrupt
+        if let Err(e) = fs::remove_dir_all(&index_dir_path) {
+            warn!("Failed to remove index directory {}: {}", index_dir_path.display(), e);
+        }
+    }
+    result
+}
+
+/// Builds, merges and packs the index in `index_dir_path`.
+fn build_index(mdx_reader: &mut MdxReader, index_dir_path: &PathBuf, options: &FtsIndexOptions, prog_rpt: Option<ProgressReportFn>) -> Result<()> {
+    let entry_count = mdx_reader.get_entry_count();
+
     // Create the Tantivy index  
-    let (index, index_fields) = init_index(&index_dir_path)?;
+    let (index, index_fields) = init_index(index_dir_path, options)?;
     let mut index_writer = index.writer(50_000_000)
         .map_err(|e| ZdbError::general_error(format!("Failed to create index writer: {}", e)))?;
     
@@ -87,11 +240,24 @@ pub fn make_index(file_path: &PathBuf, prog_rpt: Option<ProgressReportFn>) -> Re
     
     // Create progress state with 10% report interval
     let mut progress_state = ProgressState::new("FtsIndexBuilder::make_index", entry_count, 10, prog_rpt);
+    let mut key_tokenizer = match index_fields.key_tokens {
+        Some(field) => Some((field, index.tokenizer_for_field(field)
+            .map_err(|e| ZdbError::general_error(format!("Failed to get the tokenizer of field '{}': {}", FTS_KEY_TOKENS_FIELD, e)))?)),
+        None => None,
+    };
     
     // Index all entries
     for entry_no in 0..entry_count {
         // Get the index and content for this entry
         let key_index = mdx_reader.get_index(entry_no as EntryNo)?;
+        // Union entries repeat the contents of their members, which are indexed themselves
+        if mdx_reader.content_db.is_union_entry(&key_index)? {
+            if progress_state.report(entry_no) {
+                info!("Indexing cancelled by user");
+                return Err(ZdbError::user_interrupted());
+            }
+            continue;
+        }
         
         let result = mdx_reader.get_html(&key_index);
         if let Err(e) = result {
@@ -104,11 +270,22 @@ pub fn make_index(file_path: &PathBuf, prog_rpt: Option<ProgressReportFn>) -> Re
         let text_content = crate::utils::utils::extract_text_from_html(&html_content)?;
         
         // Create document and add to index
-        let doc = doc!(
+        let mut doc = doc!(
             index_fields.entry_no => entry_no,
             index_fields.key => key_index.key.clone(),
             index_fields.content => text_content,
         );
+        if let (Some(field), Some(romanization)) = (index_fields.key_romanized, options.romanization)
+            && let Some(romanized) = romanization.romanize(&key_index.key) {
+            doc.add_text(field, romanized);
+        }
+        if let Some((field, tokenizer)) = key_tokenizer.as_mut() {
+            let mut token_count = 0;
+            tokenizer.token_stream(&key_index.key).process(&mut |_| token_count += 1);
+            if token_count > 1 {
+                doc.add_text(*field, &key_index.key);
+            }
+        }
         
         index_writer.add_document(doc)
             .map_err(|e| ZdbError::general_error(format!("Failed to add document: {}", e)))?;
@@ -126,13 +303,14 @@ pub fn make_index(file_path: &PathBuf, prog_rpt: Option<ProgressReportFn>) -> Re
         .map_err(|e| ZdbError::general_error(format!("Failed to commit index: {}", e)))?;
     
     info!("Successfully indexed {} entries to Tantivy index", entry_count);
+    fs::write(index_dir_path.join(FTS_METADATA_FILE), serde_json::to_vec(&FtsIndexMetadata::new(entry_count, mdx_reader.content_db.meta.db_info.uuid.clone(), options))?)?;
     
     drop(index_writer); // Drop the index writer to release the file lock
         
     // Merge index segments
     info!("Merging index segments...");
     let mut progress_state = ProgressState::new("FtsIndexBuilder::merge_index", 1, 10, prog_rpt);
-    merge_index(&index_dir_path)?;
+    merge_index(index_dir_path)?;
     if progress_state.report(1) {
         info!("Merge index cancelled by user");
         return Err(ZdbError::user_interrupted());
@@ -141,7 +319,7 @@ pub fn make_index(file_path: &PathBuf, prog_rpt: Option<ProgressReportFn>) -> Re
     // Pack index into .idx file and remove source directory
     info!("Packing index into .{} file...", MDICT_INDEX_EXT);
     let mut progress_state = ProgressState::new("FtsIndexBuilder::pack_index", 1, 10, prog_rpt);
-    pack_index(&index_dir_path, true)?;
+    pack_index(index_dir_path, true)?;
     if progress_state.report(1) {
         info!("Pack index cancelled by user");
         return Err(ZdbError::user_interrupted());
@@ -195,7 +373,8 @@ pub fn pack_index(index_path: &PathBuf, remove_source: bool) -> Result<()> {
 
     info!("Creating ZIP .{} file: {}", MDICT_INDEX_EXT, zip_file_path);
 
-    let zip_file = fs::File::create(&zip_file_path)
+    // Written to a temporary file first, so a failure doesn't leave a truncated .idx file
+    let (output, zip_file) = AtomicOutput::create(&zip_file_path)
         .map_err(|e| ZdbError::general_error(format!("Failed to create output file: {}", e)))?;
     let mut zip = ZipWriter::new(zip_file);
     let options = FileOptions::<()>::default()
@@ -224,7 +403,10 @@ pub fn pack_index(index_path: &PathBuf, remove_source: bool) -> Result<()> {
         }
     }
 
-    zip.finish().map_err(|e| ZdbError::general_error(format!("Failed to finalize zip: {}", e)))?;
+    let zip_file = zip.finish().map_err(|e| ZdbError::general_error(format!("Failed to finalize zip: {}", e)))?;
+    zip_file.sync_all()?;
+    drop(zip_file);
+    output.commit()?;
     info!("Successfully packed index into ZIP (Stored) at: {}", zip_file_path);
 
     if remove_source {
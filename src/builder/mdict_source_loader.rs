@@ -3,15 +3,22 @@ use std::io::{BufRead, BufReader, Read, Seek, SeekFrom};
 
 use snafu::Backtrace;
 
-use crate::builder::data_loader::{DataLoader, ZdbRecord, MAX_ENTRY_LEN, ZDB_MAX_KEYWORD_LENGTH};
-use crate::utils::progress_report::{ProgressReportFn, ProgressState};
+use crate::builder::build_report::BuildWarning;
+use crate::builder::data_loader::{DataLoader, SourceMetadata, ZdbRecord};
+use crate::readers::mdx_reader::MdxReader;
+use crate::utils::progress_report::{ProgressOptions, ProgressState};
 use crate::{Result, ZdbError};
 
 
 pub struct MDictSourceLoader{
     pub source_file: String,
     input_reader: BufReader<File>,
-
+    /// Warnings raised while reading the entry list
+    pub warnings: Vec<BuildWarning>,
+    compact_stylesheet: Vec<(String, String)>,
+    progress_state: ProgressState,
+    /// Number of lines scanned so far
+    line_count: u64,
 }
 
 fn skip_utf8_bom(line: &str) -> &str {
@@ -25,106 +32,139 @@ fn is_text_end(line: &str) -> bool {
 }
 
 impl DataLoader for MDictSourceLoader{
+    fn metadata(&mut self) -> SourceMetadata {
+        SourceMetadata { warnings: std::mem::take(&mut self.warnings), ..Default::default() }
+    }
+
+    fn records(&mut self) -> Box<dyn Iterator<Item = Result<ZdbRecord>> + '_> {
+        // Scanning ends at the first error
+        let mut failed = false;
+        Box::new(std::iter::from_fn(move || {
+            if failed {
+                return None;
+            }
+            let record = self.next_record().transpose();
+            failed = matches!(record, Some(Err(_)));
+            record
+        }))
+    }
 
     fn load_data(&mut self, entry: &ZdbRecord) -> Result<Vec<u8>> {
-        let mut data = Vec::<u8>::with_capacity(entry.content_len as usize);
+        let mut data = vec![0u8; entry.content_len as usize];
         self.input_reader.seek(SeekFrom::Start(entry.position))?;
         self.input_reader.read_exact(&mut data)?;
-        Ok(data)
+        if self.compact_stylesheet.is_empty() {
+            Ok(data)
+        } else {
+            Ok(MdxReader::reformat(&String::from_utf8(data)?, &self.compact_stylesheet)?.into_bytes())
+        }
     }
 }
 
 
 
 impl MDictSourceLoader{
-    pub fn new(source_file:&str, prog_rpt: Option<ProgressReportFn>) -> Result<(Self, Vec<ZdbRecord>)> {
+    /// Expands the compacted content of the source with a compact stylesheet when it's loaded.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `InvalidDataFormat` error if the stylesheet can't be parsed.
+    pub fn set_compact_style_sheet(&mut self, style_sheet: &str) -> Result<()> {
+        self.compact_stylesheet = MdxReader::load_compact_stylesheet(style_sheet)?;
+        Ok(())
+    }
+
+    /// Opens an MDict source file, its entries are scanned as [`DataLoader::records`] is consumed.
+    ///
+    /// Progress is reported in bytes of the file, with the key of the last entry found.
+    pub fn new(source_file:&str, progress: impl Into<ProgressOptions>) -> Result<Self> {
         let source_file = source_file.to_string();
         let mut input_reader = BufReader::new(File::open(&source_file)?);
         // Get total file size for progress reporting
         let total_size = input_reader.seek(SeekFrom::End(0))?;
         input_reader.seek(SeekFrom::Start(0))?;
-        
+        let mut progress_state = ProgressState::with_options("MDictSourceLoader::new", "Scanning source file", total_size, 10, progress.into());
+        progress_state.total_bytes = total_size;
+        Ok(MDictSourceLoader{
+            source_file,
+            input_reader,
+            warnings: Vec::new(),
+            compact_stylesheet: Vec::new(),
+            progress_state,
+            line_count: 0,
+        })
+    }
+
+    /// Reads the next entry of the source file, `None` at the end of the file.
+    fn next_record(&mut self) -> Result<Option<ZdbRecord>> {
+        let input_reader = &mut self.input_reader;
         let mut line_buffer = String::new();
-        let mut line_count = 0usize;
-        let mut entry_records = Vec::<ZdbRecord>::with_capacity(total_size as usize/1024);
-        let mut progress_state = ProgressState::new("MDictSourceLoader::new", total_size, 10, prog_rpt);
-    
-        while !input_reader.fill_buf()?.is_empty() {
-            line_buffer.clear();
-            
-            // Use standard library read_line
-            input_reader.read_line(&mut line_buffer)?;
-            line_count += 1;
-            
-            let mut line = line_buffer.as_str();
-            if line_count == 1 {
-                line = skip_utf8_bom(line);
-            }
-            
-            let trimmed_line = line.trim_end_matches(&['\r', '\n']);        
-            if trimmed_line.is_empty() {
-                if input_reader.fill_buf()?.is_empty() { //This is the last line of the file, so we can break
-                    break;
-                } else {
-                    return Err(ZdbError::InvalidDataFormat { 
-                        message: "Invalid key".to_string(),
-                        backtrace: Backtrace::capture(),
-                    });
-                }
-            }
-            if trimmed_line.len() > ZDB_MAX_KEYWORD_LENGTH {
-                return Err(ZdbError::InvalidDataFormat { 
-                    message: "Key too long".to_string(),
+        if input_reader.fill_buf()?.is_empty() {
+            return Ok(None);
+        }
+        // Use standard library read_line
+        input_reader.read_line(&mut line_buffer)?;
+        self.line_count += 1;
+
+        let mut line = line_buffer.as_str();
+        if self.line_count == 1 {
+            line = skip_utf8_bom(line);
+        }
+
+        let trimmed_line = line.trim_end_matches(&['\r', '\n']);
+        if trimmed_line.is_empty() {
+            if input_reader.fill_buf()?.is_empty() { //This is the last line of the file, so we can break
+                return Ok(None);
+            } else {
+                return Err(ZdbError::InvalidDataFormat {
+                    message: "Invalid key".to_string(),
                     backtrace: Backtrace::capture(),
                 });
             }
-            
-            // Record position where content starts (after the key line)
-            let content_start_pos = input_reader.stream_position()?;
-            
-            // Read content until text end marker
-            let mut content_buffer = String::new();
-            
-            loop {
-                content_buffer.clear();
-                
-                let bytes_read = input_reader.read_line(&mut content_buffer)?;
-                line_count += 1;
-                if bytes_read == 0 || is_text_end(&content_buffer) {
-                    break;
-                }
-                
-            }
-            let content_length = input_reader.stream_position()? - content_start_pos;
-                
-            if content_length > MAX_ENTRY_LEN as u64 {
-                return Err(ZdbError::InvalidDataFormat { 
-                    message: "Record too long".to_string(),
-                    backtrace: Backtrace::capture(),
-                });
+        }
+
+        let key_line_no = self.line_count;
+        // Record position where content starts (after the key line)
+        let content_start_pos = input_reader.stream_position()?;
+
+        // Read content until text end marker, which isn't part of the content
+        let mut content_buffer = String::new();
+        let mut content_end_pos;
+        loop {
+            content_buffer.clear();
+            content_end_pos = input_reader.stream_position()?;
+            let bytes_read = input_reader.read_line(&mut content_buffer)?;
+            self.line_count += 1;
+            if bytes_read == 0 {
+                let warning = BuildWarning::MissingEntryTerminator { key: trimmed_line.to_string(), line_no: self.line_count - 1 };
+                warning.log();
+                self.warnings.push(warning);
+                break;
             }
-        
-            // Create new record using ZdbRecord structure
-            let record = ZdbRecord {
-                key: trimmed_line.to_string(),
-                content_offset_in_source: 0, // Will be set later during processing
-                position: content_start_pos,
-                content: String::new(), // Content will be loaded separately when needed
-                content_len: content_length,
-                line_no: line_count as u64,
-            };
-            
-            entry_records.push(record);
-            
-            // Report progress using current file position
-            let current_file_pos = input_reader.stream_position()?;
-            if progress_state.report(current_file_pos) {
-                return Err(ZdbError::user_interrupted());
+            if is_text_end(&content_buffer) {
+                break;
             }
         }
-        Ok((MDictSourceLoader{
-            source_file,
-            input_reader,
-        }, entry_records))    
+        let content_length = content_end_pos - content_start_pos;
+
+        // Create new record using ZdbRecord structure
+        let record = ZdbRecord {
+            key: trimmed_line.to_string(),
+            content_offset_in_source: 0, // Will be set later during processing
+            position: content_start_pos,
+            content: String::new(), // Content will be loaded separately when needed
+            content_len: content_length,
+            line_no: key_line_no,
+            content_type: None,
+            union_members: Vec::new(),
+            no_compress: false,
+        };
+
+        // Report progress using current file position
+        let current_file_pos = input_reader.stream_position()?;
+        if self.progress_state.report_item(current_file_pos, current_file_pos, &record.key) {
+            return Err(ZdbError::user_interrupted());
+        }
+        Ok(Some(record))
     }
 }
\ No newline at end of file
@@ -0,0 +1,77 @@
+//! Per-extension settings of resources packed into MDD files.
+//!
+//! Media like JPEG images or MP3 audio is already compressed, compressing it again
+//! costs build time and usually makes it slightly larger. Entries whose extension is
+//! marked `no_compress` are stored in content blocks of their own, without compression.
+//!
+//! MIME types configured here are recorded in the header, and readers return them for
+//! resources with that extension instead of guessing the type, see
+//! [`MdxReader::get_data`](crate::MdxReader::get_data).
+//!
+//! # Examples
+//!
+//! ```
+//! use std::collections::BTreeMap;
+//! use mdx::builder::media_types::{self, MediaTypeConfig};
+//!
+//! let mut config = BTreeMap::new();
+//! config.insert("jpg".to_string(), MediaTypeConfig { mime: String::new(), no_compress: true });
+//! config.insert(".SVGZ".to_string(), MediaTypeConfig { mime: "image/svg+xml".to_string(), no_compress: true });
+//! assert!(media_types::lookup(&config, "/img/cat.JPG").unwrap().no_compress);
+//! assert_eq!(media_types::to_header_value(&config), "svgz=image/svg+xml");
+//! ```
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::utils::mdd_key;
+
+/// Settings of the resources with one file extension.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MediaTypeConfig {
+    /// MIME type recorded in the header, empty to let readers guess it from the extension
+    pub mime: String,
+    /// Store the content without compression
+    pub no_compress: bool,
+}
+
+/// Extension as written in the config, without leading dot and in lower case.
+fn normalize_extension(extension: &str) -> String {
+    extension.trim().trim_start_matches('.').to_lowercase()
+}
+
+/// Finds the settings of a resource by the extension of its key.
+///
+/// Extensions in the config are matched ignoring case and an optional leading dot.
+pub fn lookup<'a>(media_types: &'a BTreeMap<String, MediaTypeConfig>, key: &str) -> Option<&'a MediaTypeConfig> {
+    let extension = mdd_key::extension(key)?;
+    media_types.iter()
+        .find(|(config_extension, _)| normalize_extension(config_extension) == extension)
+        .map(|(_, media_type)| media_type)
+}
+
+/// Encodes the configured MIME types as the `MediaTypes` header attribute, e.g. `mp3=audio/mpeg;svgz=image/svg+xml`.
+pub fn to_header_value(media_types: &BTreeMap<String, MediaTypeConfig>) -> String {
+    let types: Vec<String> = media_types.iter()
+        .filter(|(_, media_type)| !media_type.mime.is_empty())
+        .map(|(extension, media_type)| format!("{}={}", normalize_extension(extension), media_type.mime.trim()))
+        .collect();
+    types.join(";")
+}
+
+/// Describes the entries that can't be recorded in the header.
+pub fn validate(media_types: &BTreeMap<String, MediaTypeConfig>) -> Vec<String> {
+    let mut problems = Vec::new();
+    for (extension, media_type) in media_types {
+        let normalized = normalize_extension(extension);
+        if normalized.is_empty() || normalized.contains(['.', '/', '\\', ';', '=']) {
+            problems.push(format!("media_types: \"{}\" is not a file extension", extension));
+        }
+        if media_type.mime.contains([';', '=']) {
+            problems.push(format!("media_types.{}: MIME type \"{}\" must not contain ';' or '='", extension, media_type.mime));
+        }
+    }
+    problems
+}
@@ -4,15 +4,32 @@
 //! and building ZDB (MDX/MDD) file formats and their associated indexes.
 
 pub mod zdb_builder;
+pub mod build_report;
 pub mod zdb_unit_builder;
 pub mod data_loader;
 pub mod fts_index_builder;
 pub mod mdict_source_loader;
 pub mod zdb_loader;
 pub mod data_dir_loader;
+pub mod script_filter;
+pub mod media_types;
+pub mod block_layout;
+pub mod preflight;
+pub mod synthetic_corpus;
+pub mod entry_meta_loader;
+pub mod cross_references;
 
 // Re-export commonly used types for convenience
-pub use zdb_builder::{BuilderConfig, ZDBBuilder, ZdbHeader, SourceType};
+pub use zdb_builder::{BuilderConfig, UnitMethods, ZDBBuilder, ZdbHeader, SourceType, DEFAULT_WRITE_BUFFER_SIZE};
+pub use build_report::{BuildPhase, BuildReport, BuildWarning};
 pub use zdb_unit_builder::ZdbUnitBuilder;
-pub use data_loader::{ZdbRecord, DataLoader};
-pub use fts_index_builder::{IndexFields, make_index, merge_index, pack_index};
+pub use data_loader::{ZdbRecord, DataLoader, EntrySink, RecordErrorPolicy, SourceMetadata};
+pub use script_filter::ScriptFilterConfig;
+pub use media_types::MediaTypeConfig;
+pub use block_layout::ContentBlockLayout;
+pub use preflight::{preflight, PreflightReport};
+pub use data_dir_loader::{DataDirLoader, DirManifest, DirScanConfig};
+pub use synthetic_corpus::SyntheticCorpus;
+pub use entry_meta_loader::EntryMetaTable;
+pub use cross_references::CrossReferenceResolver;
+pub use fts_index_builder::{FtsIndexMetadata, FtsIndexOptions, IndexFields, make_index, make_index_with_options, merge_index, pack_index};
@@ -0,0 +1,140 @@
+//! Checks run before the content of a build is written.
+//!
+//! Building a large dictionary can take hours, and a source entry that can't be decoded
+//! or a full disk used to surface only when the build got there. The preflight loads the
+//! first entries of the source as the build would, estimates the size of the output from
+//! their compressed size, and compares it with the free space at the output path, so
+//! such builds fail within seconds with an error naming the problem.
+//!
+//! It runs at the start of every build unless [`BuilderConfig::preflight`](crate::builder::BuilderConfig::preflight) is turned off.
+
+use std::path::Path;
+
+use log::{info, warn};
+use serde::Serialize;
+
+use crate::builder::data_loader::{DataLoader, RecordErrorPolicy, ZdbRecord};
+use crate::builder::zdb_builder::{ContentPipeline, ZDBBuilder};
+use crate::storage::unit_base::UnitType;
+use crate::utils::compression::get_compressor;
+use crate::{Result, ZdbError};
+
+/// Number of entries from the start of the source loaded by the preflight.
+pub const PREFLIGHT_SAMPLE_ENTRIES: usize = 64;
+
+/// Bytes added to the estimate for the header and the indexes besides the keys.
+const FIXED_OVERHEAD: u64 = 4 * 1024;
+
+/// Estimates made by the preflight of a build.
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct PreflightReport {
+    /// Number of entries to be built
+    pub entry_count: u64,
+    /// Number of entries loaded to check the source
+    pub sampled_entries: u64,
+    /// Compressed size of the sampled content divided by its stored size
+    pub compression_ratio: f64,
+    /// Estimated size of the output file in bytes, rather too high than too low
+    pub estimated_output_size: u64,
+    /// Space available at the output path in bytes, `None` if unknown or not written to a file
+    pub available_space: Option<u64>,
+}
+
+/// Checks that the source can be loaded and the output will fit on the disk.
+///
+/// The entries of `builder` must be in source order, i.e. not sorted yet, so that the
+/// first entries of the source are sampled.
+///
+/// # Arguments
+///
+/// * `builder` - The builder with the entries of the source and the output path, if any
+/// * `data_loader` - The loader the content will be built with
+///
+/// # Returns
+///
+/// Returns the estimates, which are also logged.
+///
+/// # Errors
+///
+/// Returns an `InvalidDataFormat` error naming the first sampled entry that can't be loaded
+/// if the [`RecordErrorPolicy`] is `Fail`, or an `InsufficientDiskSpace` error if the estimated output exceeds the available space.
+pub fn preflight<T: DataLoader>(builder: &ZDBBuilder, data_loader: &mut T) -> Result<PreflightReport> {
+    let config = &builder.config;
+    let pipeline = ContentPipeline::from_config(config)?;
+    let samples: Vec<&ZdbRecord> = builder.entries.iter()
+        .filter(|entry| entry.union_members.is_empty())
+        .take(PREFLIGHT_SAMPLE_ENTRIES)
+        .collect();
+    let mut sampled = Vec::new();
+    let mut sampled_source_len = 0u64;
+    for entry in &samples {
+        let content = match pipeline.stored_content(data_loader, entry) {
+            Ok(content) => content,
+            // Other policies handle the entry when the content is written
+            Err(e) if config.record_error_policy != RecordErrorPolicy::Fail => {
+                warn!("Preflight: entry \"{}\"{} of the source can't be loaded: {}", entry.key, line_hint(entry), e);
+                continue;
+            }
+            Err(e @ ZdbError::ContentTooLarge { .. }) => return Err(e),
+            Err(e) => return Err(ZdbError::invalid_data_format(format!("Entry \"{}\"{} of the source can't be loaded, check the source and its encoding: {}",
+                entry.key, line_hint(entry), e))),
+        };
+        sampled_source_len += entry.content_len.max(content.len() as u64);
+        sampled.extend(content);
+    }
+
+    let compression_ratio = if sampled.is_empty() {
+        1.0
+    } else {
+        get_compressor(config.unit_compression_method(UnitType::Content)).compress(&sampled)?.len() as f64 / sampled.len() as f64
+    };
+    let sampled_average = sampled_source_len / (samples.len() as u64).max(1);
+    let estimated_output_size = estimate_output_size(&builder.entries, sampled_average, sampled.len() as u64, sampled_source_len, compression_ratio);
+    let available_space = builder.output_path.as_deref().and_then(available_space);
+    let report = PreflightReport {
+        entry_count: builder.entries.len() as u64,
+        sampled_entries: samples.len() as u64,
+        compression_ratio,
+        estimated_output_size,
+        available_space,
+    };
+    info!("Preflight: {} entries, about {} bytes of output, {} bytes available", report.entry_count, estimated_output_size,
+        available_space.map_or("unknown".to_string(), |space| space.to_string()));
+    if let (Some(available), Some(output_path)) = (available_space, &builder.output_path)
+        && estimated_output_size > available {
+        return Err(ZdbError::insufficient_disk_space(output_path.display().to_string(), estimated_output_size, available));
+    }
+    Ok(report)
+}
+
+/// Size of the content as stored and compressed plus the keys, which are stored twice at most.
+///
+/// Errs on the high side: keys are counted uncompressed, and the sampled entries compress
+/// worse on their own than in full content blocks.
+///
+/// Entries of unknown length, e.g. records supplied by the caller, count as the sampled average.
+fn estimate_output_size(entries: &[ZdbRecord], sampled_average: u64, sampled_len: u64, sampled_source_len: u64, compression_ratio: f64) -> u64 {
+    // Stored content differs from the source in size when it's filtered or transcoded
+    let stored_per_source_byte = if sampled_source_len == 0 { 1.0 } else { sampled_len as f64 / sampled_source_len as f64 };
+    let (source_len, key_len) = entries.iter().fold((0u64, 0u64), |(source_len, key_len), entry| {
+        let content_len = if entry.content_len > 0 { entry.content_len } else { sampled_average };
+        (source_len + content_len, key_len + entry.key.len() as u64)
+    });
+    let content_len = (source_len as f64 * stored_per_source_byte * compression_ratio) as u64;
+    content_len + key_len * 2 + FIXED_OVERHEAD
+}
+
+fn line_hint(entry: &ZdbRecord) -> String {
+    if entry.line_no > 0 { format!(" at line {}", entry.line_no) } else { String::new() }
+}
+
+/// Space available to the user at the directory of `output_path`.
+fn available_space(output_path: &Path) -> Option<u64> {
+    let dir = match output_path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    fs4::available_space(dir)
+        .inspect_err(|e| warn!("Can't get the free space at {}: {}", dir.display(), e))
+        .ok()
+}
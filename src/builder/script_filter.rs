@@ -0,0 +1,188 @@
+//! Build-time removal of embedded JavaScript from HTML content.
+//!
+//! Dictionaries converted from third-party sources often carry scripts that must
+//! be removed before the result can be distributed (e.g. for app-store compliance).
+//! This module implements a content transform on top of lol_html's streaming
+//! rewriter, so each entry is processed without building a DOM.
+//!
+//! # Examples
+//!
+//! ```
+//! use mdx::builder::script_filter::ScriptFilterConfig;
+//!
+//! let filter = ScriptFilterConfig {
+//!     strip_scripts: true,
+//!     strip_event_handlers: true,
+//!     allowed_scripts: vec!["jquery.js".to_string()],
+//! };
+//! let html = r#"<script>alert(1)</script><script src="jquery.js"></script><a onclick="x()">a</a>"#;
+//! let filtered = filter.apply(html.as_bytes().to_vec()).unwrap();
+//! assert_eq!(filtered, br#"<script src="jquery.js"></script><a>a</a>"#);
+//! ```
+
+use lol_html::{element, HtmlRewriter, Settings};
+use serde::{Deserialize, Serialize};
+
+use crate::{Result, ZdbError};
+
+const JAVASCRIPT_URL_ATTRIBUTES: &[&str] = &["href", "src", "action", "formaction"];
+
+/// Configuration of the script filter applied to HTML content while building.
+///
+/// The default configuration leaves content untouched.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ScriptFilterConfig {
+    /// Remove `<script>` elements and `javascript:` URLs
+    pub strip_scripts: bool,
+    /// Remove inline event handler attributes such as `onclick`
+    pub strip_event_handlers: bool,
+    /// File names of external scripts that are kept when `strip_scripts` is set
+    pub allowed_scripts: Vec<String>,
+}
+
+impl ScriptFilterConfig {
+    /// Returns true if the filter changes content at all.
+    pub fn is_enabled(&self) -> bool {
+        self.strip_scripts || self.strip_event_handlers
+    }
+
+    /// Checks whether an external script is on the allowlist.
+    ///
+    /// Only the file name part of `src` is compared, case-insensitively.
+    pub fn is_script_allowed(&self, src: &str) -> bool {
+        let src = src.split(['?', '#']).next().unwrap_or_default();
+        let file_name = src.rsplit(['/', '\\']).next().unwrap_or_default();
+        !file_name.is_empty() && self.allowed_scripts.iter().any(|allowed| allowed.eq_ignore_ascii_case(file_name))
+    }
+
+    /// Applies the filter to the HTML content of one entry.
+    ///
+    /// # Arguments
+    ///
+    /// * `content` - UTF-8 encoded HTML content
+    ///
+    /// # Returns
+    ///
+    /// Returns the filtered content, or the input unchanged if the filter is disabled.
+    pub fn apply(&self, content: Vec<u8>) -> Result<Vec<u8>> {
+        if !self.is_enabled() {
+            return Ok(content);
+        }
+
+        let mut handlers = Vec::new();
+        if self.strip_scripts {
+            handlers.push(element!("script", |el| {
+                match el.get_attribute("src") {
+                    Some(src) if self.is_script_allowed(&src) => {}
+                    _ => el.remove(),
+                }
+                Ok(())
+            }));
+            for &attr in JAVASCRIPT_URL_ATTRIBUTES {
+                handlers.push(element!(format!("*[{}]", attr), move |el| {
+                    let is_js_url = el.get_attribute(attr).is_some_and(|value| is_javascript_url(&value));
+                    if is_js_url {
+                        el.remove_attribute(attr);
+                    }
+                    Ok(())
+                }));
+            }
+        }
+        if self.strip_event_handlers {
+            handlers.push(element!("*", |el| {
+                let event_attrs: Vec<String> = el.attributes().iter()
+                    .map(|attr| attr.name())
+                    .filter(|name| name.len() > 2 && name.starts_with("on"))
+                    .collect();
+                for name in event_attrs {
+                    el.remove_attribute(&name);
+                }
+                Ok(())
+            }));
+        }
+
+        let mut output = Vec::with_capacity(content.len());
+        let mut rewriter = HtmlRewriter::new(
+            Settings {
+                element_content_handlers: handlers,
+                ..Settings::default()
+            },
+            |c: &[u8]| output.extend_from_slice(c),
+        );
+        rewriter.write(&content)
+            .map_err(|e| ZdbError::general_error(format!("Failed to filter scripts: {}", e)))?;
+        rewriter.end()
+            .map_err(|e| ZdbError::general_error(format!("Failed to filter scripts: {}", e)))?;
+        Ok(output)
+    }
+}
+
+/// Checks whether an attribute value is a `javascript:` URL as a browser would see it.
+///
+/// Character references are decoded, then ASCII whitespace and control characters are
+/// removed, since browsers ignore them in the scheme, e.g. in `java&#9;script:`.
+fn is_javascript_url(value: &str) -> bool {
+    let scheme: String = decode_char_refs(value).chars()
+        .filter(|c| !c.is_ascii_whitespace() && !c.is_ascii_control())
+        .take("javascript:".len())
+        .collect();
+    scheme.eq_ignore_ascii_case("javascript:")
+}
+
+/// Decodes numeric character references and the named ones that can hide a scheme.
+///
+/// The raw attribute value is used by lol_html, unlike the value seen by a browser.
+fn decode_char_refs(value: &str) -> String {
+    const NAMED: &[(&str, char)] = &[("Tab;", '\t'), ("NewLine;", '\n'), ("colon;", ':')];
+    let mut decoded = String::with_capacity(value.len());
+    let mut rest = value;
+    while let Some(pos) = rest.find('&') {
+        decoded.push_str(&rest[..pos]);
+        rest = &rest[pos + 1..];
+        if let Some(number) = rest.strip_prefix('#') {
+            let (digits, radix) = match number.strip_prefix(['x', 'X']) {
+                Some(hex) => (hex, 16),
+                None => (number, 10),
+            };
+            let length = digits.find(|c: char| !c.is_digit(radix)).unwrap_or(digits.len());
+            if length > 0 {
+                let c = u32::from_str_radix(&digits[..length], radix).ok()
+                    .and_then(char::from_u32)
+                    .unwrap_or(char::REPLACEMENT_CHARACTER);
+                decoded.push(c);
+                let after = &digits[length..];
+                rest = after.strip_prefix(';').unwrap_or(after);
+                continue;
+            }
+        } else if let Some((name, c)) = NAMED.iter().find(|(name, _)| rest.starts_with(name)) {
+            decoded.push(*c);
+            rest = &rest[name.len()..];
+            continue;
+        }
+        decoded.push('&');
+    }
+    decoded.push_str(rest);
+    decoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_javascript_url_forms() {
+        for value in ["javascript:x()", " JavaScript:x()", "java\tscript:x()", "java\nscript:x()", "\u{1}javascript:x()",
+            "&#106;avascript:x()", "&#x6A;avascript:x()", "&#0000106avascript:x()", "java&#9;script:x()",
+            "java&Tab;script:x()", "javascript&colon;x()", "javascript&#58;x()"] {
+            assert!(is_javascript_url(value), "{:?}", value);
+        }
+        for value in ["entry://javascript", "sound://javascript.mp3", "&amp;javascript:", "java-script:x()", "#javascript:"] {
+            assert!(!is_javascript_url(value), "{:?}", value);
+        }
+
+        let filter = ScriptFilterConfig { strip_scripts: true, ..Default::default() };
+        let filtered = filter.apply(br#"<a href="java&#x09;script:x()">a</a><a href="x.html">b</a>"#.to_vec()).unwrap();
+        assert_eq!(filtered, br#"<a>a</a><a href="x.html">b</a>"#);
+    }
+}
@@ -0,0 +1,159 @@
+//! Synthetic dictionary corpora for benchmarks and tests.
+//!
+//! [`SyntheticCorpus`] generates a deterministic set of pseudo-word headwords with
+//! HTML definitions, writes them as MDict source text and builds a dictionary from
+//! it. The same seed always produces the same corpus, so benchmark results can be
+//! compared across commits.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use mdx::builder::{BuilderConfig, SyntheticCorpus};
+//!
+//! # fn main() -> mdx::Result<()> {
+//! let corpus = SyntheticCorpus::new(10_000);
+//! let mut config = BuilderConfig::default();
+//! config.output_file = "/tmp/synthetic.mdx".to_string();
+//! config.default_sorting_locale = "en".to_string();
+//! corpus.build(&mut config)?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use crate::builder::zdb_builder::{BuilderConfig, SourceType, ZDBBuilder};
+use crate::Result;
+
+const SYLLABLES: &[&str] = &[
+    "ba", "ko", "ri", "tan", "mel", "sor", "vi", "qua", "den", "lu", "pra", "ste",
+    "no", "gel", "fi", "zam", "cor", "hu", "wen", "ty", "mo", "ex", "dra", "pol",
+];
+
+const PARTS_OF_SPEECH: &[&str] = &["noun", "verb", "adjective", "adverb"];
+
+/// Generator of a deterministic synthetic dictionary.
+#[derive(Debug, Clone)]
+pub struct SyntheticCorpus {
+    /// Number of entries
+    pub entry_count: usize,
+    /// Seed of the random generator
+    pub seed: u64,
+    /// Number of definitions per entry, each definition is a sentence of 8 to 24 words
+    pub definitions_per_entry: usize,
+}
+
+impl SyntheticCorpus {
+    /// Creates a corpus of `entry_count` entries with the default seed.
+    pub fn new(entry_count: usize) -> Self {
+        Self { entry_count, seed: 0x6d6478, definitions_per_entry: 3 }
+    }
+
+    fn word(rng: &mut StdRng) -> String {
+        let syllable_count = rng.random_range(1..=4);
+        (0..syllable_count).map(|_| SYLLABLES[rng.random_range(0..SYLLABLES.len())]).collect()
+    }
+
+    /// Generates the headwords, unique and in generation order.
+    pub fn keys(&self) -> Vec<String> {
+        let mut rng = StdRng::seed_from_u64(self.seed);
+        let mut seen = HashSet::with_capacity(self.entry_count);
+        let mut keys = Vec::with_capacity(self.entry_count);
+        while keys.len() < self.entry_count {
+            let mut key = Self::word(&mut rng);
+            if rng.random_ratio(1, 8) {
+                key = format!("{} {}", key, Self::word(&mut rng));
+            }
+            if !seen.contains(&key) {
+                seen.insert(key.clone());
+                keys.push(key);
+            } else if seen.len() > SYLLABLES.len().pow(4) / 2 {
+                // Running out of distinct words, make the key unique with a number
+                let key = format!("{}{}", key, keys.len());
+                seen.insert(key.clone());
+                keys.push(key);
+            }
+        }
+        keys
+    }
+
+    /// Generates the HTML definition of a headword.
+    ///
+    /// The definition links to other headwords and references an image and a stylesheet,
+    /// so it exercises the HTML rewriter like real dictionary content.
+    fn definition(&self, key: &str, keys: &[String], rng: &mut StdRng) -> String {
+        let mut html = format!(
+            "<link rel=\"stylesheet\" href=\"style.css\"/><h1 class=\"hw\">{}</h1><span class=\"pos\">{}</span><ol>",
+            key,
+            PARTS_OF_SPEECH[rng.random_range(0..PARTS_OF_SPEECH.len())]
+        );
+        for _ in 0..self.definitions_per_entry {
+            html.push_str("<li>");
+            let word_count = rng.random_range(8..=24);
+            for n in 0..word_count {
+                if n > 0 {
+                    html.push(' ');
+                }
+                if rng.random_ratio(1, 10) && !keys.is_empty() {
+                    let target = &keys[rng.random_range(0..keys.len())];
+                    html.push_str(&format!("<a href=\"entry://{}\">{}</a>", target, target));
+                } else {
+                    html.push_str(&Self::word(rng));
+                }
+            }
+            html.push_str(".</li>");
+        }
+        html.push_str(&format!("</ol><img src=\"img/{}.png\"/>", rng.random_range(0..100)));
+        html
+    }
+
+    /// Generates all entries as (headword, HTML definition) pairs.
+    pub fn entries(&self) -> Vec<(String, String)> {
+        let keys = self.keys();
+        let mut rng = StdRng::seed_from_u64(self.seed ^ 0x5eed);
+        keys.iter().map(|key| (key.clone(), self.definition(key, &keys, &mut rng))).collect()
+    }
+
+    /// Writes the corpus as MDict source text, one `key`, `definition`, `</>` triple per entry.
+    pub fn write_source<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        for (key, html) in self.entries() {
+            write!(writer, "{}\r\n{}\r\n</>\r\n", key, html)?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Builds a dictionary from the corpus.
+    ///
+    /// The source text is written next to `config.output_file` with a `.txt` extension,
+    /// `input_path` and `data_source_format` of the config are set accordingly.
+    pub fn build(&self, config: &mut BuilderConfig) -> Result<()> {
+        let source_path = Path::new(&config.output_file).with_extension("txt");
+        self.write_source(&source_path)?;
+        config.input_path = source_path.to_string_lossy().to_string();
+        config.data_source_format = SourceType::MdictHtml;
+        ZDBBuilder::build_with_config(config, None)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_corpus_is_deterministic() {
+        let corpus = SyntheticCorpus::new(500);
+        let keys = corpus.keys();
+        assert_eq!(keys.len(), 500);
+        assert_eq!(keys.iter().collect::<HashSet<_>>().len(), 500);
+        assert_eq!(corpus.entries(), corpus.entries());
+    }
+}
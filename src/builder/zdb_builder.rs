@@ -75,10 +75,9 @@
 //! use std::fs;
 //!
 //! # fn main() -> mdx::Result<()> {
-//! // Load configuration from JSON
+//! // Load configuration from JSON, missing fields take their defaults
 //! let json_content = fs::read_to_string("config.json")?;
-//! let config: BuilderConfig = serde_json::from_str(&json_content)
-//!     .map_err(|e| mdx::ZdbError::general_error(e.to_string()))?;
+//! let config = BuilderConfig::from_json(&json_content)?;
 //!
 //! // Build with configuration
 //! ZDBBuilder::build_with_config(&config, None)?;
@@ -86,30 +85,61 @@
 //! # }
 //! ```
 
-use std::io::{Seek, Write};
+use std::cell::RefCell;
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs::File;
+use std::io::{BufWriter, Cursor, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
 
 use byteorder::{BigEndian, LittleEndian, WriteBytesExt};
 use log::*;
 use serde::{Deserialize, Serialize};
 
-use crate::builder::data_loader::ZdbRecord;
-use crate::builder::zdb_unit_builder::ZdbUnitBuilder;
+use crate::builder::build_report::{BuildReport, BuildWarning};
+use crate::builder::cross_references::CrossReferenceResolver;
+use crate::builder::data_dir_loader::DirScanConfig;
+use crate::builder::entry_meta_loader::EntryMetaTable;
+use crate::builder::data_loader::{DataLoader, EntrySink, RecordErrorPolicy, ZdbRecord, MAX_ENTRY_LEN, ZDB_MAX_KEYWORD_LENGTH};
+use crate::builder::block_layout::ContentBlockLayout;
+use crate::builder::preflight::preflight;
+use crate::builder::media_types::{self, MediaTypeConfig};
+use crate::builder::script_filter::ScriptFilterConfig;
+use crate::builder::zdb_unit_builder::{ZdbUnitBuilder, OUTPUT_ENCODING};
+use crate::utils::checksum::ChecksumAlgorithm;
 use crate::utils::compression::CompressionMethod;
+use crate::storage::bloom_filter_unit::BloomFilter;
+use crate::storage::entry_meta_unit::{EntryMetaDataInfo, EntryMetaExt, ENTRY_META_BLOCK_ENTRIES, ENTRY_META_RECORD_SIZE};
+use crate::storage::source_map_unit::{write_source_map_record, SourceMapDataInfo, SOURCE_MAP_BLOCK_ENTRIES, SOURCE_MAP_RECORD_SIZE};
 use crate::storage::content_block_index_unit::ContentBlockIndex;
-use crate::crypto::digest::fast_hash_digest;
+use crate::storage::content_unit::CONTENT_FILE_MAGIC;
+use crate::storage::storage_block::BLOCK_FLAG_OFFSET_NONCE;
+use crate::crypto::digest::DigestAlgorithm;
 use crate::crypto::encryption::EncryptionMethod;
-use crate::utils::icu_wrapper::UCollator;
-use crate::storage::key_block::EntryNo;
+use crate::crypto::secret::{SecretBytes, SecretString};
+use crate::utils::icu_wrapper::{shared_collator, UChar};
+use crate::utils::key_normalization::{normalize_query, KeyNormalization};
+use crate::utils::html_preview::is_valid_class_name;
+use crate::utils::named_enum::{named_enum_serde, NamedEnum};
+use crate::storage::key_block::{EntryNo, UNION_PREFIX};
 use crate::storage::key_block_index::KeyBlockIndex;
-use crate::utils::progress_report::{ProgressReportFn, ProgressState};
+use crate::storage::key_unit::{KeyDataInfo, KEY_UNIT_CONTENT_LENGTHS};
+use crate::readers::mdx_reader::MdxReader;
+use crate::readers::zdb_reader::ZdbReader;
+use crate::utils::progress_report::{ProgressOptions, ProgressReportFn, ProgressState};
 use crate::storage::unit_base::UnitType;
+use crate::storage::meta_unit::{ContentType, ZdbVersion};
+use crate::storage::unit_digest::UnitDigestTrailer;
+use crate::utils::atomic_output::AtomicOutput;
 use crate::utils::remove_xml_declaration;
 use crate::{Result, ZdbError};
 
 /// Source dictionary format type.
 ///
 /// Specifies the format of the input source when building a ZDB file.
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+///
+/// Configured by name, e.g. `"MdictHtml"`, or by number, e.g. `107`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum SourceType {
     /// SGD format (105)
     Sgd = 105,
@@ -129,11 +159,44 @@ pub enum SourceType {
     Directory = 114,
 }
 
+impl NamedEnum for SourceType {
+    const KIND: &'static str = "source type";
+    const VARIANTS: &'static [(Self, &'static str, u64)] = &[
+        (SourceType::Sgd, "Sgd", 105),
+        (SourceType::MdictCompact, "MdictCompact", 106),
+        (SourceType::MdictHtml, "MdictHtml", 107),
+        (SourceType::SugarDictWithPhonetic, "SugarDictWithPhonetic", 110),
+        (SourceType::StarDict, "StarDict", 111),
+        (SourceType::Kdic, "Kdic", 112),
+        (SourceType::Zdb, "Zdb", 113),
+        (SourceType::Directory, "Directory", 114),
+    ];
+}
+
+named_enum_serde!(SourceType);
+
+/// Compression and encryption of the blocks of one unit type, overriding the methods of [`BuilderConfig`].
+///
+/// Every block records its methods in its header, so readers need no setting to decode
+/// units written with other methods than the rest of the file.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UnitMethods {
+    /// Compression method of the blocks, `None` for `compression_method`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub compression_method: Option<CompressionMethod>,
+    /// Encryption method of the blocks, `None` for `encryption_method`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub encryption_method: Option<EncryptionMethod>,
+}
+
 /// Configuration for building ZDB dictionaries.
 ///
 /// Contains all parameters needed to build a dictionary file,
 /// including input/output paths, compression settings, and metadata.
+/// Fields missing from a config file take their default values, see [`BuilderConfig::from_json`].
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct BuilderConfig{
     /// Path to the input source file or directory
     pub input_path: String,
@@ -142,7 +205,7 @@ pub struct BuilderConfig{
     /// Whether registration is by email
     pub register_by_email: bool,
     /// Password for the dictionary (if applicable)
-    pub password: String,
+    pub password: SecretString,
     /// Format of the source data
     pub data_source_format: SourceType,
     /// Type of content (Html, Text, or Binary)
@@ -153,18 +216,190 @@ pub struct BuilderConfig{
     pub preferred_content_block_size: u32,
     /// Preferred size for key blocks (default: 16KB)
     pub preferred_key_block_size: u32,
+    /// How entries are grouped into content blocks (default: by size)
+    ///
+    /// See [`block_layout`](crate::builder::block_layout) for the strategies.
+    #[serde(default)]
+    pub content_block_layout: ContentBlockLayout,
+    /// Encrypt each block with a nonce derived from its offset (default: true)
+    ///
+    /// Files written with the legacy all-zero nonce can still be read either way.
+    #[serde(default = "default_per_block_nonce")]
+    pub per_block_nonce: bool,
+    /// Checksum of each block: `"Adler32"` (default), `"Crc32c"` or `"Xxh3"`
+    ///
+    /// Adler-32 misses many corruptions of large blocks but can be read by all versions,
+    /// the others need a reader that knows them, see [`checksum`](crate::utils::checksum).
+    #[serde(default)]
+    pub block_checksum: ChecksumAlgorithm,
+    /// Store the keys of the key block index front-coded (default: true)
+    ///
+    /// Adjacent index keys usually share long prefixes, storing only the differing
+    /// suffixes makes the index considerably smaller. Requires a reader supporting
+    /// key block index minor version 1.
+    #[serde(default = "default_front_coded_key_index")]
+    pub front_coded_key_index: bool,
+    /// Append a Bloom filter over the headwords for fast negative lookups (default: false)
+    ///
+    /// Costs about 10 bits per key, see [`ZdbReader::may_contain`](crate::ZdbReader::may_contain).
+    #[serde(default)]
+    pub bloom_filter: bool,
+    /// TSV file with tags, frequency rank and part of speech of the entries, empty for none
+    ///
+    /// Stored in an entry metadata unit, see [`EntryMetaTable`](crate::builder::EntryMetaTable)
+    /// for the format and [`ZdbReader::get_entry_meta_ext`](crate::ZdbReader::get_entry_meta_ext).
+    #[serde(default)]
+    pub entry_meta_path: String,
+    /// TSV file of labels used in the content and their expansions, empty for none
+    ///
+    /// Each line holds a label, a tab and its expansion, lines starting with `#` are
+    /// skipped. The table is stored in the header, see [`LabelExpander`](crate::utils::LabelExpander).
+    #[serde(default)]
+    pub labels_path: String,
+    /// Classes of the elements left out of entry previews, e.g. examples (default: none)
+    ///
+    /// Stored in the header as the default of [`PreviewOptions::skip_classes`](crate::utils::PreviewOptions::skip_classes).
+    #[serde(default)]
+    pub preview_skip_classes: Vec<String>,
+    /// Extension of a companion file holding the content blocks, e.g. `zdat`, empty to keep
+    /// them in the output file (default: empty)
+    ///
+    /// The companion file is written next to `output_file` with its stem, and named in the
+    /// header, so readers open both. The output file keeps the indexes and stays small, and
+    /// dictionaries too large for filesystems limiting files to 4 GB can be split. Only builds
+    /// writing to `output_file` support it, and unit digests don't cover the companion file.
+    #[serde(default)]
+    pub content_file_extension: String,
+    /// Append a source map with the line of every entry in the source file (default: false)
+    ///
+    /// Only text sources have lines, see [`ZdbReader::get_source_location`](crate::ZdbReader::get_source_location).
+    #[serde(default)]
+    pub source_map: bool,
+    /// Rewrite `entry://` links of html content to `entryx://` links by entry number (default: false)
+    ///
+    /// Links are resolved against the final key set, and links to missing entries are
+    /// reported as warnings, see [`CrossReferenceResolver`](crate::builder::cross_references::CrossReferenceResolver).
+    /// Entries whose content can't be loaded are kept empty instead of skipped, since
+    /// skipping would change the entry numbers.
+    #[serde(default)]
+    pub resolve_cross_references: bool,
+    /// Insert a union entry before every run of duplicate headwords (default: false)
+    ///
+    /// The union entry lists the entry numbers of the duplicates, see
+    /// [`ZdbReader::expand_union`](crate::ZdbReader::expand_union).
+    #[serde(default)]
+    pub merge_duplicate_keys: bool,
+    /// Normalization applied to keys before they are compared (default: none)
+    ///
+    /// Recorded in the header, so readers normalize queries the same way. Keys are
+    /// stored unchanged.
+    #[serde(default)]
+    pub key_normalization: KeyNormalization,
+    /// Append per-unit SHA-256 digests to the output file (default: false)
+    #[serde(default)]
+    pub write_unit_digests: bool,
+    /// Hash algorithm used to derive the crypto key (default: FastHash)
+    #[serde(default)]
+    pub key_digest: DigestAlgorithm,
+    /// Store identical content once, recognized by its digest with this algorithm (default: off)
+    ///
+    /// Entries sharing content store their content length in the key unit. Resource
+    /// dictionaries with many copies of the same file get smaller and faster to build,
+    /// since copies aren't compressed again. BLAKE3 is collision resistant, so content
+    /// that isn't trusted can't make different entries share content. Content copied
+    /// from the blocks of a ZDB source isn't deduplicated.
+    #[serde(default)]
+    pub content_digest: Option<DigestAlgorithm>,
+    /// Removal of embedded JavaScript from HTML content
+    #[serde(default)]
+    pub script_filter: ScriptFilterConfig,
+    /// Compact stylesheet of an MDict source, empty if the content isn't compacted
+    ///
+    /// Lines of token number, prefix and suffix, as used by MDict compact dictionaries.
+    /// A ZDB source carries its stylesheet in its header, so this is only read for MDict sources.
+    #[serde(default)]
+    pub style_sheet_path: String,
+    /// Store compacted content with its stylesheet instead of expanding it (default: false)
+    ///
+    /// The output is marked compact and readers expand the content, see
+    /// [`MdxReader::get_string`](crate::MdxReader::get_string). Otherwise every entry is
+    /// expanded during the build.
+    #[serde(default)]
+    pub keep_compact: bool,
+    /// Check the source and the free disk space before building, see [`preflight`](crate::builder::preflight) (default: true)
+    #[serde(default = "default_preflight")]
+    pub preflight: bool,
+    /// Call the progress reporter for every record read from the source (default: false)
+    ///
+    /// The key of the record is in [`ProgressState::current_key`], e.g. to show it in a GUI.
+    /// Otherwise the reporter is called at intervals.
+    #[serde(default)]
+    pub progress_every_record: bool,
+    /// Maximum length of a key in bytes of UTF-8 (default: [`ZDB_MAX_KEYWORD_LENGTH`])
+    ///
+    /// Keys are stored with a 16-bit length, so the limit can't exceed 65535.
+    #[serde(default = "default_max_key_length")]
+    pub max_key_length: usize,
+    /// Maximum size of the stored content of an entry in bytes (default: [`MAX_ENTRY_LEN`])
+    ///
+    /// A content block holds up to the preferred size plus one entry, and must fit in 4 GiB.
+    #[serde(default = "default_max_content_size")]
+    pub max_content_size: u64,
+    /// What to do with entries whose content can't be loaded (default: fail the build)
+    #[serde(default)]
+    pub record_error_policy: RecordErrorPolicy,
+    /// Number of threads reading source files, `0` uses the number of CPUs (default: 0)
+    ///
+    /// Only directory sources read in parallel, the order of the records doesn't depend on it.
+    #[serde(default)]
+    pub io_threads: usize,
+    /// Size in bytes of the buffer of the output file (default: [`DEFAULT_WRITE_BUFFER_SIZE`])
+    ///
+    /// Larger buffers mean fewer writes, which helps on slow disks.
+    #[serde(default = "default_write_buffer_size")]
+    pub write_buffer_size: usize,
+    /// Directory of the temporary output file, empty for the directory of `output_file` (default: empty)
+    ///
+    /// The output is written to a temporary file first and moved to `output_file` once the
+    /// build succeeded. Moving it across filesystems copies it, so the destination needs
+    /// room for a second copy while it's moved.
+    #[serde(default)]
+    pub temp_dir: String,
+    /// Compression level by method, e.g. `{"Deflate": 9}`, methods missing use their default level
+    ///
+    /// See [`CompressionMethod::level_range`] for the levels of each method.
+    #[serde(default)]
+    pub compression_levels: BTreeMap<CompressionMethod, u32>,
+    /// Encrypt the key and key block index units with `encryption_method` (default: true)
+    #[serde(default = "default_encrypt_units")]
+    pub encrypt_key_units: bool,
+    /// Encrypt the content and content block index units with `encryption_method` (default: true)
+    #[serde(default = "default_encrypt_units")]
+    pub encrypt_content_units: bool,
+    /// Methods by unit type, e.g. `{"Key": {"compression_method": "Lz4"}, "KeyBlockIndex": {"encryption_method": "None"}}`
+    ///
+    /// An encryption method set here takes precedence over `encrypt_key_units` and
+    /// `encrypt_content_units`.
+    #[serde(default)]
+    pub unit_methods: BTreeMap<UnitType, UnitMethods>,
+    /// Selection of the files packed from a directory source
+    #[serde(default)]
+    pub dir_scan: DirScanConfig,
+    /// MIME types and compression of binary entries by file extension, e.g. `"jpg"`
+    ///
+    /// See [`media_types`](crate::builder::media_types) for how they are applied.
+    #[serde(default)]
+    pub media_types: BTreeMap<String, MediaTypeConfig>,
 
     /// Device ID for encryption (not serialized)
     #[serde(skip)]
     pub device_id: String,
     /// Encryption key (not serialized)
     #[serde(skip)]
-    pub crypto_key: Vec<u8>,
-    /// Compression method to use (not serialized)
-    #[serde(skip)]
+    pub crypto_key: SecretBytes,
+    /// Compression method to use, by name or number (default: Deflate)
     pub compression_method: CompressionMethod,
-    /// Encryption method to use (not serialized)
-    #[serde(skip)]
+    /// Encryption method to use, by name or number (default: Salsa20)
     pub encryption_method: EncryptionMethod,
     /// Whether to build MDD (resource) file (not serialized)
     #[serde(skip)]
@@ -176,22 +411,235 @@ impl Default for BuilderConfig {
         BuilderConfig {
             preferred_content_block_size: 64*1024,
             preferred_key_block_size: 16*1024,
+            content_block_layout: ContentBlockLayout::default(),
             compression_method: CompressionMethod::Deflate,
             encryption_method: EncryptionMethod::Salsa20,
             build_mdd: false,
-            crypto_key: Vec::new(),
+            crypto_key: SecretBytes::default(),
             input_path: String::new(),
             output_file: String::new(),
             register_by_email: true,
-            password: String::new(),
+            password: SecretString::default(),
             data_source_format: SourceType::MdictHtml,
             content_type: "Html".to_string(),
             default_sorting_locale: "root".to_string(),
             device_id: String::new(),
+            script_filter: ScriptFilterConfig::default(),
+            style_sheet_path: String::new(),
+            keep_compact: false,
+            preflight: true,
+            progress_every_record: false,
+            record_error_policy: RecordErrorPolicy::default(),
+            max_key_length: ZDB_MAX_KEYWORD_LENGTH,
+            max_content_size: MAX_ENTRY_LEN as u64,
+            io_threads: 0,
+            write_buffer_size: DEFAULT_WRITE_BUFFER_SIZE,
+            temp_dir: String::new(),
+            compression_levels: BTreeMap::new(),
+            encrypt_key_units: true,
+            encrypt_content_units: true,
+            unit_methods: BTreeMap::new(),
+            dir_scan: DirScanConfig::default(),
+            media_types: BTreeMap::new(),
+            per_block_nonce: true,
+            block_checksum: ChecksumAlgorithm::default(),
+            front_coded_key_index: true,
+            bloom_filter: false,
+            entry_meta_path: String::new(),
+            labels_path: String::new(),
+            preview_skip_classes: Vec::new(),
+            content_file_extension: String::new(),
+            source_map: false,
+            resolve_cross_references: false,
+            merge_duplicate_keys: false,
+            key_normalization: KeyNormalization::default(),
+            key_digest: DigestAlgorithm::default(),
+            content_digest: None,
+            write_unit_digests: false,
         }
     }
 }
 
+/// Default of [`BuilderConfig::write_buffer_size`], the default of `BufWriter`.
+pub const DEFAULT_WRITE_BUFFER_SIZE: usize = 8 * 1024;
+
+impl BuilderConfig {
+    /// Parses a JSON config and validates it.
+    ///
+    /// Missing fields take their default values, enums can be given by name or number.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error naming the line and column of malformed JSON or an unknown enum
+    /// value, or an `InvalidParameter` error listing every problem found by [`validate`](Self::validate).
+    pub fn from_json(json: &str) -> Result<Self> {
+        let config: BuilderConfig = serde_json::from_str(json)
+            .map_err(|e| ZdbError::invalid_parameter(format!("Invalid builder config: {}", e)))?;
+        config.validate()
+            .map_err(|problems| ZdbError::invalid_parameter(format!("Invalid builder config:\n{}", problems.join("\n"))))?;
+        Ok(config)
+    }
+
+    /// Checks the config for problems that would make a build fail.
+    ///
+    /// # Returns
+    ///
+    /// Returns all problems found, one message each, rather than stopping at the first.
+    pub fn validate(&self) -> std::result::Result<(), Vec<String>> {
+        let mut problems = Vec::new();
+        if self.input_path.trim().is_empty() {
+            problems.push("input_path is empty".to_string());
+        } else if !std::path::Path::new(&self.input_path).exists() {
+            problems.push(format!("input_path does not exist: {}", self.input_path));
+        }
+        problems.extend(self.settings_problems());
+        if problems.is_empty() { Ok(()) } else { Err(problems) }
+    }
+
+    /// Compression method of the blocks of a unit type, from [`unit_methods`](Self::unit_methods) or else `compression_method`.
+    pub fn unit_compression_method(&self, unit_type: UnitType) -> CompressionMethod {
+        self.unit_methods.get(&unit_type).and_then(|methods| methods.compression_method).unwrap_or(self.compression_method)
+    }
+
+    /// Encryption method of the blocks of a unit type.
+    ///
+    /// A method set in [`unit_methods`](Self::unit_methods) comes first. Otherwise key and
+    /// content units are left unencrypted if [`encrypt_key_units`](Self::encrypt_key_units) or
+    /// [`encrypt_content_units`](Self::encrypt_content_units) is off, and use `encryption_method` else.
+    pub fn unit_encryption_method(&self, unit_type: UnitType) -> EncryptionMethod {
+        if let Some(encryption_method) = self.unit_methods.get(&unit_type).and_then(|methods| methods.encryption_method) {
+            return encryption_method;
+        }
+        let encrypted = match unit_type {
+            UnitType::Key | UnitType::KeyBlockIndex => self.encrypt_key_units,
+            UnitType::Content | UnitType::ContentBlockIndex => self.encrypt_content_units,
+            _ => true,
+        };
+        if encrypted { self.encryption_method } else { EncryptionMethod::None }
+    }
+
+    /// Path of the companion file holding the content blocks, see [`content_file_extension`](Self::content_file_extension).
+    pub fn content_file_path(&self) -> Option<PathBuf> {
+        (!self.content_file_extension.is_empty()).then(|| Path::new(&self.output_file).with_extension(&self.content_file_extension))
+    }
+
+    /// Problems of the settings other than the input, for builds from a caller's data loader.
+    fn settings_problems(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        if self.output_file.trim().is_empty() {
+            problems.push("output_file is empty".to_string());
+        }
+        if ContentType::from_str(&self.content_type).is_err() {
+            problems.push(format!("content_type \"{}\" is not one of Html, Text or Binary", self.content_type));
+        }
+        if self.preferred_content_block_size == 0 {
+            problems.push("preferred_content_block_size must be greater than 0".to_string());
+        }
+        if self.preferred_key_block_size == 0 {
+            problems.push("preferred_key_block_size must be greater than 0".to_string());
+        }
+        problems.extend(self.content_block_layout.validate());
+        if self.max_key_length == 0 || self.max_key_length > u16::MAX as usize {
+            problems.push(format!("max_key_length must be between 1 and {}", u16::MAX));
+        }
+        if self.max_content_size == 0 || self.max_content_size + self.preferred_content_block_size as u64 > u32::MAX as u64 {
+            problems.push("max_content_size must be greater than 0, and together with preferred_content_block_size fit in 4 GiB".to_string());
+        }
+        if self.write_buffer_size == 0 {
+            problems.push("write_buffer_size must be greater than 0".to_string());
+        }
+        if !self.temp_dir.is_empty() && !Path::new(&self.temp_dir).is_dir() {
+            problems.push(format!("temp_dir is not a directory: {}", self.temp_dir));
+        }
+        for (method, level) in &self.compression_levels {
+            match method.level_range() {
+                Some(range) if !range.contains(level) => problems.push(format!("compression_levels: level {} of {:?} must be between {} and {}",
+                    level, method, range.start(), range.end())),
+                Some(_) => {}
+                None => problems.push(format!("compression_levels: {:?} has no levels", method)),
+            }
+        }
+        if self.unit_methods.contains_key(&UnitType::Invalid) {
+            problems.push("unit_methods: Invalid is not a unit type".to_string());
+        }
+        problems.extend(media_types::validate(&self.media_types));
+        if let Err(e) = self.dir_scan.include_set() {
+            problems.push(format!("dir_scan.include: {}", e));
+        }
+        if let Err(e) = self.dir_scan.exclude_set() {
+            problems.push(format!("dir_scan.exclude: {}", e));
+        }
+        if !self.style_sheet_path.is_empty() && !std::path::Path::new(&self.style_sheet_path).is_file() {
+            problems.push(format!("style_sheet_path is not a file: {}", self.style_sheet_path));
+        }
+        if !self.labels_path.is_empty() && !std::path::Path::new(&self.labels_path).is_file() {
+            problems.push(format!("labels_path is not a file: {}", self.labels_path));
+        }
+        for class in self.preview_skip_classes.iter().filter(|class| !is_valid_class_name(class)) {
+            problems.push(format!("preview_skip_classes: invalid class name \"{}\"", class));
+        }
+        if !self.content_file_extension.is_empty() {
+            let output_extension = Path::new(&self.output_file).extension().unwrap_or_default().to_string_lossy();
+            if !self.content_file_extension.chars().all(|c| c.is_ascii_alphanumeric()) {
+                problems.push(format!("content_file_extension \"{}\" must only contain letters and digits", self.content_file_extension));
+            } else if self.content_file_extension.eq_ignore_ascii_case(&output_extension) {
+                problems.push(format!("content_file_extension \"{}\" must differ from the extension of output_file", self.content_file_extension));
+            }
+        }
+        if !self.entry_meta_path.is_empty() && !std::path::Path::new(&self.entry_meta_path).is_file() {
+            problems.push(format!("entry_meta_path is not a file: {}", self.entry_meta_path));
+        }
+        for (field, algorithm) in [("key_digest", Some(self.key_digest)), ("content_digest", self.content_digest)] {
+            if let Some(algorithm) = algorithm && let Err(e) = algorithm.digest(b"probe") {
+                problems.push(format!("{}: {}", field, e));
+            }
+        }
+        if let Err(e) = shared_collator(&self.default_sorting_locale) {
+            problems.push(format!("default_sorting_locale \"{}\" can't be used for sorting: {}", self.default_sorting_locale, e));
+        }
+        problems
+    }
+}
+
+fn default_per_block_nonce() -> bool {
+    true
+}
+
+fn default_encrypt_units() -> bool {
+    true
+}
+
+fn default_write_buffer_size() -> usize {
+    DEFAULT_WRITE_BUFFER_SIZE
+}
+
+fn default_front_coded_key_index() -> bool {
+    true
+}
+
+fn default_preflight() -> bool {
+    true
+}
+
+fn default_max_key_length() -> usize {
+    ZDB_MAX_KEYWORD_LENGTH
+}
+
+fn default_max_content_size() -> u64 {
+    MAX_ENTRY_LEN as u64
+}
+
+/// Content of a union entry: the prefix followed by the comma separated member entry numbers.
+fn union_content(union_members: &[EntryNo]) -> String {
+    let members: Vec<String> = union_members.iter().map(|entry_no| entry_no.to_string()).collect();
+    format!("{}{}", UNION_PREFIX, members.join(","))
+}
+
+/// Writes a flag as "Yes" or "No", the values readers recognize.
+fn serialize_yes_no<S: serde::Serializer>(value: &bool, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+    serializer.serialize_str(if *value { "Yes" } else { "No" })
+}
+
 /// ZDB file header metadata.
 ///
 /// Contains metadata information that goes into the ZDB file header,
@@ -205,8 +653,8 @@ pub struct ZdbHeader{
     /// Minimum engine version required to read this file
     #[serde(rename = "@RequiredEngineVersion")]
     pub required_engine_version: String,
-    /// Whether the dictionary uses compact format
-    #[serde(rename = "@Compact")]
+    /// Whether the content is compacted with `style_sheet`
+    #[serde(rename = "@Compact", serialize_with = "serialize_yes_no")]
     pub compact: bool,
     /// Registration type (e.g., "EMail", "DeviceID")
     #[serde(rename = "@RegisterBy")]
@@ -217,7 +665,7 @@ pub struct ZdbHeader{
     /// Source format type code
     #[serde(rename = "@DataSourceFormat")]
     pub data_source_format: u32,
-    /// CSS stylesheet for content display
+    /// Compact stylesheet the content is expanded with, empty if the content isn't compacted
     #[serde(rename = "@StyleSheet")]
     pub style_sheet: String,
     /// Unique identifier for this dictionary
@@ -229,6 +677,27 @@ pub struct ZdbHeader{
     /// Default sorting locale
     #[serde(rename = "@DefaultSortingLocale")]
     pub default_sorting_locale: String,
+    /// Hash algorithm used for key derivation, omitted for the default algorithm
+    #[serde(rename = "@KeyDigest", skip_serializing_if = "String::is_empty")]
+    pub key_digest: String,
+    /// Normalization applied to keys before comparison, omitted if keys are compared unchanged
+    #[serde(rename = "@KeyNormalization", skip_serializing_if = "String::is_empty")]
+    pub key_normalization: String,
+    /// MIME types of resources by extension, omitted if none are configured
+    #[serde(rename = "@MediaTypes", skip_serializing_if = "String::is_empty")]
+    pub media_types: String,
+    /// How entries are grouped into content blocks, omitted for the default layout by size
+    #[serde(rename = "@ContentBlockLayout", skip_serializing_if = "String::is_empty")]
+    pub content_block_layout: String,
+    /// Labels and their expansions as a JSON object, omitted if there are none
+    #[serde(rename = "@Labels", skip_serializing_if = "String::is_empty")]
+    pub labels: String,
+    /// Space separated classes of the elements left out of entry previews, omitted if there are none
+    #[serde(rename = "@PreviewSkipClasses", skip_serializing_if = "String::is_empty")]
+    pub preview_skip_classes: String,
+    /// File name of the companion file holding the content blocks, omitted if they're in the dictionary
+    #[serde(rename = "@ContentFile", skip_serializing_if = "String::is_empty")]
+    pub content_file: String,
 }
 
 impl ZdbHeader{
@@ -249,14 +718,91 @@ impl ZdbHeader{
             register_by: if config.register_by_email {"Yes".to_string()} else {"No".to_string()},
             creation_date: String::new(), // Should be the current date when generating the zdb
             data_source_format: config.data_source_format as u32,
-            style_sheet: String::new(), // Not used anymore
+            style_sheet: String::new(), // Set when compacted content is kept, see ZDBBuilder::set_compact_style_sheet
             uuid: String::new(), // Should be calculated when generating the zdb
             content_type: config.content_type.clone(),
             default_sorting_locale: config.default_sorting_locale.clone(),
+            key_digest: if config.key_digest == DigestAlgorithm::FastHash { String::new() } else { config.key_digest.name().to_string() },
+            key_normalization: config.key_normalization.to_header_value(),
+            media_types: media_types::to_header_value(&config.media_types),
+            content_block_layout: config.content_block_layout.to_header_value(),
+            labels: String::new(), // Set from `labels_path`, see ZDBBuilder::set_labels
+            preview_skip_classes: config.preview_skip_classes.join(" "),
+            content_file: config.content_file_path()
+                .and_then(|path| path.file_name().map(|name| name.to_string_lossy().into_owned()))
+                .unwrap_or_default(),
         }
     }
 }
 
+/// Turns the data of an entry into its content as stored.
+pub(crate) struct ContentPipeline {
+    script_filter: ScriptFilterConfig,
+    is_html: bool,
+    is_binary: bool,
+    max_content_size: u64,
+    cross_references: Option<CrossReferenceResolver>,
+    /// Keys and targets of the cross-references that can't be resolved
+    broken_cross_references: RefCell<Vec<(String, String)>>,
+}
+
+impl ContentPipeline {
+    pub(crate) fn from_config(config: &BuilderConfig) -> Result<Self> {
+        Ok(Self {
+            script_filter: config.script_filter.clone(),
+            is_html: config.content_type.eq_ignore_ascii_case("html"),
+            is_binary: config.content_type.eq_ignore_ascii_case("binary"),
+            max_content_size: config.max_content_size,
+            cross_references: None,
+            broken_cross_references: RefCell::new(Vec::new()),
+        })
+    }
+
+    /// Resolves the cross-references of html content against the final entries, see [`CrossReferenceResolver`].
+    pub(crate) fn resolve_cross_references(&mut self, entries: &[ZdbRecord], config: &BuilderConfig) -> Result<()> {
+        self.cross_references = Some(CrossReferenceResolver::new(entries, &config.default_sorting_locale, config.key_normalization)?);
+        Ok(())
+    }
+
+    /// Takes the keys and targets of the cross-references that couldn't be resolved so far.
+    pub(crate) fn take_broken_cross_references(&self) -> Vec<(String, String)> {
+        std::mem::take(&mut self.broken_cross_references.borrow_mut())
+    }
+
+    /// Loads the content of an entry, with scripts filtered from html.
+    ///
+    /// Fails with `ContentTooLarge` if the entry exceeds the size limit in the source or as stored.
+    pub(crate) fn stored_content<T: DataLoader>(&self, data_loader: &mut T, entry: &ZdbRecord) -> Result<Vec<u8>> {
+        // Checked before loading as well, so a huge entry isn't read into memory
+        if entry.content_len > self.max_content_size {
+            return Err(ZdbError::content_too_large(&entry.key, entry.content_len, self.max_content_size));
+        }
+        // Scripts are only filtered for html content, binary resources are stored as is
+        let is_html = match &entry.content_type {
+            Some(content_type) => *content_type == ContentType::Html,
+            None => self.is_html,
+        };
+        // Union entries are generated by the builder, the loader doesn't know them
+        let mut content = if entry.union_members.is_empty() {
+            data_loader.load_data(entry)?
+        } else {
+            entry.content.clone().into_bytes()
+        };
+        if is_html {
+            content = self.script_filter.apply(content)?;
+            if let Some(cross_references) = &self.cross_references {
+                let mut broken = Vec::new();
+                content = cross_references.apply(content, &mut broken)?;
+                self.broken_cross_references.borrow_mut().extend(broken.into_iter().map(|target| (entry.key.clone(), target)));
+            }
+        }
+        if content.len() as u64 > self.max_content_size {
+            return Err(ZdbError::content_too_large(&entry.key, content.len() as u64, self.max_content_size));
+        }
+        Ok(content)
+    }
+}
+
 /// Main builder for ZDB dictionary files.
 ///
 /// Orchestrates the process of building a complete ZDB file from entries,
@@ -275,24 +821,68 @@ pub struct ZDBBuilder{
     pub content_block_indexes: Vec<ContentBlockIndex>,
     /// Total size of key index data
     pub total_key_index_data_size: u64,
+    /// Type, offset and length of each unit written so far
+    pub unit_ranges: Vec<(UnitType, u64, u64)>,
+    /// Warnings raised so far, including those of the data loader
+    pub warnings: Vec<BuildWarning>,
+    /// File the output is written to, if any, checked for free space by the preflight
+    pub output_path: Option<PathBuf>,
+    /// Store the content length of every entry in the key unit, set if the content isn't in key order
+    pub content_lengths_stored: bool,
+    /// Copy the content blocks of the source even if the entries are reordered, see [`resort`](Self::resort)
+    pub reorder_copied_content: bool,
+    /// Metadata of the entries by entry number in the source and the tag names, kept by [`resort`](Self::resort)
+    pub source_entry_meta: Option<(Vec<EntryMetaExt>, Vec<String>)>,
 }
 
-fn write_key<W:Write>(writer: &mut W, key: &[u8]) -> Result<()> {
+fn write_key<W:Write>(writer: &mut W, key: &str) -> Result<()> {
+    if key.len() > u16::MAX as usize {
+        return Err(ZdbError::key_too_long(key, key.len() as u64, u16::MAX as u64));
+    }
     writer.write_u16::<BigEndian>(key.len() as u16)?; // Key length doesn't include the terminating zero
-    writer.write_all(key)?;
+    writer.write_all(key.as_bytes())?;
     writer.write_u8(0)?; // Append a ending zero
     Ok(())
 }
 
 fn write_key_block_index<W:Write>(writer: &mut W, key_block_index: &KeyBlockIndex) -> Result<()> {
     writer.write_u32::<BigEndian>(key_block_index.entry_count_in_block as u32)?;
-    write_key(writer, key_block_index.first_key.as_bytes())?;
-    write_key(writer, key_block_index.last_key.as_bytes())?;
+    write_key(writer, &key_block_index.first_key)?;
+    write_key(writer, &key_block_index.last_key)?;
     writer.write_u32::<BigEndian>(key_block_index.block_length as u32)?;
     writer.write_u32::<BigEndian>(key_block_index.raw_data_length as u32)?; // Need to be calculated before writing
     Ok(())
 }
 
+/// Writes a key as the length of the prefix it shares with `prev_key` and the remaining suffix.
+///
+/// Both lengths are u16 in bytes, the suffix has no terminator.
+fn write_front_coded_key<W:Write>(writer: &mut W, key: &[u8], prev_key: &[u8]) -> Result<()> {
+    let shared_len = key.iter().zip(prev_key).take_while(|(a, b)| a == b).count().min(u16::MAX as usize);
+    let suffix = &key[shared_len..];
+    if suffix.len() > u16::MAX as usize {
+        return Err(ZdbError::invalid_parameter(format!("Key too long for key block index: {} bytes", key.len())));
+    }
+    writer.write_u16::<BigEndian>(shared_len as u16)?;
+    writer.write_u16::<BigEndian>(suffix.len() as u16)?;
+    writer.write_all(suffix)?;
+    Ok(())
+}
+
+/// Writes a key block index entry with front-coded keys, `prev_last_key` is updated
+/// to the last key of this entry.
+fn write_front_coded_key_block_index<W:Write>(writer: &mut W, key_block_index: &KeyBlockIndex, prev_last_key: &mut Vec<u8>) -> Result<()> {
+    let first_key = key_block_index.first_key.as_bytes();
+    let last_key = key_block_index.last_key.as_bytes();
+    writer.write_u32::<BigEndian>(key_block_index.entry_count_in_block as u32)?;
+    write_front_coded_key(writer, first_key, prev_last_key)?;
+    write_front_coded_key(writer, last_key, first_key)?;
+    writer.write_u32::<BigEndian>(key_block_index.block_length as u32)?;
+    writer.write_u32::<BigEndian>(key_block_index.raw_data_length as u32)?;
+    *prev_last_key = last_key.to_vec();
+    Ok(())
+}
+
 impl ZDBBuilder {
     /// Creates a new ZDB builder from configuration.
     ///
@@ -311,22 +901,193 @@ impl ZDBBuilder {
             key_block_indexes: Vec::new(),
             content_block_indexes: Vec::new(),
             total_key_index_data_size: 0,
+            unit_ranges: Vec::new(),
+            warnings: Vec::new(),
+            output_path: None,
+            content_lengths_stored: false,
+            reorder_copied_content: false,
+            source_entry_meta: None,
+        }
+    }
+
+    /// Marks the output compact, storing the content unexpanded with its stylesheet.
+    ///
+    /// Must be called before the header is written.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `InvalidDataFormat` error if the stylesheet can't be parsed.
+    pub fn set_compact_style_sheet(&mut self, style_sheet: &str) -> Result<()> {
+        MdxReader::load_compact_stylesheet(style_sheet)?;
+        self.db_header.compact = !style_sheet.trim().is_empty();
+        self.db_header.style_sheet = style_sheet.to_string();
+        Ok(())
+    }
+
+    /// Stores a table of labels and their expansions in the header.
+    ///
+    /// Must be called before the header is written.
+    pub fn set_labels(&mut self, labels: &BTreeMap<String, String>) -> Result<()> {
+        self.db_header.labels = if labels.is_empty() { String::new() } else { serde_json::to_string(labels)? };
+        Ok(())
+    }
+
+    /// Reads a table of labels from a TSV file, see [`BuilderConfig::labels_path`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an `InvalidDataFormat` error naming the line if a line has no expansion.
+    pub fn load_labels<P: AsRef<Path>>(path: P) -> Result<BTreeMap<String, String>> {
+        let mut labels = BTreeMap::new();
+        for (n, line) in std::fs::read_to_string(path.as_ref())?.lines().enumerate() {
+            let line = if n == 0 { line.trim_start_matches('\u{FEFF}') } else { line };
+            if line.trim().is_empty() || line.starts_with('#') {
+                continue;
+            }
+            match line.split_once('\t') {
+                Some((label, expansion)) if !label.trim().is_empty() && !expansion.trim().is_empty() => {
+                    labels.insert(label.trim().to_string(), expansion.trim().to_string());
+                }
+                _ => return Err(ZdbError::invalid_data_format(format!("Label line {} has no tab-separated expansion", n + 1))),
+            }
         }
+        Ok(labels)
+    }
+
+    /// Logs a warning and records it for the build report.
+    pub fn add_warning(&mut self, warning: BuildWarning) {
+        warning.log();
+        self.warnings.push(warning);
+    }
+
+    /// Summary of the build so far.
+    pub fn report(&self) -> BuildReport {
+        BuildReport {
+            entry_count: self.entries.len() as u64,
+            warnings: self.warnings.clone(),
+        }
+    }
+
+    fn record_unit_range<W: Seek>(&mut self, writer: &mut W, unit_builder: &ZdbUnitBuilder) -> Result<()> {
+        let unit_end = writer.stream_position()?;
+        self.unit_ranges.push((unit_builder.unit_info.unit_type, unit_builder.unit_info_pos, unit_end - unit_builder.unit_info_pos));
+        Ok(())
+    }
+
+    /// Appends the SHA-256 digests of all units written so far to the end of the file.
+    ///
+    /// The file must be readable, since the digests are computed from the written data.
+    pub fn write_unit_digests<F: Read + Write + Seek>(&self, file: &mut F) -> Result<()> {
+        let trailer = UnitDigestTrailer::compute(file, &self.unit_ranges)?;
+        file.seek(SeekFrom::End(0))?;
+        trailer.to_writer(file)?;
+        Ok(())
     }
 
     pub fn prepare_key_index(&mut self) -> Result<()> {
         //Sort data entries by collator
         let locale_id=self.config.default_sorting_locale.clone();
         //locale_id.push_str("-kc-true-kf-upper"); //Force to sort uppercase first, Just to make the display order more consistent
-        let collator=UCollator::try_from(locale_id.as_str())?;
+        let collator = shared_collator(&locale_id)?;
         debug!("Sorting entries by locale: {}", locale_id);
+        // Sort by the normalized keys, the reader normalizes queries the same way
+        let normalization = self.config.key_normalization;
         self.entries.sort_by(
-            |a, b| collator.strcoll_utf8(a.key.as_str(), b.key.as_str()).unwrap()
+            |a, b| collator.strcoll_utf8(&normalize_query(&a.key, &normalization), &normalize_query(&b.key, &normalization)).unwrap()
         );
         debug!("Sorting entries by locale: done");
         Ok(())
     }
 
+    /// Checks that no key exceeds [`BuilderConfig::max_key_length`].
+    ///
+    /// # Errors
+    ///
+    /// Returns a `KeyTooLong` error for the first key over the limit.
+    pub fn check_key_lengths(&self) -> Result<()> {
+        for entry in &self.entries {
+            self.check_key_length(&entry.key)?;
+        }
+        Ok(())
+    }
+
+    fn check_key_length(&self, key: &str) -> Result<()> {
+        let limit = self.config.max_key_length;
+        if key.len() > limit {
+            return Err(ZdbError::key_too_long(key, key.len() as u64, limit as u64));
+        }
+        Ok(())
+    }
+
+    /// Adds the records of a source as they are read, in any order.
+    ///
+    /// Keys are checked as they arrive, so a source with a bad key fails before the rest
+    /// is read. The records are sorted by [`prepare_key_index`](Self::prepare_key_index),
+    /// sources don't need to produce them in key order or collect them up front.
+    ///
+    /// # Errors
+    ///
+    /// Returns the first error of `records`, or a `KeyTooLong` error for the first key over
+    /// [`BuilderConfig::max_key_length`].
+    pub fn ingest_records<I: IntoIterator<Item = Result<ZdbRecord>>>(&mut self, records: I) -> Result<()> {
+        let records = records.into_iter();
+        self.entries.reserve(records.size_hint().0);
+        for record in records {
+            let record = record?;
+            self.check_key_length(&record.key)?;
+            self.entries.push(record);
+        }
+        Ok(())
+    }
+
+    /// Inserts a union entry before every run of entries with identical keys.
+    ///
+    /// Must be called after [`prepare_key_index`](Self::prepare_key_index), since the
+    /// member entry numbers refer to the sorted order. The members stay ordinary entries.
+    pub fn insert_union_entries(&mut self) {
+        let entries = std::mem::take(&mut self.entries);
+        let mut merged = Vec::with_capacity(entries.len());
+        let mut i = 0;
+        while i < entries.len() {
+            let run_end = entries[i..].iter().position(|entry| entry.key != entries[i].key).map_or(entries.len(), |len| i + len);
+            if run_end - i > 1 {
+                let first_member = merged.len() as EntryNo + 1;
+                let union_members: Vec<EntryNo> = (first_member..first_member + (run_end - i) as EntryNo).collect();
+                let content = union_content(&union_members);
+                merged.push(ZdbRecord {
+                    key: entries[i].key.clone(),
+                    content_len: content.len() as u64,
+                    content,
+                    content_type: Some(ContentType::Text),
+                    union_members,
+                    ..Default::default()
+                });
+            }
+            merged.extend_from_slice(&entries[i..run_end]);
+            i = run_end;
+        }
+        debug!("Inserted {} union entries", merged.len() - entries.len());
+        self.entries = merged;
+    }
+
+    /// Marks the binary entries whose extension is configured `no_compress` in
+    /// [`BuilderConfig::media_types`] to be stored without compression.
+    ///
+    /// # Arguments
+    ///
+    /// * `is_binary` - Whether entries without their own content type are binary
+    pub fn apply_media_types(&mut self, is_binary: bool) {
+        if self.config.media_types.is_empty() {
+            return;
+        }
+        for entry in self.entries.iter_mut() {
+            let entry_is_binary = entry.content_type.as_ref().map_or(is_binary, |content_type| *content_type == ContentType::Binary);
+            if entry_is_binary && media_types::lookup(&self.config.media_types, &entry.key).is_some_and(|media_type| media_type.no_compress) {
+                entry.no_compress = true;
+            }
+        }
+    }
+
     pub fn prepare_key_block_index_unit(&mut self, preferred_block_size: u64, prog_rpt: Option<ProgressReportFn>) -> Result<()> {
         let mut i = 0;
         let extra_size: u64 = 1 + 8; // 1 byte ending zero + 8 bytes record offset
@@ -373,13 +1134,12 @@ impl ZDBBuilder {
     pub fn build_db_header<W: Write>(&mut self, writer: &mut W) -> Result<()> {
         self.db_header.creation_date = chrono::Utc::now().format("%Y-%m-%d").to_string();
         self.db_header.uuid = uuid::Uuid::new_v4().to_string();
-        self.config.crypto_key = if self.config.password.is_empty(){
+        self.config.crypto_key = SecretBytes::new(if self.config.password.is_empty(){
             debug!("uuid:{}",self.db_header.uuid);
-            fast_hash_digest(self.db_header.uuid.as_bytes())?
+            self.config.key_digest.digest(self.db_header.uuid.as_bytes())?
         } else {
-            fast_hash_digest(self.config.password.as_bytes())?
-        };
-        debug!("crypto_key:{}",hex::encode(&self.config.crypto_key));
+            self.config.key_digest.digest(self.config.password.as_bytes())?
+        });
         let mut header_str = serde_xml_rs::to_string(&self.db_header)?;
         remove_xml_declaration(&mut header_str);
         writer.write_u32::<BigEndian>(header_str.len() as u32 + 1)?;
@@ -392,17 +1152,18 @@ impl ZDBBuilder {
     }
     
     pub fn build_key_block_index_unit<W: Write+Seek>(&mut self, writer: &mut W, prog_rpt: Option<ProgressReportFn>) -> Result<()> {
-
-        if self.entries.is_empty() {
-            return Err(ZdbError::invalid_parameter("No entries"));
-        }
         let mut unit_builder = ZdbUnitBuilder::from_config(&self.config);
 
         let mut progress_state = ProgressState::new("ZDBBuilder::build_key_block_index_unit", self.key_block_indexes.len() as u64, 10, prog_rpt);
         unit_builder.write_unit_begin(writer, UnitType::KeyBlockIndex)?;        
         let mut key_block_indexes_data = Vec::<u8>::with_capacity(self.key_block_indexes.len()*100);
+        let mut prev_last_key = Vec::new();
         for (n, key_block_index) in self.key_block_indexes.iter().enumerate() {
-            write_key_block_index(&mut key_block_indexes_data, key_block_index)?;
+            if self.config.front_coded_key_index {
+                write_front_coded_key_block_index(&mut key_block_indexes_data, key_block_index, &mut prev_last_key)?;
+            } else {
+                write_key_block_index(&mut key_block_indexes_data, key_block_index)?;
+            }
             if progress_state.report(n as u64){
                 info!("Buil key block index unit cancelled by user");
                 return Err(ZdbError::user_interrupted());
@@ -411,6 +1172,98 @@ impl ZDBBuilder {
 
         unit_builder.output_block(writer, &key_block_indexes_data)?;
         unit_builder.write_unit_end(writer, self.key_block_indexes.len() as u64)?;
+        self.record_unit_range(writer, &unit_builder)?;
+        Ok(())
+    }
+
+    /// Writes a Bloom filter unit over the sort keys of all entries.
+    ///
+    /// The sort keys are computed with the collator of the sorting locale, the same way the
+    /// reader computes them for a lookup.
+    pub fn build_bloom_filter_unit<W: Write+Seek>(&mut self, writer: &mut W) -> Result<()> {
+        let collator = shared_collator(&self.config.default_sorting_locale)?;
+        let mut filter = BloomFilter::with_key_count(self.entries.len() as u64);
+        for entry in &self.entries {
+            let key = normalize_query(&entry.key, &self.config.key_normalization);
+            filter.insert(&collator.get_sort_key(&UChar::try_from(key.as_ref())?));
+        }
+
+        let mut unit_builder = ZdbUnitBuilder::from_config(&self.config);
+        unit_builder.write_unit_begin(writer, UnitType::BloomFilter)?;
+        unit_builder.output_block(writer, &filter.bits)?;
+        unit_builder.write_unit_end(writer, self.entries.len() as u64)?;
+        self.record_unit_range(writer, &unit_builder)?;
+        Ok(())
+    }
+
+    /// Writes an entry metadata unit with a record for every entry, in entry order.
+    ///
+    /// Entries without a row in the table get empty metadata, and rows matching no
+    /// entry are reported as warnings.
+    pub fn build_entry_meta_unit<W: Write+Seek>(&mut self, writer: &mut W, table: &EntryMetaTable) -> Result<()> {
+        let mut matched = HashSet::new();
+        self.write_entry_meta_unit(writer, &table.tag_names, |entry| match table.rows.get_key_value(&entry.key) {
+            Some((key, (meta, _))) => {
+                matched.insert(key.as_str());
+                *meta
+            }
+            None => EntryMetaExt::default(),
+        })?;
+
+        let mut unmatched: Vec<(&String, u64)> = table.rows.iter()
+            .filter(|(key, _)| !matched.contains(key.as_str()))
+            .map(|(key, (_, line_no))| (key, *line_no))
+            .collect();
+        unmatched.sort_by_key(|(_, line_no)| *line_no);
+        for (key, line_no) in unmatched {
+            let warning = BuildWarning::UnmatchedEntryMeta { key: key.clone(), line_no };
+            warning.log();
+            self.warnings.push(warning);
+        }
+        Ok(())
+    }
+
+    /// Writes an entry metadata unit with the metadata returned by `meta_of` for every entry, in entry order.
+    fn write_entry_meta_unit<W: Write+Seek, F: FnMut(&ZdbRecord) -> EntryMetaExt>(&mut self, writer: &mut W, tag_names: &[String], mut meta_of: F) -> Result<()> {
+        let mut unit_builder = ZdbUnitBuilder::from_config(&self.config);
+        unit_builder.write_unit_begin(writer, UnitType::EntryMeta)?;
+        for entries in self.entries.chunks(ENTRY_META_BLOCK_ENTRIES) {
+            let mut block_data = Vec::with_capacity(entries.len() * ENTRY_META_RECORD_SIZE as usize);
+            for entry in entries {
+                meta_of(entry).write_record(&mut block_data);
+            }
+            unit_builder.output_block(writer, &block_data)?;
+        }
+        let data_info = EntryMetaDataInfo {
+            entry_count: self.entries.len() as u64,
+            record_size: ENTRY_META_RECORD_SIZE,
+            tag_names: tag_names.join(","),
+        };
+        unit_builder.write_unit_end_with_data_info(writer, &data_info)?;
+        self.record_unit_range(writer, &unit_builder)
+    }
+
+    /// Writes a source map unit with the key line and content offset of every entry, in entry order.
+    ///
+    /// Entries without a line number, e.g. union entries, are stored without location.
+    pub fn build_source_map_unit<W: Write+Seek>(&mut self, writer: &mut W) -> Result<()> {
+        let mut unit_builder = ZdbUnitBuilder::from_config(&self.config);
+        unit_builder.write_unit_begin(writer, UnitType::SourceMap)?;
+        for entries in self.entries.chunks(SOURCE_MAP_BLOCK_ENTRIES) {
+            let mut block_data = Vec::with_capacity(entries.len() * SOURCE_MAP_RECORD_SIZE as usize);
+            for entry in entries {
+                let offset = if entry.line_no > 0 { entry.position } else { 0 };
+                write_source_map_record(entry.line_no, offset, &mut block_data);
+            }
+            unit_builder.output_block(writer, &block_data)?;
+        }
+        let data_info = SourceMapDataInfo {
+            entry_count: self.entries.len() as u64,
+            record_size: SOURCE_MAP_RECORD_SIZE,
+            source_file: Path::new(&self.config.input_path).file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default(),
+        };
+        unit_builder.write_unit_end_with_data_info(writer, &data_info)?;
+        self.record_unit_range(writer, &unit_builder)?;
         Ok(())
     }
 
@@ -425,6 +1278,9 @@ impl ZDBBuilder {
             for j in 0..key_block_index.entry_count_in_block {
                 let entry = &self.entries[(key_block_index.first_entry_no_in_block as u64 + j )as usize];
                 key_block_data.write_u64::<BigEndian>(entry.content_offset_in_source)?;
+                if self.content_lengths_stored {
+                    key_block_data.write_u64::<BigEndian>(entry.content_len)?;
+                }
                 key_block_data.write_all(entry.key.as_bytes())?;
                 key_block_data.write_u8(0)?;
             }
@@ -439,7 +1295,18 @@ impl ZDBBuilder {
             key_block_index.block_length = key_block_compressed_size;
         }
 
-        unit_builder.write_unit_end(writer, self.entries.len() as u64)?;
+        if self.content_lengths_stored {
+            let data_info = KeyDataInfo {
+                key_count: self.entries.len() as u64,
+                encoding: OUTPUT_ENCODING.to_string(),
+                locale_id: self.config.default_sorting_locale.clone(),
+                minor_version: KEY_UNIT_CONTENT_LENGTHS,
+            };
+            unit_builder.write_unit_end_with_data_info(writer, &data_info)?;
+        } else {
+            unit_builder.write_unit_end(writer, self.entries.len() as u64)?;
+        }
+        self.record_unit_range(writer, &unit_builder)?;
         Ok(())
     }
 
@@ -458,10 +1325,35 @@ impl ZDBBuilder {
         }
         unit_builder.output_block(writer, &content_block_index_data)?;
         unit_builder.write_unit_end(writer, self.content_block_indexes.len() as u64)?;
+        self.record_unit_range(writer, &unit_builder)?;
         Ok(())
     }
 
-    pub fn build_content_unit<W: Write+Seek, L: FnMut(&ZdbRecord) -> Result<Vec<u8>>>(&mut self, writer: &mut W, mut data_loader:L, prog_rpt: Option<ProgressReportFn>) -> Result<()> {
+    /// Writes the content unit, loading the content of each entry with `data_loader`.
+    ///
+    /// Entries whose content can't be loaded are handled by the configured [`RecordErrorPolicy`].
+    pub fn build_content_unit<W: Write+Seek, L: FnMut(&ZdbRecord) -> Result<Vec<u8>>>(&mut self, writer: &mut W, data_loader:L, prog_rpt: Option<ProgressReportFn>) -> Result<()> {
+        self.write_content_unit(writer, None, data_loader, None, prog_rpt)
+    }
+
+    /// Writes a content block to the companion content file if there's one, to `writer` otherwise.
+    fn output_content_block<W: Write+Seek>(unit_builder: &mut ZdbUnitBuilder, writer: &mut W, content_writer: Option<&mut BufWriter<File>>, block_data: &[u8], compression_method: CompressionMethod) -> Result<u64> {
+        match content_writer {
+            Some(content_writer) => unit_builder.output_block_with_compression(content_writer, block_data, compression_method),
+            None => unit_builder.output_block_with_compression(writer, block_data, compression_method),
+        }
+    }
+
+    /// Ends the content unit, whose data section is empty if the blocks are in the companion content file.
+    fn write_content_unit_end<W: Write+Seek>(&mut self, unit_builder: &mut ZdbUnitBuilder, writer: &mut W, in_content_file: bool) -> Result<()> {
+        if in_content_file {
+            unit_builder.unit_info.data_section_length = 0;
+        }
+        unit_builder.write_unit_end(writer, self.entries.len() as u64)?;
+        self.record_unit_range(writer, unit_builder)
+    }
+
+    fn write_content_unit<W: Write+Seek, L: FnMut(&ZdbRecord) -> Result<Vec<u8>>>(&mut self, writer: &mut W, mut content_writer: Option<&mut BufWriter<File>>, mut data_loader:L, mut sink: Option<&mut dyn EntrySink>, prog_rpt: Option<ProgressReportFn>) -> Result<()> {
         let mut progress_state = ProgressState::new("ZDBBuilder::build_content_unit", self.entries.len() as u64, 10, prog_rpt);
         let mut unit_builder = ZdbUnitBuilder::from_config(&self.config);
         unit_builder.write_unit_begin(writer, UnitType::Content)?;
@@ -470,26 +1362,92 @@ impl ZDBBuilder {
         let mut offset_in_unit = 0;
         let total_entries = self.entries.len();
         let mut content_data = Vec::<u8>::with_capacity(self.config.preferred_content_block_size as usize);
+        let layout = self.config.content_block_layout;
+        let preferred_size = self.config.preferred_content_block_size as usize;
+        // Union entries and resolved cross-references refer to entries by number, so no entry can be left out then
+        let can_skip = !self.config.resolve_cross_references && !self.entries.iter().any(|entry| !entry.union_members.is_empty());
+        let mut skipped = Vec::new();
+        let content_digest = self.config.content_digest;
+        // Offset of the content stored first by digest and length
+        let mut stored_contents = HashMap::<(Vec<u8>, usize), u64>::new();
 
         let mut i = 0;
         let mut content_offset_in_source = 0;
         while  i < total_entries {
             content_data.clear();
+            // Uncompressed entries are kept in blocks of their own
+            let no_compress = self.entries[i].no_compress;
+            let block_start = i;
             while i < total_entries {
+                if self.entries[i].no_compress != no_compress {
+                    break;
+                }
+                //Because we don't know the real content length before loading it,
+                //the layout decides whether the block is full before loading the next entry.
+                if i > block_start && layout.ends_block_before(content_data.len(), i - block_start, &self.entries[i - 1], &self.entries[i], preferred_size) {
+                    break;
+                }
+                let mut is_skipped = false;
+                let content = match data_loader(&self.entries[i]) {
+                    Ok(content) => content,
+                    Err(e) => {
+                        let key = self.entries[i].key.clone();
+                        match &self.config.record_error_policy {
+                            RecordErrorPolicy::Fail => return Err(e),
+                            RecordErrorPolicy::Skip => {
+                                if can_skip {
+                                    skipped.push(i);
+                                }
+                                is_skipped = true;
+                                self.add_warning(BuildWarning::SkippedEntry { key, error: e.to_string() });
+                                Vec::new()
+                            }
+                            RecordErrorPolicy::Placeholder(text) => {
+                                let content = text.as_bytes().to_vec();
+                                self.add_warning(BuildWarning::PlaceholderContent { key, error: e.to_string() });
+                                content
+                            }
+                        }
+                    }
+                };
+                let shared_offset = match content_digest {
+                    Some(algorithm) if !content.is_empty() => {
+                        match stored_contents.entry((algorithm.digest(&content)?, content.len())) {
+                            Entry::Occupied(stored) => Some(*stored.get()),
+                            Entry::Vacant(vacant) => {
+                                vacant.insert(content_offset_in_source);
+                                None
+                            }
+                        }
+                    }
+                    _ => None,
+                };
+                if shared_offset.is_some() {
+                    self.content_lengths_stored = true;
+                }
                 let entry = &mut self.entries[i];
-                let content = data_loader(entry)?;
-                entry.content_offset_in_source = content_offset_in_source;
-                content_offset_in_source += content.len() as u64;
-                content_data.extend(content);
+                if content_digest.is_some() {
+                    entry.content_len = content.len() as u64;
+                }
+                entry.content_offset_in_source = shared_offset.unwrap_or(content_offset_in_source);
+                if !is_skipped {
+                    if let Some(sink) = sink.as_deref_mut() {
+                        sink.write_entry(entry, &content)?;
+                    }
+                    if content.is_empty() {
+                        let key = entry.key.clone();
+                        self.add_warning(BuildWarning::EmptyContent { key });
+                    }
+                }
+                if shared_offset.is_none() {
+                    content_offset_in_source += content.len() as u64;
+                    content_data.extend(content);
+                }
                 i += 1;
-                //Because we don't know the real content length before loading it. 
-                //So we need to break the loop when the content data length is greater than the preferred block size.
-                if content_data.len() > self.config.preferred_content_block_size as usize {
-                    break;
-                }   
             }
 
-            let data_block_size= unit_builder.output_block(writer, &content_data)?;
+            let compression_method = if no_compress { CompressionMethod::None } else { unit_builder.compression_method() };
+            let data_block_size = Self::output_content_block(&mut unit_builder, writer, content_writer.as_deref_mut(), &content_data, compression_method)?;
 
             if progress_state.report(i as u64) {
                 info!("Buil content unit cancelled by user");
@@ -506,23 +1464,187 @@ impl ZDBBuilder {
             offset_in_source += content_data.len() as u64;
             offset_in_unit += data_block_size as u64;
         }
-        unit_builder.write_unit_end(writer, self.entries.len() as u64)?;
-        Ok(())
+        if !skipped.is_empty() {
+            // Skipped entries have no content, leaving them out keeps the offsets of the others
+            let mut entry_no = 0;
+            self.entries.retain(|_| {
+                entry_no += 1;
+                skipped.binary_search(&(entry_no - 1)).is_err()
+            });
+            self.prepare_key_block_index_unit(self.config.preferred_key_block_size as u64, None)?;
+        }
+        self.write_content_unit_end(&mut unit_builder, writer, content_writer.is_some())
+    }
+
+    /// Writes the content unit by copying the content blocks of the source file as stored.
+    ///
+    /// Rebuilds that only change metadata, like the header or the key normalization,
+    /// don't need to decompress and recompress the content. Copying is only possible if
+    /// the entries are still in the order of the source, their content is stored in UTF-8
+    /// without filtering, the blocks have the configured layout, and every
+    /// block uses the configured compression method or none. Blocks are encrypted again for the new file.
+    ///
+    /// # Arguments
+    ///
+    /// * `writer` - Destination of the content unit
+    /// * `source` - The file the entries were loaded from, see [`DataLoader::stored_source`]
+    /// * `prog_rpt` - Optional progress reporter callback function
+    ///
+    /// # Returns
+    ///
+    /// Returns false, without writing anything, if the content has to be rebuilt.
+    pub fn copy_content_unit<W: Write+Seek, R: Read+Seek>(&mut self, writer: &mut W, source: &mut ZdbReader<R>, prog_rpt: Option<ProgressReportFn>) -> Result<bool> {
+        self.write_copied_content_unit(writer, None, source, prog_rpt)
+    }
+
+    fn write_copied_content_unit<W: Write+Seek, R: Read+Seek>(&mut self, writer: &mut W, mut content_writer: Option<&mut BufWriter<File>>, source: &mut ZdbReader<R>, prog_rpt: Option<ProgressReportFn>) -> Result<bool> {
+        if source.meta.version != ZdbVersion::V3
+            || self.config.script_filter != ScriptFilterConfig::default()
+            || self.config.resolve_cross_references
+            || source.meta.encoding_obj != encoding_rs::UTF_8
+            || self.config.content_block_layout.to_header_value() != source.meta.db_info.content_block_layout
+            || self.entries.iter().any(|entry| entry.no_compress) {
+            return Ok(false);
+        }
+        // Every entry must be at its position in the source, union entries are recreated identically
+        let mut in_place = self.entries.len() as u64 == source.get_entry_count();
+        let mut content_offsets = Vec::with_capacity(self.entries.len());
+        for (entry_no, entry) in self.entries.iter().enumerate() {
+            if !in_place {
+                break;
+            }
+            let key_index = source.get_index(entry_no as EntryNo)?;
+            in_place = if entry.union_members.is_empty() {
+                entry.position == entry_no as u64
+            } else {
+                entry.key == key_index.key && source.is_union_entry(&key_index)?
+            };
+            content_offsets.push(Some(key_index.content_offset_in_source));
+        }
+        if !in_place {
+            if !self.reorder_copied_content {
+                return Ok(false);
+            }
+            // Entries refer to their content in the copied blocks, union entries get new content
+            // after them since the entry numbers of their members changed
+            content_offsets.clear();
+            for entry in &self.entries {
+                content_offsets.push(if entry.union_members.is_empty() {
+                    Some(source.get_index(entry.position as EntryNo)?.content_offset_in_source)
+                } else {
+                    None
+                });
+            }
+        }
+        let source_block_indexes = source.content_block_indexes().to_vec();
+        for content_block_index in &source_block_indexes {
+            let compression_method = source.content_block_compression(content_block_index)?;
+            if compression_method != self.config.unit_compression_method(UnitType::Content) && compression_method != CompressionMethod::None {
+                return Ok(false);
+            }
+        }
+
+        let mut progress_state = ProgressState::new("ZDBBuilder::copy_content_unit", source_block_indexes.len() as u64, 10, prog_rpt);
+        let source_key = source.meta.crypto_key.clone();
+        let mut unit_builder = ZdbUnitBuilder::from_config(&self.config);
+        unit_builder.write_unit_begin(writer, UnitType::Content)?;
+        self.content_block_indexes.clear();
+        let mut offset_in_unit = 0;
+        for (n, source_block_index) in source_block_indexes.iter().enumerate() {
+            let (source_offset, stored_block) = source.read_stored_content_block(source_block_index)?;
+            let data_block_size = match content_writer.as_deref_mut() {
+                Some(content_writer) => unit_builder.output_stored_block(content_writer, &stored_block, source_block_index.block_original_length, source_offset, &source_key)?,
+                None => unit_builder.output_stored_block(writer, &stored_block, source_block_index.block_original_length, source_offset, &source_key)?,
+            };
+            self.content_block_indexes.push(ContentBlockIndex {
+                block_offset_in_source: source_block_index.block_offset_in_source,
+                block_offset_in_unit: offset_in_unit,
+                block_original_length: source_block_index.block_original_length,
+                block_compressed_length: data_block_size,
+            });
+            offset_in_unit += data_block_size;
+            if progress_state.report(n as u64 + 1) {
+                info!("Copy content unit cancelled by user");
+                return Err(ZdbError::user_interrupted());
+            }
+        }
+
+        let mut union_block = Vec::new();
+        let union_block_offset = source_block_indexes.last().map_or(0, |block| block.block_offset_in_source + block.block_original_length);
+        let mut empty_keys = Vec::new();
+        for (entry, content_offset) in self.entries.iter_mut().zip(content_offsets) {
+            entry.content_offset_in_source = match content_offset {
+                Some(content_offset) => content_offset,
+                None => {
+                    let content_offset = union_block_offset + union_block.len() as u64;
+                    entry.content_len = entry.content.len() as u64;
+                    union_block.extend_from_slice(entry.content.as_bytes());
+                    content_offset
+                }
+            };
+            if entry.content_len == 0 {
+                empty_keys.push(entry.key.clone());
+            }
+        }
+        if !union_block.is_empty() {
+            let data_block_size = match content_writer.as_deref_mut() {
+                Some(content_writer) => unit_builder.output_block(content_writer, &union_block)?,
+                None => unit_builder.output_block(writer, &union_block)?,
+            };
+            self.content_block_indexes.push(ContentBlockIndex {
+                block_offset_in_source: union_block_offset,
+                block_offset_in_unit: offset_in_unit,
+                block_original_length: union_block.len() as u64,
+                block_compressed_length: data_block_size,
+            });
+        }
+        // The length of an entry is the distance to the next one only if the content is in key order
+        self.content_lengths_stored = !in_place;
+        for key in empty_keys {
+            self.add_warning(BuildWarning::EmptyContent { key });
+        }
+        self.write_content_unit_end(&mut unit_builder, writer, content_writer.is_some())?;
+        Ok(true)
     }
 
-    /// Build ZDB with a specific data loader
-    fn build_with_data_loader<T: crate::builder::data_loader::DataLoader>(
+    /// Writes the header and all units for the ingested records, using a specific data loader.
+    ///
+    /// Returns the builder so the caller can append the unit digests.
+    fn build_units<W: Write+Seek, T: DataLoader>(
         mut zdb_builder: ZDBBuilder,
-        mut zdb_writer: std::io::BufWriter<std::fs::File>,
+        zdb_writer: &mut W,
+        mut content_writer: Option<&mut BufWriter<File>>,
         mut data_loader: T,
-        entry_records: Vec<ZdbRecord>,
+        sink: Option<&mut dyn EntrySink>,
         prog_rpt: Option<ProgressReportFn>
-    ) -> Result<()> {
-        // Load entries from data loader
-        zdb_builder.entries = entry_records;
+    ) -> Result<ZDBBuilder> {
+        if !zdb_builder.config.labels_path.is_empty() {
+            let labels = Self::load_labels(&zdb_builder.config.labels_path)?;
+            zdb_builder.set_labels(&labels)?;
+        }
+        if !zdb_builder.db_header.content_file.is_empty() && content_writer.is_none() {
+            return Err(ZdbError::invalid_parameter("content_file_extension requires building to output_file, e.g. with build_with_config"));
+        }
+        zdb_builder.build_db_header(zdb_writer)?;
+
+        let entry_meta = match zdb_builder.config.entry_meta_path.as_str() {
+            "" => None,
+            path => Some(EntryMetaTable::from_file(path)?),
+        };
+        if zdb_builder.config.preflight {
+            info!("Checking source and disk space...");
+            preflight(&zdb_builder, &mut data_loader)?;
+            info!("done");
+        }
 
         info!("Sorting index...");
         zdb_builder.prepare_key_index()?;
+        if zdb_builder.entries.is_empty() {
+            zdb_builder.add_warning(BuildWarning::NoEntries);
+        }
+        if zdb_builder.config.merge_duplicate_keys {
+            zdb_builder.insert_union_entries();
+        }
         info!("done");
 
         info!("Preparing key index...");
@@ -530,29 +1652,260 @@ impl ZDBBuilder {
         info!("done");
 
         info!("Building content unit...");
-        // Use closure to pass DataLoader::load_data to build_content_unit
-        zdb_builder.build_content_unit(
-            &mut zdb_writer,
-            |entry| data_loader.load_data(entry),
-            prog_rpt,
-        )?;
+        let mut pipeline = ContentPipeline::from_config(&zdb_builder.config)?;
+        if zdb_builder.config.resolve_cross_references {
+            pipeline.resolve_cross_references(&zdb_builder.entries, &zdb_builder.config)?;
+        }
+        zdb_builder.apply_media_types(pipeline.is_binary);
+        // A sink needs the content of every entry, so the blocks of the source aren't copied then
+        let copied = match data_loader.stored_source() {
+            Some(source) if sink.is_none() => zdb_builder.write_copied_content_unit(zdb_writer, content_writer.as_deref_mut(), source, prog_rpt)?,
+            _ => false,
+        };
+        if copied {
+            info!("Copied the content blocks of the source");
+        } else {
+            data_loader.prepare(&zdb_builder.entries)?;
+            // Use closure to pass DataLoader::load_data to build_content_unit
+            zdb_builder.write_content_unit(
+                zdb_writer,
+                content_writer,
+                |entry| pipeline.stored_content(&mut data_loader, entry),
+                sink,
+                prog_rpt,
+            )?;
+            for (key, target) in pipeline.take_broken_cross_references() {
+                zdb_builder.add_warning(BuildWarning::BrokenCrossReference { key, target });
+            }
+        }
         info!("done");
 
         info!("Building content block index unit...");
-        zdb_builder.build_content_block_index_unit(&mut zdb_writer, prog_rpt)?;
+        zdb_builder.build_content_block_index_unit(zdb_writer, prog_rpt)?;
         info!("done");
 
         info!("Building key block unit...");
-        zdb_builder.build_key_block_unit(&mut zdb_writer, prog_rpt)?;
+        zdb_builder.build_key_block_unit(zdb_writer, prog_rpt)?;
         info!("done");
 
         info!("Building key block index unit...");
-        zdb_builder.build_key_block_index_unit(&mut zdb_writer, prog_rpt)?;
+        zdb_builder.build_key_block_index_unit(zdb_writer, prog_rpt)?;
         info!("done");
 
+        if zdb_builder.config.bloom_filter {
+            info!("Building bloom filter unit...");
+            zdb_builder.build_bloom_filter_unit(zdb_writer)?;
+            info!("done");
+        }
+
+        if let Some(entry_meta) = &entry_meta {
+            info!("Building entry metadata unit...");
+            zdb_builder.build_entry_meta_unit(zdb_writer, entry_meta)?;
+            info!("done");
+        } else if let Some((source_entry_meta, tag_names)) = zdb_builder.source_entry_meta.take() {
+            info!("Building entry metadata unit...");
+            zdb_builder.write_entry_meta_unit(zdb_writer, &tag_names, |entry| if entry.union_members.is_empty() {
+                source_entry_meta.get(entry.position as usize).copied().unwrap_or_default()
+            } else {
+                EntryMetaExt::default()
+            })?;
+            info!("done");
+        }
+
+        if zdb_builder.config.source_map {
+            info!("Building source map unit...");
+            zdb_builder.build_source_map_unit(zdb_writer)?;
+            info!("done");
+        }
+
+        zdb_writer.flush()?;
         info!("Build completed");
 
-        Ok(())
+        Ok(zdb_builder)
+    }
+
+    /// Writes the header and all units, loading the entries from the source in the configuration.
+    fn build_from_source<W: Write+Seek>(config: &BuilderConfig, zdb_writer: &mut W, content_writer: Option<&mut BufWriter<File>>, output_path: Option<&Path>, sink: Option<&mut dyn EntrySink>, prog_rpt: Option<ProgressReportFn>) -> Result<ZDBBuilder> {
+        let mut zdb_builder = ZDBBuilder::new(config);
+        zdb_builder.output_path = output_path.map(Path::to_path_buf);
+
+        info!("Loading source: {}...", config.input_path);
+        let progress = ProgressOptions { reporter: prog_rpt, every_item: config.progress_every_record };
+
+        // Create appropriate data loader based on SourceType and build
+        let data_loader: Box<dyn DataLoader> = match config.data_source_format {
+            SourceType::MdictHtml => {
+                use crate::builder::mdict_source_loader::MDictSourceLoader;
+                let mut data_loader = MDictSourceLoader::new(&config.input_path, progress)?;
+                if !config.style_sheet_path.is_empty() {
+                    let style_sheet = std::fs::read_to_string(&config.style_sheet_path)?;
+                    if config.keep_compact {
+                        zdb_builder.set_compact_style_sheet(&style_sheet)?;
+                    } else {
+                        data_loader.set_compact_style_sheet(&style_sheet)?;
+                    }
+                }
+                Box::new(data_loader)
+            },
+            SourceType::Zdb => {
+                use crate::builder::zdb_loader::ZdbLoader;
+                let mut data_loader = ZdbLoader::new(&config.input_path, &config.device_id, &config.password, progress)?;
+                if config.keep_compact {
+                    let style_sheet = data_loader.input_reader.meta.db_info.style_sheet.clone();
+                    zdb_builder.set_compact_style_sheet(&style_sheet)?;
+                    data_loader.keep_compact();
+                }
+                Box::new(data_loader)
+            },
+            SourceType::Directory => {
+                use crate::builder::data_dir_loader::DataDirLoader;
+                Box::new(DataDirLoader::new(&config.input_path, &config.dir_scan, config.io_threads, progress)?)
+            },
+            _ => {
+                return Err(ZdbError::invalid_data_format(format!("Unsupported source format: {:?}", config.data_source_format)));
+            }
+        };
+        Self::build_loader(zdb_builder, zdb_writer, content_writer, data_loader, sink, prog_rpt)
+    }
+
+    /// Writes the header and all units, with the metadata and records of the data loader.
+    fn build_loader<W: Write+Seek, T: DataLoader>(
+        mut zdb_builder: ZDBBuilder,
+        zdb_writer: &mut W,
+        content_writer: Option<&mut BufWriter<File>>,
+        mut data_loader: T,
+        sink: Option<&mut dyn EntrySink>,
+        prog_rpt: Option<ProgressReportFn>
+    ) -> Result<ZDBBuilder> {
+        zdb_builder.ingest_records(data_loader.records())?;
+        let metadata = data_loader.metadata();
+        zdb_builder.warnings.extend(metadata.warnings);
+        if zdb_builder.config.default_sorting_locale.is_empty() {
+            zdb_builder.config.default_sorting_locale = metadata.locale_id;
+            zdb_builder.db_header.default_sorting_locale = zdb_builder.config.default_sorting_locale.clone();
+        }
+        // The labels of the source are kept unless a label file replaces them
+        zdb_builder.set_labels(&metadata.labels)?;
+        if zdb_builder.config.preview_skip_classes.is_empty() {
+            zdb_builder.db_header.preview_skip_classes = metadata.preview_skip_classes.join(" ");
+        }
+        Self::build_units(zdb_builder, zdb_writer, content_writer, data_loader, sink, prog_rpt)
+    }
+
+    /// Build a ZDB file from the configured data source into any seekable writer.
+    ///
+    /// Same as [`build_with_config`](Self::build_with_config), except that `output_file` is ignored
+    /// and the dictionary is written to `writer`, e.g. a `Cursor<Vec<u8>>` or an object storage stream.
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - Build configuration specifying the input and settings
+    /// * `writer` - Destination of the dictionary, written from its current position
+    /// * `prog_rpt` - Optional progress reporter callback function
+    ///
+    /// # Returns
+    ///
+    /// Returns the entry count and the warnings of the build.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if building fails, or if `write_unit_digests` is set, since the digests
+    /// are computed by reading back the written units. Use [`build_to_buffer`](Self::build_to_buffer)
+    /// or [`build_with_config`](Self::build_with_config) for files with unit digests.
+    pub fn build_to_writer<W: Write+Seek>(config: &BuilderConfig, writer: &mut W, prog_rpt: Option<ProgressReportFn>) -> Result<BuildReport> {
+        if config.write_unit_digests {
+            return Err(ZdbError::invalid_parameter("Unit digests require a readable output, use build_to_buffer or build_with_config"));
+        }
+        let zdb_builder = Self::build_from_source(config, writer, None, None, None, prog_rpt)?;
+        Ok(zdb_builder.report())
+    }
+
+    /// Build a ZDB file from the configured data source into memory.
+    ///
+    /// # Returns
+    ///
+    /// Returns the content of the dictionary file, including unit digests if enabled.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use mdx::builder::{ZDBBuilder, BuilderConfig, SourceType};
+    ///
+    /// # fn main() -> mdx::Result<()> {
+    /// let mut config = BuilderConfig::default();
+    /// config.input_path = "resources".to_string();
+    /// config.data_source_format = SourceType::Directory;
+    /// let data = ZDBBuilder::build_to_buffer(&config, None)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn build_to_buffer(config: &BuilderConfig, prog_rpt: Option<ProgressReportFn>) -> Result<Vec<u8>> {
+        let mut cursor = Cursor::new(Vec::new());
+        let zdb_builder = Self::build_from_source(config, &mut cursor, None, None, None, prog_rpt)?;
+        if zdb_builder.config.write_unit_digests {
+            zdb_builder.write_unit_digests(&mut cursor)?;
+        }
+        Ok(cursor.into_inner())
+    }
+
+    /// Build a ZDB file from records supplied by the caller into any seekable writer.
+    ///
+    /// The input settings of `config` (`input_path`, `data_source_format`) are ignored, the content
+    /// of each record is loaded with `data_loader`. Together with an in-memory writer this builds a
+    /// dictionary without touching the filesystem.
+    ///
+    /// # Returns
+    ///
+    /// Returns the entry count and the warnings of the build.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if building fails, or if `write_unit_digests` is set and the writer
+    /// can't be read back, see [`build_to_writer`](Self::build_to_writer).
+    pub fn build_records_to_writer<W: Write+Seek, T: DataLoader, R: IntoIterator<Item = ZdbRecord>>(
+        config: &BuilderConfig,
+        writer: &mut W,
+        data_loader: T,
+        entry_records: R,
+        prog_rpt: Option<ProgressReportFn>
+    ) -> Result<BuildReport> {
+        if config.write_unit_digests {
+            return Err(ZdbError::invalid_parameter("Unit digests require a readable output, use build_to_buffer or build_with_config"));
+        }
+        let mut zdb_builder = ZDBBuilder::new(config);
+        zdb_builder.ingest_records(entry_records.into_iter().map(Ok))?;
+        let zdb_builder = Self::build_units(zdb_builder, writer, None, data_loader, None, prog_rpt)?;
+        Ok(zdb_builder.report())
+    }
+
+    /// Build a ZDB file from the entries of a data loader supplied by the caller.
+    ///
+    /// Lets other crates add source formats, see [`DataLoader`]. The input settings of `config`
+    /// (`input_path`, `data_source_format`) are ignored, the sorting locale and labels of the
+    /// source are used unless `config` sets them, see [`DataLoader::metadata`].
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - Build configuration specifying the output file and settings
+    /// * `data_loader` - Lists the records of the entries and loads their content
+    /// * `prog_rpt` - Optional progress reporter callback function
+    ///
+    /// # Returns
+    ///
+    /// Returns the entry count and the warnings of the build.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the config is invalid, the loader fails or building fails,
+    /// in which case no output file is written.
+    pub fn build(config: &BuilderConfig, data_loader: Box<dyn DataLoader>, prog_rpt: Option<ProgressReportFn>) -> Result<BuildReport> {
+        let problems = config.settings_problems();
+        if !problems.is_empty() {
+            return Err(ZdbError::invalid_parameter(format!("Invalid builder config:\n{}", problems.join("\n"))));
+        }
+        let mut zdb_builder = ZDBBuilder::new(config);
+        zdb_builder.output_path = Some(PathBuf::from(&config.output_file));
+        Self::write_to_file(config, |zdb_writer, content_writer| Self::build_loader(zdb_builder, zdb_writer, content_writer, data_loader, None, prog_rpt))
     }
 
     /// Build ZDB file from configured data source
@@ -568,6 +1921,11 @@ impl ZDBBuilder {
     ///   - Return `true` from the callback to cancel the build
     ///   - Return `false` to continue building
     ///
+    /// # Returns
+    ///
+    /// Returns a [`BuildReport`] with the entry count and the warnings of the build,
+    /// e.g. files with undecodable names or entries without content.
+    ///
     /// # Supported Source Formats
     ///
     /// - `MdictHtml`: MDX dictionary files with HTML content
@@ -617,44 +1975,195 @@ impl ZDBBuilder {
     /// - Source format is not supported
     /// - Data corruption is detected
     /// - Compression/encryption fails
-    pub fn build_with_config(config: &BuilderConfig, prog_rpt: Option<ProgressReportFn>) -> Result<()> {
-        use std::fs::File;
-        use std::io::BufWriter;
-        
-        let mut zdb_builder = ZDBBuilder::new(config);
-        let mut zdb_writer = BufWriter::new(File::create(&zdb_builder.config.output_file)?);
-        zdb_builder.build_db_header(&mut zdb_writer)?;
+    ///
+    /// The output is written to a temporary file in the destination directory and renamed
+    /// over `output_file` on success, so a failed or cancelled build leaves no partial file.
+    pub fn build_with_config(config: &BuilderConfig, prog_rpt: Option<ProgressReportFn>) -> Result<BuildReport> {
+        config.validate()
+            .map_err(|problems| ZdbError::invalid_parameter(format!("Invalid builder config:\n{}", problems.join("\n"))))?;
+        Self::write_to_file(config, |zdb_writer, content_writer| Self::build_from_source(config, zdb_writer, content_writer, Some(Path::new(&config.output_file)), None, prog_rpt))
+    }
 
-        info!("Loading source: {}...", config.input_path);
+    /// Build ZDB file from configured data source, passing every entry to `sink` as it is written.
+    ///
+    /// Same as [`build_with_config`](Self::build_with_config), with side outputs like a word list
+    /// or an external search index produced in the same pass, see [`EntrySink`]. The content
+    /// blocks of a ZDB source are never copied as stored, since the sink needs the content.
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - Build configuration specifying input/output paths and settings
+    /// * `sink` - Receives each entry and its content in key order
+    /// * `prog_rpt` - Optional progress reporter callback function
+    ///
+    /// # Returns
+    ///
+    /// Returns the entry count and the warnings of the build.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if building fails or the sink returns an error, in which case no
+    /// output file is written.
+    pub fn build_with_sink(config: &BuilderConfig, sink: &mut dyn EntrySink, prog_rpt: Option<ProgressReportFn>) -> Result<BuildReport> {
+        config.validate()
+            .map_err(|problems| ZdbError::invalid_parameter(format!("Invalid builder config:\n{}", problems.join("\n"))))?;
+        Self::write_to_file(config, |zdb_writer, content_writer| Self::build_from_source(config, zdb_writer, content_writer, Some(Path::new(&config.output_file)), Some(sink), prog_rpt))
+    }
+
+    /// Rebuilds a ZDB file with keys sorted for another locale.
+    ///
+    /// Fixes dictionaries built with the wrong sort order. Every setting is taken from the
+    /// source: content type, key normalization, key digest, compression, encryption,
+    /// block checksum and nonces, compacted content, union entries, labels, media types,
+    /// preview skip classes, the companion content file, the Bloom filter and the entry
+    /// metadata. The source map isn't kept, since it refers to the lines of the original source.
+    ///
+    /// The content blocks of a V3 source are copied as stored, and only the key units and
+    /// the sorting locale in the header are rebuilt. If the new order differs, the key unit
+    /// stores the content length of every entry, see [`KEY_UNIT_CONTENT_LENGTHS`], and union
+    /// entries get their content in a block after the copied ones. The content of V1/V2
+    /// sources, of sources not in UTF-8, or of sources whose blocks use several compression
+    /// methods, is rewritten in UTF-8.
+    ///
+    /// # Arguments
+    ///
+    /// * `source_file` - The ZDB file to re-sort, encrypted files must not require a license
+    /// * `output_file` - Path of the re-sorted file, may be `source_file` to replace it
+    /// * `locale_id` - BCP-47 locale of the new sort order, e.g. `de-u-co-phonebk`
+    /// * `prog_rpt` - Optional progress reporter callback function
+    ///
+    /// # Returns
+    ///
+    /// Returns the entry count and the warnings of the build.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the locale isn't supported or the source can't be read.
+    pub fn resort(source_file: &str, output_file: &str, locale_id: &str, prog_rpt: Option<ProgressReportFn>) -> Result<BuildReport> {
+        use crate::builder::zdb_loader::ZdbLoader;
 
+        shared_collator(locale_id)
+            .map_err(|e| ZdbError::invalid_parameter(format!("Unsupported sorting locale \"{}\": {}", locale_id, e)))?;
+        let mut data_loader = ZdbLoader::new(source_file, "", "", prog_rpt)?;
+        let source = &mut data_loader.input_reader;
+        let db_info = source.meta.db_info.clone();
+        let mut config = BuilderConfig {
+            input_path: source_file.to_string(),
+            output_file: output_file.to_string(),
+            data_source_format: SourceType::Zdb,
+            content_type: format!("{:?}", db_info.content_type),
+            default_sorting_locale: locale_id.to_string(),
+            key_normalization: db_info.key_normalization,
+            key_digest: DigestAlgorithm::from_name(&db_info.key_digest)?,
+            bloom_filter: source.has_bloom_filter(),
+            merge_duplicate_keys: data_loader.union_entry_count > 0,
+            keep_compact: db_info.is_compact_format,
+            content_block_layout: ContentBlockLayout::from_header_value(&db_info.content_block_layout).unwrap_or_default(),
+            media_types: db_info.media_types.iter()
+                .map(|(extension, mime)| (extension.clone(), MediaTypeConfig { mime: mime.clone(), no_compress: false }))
+                .collect(),
+            preview_skip_classes: db_info.preview_skip_classes.clone(),
+            content_file_extension: Path::new(&db_info.content_file).extension()
+                .map(|extension| extension.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+            ..Default::default()
+        };
+        if !source.content_block_indexes().is_empty() {
+            let first_block = source.debug_block(0)?;
+            config.compression_method = first_block.compression;
+            config.encryption_method = first_block.encryption;
+            config.per_block_nonce = first_block.flags & BLOCK_FLAG_OFFSET_NONCE != 0;
+            config.block_checksum = ChecksumAlgorithm::from_block_flags(first_block.flags)?;
+        }
+        let mut zdb_builder = ZDBBuilder::new(&config);
+        zdb_builder.output_path = Some(PathBuf::from(output_file));
+        zdb_builder.reorder_copied_content = true;
+        if source.has_entry_meta() {
+            let entry_meta = (0..source.get_entry_count())
+                .map(|entry_no| source.get_entry_meta_ext(entry_no as EntryNo).map(Option::unwrap_or_default))
+                .collect::<Result<Vec<_>>>()?;
+            zdb_builder.source_entry_meta = Some((entry_meta, source.entry_meta_tag_names().to_vec()));
+        }
+        if config.keep_compact {
+            zdb_builder.set_compact_style_sheet(&db_info.style_sheet)?;
+            data_loader.keep_compact();
+        }
+        zdb_builder.set_labels(&db_info.labels)?;
+        zdb_builder.ingest_records(data_loader.records())?;
+        Self::write_to_file(&config, |zdb_writer, content_writer| Self::build_units(zdb_builder, zdb_writer, content_writer, data_loader, None, prog_rpt))
+    }
 
-        // Create appropriate data loader based on SourceType and build
-        match config.data_source_format {
-            SourceType::MdictHtml => {
-                use crate::builder::mdict_source_loader::MDictSourceLoader;
-                let (data_loader, entry_records) = MDictSourceLoader::new(&config.input_path, prog_rpt)?;
-                Self::build_with_data_loader(zdb_builder, zdb_writer, data_loader, entry_records, prog_rpt)
-            },
-            SourceType::Zdb => {
-                use crate::builder::zdb_loader::ZdbLoader;
-                let (data_loader, entry_records) = ZdbLoader::new(&config.input_path, &config.device_id, &config.password, prog_rpt)?;
-                
-                // Update sorting locale if empty and source is ZDB
-                if zdb_builder.config.default_sorting_locale.is_empty() {
-                    zdb_builder.config.default_sorting_locale = 
-                        data_loader.input_reader.meta.db_info.locale_id.clone();
-                }
-                
-                Self::build_with_data_loader(zdb_builder, zdb_writer, data_loader, entry_records, prog_rpt)
-            },
-            SourceType::Directory => {
-                use crate::builder::data_dir_loader::DataDirLoader;
-                let (data_loader, entry_records) = DataDirLoader::new(&config.input_path, prog_rpt)?;
-                Self::build_with_data_loader(zdb_builder, zdb_writer, data_loader, entry_records, prog_rpt)
-            },
-            _ => {
-                Err(ZdbError::invalid_data_format(format!("Unsupported source format: {:?}", config.data_source_format)))
+    /// Writes a file built by `build` to a temporary file in the destination directory or
+    /// `temp_dir`, which replaces `output_file` once the build succeeded. The companion
+    /// content file, if any, is written and replaced the same way.
+    fn write_to_file<F: FnOnce(&mut BufWriter<File>, Option<&mut BufWriter<File>>) -> Result<ZDBBuilder>>(config: &BuilderConfig, build: F) -> Result<BuildReport> {
+        // The file is written to a temporary file and only replaces the output file once the build succeeded,
+        // it's opened for reading as well since unit digests are computed from the written data.
+        // Returning early on error drops the output, which removes the temporary file.
+        let temp_dir = (!config.temp_dir.is_empty()).then(|| Path::new(&config.temp_dir));
+        let (output, output_file) = AtomicOutput::create_in(&config.output_file, temp_dir)?;
+        let mut zdb_writer = BufWriter::with_capacity(config.write_buffer_size, output_file);
+        let mut content_output = match config.content_file_path() {
+            Some(content_file) => {
+                let (content_output, content_file) = AtomicOutput::create_in(content_file, temp_dir)?;
+                let mut content_writer = BufWriter::with_capacity(config.write_buffer_size, content_file);
+                content_writer.write_all(CONTENT_FILE_MAGIC)?;
+                Some((content_output, content_writer))
             }
+            None => None,
+        };
+        let zdb_builder = build(&mut zdb_writer, content_output.as_mut().map(|(_, content_writer)| content_writer))?;
+        let content_output = match content_output {
+            Some((content_output, content_writer)) => {
+                let content_file = content_writer.into_inner().map_err(|e| e.into_error())?;
+                content_file.sync_all()?;
+                Some(content_output)
+            }
+            None => None,
+        };
+
+        let mut file = zdb_writer.into_inner().map_err(|e| e.into_error())?;
+        if zdb_builder.config.write_unit_digests {
+            info!("Writing unit digests...");
+            zdb_builder.write_unit_digests(&mut file)?;
+            info!("done");
         }
+        file.sync_all()?;
+        drop(file);
+        // The companion file is in place before the file naming it
+        if let Some(content_output) = content_output {
+            content_output.commit()?;
+        }
+        output.commit()?;
+        Ok(zdb_builder.report())
+    }
+}
+
+// Validating the sorting locale needs compiled collation data
+#[cfg(all(test, any(feature = "icu", feature = "rust-icu")))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_config_from_json() {
+        let input_path = std::env::temp_dir().to_string_lossy().to_string();
+        let json = format!(r#"{{"input_path": {:?}, "output_file": "out.mdx", "data_source_format": 114,
+            "compression_method": "lz4", "encryption_method": 0, "default_sorting_locale": "en", "compression_levels": {{"Lz4": 12}}}}"#, input_path);
+        let config = BuilderConfig::from_json(&json).unwrap();
+        assert_eq!(config.data_source_format, SourceType::Directory);
+        assert_eq!(config.compression_method, CompressionMethod::Lz4);
+        assert_eq!(config.encryption_method, EncryptionMethod::None);
+        assert_eq!(config.compression_levels.get(&CompressionMethod::Lz4), Some(&12));
+        assert!(config.encrypt_key_units && config.encrypt_content_units);
+        assert_eq!(config.preferred_key_block_size, BuilderConfig::default().preferred_key_block_size);
+        assert_eq!(serde_json::to_value(&config).unwrap()["data_source_format"], "Directory");
+
+        let error = BuilderConfig::from_json(r#"{"data_source_format": 108}"#).unwrap_err().to_string();
+        assert!(error.contains("unknown source type \"108\"") && error.contains("MdictHtml (107)"), "{}", error);
+
+        let config: BuilderConfig = serde_json::from_str(r#"{"content_type": "Pdf", "preferred_key_block_size": 0, "write_buffer_size": 0}"#).unwrap();
+        let problems = config.validate().unwrap_err();
+        assert_eq!(problems.len(), 5, "{:?}", problems);
+        assert!(BuilderConfig::default().validate().unwrap_err().iter().all(|problem| problem.contains("_path") || problem.contains("output_file")));
     }
 }
@@ -1,21 +1,39 @@
 use std::fs::File;
 use std::io::BufReader;
 
-use crate::builder::data_loader::{DataLoader, ZdbRecord};
-use crate::utils::io_utils::windows_path_to_unix_path;
+use crate::builder::data_loader::{DataLoader, SourceMetadata, ZdbRecord};
+use crate::utils::mdd_key;
 use crate::storage::key_block::EntryNo;
+use crate::storage::meta_unit::ZdbVersion;
 use crate::readers::mdx_reader::MdxReader;
-use crate::utils::progress_report::{ProgressReportFn, ProgressState};
+use crate::utils::progress_report::{ProgressOptions, ProgressState};
 use crate::readers::zdb_reader::ZdbReader;
 use crate::{Result, ZdbError};
 
 
 pub struct ZdbLoader{
     pub input_reader: ZdbReader<BufReader<File>>,
+    /// Number of union entries of the source, they are left out of the records
+    pub union_entry_count: u64,
     compact_stylesheet: Vec<(String, String)>,
+    /// Records not handed out by `records` yet
+    records: Vec<ZdbRecord>,
 }
 
 impl DataLoader for ZdbLoader{
+    fn metadata(&mut self) -> SourceMetadata {
+        let db_info = &self.input_reader.meta.db_info;
+        SourceMetadata {
+            locale_id: db_info.locale_id.clone(),
+            labels: db_info.labels.clone(),
+            preview_skip_classes: db_info.preview_skip_classes.clone(),
+            warnings: Vec::new(),
+        }
+    }
+
+    fn records(&mut self) -> Box<dyn Iterator<Item = Result<ZdbRecord>> + '_> {
+        Box::new(std::mem::take(&mut self.records).into_iter().map(Ok))
+    }
 
     fn load_data(&mut self, entry: &ZdbRecord) -> Result<Vec<u8>> {
         let key_index = self.input_reader.get_index(entry.position as EntryNo)?;
@@ -31,23 +49,43 @@ impl DataLoader for ZdbLoader{
             self.input_reader.get_data(&key_index, false)
         }
     }
+
+    fn stored_source(&mut self) -> Option<&mut ZdbReader<BufReader<File>>> {
+        // Expanded content differs from the stored content
+        if self.compact_stylesheet.is_empty() {
+            Some(&mut self.input_reader)
+        } else {
+            None
+        }
+    }
 }
 
 
 
 impl ZdbLoader{
-    pub fn new(source_file:&str, device_id:&str, license_key:&str, prog_rpt: Option<ProgressReportFn>) -> Result<(Self, Vec<ZdbRecord>)> {
+    /// Loads the content as stored instead of expanding compacted content with the stylesheet of the source.
+    pub fn keep_compact(&mut self) {
+        self.compact_stylesheet.clear();
+    }
+
+    /// Reads the entry list of a ZDB file.
+    ///
+    /// Progress is reported in entries, and in bytes of the content the entries read so far span.
+    /// The records are handed out by [`DataLoader::records`].
+    pub fn new(source_file:&str, device_id:&str, license_key:&str, progress: impl Into<ProgressOptions>) -> Result<Self> {
         let mut zdb_reader = ZdbReader::<BufReader<File>>::from_file(source_file, device_id, license_key)?;
         let mut entry_records = Vec::<ZdbRecord>::with_capacity(zdb_reader.get_entry_count() as usize);
-        let mut progress_state = ProgressState::new("ZdbLoader::new",zdb_reader.get_entry_count() as u64, 5, prog_rpt);
+        let mut progress_state = ProgressState::with_options("ZdbLoader::new", "Reading source index", zdb_reader.get_entry_count() as u64, 5, progress.into());
+        progress_state.total_bytes = zdb_reader.meta.content_data_total_length;
         let compact_stylesheet = MdxReader::load_compact_stylesheet(&zdb_reader.meta.db_info.style_sheet)?;
     
+        let mut union_entry_count = 0;
         let mut i=0u64;
         while i < zdb_reader.get_entry_count() {
             let key_index = zdb_reader.get_index(i as EntryNo)?;
             let rec = ZdbRecord {
                 key: if zdb_reader.meta.db_info.is_mdd && key_index.key.starts_with("\\") {
-                    windows_path_to_unix_path(&key_index.key)
+                    mdd_key::normalize(&key_index.key, ZdbVersion::V3)
                 } else {
                     key_index.key.clone()
                 },
@@ -56,17 +94,28 @@ impl ZdbLoader{
                 content: String::new(), //unused for zdb    
                 content_len: zdb_reader.get_content_length(i as EntryNo)?, //probably need to be re-calculated again later due to encoding changes
                 line_no: 0, //unused for zdb
+                content_type: None,
+                union_members: Vec::new(),
+                no_compress: false,
             };
-            entry_records.push(rec);
             i += 1;
-    
-            if progress_state.report(i) {
+            // Union entries are recreated by the builder if merging is enabled, since the entry numbers may change.
+            // A union entry is always followed by its members, so only entries followed by the same key are checked.
+            let followed_by_same_key = i < zdb_reader.get_entry_count() && zdb_reader.get_index(i as EntryNo)?.key == key_index.key;
+            if followed_by_same_key && zdb_reader.is_union_entry(&key_index)? {
+                union_entry_count += 1;
+                continue;
+            }
+            if progress_state.report_item(i, key_index.content_offset_in_source + rec.content_len, &rec.key) {
                 return Err(ZdbError::user_interrupted());
             }
+            entry_records.push(rec);
         }
-        Ok((ZdbLoader{
+        Ok(ZdbLoader{
             input_reader: zdb_reader,
+            union_entry_count,
             compact_stylesheet,
-        }, entry_records))
+            records: entry_records,
+        })
     }
 }
\ No newline at end of file
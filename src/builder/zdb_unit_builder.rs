@@ -3,17 +3,88 @@
 //! This module provides the [`ZdbUnitBuilder`] struct which handles the low-level
 //! construction of ZDB file units (key blocks, content blocks, and their indexes).
 //! It manages block writing, compression, encryption, and metadata tracking.
+//!
+//! [`ZDBBuilder`](crate::builder::ZDBBuilder) writes every unit of a file this way, and the
+//! same workflow is available to write units into another container:
+//!
+//! 1. [`write_unit_begin`](ZdbUnitBuilder::write_unit_begin) writes a placeholder unit info section.
+//! 2. [`output_block`](ZdbUnitBuilder::output_block) writes each block of the data section,
+//!    compressed and encrypted as configured.
+//! 3. [`write_unit_end`](ZdbUnitBuilder::write_unit_end) fills in the unit info section and
+//!    writes the data-info section after the blocks.
+//!
+//! The following must hold for the unit to be readable:
+//!
+//! - Nothing else is written to the writer between the begin and the end of a unit, since
+//!   the length of the data section is the sum of the written blocks.
+//! - The writer can seek back to the start of the unit until its end is written, and is left
+//!   at the end of the unit.
+//! - Each unit is begun once and ended once. A builder can be reused for the next unit after
+//!   [`write_unit_end`](ZdbUnitBuilder::write_unit_end).
+//! - Blocks are decoded with the crypto key and encryption method of the configuration, and
+//!   blocks encrypted with [`per_block_nonce`](BuilderConfig::per_block_nonce) depend on their
+//!   offset in the writer, so such units can't be moved without re-encrypting them.
+//!
+//! The readers of this crate only accept the unit types of [`UnitType`], in the order written
+//! by `ZDBBuilder`. Units in another container, or carrying custom data, are read back with
+//! [`UnitInfoSection::from_reader`] and [`StorageBlock::decode_block`], and
+//! [`write_unit_end_with_data_info`](ZdbUnitBuilder::write_unit_end_with_data_info) records a
+//! data-info section of any serializable type.
+//!
+//! # Examples
+//!
+//! ```
+//! use std::io::{Cursor, Read, Seek, SeekFrom};
+//! use byteorder::{BigEndian, ReadBytesExt};
+//! use mdx::builder::{BuilderConfig, ZdbUnitBuilder};
+//! use mdx::crypto::encryption::EncryptionMethod;
+//! use mdx::storage::StorageBlock;
+//! use mdx::storage::unit_base::{UnitInfoSection, UnitType};
+//!
+//! # fn main() -> mdx::Result<()> {
+//! let mut config = BuilderConfig::default();
+//! config.encryption_method = EncryptionMethod::None;
+//! let mut writer = Cursor::new(Vec::new());
+//! let mut unit_builder = ZdbUnitBuilder::from_config(&config);
+//! unit_builder.write_unit_begin(&mut writer, UnitType::Content)?;
+//! unit_builder.output_block(&mut writer, b"first block")?;
+//! unit_builder.output_block(&mut writer, b"second block")?;
+//! unit_builder.write_unit_end(&mut writer, 2)?;
+//!
+//! writer.seek(SeekFrom::Start(0))?;
+//! let info = UnitInfoSection::from_reader(&mut writer)?;
+//! assert_eq!((info.unit_type, info.block_count), (UnitType::Content, 2));
+//! let block_offset = writer.stream_position()?;
+//! let original_length = writer.read_u32::<BigEndian>()?;
+//! let mut block = vec![0; writer.read_u32::<BigEndian>()? as usize];
+//! writer.read_exact(&mut block)?;
+//! let block = StorageBlock::decode_block(&mut block, config.crypto_key.expose(), original_length, block_offset)?;
+//! assert_eq!(block.data, b"first block");
+//! # Ok(())
+//! # }
+//! ```
 
 use std::io::{Seek, SeekFrom, Write};
 
+use serde::Serialize;
+
 use crate::builder::zdb_builder::BuilderConfig;
+use crate::storage::bloom_filter_unit::{BloomFilterDataInfo, BLOOM_FILTER_HASH_COUNT};
 use crate::storage::content_block_index_unit::ContentBlockIndexDataInfo;
 use crate::storage::content_unit::ContentDataInfo;
-use crate::storage::key_block_index_unit::KeyBlockIndexDataInfo;
+use crate::storage::entry_meta_unit::{EntryMetaDataInfo, ENTRY_META_RECORD_SIZE};
+use crate::storage::key_block_index_unit::{KeyBlockIndexDataInfo, KEY_BLOCK_INDEX_FRONT_CODED};
 use crate::storage::key_unit::KeyDataInfo;
+use crate::storage::source_map_unit::{SourceMapDataInfo, SOURCE_MAP_RECORD_SIZE};
 use crate::storage::storage_block::StorageBlock;
+use crate::utils::icu_wrapper::BACKEND;
 use crate::storage::unit_base::{write_data_info_section, UnitInfoSection, UnitType};
-use crate::Result;
+use crate::crypto::encryption::EncryptionMethod;
+use crate::utils::compression::CompressionMethod;
+use crate::{Result, ZdbError};
+
+/// Encoding of the keys and text content written by the builder, recorded in the data info of the units.
+pub(crate) const OUTPUT_ENCODING: &str = "utf-8";
 
 /// Builder for constructing individual units in a ZDB file.
 ///
@@ -64,7 +135,10 @@ impl ZdbUnitBuilder {
     ///
     /// Returns an error if writing to the writer fails.
     pub fn write_unit_begin<W: Write+Seek>(&mut self, writer: &mut W, unit_type: UnitType) -> Result<()> {
-        self.unit_info.unit_type = unit_type;
+        if unit_type == UnitType::Invalid {
+            return Err(ZdbError::invalid_parameter("Can't write a unit of the invalid type"));
+        }
+        self.unit_info = UnitInfoSection { unit_type, ..Default::default() };
         self.unit_info_pos = writer.seek(SeekFrom::Current(0))?;
         self.unit_info.to_writer(writer)?;
         Ok(())
@@ -73,7 +147,7 @@ impl ZdbUnitBuilder {
     /// Writes a data block to the writer with compression and encryption.
     ///
     /// This method compresses and encrypts the block data according to the
-    /// builder configuration, then writes it to the writer. It updates the
+    /// configuration of the unit type, then writes it to the writer. It updates the
     /// unit info section with the block count and data lengths.
     ///
     /// # Arguments
@@ -89,13 +163,47 @@ impl ZdbUnitBuilder {
     ///
     /// Returns an error if compression, encryption, or writing fails.
     pub fn output_block<W: Write+Seek>(&mut self, writer: &mut W, block_data: &[u8]) -> Result<u64> {
-        let block_data_len = StorageBlock::to_writer(writer, &block_data, &self.config.crypto_key, self.config.compression_method, self.config.encryption_method)?;
+        self.output_block_with_compression(writer, block_data, self.compression_method())
+    }
+
+    /// Writes a data block like [`output_block`](Self::output_block), with another compression method than the configured one.
+    ///
+    /// Every block records its compression method, so blocks of one unit can differ.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if compression, encryption, or writing fails.
+    pub fn output_block_with_compression<W: Write+Seek>(&mut self, writer: &mut W, block_data: &[u8], compression_method: CompressionMethod) -> Result<u64> {
+        let compression_level = self.config.compression_levels.get(&compression_method).copied();
+        let block_data_len = StorageBlock::to_writer_with_level(writer, block_data, &self.config.crypto_key, compression_method, compression_level,
+            self.encryption_method(), self.config.per_block_nonce, self.config.block_checksum)?;
         self.unit_info.block_count += 1;
         self.unit_info.data_section_length += block_data_len as u64;
         self.unit_info.orig_data_section_length += block_data.len() as u64;
         Ok(block_data_len)
     }
 
+    /// Writes a block copied as stored from another file, see [`StorageBlock::copy_to_writer`].
+    ///
+    /// # Arguments
+    ///
+    /// * `writer` - The writer to output to
+    /// * `stored_block` - The block as stored, starting with its length fields
+    /// * `original_length` - Length of the uncompressed block data
+    /// * `source_offset` - Offset of the block in the source file
+    /// * `source_key` - Crypto key of the source file
+    ///
+    /// # Returns
+    ///
+    /// Returns the number of bytes written.
+    pub fn output_stored_block<W: Write+Seek>(&mut self, writer: &mut W, stored_block: &[u8], original_length: u64, source_offset: u64, source_key: &[u8]) -> Result<u64> {
+        let block_data_len = StorageBlock::copy_to_writer(writer, stored_block, source_offset, source_key, &self.config.crypto_key, self.encryption_method(), self.config.per_block_nonce, self.config.block_checksum)?;
+        self.unit_info.block_count += 1;
+        self.unit_info.data_section_length += block_data_len;
+        self.unit_info.orig_data_section_length += original_length;
+        Ok(block_data_len)
+    }
+
     /// Finalizes the unit by writing the data info section.
     ///
     /// This method:
@@ -110,47 +218,104 @@ impl ZdbUnitBuilder {
     ///
     /// # Errors
     ///
-    /// Returns an error if seeking or writing fails.
+    /// Returns an error if no unit was begun, or if seeking or writing fails.
     pub fn write_unit_end<W: Write+Seek>(&mut self, writer: &mut W, count: u64) -> Result<()> {
-        // Rewrite unit info with correct data
-        let data_info_pos = writer.seek(SeekFrom::Current(0))?;
-        writer.seek(SeekFrom::Start(self.unit_info_pos))?;
-        self.unit_info.to_writer(writer)?; 
-        writer.seek(SeekFrom::Start(data_info_pos))?;
-        let encoding = "utf-8".to_string();
+        let encoding = OUTPUT_ENCODING.to_string();
         match self.unit_info.unit_type {
             UnitType::KeyBlockIndex => {
                 let data_info = KeyBlockIndexDataInfo{
                     block_count: count as u32,
-                    encoding: encoding,
+                    encoding,
                     locale_id: self.config.default_sorting_locale.clone(),
+                    minor_version: if self.config.front_coded_key_index { KEY_BLOCK_INDEX_FRONT_CODED } else { 0 },
                 };
-                write_data_info_section(writer, &data_info, &self.config.crypto_key, self.config.compression_method, self.config.encryption_method)?;
+                self.write_unit_end_with_data_info(writer, &data_info)
             }
             UnitType::Key => {
                 let data_info = KeyDataInfo{
                     key_count: count,
-                    encoding: encoding,
+                    encoding,
                     locale_id: self.config.default_sorting_locale.clone(),
+                    minor_version: 0,
                 };
-                write_data_info_section(writer, &data_info, &self.config.crypto_key, self.config.compression_method, self.config.encryption_method)?;
+                self.write_unit_end_with_data_info(writer, &data_info)
             }
             UnitType::ContentBlockIndex => {
                 let data_info = ContentBlockIndexDataInfo{
                     record_count: count,
-                    encoding: encoding,
+                    encoding,
                 };
-                write_data_info_section(writer, &data_info, &self.config.crypto_key, self.config.compression_method, self.config.encryption_method)?;
+                self.write_unit_end_with_data_info(writer, &data_info)
             }
             UnitType::Content => {
                 let data_info = ContentDataInfo{
                     record_count: count,
-                    encoding: encoding,
+                    encoding,
+                };
+                self.write_unit_end_with_data_info(writer, &data_info)
+            }
+            UnitType::BloomFilter => {
+                let data_info = BloomFilterDataInfo{
+                    key_count: count,
+                    hash_count: BLOOM_FILTER_HASH_COUNT,
+                    sort_key_backend: BACKEND.to_string(),
+                };
+                self.write_unit_end_with_data_info(writer, &data_info)
+            }
+            UnitType::EntryMeta => {
+                let data_info = EntryMetaDataInfo{
+                    entry_count: count,
+                    record_size: ENTRY_META_RECORD_SIZE,
+                    tag_names: String::new(),
                 };
-                write_data_info_section(writer, &data_info, &self.config.crypto_key, self.config.compression_method, self.config.encryption_method)?;
+                self.write_unit_end_with_data_info(writer, &data_info)
             }
-            _ => {}
+            UnitType::SourceMap => {
+                let data_info = SourceMapDataInfo{
+                    entry_count: count,
+                    record_size: SOURCE_MAP_RECORD_SIZE,
+                    source_file: String::new(),
+                };
+                self.write_unit_end_with_data_info(writer, &data_info)
+            }
+            UnitType::Invalid => Err(ZdbError::invalid_parameter("write_unit_end called without write_unit_begin")),
         }
-        Ok(())
+    }
+
+    /// Finalizes the unit like [`write_unit_end`](Self::write_unit_end), with a data info
+    /// section of the caller's choice.
+    ///
+    /// The data info is serialized to XML and written as a block, compressed and encrypted
+    /// like the data blocks.
+    ///
+    /// # Arguments
+    ///
+    /// * `writer` - The writer to finalize the unit in
+    /// * `data_info` - The data info of the unit
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if no unit was begun, if the data info can't be serialized, or if
+    /// seeking or writing fails.
+    pub fn write_unit_end_with_data_info<W: Write+Seek, D: Serialize>(&mut self, writer: &mut W, data_info: &D) -> Result<()> {
+        if self.unit_info.unit_type == UnitType::Invalid {
+            return Err(ZdbError::invalid_parameter("write_unit_end called without write_unit_begin"));
+        }
+        // Rewrite unit info with correct data
+        let data_info_pos = writer.seek(SeekFrom::Current(0))?;
+        writer.seek(SeekFrom::Start(self.unit_info_pos))?;
+        self.unit_info.to_writer(writer)?; 
+        writer.seek(SeekFrom::Start(data_info_pos))?;
+        write_data_info_section(writer, data_info, &self.config.crypto_key, self.compression_method(), self.encryption_method(), self.config.per_block_nonce, self.config.block_checksum)
+    }
+
+    /// Compression method of the blocks of the current unit, see [`BuilderConfig::unit_compression_method`].
+    pub fn compression_method(&self) -> CompressionMethod {
+        self.config.unit_compression_method(self.unit_info.unit_type)
+    }
+
+    /// Encryption method of the blocks of the current unit, see [`BuilderConfig::unit_encryption_method`].
+    pub fn encryption_method(&self) -> EncryptionMethod {
+        self.config.unit_encryption_method(self.unit_info.unit_type)
     }
 }
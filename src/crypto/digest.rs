@@ -5,6 +5,11 @@
 //! - Creating checksums for data integrity
 //! - Computing fast hashes for lookups
 //!
+//! The fast hash used for key derivation and for finding identical content while
+//! building is selectable through [`DigestAlgorithm`]. BLAKE3 is available with the
+//! `blake3` feature. It is slower than [`fast_hash_digest`] but collision resistant,
+//! see `benches/digest.rs`.
+//!
 //! # Examples
 //!
 //! ```
@@ -15,11 +20,61 @@
 //! assert_eq!(hash.len(), 16); // 128-bit hash
 //! ```
 
+use std::io::Read;
+
 use ripemd128::{Digest, Ripemd128};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
 use xxhash_rust::xxh64::Xxh64;
 
 use crate::{Result, ZdbError};
 
+/// Fast hash algorithm used to derive crypto keys and to find identical content.
+///
+/// The algorithm of a file is recorded in the `KeyDigest` header attribute.
+/// Files without the attribute use [`DigestAlgorithm::FastHash`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum DigestAlgorithm {
+    /// Two XXH64 hashes, see [`fast_hash_digest`]
+    #[default]
+    FastHash,
+    /// BLAKE3 truncated to 128 bits, see [`blake3_digest`]
+    Blake3,
+}
+
+impl DigestAlgorithm {
+    /// Returns the name recorded in the file header.
+    pub fn name(&self) -> &'static str {
+        match self {
+            DigestAlgorithm::FastHash => "FastHash",
+            DigestAlgorithm::Blake3 => "Blake3",
+        }
+    }
+
+    /// Parses the name recorded in the file header.
+    ///
+    /// An empty name selects the default algorithm for compatibility with older files.
+    pub fn from_name(name: &str) -> Result<Self> {
+        match name.to_lowercase().as_str() {
+            "" | "fasthash" => Ok(DigestAlgorithm::FastHash),
+            "blake3" => Ok(DigestAlgorithm::Blake3),
+            _ => Err(ZdbError::invalid_data_format(format!("Unsupported digest algorithm: {}", name))),
+        }
+    }
+
+    /// Computes a 128-bit digest of the input with this algorithm.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the input is empty, or if the algorithm was not enabled at compile time.
+    pub fn digest(&self, input: &[u8]) -> Result<Vec<u8>> {
+        match self {
+            DigestAlgorithm::FastHash => fast_hash_digest(input),
+            DigestAlgorithm::Blake3 => blake3_digest(input),
+        }
+    }
+}
+
 /// Computes a 128-bit hash digest using two XXH64 hashes over the input.
 ///
 /// This function splits the input into two parts and computes an XXH64 hash
@@ -85,3 +140,55 @@ pub fn ripemd_digest(data: &[u8]) -> Result<Vec<u8>> {
     let digest = ripemd.result();
     Ok(digest.to_vec())
 }
+
+/// Computes a 128-bit digest using BLAKE3, truncating the output to 16 bytes.
+///
+/// # Errors
+///
+/// Returns an error if the input is empty.
+#[cfg(feature = "blake3")]
+pub fn blake3_digest(input: &[u8]) -> Result<Vec<u8>> {
+    if input.is_empty() {
+        return Err(ZdbError::invalid_parameter("Input is empty"));
+    }
+    Ok(blake3::hash(input).as_bytes()[..16].to_vec())
+}
+
+/// Placeholder used when the `blake3` feature is disabled, always returns an error.
+#[cfg(not(feature = "blake3"))]
+pub fn blake3_digest(_input: &[u8]) -> Result<Vec<u8>> {
+    Err(ZdbError::unsupported_feature("BLAKE3 key digests", "rebuild with feature `blake3`"))
+}
+
+/// Computes a SHA-256 digest of the input data.
+///
+/// # Examples
+///
+/// ```
+/// use mdx::crypto::digest::sha256_digest;
+///
+/// assert_eq!(sha256_digest(b"abc").len(), 32);
+/// ```
+pub fn sha256_digest(data: &[u8]) -> [u8; 32] {
+    <Sha256 as sha2::Digest>::digest(data).into()
+}
+
+/// Computes a SHA-256 digest of `length` bytes read from `reader`.
+///
+/// The data is hashed in chunks, so large ranges don't need to fit in memory.
+///
+/// # Errors
+///
+/// Returns an error if fewer than `length` bytes can be read.
+pub fn sha256_reader<R: Read>(reader: &mut R, length: u64) -> Result<[u8; 32]> {
+    let mut hasher = <Sha256 as sha2::Digest>::new();
+    let mut buffer = vec![0u8; 64 * 1024];
+    let mut remaining = length;
+    while remaining > 0 {
+        let chunk = remaining.min(buffer.len() as u64) as usize;
+        reader.read_exact(&mut buffer[..chunk])?;
+        sha2::Digest::update(&mut hasher, &buffer[..chunk]);
+        remaining -= chunk as u64;
+    }
+    Ok(sha2::Digest::finalize(hasher).into())
+}
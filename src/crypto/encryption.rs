@@ -25,8 +25,12 @@
 
 use std::io;
 
+use zeroize::Zeroize;
+
 use super::salsa20::*;
+use super::secret::SecretBytes;
 use crate::{Result, ZdbError};
+use crate::utils::named_enum::{named_enum_serde, NamedEnum};
 
 /// Encryption methods supported by ZDB files.
 ///
@@ -44,6 +48,17 @@ pub enum EncryptionMethod {
     Salsa20 = 2,
 }
 
+impl NamedEnum for EncryptionMethod {
+    const KIND: &'static str = "encryption method";
+    const VARIANTS: &'static [(Self, &'static str, u64)] = &[
+        (EncryptionMethod::None, "None", 0),
+        (EncryptionMethod::Simple, "Simple", 1),
+        (EncryptionMethod::Salsa20, "Salsa20", 2),
+    ];
+}
+
+named_enum_serde!(EncryptionMethod);
+
 impl TryFrom<u8> for EncryptionMethod {
     type Error = ZdbError;
 
@@ -52,7 +67,7 @@ impl TryFrom<u8> for EncryptionMethod {
             0 => Ok(EncryptionMethod::None),
             1 => Ok(EncryptionMethod::Simple),
             2 => Ok(EncryptionMethod::Salsa20),
-            _ => Err(ZdbError::invalid_parameter(format!("Invalid encryption method:{}",value))),
+            _ => Err(crate::format::unsupported_value::<EncryptionMethod>(value as u64)),
         }
     }
 }
@@ -135,12 +150,12 @@ impl Encryptor for NoEncryption {
 /// assert_eq!(input, &encrypted2[..]);
 /// ```
 pub struct SimpleEncryptor{
-    key: Vec<u8>,
+    key: SecretBytes,
 }
 
 impl SimpleEncryptor {
     pub fn new(key: &[u8], _nonce: &[u8]) -> Self {
-        Self { key: key.to_vec() }
+        Self { key: SecretBytes::from(key) }
     }
 }
 
@@ -201,6 +216,13 @@ impl Salsa20Encryptor{
     }
 
 }
+
+impl Drop for Salsa20Encryptor {
+    fn drop(&mut self) {
+        // The context holds the expanded key
+        self.ctx.input.zeroize();
+    }
+}
 impl Encryptor for Salsa20Encryptor {
     fn encrypt(&mut self, input: &[u8], output: &mut [u8]) -> io::Result<()> {
         salsa20_encrypt_bytes(&mut self.ctx, input, output);
@@ -222,19 +244,36 @@ pub fn get_encryptor(method: EncryptionMethod, key: &[u8], nonce: &[u8]) -> Resu
     Ok(encryptor)
 } 
 
+/// All-zero nonce used by files written before per-block nonces were introduced.
+pub const ZERO_NONCE: [u8; 8] = [0u8; 8];
+
+/// Derives the nonce of a storage block from its offset in the file.
+///
+/// Blocks at different offsets get different keystreams, so identical plaintext
+/// blocks no longer produce identical ciphertext.
+pub fn block_nonce(block_offset: u64) -> [u8; 8] {
+    block_offset.to_le_bytes()
+}
+
 pub fn decrypt_salsa20(data: &[u8], key: &[u8]) -> Result<Vec<u8>> {
-    let nonce = [0u8; 8];
+    decrypt_salsa20_with_nonce(data, key, &ZERO_NONCE)
+}
+
+pub fn encrypt_salsa20(data: &[u8], key: &[u8]) -> Result<Vec<u8>> {
+    encrypt_salsa20_with_nonce(data, key, &ZERO_NONCE)
+}
+
+pub fn decrypt_salsa20_with_nonce(data: &[u8], key: &[u8], nonce: &[u8]) -> Result<Vec<u8>> {
     let mut salsa20_encryptor =
-        get_encryptor(crate::crypto::encryption::EncryptionMethod::Salsa20, key, &nonce)?;
+        get_encryptor(crate::crypto::encryption::EncryptionMethod::Salsa20, key, nonce)?;
     let mut decrypted_data = vec![0; data.len()];
     salsa20_encryptor.decrypt(data, &mut decrypted_data)?;
     Ok(decrypted_data)
 }
 
-pub fn encrypt_salsa20(data: &[u8], key: &[u8]) -> Result<Vec<u8>> {
-    let nonce = [0u8; 8];
+pub fn encrypt_salsa20_with_nonce(data: &[u8], key: &[u8], nonce: &[u8]) -> Result<Vec<u8>> {
     let mut salsa20_encryptor =
-        get_encryptor(crate::crypto::encryption::EncryptionMethod::Salsa20, key, &nonce)?;
+        get_encryptor(crate::crypto::encryption::EncryptionMethod::Salsa20, key, nonce)?;
     let mut encrypted_data = vec![0; data.len()];
     salsa20_encryptor.encrypt(data, &mut encrypted_data)?;
     Ok(encrypted_data)
@@ -5,6 +5,8 @@
 pub mod digest;
 pub mod encryption;
 pub mod salsa20;
+pub mod secret;
 
-pub use digest::ripemd_digest;
-pub use encryption::{EncryptionMethod, get_encryptor, decrypt_salsa20, encrypt_salsa20};
+pub use digest::{ripemd_digest, DigestAlgorithm};
+pub use secret::{SecretBytes, SecretString};
+pub use encryption::{EncryptionMethod, get_encryptor, decrypt_salsa20, encrypt_salsa20, block_nonce};
@@ -30,14 +30,188 @@
 //! - [`ZdbError::InvalidParameter`]: Invalid function parameters
 //! - [`ZdbError::KeyNotFound`]: Dictionary key lookup failures
 //! - [`ZdbError::CompressionError`]: Compression/decompression failures
-//! - [`ZdbError::ParserError`]: XML/JSON parsing errors
+//! - [`ZdbError::ParserError`]: XML/JSON/TOML parsing errors
+//! - [`ZdbError::LicenseError`]: Missing or unusable license data
+//! - [`ZdbError::KeyOrderMismatch`]: Keys sorted differently than the header locale
+//! - [`ZdbError::InsufficientDiskSpace`]: Not enough room for the output of a build
+//! - [`ZdbError::KeyTooLong`]: A key exceeds the key length limit of a build
+//! - [`ZdbError::ContentTooLarge`]: The content of an entry exceeds the size limit of a build
+//!
+//! Errors that are expected control flow can be created without a backtrace, see
+//! [`set_lightweight_errors`].
+//!
+//! Every variant has a stable [`ErrorCode`] and an [`ErrorCategory`], see [`ZdbError::code`].
 
+use std::fmt;
 use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
 use snafu::{Snafu, Backtrace};
+use crate::storage::meta_unit::ContentType;
 
 // Re-export snafu for context providers
 pub use snafu;
 
+/// Reason why a dictionary could not be opened with the given license data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LicenseErrorKind {
+    /// The dictionary is encrypted but no license data was provided
+    Required,
+    /// The license data is malformed
+    Invalid,
+    /// The license data is well-formed but was issued for another device
+    WrongDevice,
+}
+
+impl fmt::Display for LicenseErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LicenseErrorKind::Required => f.write_str("license required"),
+            LicenseErrorKind::Invalid => f.write_str("invalid license"),
+            LicenseErrorKind::WrongDevice => f.write_str("license issued for another device"),
+        }
+    }
+}
+
+/// Broad class of an error, see [`ZdbError::category`].
+///
+/// The numeric values are stable and are the hundreds of the [`ErrorCode`]s in the category.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(u32)]
+pub enum ErrorCategory {
+    /// Reading or writing a file failed
+    Io = 1,
+    /// A file or a source is malformed or corrupted
+    Format = 2,
+    /// A function or a build was called with invalid parameters
+    Parameter = 3,
+    /// A key or a profile doesn't exist
+    NotFound = 4,
+    /// License data is missing or doesn't decrypt the dictionary
+    Crypto = 5,
+    /// The full-text search index can't be used
+    Search = 6,
+    /// Collation or locale operations failed
+    Collation = 7,
+    /// A memory or disk space limit would be exceeded
+    Resource = 8,
+    /// The user cancelled the operation
+    Interrupted = 9,
+    /// Errors that don't fit another category
+    General = 10,
+}
+
+/// Machine-readable identity of an error, see [`ZdbError::code`].
+///
+/// The numeric values are stable, new codes get new values, so FFI consumers and
+/// telemetry can store them. The value divided by 100 is the [`ErrorCategory`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(u32)]
+pub enum ErrorCode {
+    /// [`ZdbError::Io`]
+    Io = 100,
+    /// [`ZdbError::CrcMismatch`]
+    CrcMismatch = 200,
+    /// [`ZdbError::InvalidDataFormat`]
+    InvalidDataFormat = 201,
+    /// [`ZdbError::ParserError`]
+    Parser = 202,
+    /// [`ZdbError::CompressionError`]
+    Compression = 203,
+    /// [`ZdbError::KeyOrderMismatch`]
+    KeyOrderMismatch = 204,
+    /// [`ZdbError::UnsupportedFeature`]
+    UnsupportedFeature = 205,
+    /// [`ZdbError::InvalidParameter`]
+    InvalidParameter = 300,
+    /// [`ZdbError::KeyTooLong`]
+    KeyTooLong = 301,
+    /// [`ZdbError::ContentTooLarge`]
+    ContentTooLarge = 302,
+    /// [`ZdbError::ContentTypeMismatch`]
+    ContentTypeMismatch = 303,
+    /// [`ZdbError::PathOutsideDictionary`]
+    PathOutsideDictionary = 304,
+    /// [`ZdbError::KeyNotFound`]
+    KeyNotFound = 400,
+    /// [`ZdbError::ProfileNotFound`]
+    ProfileNotFound = 401,
+    /// [`ZdbError::LicenseError`] of kind [`LicenseErrorKind::Required`]
+    LicenseRequired = 500,
+    /// [`ZdbError::LicenseError`] of kind [`LicenseErrorKind::Invalid`]
+    LicenseInvalid = 501,
+    /// [`ZdbError::LicenseError`] of kind [`LicenseErrorKind::WrongDevice`]
+    LicenseWrongDevice = 502,
+    /// [`ZdbError::FtsIndexOutdated`]
+    FtsIndexOutdated = 600,
+    /// [`ZdbError::IcuError`]
+    Icu = 700,
+    /// [`ZdbError::MemoryLimitExceeded`]
+    MemoryLimitExceeded = 800,
+    /// [`ZdbError::InsufficientDiskSpace`]
+    InsufficientDiskSpace = 801,
+    /// [`ZdbError::UserInterrupted`]
+    UserInterrupted = 900,
+    /// [`ZdbError::GeneralError`]
+    General = 1000,
+}
+
+impl ErrorCode {
+    /// The category the code belongs to.
+    pub fn category(self) -> ErrorCategory {
+        match self as u32 / 100 {
+            1 => ErrorCategory::Io,
+            2 => ErrorCategory::Format,
+            3 => ErrorCategory::Parameter,
+            4 => ErrorCategory::NotFound,
+            5 => ErrorCategory::Crypto,
+            6 => ErrorCategory::Search,
+            7 => ErrorCategory::Collation,
+            8 => ErrorCategory::Resource,
+            9 => ErrorCategory::Interrupted,
+            _ => ErrorCategory::General,
+        }
+    }
+}
+
+/// Whether errors of expected control flow are created without a backtrace.
+static LIGHTWEIGHT_ERRORS: AtomicBool = AtomicBool::new(false);
+
+/// Skips capturing backtraces for errors that are expected control flow.
+///
+/// Capturing a backtrace walks the stack when backtraces are enabled, e.g. by
+/// `RUST_BACKTRACE`, which is costly where such errors are frequent, e.g. `KeyNotFound`
+/// while looking a key up in many dictionaries. With lightweight errors, `KeyNotFound`,
+/// `ProfileNotFound` and `UserInterrupted` carry a disabled backtrace; errors that point
+/// to a problem, e.g. I/O or format errors, still capture theirs.
+///
+/// The setting is global and off by default.
+///
+/// # Examples
+///
+/// ```
+/// use mdx::error::set_lightweight_errors;
+/// use mdx::ZdbError;
+/// use std::backtrace::BacktraceStatus;
+///
+/// set_lightweight_errors(true);
+/// if let ZdbError::KeyNotFound { backtrace, .. } = ZdbError::key_not_found("apple") {
+///     assert_eq!(backtrace.status(), BacktraceStatus::Disabled);
+/// }
+/// ```
+pub fn set_lightweight_errors(enabled: bool) {
+    LIGHTWEIGHT_ERRORS.store(enabled, Ordering::Relaxed);
+}
+
+/// Whether lightweight errors are enabled, see [`set_lightweight_errors`].
+pub fn lightweight_errors() -> bool {
+    LIGHTWEIGHT_ERRORS.load(Ordering::Relaxed)
+}
+
+/// Backtrace of an error that is expected control flow, disabled with lightweight errors.
+fn control_flow_backtrace() -> Backtrace {
+    if lightweight_errors() { Backtrace::disabled() } else { Backtrace::capture() }
+}
+
 /// Main error type for the MDX crate.
 ///
 /// All errors include automatic backtrace capture for debugging purposes.
@@ -115,6 +289,93 @@ pub enum ZdbError {
         backtrace: Backtrace,
     },
 
+    /// License data is missing, malformed, or doesn't match the device.
+    #[snafu(display("License error ({kind}): {message}"))]
+    LicenseError {
+        kind: LicenseErrorKind,
+        message: String,
+        backtrace: Backtrace,
+    },
+
+    /// Full-text search index was built with another schema or for another version of the dictionary.
+    #[snafu(display("FTS index out of date, rebuild required: {message}"))]
+    FtsIndexOutdated {
+        message: String,
+        backtrace: Backtrace,
+    },
+
+    /// Opening or reading a dictionary would exceed the configured memory limit.
+    #[snafu(display("Memory limit exceeded: {required} bytes required, limit is {limit} bytes"))]
+    MemoryLimitExceeded {
+        required: u64,
+        limit: u64,
+        backtrace: Backtrace,
+    },
+
+    /// The destination of a build doesn't have room for the estimated output.
+    #[snafu(display("Not enough disk space at {path}: about {required} bytes needed, {available} bytes available"))]
+    InsufficientDiskSpace {
+        path: String,
+        required: u64,
+        available: u64,
+        backtrace: Backtrace,
+    },
+
+    /// A key to be built is longer than the configured limit.
+    #[snafu(display("Key \"{key}\" is {length} bytes long, the limit is {limit} bytes"))]
+    KeyTooLong {
+        key: String,
+        length: u64,
+        limit: u64,
+        backtrace: Backtrace,
+    },
+
+    /// The content of an entry to be built is larger than the configured limit.
+    #[snafu(display("Content of entry \"{key}\" is {size} bytes, the limit is {limit} bytes"))]
+    ContentTooLarge {
+        key: String,
+        size: u64,
+        limit: u64,
+        backtrace: Backtrace,
+    },
+
+    /// Stored keys aren't in the order of the collator of the header locale.
+    #[snafu(display("Keys are not sorted for the header locale by {}: \"{previous}\" is followed by \"{key}\" at entry {entry_no}", crate::utils::icu_wrapper::BACKEND))]
+    KeyOrderMismatch {
+        entry_no: u64,
+        previous: String,
+        key: String,
+        backtrace: Backtrace,
+    },
+
+    /// The content of the dictionary can't be read this way, e.g. binary content as a string.
+    #[snafu(display("{operation} is not available for {content_type} content{}", hint.as_deref().map(|hint| format!(", use {}", hint)).unwrap_or_default()))]
+    ContentTypeMismatch {
+        operation: String,
+        /// Content type of the dictionary, e.g. "Binary"
+        content_type: String,
+        /// Function to use instead
+        hint: Option<String>,
+        backtrace: Backtrace,
+    },
+
+    /// A resource path leads out of the folder of the dictionary, e.g. through `..` or a symbolic link.
+    #[snafu(display("Path \"{path}\" leads outside the dictionary folder"))]
+    PathOutsideDictionary {
+        path: String,
+        backtrace: Backtrace,
+    },
+
+    /// The file needs a feature this build of the crate doesn't support, see [`crate::format::capabilities`].
+    #[snafu(display("File requires {feature}; {hint}"))]
+    UnsupportedFeature {
+        /// The feature, e.g. "compression method 7"
+        feature: String,
+        /// What supports it, e.g. the cargo feature to enable
+        hint: String,
+        backtrace: Backtrace,
+    },
+
     /// General error that doesn't fit other categories.
     #[snafu(display("General error: {message}"))]
     GeneralError {
@@ -184,9 +445,63 @@ impl From<serde_json::Error> for ZdbError {
     }
 }
 
+impl From<toml::de::Error> for ZdbError {
+    fn from(source: toml::de::Error) -> Self {
+        Self::ParserError {
+            source: Box::new(source),
+            backtrace: Backtrace::capture(),
+        }
+    }
+}
+
 
 /// Helper methods for creating errors without context providers.
 impl ZdbError {
+    /// Stable code identifying the kind of error, without parsing the message.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mdx::{ErrorCategory, ErrorCode, ZdbError};
+    ///
+    /// let error = ZdbError::key_not_found("apple");
+    /// assert_eq!(error.code(), ErrorCode::KeyNotFound);
+    /// assert_eq!(error.code() as u32, 400);
+    /// assert_eq!(error.category(), ErrorCategory::NotFound);
+    /// ```
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            ZdbError::Io { .. } => ErrorCode::Io,
+            ZdbError::CrcMismatch { .. } => ErrorCode::CrcMismatch,
+            ZdbError::ParserError { .. } => ErrorCode::Parser,
+            ZdbError::InvalidDataFormat { .. } => ErrorCode::InvalidDataFormat,
+            ZdbError::InvalidParameter { .. } => ErrorCode::InvalidParameter,
+            ZdbError::IcuError { .. } => ErrorCode::Icu,
+            ZdbError::KeyNotFound { .. } => ErrorCode::KeyNotFound,
+            ZdbError::ProfileNotFound { .. } => ErrorCode::ProfileNotFound,
+            ZdbError::CompressionError { .. } => ErrorCode::Compression,
+            ZdbError::UserInterrupted { .. } => ErrorCode::UserInterrupted,
+            ZdbError::LicenseError { kind: LicenseErrorKind::Required, .. } => ErrorCode::LicenseRequired,
+            ZdbError::LicenseError { kind: LicenseErrorKind::Invalid, .. } => ErrorCode::LicenseInvalid,
+            ZdbError::LicenseError { kind: LicenseErrorKind::WrongDevice, .. } => ErrorCode::LicenseWrongDevice,
+            ZdbError::FtsIndexOutdated { .. } => ErrorCode::FtsIndexOutdated,
+            ZdbError::MemoryLimitExceeded { .. } => ErrorCode::MemoryLimitExceeded,
+            ZdbError::InsufficientDiskSpace { .. } => ErrorCode::InsufficientDiskSpace,
+            ZdbError::KeyTooLong { .. } => ErrorCode::KeyTooLong,
+            ZdbError::ContentTooLarge { .. } => ErrorCode::ContentTooLarge,
+            ZdbError::KeyOrderMismatch { .. } => ErrorCode::KeyOrderMismatch,
+            ZdbError::UnsupportedFeature { .. } => ErrorCode::UnsupportedFeature,
+            ZdbError::ContentTypeMismatch { .. } => ErrorCode::ContentTypeMismatch,
+            ZdbError::PathOutsideDictionary { .. } => ErrorCode::PathOutsideDictionary,
+            ZdbError::GeneralError { .. } => ErrorCode::General,
+        }
+    }
+
+    /// Broad class of the error, e.g. to decide whether retrying or asking for a license can help.
+    pub fn category(&self) -> ErrorCategory {
+        self.code().category()
+    }
+
     /// Creates an `InvalidParameter` error with the given message.
     ///
     /// # Examples
@@ -212,18 +527,22 @@ impl ZdbError {
     }
 
     /// Creates a `KeyNotFound` error for the given key.
+    ///
+    /// The backtrace isn't captured with [`set_lightweight_errors`].
     pub fn key_not_found<S: Into<String>>(key: S) -> Self {
         Self::KeyNotFound {
             key: key.into(),
-            backtrace: Backtrace::capture(),
+            backtrace: control_flow_backtrace(),
         }
     }
 
     /// Creates a `ProfileNotFound` error for the given profile ID.
+    ///
+    /// The backtrace isn't captured with [`set_lightweight_errors`].
     pub fn profile_not_found(profile_id: u32) -> Self {
         Self::ProfileNotFound {
             profile_id,
-            backtrace: Backtrace::capture(),
+            backtrace: control_flow_backtrace(),
         }
     }
 
@@ -252,9 +571,11 @@ impl ZdbError {
     }
 
     /// Creates a `UserInterrupted` error.
+    ///
+    /// The backtrace isn't captured with [`set_lightweight_errors`].
     pub fn user_interrupted() -> Self {
         Self::UserInterrupted {
-            backtrace: Backtrace::capture(),
+            backtrace: control_flow_backtrace(),
         }
     }
 
@@ -275,6 +596,116 @@ impl ZdbError {
         false
     }
 
+    /// Creates a `LicenseError` of the given kind.
+    pub fn license_error<S: Into<String>>(kind: LicenseErrorKind, message: S) -> Self {
+        Self::LicenseError {
+            kind,
+            message: message.into(),
+            backtrace: Backtrace::capture(),
+        }
+    }
+
+    /// Returns the license error kind if this error is a `LicenseError` variant.
+    pub fn license_error_kind(&self) -> Option<LicenseErrorKind> {
+        if let ZdbError::LicenseError { kind, .. } = self {
+            return Some(*kind);
+        }
+        None
+    }
+
+    /// Creates a `FtsIndexOutdated` error with the reason the index has to be rebuilt.
+    pub fn fts_index_outdated(message: impl Into<String>) -> Self {
+        Self::FtsIndexOutdated {
+            message: message.into(),
+            backtrace: Backtrace::capture(),
+        }
+    }
+
+    /// Creates a `MemoryLimitExceeded` error with the required and allowed number of bytes.
+    pub fn memory_limit_exceeded(required: u64, limit: u64) -> Self {
+        Self::MemoryLimitExceeded {
+            required,
+            limit,
+            backtrace: Backtrace::capture(),
+        }
+    }
+
+    /// Creates an `InsufficientDiskSpace` error for the directory a build writes to.
+    pub fn insufficient_disk_space<S: Into<String>>(path: S, required: u64, available: u64) -> Self {
+        Self::InsufficientDiskSpace {
+            path: path.into(),
+            required,
+            available,
+            backtrace: Backtrace::capture(),
+        }
+    }
+
+    /// Creates a `KeyTooLong` error, the key is shortened to keep the message readable.
+    pub fn key_too_long(key: &str, length: u64, limit: u64) -> Self {
+        Self::KeyTooLong {
+            key: shorten_key(key),
+            length,
+            limit,
+            backtrace: Backtrace::capture(),
+        }
+    }
+
+    /// Creates a `ContentTooLarge` error for the entry with the given key.
+    pub fn content_too_large(key: &str, size: u64, limit: u64) -> Self {
+        Self::ContentTooLarge {
+            key: shorten_key(key),
+            size,
+            limit,
+            backtrace: Backtrace::capture(),
+        }
+    }
+
+    /// Creates a `KeyOrderMismatch` error for the entry following a key that sorts after it.
+    pub fn key_order_mismatch(entry_no: u64, previous: &str, key: &str) -> Self {
+        Self::KeyOrderMismatch {
+            entry_no,
+            previous: previous.to_string(),
+            key: key.to_string(),
+            backtrace: Backtrace::capture(),
+        }
+    }
+
+    /// Creates a `ContentTypeMismatch` error for an operation the content type of the dictionary doesn't allow.
+    pub fn content_type_mismatch<O: Into<String>>(operation: O, content_type: &ContentType, hint: Option<&str>) -> Self {
+        Self::ContentTypeMismatch {
+            operation: operation.into(),
+            content_type: format!("{:?}", content_type),
+            hint: hint.map(str::to_string),
+            backtrace: Backtrace::capture(),
+        }
+    }
+
+    /// Creates a `PathOutsideDictionary` error for a resource path escaping the dictionary folder.
+    pub fn path_outside_dictionary<S: Into<String>>(path: S) -> Self {
+        Self::PathOutsideDictionary {
+            path: path.into(),
+            backtrace: Backtrace::capture(),
+        }
+    }
+
+    /// Creates an `UnsupportedFeature` error for a feature the file requires.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mdx::ZdbError;
+    ///
+    /// let error = ZdbError::unsupported_feature("BLAKE3 key digests", "rebuild with feature `blake3`");
+    /// assert_eq!(error.to_string(), "File requires BLAKE3 key digests; rebuild with feature `blake3`");
+    /// ```
+    pub fn unsupported_feature<F: Into<String>, H: Into<String>>(feature: F, hint: H) -> Self {
+        Self::UnsupportedFeature {
+            feature: feature.into(),
+            hint: hint.into(),
+            backtrace: Backtrace::capture(),
+        }
+    }
+
     /// Creates a `GeneralError` with the given message.
     pub fn general_error<S: Into<String>>(message: S) -> Self {
         Self::GeneralError {
@@ -284,6 +715,17 @@ impl ZdbError {
     }
 }
 
+/// Number of characters of a key kept in error messages.
+const MAX_KEY_CHARS_IN_ERROR: usize = 64;
+
+/// The start of a key followed by an ellipsis if it's longer than fits in an error message.
+fn shorten_key(key: &str) -> String {
+    match key.char_indices().nth(MAX_KEY_CHARS_IN_ERROR) {
+        Some((end, _)) => format!("{}...", &key[..end]),
+        None => key.to_string(),
+    }
+}
+
 /// A specialized `Result` type for MDX operations.
 ///
 /// This is a convenience type alias that uses [`ZdbError`] as the error type.
@@ -0,0 +1,111 @@
+//! Features of the file format supported by this build of the crate.
+//!
+//! Which codecs and digests are available depends on the enabled cargo features, and
+//! newer versions of the crate add unit types and engine versions. Applications call
+//! [`capabilities`] to show what they can open, and the readers report a file using a
+//! feature missing here with a [`ZdbError::UnsupportedFeature`] naming the feature,
+//! instead of a generic invalid parameter error.
+//!
+//! # Examples
+//!
+//! ```
+//! use mdx::format::capabilities;
+//! use mdx::utils::compression::CompressionMethod;
+//!
+//! let capabilities = capabilities();
+//! assert!(capabilities.compression_methods.contains(&CompressionMethod::Deflate));
+//! println!("{}", serde_json::to_string_pretty(&capabilities).unwrap());
+//! ```
+
+use serde::Serialize;
+
+use crate::crypto::digest::DigestAlgorithm;
+use crate::crypto::encryption::EncryptionMethod;
+use crate::storage::key_block_index_unit::KEY_BLOCK_INDEX_FRONT_CODED;
+use crate::storage::key_unit::KEY_UNIT_CONTENT_LENGTHS;
+use crate::storage::unit_base::UnitType;
+use crate::utils::checksum::ChecksumAlgorithm;
+use crate::utils::compression::CompressionMethod;
+use crate::utils::icu_wrapper::BACKEND;
+use crate::utils::named_enum::NamedEnum;
+use crate::ZdbError;
+
+/// What this build of the crate can read and write, see the [module documentation](self).
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Capabilities {
+    /// Version of the crate
+    pub crate_version: &'static str,
+    /// Engine versions of the file header, e.g. "3.0"
+    pub engine_versions: Vec<&'static str>,
+    pub compression_methods: Vec<CompressionMethod>,
+    pub encryption_methods: Vec<EncryptionMethod>,
+    /// Checksum algorithms of storage blocks
+    pub checksum_algorithms: Vec<ChecksumAlgorithm>,
+    /// Digest algorithms for key derivation
+    pub digest_algorithms: Vec<DigestAlgorithm>,
+    /// Unit types of V3 files, including optional ones
+    pub unit_types: Vec<UnitType>,
+    /// Minor versions of the key block index unit
+    pub key_block_index_versions: Vec<u32>,
+    /// Minor versions of the key unit
+    pub key_unit_versions: Vec<u32>,
+    /// Collation implementation, e.g. "ICU4X"
+    pub collation_backend: &'static str,
+    /// Enabled optional cargo features
+    pub features: Vec<&'static str>,
+}
+
+/// Reports the features of the file format supported by this build.
+pub fn capabilities() -> Capabilities {
+    let mut digest_algorithms = vec![DigestAlgorithm::FastHash];
+    if cfg!(feature = "blake3") {
+        digest_algorithms.push(DigestAlgorithm::Blake3);
+    }
+    let features = [
+        ("icu", cfg!(feature = "icu")),
+        ("icu-core", cfg!(feature = "icu-core")),
+        ("rust-icu", cfg!(feature = "rust-icu")),
+        ("blake3", cfg!(feature = "blake3")),
+        ("whatlang", cfg!(feature = "whatlang")),
+        ("pinyin", cfg!(feature = "pinyin")),
+    ];
+    Capabilities {
+        crate_version: env!("CARGO_PKG_VERSION"),
+        engine_versions: vec!["1.0", "2.0", "3.0"],
+        compression_methods: CompressionMethod::VARIANTS.iter().map(|(method, _, _)| *method).collect(),
+        encryption_methods: EncryptionMethod::VARIANTS.iter().map(|(method, _, _)| *method).collect(),
+        checksum_algorithms: ChecksumAlgorithm::VARIANTS.iter().map(|(algorithm, _, _)| *algorithm).collect(),
+        digest_algorithms,
+        unit_types: (1..=u8::MAX).map_while(|value| UnitType::try_from(value).ok()).collect(),
+        key_block_index_versions: (0..=KEY_BLOCK_INDEX_FRONT_CODED).collect(),
+        key_unit_versions: (0..=KEY_UNIT_CONTENT_LENGTHS).collect(),
+        collation_backend: BACKEND,
+        features: features.into_iter().filter(|(_, enabled)| *enabled).map(|(name, _)| name).collect(),
+    }
+}
+
+/// Error for a value of an enum stored in a file that this build doesn't know, e.g. a newer compression method.
+pub(crate) fn unsupported_value<T: NamedEnum>(value: u64) -> ZdbError {
+    ZdbError::unsupported_feature(format!("{} {}", T::KIND, value), newer_version_hint(&T::expected()))
+}
+
+/// Hint for features a newer version of the crate may support.
+pub(crate) fn newer_version_hint(supported: &str) -> String {
+    format!("mdx {} supports {}, a newer version may support it", env!("CARGO_PKG_VERSION"), supported)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_capabilities() {
+        let capabilities = capabilities();
+        assert_eq!(capabilities.unit_types.first(), Some(&UnitType::Content));
+        assert!(capabilities.unit_types.contains(&UnitType::BloomFilter));
+        assert_eq!(capabilities.digest_algorithms.contains(&DigestAlgorithm::Blake3), cfg!(feature = "blake3"));
+        let error = CompressionMethod::try_from(9).unwrap_err();
+        assert_eq!(error.code(), crate::ErrorCode::UnsupportedFeature);
+        assert!(error.to_string().starts_with("File requires compression method 9; mdx "), "{}", error);
+    }
+}
@@ -0,0 +1,280 @@
+//! Structural dumps of ZDB files for support.
+//!
+//! A dump lists the header and the units of a file with their offsets, sizes and
+//! data-info XML, and optionally the header of every block, but no key or content.
+//! Users can attach it to a report instead of the dictionary itself, which may be
+//! licensed. The license code embedded in the header is redacted.
+//!
+//! The dump is serializable to JSON, and its `Display` output is a listing with
+//! hexadecimal offsets.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use mdx::inspect::{dump, Verbosity};
+//!
+//! # fn main() -> mdx::Result<()> {
+//! let file_dump = dump("dictionary.mdx", Verbosity::Blocks)?;
+//! println!("{}", file_dump);
+//! println!("{}", serde_json::to_string_pretty(&file_dump).unwrap());
+//! # Ok(())
+//! # }
+//! ```
+
+use std::fmt;
+use std::fs::File;
+use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::path::Path;
+
+use byteorder::{BigEndian, ReadBytesExt};
+use log::debug;
+use serde::Serialize;
+
+use crate::crypto::encryption::EncryptionMethod;
+use crate::storage::meta_unit::{read_cstr_with_crc, DbInfo, MetaUnit, ZdbVersion};
+use crate::storage::reader_helper::bytes_from_cstr;
+use crate::storage::storage_block::{RawBlockInfo, StorageBlock};
+use crate::storage::unit_base::{UnitInfoSection, UnitType};
+use crate::storage::unit_digest::UnitDigestTrailer;
+use crate::utils::compression::CompressionMethod;
+use crate::utils::io_utils::read_exact_to_vec;
+use crate::Result;
+
+/// Length of the length fields preceding the header of a V3 block.
+const BLOCK_LENGTH_FIELDS: u64 = 8;
+
+/// Header attribute holding a license code, redacted in dumps.
+const REG_CODE_ATTRIBUTE: &str = "RegCode";
+
+/// Level of detail of a dump.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Verbosity {
+    /// The header and the units
+    #[default]
+    Units,
+    /// The header, the units and the header of every block
+    Blocks,
+}
+
+/// Structure of a ZDB file, see the [module documentation](self).
+#[derive(Debug, Clone, Serialize)]
+pub struct FileDump {
+    /// Size of the file in bytes
+    pub file_size: u64,
+    /// Format version, 1 to 3
+    pub version: u8,
+    /// Header XML, with the license code redacted
+    pub header_xml: String,
+    /// Length of the header including its length and checksum fields
+    pub header_length: u64,
+    /// Units in file order, only V3 files consist of units
+    pub units: Vec<UnitDump>,
+    /// Whether the file ends with unit digests, see [`UnitDigestTrailer`]
+    pub has_unit_digests: bool,
+}
+
+/// A unit of a V3 file.
+#[derive(Debug, Clone, Serialize)]
+pub struct UnitDump {
+    /// Type of the unit
+    pub unit_type: UnitType,
+    /// Offset of the unit in the file
+    pub offset: u64,
+    /// Length of the unit including its info and data-info sections
+    pub length: u64,
+    /// Number of blocks recorded in the unit info
+    pub block_count: u32,
+    /// Offset of the data section, the blocks of the unit
+    pub data_section_offset: u64,
+    /// Length of the data section in bytes
+    pub data_section_length: u64,
+    /// Data-info XML, `None` if it can't be decrypted without the license
+    pub data_info_xml: Option<String>,
+    /// The blocks of the data section, only with [`Verbosity::Blocks`]
+    pub blocks: Vec<BlockSummary>,
+}
+
+/// Header fields of a block, see [`RawBlockInfo`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct BlockSummary {
+    /// Offset of the block in the file
+    pub offset: u64,
+    /// Length of the block in the file, including its length fields
+    pub stored_length: u64,
+    /// Length of the uncompressed data
+    pub original_length: u32,
+    /// Compression method from the block header
+    pub compression: CompressionMethod,
+    /// Encryption method from the block header
+    pub encryption: EncryptionMethod,
+    /// Number of encrypted bytes at the start of the compressed data
+    pub encrypted_length: u8,
+    /// Flags of the block
+    pub flags: u16,
+    /// Checksum from the block header
+    pub crc: u32,
+}
+
+/// Dumps the structure of a ZDB file.
+///
+/// No license is needed: the data-info sections of licensed files are left out.
+///
+/// # Arguments
+///
+/// * `path` - Path of the file
+/// * `verbosity` - Whether to list every block
+///
+/// # Errors
+///
+/// Returns an error if the file can't be read or its structure is invalid.
+pub fn dump<P: AsRef<Path>>(path: P, verbosity: Verbosity) -> Result<FileDump> {
+    let mut reader = BufReader::new(File::open(path.as_ref())?);
+    dump_reader(&mut reader, verbosity)
+}
+
+/// Dumps the structure of a ZDB file from a reader, see [`dump`].
+pub fn dump_reader<R: Read + Seek>(reader: &mut R, verbosity: Verbosity) -> Result<FileDump> {
+    let file_size = reader.seek(SeekFrom::End(0))?;
+    reader.seek(SeekFrom::Start(0))?;
+    let header_xml = read_cstr_with_crc(reader)?;
+    let header_length = reader.stream_position()?;
+    let db_info = DbInfo::from_xml(&header_xml)?;
+
+    let trailer = UnitDigestTrailer::from_reader(reader)?;
+    let mut units = Vec::new();
+    if db_info.version == ZdbVersion::V3 {
+        // Licensed files can be dumped without their key, only the data-info isn't decoded
+        reader.seek(SeekFrom::Start(0))?;
+        let crypto_key = match MetaUnit::from_reader(reader, "", "", 0) {
+            Ok(meta) => Some(meta.crypto_key),
+            Err(e) => {
+                debug!("Data-info sections aren't decoded: {}", e);
+                None
+            }
+        };
+        let units_end = file_size - trailer.as_ref().map_or(0, UnitDigestTrailer::stored_length);
+        let mut offset = header_length;
+        while offset < units_end {
+            reader.seek(SeekFrom::Start(offset))?;
+            let unit = dump_unit(reader, offset, crypto_key.as_ref().map(|key| key.expose()), verbosity)?;
+            offset += unit.length;
+            units.push(unit);
+        }
+    }
+    Ok(FileDump {
+        file_size,
+        version: db_info.version as u8,
+        header_xml: redact_attribute(&header_xml, REG_CODE_ATTRIBUTE),
+        header_length,
+        units,
+        has_unit_digests: trailer.is_some(),
+    })
+}
+
+/// Dumps the unit starting at the position of `reader`.
+fn dump_unit<R: Read + Seek>(reader: &mut R, offset: u64, crypto_key: Option<&[u8]>, verbosity: Verbosity) -> Result<UnitDump> {
+    let info = UnitInfoSection::from_reader(reader)?;
+    let data_section_offset = reader.stream_position()?;
+    let data_section_end = data_section_offset + info.data_section_length;
+    let mut blocks = Vec::new();
+    if verbosity == Verbosity::Blocks {
+        let mut block_offset = data_section_offset;
+        while block_offset < data_section_end {
+            let block = read_block_summary(reader, block_offset)?;
+            block_offset += block.stored_length;
+            blocks.push(block);
+        }
+    }
+
+    reader.seek(SeekFrom::Start(data_section_end))?;
+    let original_length = reader.read_u32::<BigEndian>()?;
+    let block_length = reader.read_u32::<BigEndian>()?;
+    let mut data_info_block = read_exact_to_vec(reader, block_length as usize)?;
+    let data_info_xml = match crypto_key {
+        Some(crypto_key) => {
+            let data = StorageBlock::decode_block(&mut data_info_block, crypto_key, original_length, data_section_end)?.data;
+            Some(String::from_utf8_lossy(bytes_from_cstr(&data, false)).into_owned())
+        }
+        None => None,
+    };
+    let end = reader.stream_position()?;
+    Ok(UnitDump {
+        unit_type: info.unit_type,
+        offset,
+        length: end - offset,
+        block_count: info.block_count,
+        data_section_offset,
+        data_section_length: info.data_section_length,
+        data_info_xml,
+        blocks,
+    })
+}
+
+/// Reads the header of the V3 block at `offset`, without the compressed data.
+fn read_block_summary<R: Read + Seek>(reader: &mut R, offset: u64) -> Result<BlockSummary> {
+    reader.seek(SeekFrom::Start(offset))?;
+    let mut header = [0u8; BLOCK_LENGTH_FIELDS as usize + 8];
+    reader.read_exact(&mut header)?;
+    let block = RawBlockInfo::from_stored(offset, &header, 0, true)?;
+    let block_length = u32::from_be_bytes([header[4], header[5], header[6], header[7]]) as u64;
+    Ok(BlockSummary {
+        offset,
+        stored_length: BLOCK_LENGTH_FIELDS + block_length,
+        original_length: block.original_length,
+        compression: block.compression,
+        encryption: block.encryption,
+        encrypted_length: block.encrypted_length,
+        flags: block.flags,
+        crc: block.crc,
+    })
+}
+
+/// Replaces the value of an XML attribute with asterisks.
+fn redact_attribute(xml: &str, name: &str) -> String {
+    let pattern = format!("{}=\"", name);
+    match xml.find(&pattern) {
+        Some(start) => {
+            let value_start = start + pattern.len();
+            match xml[value_start..].find('"') {
+                Some(len) if len > 0 => format!("{}***{}", &xml[..value_start], &xml[value_start + len..]),
+                _ => xml.to_string(),
+            }
+        }
+        None => xml.to_string(),
+    }
+}
+
+impl fmt::Display for FileDump {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "ZDB version {}, {} bytes", self.version, self.file_size)?;
+        writeln!(f, "{:#010x}  Header  {} bytes", 0, self.header_length)?;
+        writeln!(f, "  {}", self.header_xml)?;
+        for unit in &self.units {
+            writeln!(f, "{:#010x}  {:?}  {} bytes, {} blocks in {} bytes at {:#010x}",
+                unit.offset, unit.unit_type, unit.length, unit.block_count, unit.data_section_length, unit.data_section_offset)?;
+            match &unit.data_info_xml {
+                Some(xml) => writeln!(f, "  {}", xml)?,
+                None => writeln!(f, "  (data-info encrypted)")?,
+            }
+            for block in &unit.blocks {
+                writeln!(f, "  {:#010x}  {} -> {} bytes, {:?}/{:?}, flags {:#06x}, crc {:#010x}",
+                    block.offset, block.stored_length, block.original_length, block.compression, block.encryption, block.flags, block.crc)?;
+            }
+        }
+        if self.has_unit_digests {
+            writeln!(f, "Unit digests")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_redact_attribute() {
+        assert_eq!(redact_attribute(r#"<Dictionary RegCode="0a1b2c" Title="t"/>"#, REG_CODE_ATTRIBUTE), r#"<Dictionary RegCode="***" Title="t"/>"#);
+        assert_eq!(redact_attribute(r#"<Dictionary RegCode="" Title="t"/>"#, REG_CODE_ATTRIBUTE), r#"<Dictionary RegCode="" Title="t"/>"#);
+    }
+}
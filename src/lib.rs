@@ -68,6 +68,7 @@
 //! - **Builder modules**: [`builder`] for creating and converting dictionary files
 //! - **Storage & core types**: [`storage`] for core data structures and storage management
 //! - **Cryptography**: [`crypto`] for encryption operations
+//! - **Inspection**: [`inspect`] for structural dumps of files
 //! - **Utilities**: [`utils`] for helper functions and common operations
 //!
 //! ## Error Handling
@@ -88,14 +89,16 @@
 pub mod builder;
 pub mod crypto;
 pub mod error;
+pub mod format;
+pub mod inspect;
 pub mod readers;
 pub mod storage;
 pub mod utils;
 
 // Re-export commonly used types for convenience
 pub use readers::{MdxReader, MddReader, ZdbReader};
-pub use storage::{MetaUnit, KeyIndex};
+pub use storage::{MetaUnit, KeyIndex, CompactKeyIndex};
 
 // Re-export error types for convenience
-pub use error::{ZdbError, Result, snafu};
+pub use error::{ZdbError, ErrorCategory, ErrorCode, LicenseErrorKind, Result, snafu};
 
@@ -0,0 +1,116 @@
+//! Primary stylesheet of a dictionary, for the head of the page showing its entries.
+//!
+//! Most dictionaries ship a stylesheet with the stem of the MDX file, e.g. `Oxford.css`,
+//! next to it or in the MDD file, and every entry links to it. An application showing
+//! entries of several dictionaries on one page can inject each stylesheet once instead,
+//! see [`MdxReader::get_stylesheet`]. [`StylesheetCache`] keeps the stylesheets by
+//! dictionary UUID, so they are read and rewritten once per dictionary.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use mdx::MdxReader;
+//! use mdx::readers::{StylesheetCache, StylesheetOptions};
+//! use url::Url;
+//!
+//! # fn main() -> mdx::Result<()> {
+//! let mut reader = MdxReader::from_url(&Url::parse("file:///dict/Oxford.mdx")?, "my_device")?;
+//! let mut cache = StylesheetCache::new(StylesheetOptions { minify: true, ..Default::default() });
+//! if let Some(stylesheet) = cache.get(&mut reader, 1)? {
+//!     println!("<style>{}</style>", stylesheet.css);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use url::Url;
+
+use crate::readers::mdx_reader::MdxReader;
+use crate::utils::mdx_html_rewriter::DEFAULT_BASE_URL;
+use crate::Result;
+
+/// Where the stylesheet of a dictionary was found.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StylesheetSource {
+    /// File next to the MDX file
+    File(Url),
+    /// Resource of the MDD file, by key
+    Resource(String),
+}
+
+/// Stylesheet of a dictionary with its URLs rewritten, see [`MdxReader::get_stylesheet`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DictStylesheet {
+    pub source: StylesheetSource,
+    pub css: String,
+}
+
+/// Options of [`MdxReader::get_stylesheet`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StylesheetOptions {
+    /// Base URL of the rewritten `url()` references, see [`MdxHtmlRewriter`](crate::utils::MdxHtmlRewriter)
+    pub base_url: String,
+    /// Minify the stylesheet, see [`minify_css`](crate::utils::minify_css) (default: false)
+    pub minify: bool,
+}
+
+impl Default for StylesheetOptions {
+    fn default() -> Self {
+        Self { base_url: DEFAULT_BASE_URL.to_string(), minify: false }
+    }
+}
+
+/// Stylesheets of dictionaries by UUID, see the [module documentation](self).
+#[derive(Debug, Default)]
+pub struct StylesheetCache {
+    options: StylesheetOptions,
+    /// Stylesheets by dictionary UUID, or by URL for dictionaries without a UUID, `None`
+    /// for dictionaries without a stylesheet
+    stylesheets: HashMap<String, Option<Arc<DictStylesheet>>>,
+}
+
+impl StylesheetCache {
+    /// Creates an empty cache whose stylesheets are rewritten with `options`.
+    pub fn new(options: StylesheetOptions) -> Self {
+        Self { options, stylesheets: HashMap::new() }
+    }
+
+    /// Gets the stylesheet of a dictionary, reading it on first use.
+    ///
+    /// # Arguments
+    ///
+    /// * `reader` - The dictionary
+    /// * `profile_id` - Profile id embedded in the rewritten URLs, which must stay the same
+    ///   for the dictionary, see [`DictRegistry`](crate::readers::DictRegistry)
+    ///
+    /// # Returns
+    ///
+    /// Returns `None` if the dictionary has no stylesheet.
+    pub fn get(&mut self, reader: &mut MdxReader, profile_id: i32) -> Result<Option<Arc<DictStylesheet>>> {
+        let cache_key = Self::cache_key(reader);
+        if let Some(stylesheet) = self.stylesheets.get(&cache_key) {
+            return Ok(stylesheet.clone());
+        }
+        let stylesheet = reader.get_stylesheet(profile_id, &self.options)?.map(Arc::new);
+        self.stylesheets.insert(cache_key, stylesheet.clone());
+        Ok(stylesheet)
+    }
+
+    /// Forgets the stylesheet of a dictionary, e.g. after its stylesheet file was edited.
+    pub fn invalidate(&mut self, reader: &MdxReader) {
+        self.stylesheets.remove(&Self::cache_key(reader));
+    }
+
+    /// Forgets all stylesheets.
+    pub fn clear(&mut self) {
+        self.stylesheets.clear();
+    }
+
+    fn cache_key(reader: &MdxReader) -> String {
+        let uuid = &reader.content_db.meta.db_info.uuid;
+        if uuid.is_empty() { reader.mdx_url.to_string() } else { uuid.clone() }
+    }
+}
@@ -0,0 +1,99 @@
+//! Metadata of a dictionary read without opening it.
+//!
+//! Opening a dictionary decodes its key and content block indexes, which is too slow
+//! for a library screen listing hundreds of dictionaries. [`DictMetadata`] only parses
+//! the header and the unit info of the first unit, which holds the entry count.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use mdx::MdxReader;
+//! use url::Url;
+//!
+//! # fn main() -> mdx::Result<()> {
+//! let url = Url::parse("file:///dict/Oxford.mdx")?;
+//! let metadata = MdxReader::open_metadata_only(&url)?;
+//! println!("{}: {:?} entries", metadata.title, metadata.entry_count);
+//! # Ok(())
+//! # }
+//! ```
+
+use std::io::{Read, Seek, SeekFrom};
+use std::rc::Rc;
+
+use serde::Serialize;
+
+use crate::storage::content_unit::ContentUnit;
+use crate::storage::key_block_index_unit::KeyBlockIndexUnit;
+use crate::storage::meta_unit::{DbInfo, MetaUnit, ZdbVersion};
+use crate::{Result, ZdbError};
+
+/// Header information, entry count and sizes of a dictionary.
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct DictMetadata {
+    /// Name of the dictionary, the file stem of the MDX file
+    pub db_name: String,
+    pub title: String,
+    pub description: String,
+    pub creation_date: String,
+    /// File format version, e.g. "V3"
+    pub version: String,
+    pub locale_id: String,
+    /// Number of entries, `None` if the count is encrypted and needs a license to read
+    pub entry_count: Option<u64>,
+    /// Size of the MDX file in bytes
+    pub file_size: u64,
+    /// Size of the resource (MDD) file in bytes, `None` if there is none
+    pub resource_file_size: Option<u64>,
+}
+
+impl DictMetadata {
+    /// Reads the metadata from the start of a ZDB file.
+    ///
+    /// `db_name` and `resource_file_size` are left empty, they depend on the location of the file.
+    ///
+    /// # Arguments
+    ///
+    /// * `reader` - A reader for the ZDB file data
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the header or the unit info can't be parsed.
+    pub fn from_reader<R: Read + Seek>(reader: &mut R) -> Result<Self> {
+        let file_size = reader.seek(SeekFrom::End(0))?;
+        reader.seek(SeekFrom::Start(0))?;
+        let db_info = DbInfo::from_reader(reader)?;
+        reader.seek(SeekFrom::Start(0))?;
+        let entry_count = match Self::read_entry_count(reader) {
+            Ok(entry_count) => Some(entry_count),
+            // Without the license the count can't be decrypted, which is reported as a crc mismatch
+            Err(ZdbError::LicenseError { .. } | ZdbError::CrcMismatch { .. }) => None,
+            Err(e) => return Err(e),
+        };
+        let version = match db_info.version {
+            ZdbVersion::V1 => "V1",
+            ZdbVersion::V2 => "V2",
+            ZdbVersion::V3 => "V3",
+        };
+        Ok(Self {
+            db_name: String::new(),
+            title: db_info.title,
+            description: db_info.description,
+            creation_date: db_info.creation_date,
+            version: version.to_string(),
+            locale_id: db_info.locale_id,
+            entry_count,
+            file_size,
+            resource_file_size: None,
+        })
+    }
+
+    fn read_entry_count<R: Read + Seek>(reader: &mut R) -> Result<u64> {
+        let meta = Rc::new(MetaUnit::from_reader(reader, "", "", 0)?);
+        if meta.is_v3() {
+            Ok(ContentUnit::from_reader_v3(reader, &meta)?.total_record_count)
+        } else {
+            KeyBlockIndexUnit::read_record_count_v1_v2(reader, &meta)
+        }
+    }
+}
@@ -0,0 +1,194 @@
+//! Manifest of a set of dictionaries, shared by front-ends as their library file.
+//!
+//! A pack manifest lists the dictionaries of a library with their display settings.
+//! It's stored as JSON or TOML, chosen by the extension of the file, and opened with
+//! [`DictRegistry::open_pack`](crate::readers::DictRegistry::open_pack).
+//!
+//! ```toml
+//! name = "English"
+//!
+//! [[dictionaries]]
+//! path = "Oxford/Oxford.mdx"
+//! title = "Oxford Advanced Learner's"
+//! icon = "Oxford/Oxford.png"
+//! order = 1
+//!
+//! [[dictionaries]]
+//! path = "zip:///dict/Collins.zip!/Collins.mdx"
+//! enabled = false
+//! ```
+//!
+//! A path is a URL like those accepted by [`MdxReader::from_url`](crate::readers::MdxReader::from_url),
+//! an absolute file path, or a file path relative to the directory of the manifest.
+//! Dictionaries are opened by ascending `order`, dictionaries with the same order in
+//! the order they are listed.
+
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+use crate::utils::atomic_output::AtomicOutput;
+use crate::{Result, ZdbError};
+
+/// A dictionary of a pack manifest.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DictPackEntry {
+    /// URL or file path of the MDX file, see the [module documentation](self)
+    pub path: String,
+    /// Title shown instead of the title in the header of the dictionary
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    /// URL or file path of the icon, resolved like `path`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub icon: Option<String>,
+    /// Position of the dictionary in the library, lower first (default: 0)
+    pub order: i32,
+    /// Whether the dictionary is opened (default: true)
+    pub enabled: bool,
+}
+
+impl Default for DictPackEntry {
+    fn default() -> Self {
+        Self { path: String::new(), title: None, icon: None, order: 0, enabled: true }
+    }
+}
+
+impl DictPackEntry {
+    /// Resolves the path of the dictionary to a URL.
+    ///
+    /// # Arguments
+    ///
+    /// * `base_dir` - Directory relative paths are resolved against, usually the directory of the manifest
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the path is empty or isn't a valid URL or file path.
+    pub fn url(&self, base_dir: &Path) -> Result<Url> {
+        resolve_pack_path(&self.path, base_dir)
+    }
+
+    /// Resolves the path of the icon to a URL, `None` if the dictionary has no icon.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the path isn't a valid URL or file path.
+    pub fn icon_url(&self, base_dir: &Path) -> Result<Option<Url>> {
+        self.icon.as_deref().map(|icon| resolve_pack_path(icon, base_dir)).transpose()
+    }
+}
+
+/// Manifest describing a set of dictionaries, see the [module documentation](self).
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DictPackManifest {
+    /// Name of the library
+    pub name: String,
+    pub dictionaries: Vec<DictPackEntry>,
+}
+
+impl DictPackManifest {
+    /// Parses a manifest in JSON format.
+    pub fn from_json(text: &str) -> Result<Self> {
+        Ok(serde_json::from_str(text)?)
+    }
+
+    /// Parses a manifest in TOML format.
+    pub fn from_toml(text: &str) -> Result<Self> {
+        Ok(toml::from_str(text)?)
+    }
+
+    /// Serializes the manifest to pretty-printed JSON.
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    /// Serializes the manifest to TOML.
+    pub fn to_toml(&self) -> Result<String> {
+        toml::to_string(self).map_err(|e| ZdbError::general_error(format!("Can't serialize the pack manifest: {}", e)))
+    }
+
+    /// Loads a manifest file, in TOML format if its extension is `.toml`, in JSON format otherwise.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file can't be read or parsed.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let text = fs::read_to_string(path.as_ref())?;
+        if is_toml(path.as_ref()) {
+            Self::from_toml(&text)
+        } else {
+            Self::from_json(&text)
+        }
+    }
+
+    /// Saves the manifest to a file, in the format chosen like [`load`](Self::load).
+    ///
+    /// The file is replaced atomically, an existing manifest is kept if saving fails.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let text = if is_toml(path.as_ref()) { self.to_toml()? } else { self.to_json()? };
+        let (output, mut file) = AtomicOutput::create(path.as_ref())?;
+        file.write_all(text.as_bytes())?;
+        drop(file);
+        output.commit()
+    }
+
+    /// Enabled dictionaries in the order they are opened.
+    pub fn enabled_entries(&self) -> Vec<&DictPackEntry> {
+        let mut entries: Vec<&DictPackEntry> = self.dictionaries.iter().filter(|entry| entry.enabled).collect();
+        entries.sort_by_key(|entry| entry.order);
+        entries
+    }
+}
+
+fn is_toml(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("toml"))
+}
+
+fn resolve_pack_path(path: &str, base_dir: &Path) -> Result<Url> {
+    let path = path.trim();
+    if path.is_empty() {
+        return Err(ZdbError::invalid_parameter("Empty path in pack manifest"));
+    }
+    // Windows paths like C:\dict.mdx parse as URLs with a one-letter scheme
+    if path.contains("://") {
+        return Ok(Url::parse(path)?);
+    }
+    let path = Path::new(path);
+    let path = if path.is_absolute() { path.to_path_buf() } else { std::path::absolute(base_dir.join(path))? };
+    Url::from_file_path(&path).map_err(|_| ZdbError::invalid_path(path.display().to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pack_manifest_formats() {
+        let toml = r#"
+            name = "English"
+
+            [[dictionaries]]
+            path = "b.mdx"
+            order = 2
+
+            [[dictionaries]]
+            path = "a.mdx"
+            title = "A"
+            order = 1
+
+            [[dictionaries]]
+            path = "file:///dict/c.mdx"
+            enabled = false
+        "#;
+        let manifest = DictPackManifest::from_toml(toml).unwrap();
+        let paths: Vec<&str> = manifest.enabled_entries().iter().map(|entry| entry.path.as_str()).collect();
+        assert_eq!(paths, ["a.mdx", "b.mdx"]);
+        assert_eq!(DictPackManifest::from_json(&manifest.to_json().unwrap()).unwrap(), manifest);
+        assert_eq!(DictPackManifest::from_toml(&manifest.to_toml().unwrap()).unwrap(), manifest);
+        assert_eq!(manifest.dictionaries[2].url(Path::new("/lib")).unwrap().as_str(), "file:///dict/c.mdx");
+    }
+}
@@ -0,0 +1,311 @@
+//! Registry of the open dictionaries of an application.
+//!
+//! Links in the rewritten HTML of an entry name their dictionary by profile id, see
+//! [`MdxHtmlRewriter`](crate::utils::MdxHtmlRewriter). [`DictRegistry`] assigns the
+//! profile ids and keeps the open dictionaries in library order, so a front-end can
+//! resolve `mdx://` requests to the reader serving them.
+//!
+//! Links in cached HTML stay valid across restarts when the profile ids are persisted,
+//! see [`DictRegistry::with_profile_ids`]: a dictionary gets the same profile id every
+//! time it is opened, whatever dictionaries were added or removed since.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use mdx::readers::{DictPackManifest, DictRegistry};
+//!
+//! # fn main() -> mdx::Result<()> {
+//! let manifest = DictPackManifest::load("/dict/library.toml")?;
+//! let mut registry = DictRegistry::new();
+//! for (path, error) in registry.open_pack(&manifest, "/dict".as_ref(), "my_device") {
+//!     println!("Can't open {}: {}", path, error);
+//! }
+//! for dict in registry.iter() {
+//!     println!("{}: {}", dict.profile_id, dict.title);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::{BTreeMap, LinkedList};
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+use crate::readers::dict_pack::{DictPackEntry, DictPackManifest};
+use crate::readers::mdx_reader::MdxReader;
+use crate::utils::atomic_output::AtomicOutput;
+use crate::utils::mdx_service_url::MdxServiceUrl;
+use crate::utils::io_utils::scan_dir;
+use crate::{Result, ZdbError};
+
+/// Persisted profile ids, see [`DictRegistry::with_profile_ids`].
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ProfileIdMap {
+    /// Profile id given to the next new dictionary
+    pub next_profile_id: u32,
+    /// Profile ids by dictionary UUID, or by URL for dictionaries without a UUID
+    pub profiles: BTreeMap<String, u32>,
+}
+
+/// An open dictionary of a [`DictRegistry`].
+pub struct RegisteredDict {
+    /// Id of the dictionary in the links of rewritten HTML
+    pub profile_id: u32,
+    /// Title shown for the dictionary
+    pub title: String,
+    /// URL of the icon of the dictionary, if any
+    pub icon: Option<Url>,
+    pub reader: MdxReader,
+}
+
+/// Open dictionaries by profile id, see the [module documentation](self).
+#[derive(Default)]
+pub struct DictRegistry {
+    /// Dictionaries in library order
+    dicts: Vec<RegisteredDict>,
+    profile_ids: ProfileIdMap,
+    /// File the profile ids are saved to, if they are persisted
+    profile_id_path: Option<PathBuf>,
+}
+
+impl DictRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a registry keeping the profile ids of dictionaries in a JSON file.
+    ///
+    /// A dictionary registered before gets its profile id from the file, a new one the
+    /// next unused id. Call [`save_profile_ids`](Self::save_profile_ids) after opening
+    /// the dictionaries to record the ids of the new ones.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The mapping file, it doesn't need to exist yet
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file exists but can't be read or parsed.
+    pub fn with_profile_ids<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let profile_ids = if path.exists() {
+            serde_json::from_str(&fs::read_to_string(path)?)?
+        } else {
+            ProfileIdMap::default()
+        };
+        Ok(Self { profile_ids, profile_id_path: Some(path.to_path_buf()), ..Self::default() })
+    }
+
+    /// Saves the profile ids to the file given to [`with_profile_ids`](Self::with_profile_ids),
+    /// does nothing for a registry without one.
+    ///
+    /// The file is replaced atomically, the previous ids are kept if saving fails.
+    pub fn save_profile_ids(&self) -> Result<()> {
+        let Some(ref path) = self.profile_id_path else {
+            return Ok(());
+        };
+        let (output, mut file) = AtomicOutput::create(path)?;
+        file.write_all(serde_json::to_string_pretty(&self.profile_ids)?.as_bytes())?;
+        drop(file);
+        output.commit()
+    }
+
+    /// Persisted profile ids, including those of dictionaries that aren't open.
+    pub fn profile_ids(&self) -> &ProfileIdMap {
+        &self.profile_ids
+    }
+
+    /// Gives a dictionary its recorded profile id, or records a new one.
+    fn assign_profile_id(&mut self, reader: &MdxReader) -> u32 {
+        let uuid = &reader.content_db.meta.db_info.uuid;
+        let dict_id = if uuid.is_empty() { reader.mdx_url.to_string() } else { uuid.clone() };
+        if let Some(&profile_id) = self.profile_ids.profiles.get(&dict_id)
+            && self.dicts.iter().all(|dict| dict.profile_id != profile_id) {
+            return profile_id;
+        }
+        // Ids recorded for other dictionaries are never given to a new one
+        let profile_id = self.profile_ids.profiles.values().map(|id| id + 1).max().unwrap_or(0).max(self.profile_ids.next_profile_id);
+        self.profile_ids.next_profile_id = profile_id + 1;
+        self.profile_ids.profiles.entry(dict_id).or_insert(profile_id);
+        profile_id
+    }
+
+    /// Adds an open dictionary after the others.
+    ///
+    /// # Arguments
+    ///
+    /// * `reader` - The dictionary
+    /// * `title` - Title shown for the dictionary, `None` uses the title in its header
+    /// * `icon` - URL of the icon of the dictionary
+    ///
+    /// # Returns
+    ///
+    /// Returns the profile id assigned to the dictionary, the recorded one if the profile
+    /// ids are persisted and it isn't in use, e.g. by another copy of the dictionary.
+    pub fn register(&mut self, reader: MdxReader, title: Option<String>, icon: Option<Url>) -> u32 {
+        let profile_id = self.assign_profile_id(&reader);
+        let title = title.unwrap_or_else(|| match reader.content_db.meta.db_info.title.as_str() {
+            "" => reader.db_name.clone(),
+            title => title.to_string(),
+        });
+        self.dicts.push(RegisteredDict { profile_id, title, icon, reader });
+        profile_id
+    }
+
+    /// Opens a dictionary of a pack manifest and adds it after the others.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the paths of the entry can't be resolved or the dictionary can't be opened.
+    pub fn open_entry(&mut self, entry: &DictPackEntry, base_dir: &Path, device_id: &str) -> Result<u32> {
+        let reader = MdxReader::from_url(&entry.url(base_dir)?, device_id)?;
+        Ok(self.register(reader, entry.title.clone(), entry.icon_url(base_dir)?))
+    }
+
+    /// Opens the enabled dictionaries of a pack manifest in library order.
+    ///
+    /// Dictionaries that can't be opened are left out, the others are still opened.
+    ///
+    /// # Arguments
+    ///
+    /// * `manifest` - The manifest
+    /// * `base_dir` - Directory relative paths are resolved against, usually the directory of the manifest
+    /// * `device_id` - Device identifier for license verification
+    ///
+    /// # Returns
+    ///
+    /// Returns the paths of the dictionaries that couldn't be opened with their errors.
+    pub fn open_pack(&mut self, manifest: &DictPackManifest, base_dir: &Path, device_id: &str) -> Vec<(String, ZdbError)> {
+        manifest.enabled_entries().into_iter()
+            .filter_map(|entry| self.open_entry(entry, base_dir, device_id).err().map(|e| (entry.path.clone(), e)))
+            .collect()
+    }
+
+    /// Opens the MDX files below a directory in the order of their paths.
+    ///
+    /// Dictionaries that can't be opened are left out, the others are still opened.
+    ///
+    /// # Arguments
+    ///
+    /// * `dir` - The directory, scanned recursively
+    /// * `device_id` - Device identifier for license verification
+    ///
+    /// # Returns
+    ///
+    /// Returns the paths of the dictionaries that couldn't be opened with their errors.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the directory can't be scanned.
+    pub fn open_dir(&mut self, dir: &Path, device_id: &str) -> Result<Vec<(String, ZdbError)>> {
+        let mut files = LinkedList::new();
+        scan_dir(dir, &Regex::new(r"(?i)\.mdx$").expect("valid regex"), true, &mut files)?;
+        let mut files: Vec<PathBuf> = files.into_iter().collect();
+        // The file system lists files in any order
+        files.sort();
+        let mut failures = Vec::new();
+        for file in files {
+            let opened = Url::from_file_path(&file)
+                .map_err(|_| ZdbError::invalid_path(file.display().to_string()))
+                .and_then(|url| MdxReader::from_url(&url, device_id));
+            match opened {
+                Ok(reader) => {
+                    self.register(reader, None, None);
+                }
+                Err(e) => failures.push((file.display().to_string(), e)),
+            }
+        }
+        Ok(failures)
+    }
+
+    /// Gets a dictionary by profile id.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `ProfileNotFound` error if no dictionary has the profile id.
+    pub fn get(&self, profile_id: u32) -> Result<&RegisteredDict> {
+        self.dicts.iter().find(|dict| dict.profile_id == profile_id).ok_or_else(|| ZdbError::profile_not_found(profile_id))
+    }
+
+    /// Gets a dictionary by profile id for lookups, see [`get`](Self::get).
+    pub fn get_mut(&mut self, profile_id: u32) -> Result<&mut RegisteredDict> {
+        self.dicts.iter_mut().find(|dict| dict.profile_id == profile_id).ok_or_else(|| ZdbError::profile_not_found(profile_id))
+    }
+
+    /// Closes a dictionary, its profile id isn't reused.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `ProfileNotFound` error if no dictionary has the profile id.
+    pub fn remove(&mut self, profile_id: u32) -> Result<RegisteredDict> {
+        let position = self.dicts.iter().position(|dict| dict.profile_id == profile_id).ok_or_else(|| ZdbError::profile_not_found(profile_id))?;
+        Ok(self.dicts.remove(position))
+    }
+
+    /// Resolves the name of a dictionary in a cross-dictionary link, see
+    /// [`MdxHtmlRewriter::rewrite_cross_dictionary_link`](crate::utils::MdxHtmlRewriter::rewrite_cross_dictionary_link).
+    ///
+    /// Names are matched against the file names of the dictionaries without extension,
+    /// then their titles, then both ignoring case. The first dictionary in library order wins.
+    ///
+    /// # Returns
+    ///
+    /// Returns the profile id of the dictionary, `None` if no dictionary has the name.
+    pub fn resolve_profile_name(&self, name: &str) -> Option<u32> {
+        let name = name.trim();
+        let matchers: [&dyn Fn(&RegisteredDict) -> bool; 3] = [
+            &|dict| dict.reader.db_name == name,
+            &|dict| dict.title == name,
+            &|dict| dict.reader.db_name.to_lowercase() == name.to_lowercase() || dict.title.to_lowercase() == name.to_lowercase(),
+        ];
+        matchers.iter().find_map(|matches| self.dicts.iter().find(|dict| matches(dict))).map(|dict| dict.profile_id)
+    }
+
+    /// Resolves the dictionary serving a service URL, the one named by its `profile_name`
+    /// if it has one, else the one of its profile id.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `InvalidParameter` error if no dictionary has the name, or a
+    /// `ProfileNotFound` error if no dictionary has the profile id.
+    pub fn resolve_service_url(&self, url: &MdxServiceUrl) -> Result<u32> {
+        match &url.profile_name {
+            Some(name) => self.resolve_profile_name(name)
+                .ok_or_else(|| ZdbError::invalid_parameter(format!("No dictionary is named '{}'", name))),
+            // Negative ids belong to no dictionary
+            None => Ok(self.get(u32::try_from(url.profile_id).unwrap_or(u32::MAX))?.profile_id),
+        }
+    }
+
+    /// Names cross-dictionary links may use for the dictionaries, their file names
+    /// without extension and their titles, see
+    /// [`MdxHtmlRewriterInstance::with_dictionary_names`](crate::utils::MdxHtmlRewriterInstance::with_dictionary_names).
+    pub fn dictionary_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.dicts.iter()
+            .flat_map(|dict| [dict.reader.db_name.clone(), dict.title.clone()])
+            .filter(|name| !name.is_empty())
+            .collect();
+        names.sort();
+        names.dedup();
+        names
+    }
+
+    /// Dictionaries in library order.
+    pub fn iter(&self) -> impl Iterator<Item = &RegisteredDict> {
+        self.dicts.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.dicts.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.dicts.is_empty()
+    }
+}
@@ -0,0 +1,198 @@
+//! Headword and content statistics for dictionary files.
+//!
+//! This module walks every entry of a dictionary and summarizes it into a
+//! serializable [`DictStatistics`] struct. It is intended for tooling dashboards
+//! and for sanity checks after converting a dictionary, e.g. to verify that the
+//! number of link entries or the distribution of resource types looks right.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use mdx::readers::ZdbReader;
+//! use mdx::readers::dict_stats::DictStatistics;
+//!
+//! # fn main() -> mdx::Result<()> {
+//! let mut reader = ZdbReader::<std::io::BufReader<std::fs::File>>::from_file("dict.mdx", "", "")?;
+//! let stats = DictStatistics::collect(&mut reader, None)?;
+//! println!("{}", serde_json::to_string_pretty(&stats)?);
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::BTreeMap;
+use std::io::{Read, Seek};
+
+use serde::Serialize;
+
+use crate::storage::key_block::EntryNo;
+use crate::utils::mdd_key;
+use crate::utils::progress_report::{ProgressReportFn, ProgressState};
+use crate::{Result, ZdbError};
+use super::zdb_reader::ZdbReader;
+
+/// Bucket name used for keys without any character.
+const EMPTY_KEY_BUCKET: &str = "(empty)";
+/// Bucket name used for resources without a file extension.
+const NO_EXTENSION_BUCKET: &str = "(none)";
+
+/// Size distribution of entry contents, in bytes.
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct ContentSizeStats {
+    pub total: u64,
+    pub min: u64,
+    pub max: u64,
+    pub average: f64,
+    pub p50: u64,
+    pub p90: u64,
+    pub p99: u64,
+}
+
+impl ContentSizeStats {
+    /// Computes the size distribution from a list of content sizes.
+    ///
+    /// The list is sorted in place. Percentiles use the nearest-rank method.
+    pub fn from_sizes(sizes: &mut [u64]) -> Self {
+        if sizes.is_empty() {
+            return Self::default();
+        }
+        sizes.sort_unstable();
+        let total: u64 = sizes.iter().sum();
+        Self {
+            total,
+            min: sizes[0],
+            max: sizes[sizes.len() - 1],
+            average: total as f64 / sizes.len() as f64,
+            p50: percentile(sizes, 50),
+            p90: percentile(sizes, 90),
+            p99: percentile(sizes, 99),
+        }
+    }
+}
+
+/// Statistics over all entries of a dictionary.
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct DictStatistics {
+    /// Total number of entries
+    pub entry_count: u64,
+    /// Whether the dictionary is a resource (MDD) file
+    pub is_mdd: bool,
+    /// Entry count per lowercased initial character of the headword
+    pub initials: BTreeMap<String, u64>,
+    /// Entry count per Unicode script of the first character of the headword
+    pub scripts: BTreeMap<String, u64>,
+    /// Distribution of content sizes
+    pub content_sizes: ContentSizeStats,
+    /// Number of `@@@LINK=` redirect entries
+    pub link_count: u64,
+    /// Resource count per lowercased file extension, only filled for MDD files
+    pub resource_types: BTreeMap<String, u64>,
+}
+
+impl DictStatistics {
+    /// Walks all entries of the dictionary and collects statistics.
+    ///
+    /// # Arguments
+    ///
+    /// * `reader` - The dictionary reader
+    /// * `prog_rpt` - Optional progress reporter, returning `true` cancels the walk
+    ///
+    /// # Returns
+    ///
+    /// Returns the collected statistics.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if an entry can not be read or if the walk was cancelled.
+    pub fn collect<R: Read + Seek>(reader: &mut ZdbReader<R>, prog_rpt: Option<ProgressReportFn>) -> Result<Self> {
+        let entry_count = reader.get_entry_count();
+        let is_mdd = reader.meta.db_info.is_mdd;
+        let mut stats = DictStatistics { entry_count, is_mdd, ..Default::default() };
+        let mut sizes = Vec::with_capacity(entry_count as usize);
+        let mut prog = ProgressState::new("collect_statistics", entry_count, 1, prog_rpt);
+
+        for entry_no in 0..entry_count as EntryNo {
+            let key_index = reader.get_index(entry_no)?;
+            let first_char = key_index.key.trim_start_matches(['/', '\\']).chars().next();
+            let (initial, script) = match first_char {
+                Some(c) => (c.to_lowercase().collect::<String>(), char_script(c).to_string()),
+                None => (EMPTY_KEY_BUCKET.to_string(), EMPTY_KEY_BUCKET.to_string()),
+            };
+            *stats.initials.entry(initial).or_default() += 1;
+            *stats.scripts.entry(script).or_default() += 1;
+
+            sizes.push(reader.get_content_length(entry_no)?);
+
+            if is_mdd {
+                let extension = mdd_key::extension(&key_index.key).unwrap_or_else(|| NO_EXTENSION_BUCKET.to_string());
+                *stats.resource_types.entry(extension).or_default() += 1;
+            } else if reader.is_link_entry(&key_index)? {
+                stats.link_count += 1;
+            }
+
+            if prog.report(entry_no as u64) {
+                return Err(ZdbError::user_interrupted());
+            }
+        }
+        stats.content_sizes = ContentSizeStats::from_sizes(&mut sizes);
+        Ok(stats)
+    }
+}
+
+fn percentile(sorted: &[u64], pct: usize) -> u64 {
+    let rank = (sorted.len() * pct).div_ceil(100);
+    sorted[rank.saturating_sub(1).min(sorted.len() - 1)]
+}
+
+/// Returns a coarse Unicode script name for a character.
+///
+/// Only the scripts commonly found in dictionaries are distinguished, everything
+/// else is reported as `Other`.
+pub fn char_script(c: char) -> &'static str {
+    match c as u32 {
+        0x30..=0x39 => "Digit",
+        0x41..=0x5A | 0x61..=0x7A | 0xC0..=0x24F | 0x1E00..=0x1EFF => "Latin",
+        0x370..=0x3FF | 0x1F00..=0x1FFF => "Greek",
+        0x400..=0x52F => "Cyrillic",
+        0x590..=0x5FF => "Hebrew",
+        0x600..=0x6FF | 0x750..=0x77F => "Arabic",
+        0x900..=0x97F => "Devanagari",
+        0xE00..=0xE7F => "Thai",
+        0x1100..=0x11FF | 0x3130..=0x318F | 0xAC00..=0xD7AF => "Hangul",
+        0x3040..=0x309F => "Hiragana",
+        0x30A0..=0x30FF | 0x31F0..=0x31FF => "Katakana",
+        0x3400..=0x4DBF | 0x4E00..=0x9FFF | 0xF900..=0xFAFF | 0x20000..=0x2FA1F => "Han",
+        _ if c.is_ascii_punctuation() || c.is_whitespace() => "Punctuation",
+        _ => "Other",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_content_size_stats() {
+        let mut sizes: Vec<u64> = (1..=100).rev().collect();
+        let stats = ContentSizeStats::from_sizes(&mut sizes);
+        assert_eq!(stats.total, 5050);
+        assert_eq!(stats.min, 1);
+        assert_eq!(stats.max, 100);
+        assert_eq!(stats.p50, 50);
+        assert_eq!(stats.p90, 90);
+        assert_eq!(stats.p99, 99);
+        assert_eq!(ContentSizeStats::from_sizes(&mut []), ContentSizeStats::default());
+    }
+
+    #[test]
+    fn test_buckets() {
+        assert_eq!(char_script('a'), "Latin");
+        assert_eq!(char_script('é'), "Latin");
+        assert_eq!(char_script('中'), "Han");
+        assert_eq!(char_script('あ'), "Hiragana");
+        assert_eq!(char_script('한'), "Hangul");
+        assert_eq!(char_script('я'), "Cyrillic");
+        assert_eq!(mdd_key::extension("\\img\\Cat.PNG").as_deref(), Some("png"));
+        assert_eq!(mdd_key::extension("/sound/.hidden"), None);
+        assert_eq!(mdd_key::extension("/readme"), None);
+    }
+}
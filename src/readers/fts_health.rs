@@ -0,0 +1,203 @@
+//! Statistics and consistency checks of FTS indexes.
+//!
+//! An index is built for one version of a dictionary. When the dictionary is updated,
+//! the index may still open but return wrong entries. [`FtsIndexStats`] describes an
+//! index, e.g. for an "about" page, and [`check_index`] compares it with the dictionary
+//! so an application can ask the user to rebuild it, see
+//! [`MdxReader::check_fts_index`](crate::MdxReader::check_fts_index).
+
+use std::collections::BTreeSet;
+use std::fmt;
+
+use serde::Serialize;
+use tantivy::collector::DocSetCollector;
+use tantivy::query::AllQuery;
+use tantivy::schema::{FieldType, Value};
+use tantivy::{Index, TantivyDocument};
+
+use crate::builder::fts_index_builder::{FtsIndexMetadata, FTS_SCHEMA_VERSION};
+use crate::storage::key_block::EntryNo;
+use crate::{Result, ZdbError};
+
+/// Field of an FTS index.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct FtsFieldInfo {
+    pub name: String,
+    /// Type of the values, e.g. `Str` or `U64`
+    pub value_type: &'static str,
+    pub stored: bool,
+    /// Tokenizer of indexed text fields
+    pub tokenizer: Option<String>,
+}
+
+/// Size and schema of an FTS index, see [`FtsIndexStats::collect`].
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct FtsIndexStats {
+    /// Number of indexed documents
+    pub doc_count: u64,
+    /// Number of entries of the dictionary
+    pub entry_count: u64,
+    pub segment_count: usize,
+    /// Total size of the index files in bytes
+    pub total_size: u64,
+    /// Metadata document of the index, `None` for indexes built by other tools or older versions
+    pub metadata: Option<FtsIndexMetadata>,
+    pub fields: Vec<FtsFieldInfo>,
+}
+
+impl FtsIndexStats {
+    /// Collects the statistics of an index.
+    ///
+    /// # Arguments
+    ///
+    /// * `index` - The opened index
+    /// * `entry_count` - Number of entries of the dictionary the index belongs to
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the index can't be read.
+    pub fn collect(index: &Index, entry_count: u64) -> Result<Self> {
+        let searcher = open_searcher(index)?;
+        let total_size = searcher.space_usage()
+            .map_err(|e| ZdbError::general_error(format!("Failed to compute the FTS index size: {}", e)))?
+            .total().get_bytes();
+        let fields = index.schema().fields()
+            .map(|(_, entry)| FtsFieldInfo {
+                name: entry.name().to_string(),
+                value_type: entry.field_type().value_type().name(),
+                stored: entry.is_stored(),
+                tokenizer: match entry.field_type() {
+                    FieldType::Str(options) => options.get_indexing_options().map(|indexing| indexing.tokenizer().to_string()),
+                    _ => None,
+                },
+            })
+            .collect();
+        Ok(Self {
+            doc_count: searcher.num_docs(),
+            entry_count,
+            segment_count: searcher.segment_readers().len(),
+            total_size,
+            metadata: FtsIndexMetadata::read(index).ok(),
+            fields,
+        })
+    }
+}
+
+/// Inconsistency between an FTS index and its dictionary, see [`check_index`].
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind")]
+pub enum FtsIndexProblem {
+    /// The index has no metadata document, it was built by another tool or an older version
+    MissingMetadata,
+    /// The index was built with another schema version
+    SchemaVersion { found: u32, expected: u32 },
+    /// The index was built for a dictionary with another number of entries
+    EntryCount { indexed: u64, dictionary: u64 },
+    /// The index was built for another build of the dictionary
+    UuidMismatch { indexed: String, dictionary: String },
+    /// The number of documents differs from the number of entries
+    DocCount { doc_count: u64, entry_count: u64 },
+    /// Entries without a document, e.g. their content couldn't be read when indexing
+    MissingEntries { count: u64, first: EntryNo },
+    /// Entries indexed more than once
+    DuplicateEntries { count: u64, first: EntryNo },
+    /// Documents of entries the dictionary doesn't have
+    UnknownEntries { count: u64, first: EntryNo },
+}
+
+impl FtsIndexProblem {
+    /// Checks whether searches return wrong entries until the index is rebuilt, rather than missing some.
+    pub fn is_stale(&self) -> bool {
+        !matches!(self, FtsIndexProblem::MissingEntries { .. } | FtsIndexProblem::DocCount { .. })
+    }
+}
+
+impl fmt::Display for FtsIndexProblem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FtsIndexProblem::MissingMetadata => write!(f, "The index has no metadata, it was built by another tool or an older version"),
+            FtsIndexProblem::SchemaVersion { found, expected } => write!(f, "The index has schema version {}, expected {}", found, expected),
+            FtsIndexProblem::EntryCount { indexed, dictionary } => write!(f, "The index was built for {} entries but the dictionary has {}", indexed, dictionary),
+            FtsIndexProblem::UuidMismatch { indexed, dictionary } => write!(f, "The index was built for dictionary {} but the dictionary is {}", indexed, dictionary),
+            FtsIndexProblem::DocCount { doc_count, entry_count } => write!(f, "The index has {} documents but the dictionary has {} entries", doc_count, entry_count),
+            FtsIndexProblem::MissingEntries { count, first } => write!(f, "{} entries are not indexed, the first is entry {}", count, first),
+            FtsIndexProblem::DuplicateEntries { count, first } => write!(f, "{} entries are indexed more than once, the first is entry {}", count, first),
+            FtsIndexProblem::UnknownEntries { count, first } => write!(f, "{} documents belong to no entry, the first is entry {}", count, first),
+        }
+    }
+}
+
+/// Compares an index with its dictionary.
+///
+/// Reads the entry number of every document, so it takes about as long as a search
+/// returning all of them.
+///
+/// # Arguments
+///
+/// * `index` - The opened index
+/// * `entry_count` - Number of entries of the dictionary
+/// * `dict_uuid` - UUID of the dictionary, not compared if it or the one of the index is empty
+///
+/// # Returns
+///
+/// Returns the problems found, empty for a healthy index.
+///
+/// # Errors
+///
+/// Returns an error if the index can't be read.
+pub fn check_index(index: &Index, entry_count: u64, dict_uuid: &str) -> Result<Vec<FtsIndexProblem>> {
+    let mut problems = Vec::new();
+    match FtsIndexMetadata::read(index) {
+        Ok(metadata) => {
+            if metadata.schema_version != FTS_SCHEMA_VERSION {
+                problems.push(FtsIndexProblem::SchemaVersion { found: metadata.schema_version, expected: FTS_SCHEMA_VERSION });
+            }
+            if metadata.entry_count != entry_count {
+                problems.push(FtsIndexProblem::EntryCount { indexed: metadata.entry_count, dictionary: entry_count });
+            }
+            if !metadata.dict_uuid.is_empty() && !dict_uuid.is_empty() && metadata.dict_uuid != dict_uuid {
+                problems.push(FtsIndexProblem::UuidMismatch { indexed: metadata.dict_uuid, dictionary: dict_uuid.to_string() });
+            }
+        }
+        Err(_) => problems.push(FtsIndexProblem::MissingMetadata),
+    }
+
+    let searcher = open_searcher(index)?;
+    let doc_count = searcher.num_docs();
+    if doc_count != entry_count {
+        problems.push(FtsIndexProblem::DocCount { doc_count, entry_count });
+    }
+    let entry_no_field = index.schema().get_field("entry_no")
+        .map_err(|_| ZdbError::general_error("Field 'entry_no' not found in FTS schema".to_string()))?;
+    let doc_addresses = searcher.search(&AllQuery, &DocSetCollector)
+        .map_err(|e| ZdbError::general_error(format!("Failed to list the FTS documents: {}", e)))?;
+    let mut indexed = BTreeSet::new();
+    let mut duplicates = BTreeSet::new();
+    for doc_address in doc_addresses {
+        let doc = searcher.doc::<TantivyDocument>(doc_address)
+            .map_err(|e| ZdbError::general_error(format!("Failed to retrieve document: {}", e)))?;
+        let entry_no = doc.get_first(entry_no_field).and_then(|v| v.as_u64())
+            .ok_or(ZdbError::general_error("Entry number not found in FTS index".to_string()))?;
+        if !indexed.insert(entry_no) {
+            duplicates.insert(entry_no);
+        }
+    }
+    if let Some(&first) = duplicates.first() {
+        problems.push(FtsIndexProblem::DuplicateEntries { count: duplicates.len() as u64, first: first as EntryNo });
+    }
+    let unknown = indexed.range(entry_count..);
+    if let Some(&first) = unknown.clone().next() {
+        problems.push(FtsIndexProblem::UnknownEntries { count: unknown.count() as u64, first: first as EntryNo });
+    }
+    let mut missing = (0..entry_count).filter(|entry_no| !indexed.contains(entry_no));
+    if let Some(first) = missing.next() {
+        problems.push(FtsIndexProblem::MissingEntries { count: missing.count() as u64 + 1, first: first as EntryNo });
+    }
+    Ok(problems)
+}
+
+fn open_searcher(index: &Index) -> Result<tantivy::Searcher> {
+    let reader = index.reader()
+        .map_err(|e| ZdbError::general_error(format!("Failed to create FTS index reader: {}", e)))?;
+    Ok(reader.searcher())
+}
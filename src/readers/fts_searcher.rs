@@ -0,0 +1,373 @@
+//! Reusable full-text search handle.
+//!
+//! Opening an index reader and building a query parser is much more expensive than
+//! running a query, which matters for searching as the user types. [`FtsSearcher`]
+//! keeps both, and takes a searcher of the latest committed generation of the index
+//! for every query. The reader reloads on commit, so an index rebuilt in place is
+//! picked up without reopening the dictionary.
+//!
+//! A searcher is cheap to clone and can be moved to other threads, unlike the
+//! dictionary reader it comes from.
+
+use std::collections::HashSet;
+
+use tantivy::collector::{DocSetCollector, TopDocs};
+use tantivy::query::{BooleanQuery, EnableScoring, FuzzyTermQuery, Occur, Query, QueryParser, TermQuery};
+use tantivy::schema::{Field, IndexRecordOption, Value};
+use tantivy::tokenizer::TextAnalyzer;
+use tantivy::{DocAddress, DocSet, Index, IndexReader, Searcher, TantivyDocument, Term, TERMINATED};
+
+use crate::builder::fts_index_builder::{FTS_KEY_TOKENS_FIELD, FTS_ROMANIZED_KEY_FIELD};
+use crate::readers::search_limits::{LimitedResults, SearchBudget, SearchLimits};
+use crate::storage::key_block::EntryNo;
+use crate::{Result, ZdbError};
+
+/// Largest edit distance supported by suggestions.
+pub const MAX_SUGGESTION_DISTANCE: u8 = 2;
+/// Maximum number of entries examined per edit distance when collecting suggestions.
+const MAX_SUGGESTION_CANDIDATES: usize = 1000;
+
+/// Headword suggested for a possibly misspelled term, see [`FtsSearcher::suggest`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Suggestion {
+    pub key: String,
+    pub entry_no: EntryNo,
+    /// Edit distance between the term and the closest word of the headword, a transposition counts as one edit
+    pub distance: u8,
+    /// Number of indexed headwords containing that closest word
+    pub frequency: u64,
+}
+
+/// Options of [`FtsSearcher::search_with_options`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct FtsSearchOptions {
+    /// Also matches the query against the romanized keys, e.g. "shuang" finds 双. The index
+    /// must have been built with [`FtsIndexOptions::romanization`](crate::builder::FtsIndexOptions::romanization).
+    pub match_romanized: bool,
+}
+
+/// Full-text search handle of a dictionary, see [`MdxReader::fts_searcher`](crate::MdxReader::fts_searcher).
+#[derive(Clone)]
+pub struct FtsSearcher {
+    reader: IndexReader,
+    query_parser: QueryParser,
+    romanized_query_parser: Option<QueryParser>,
+    key_field: Field,
+    entry_no_field: Field,
+    key_tokenizer: TextAnalyzer,
+    key_tokens: Option<(Field, TextAnalyzer)>,
+}
+
+impl FtsSearcher {
+    /// Opens a reader of the index, reloading when a new generation is committed.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the reader can't be created or the index lacks a field of the schema.
+    pub fn new(index: &Index) -> Result<Self> {
+        let reader = index.reader()
+            .map_err(|e| ZdbError::general_error(format!("Failed to create FTS index reader: {}", e)))?;
+
+        // Get schema fields
+        let schema = index.schema();
+        let key_field = schema.get_field("key")
+            .map_err(|_| ZdbError::general_error("Field 'key' not found in FTS schema".to_string()))?;
+        let content_field = schema.get_field("content")
+            .map_err(|_| ZdbError::general_error("Field 'content' not found in FTS schema".to_string()))?;
+        let entry_no_field = schema.get_field("entry_no")
+            .map_err(|_| ZdbError::general_error("Field 'entry_no' not found in FTS schema".to_string()))?;
+
+        // Create query parser for the searchable fields
+        let query_parser = QueryParser::for_index(index, vec![key_field, content_field]);
+        let romanized_query_parser = schema.get_field(FTS_ROMANIZED_KEY_FIELD).ok()
+            .map(|romanized_field| QueryParser::for_index(index, vec![key_field, content_field, romanized_field]));
+        let key_tokenizer = index.tokenizer_for_field(key_field)
+            .map_err(|e| ZdbError::general_error(format!("Failed to get the tokenizer of field 'key': {}", e)))?;
+        let key_tokens = match schema.get_field(FTS_KEY_TOKENS_FIELD) {
+            Ok(field) => Some((field, index.tokenizer_for_field(field)
+                .map_err(|e| ZdbError::general_error(format!("Failed to get the tokenizer of field '{}': {}", FTS_KEY_TOKENS_FIELD, e)))?)),
+            Err(_) => None,
+        };
+        Ok(Self { reader, query_parser, romanized_query_parser, key_field, entry_no_field, key_tokenizer, key_tokens })
+    }
+
+    /// Number of indexed entries in the current generation of the index.
+    pub fn num_docs(&self) -> u64 {
+        self.reader.searcher().num_docs()
+    }
+
+    /// Checks whether the index has romanized keys, see [`FtsSearchOptions::match_romanized`].
+    pub fn has_romanized_keys(&self) -> bool {
+        self.romanized_query_parser.is_some()
+    }
+
+    /// Searches the keys and contents of the dictionary.
+    ///
+    /// # Arguments
+    ///
+    /// * `query_str` - The query in Tantivy query syntax
+    /// * `max_results` - Maximum number of results
+    ///
+    /// # Returns
+    ///
+    /// Returns (score, entry_no, key) tuples of the matching entries, best match first.
+    pub fn search(&self, query_str: &str, max_results: usize) -> Result<Vec<(f32, EntryNo, String)>> {
+        self.search_with_options(query_str, max_results, &FtsSearchOptions::default())
+    }
+
+    /// Searches the dictionary like [`search`](Self::search), with the fields selected by `options`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `InvalidParameter` error if romanized keys are requested but the index has none.
+    pub fn search_with_options(&self, query_str: &str, max_results: usize, options: &FtsSearchOptions) -> Result<Vec<(f32, EntryNo, String)>> {
+        let query_parser = if options.match_romanized {
+            self.romanized_query_parser.as_ref().ok_or_else(|| ZdbError::invalid_parameter(
+                "The FTS index has no romanized keys, rebuild it with a romanization"
+            ))?
+        } else {
+            &self.query_parser
+        };
+        let searcher = self.reader.searcher();
+
+        // Parse the search query
+        let query = query_parser.parse_query(query_str)
+            .map_err(|e| ZdbError::general_error(format!("Failed to parse query '{}': {}", query_str, e)))?;
+
+        // Perform the search
+        let top_docs = searcher.search(&query, &TopDocs::with_limit(max_results))
+            .map_err(|e| ZdbError::general_error(format!("FTS search failed: {}", e)))?;
+
+        // Extract results
+        let mut results = Vec::new();
+        for (score, doc_address) in top_docs {
+            let (entry_no, key) = self.get_entry(&searcher, doc_address)?;
+            results.push((score, entry_no, key));
+        }
+
+        Ok(results)
+    }
+
+    /// Searches the keys and contents like [`search`](Self::search), stopping at the limits.
+    ///
+    /// The matching documents are scored one by one, so [`SearchLimits::max_matches`]
+    /// bounds the documents examined and the timeout is checked while scoring. The
+    /// results are the best of the documents examined before a limit was reached.
+    ///
+    /// # Arguments
+    ///
+    /// * `query_str` - The query in Tantivy query syntax
+    /// * `max_results` - Maximum number of results
+    /// * `limits` - Limits of the search, key block limits don't apply
+    ///
+    /// # Returns
+    ///
+    /// Returns (score, entry_no, key) tuples best match first, and the limit that stopped the search if any.
+    pub fn search_limited(&self, query_str: &str, max_results: usize, limits: &SearchLimits) -> Result<LimitedResults<(f32, EntryNo, String)>> {
+        let mut budget = SearchBudget::start(limits);
+        let searcher = self.reader.searcher();
+        let query = self.query_parser.parse_query(query_str)
+            .map_err(|e| ZdbError::general_error(format!("Failed to parse query '{}': {}", query_str, e)))?;
+        let weight = query.weight(EnableScoring::enabled_from_searcher(&searcher))
+            .map_err(|e| ZdbError::general_error(format!("FTS search failed: {}", e)))?;
+
+        let mut scored: Vec<(f32, DocAddress)> = Vec::new();
+        let mut truncated = None;
+        'segments: for (segment_ord, segment_reader) in searcher.segment_readers().iter().enumerate() {
+            if let Some(limit) = budget.check_time() {
+                truncated = Some(limit);
+                break;
+            }
+            let mut scorer = weight.scorer(segment_reader, 1.0)
+                .map_err(|e| ZdbError::general_error(format!("FTS search failed: {}", e)))?;
+            let mut doc = scorer.doc();
+            while doc != TERMINATED {
+                if !segment_reader.is_deleted(doc) {
+                    if let Some(limit) = budget.add_match() {
+                        truncated = Some(limit);
+                        break 'segments;
+                    }
+                    scored.push((scorer.score(), DocAddress::new(segment_ord as u32, doc)));
+                    // Keep the memory bounded when many documents match
+                    if scored.len() >= max_results.saturating_mul(2).max(1024) {
+                        keep_best(&mut scored, max_results);
+                    }
+                }
+                doc = scorer.advance();
+            }
+        }
+        keep_best(&mut scored, max_results);
+
+        let mut results = Vec::with_capacity(scored.len());
+        for (score, doc_address) in scored {
+            let (entry_no, key) = self.get_entry(&searcher, doc_address)?;
+            results.push((score, entry_no, key));
+        }
+        Ok(LimitedResults { results, truncated })
+    }
+
+    /// Finds the multi-word headwords containing `word`, e.g. "kick the bucket" for "bucket".
+    ///
+    /// Words are matched case-insensitively, a `word` of several words matches the headwords
+    /// containing all of them in any order.
+    ///
+    /// # Returns
+    ///
+    /// Returns (entry_no, key) pairs of the matching headwords in key order.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `InvalidParameter` error if the index was built without
+    /// [`FtsIndexOptions::headword_tokens`](crate::builder::FtsIndexOptions::headword_tokens).
+    pub fn search_headword_tokens(&self, word: &str) -> Result<Vec<(EntryNo, String)>> {
+        let (field, tokenizer) = self.key_tokens.as_ref().ok_or_else(|| ZdbError::invalid_parameter(
+            "The FTS index has no headword tokens, rebuild it with headword tokens"
+        ))?;
+        let mut tokenizer = tokenizer.clone();
+        let mut terms: Vec<(Occur, Box<dyn Query>)> = Vec::new();
+        tokenizer.token_stream(word).process(&mut |token| {
+            let term = Term::from_field_text(*field, &token.text);
+            terms.push((Occur::Must, Box::new(TermQuery::new(term, IndexRecordOption::Basic))));
+        });
+        if terms.is_empty() {
+            return Ok(Vec::new());
+        }
+        let searcher = self.reader.searcher();
+        let doc_addresses = searcher.search(&BooleanQuery::new(terms), &DocSetCollector)
+            .map_err(|e| ZdbError::general_error(format!("FTS headword token search failed: {}", e)))?;
+        let mut entries = doc_addresses.into_iter()
+            .map(|doc_address| self.get_entry(&searcher, doc_address))
+            .collect::<Result<Vec<_>>>()?;
+        entries.sort_unstable();
+        Ok(entries)
+    }
+
+    /// Suggests headwords for a possibly misspelled term from the term dictionary of the index.
+    ///
+    /// Matches words of the indexed headwords within `max_distance` edits of the term,
+    /// without scanning the key blocks of the dictionary. Closer matches are collected
+    /// first, so the examined entries are limited per distance.
+    ///
+    /// # Arguments
+    ///
+    /// * `term` - A single word, matched case-insensitively
+    /// * `max_distance` - Maximum edit distance, at most [`MAX_SUGGESTION_DISTANCE`]
+    /// * `limit` - Maximum number of suggestions
+    ///
+    /// # Returns
+    ///
+    /// Returns distinct headwords ordered by distance, then by frequency of the matched word
+    /// in the headwords, most frequent first.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `InvalidParameter` error if `max_distance` is too large.
+    pub fn suggest(&self, term: &str, max_distance: u8, limit: usize) -> Result<Vec<Suggestion>> {
+        if max_distance > MAX_SUGGESTION_DISTANCE {
+            return Err(ZdbError::invalid_parameter(format!(
+                "Suggestion distance {} is too large, at most {} is supported", max_distance, MAX_SUGGESTION_DISTANCE
+            )));
+        }
+        let term = term.trim().to_lowercase();
+        if term.is_empty() || limit == 0 {
+            return Ok(Vec::new());
+        }
+        let searcher = self.reader.searcher();
+        let mut key_tokenizer = self.key_tokenizer.clone();
+        let mut seen_docs = HashSet::new();
+        let mut seen_keys = HashSet::new();
+        let mut suggestions = Vec::new();
+        for distance in 0..=max_distance {
+            let query = FuzzyTermQuery::new(Term::from_field_text(self.key_field, &term), distance, true);
+            let top_docs = searcher.search(&query, &TopDocs::with_limit(MAX_SUGGESTION_CANDIDATES))
+                .map_err(|e| ZdbError::general_error(format!("FTS suggestion failed: {}", e)))?;
+            let mut candidates = Vec::new();
+            for (_, doc_address) in top_docs {
+                if !seen_docs.insert(doc_address) {
+                    continue;
+                }
+                let (entry_no, key) = self.get_entry(&searcher, doc_address)?;
+                if !seen_keys.insert(key.clone()) {
+                    continue;
+                }
+                // Documents matched at a smaller distance were skipped, so every word within reach is `distance` edits away
+                let mut frequency = 0;
+                {
+                    let mut token_stream = key_tokenizer.token_stream(&key);
+                    while let Some(token) = token_stream.next() {
+                        if edit_distance(&token.text, &term) <= distance as usize {
+                            frequency = frequency.max(searcher.doc_freq(&Term::from_field_text(self.key_field, &token.text))
+                                .map_err(|e| ZdbError::general_error(format!("Failed to read term frequency: {}", e)))?);
+                        }
+                    }
+                }
+                candidates.push(Suggestion { key, entry_no, distance, frequency });
+            }
+            candidates.sort_by(|a, b| b.frequency.cmp(&a.frequency).then_with(|| a.key.cmp(&b.key)));
+            suggestions.extend(candidates);
+            if suggestions.len() >= limit {
+                break;
+            }
+        }
+        suggestions.truncate(limit);
+        Ok(suggestions)
+    }
+
+    /// Reads the entry number and key stored in a document.
+    fn get_entry(&self, searcher: &Searcher, doc_address: DocAddress) -> Result<(EntryNo, String)> {
+        // Retrieve the document from the index
+        let retrieved_doc = searcher.doc::<TantivyDocument>(doc_address)
+            .map_err(|e| ZdbError::general_error(format!("Failed to retrieve document: {}", e)))?;
+
+        // Extract fields from the document
+        let entry_no: EntryNo = retrieved_doc
+            .get_first(self.entry_no_field)
+            .and_then(|v| v.as_u64())
+            .map(|n| n as EntryNo)
+            .ok_or(ZdbError::general_error("Entry number not found in FTS index".to_string()))?;
+        let key = retrieved_doc.get_first(self.key_field)
+            .and_then(|v| v.as_str())
+            .unwrap_or("Unknown")
+            .to_string();
+        Ok((entry_no, key))
+    }
+}
+
+/// Sorts scored documents best first and drops all but the first `max_results`.
+fn keep_best(scored: &mut Vec<(f32, DocAddress)>, max_results: usize) {
+    scored.sort_by(|a, b| b.0.total_cmp(&a.0).then(a.1.cmp(&b.1)));
+    scored.truncate(max_results);
+}
+
+/// Levenshtein distance counting a transposition of adjacent characters as one edit, like the fuzzy query.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut rows = vec![(0..=b.len()).collect::<Vec<usize>>()];
+    for i in 1..=a.len() {
+        let mut row = vec![i; b.len() + 1];
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            row[j] = (rows[i - 1][j] + 1).min(row[j - 1] + 1).min(rows[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                row[j] = row[j].min(rows[i - 2][j - 2] + 1);
+            }
+        }
+        rows.push(row);
+    }
+    rows[a.len()][b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_edit_distance() {
+        assert_eq!(edit_distance("word", "word"), 0);
+        assert_eq!(edit_distance("wrod", "word"), 1);
+        assert_eq!(edit_distance("wor", "word"), 1);
+        assert_eq!(edit_distance("ward", "world"), 2);
+        assert_eq!(edit_distance("", "ab"), 2);
+    }
+}
@@ -0,0 +1,173 @@
+//! Language and script detection for dictionary contents.
+//!
+//! Conversions often end up with a sorting locale that doesn't fit the data, e.g.
+//! Chinese headwords sorted with an `en` collator, which breaks the order users
+//! expect and prefix lookups. [`LanguageReport`] samples entries evenly over the
+//! dictionary, reports the dominant script of the headwords and the dominant
+//! language of the contents, and warns if the sorting locale doesn't match.
+//!
+//! Requires the `whatlang` feature.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use mdx::readers::ZdbReader;
+//! use mdx::readers::language_detect::LanguageReport;
+//!
+//! # fn main() -> mdx::Result<()> {
+//! let mut reader = ZdbReader::<std::io::BufReader<std::fs::File>>::from_file("dict.mdx", "", "")?;
+//! let report = LanguageReport::detect(&mut reader, 500)?;
+//! if let Some(warning) = &report.locale_warning {
+//!     println!("{}", warning);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::BTreeMap;
+use std::io::{Read, Seek};
+
+use serde::Serialize;
+use whatlang::Script;
+
+use crate::storage::key_block::EntryNo;
+use crate::utils::extract_text_from_html;
+use crate::Result;
+use super::zdb_reader::ZdbReader;
+
+/// Number of characters of an entry passed to language detection.
+const MAX_SAMPLE_TEXT_CHARS: usize = 2000;
+
+/// Dominant scripts and languages of a dictionary.
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct LanguageReport {
+    /// Number of entries sampled
+    pub sampled_entries: u64,
+    /// Sampled headword count per script
+    pub key_scripts: BTreeMap<String, u64>,
+    /// Sampled entry count per reliably detected content language, as ISO 639-3 code
+    pub content_languages: BTreeMap<String, u64>,
+    /// Most frequent headword script
+    pub dominant_key_script: Option<String>,
+    /// Most frequent content language
+    pub dominant_content_language: Option<String>,
+    /// Sorting locale of the dictionary
+    pub sorting_locale: String,
+    /// Set if the sorting locale doesn't suit the dominant headword script
+    pub locale_warning: Option<String>,
+}
+
+impl LanguageReport {
+    /// Samples entries of the dictionary and detects their scripts and languages.
+    ///
+    /// # Arguments
+    ///
+    /// * `reader` - The dictionary reader
+    /// * `max_samples` - Maximum number of entries to sample, spread evenly over the dictionary
+    ///
+    /// # Returns
+    ///
+    /// Returns the report, contents of resource (MDD) files are not sampled.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a sampled entry can not be read.
+    pub fn detect<R: Read + Seek>(reader: &mut ZdbReader<R>, max_samples: u64) -> Result<Self> {
+        let entry_count = reader.get_entry_count();
+        let step = entry_count.div_ceil(max_samples.max(1)).max(1);
+        let is_mdd = reader.meta.db_info.is_mdd;
+        let mut report = LanguageReport { sorting_locale: reader.meta.db_info.locale_id.clone(), ..Default::default() };
+
+        for entry_no in (0..entry_count).step_by(step as usize) {
+            let key_index = reader.get_index(entry_no as EntryNo)?;
+            report.sampled_entries += 1;
+            if let Some(script) = whatlang::detect_script(&key_index.key) {
+                *report.key_scripts.entry(script.name().to_string()).or_default() += 1;
+            }
+            if is_mdd || reader.is_link_entry(&key_index)? {
+                continue;
+            }
+            let content = reader.get_string(&key_index, false)?;
+            let text = extract_text_from_html(&content).unwrap_or(content);
+            let text: String = text.chars().take(MAX_SAMPLE_TEXT_CHARS).collect();
+            if let Some(info) = whatlang::detect(&text) && info.is_reliable() {
+                *report.content_languages.entry(info.lang().code().to_string()).or_default() += 1;
+            }
+        }
+
+        report.dominant_key_script = most_frequent(&report.key_scripts);
+        report.dominant_content_language = most_frequent(&report.content_languages);
+        report.locale_warning = report.dominant_key_script.as_deref()
+            .and_then(|script| locale_mismatch(script, &report.sorting_locale));
+        Ok(report)
+    }
+}
+
+fn most_frequent(counts: &BTreeMap<String, u64>) -> Option<String> {
+    counts.iter().max_by_key(|(_, count)| **count).map(|(name, _)| name.clone())
+}
+
+/// Languages whose collation suits a script, `None` for scripts any collator orders reasonably.
+fn script_languages(script: Script) -> Option<&'static [&'static str]> {
+    let languages: &'static [&'static str] = match script {
+        Script::Latin => return None,
+        Script::Mandarin => &["zh", "ja", "yue"],
+        Script::Hiragana | Script::Katakana => &["ja"],
+        Script::Hangul => &["ko"],
+        Script::Cyrillic => &["ru", "uk", "be", "bg", "sr", "mk", "kk", "ky", "mn", "tg", "tt"],
+        Script::Greek => &["el"],
+        Script::Arabic => &["ar", "fa", "ur", "ps", "ug", "sd", "ku"],
+        Script::Hebrew => &["he", "yi"],
+        Script::Thai => &["th"],
+        Script::Devanagari => &["hi", "mr", "ne", "sa", "kok"],
+        Script::Bengali => &["bn", "as"],
+        Script::Tamil => &["ta"],
+        Script::Telugu => &["te"],
+        Script::Kannada => &["kn"],
+        Script::Malayalam => &["ml"],
+        Script::Gujarati => &["gu"],
+        Script::Gurmukhi => &["pa"],
+        Script::Oriya => &["or"],
+        Script::Sinhala => &["si"],
+        Script::Khmer => &["km"],
+        Script::Myanmar => &["my"],
+        Script::Georgian => &["ka"],
+        Script::Armenian => &["hy"],
+        Script::Ethiopic => &["am", "ti"],
+    };
+    Some(languages)
+}
+
+/// Checks whether a sorting locale suits the dominant headword script.
+///
+/// # Returns
+///
+/// Returns a warning message if the language of the locale isn't one of the languages
+/// written in the script, `None` if it is or the script isn't known.
+pub fn locale_mismatch(script_name: &str, locale_id: &str) -> Option<String> {
+    let script = Script::all().iter().copied().find(|script| script.name() == script_name)?;
+    let languages = script_languages(script)?;
+    let language = locale_id.split(['-', '_']).next().unwrap_or_default().to_lowercase();
+    if languages.contains(&language.as_str()) {
+        return None;
+    }
+    Some(format!(
+        "Headwords are mostly {} but the sorting locale is \"{}\", consider a locale for one of: {}",
+        script_name, locale_id, languages.join(", ")
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_locale_mismatch() {
+        assert!(locale_mismatch("Mandarin", "en-u-ks-level2").is_some());
+        assert!(locale_mismatch("Mandarin", "zh-Hans-u-co-pinyin").is_none());
+        assert!(locale_mismatch("Cyrillic", "ru").is_none());
+        assert!(locale_mismatch("Cyrillic", "root").is_some());
+        assert!(locale_mismatch("Latin", "zh").is_none());
+        assert!(locale_mismatch("Unknown", "en").is_none());
+    }
+}
@@ -4,7 +4,7 @@
 //! and other resources referenced by MDX dictionary files. It handles:
 //! - Single and multi-part MDD files
 //! - Resource lookup by file path or key
-//! - Content override from the filesystem
+//! - Content override from the filesystem, limited to the folder of the MDD file
 //!
 //! # Examples
 //!
@@ -26,15 +26,17 @@
 
 use std::cell::RefCell;
 use std::collections::LinkedList;
-use std::fs::File;
-use std::io::BufReader;
+use std::path::Path;
 
+use percent_encoding::percent_decode_str;
 use url::Url;
 
-use crate::utils::io_utils::{bytes_from_file_url, file_url_exists, load_string_from_file_with_ext, open_file_url_as_reader};
+use crate::utils::io_utils::{bytes_from_file_url, file_url_exists, file_url_to_path, load_string_from_file_with_ext, open_file_url_as_reader, DictFile};
+use crate::storage::key_block::{EntryNo, KeyIndex};
+use crate::utils::mdd_key;
 use crate::utils::url_utils;
-use super::zdb_reader::ZdbReader;
-use crate::Result;
+use super::zdb_reader::{ReaderOptions, ZdbReader};
+use crate::{Result, ZdbError};
 
 /// Reader for MDD (resource) files.
 ///
@@ -47,12 +49,18 @@ pub struct MddReader {
     /// Database name
     _db_name: String,
     /// List of ZDB readers for multi-part MDD files
-    zdb_readers: RefCell<LinkedList<ZdbReader<BufReader<std::fs::File>>>>,
+    zdb_readers: RefCell<LinkedList<ZdbReader<DictFile>>>,
+    /// Whether resources missing with the exact case are looked up ignoring case
+    case_insensitive: bool,
+    /// Case folded keys with the number of their file and their entry number, sorted, built on first use
+    folded_keys: RefCell<Option<Vec<(String, usize, EntryNo)>>>,
+    /// Whether files in the folder of the MDD file override resources
+    overrides_enabled: bool,
 }
 
 impl Default for MddReader {
     fn default() -> Self {
-        Self {mdd_base_url: Url::parse("file:///").unwrap(), _db_name: String::new(), zdb_readers: RefCell::new(LinkedList::new())}
+        Self {mdd_base_url: Url::parse("file:///").unwrap(), _db_name: String::new(), zdb_readers: RefCell::new(LinkedList::new()), case_insensitive: false, folded_keys: RefCell::new(None), overrides_enabled: true}
     }
 }
 
@@ -102,11 +110,29 @@ impl MddReader {
     /// # Ok::<(), Box<dyn std::error::Error>>(())
     /// ```
     pub fn from_url(mdd_url: &Url, device_id: &str) -> Result<Self> {
+        Self::open_with_license(mdd_url, device_id, None)
+    }
+
+    /// Opens an MDD resource file from a URL with the given license data.
+    ///
+    /// # Arguments
+    ///
+    /// * `mdd_url` - URL to the MDD file
+    /// * `device_id` - Device identifier for license verification
+    /// * `license` - Hex encoded registration code, `None` reads it from the `.key` file next to the MDD file
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`ZdbError::LicenseError`](crate::ZdbError::LicenseError) if the file is encrypted and
+    /// the license is missing, malformed or issued for another device.
+    pub fn open_with_license(mdd_url: &Url, device_id: &str, license: Option<&str>) -> Result<Self> {
         let mut zdb_readers = LinkedList::new();
-        let license_data = load_string_from_file_with_ext(mdd_url, "key")?;
+        let license_data = match license {
+            Some(license) => license.to_string(),
+            None => load_string_from_file_with_ext(mdd_url, "key")?,
+        };
         if file_url_exists(&mdd_url) {
-            let reader = open_file_url_as_reader(mdd_url)?;
-            let zdb_reader = ZdbReader::<BufReader<File>>::from_reader(reader, device_id, &license_data)?;
+            let zdb_reader = Self::open_zdb(mdd_url, device_id, &license_data)?;
             zdb_readers.push_back(zdb_reader);
         }
         let db_name= url_utils::get_decoded_file_stem(&mdd_url)?;
@@ -115,18 +141,46 @@ impl MddReader {
         for i in 1..100{
             let mdd_url = url_utils::with_extension(&mdd_base_url, &format!("{}.mdd", i))?; // File names are base.mdd, base.1.mdd, base.2.mdd, ...
             if file_url_exists(&mdd_url) {
-                let reader = open_file_url_as_reader(&mdd_url)?;
-                let zdb_reader = ZdbReader::<BufReader<File>>::from_reader(reader, device_id, &license_data)?;
+                let zdb_reader = Self::open_zdb(&mdd_url, device_id, &license_data)?;
                 zdb_readers.push_back(zdb_reader);
             }
         }
-        Ok(Self {mdd_base_url, _db_name: db_name, zdb_readers: RefCell::new(zdb_readers)})
+        Ok(Self {mdd_base_url, _db_name: db_name, zdb_readers: RefCell::new(zdb_readers), case_insensitive: false, folded_keys: RefCell::new(None), overrides_enabled: true})
+    }
+
+    /// Opens one resource file, plain files get their indexes decoded in parallel since they can be several gigabytes.
+    fn open_zdb(mdd_url: &Url, device_id: &str, license_data: &str) -> Result<ZdbReader<DictFile>> {
+        let reader = open_file_url_as_reader(mdd_url)?;
+        let source_path = match reader {
+            DictFile::File(_) => Some(file_url_to_path(mdd_url)?),
+            DictFile::Zip(_) | DictFile::Resolved(_) => None,
+        };
+        let content_dir = source_path.as_deref().and_then(Path::parent).map(Path::to_path_buf);
+        let options = ReaderOptions { parallel_open: true, content_dir, ..Default::default() };
+        ZdbReader::open_with_options(reader, device_id, license_data, options, source_path.as_deref())
+    }
+
+    /// Sets whether resources are looked up ignoring case when no key matches exactly.
+    ///
+    /// The first lookup ignoring case reads all keys of the MDD file(s) to build an index
+    /// of their case folded forms, see [`mdd_key::fold_case`].
+    pub fn set_case_insensitive(&mut self, case_insensitive: bool) {
+        self.case_insensitive = case_insensitive;
+    }
+
+    /// Sets whether files in the folder of the MDD file override resources, on by default.
+    ///
+    /// When off, `allow_override` of [`get_data_by_path`](Self::get_data_by_path) and
+    /// [`contains_path`](Self::contains_path) is ignored and only the MDD file(s) are searched.
+    pub fn set_overrides_enabled(&mut self, enabled: bool) {
+        self.overrides_enabled = enabled;
     }
     
     /// Gets resource data by file path, with optional override capability.
     ///
     /// This method first checks for overrides in the local filesystem, then searches
-    /// the MDD file(s) by key.
+    /// the MDD file(s) by key. Overrides are only read from the folder of the MDD file
+    /// and its subfolders.
     ///
     /// # Arguments
     ///
@@ -136,13 +190,15 @@ impl MddReader {
     /// # Returns
     ///
     /// Returns `Some(data)` if found, `None` if not found.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `PathOutsideDictionary` error if overrides are allowed and the path leads
+    /// out of the folder of the MDD file. A file in the folder linking out of it isn't used
+    /// as an override.
     pub fn get_data_by_path(&mut self, file_path: &str, allow_override: bool) -> Result<Option<Vec<u8>>> {
-        if allow_override {
-            let file_url = Url::parse(&format!("file://{}", file_path))?;
-            let override_url = url_utils::join_url_path(&self.mdd_base_url, &file_url)?;
-            if file_url_exists(&override_url) {
-                return Ok(Some(bytes_from_file_url(&override_url)?));
-            }
+        if let Some(override_url) = self.find_override(file_path, allow_override)? {
+            return Ok(Some(bytes_from_file_url(&override_url)?));
         }
         self.get_data_by_key(file_path)    
     }
@@ -160,32 +216,146 @@ impl MddReader {
     ///
     /// Returns `Some(data)` if found, `None` if not found.
     pub fn get_data_by_key(&mut self, file_path: &str) -> Result<Option<Vec<u8>>> {
-        if self.zdb_readers.borrow().front().is_none() {
+        match self.find_key(file_path)? {
+            Some((reader_no, key_index)) => {
+                let mut zdb_readers = self.zdb_readers.borrow_mut();
+                let zdb_reader = zdb_readers.iter_mut().nth(reader_no)
+                    .ok_or_else(|| ZdbError::invalid_parameter(format!("Invalid MDD file number: {}", reader_no)))?;
+                Ok(Some(zdb_reader.get_data(&key_index, true)?))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Checks whether a resource exists without reading its data.
+    ///
+    /// Only the key indexes are searched, and the Bloom filter if the file has one,
+    /// so no content block is decoded. Overrides in the filesystem aren't considered.
+    /// Matches ignoring case if enabled, see [`set_case_insensitive`](Self::set_case_insensitive).
+    ///
+    /// # Arguments
+    ///
+    /// * `file_path` - Key path for the resource
+    ///
+    /// # Returns
+    ///
+    /// Returns true if any of the MDD files contains the key.
+    pub fn contains_key(&self, file_path: &str) -> Result<bool> {
+        Ok(self.find_key(file_path)?.is_some())
+    }
+
+    /// Checks whether a resource exists, either as an override in the filesystem or in the MDD file(s).
+    ///
+    /// Finds the same resources as [`get_data_by_path`](Self::get_data_by_path) without reading them,
+    /// and fails the same way for paths leading out of the folder of the MDD file.
+    pub fn contains_path(&self, file_path: &str, allow_override: bool) -> Result<bool> {
+        if self.find_override(file_path, allow_override)?.is_some() {
+            return Ok(true);
+        }
+        self.contains_key(file_path)
+    }
+
+    /// Finds the file overriding a resource in the folder of the MDD file.
+    ///
+    /// The path is checked before the filesystem is accessed. A file found is ignored if it
+    /// leads out of the folder after resolving symbolic links, like a missing file, so the
+    /// result doesn't tell whether the target of the link exists.
+    fn find_override(&self, file_path: &str, allow_override: bool) -> Result<Option<Url>> {
+        if !allow_override || !self.overrides_enabled {
             return Ok(None);
         }
-        let actual_file_path = if !self.zdb_readers.borrow().front().unwrap().meta.is_v3() {
-            // Convert unix path to windows path
-            file_path.replace("/", "\\")
-        } else {
-            file_path.to_string()
+        let file_url = Url::parse(&format!("file://{}", file_path))?;
+        // Percent-encoded separators and backslashes become separators once the path is decoded
+        let decoded_path = percent_decode_str(file_url.path()).decode_utf8_lossy().replace('\\', "/");
+        if escapes_folder(&decoded_path) {
+            return Err(ZdbError::path_outside_dictionary(file_path));
+        }
+        let override_url = url_utils::join_url_path(&self.mdd_base_url, &file_url)?;
+        if !file_url_exists(&override_url) {
+            return Ok(None);
+        }
+        if override_url.scheme() == "file" {
+            let mdd_path = file_url_to_path(&self.mdd_base_url)?;
+            let folder = mdd_path.parent().unwrap_or(Path::new("/")).canonicalize()?;
+            if !file_url_to_path(&override_url)?.canonicalize()?.starts_with(&folder) {
+                return Ok(None);
+            }
+        }
+        Ok(Some(override_url))
+    }
+
+    /// MIME type recorded for the extension of a resource when the MDD file was built.
+    ///
+    /// # Returns
+    ///
+    /// Returns `None` if the path has no extension or the header has no type for it,
+    /// callers then guess the type from the extension.
+    pub fn media_type(&self, file_path: &str) -> Option<String> {
+        let extension = mdd_key::extension(file_path)?;
+        self.zdb_readers.borrow().iter()
+            .find_map(|zdb_reader| zdb_reader.meta.db_info.media_types.get(&extension).cloned())
+    }
+
+    /// Finds the key of a resource, returning the number of the file containing it and its key index.
+    fn find_key(&self, file_path: &str) -> Result<Option<(usize, KeyIndex)>> {
+        let version = match self.zdb_readers.borrow().front() {
+            Some(zdb_reader) => zdb_reader.meta.db_info.version,
+            None => return Ok(None),
         };
+        let key = mdd_key::normalize(file_path, version);
+        for (reader_no, zdb_reader) in self.zdb_readers.borrow_mut().iter_mut().enumerate() {
+            if !zdb_reader.may_contain(&key)? {
+                continue;
+            }
+            if let Some(key_index) = zdb_reader.find_first_match(&key, false, false, true)? {
+                return Ok(Some((reader_no, key_index)));
+            }
+        }
+        if self.case_insensitive {
+            return self.find_key_ignoring_case(file_path);
+        }
+        Ok(None)
+    }
 
-        for zdb_reader in self.zdb_readers.borrow_mut().iter_mut() {
-            let result = zdb_reader.get_data_by_key(&actual_file_path);
-            match result {
-                Ok(data) => {
-                    if data.is_some(){
-                        return Ok(data);
-                    }else {
-                        // Key not found
-                        continue;
-                    }
-                }
-                Err(e) => {
-                    return Err(e);
+    fn find_key_ignoring_case(&self, file_path: &str) -> Result<Option<(usize, KeyIndex)>> {
+        let mut zdb_readers = self.zdb_readers.borrow_mut();
+        let mut folded_keys = self.folded_keys.borrow_mut();
+        if folded_keys.is_none() {
+            let mut keys = Vec::new();
+            for (reader_no, zdb_reader) in zdb_readers.iter_mut().enumerate() {
+                for entry_no in 0..zdb_reader.get_entry_count() as EntryNo {
+                    keys.push((mdd_key::fold_case(&zdb_reader.get_index(entry_no)?.key), reader_no, entry_no));
                 }
             }
+            keys.sort();
+            *folded_keys = Some(keys);
+        }
+        let folded_keys = folded_keys.as_ref().unwrap();
+        let folded_path = mdd_key::fold_case(file_path);
+        let pos = folded_keys.partition_point(|(key, _, _)| key.as_str() < folded_path.as_str());
+        match folded_keys.get(pos) {
+            Some((key, reader_no, entry_no)) if *key == folded_path => {
+                let zdb_reader = zdb_readers.iter_mut().nth(*reader_no)
+                    .ok_or_else(|| ZdbError::invalid_parameter(format!("Invalid MDD file number: {}", reader_no)))?;
+                Ok(Some((*reader_no, zdb_reader.get_index(*entry_no)?)))
+            }
+            _ => Ok(None),
+        }
+    }
+}
+
+/// Whether a relative path leads out of the folder it's relative to, through `..` components.
+fn escapes_folder(path: &str) -> bool {
+    let mut depth = 0usize;
+    for component in path.split('/') {
+        match component {
+            "" | "." => {}
+            ".." => match depth.checked_sub(1) {
+                Some(parent) => depth = parent,
+                None => return true,
+            },
+            _ => depth += 1,
         }
-        return Ok(None);
     }
-}
\ No newline at end of file
+    false
+}
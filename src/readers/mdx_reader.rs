@@ -35,30 +35,51 @@
 //! - **Resource Loading**: Automatically load associated MDD files for images and media
 //! - **HTML Rewriting**: Convert internal links to MDX protocol format
 
-use std::collections::LinkedList;
-use std::fs::File;
-use std::io::BufReader;
+use std::collections::{BTreeMap, LinkedList};
+use std::path::Path;
+use std::time::Instant;
 
 use log::*;
-use mime_guess::MimeGuess;
-use tantivy::collector::TopDocs;
-use tantivy::query::QueryParser;
-use tantivy::schema::Value;
-use tantivy::{Index, TantivyDocument};
+use tantivy::Index;
 use url::Url;
 
-use crate::utils::io_utils::{load_string_from_file_with_ext, open_file_url_as_reader};
+use crate::utils::io_utils::{bytes_from_file_url, file_url_exists, file_url_to_path, load_string_from_file_with_ext, open_file_url_as_reader, DictFile};
+use crate::storage::entry_meta_unit::EntryMetaExt;
+use crate::storage::source_map_unit::SourceLocation;
 use crate::storage::key_block::{EntryNo, KeyIndex};
+use crate::storage::reader_helper::{decode_bytes_to_string, DecodeDiagnostics, DecodeMode};
 use crate::utils::url_utils::{self, with_extension};
+use crate::builder::fts_index_builder::FtsIndexMetadata;
+use super::dict_css::{DictStylesheet, StylesheetOptions, StylesheetSource};
+use super::dict_metadata::DictMetadata;
+use super::dict_stats::DictStatistics;
+use super::fts_health::{self, FtsIndexProblem, FtsIndexStats};
+use super::fts_searcher::{FtsSearchOptions, FtsSearcher, Suggestion};
+use super::ranked_search::{HitMerger, SearchHit, SearchOptions, SearchSource};
+use super::search_limits::{LimitedResults, SearchLimits};
 use super::mdd_reader::MddReader;
+use super::open_report::OpenReport;
 use crate::storage::meta_unit::ContentType;
 use crate::utils::html_escape_mdx_text;
-use super::zdb_reader::ZdbReader;
+use crate::utils::html_text::HtmlTextExtractor;
+use crate::utils::css::minify_css;
+use crate::utils::dark_mode::DarkMode;
+use crate::utils::html_normalize::{HtmlNormalizer, NormalizeOptions};
+use crate::utils::html_preview::{HtmlPreviewer, PreviewOptions};
+use crate::utils::label_expander::LabelExpander;
+use crate::utils::mdd_key;
+use crate::utils::mdx_html_rewriter::MdxHtmlRewriter;
+use crate::utils::mime_sniff::infer_mime_type;
+use crate::utils::progress_report::ProgressReportFn;
+use super::zdb_reader::{MemoryFootprint, ReaderOptions, SearchDirection, ZdbReader};
 use crate::storage::zip_directory::ZipDirectory;
 use crate::{Result, ZdbError};
 const MDICT_INDEX_EXT: &str = "idx";
 const MDICT_MDD_EXT: &str = "mdd";
 const MDICT_KEY_EXT: &str = "key";
+const CSS_EXT: &str = "css";
+/// Bytes of content decoded and parsed at a time by [`MdxReader::get_text`].
+const TEXT_CHUNK_SIZE: usize = 4096;
 
 /// High-level MDX dictionary reader.
 ///
@@ -66,7 +87,7 @@ const MDICT_KEY_EXT: &str = "key";
 /// It manages the content database, optional resource database, and full-text search index.
 pub struct MdxReader {
     /// The main content database reader
-    pub content_db: ZdbReader<BufReader<File>>,
+    pub content_db: ZdbReader<DictFile>,
     /// Optional associated resource (MDD) file reader
     pub data_db: Option<MddReader>,
     /// Optional full-text search index
@@ -77,6 +98,18 @@ pub struct MdxReader {
     pub mdx_url: Url,
     /// Compact stylesheet for decompacting content
     compact_stylesheet: Vec<(String, String)>,
+    /// Whether an FTS index exists but was rejected as out of date
+    fts_needs_reindex: bool,
+    /// Search handle of the FTS index, opened with the index
+    fts_searcher: Option<FtsSearcher>,
+    /// Expander of the labels in the header, set by [`MdxReader::set_expand_labels`]
+    label_expander: Option<LabelExpander>,
+    /// Dark mode style added by [`MdxReader::get_html`], set by [`MdxReader::set_dark_mode`]
+    dark_mode: Option<DarkMode>,
+    /// Normalizer of legacy markup, set by [`MdxReader::set_normalize_html`]
+    html_normalizer: Option<HtmlNormalizer>,
+    /// Time spent opening the dictionary, see [`MdxReader::open_report`]
+    open_report: OpenReport,
 }
 
 impl MdxReader {
@@ -93,7 +126,8 @@ impl MdxReader {
     ///
     /// # Arguments
     ///
-    /// * `mdx_url` - URL to the MDX file (typically `file:///path/to/file.mdx`)
+    /// * `mdx_url` - URL to the MDX file (typically `file:///path/to/file.mdx`), files in a zip archive
+    ///   are opened without extracting them by URLs like `zip:///path/to/pack.zip!/file.mdx`
     /// * `device_id` - Device identifier for license verification
     ///
     /// # Returns
@@ -115,35 +149,131 @@ impl MdxReader {
     /// # Ok::<(), Box<dyn std::error::Error>>(())
     /// ```
     pub fn from_url(mdx_url: &Url, device_id: &str) -> Result<Self> {
+        Self::open_with_license(mdx_url, device_id, None)
+    }
+
+    /// Opens an MDX dictionary file from a URL with the given license data.
+    ///
+    /// # Arguments
+    ///
+    /// * `mdx_url` - URL to the MDX file
+    /// * `device_id` - Device identifier for license verification
+    /// * `license` - Hex encoded registration code, `None` reads it from the `.key` file next to the MDX file.
+    ///   An explicit license is also used for the MDD file.
+    ///
+    /// # Returns
+    ///
+    /// Returns an initialized MdxReader on success.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`ZdbError::LicenseError`] if the dictionary is encrypted and the license is missing,
+    /// malformed or issued for another device. See [`ZdbError::license_error_kind`].
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use mdx::MdxReader;
+    /// use mdx::LicenseErrorKind;
+    /// use url::Url;
+    ///
+    /// let url = Url::parse("file:///dict/Oxford.mdx")?;
+    /// match MdxReader::open_with_license(&url, "my_device", None) {
+    ///     Ok(_reader) => {}
+    ///     Err(e) if e.license_error_kind() == Some(LicenseErrorKind::Required) => println!("Please register"),
+    ///     Err(e) => return Err(e.into()),
+    /// }
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn open_with_license(mdx_url: &Url, device_id: &str, license: Option<&str>) -> Result<Self> {
+        let start = Instant::now();
         let mdx_url = mdx_url.clone();
         let reader = open_file_url_as_reader(&mdx_url)?;
-        let license_data = load_string_from_file_with_ext(&mdx_url, MDICT_KEY_EXT)?;
-        let content_db = ZdbReader::<BufReader<File>>::from_reader(reader, device_id, &license_data)?;
+        let license_data = match license {
+            Some(license) => license.to_string(),
+            None => load_string_from_file_with_ext(&mdx_url, MDICT_KEY_EXT)?,
+        };
+        let content_dir = match reader {
+            DictFile::File(_) => file_url_to_path(&mdx_url)?.parent().map(Path::to_path_buf),
+            DictFile::Zip(_) | DictFile::Resolved(_) => None,
+        };
+        let options = ReaderOptions { content_dir, ..Default::default() };
+        let content_db = ZdbReader::<DictFile>::from_reader_with_options(reader, device_id, &license_data, options)?;
+        let mut open_report = content_db.open_report().clone();
         
         // Try to initialize data_db, but allow it to fail
-        let data_db = match MddReader::from_url(&with_extension(&mdx_url, MDICT_MDD_EXT)?, device_id) {
+        let mdd_start = Instant::now();
+        let data_db = match MddReader::open_with_license(&with_extension(&mdx_url, MDICT_MDD_EXT)?, device_id, license) {
             Ok(db) => Some(db),
             Err(e) => {
                 warn!("Failed to load MDD data database: {}. Data resources will not be available.", e);
                 None
             }
         };
+        open_report.mdd = mdd_start.elapsed();
         
         let db_name= url_utils::get_decoded_file_stem(&mdx_url)?;
-        let compact_stylesheet = Self::load_compact_stylesheet(&content_db.meta.db_info.style_sheet)?;
+        // A broken stylesheet can be replaced with set_compact_stylesheet, the dictionary is still opened
+        let compact_stylesheet = Self::load_compact_stylesheet(&content_db.meta.db_info.style_sheet)
+            .unwrap_or_else(|e| {
+                warn!("Ignoring the compact stylesheet of {}: {}", db_name, e);
+                Vec::new()
+            });
         
         // Try to initialize FTS index, but allow it to fail
-        let fts_index = match Self::load_fts_index(&with_extension(&mdx_url, MDICT_INDEX_EXT)?) {
-            Ok(index) => Some(index),
+        let mut fts_needs_reindex = false;
+        let fts_start = Instant::now();
+        let (fts_index, fts_searcher) = match Self::load_fts_index(&with_extension(&mdx_url, MDICT_INDEX_EXT)?, content_db.get_entry_count()) {
+            Ok((index, searcher)) => (Some(index), Some(searcher)),
+            Err(e @ ZdbError::FtsIndexOutdated { .. }) => {
+                warn!("{}. Full-text search will not be available.", e);
+                fts_needs_reindex = true;
+                (None, None)
+            }
             Err(e) => {
                 info!("Failed to load FTS index: {}. Full-text search will not be available.", e);
-                None
+                (None, None)
             }
         };
-        let mdx_reader = Self { content_db, data_db, fts_index, db_name, mdx_url, compact_stylesheet };
+        open_report.fts = fts_start.elapsed();
+        open_report.total = start.elapsed();
+        let mdx_reader = Self { content_db, data_db, fts_index, db_name, mdx_url, compact_stylesheet, fts_needs_reindex, fts_searcher, label_expander: None, dark_mode: None, html_normalizer: None, open_report };
         Ok(mdx_reader)
     }
 
+    /// Time spent opening the dictionary, by step, see [`OpenReport`].
+    ///
+    /// Includes the steps of the MDX file, see [`ZdbReader::open_report`], the probing for
+    /// the MDD file(s) and the opening of the full-text search index.
+    pub fn open_report(&self) -> &OpenReport {
+        &self.open_report
+    }
+
+    /// Reads the title, entry count and sizes of a dictionary without opening it.
+    ///
+    /// Only the header and the unit info of the MDX file are parsed, no key or content
+    /// index is decoded, so it is fast enough to list many dictionaries.
+    ///
+    /// # Arguments
+    ///
+    /// * `mdx_url` - URL to the MDX file
+    ///
+    /// # Returns
+    ///
+    /// Returns the metadata, the entry count is `None` if it can only be read with a license.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the MDX file cannot be opened or its header cannot be parsed.
+    pub fn open_metadata_only(mdx_url: &Url) -> Result<DictMetadata> {
+        let mut reader = open_file_url_as_reader(mdx_url)?;
+        let mut metadata = DictMetadata::from_reader(&mut reader)?;
+        metadata.db_name = url_utils::get_decoded_file_stem(mdx_url)?;
+        metadata.resource_file_size = open_file_url_as_reader(&with_extension(mdx_url, MDICT_MDD_EXT)?).ok()
+            .and_then(|mut reader| reader.len().ok());
+        Ok(metadata)
+    }
+
 
     /// Gets multiple key indexes starting from a specific entry number.
     ///
@@ -201,7 +331,12 @@ impl MdxReader {
     /// # Returns
     ///
     /// Returns the content as a UTF-8 string.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `ContentTypeMismatch` error for a dictionary of binary content, see [`get_blob`](Self::get_blob).
     pub fn get_string(&mut self, key_index: &KeyIndex, decompact: bool) -> Result<String> {
+        self.check_not_binary("get_string")?;
         if decompact && !self.compact_stylesheet.is_empty() {
 			let compacted_content = self.content_db.get_string(key_index, true)?;
             Self::reformat(&compacted_content, &self.compact_stylesheet)
@@ -210,10 +345,27 @@ impl MdxReader {
         }
     }
 
+    /// Gets the content of an entry as a string with its [`DecodeDiagnostics`], see
+    /// [`ZdbReader::get_string_with_diagnostics`]. The content isn't decompacted.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `ContentTypeMismatch` error for a dictionary of binary content.
+    pub fn get_string_with_diagnostics(&mut self, key_index: &KeyIndex) -> Result<(String, DecodeDiagnostics)> {
+        self.check_not_binary("get_string_with_diagnostics")?;
+        self.content_db.get_string_with_diagnostics(key_index, true)
+    }
+
+    /// Sets how malformed text in entries is handled, [`DecodeMode::Lossy`] by default.
+    pub fn set_decode_mode(&mut self, mode: DecodeMode) {
+        self.content_db.set_decode_mode(mode);
+    }
+
     /// Gets content as HTML for a dictionary entry.
     ///
     /// This method automatically converts text content to HTML-escaped format
-    /// and retrieves HTML content as-is.
+    /// and retrieves HTML content as-is. A union entry returns the contents of its
+    /// members one after another, see [`expand_union`](Self::expand_union).
     ///
     /// # Arguments
     ///
@@ -229,16 +381,203 @@ impl MdxReader {
     pub fn get_html(&mut self, key_index: &KeyIndex) -> Result<String> {
         //TODO Need to rebuild links in html to use mdx schema (mdx://)
         let content_type = self.content_db.meta.db_info.content_type.clone();
-        match content_type {
+        if content_type == ContentType::Binary {
+            return Err(ZdbError::content_type_mismatch("get_html", &content_type, Some("get_blob")));
+        }
+        // A union entry is shown as the contents of its members, one after another
+        let mut html = String::with_capacity(1024);
+        for member in self.content_db.expand_union(key_index)? {
+            let content = self.get_string(&member, true)?;
+            match content_type {
+                ContentType::Text => {
+                    if !html.is_empty() {
+                        html.push_str("<br>");
+                    }
+                    html_escape_mdx_text(&content, &mut html);
+                }
+                _ => {
+                    if !html.is_empty() {
+                        html.push('\n');
+                    }
+                    html.push_str(&content);
+                }
+            }
+        }
+        let html = match &self.html_normalizer {
+            Some(html_normalizer) => html_normalizer.normalize_html(&html)?,
+            None => html,
+        };
+        let html = match &self.label_expander {
+            Some(label_expander) => label_expander.expand_html(&html)?,
+            None => html,
+        };
+        match &self.dark_mode {
+            Some(dark_mode) => dark_mode.apply(&html),
+            None => Ok(html),
+        }
+    }
+
+    /// Adds a dark mode style to the content returned by [`get_html`](Self::get_html), see
+    /// [`DarkMode`]. `None`, the default, returns the content unchanged.
+    pub fn set_dark_mode(&mut self, dark_mode: Option<DarkMode>) {
+        self.dark_mode = dark_mode;
+    }
+
+    /// Fixes legacy markup such as ruby notation, `<embed>` audio and `<font>` in the
+    /// content returned by [`get_html`](Self::get_html), see [`HtmlNormalizer`]. `None`,
+    /// the default, returns the content unchanged.
+    pub fn set_normalize_html(&mut self, options: Option<NormalizeOptions>) {
+        self.html_normalizer = options.map(HtmlNormalizer::new);
+    }
+
+    /// Labels used in the content and their expansions, stored by the builder from
+    /// [`BuilderConfig::labels_path`](crate::builder::BuilderConfig::labels_path).
+    pub fn labels(&self) -> &BTreeMap<String, String> {
+        &self.content_db.meta.db_info.labels
+    }
+
+    /// Wraps the labels in the content returned by [`get_html`](Self::get_html) in
+    /// `<abbr title="expansion">`, see [`LabelExpander`]. Off by default.
+    pub fn set_expand_labels(&mut self, expand: bool) {
+        self.label_expander = if expand && !self.labels().is_empty() {
+            Some(LabelExpander::new(self.labels()))
+        } else {
+            None
+        };
+    }
+
+    /// Gets the content of an entry as plain text, e.g. for a preview.
+    ///
+    /// Compacted content is expanded, HTML tags are stripped with a line break after
+    /// every block element and whitespace is collapsed. The content is decoded and parsed
+    /// piece by piece only until `max_len` characters are collected, so short previews of
+    /// long entries are cheap. Malformed text is replaced rather than failing, and compacted
+    /// content is decoded as a whole before it is expanded.
+    ///
+    /// # Arguments
+    ///
+    /// * `key_index` - The key index of the entry
+    /// * `max_len` - Maximum length of the text in characters
+    ///
+    /// # Returns
+    ///
+    /// Returns the text, truncated to `max_len` characters.
+    pub fn get_text(&mut self, key_index: &KeyIndex, max_len: usize) -> Result<String> {
+        self.check_not_binary("get_text")?;
+        match self.content_db.meta.db_info.content_type {
             ContentType::Text => {
-                let mut buffer = String::with_capacity(1024);
-                html_escape_mdx_text(&self.get_string(key_index, true)?, &mut buffer);
-                Ok(buffer)
+                let mut text = String::new();
+                let mut char_count = 0;
+                self.decode_chunks(key_index, |piece| {
+                    for ch in piece.chars().take(max_len - char_count) {
+                        text.push(ch);
+                        char_count += 1;
+                    }
+                    Ok(char_count == max_len)
+                })?;
+                Ok(text)
             }
             ContentType::Html => {
-                self.get_string(key_index, true)
+                let mut extractor = HtmlTextExtractor::new(max_len);
+                if self.compact_stylesheet.is_empty() {
+                    self.decode_chunks(key_index, |piece| extractor.write(piece.as_bytes()))?;
+                } else {
+                    let content = self.content_db.get_string(key_index, true)?;
+                    Self::reformat_with(&content, &self.compact_stylesheet, |piece| extractor.write(piece.as_bytes()))?;
+                }
+                extractor.finish()
+            }
+            ContentType::Binary => Err(ZdbError::content_type_mismatch("get_text", &ContentType::Binary, Some("get_blob"))),
+        }
+    }
+
+    /// Decodes the content of an entry [`TEXT_CHUNK_SIZE`] bytes at a time, until `sink`
+    /// returns true or the content ends.
+    fn decode_chunks<F: FnMut(&str) -> Result<bool>>(&mut self, key_index: &KeyIndex, mut sink: F) -> Result<()> {
+        let data = self.content_db.get_data(key_index, true)?;
+        let mut decoder = self.content_db.meta.encoding_obj.new_decoder();
+        let mut piece = String::new();
+        let mut chunks = data.chunks(TEXT_CHUNK_SIZE).peekable();
+        while let Some(chunk) = chunks.next() {
+            piece.clear();
+            piece.reserve(decoder.max_utf8_buffer_length(chunk.len()).unwrap_or(chunk.len() * 3));
+            let _ = decoder.decode_to_string(chunk, &mut piece, chunks.peek().is_none());
+            if sink(&piece)? {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    /// Gets a short preview of an entry for list views, see [`HtmlPreviewer`].
+    ///
+    /// Compacted content is expanded and the text of text dictionaries is converted to HTML
+    /// first. The HTML is parsed only until the preview is complete.
+    ///
+    /// # Arguments
+    ///
+    /// * `key_index` - The key index of the entry
+    /// * `options` - Length and format of the preview, and the classes left out, by default
+    ///   those stored in the dictionary
+    ///
+    /// # Returns
+    ///
+    /// Returns the preview as plain text or simplified HTML.
+    pub fn get_preview(&mut self, key_index: &KeyIndex, options: &PreviewOptions) -> Result<String> {
+        self.check_not_binary("get_preview")?;
+        let skip_classes = options.skip_classes.as_ref().unwrap_or(&self.content_db.meta.db_info.preview_skip_classes);
+        let mut previewer = HtmlPreviewer::new(options, skip_classes)?;
+        let mut content = self.content_db.get_string(key_index, true)?;
+        if self.content_db.meta.db_info.content_type == ContentType::Text {
+            let mut buffer = String::with_capacity(content.len());
+            html_escape_mdx_text(&content, &mut buffer);
+            content = buffer;
+        }
+        if self.compact_stylesheet.is_empty() {
+            for chunk in content.as_bytes().chunks(TEXT_CHUNK_SIZE) {
+                if previewer.write(chunk)? {
+                    break;
+                }
             }
-            _ => Err(ZdbError::invalid_data_format("Db content type is not supported")),
+        } else {
+            Self::reformat_with(&content, &self.compact_stylesheet, |piece| previewer.write(piece.as_bytes()))?;
+        }
+        previewer.finish()
+    }
+
+    /// Gets the content of an entry of a dictionary of binary content with its MIME type.
+    ///
+    /// The MIME type is the one recorded for the extension of the key when the file was
+    /// built, the one recognized from the content, or the one guessed from the extension,
+    /// see [`infer_mime_type`].
+    ///
+    /// # Arguments
+    ///
+    /// * `key_index` - The key index of the entry
+    ///
+    /// # Returns
+    ///
+    /// Returns the content and its MIME type.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `ContentTypeMismatch` error for a dictionary of text or HTML content, see [`get_string`](Self::get_string).
+    pub fn get_blob(&mut self, key_index: &KeyIndex) -> Result<(Vec<u8>, String)> {
+        let content_type = &self.content_db.meta.db_info.content_type;
+        if *content_type != ContentType::Binary {
+            return Err(ZdbError::content_type_mismatch("get_blob", content_type, Some("get_string")));
+        }
+        let data = self.content_db.get_data(key_index, true)?;
+        let recorded = mdd_key::extension(&key_index.key)
+            .and_then(|extension| self.content_db.meta.db_info.media_types.get(&extension).cloned());
+        let mime_type = infer_mime_type(&key_index.key, &data, recorded.as_deref());
+        Ok((data, mime_type))
+    }
+
+    fn check_not_binary(&self, operation: &str) -> Result<()> {
+        match &self.content_db.meta.db_info.content_type {
+            ContentType::Binary => Err(ZdbError::content_type_mismatch(operation, &ContentType::Binary, Some("get_blob"))),
+            _ => Ok(()),
         }
     }
 
@@ -247,61 +586,107 @@ impl MdxReader {
 	/// `compact_stylesheet[token] = (prefix, suffix)`.
     /// 
     pub fn reformat(compacted_source: &str, compact_style: &[(String, String)]) -> Result<String> {
-        let mut chars = compacted_source.chars().peekable();
         let mut expanded_text = String::with_capacity(compacted_source.len()+1024);
-        let mut processed_source= String::with_capacity(expanded_text.len()/2);
-        while let Some(c) = chars.next() {
-            if c == '`' {
-                processed_source.push(c);
-                let mut number = String::new();
-                let mut has_number = false;
-                while let Some(nc) = chars.next() {
-                    processed_source.push(nc);
-                    if nc.is_digit(10) {
-                        number.push(nc);
-                    } else if nc == '`' {
-                        has_number = !number.is_empty();
-                        break;
-                    }else{
-                        break;
+        Self::reformat_with(compacted_source, compact_style, |piece| {
+            expanded_text.push_str(piece);
+            Ok(false)
+        })?;
+        Ok(expanded_text)
+    }
+
+    /// Expands compacted content piece by piece, see [`reformat`](Self::reformat).
+    ///
+    /// Stops early once `sink` returns true.
+    fn reformat_with<F: FnMut(&str) -> Result<bool>>(compacted_source: &str, compact_style: &[(String, String)], mut sink: F) -> Result<()> {
+        let mut rest = compacted_source;
+        while let Some(start) = rest.find('`') {
+            if start > 0 && sink(&rest[..start])? {
+                return Ok(());
+            }
+            let after = &rest[start + 1..];
+            let digits_len = after.bytes().take_while(u8::is_ascii_digit).count();
+            let token = if digits_len > 0 && after[digits_len..].starts_with('`') {
+                after[..digits_len].parse::<usize>().ok().filter(|token| *token < 256)
+            } else {
+                None
+            };
+            match token {
+                Some(token) => {
+                    // The token applies to the text up to the next backtick
+                    let text = &after[digits_len + 1..];
+                    let text_len = text.find('`').unwrap_or(text.len());
+                    let (prefix, suffix) = compact_style.get(token).map(|(prefix, suffix)| (prefix.as_str(), suffix.as_str())).unwrap_or_default();
+                    for piece in [prefix, &text[..text_len], suffix] {
+                        if !piece.is_empty() && sink(piece)? {
+                            return Ok(());
+                        }
                     }
+                    rest = &text[text_len..];
                 }
-                if has_number {
-                    //格式合法
-                    let token = number.parse::<usize>().unwrap_or(256);
-                    if token<256 {
-                        processed_source.clear();
-                        while let Some(nc) = chars.peek() {
-                            if *nc != '`' {
-                                processed_source.push(*nc);
-                                chars.next();
-                            } else {
-                                break;
-                            }
-                        }
-                        expanded_text.push_str(&compact_style[token].0);
-                        expanded_text.push_str(&processed_source);
-                        expanded_text.push_str(&compact_style[token].1);
-                        processed_source.clear();
+                None => {
+                    // Not a token, the backtick is kept with the digits and the character ending them
+                    let end = start + 1 + digits_len + after[digits_len..].chars().next().map_or(0, char::len_utf8);
+                    if sink(&rest[start..end])? {
+                        return Ok(());
                     }
+                    rest = &rest[end..];
                 }
-                expanded_text.push_str(&processed_source);
-                processed_source.clear();
-            } else {
-                expanded_text.push(c);
             }
         }
-        expanded_text.push_str(&processed_source);
-        Ok(expanded_text)
+        if !rest.is_empty() {
+            sink(rest)?;
+        }
+        Ok(())
+    }
+
+    /// Checks whether a resource exists without reading it.
+    ///
+    /// Finds the same resources as [`get_data`](Self::get_data), including overrides in
+    /// the filesystem, but only searches the key indexes of the MDD files. Renderers can
+    /// use it to replace references to missing resources before displaying an entry.
+    ///
+    /// # Arguments
+    ///
+    /// * `file_path` - Path of the resource, e.g. `/img/cat.png`
+    ///
+    /// # Returns
+    ///
+    /// Returns false if the dictionary has no MDD file.
+    pub fn has_resource(&self, file_path: &str) -> Result<bool> {
+        match &self.data_db {
+            Some(data_db) => data_db.contains_path(file_path, true),
+            None => Ok(false),
+        }
+    }
+
+    /// Sets whether resources are looked up ignoring case when no key matches exactly,
+    /// see [`MddReader::set_case_insensitive`].
+    pub fn set_case_insensitive_resources(&mut self, case_insensitive: bool) {
+        if let Some(data_db) = self.data_db.as_mut() {
+            data_db.set_case_insensitive(case_insensitive);
+        }
+    }
+
+    /// Sets whether files in the folder of the dictionary override resources of its MDD file,
+    /// see [`MddReader::set_overrides_enabled`].
+    pub fn set_resource_overrides(&mut self, enabled: bool) {
+        if let Some(data_db) = self.data_db.as_mut() {
+            data_db.set_overrides_enabled(enabled);
+        }
     }
 
+    /// Reads a resource with its MIME type.
+    ///
+    /// The MIME type is the one recorded for the extension when the MDD file was built,
+    /// see [`MddReader::media_type`], or inferred from the content and the extension
+    /// otherwise, see [`infer_mime_type`].
     pub fn get_data(&mut self, file_path: &str) -> Result<Option<(Vec<u8>, String)>> {
         // Handle data database lookup
-        if self.data_db.is_some() {
-            let buffer = self.data_db.as_mut().unwrap().get_data_by_path(file_path, true)?;
+        if let Some(data_db) = self.data_db.as_mut() {
+            let buffer = data_db.get_data_by_path(file_path, true)?;
             if let Some(buffer) = buffer {
-                let mime_type = MimeGuess::from_path(file_path).first_or_octet_stream().to_string();
-                    return Ok(Some((buffer, mime_type)));
+                let mime_type = infer_mime_type(file_path, &buffer, data_db.media_type(file_path).as_deref());
+                return Ok(Some((buffer, mime_type)));
             }
         }
         Ok(None)
@@ -315,10 +700,101 @@ impl MdxReader {
         self.content_db.find_first_match(key, prefix_match, partial_match, best_match)
     }
 
+    /// Finds the number of the entry with a key, see [`ZdbReader::get_entry_no_by_key`].
+    pub fn get_entry_no_by_key(&mut self, key: &str) -> Result<Option<EntryNo>> {
+        self.content_db.get_entry_no_by_key(key)
+    }
+
     pub fn get_similar_indexes(&mut self, key_index: &KeyIndex, start_with: bool, max_count: u64) -> Result<LinkedList<KeyIndex>> {
         self.content_db.get_similar_indexes(key_index, start_with, max_count)
     }
 
+    /// Gets the adjacent entries with the same key, see [`ZdbReader::get_similar_indexes_in_direction`].
+    pub fn get_similar_indexes_in_direction(&mut self, key_index: &KeyIndex, start_with: bool, max_count: u64, direction: SearchDirection) -> Result<LinkedList<KeyIndex>> {
+        self.content_db.get_similar_indexes_in_direction(key_index, start_with, max_count, direction)
+    }
+
+    /// Gets the entries on both sides with the same key, see [`ZdbReader::get_similar_indexes_around`].
+    pub fn get_similar_indexes_around(&mut self, key_index: &KeyIndex, start_with: bool, max_before: u64, max_after: u64) -> Result<LinkedList<KeyIndex>> {
+        self.content_db.get_similar_indexes_around(key_index, start_with, max_before, max_after)
+    }
+
+    /// Finds the entries whose key matches a wildcard pattern, see [`ZdbReader::find_wildcard`].
+    pub fn find_wildcard(&mut self, pattern: &str, limits: &SearchLimits) -> Result<LimitedResults<KeyIndex>> {
+        self.content_db.find_wildcard(pattern, limits)
+    }
+
+    /// Counts the entries whose key starts with `prefix`, see [`ZdbReader::count_prefix`].
+    pub fn count_prefix(&mut self, prefix: &str) -> Result<u64> {
+        self.content_db.count_prefix(prefix)
+    }
+
+    /// Gets the tags, frequency rank and part of speech of an entry, see [`ZdbReader::get_entry_meta_ext`].
+    pub fn get_entry_meta_ext(&self, entry_no: EntryNo) -> Result<Option<EntryMetaExt>> {
+        self.content_db.get_entry_meta_ext(entry_no)
+    }
+
+    /// Gets the line and offset of an entry in the source file, see [`ZdbReader::get_source_location`].
+    pub fn get_source_location(&self, entry_no: EntryNo) -> Result<Option<SourceLocation>> {
+        self.content_db.get_source_location(entry_no)
+    }
+
+    /// Looks up many keys in one pass over the key blocks, see [`ZdbReader::lookup_many`].
+    pub fn lookup_many(&mut self, keys: &[&str]) -> Result<Vec<Option<KeyIndex>>> {
+        self.content_db.lookup_many(keys)
+    }
+
+    /// Finds entries ignoring punctuation, whitespace, case and diacritics, see [`ZdbReader::find_folded`].
+    pub fn find_folded(&mut self, query: &str) -> Result<Vec<KeyIndex>> {
+        self.content_db.find_folded(query)
+    }
+
+    /// Expands a union entry into the entries sharing its key, see [`ZdbReader::expand_union`].
+    pub fn expand_union(&mut self, key_index: &KeyIndex) -> Result<Vec<KeyIndex>> {
+        self.content_db.expand_union(key_index)
+    }
+
+    /// Reports the approximate memory held by the content database, see [`ZdbReader::memory_footprint`].
+    pub fn memory_footprint(&self) -> MemoryFootprint {
+        self.content_db.memory_footprint()
+    }
+
+    /// Replaces the compact stylesheet of the header the content is expanded with.
+    ///
+    /// For dictionaries shipping a broken or no stylesheet, the header isn't changed.
+    /// An empty stylesheet turns expansion off.
+    ///
+    /// # Arguments
+    ///
+    /// * `style_sheet` - Lines of token, prefix and suffix, as in the `StyleSheet` header attribute
+    ///
+    /// # Errors
+    ///
+    /// Returns an `InvalidDataFormat` error if the stylesheet can't be parsed, the
+    /// stylesheet in use is kept then.
+    pub fn set_compact_stylesheet(&mut self, style_sheet: &str) -> Result<()> {
+        self.compact_stylesheet = Self::load_compact_stylesheet(style_sheet)?;
+        Ok(())
+    }
+
+    /// Replaces the compact stylesheet with the one in a file, see [`set_compact_stylesheet`](Self::set_compact_stylesheet).
+    ///
+    /// The file is a stylesheet of an MDict source, with Windows line endings or not.
+    pub fn set_compact_stylesheet_from_file<P: AsRef<Path>>(&mut self, path: P) -> Result<()> {
+        let style_sheet = std::fs::read_to_string(path.as_ref())?;
+        self.set_compact_stylesheet(&style_sheet.trim_start_matches('\u{FEFF}').replace("\r\n", "\n"))
+    }
+
+    /// Restores the compact stylesheet of the header, see [`set_compact_stylesheet`](Self::set_compact_stylesheet).
+    ///
+    /// # Errors
+    ///
+    /// Returns an `InvalidDataFormat` error if the stylesheet of the header can't be parsed.
+    pub fn reset_compact_stylesheet(&mut self) -> Result<()> {
+        self.compact_stylesheet = Self::load_compact_stylesheet(&self.content_db.meta.db_info.style_sheet)?;
+        Ok(())
+    }
+
     // Load compact stylesheet triples: token, prefix, suffix (newline-separated)
     pub fn load_compact_stylesheet(style_sheet: &str) -> Result<Vec<(String, String)>> {
         let mut compact_stylesheet = vec![(String::new(), String::new()); 256];
@@ -363,7 +839,24 @@ impl MdxReader {
     }
 
     /// Load FTS index from .idx file or directory
-    fn load_fts_index(idx_url: &Url) -> Result<Index> {
+    /// Opens the FTS index and its searcher, and checks its schema metadata, see [`FtsIndexMetadata::validate`].
+    fn load_fts_index(idx_url: &Url, entry_count: u64) -> Result<(Index, FtsSearcher)> {
+        let index = Self::open_fts_index(idx_url)?;
+        FtsIndexMetadata::validate(&index, entry_count)?;
+        let searcher = FtsSearcher::new(&index)?;
+        Ok((index, searcher))
+    }
+
+    fn open_fts_index(idx_url: &Url) -> Result<Index> {
+        // Packed indexes behind a resolver or inside a zip archive are read into memory
+        if idx_url.scheme() != "file" {
+            if !file_url_exists(idx_url) {
+                return Err(ZdbError::general_error(format!("FTS index not found. Checked for: {}", idx_url)));
+            }
+            let zip_directory = ZipDirectory::from_bytes(bytes_from_file_url(idx_url)?.into());
+            return Index::open(Box::new(zip_directory) as Box<dyn tantivy::directory::Directory>)
+                .map_err(|e| ZdbError::general_error(format!("Failed to open packed FTS index: {}", e)));
+        }
         let idx_path = idx_url.to_file_path().map_err(|_| ZdbError::invalid_path("Invalid FTS index URL path".to_string()))?;
         
         // Check if .idx file exists first, then fall back to directory
@@ -385,75 +878,274 @@ impl MdxReader {
     /// Perform full-text search on the database content
     /// Returns a vector of (score, entry_no, key) tuples for matching entries
     pub fn fts_search(&self, query_str: &str, max_results: usize) -> Result<Vec<(f32, EntryNo, String)>> {
-        if let Some(ref fts_index) = self.fts_index {
-            // Create a searcher for searching
-            let reader = fts_index.reader()
-                .map_err(|e| ZdbError::general_error(format!("Failed to create FTS index reader: {}", e)))?;
-            let searcher = reader.searcher();
-            
-            // Get schema fields
-            let schema = fts_index.schema();
-            let key_field = schema.get_field("key")
-                .map_err(|_| ZdbError::general_error("Field 'key' not found in FTS schema".to_string()))?;
-            let content_field = schema.get_field("content")
-                .map_err(|_| ZdbError::general_error("Field 'content' not found in FTS schema".to_string()))?;
-            let entry_no_field = schema.get_field("entry_no")
-                .map_err(|_| ZdbError::general_error("Field 'entry_no' not found in FTS schema".to_string()))?;
-            
-            // Create query parser for the searchable fields  
-            let query_parser = QueryParser::for_index(fts_index, vec![key_field, content_field]);
-            
-            // Parse the search query
-            let query = query_parser.parse_query(query_str)
-                .map_err(|e| ZdbError::general_error(format!("Failed to parse query '{}': {}", query_str, e)))?;
-            
-            // Perform the search
-            let top_docs = searcher.search(&query, &TopDocs::with_limit(max_results))
-                .map_err(|e| ZdbError::general_error(format!("FTS search failed: {}", e)))?;
-            
-            // Extract results
-            let mut results = Vec::new();
-            for (score, doc_address) in top_docs {
-                // Retrieve the document from the index
-                let retrieved_doc = searcher.doc::<TantivyDocument>(doc_address)
-                    .map_err(|e| ZdbError::general_error(format!("Failed to retrieve document: {}", e)))?;
-                
-                // Extract fields from the document
-                let entry_no: EntryNo = retrieved_doc
-                    .get_first(entry_no_field)
-                    .and_then(|v| v.as_u64())
-                    .map(|n| n as EntryNo)
-                    .ok_or(ZdbError::general_error("Entry number not found in FTS index".to_string()))?;
-                let key = retrieved_doc.get_first(key_field)
-                    .and_then(|v| v.as_str())
-                    .unwrap_or("Unknown")
-                    .to_string();
-                    
-                results.push((score, entry_no, key));
+        self.loaded_fts_searcher()?.search(query_str, max_results)
+    }
+
+    /// Performs full-text search with the fields selected by `options`, see [`FtsSearcher::search_with_options`].
+    pub fn fts_search_with_options(&self, query_str: &str, max_results: usize, options: &FtsSearchOptions) -> Result<Vec<(f32, EntryNo, String)>> {
+        self.loaded_fts_searcher()?.search_with_options(query_str, max_results, options)
+    }
+
+    /// Performs full-text search stopping at the limits, see [`FtsSearcher::search_limited`].
+    pub fn fts_search_limited(&self, query_str: &str, max_results: usize, limits: &SearchLimits) -> Result<LimitedResults<(f32, EntryNo, String)>> {
+        self.loaded_fts_searcher()?.search_limited(query_str, max_results, limits)
+    }
+
+    /// Returns a handle for issuing many full-text searches, e.g. while the user types.
+    ///
+    /// The handle shares the index reader of this dictionary, so it has no per-call setup,
+    /// and it can be used from other threads.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the dictionary has no usable FTS index.
+    pub fn fts_searcher(&self) -> Result<FtsSearcher> {
+        self.loaded_fts_searcher().cloned()
+    }
+
+    /// Searches the headwords and, if the dictionary has an FTS index, the contents,
+    /// merging the hits into one ranked list, see [`ranked_search`](super::ranked_search).
+    ///
+    /// # Arguments
+    ///
+    /// * `query` - The text typed by the user, also parsed as an FTS query
+    /// * `options` - Weights and limits of the sources
+    ///
+    /// # Returns
+    ///
+    /// Returns the hits best first.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a key block can't be read, the fuzzy distance is too large or
+    /// the query isn't a valid FTS query.
+    pub fn search(&mut self, query: &str, options: &SearchOptions) -> Result<Vec<SearchHit>> {
+        let query = query.trim();
+        if query.is_empty() {
+            return Ok(Vec::new());
+        }
+        let mut merger = HitMerger::new(options);
+
+        // Exact and prefix hits from the run of keys starting with the query
+        let scan_count = options.max_exact_hits.saturating_add(options.max_prefix_hits) as u64;
+        if scan_count > 0 && let Some(first) = self.find_index(query, true, false, false)? {
+            let count = self.count_prefix(query)?.min(scan_count);
+            let query_lowercase = query.to_lowercase();
+            let query_len = query.chars().count() as f32;
+            let (mut exact_hits, mut prefix_hits) = (0, 0);
+            for key_index in self.get_indexes(first.entry_no, count)? {
+                if key_index.key.to_lowercase() == query_lowercase {
+                    if exact_hits < options.max_exact_hits {
+                        merger.add(key_index.entry_no, &key_index.key, SearchSource::Exact, 1.0);
+                        exact_hits += 1;
+                    }
+                } else if prefix_hits < options.max_prefix_hits {
+                    // Headwords adding fewer characters to the query rank higher
+                    merger.add(key_index.entry_no, &key_index.key, SearchSource::Prefix, query_len / key_index.key.chars().count().max(1) as f32);
+                    prefix_hits += 1;
+                }
+            }
+        }
+
+        if let Some(ref fts_searcher) = self.fts_searcher {
+            if options.max_fuzzy_hits > 0 {
+                for suggestion in fts_searcher.suggest(query, options.fuzzy_distance, options.max_fuzzy_hits)? {
+                    merger.add(suggestion.entry_no, &suggestion.key, SearchSource::Fuzzy, HitMerger::fuzzy_relevance(suggestion.distance));
+                }
+            }
+            if options.max_fts_hits > 0 {
+                let results = fts_searcher.search(query, options.max_fts_hits)?;
+                // Scores are relative to the best match, their scale depends on the index
+                let best_score = results.first().map(|(score, _, _)| *score).unwrap_or_default();
+                for (score, entry_no, key) in results {
+                    merger.add(entry_no, &key, SearchSource::FullText, if best_score > 0.0 { score / best_score } else { 1.0 });
+                }
             }
-            
-            Ok(results)
+        }
+        Ok(merger.into_ranked())
+    }
+
+    /// Finds the multi-word headwords containing `word` using the FTS index, see [`FtsSearcher::search_headword_tokens`].
+    ///
+    /// Unlike prefix search, this finds idioms like "kick the bucket" by any of their words.
+    pub fn search_headword_tokens(&self, word: &str) -> Result<Vec<(EntryNo, String)>> {
+        self.loaded_fts_searcher()?.search_headword_tokens(word)
+    }
+
+    /// Suggests headwords for a possibly misspelled term using the FTS index, see [`FtsSearcher::suggest`].
+    pub fn suggest(&self, term: &str, max_distance: u8, limit: usize) -> Result<Vec<Suggestion>> {
+        self.loaded_fts_searcher()?.suggest(term, max_distance, limit)
+    }
+
+    fn loaded_fts_searcher(&self) -> Result<&FtsSearcher> {
+        if let Some(ref fts_searcher) = self.fts_searcher {
+            Ok(fts_searcher)
+        } else if self.fts_needs_reindex {
+            Err(ZdbError::fts_index_outdated("the index of this dictionary was built with an incompatible version"))
         } else {
             Err(ZdbError::general_error("Full-text search index is not available".to_string()))
         }
     }
+
+    /// Reports the size and schema of the FTS index, see [`FtsIndexStats::collect`].
+    ///
+    /// Also works for an index that needs to be rebuilt, see [`needs_reindex`](Self::needs_reindex).
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the dictionary has no FTS index or it can't be read.
+    pub fn fts_index_stats(&self) -> Result<FtsIndexStats> {
+        FtsIndexStats::collect(&self.any_fts_index()?, self.get_entry_count())
+    }
+
+    /// Compares the FTS index with the dictionary, see [`fts_health::check_index`].
+    ///
+    /// # Returns
+    ///
+    /// Returns the problems found, empty for a healthy index.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the dictionary has no FTS index or it can't be read.
+    pub fn check_fts_index(&self) -> Result<Vec<FtsIndexProblem>> {
+        fts_health::check_index(&self.any_fts_index()?, self.get_entry_count(), &self.content_db.meta.db_info.uuid)
+    }
+
+    /// The loaded FTS index, or the one rejected when the dictionary was opened.
+    fn any_fts_index(&self) -> Result<Index> {
+        match self.fts_index {
+            Some(ref index) => Ok(index.clone()),
+            None => Self::open_fts_index(&with_extension(&self.mdx_url, MDICT_INDEX_EXT)?),
+        }
+    }
+
+    /// Checks whether the dictionary has an FTS index that can't be used until it is rebuilt.
+    ///
+    /// # Returns
+    ///
+    /// Returns `true` if the index was built by another tool or version, or for a different
+    /// version of the dictionary, `false` if it is usable or there is no index at all.
+    pub fn needs_reindex(&self) -> bool {
+        self.fts_needs_reindex
+    }
     
     /// Check if full-text search is available (index is loaded and not empty)
     pub fn is_fts_available(&self) -> bool {
-        if let Some(ref fts_index) = self.fts_index {
-            if let Ok(reader) = fts_index.reader() {
-                reader.searcher().num_docs() > 0
-            } else {
-                false
-            }
-        } else {
-            false
-        }
+        self.fts_searcher.as_ref().is_some_and(|fts_searcher| fts_searcher.num_docs() > 0)
     }
     
+    /// Collects headword and content statistics of the dictionary.
+    ///
+    /// See [`DictStatistics::collect`] for details.
+    pub fn get_statistics(&mut self, prog_rpt: Option<ProgressReportFn>) -> Result<DictStatistics> {
+        DictStatistics::collect(&mut self.content_db, prog_rpt)
+    }
+
+    /// Gets the description of the dictionary as HTML for an about page.
+    ///
+    /// Entities in header attributes are already unescaped when the header is parsed, so the
+    /// description is used as is. A description with markup is passed through
+    /// [`MdxHtmlRewriter`], so images and links referring to the MDD resolve like in entry
+    /// content. A plain text description is HTML-escaped, with line breaks kept.
+    ///
+    /// # Arguments
+    ///
+    /// * `profile_id` - Profile id embedded in the rewritten resource URLs
+    ///
+    /// # Returns
+    ///
+    /// Returns the rewritten HTML, empty if the dictionary has no description.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the HTML can't be rewritten.
+    pub fn about_html(&self, profile_id: i32) -> Result<String> {
+        description_html(&self.content_db.meta.db_info.description, profile_id)
+    }
+
+    /// Gets the primary stylesheet of the dictionary with its `url()` references rewritten,
+    /// to be injected once into the head of a page, see [`dict_css`](super::dict_css).
+    ///
+    /// The stylesheet is the file with the stem of the MDX file and the `css` extension,
+    /// looked up next to the MDX file first, then in the MDD file.
+    ///
+    /// # Arguments
+    ///
+    /// * `profile_id` - Profile id embedded in the rewritten URLs
+    /// * `options` - Base URL of the rewritten URLs and whether to minify
+    ///
+    /// # Returns
+    ///
+    /// Returns `None` if the dictionary has no stylesheet.
+    pub fn get_stylesheet(&mut self, profile_id: i32, options: &StylesheetOptions) -> Result<Option<DictStylesheet>> {
+        let css_url = with_extension(&self.mdx_url, CSS_EXT)?;
+        let (source, data) = if file_url_exists(&css_url) {
+            let data = bytes_from_file_url(&css_url)?;
+            (StylesheetSource::File(css_url), data)
+        } else {
+            let key = format!("/{}.{}", self.db_name, CSS_EXT);
+            match self.data_db.as_mut().map(|data_db| data_db.get_data_by_key(&key)).transpose()?.flatten() {
+                Some(data) => (StylesheetSource::Resource(key), data),
+                None => return Ok(None),
+            }
+        };
+        let css = decode_bytes_to_string(&data, encoding_rs::UTF_8)?;
+        let css = MdxHtmlRewriter::rewrite_css_urls(&css, profile_id, &options.base_url);
+        let css = if options.minify { minify_css(&css) } else { css };
+        Ok(Some(DictStylesheet { source, css }))
+    }
+
     /// Check if data database is available (for resources like CSS, images, etc.)
     pub fn is_data_db_available(&self) -> bool {
         self.data_db.is_some()
     }
 
-}
\ No newline at end of file
+}
+
+/// Turns the description of a dictionary into HTML, see [`MdxReader::about_html`].
+fn description_html(description: &str, profile_id: i32) -> Result<String> {
+    if description.trim().is_empty() {
+        return Ok(String::new());
+    }
+    // Text is HTML only if it has a tag, a bare "<" as in "a < b" is plain text
+    let has_markup = description.match_indices('<')
+        .any(|(pos, _)| description[pos + 1..].starts_with(|c: char| c.is_ascii_alphabetic() || c == '/' || c == '!'));
+    if has_markup {
+        MdxHtmlRewriter::rewrite_html(description, profile_id)
+    } else {
+        let mut escaped = String::with_capacity(description.len());
+        html_escape_mdx_text(description, &mut escaped);
+        Ok(escaped)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::meta_unit::DbInfo;
+
+    fn about_html_of(description_attr: &str) -> String {
+        let xml = format!(r#"<Dictionary GeneratedByEngineVersion="2.0" RequiredEngineVersion="2.0" Encoding="UTF-8" Description="{}" Title="Test"/>"#, description_attr);
+        let db_info = DbInfo::from_xml(&xml).unwrap();
+        description_html(&db_info.description, 0).unwrap()
+    }
+
+    #[test]
+    fn about_html_is_not_unescaped_twice() {
+        // The header holds the text "&lt;script&gt;", it must not turn into markup
+        let html = about_html_of("Use &amp;lt;script&amp;gt; when a &lt; b");
+        assert_eq!(html, "Use &amp;lt;script&amp;gt; when a &lt; b");
+
+        let html = about_html_of("&lt;p&gt;&amp;lt;script&amp;gt;alert(1)&amp;lt;/script&amp;gt;&lt;/p&gt;");
+        assert!(!html.contains("<script"), "{}", html);
+        assert!(html.starts_with("<p>&lt;script&gt;"), "{}", html);
+    }
+
+    #[test]
+    fn about_html_rewrites_resources() {
+        let html = about_html_of("&lt;img src=&quot;logo.png&quot;&gt;");
+        assert!(html.starts_with("<img"), "{}", html);
+        assert!(!html.contains(r#"src="logo.png""#), "{}", html);
+        assert_eq!(about_html_of("  "), "");
+    }
+}
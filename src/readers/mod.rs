@@ -6,7 +6,31 @@
 pub mod mdx_reader;
 pub mod mdd_reader;
 pub mod zdb_reader;
+pub mod dict_stats;
+pub mod dict_metadata;
+pub mod fts_searcher;
+pub mod fts_health;
+pub mod ranked_search;
+pub mod search_limits;
+pub mod dict_pack;
+pub mod dict_registry;
+pub mod dict_css;
+pub mod open_report;
+#[cfg(feature = "whatlang")]
+pub mod language_detect;
 
 pub use mdx_reader::MdxReader;
 pub use mdd_reader::MddReader;
-pub use zdb_reader::ZdbReader;
+pub use zdb_reader::{KeyOrderCheck, MemoryFootprint, ReaderOptions, SearchDirection, SharedBlockCache, UnavailableUnit, ZdbReader};
+pub use dict_stats::DictStatistics;
+pub use dict_metadata::DictMetadata;
+pub use fts_health::{FtsFieldInfo, FtsIndexProblem, FtsIndexStats};
+pub use fts_searcher::{FtsSearchOptions, FtsSearcher, Suggestion};
+pub use ranked_search::{SearchHit, SearchOptions, SearchSource};
+pub use search_limits::{LimitedResults, SearchLimits, Truncation};
+pub use dict_pack::{DictPackEntry, DictPackManifest};
+pub use dict_registry::{DictRegistry, ProfileIdMap, RegisteredDict};
+pub use dict_css::{DictStylesheet, StylesheetCache, StylesheetOptions, StylesheetSource};
+pub use open_report::{OpenReport, UnitTiming};
+#[cfg(feature = "whatlang")]
+pub use language_detect::LanguageReport;
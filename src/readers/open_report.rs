@@ -0,0 +1,84 @@
+//! Time spent opening a dictionary, by step.
+//!
+//! Opening a large dictionary can take seconds, mostly spent decoding indexes. Every
+//! reader records how long each step of its opening took in an [`OpenReport`], see
+//! [`ZdbReader::open_report`](crate::readers::ZdbReader::open_report) and
+//! [`MdxReader::open_report`](crate::readers::MdxReader::open_report), so integrators can
+//! tell which part of a slow dictionary to target, e.g. with
+//! [`ReaderOptions::lazy_key_index`](crate::readers::ReaderOptions::lazy_key_index).
+//!
+//! The `Display` output lists the steps in milliseconds.
+
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+
+use crate::storage::unit_base::UnitType;
+use crate::Result;
+
+/// Time spent reading one unit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct UnitTiming {
+    pub unit_type: UnitType,
+    pub duration: Duration,
+}
+
+/// Time spent opening a dictionary, see the [module documentation](self).
+///
+/// Steps a reader doesn't take, e.g. MDD probing for a [`ZdbReader`](crate::readers::ZdbReader),
+/// are zero.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct OpenReport {
+    /// Reading the header and deriving the crypto key, without creating the collator
+    pub header: Duration,
+    /// Creating the collator of the header locale, near zero if another dictionary created it
+    pub collator: Duration,
+    /// Units read at open, in the order they were read
+    ///
+    /// With [`ReaderOptions::parallel_open`](crate::readers::ReaderOptions::parallel_open) the
+    /// key block index is decoded while the other units are read, so the durations add up to
+    /// more than the time spent.
+    pub units: Vec<UnitTiming>,
+    /// Opening the companion content file
+    pub content_file: Duration,
+    /// Verifying the key order, see [`ReaderOptions::key_order_check`](crate::readers::ReaderOptions::key_order_check)
+    pub key_order_check: Duration,
+    /// Probing for and opening the MDD file(s)
+    pub mdd: Duration,
+    /// Opening the full-text search index
+    pub fts: Duration,
+    /// Whole opening, from the first read to the reader being ready
+    pub total: Duration,
+}
+
+impl OpenReport {
+    /// Time spent reading a unit, zero if it wasn't read at open.
+    pub fn unit(&self, unit_type: UnitType) -> Duration {
+        self.units.iter().filter(|timing| timing.unit_type == unit_type).map(|timing| timing.duration).sum()
+    }
+
+    /// Runs a step reading a unit and records its duration.
+    pub(crate) fn time_unit<T, F: FnOnce() -> Result<T>>(units: &mut Vec<UnitTiming>, unit_type: UnitType, read: F) -> Result<T> {
+        let start = Instant::now();
+        let result = read();
+        units.push(UnitTiming { unit_type, duration: start.elapsed() });
+        result
+    }
+}
+
+impl fmt::Display for OpenReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let ms = |duration: Duration| duration.as_secs_f64() * 1000.0;
+        writeln!(f, "header: {:.1} ms", ms(self.header))?;
+        writeln!(f, "collator: {:.1} ms", ms(self.collator))?;
+        for timing in &self.units {
+            writeln!(f, "{:?} unit: {:.1} ms", timing.unit_type, ms(timing.duration))?;
+        }
+        writeln!(f, "content file: {:.1} ms", ms(self.content_file))?;
+        writeln!(f, "key order check: {:.1} ms", ms(self.key_order_check))?;
+        writeln!(f, "mdd: {:.1} ms", ms(self.mdd))?;
+        writeln!(f, "fts: {:.1} ms", ms(self.fts))?;
+        write!(f, "total: {:.1} ms", ms(self.total))
+    }
+}
@@ -0,0 +1,146 @@
+//! Ranked search merging headword and full-text hits.
+//!
+//! A search box usually shows exact headword matches first, then headwords starting
+//! with the query, then near misses and finally entries mentioning the query in their
+//! content. [`MdxReader::search`](crate::MdxReader::search) collects the hits of each
+//! source, scales their relevance by the weight of the source and merges them into one
+//! list, an entry found by several sources keeping its best score.
+//!
+//! Fuzzy and full-text hits need an FTS index, they are left out for dictionaries without one.
+
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+use crate::readers::fts_searcher::MAX_SUGGESTION_DISTANCE;
+use crate::storage::key_block::EntryNo;
+
+/// Source of a [`SearchHit`], in the order hits of equal score are ranked.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum SearchSource {
+    /// The headword equals the query, ignoring case
+    Exact,
+    /// The headword starts with the query
+    Prefix,
+    /// A word of the headword is within the fuzzy distance of the query
+    Fuzzy,
+    /// The query matched the content or the words of the headword
+    FullText,
+}
+
+/// Weights and limits of a ranked search, see the [module documentation](self).
+///
+/// The score of a hit is the weight of its source times its relevance in that source,
+/// between 0 and 1. A limit of 0 leaves the source out.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SearchOptions {
+    /// Maximum number of merged results
+    pub max_results: usize,
+    pub exact_weight: f32,
+    pub prefix_weight: f32,
+    pub fuzzy_weight: f32,
+    pub fts_weight: f32,
+    /// Maximum number of exact headword hits, e.g. homographs
+    pub max_exact_hits: usize,
+    /// Maximum number of headwords starting with the query, not counting exact hits
+    pub max_prefix_hits: usize,
+    pub max_fuzzy_hits: usize,
+    pub max_fts_hits: usize,
+    /// Maximum edit distance of fuzzy hits, at most [`MAX_SUGGESTION_DISTANCE`]
+    pub fuzzy_distance: u8,
+}
+
+impl Default for SearchOptions {
+    fn default() -> Self {
+        Self {
+            max_results: 50,
+            exact_weight: 4.0,
+            prefix_weight: 2.0,
+            fuzzy_weight: 1.0,
+            fts_weight: 1.0,
+            max_exact_hits: 10,
+            max_prefix_hits: 50,
+            max_fuzzy_hits: 10,
+            max_fts_hits: 50,
+            fuzzy_distance: 1,
+        }
+    }
+}
+
+/// Entry found by a ranked search.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SearchHit {
+    pub entry_no: EntryNo,
+    pub key: String,
+    pub score: f32,
+    /// Source of the best score of the entry
+    pub source: SearchSource,
+}
+
+/// Collects the hits of the sources, keeping the best score per entry.
+pub(crate) struct HitMerger<'a> {
+    options: &'a SearchOptions,
+    hits: HashMap<EntryNo, SearchHit>,
+}
+
+impl<'a> HitMerger<'a> {
+    pub(crate) fn new(options: &'a SearchOptions) -> Self {
+        Self { options, hits: HashMap::new() }
+    }
+
+    /// Adds a hit with a relevance between 0 and 1 in its source.
+    pub(crate) fn add(&mut self, entry_no: EntryNo, key: &str, source: SearchSource, relevance: f32) {
+        let weight = match source {
+            SearchSource::Exact => self.options.exact_weight,
+            SearchSource::Prefix => self.options.prefix_weight,
+            SearchSource::Fuzzy => self.options.fuzzy_weight,
+            SearchSource::FullText => self.options.fts_weight,
+        };
+        let hit = SearchHit { entry_no, key: key.to_string(), score: weight * relevance.clamp(0.0, 1.0), source };
+        match self.hits.get_mut(&entry_no) {
+            Some(best) if rank(&hit, best) != Ordering::Less => {}
+            Some(best) => *best = hit,
+            None => {
+                self.hits.insert(entry_no, hit);
+            }
+        }
+    }
+
+    /// Relevance of a fuzzy hit `distance` edits away.
+    pub(crate) fn fuzzy_relevance(distance: u8) -> f32 {
+        1.0 - distance as f32 / (MAX_SUGGESTION_DISTANCE + 1) as f32
+    }
+
+    /// Returns the hits best first, at most `max_results` of them.
+    pub(crate) fn into_ranked(self) -> Vec<SearchHit> {
+        let mut hits: Vec<SearchHit> = self.hits.into_values().collect();
+        hits.sort_by(rank);
+        hits.truncate(self.options.max_results);
+        hits
+    }
+}
+
+/// Orders hits by score, best first, then by source and entry number.
+fn rank(a: &SearchHit, b: &SearchHit) -> Ordering {
+    b.score.total_cmp(&a.score).then(a.source.cmp(&b.source)).then(a.entry_no.cmp(&b.entry_no))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_merge_hits() {
+        let options = SearchOptions { max_results: 3, ..Default::default() };
+        let mut merger = HitMerger::new(&options);
+        merger.add(5, "bucket list", SearchSource::FullText, 1.0);
+        merger.add(5, "bucket list", SearchSource::Prefix, 0.5);
+        merger.add(4, "bucket", SearchSource::Exact, 1.0);
+        merger.add(4, "bucket", SearchSource::FullText, 0.8);
+        merger.add(7, "kick the bucket", SearchSource::FullText, 0.9);
+        merger.add(9, "pail", SearchSource::FullText, 0.1);
+        let hits = merger.into_ranked();
+        assert_eq!(hits.iter().map(|hit| (hit.entry_no, hit.source)).collect::<Vec<_>>(),
+            vec![(4, SearchSource::Exact), (5, SearchSource::Prefix), (7, SearchSource::FullText)]);
+        assert_eq!(hits[0].score, 4.0);
+    }
+}
@@ -0,0 +1,130 @@
+//! Per-call limits of searches.
+//!
+//! A query matching most of a dictionary, like the wildcard pattern `*a*` or the FTS
+//! query `a*`, can take seconds on a large dictionary. Searches taking [`SearchLimits`]
+//! stop once a limit is reached and return what they found so far, with
+//! [`LimitedResults::truncated`] telling which limit cut them short, so a UI thread
+//! or a server worker is never blocked for long.
+
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+
+/// Limits of a single search, `None` for no limit.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SearchLimits {
+    /// Maximum number of matches examined
+    pub max_matches: Option<usize>,
+    /// Maximum wall-clock time of the search
+    pub timeout: Option<Duration>,
+    /// Maximum number of key blocks decoded by key scans
+    pub max_scanned_blocks: Option<usize>,
+}
+
+/// Limit that stopped a search, see [`LimitedResults`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub enum Truncation {
+    MaxMatches,
+    Timeout,
+    MaxScannedBlocks,
+}
+
+/// Results of a search with [`SearchLimits`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct LimitedResults<T> {
+    pub results: Vec<T>,
+    /// The limit that stopped the search before it examined all candidates, `None` if it completed
+    pub truncated: Option<Truncation>,
+}
+
+/// Number of matches between two checks of the clock.
+const CLOCK_CHECK_INTERVAL: usize = 256;
+
+/// Tracks the limits of a running search.
+pub(crate) struct SearchBudget<'a> {
+    limits: &'a SearchLimits,
+    deadline: Option<Instant>,
+    matches: usize,
+    scanned_blocks: usize,
+}
+
+impl<'a> SearchBudget<'a> {
+    /// Starts the clock of a search.
+    pub(crate) fn start(limits: &'a SearchLimits) -> Self {
+        Self { limits, deadline: limits.timeout.map(|timeout| Instant::now() + timeout), matches: 0, scanned_blocks: 0 }
+    }
+
+    /// Counts a match, checking the clock every few matches.
+    ///
+    /// Returns the limit reached if the match must not be examined.
+    pub(crate) fn add_match(&mut self) -> Option<Truncation> {
+        if self.limits.max_matches.is_some_and(|max_matches| self.matches >= max_matches) {
+            return Some(Truncation::MaxMatches);
+        }
+        self.matches += 1;
+        if self.matches.is_multiple_of(CLOCK_CHECK_INTERVAL) {
+            return self.check_time();
+        }
+        None
+    }
+
+    /// Counts a key block about to be scanned, checking the clock.
+    ///
+    /// Returns the limit reached if the block must not be scanned.
+    pub(crate) fn scan_block(&mut self) -> Option<Truncation> {
+        if self.limits.max_scanned_blocks.is_some_and(|max_blocks| self.scanned_blocks >= max_blocks) {
+            return Some(Truncation::MaxScannedBlocks);
+        }
+        self.scanned_blocks += 1;
+        self.check_time()
+    }
+
+    /// Returns [`Truncation::Timeout`] if the deadline has passed.
+    pub(crate) fn check_time(&self) -> Option<Truncation> {
+        self.deadline.filter(|deadline| Instant::now() >= *deadline).map(|_| Truncation::Timeout)
+    }
+}
+
+/// Matches `text` against a pattern where `*` matches any characters and `?` one character.
+///
+/// Backtracks only to the last `*`, so the time is at most proportional to the product
+/// of the lengths whatever the pattern.
+pub(crate) fn wildcard_match(pattern: &[char], text: &[char]) -> bool {
+    let (mut p, mut t) = (0, 0);
+    let mut star: Option<(usize, usize)> = None;
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            star = Some((p, t));
+            p += 1;
+        } else if let Some((star_p, star_t)) = star {
+            // Let the last star match one more character
+            p = star_p + 1;
+            t = star_t + 1;
+            star = Some((star_p, star_t + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wildcard_match() {
+        let matches = |pattern: &str, text: &str| wildcard_match(&pattern.chars().collect::<Vec<_>>(), &text.chars().collect::<Vec<_>>());
+        assert!(matches("*a*", "banana"));
+        assert!(matches("b?n*", "banana"));
+        assert!(matches("*", ""));
+        assert!(matches("ba*na", "banana"));
+        assert!(!matches("ba*nb", "banana"));
+        assert!(!matches("?", ""));
+        assert!(matches("a*b*c", "axxbyyc"));
+        assert!(!matches(&"*a".repeat(20), &"b".repeat(100)));
+    }
+}
@@ -12,25 +12,44 @@
 //! This module works with all ZDB versions (V1, V2, V3).
 
 use std::cmp::{min, Ordering};
+use std::collections::hash_map::DefaultHasher;
 use std::collections::{HashSet, LinkedList};
-use std::io::{BufReader, Read, Seek};
+use std::hash::{Hash, Hasher};
+use std::io::{BufReader, Cursor, Read, Seek, SeekFrom};
 use std::num::NonZeroUsize;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::rc::Rc;
 use std::str;
+use std::sync::Arc;
+use std::time::Instant;
 
 use lru::LruCache;
+use serde::Serialize;
 
+use crate::readers::open_report::{OpenReport, UnitTiming};
+use crate::readers::search_limits::{wildcard_match, LimitedResults, SearchBudget, SearchLimits};
+use crate::storage::bloom_filter_unit::BloomFilterUnit;
+use crate::storage::entry_meta_unit::{EntryMetaExt, EntryMetaUnit};
+use crate::storage::source_map_unit::{SourceLocation, SourceMapUnit};
 use crate::storage::content_block::ContentBlock;
-use crate::storage::content_block_index_unit::ContentBlockIndexUnit;
+use crate::storage::content_block_index_unit::{ContentBlockIndex, ContentBlockIndexUnit};
 use crate::storage::content_unit::ContentUnit;
-use crate::storage::key_block::{EntryNo, KeyIndex};
+use crate::storage::key_block::{EntryNo, KeyIndex, UNION_PREFIX};
 use crate::storage::key_block_index_unit::KeyBlockIndexUnit;
-use crate::storage::key_unit::KeyUnit;
-use crate::storage::meta_unit::{ContentType, MetaUnit};
-use crate::storage::reader_helper::decode_bytes_to_string;
+use crate::storage::key_unit::{KeyUnit, KEY_UNIT_CONTENT_LENGTHS};
+use crate::storage::meta_unit::{ContentType, MetaUnit, ZdbVersion};
+use crate::storage::reader_helper::{decode_bytes_to_string, encode_string_to_bytes, DecodeDiagnostics, DecodeMode};
+use crate::storage::unit_base::{skip_unit_v3, UnitType};
+use crate::storage::storage_block::RawBlockInfo;
+use crate::storage::unit_digest::{verify_unit_digests, UnitDigestCheck};
+use crate::utils::key_normalization::fold_headword;
+use crate::utils::checksum::ChecksumAlgorithm;
+use crate::utils::compression::CompressionMethod;
+use crate::utils::io_utils::{read_exact_to_vec, ReadSeek};
+use crate::utils::sharded_cache::ShardedLruCache;
 use crate::utils::sort_key::get_sort_key;
-use crate::utils::KeyComparable;
+use crate::utils::{key_compare, locale_compare, KeyComparable};
+use crate::error::LicenseErrorKind;
 use crate::{Result, ZdbError};
 
 const LINK_PREFIX: &[u8] = b"@@@LINK=";
@@ -45,6 +64,166 @@ const LINK_PREFIX_W: &[u8] = &[
     0x3D, 0x00, // '=' (U+003D)
 ];
 
+/// Returns the first position at or after `start` where `is_less` is false.
+///
+/// Probes exponentially growing steps from `start`, then binary searches the last step,
+/// which is fast when the position is close to `start`. `is_less` must be true for a
+/// prefix of `items` and false for the rest.
+fn gallop<T, F: FnMut(&T) -> Result<bool>>(items: &[T], start: usize, mut is_less: F) -> Result<usize> {
+    let mut low = start;
+    let mut step = 1;
+    let mut high = start;
+    while high < items.len() && is_less(&items[high])? {
+        low = high + 1;
+        high = start + step;
+        step *= 2;
+    }
+    let mut high = high.min(items.len());
+    while low < high {
+        let mid = (low + high) / 2;
+        if is_less(&items[mid])? {
+            low = mid + 1;
+        } else {
+            high = mid;
+        }
+    }
+    Ok(low)
+}
+
+/// Returns the first position in `items` where `is_less` is false, which must be true for a prefix of `items`.
+fn lower_bound<T, F: FnMut(&T) -> Result<bool>>(items: &[T], mut is_less: F) -> Result<usize> {
+    let (mut low, mut high) = (0, items.len());
+    while low < high {
+        let mid = (low + high) / 2;
+        if is_less(&items[mid])? {
+            low = mid + 1;
+        } else {
+            high = mid;
+        }
+    }
+    Ok(low)
+}
+
+/// Options for opening a dictionary.
+#[derive(Debug, Clone, Default)]
+pub struct ReaderOptions {
+    /// Approximate memory budget of the reader in bytes, see [`ZdbReader::memory_footprint`].
+    ///
+    /// Opening fails if the key block index alone exceeds the budget, and cached blocks
+    /// and the folded headword index are evicted to stay within it. The size of the key
+    /// block index of V3 files is checked against the budget before it is decoded, so
+    /// `parallel_open` has no effect then. `None` means unlimited.
+    pub max_memory: Option<u64>,
+    /// Decode the key block index of V3 files on first use instead of at open, which makes
+    /// opening faster, e.g. when many dictionaries are opened at startup. V1/V2 files are
+    /// always loaded at open.
+    pub lazy_key_index: bool,
+    /// Verify at open that the stored keys are in the order of the header locale (default: off)
+    ///
+    /// Keys sorted by another collation backend or another version of the locale data
+    /// can't be found reliably by binary search. See [`ZdbReader::check_key_order`].
+    pub key_order_check: KeyOrderCheck,
+    /// Fail to open with a `KeyOrderMismatch` error instead of logging a warning
+    pub fail_on_key_order_mismatch: bool,
+    /// Decode the key block index of V3 files on a second thread while the content block
+    /// index is decoded, which makes opening large files faster. Only used by
+    /// [`ZdbReader::from_file_with_options`], which can open the file a second time.
+    pub parallel_open: bool,
+    /// Open V3 files whose optional trailing units are incomplete, see [`ZdbReader::open_partial`]
+    pub allow_partial: bool,
+    /// How malformed text is handled by [`ZdbReader::get_string`], see [`DecodeMode`]
+    pub decode_mode: DecodeMode,
+    /// Directory of the companion file holding the content blocks of a dictionary built with
+    /// [`BuilderConfig::content_file_extension`](crate::builder::BuilderConfig::content_file_extension).
+    ///
+    /// The openers taking a path or a file URL use the directory of the dictionary if this is
+    /// `None`. Opening such a dictionary from a reader fails without it.
+    pub content_dir: Option<PathBuf>,
+    /// Cache of decoded content blocks shared with other readers, e.g. the readers of the
+    /// worker threads of a server, looked up when a block isn't in the reader's own cache.
+    ///
+    /// Blocks are keyed by the dictionary UUID and their offset, so one cache can serve
+    /// several dictionaries. V1/V2 files have no UUID, a cache shared by readers of them must
+    /// only be used for one dictionary. Only completely decoded blocks are shared.
+    pub shared_block_cache: Option<Arc<SharedBlockCache>>,
+}
+
+/// Content block cache shared by readers on several threads, see [`ReaderOptions::shared_block_cache`].
+///
+/// The number of shards is set by the [`BlockCacheConfig`](crate::utils::BlockCacheConfig) it's created with.
+pub type SharedBlockCache = ShardedLruCache<(u64, u64), Arc<ContentBlock>>;
+
+/// Extent of the key order verification, see [`ReaderOptions::key_order_check`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum KeyOrderCheck {
+    /// Keys aren't checked
+    #[default]
+    Off,
+    /// Checks the boundaries of all key blocks and every key of a few blocks
+    Sampled,
+    /// Checks every key, which decodes all key blocks
+    Full,
+}
+
+/// Direction of a walk from an anchor entry, see [`ZdbReader::get_similar_indexes_in_direction`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SearchDirection {
+    /// Towards the last entry
+    #[default]
+    Forward,
+    /// Towards the first entry
+    Backward,
+}
+
+/// An optional unit left out by [`ZdbReader::open_partial`] because it's incomplete.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct UnavailableUnit {
+    pub unit_type: UnitType,
+    /// What can't be used without the unit, e.g. "Bloom filter"
+    pub capability: &'static str,
+    /// Why the unit couldn't be read
+    pub reason: String,
+}
+
+/// Number of key blocks whose keys are all checked by [`KeyOrderCheck::Sampled`].
+const KEY_ORDER_SAMPLE_BLOCKS: usize = 4;
+
+/// Approximate number of bytes held by a reader, by purpose.
+///
+/// The sizes are estimates from the lengths of the held keys and buffers plus the
+/// size of the structs, allocator overhead isn't included.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct MemoryFootprint {
+    /// Key block index and content block index, held while the reader is open
+    pub block_indexes: u64,
+    /// Decoded key blocks in the key block cache
+    pub key_block_cache: u64,
+    /// Decoded content blocks in the content block cache
+    pub content_block_cache: u64,
+    /// Bloom filter, held while the reader is open
+    pub bloom_filter: u64,
+    /// Entry metadata table, held while the reader is open
+    pub entry_meta: u64,
+    /// Source map, held while the reader is open
+    pub source_map: u64,
+    /// Folded headword index built by [`ZdbReader::find_folded`]
+    pub folded_index: u64,
+    /// Header and dictionary information
+    pub metadata: u64,
+}
+
+impl MemoryFootprint {
+    /// Total number of bytes over all purposes, the value compared with `ReaderOptions::max_memory`.
+    pub fn total(&self) -> u64 {
+        self.block_indexes + self.key_block_cache + self.content_block_cache + self.bloom_filter + self.entry_meta + self.source_map + self.folded_index + self.metadata
+    }
+
+    /// Bytes that can't be released without closing the reader.
+    fn resident(&self) -> u64 {
+        self.block_indexes + self.bloom_filter + self.entry_meta + self.source_map + self.metadata
+    }
+}
+
 /// Low-level ZDB dictionary reader.
 ///
 /// This struct provides direct access to ZDB file contents including key indexes,
@@ -55,8 +234,27 @@ pub struct ZdbReader<R: Read + Seek> {
     content_block_index: ContentBlockIndexUnit,
     key_blocks: KeyUnit,
     key_block_indexes: KeyBlockIndexUnit,
+    bloom_filter: Option<BloomFilterUnit>,
+    entry_meta: Option<EntryMetaUnit>,
+    source_map: Option<SourceMapUnit>,
     reader: R,
-    block_cache: LruCache<u64, Rc<ContentBlock>>,
+    /// Companion file holding the content blocks, if they aren't in the dictionary
+    content_file: Option<Box<dyn ReadSeek>>,
+    block_cache: LruCache<u64, Arc<ContentBlock>>,
+    /// Folded headwords sorted with their entry numbers, built on the first folded lookup
+    folded_index: Option<Vec<(String, EntryNo)>>,
+    /// Bytes held by `folded_index`
+    folded_index_memory: u64,
+    /// Bytes held by the content blocks in `block_cache`, kept up to date as blocks are cached and evicted
+    content_cache_memory: u64,
+    /// Bytes held while the reader is open, see [`MemoryFootprint::resident`], updated when
+    /// the key block index is loaded. Only computed if the reader has a memory budget.
+    resident_memory: u64,
+    options: ReaderOptions,
+    /// Incomplete units left out when opened with `allow_partial`
+    unavailable_units: Vec<UnavailableUnit>,
+    /// Time spent opening the file
+    open_report: OpenReport,
 }
 
 impl<R: Read + Seek> ZdbReader<R> {
@@ -76,9 +274,26 @@ impl<R: Read + Seek> ZdbReader<R> {
         device_id: &str,
         license_data: &str,
     ) -> Result<ZdbReader<BufReader<std::fs::File>>> {
-        let file = std::fs::File::open(path)?;
-        let reader = BufReader::new(file);
-        ZdbReader::from_reader(reader, device_id, license_data)
+        ZdbReader::<BufReader<std::fs::File>>::from_file_with_options(path, device_id, license_data, ReaderOptions::default())
+    }
+
+    /// Opens a ZDB file held in memory, e.g. bundled with the application or downloaded as a whole.
+    ///
+    /// The buffer is read in place and never copied as a whole, so `Vec<u8>`, `Arc<[u8]>` and
+    /// `&'static [u8]` buffers all work without duplicating the file. Only the blocks being
+    /// decoded are copied, since they are decrypted and decompressed into buffers of their own.
+    ///
+    /// # Arguments
+    ///
+    /// * `bytes` - The contents of the ZDB file
+    /// * `device_id` - Device identifier for license verification
+    /// * `license_data` - License key data
+    ///
+    /// # Returns
+    ///
+    /// Returns an initialized ZdbReader on success.
+    pub fn from_bytes<B: AsRef<[u8]>>(bytes: B, device_id: &str, license_data: &str) -> Result<ZdbReader<Cursor<B>>> {
+        ZdbReader::from_reader(Cursor::new(bytes), device_id, license_data)
     }
 
     /// Opens a ZDB file from a generic reader.
@@ -93,23 +308,246 @@ impl<R: Read + Seek> ZdbReader<R> {
     ///
     /// Returns an initialized ZdbReader on success.
     pub fn from_reader(reader: R, device_id: &str, license_data: &str) -> Result<ZdbReader<R>> {
+        ZdbReader::from_reader_with_options(reader, device_id, license_data, ReaderOptions::default())
+    }
+
+    /// Opens a ZDB file from a generic reader with options.
+    ///
+    /// # Arguments
+    ///
+    /// * `reader` - A reader for the ZDB file data
+    /// * `device_id` - Device identifier for license verification
+    /// * `license_data` - License key data
+    /// * `options` - Options of the reader, e.g. its memory budget
+    ///
+    /// # Returns
+    ///
+    /// Returns an initialized ZdbReader on success.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `MemoryLimitExceeded` error if the indexes that stay in memory while the
+    /// reader is open exceed `options.max_memory`.
+    pub fn from_reader_with_options(reader: R, device_id: &str, license_data: &str, options: ReaderOptions) -> Result<ZdbReader<R>> {
+        ZdbReader::open_with_options(reader, device_id, license_data, options, None)
+    }
+
+    /// Opens a ZDB file with options.
+    ///
+    /// Like [`from_reader_with_options`](Self::from_reader_with_options), and honors
+    /// `options.parallel_open` by opening the file a second time for the worker thread.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path to the ZDB file
+    /// * `device_id` - Device identifier for license verification
+    /// * `license_data` - License key data
+    /// * `options` - Options of the reader
+    ///
+    /// # Returns
+    ///
+    /// Returns an initialized ZdbReader on success.
+    pub fn from_file_with_options<P: AsRef<Path>>(
+        path: P,
+        device_id: &str,
+        license_data: &str,
+        mut options: ReaderOptions,
+    ) -> Result<ZdbReader<BufReader<std::fs::File>>> {
+        let reader = BufReader::new(std::fs::File::open(path.as_ref())?);
+        if options.content_dir.is_none() {
+            options.content_dir = path.as_ref().parent().map(Path::to_path_buf);
+        }
+        let source_path = options.parallel_open.then_some(path.as_ref());
+        ZdbReader::open_with_options(reader, device_id, license_data, options, source_path)
+    }
+
+    /// Opens a file that may still be written or downloaded, for a preview.
+    ///
+    /// The units needed for lookups come first in a V3 file, the optional units with the
+    /// Bloom filter, entry metadata and source map follow them. An incomplete optional unit
+    /// is left out instead of failing to open, and listed by [`unavailable_units`](Self::unavailable_units)
+    /// with the capability it provides. A unit missing entirely can't be told apart from a
+    /// file built without it, so it isn't listed. Open the file again once it's complete
+    /// to use all units.
+    ///
+    /// # Arguments
+    ///
+    /// * `reader` - A reader for the data received so far
+    /// * `device_id` - Device identifier for license verification
+    /// * `license_data` - License key data
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the header or a unit needed for lookups is incomplete. The header
+    /// alone can be read with [`MetaUnit::from_reader`], e.g. to show the title.
+    pub fn open_partial(reader: R, device_id: &str, license_data: &str) -> Result<ZdbReader<R>> {
+        ZdbReader::from_reader_with_options(reader, device_id, license_data, ReaderOptions { allow_partial: true, ..Default::default() })
+    }
+
+    /// Optional units left out because they were incomplete when the file was opened,
+    /// empty unless opened with [`open_partial`](Self::open_partial).
+    pub fn unavailable_units(&self) -> &[UnavailableUnit] {
+        &self.unavailable_units
+    }
+
+    /// Opens a ZDB file, `source_path` is the path of the file if it can be opened again.
+    pub(crate) fn open_with_options(reader: R, device_id: &str, license_data: &str, options: ReaderOptions, source_path: Option<&Path>) -> Result<ZdbReader<R>> {
+        let start = Instant::now();
+        // With a memory budget the key block index is decoded only after its declared size is checked
+        let lazy_key_index = options.lazy_key_index || options.max_memory.is_some();
+        let mut zdb = ZdbReader::open(reader, device_id, license_data, lazy_key_index, options.allow_partial, source_path)?;
+        if !zdb.meta.db_info.content_file.is_empty() {
+            let content_file_start = Instant::now();
+            zdb.open_content_file(options.content_dir.as_deref())?;
+            zdb.open_report.content_file = content_file_start.elapsed();
+        }
+        if let Some(limit) = options.max_memory {
+            let resident = zdb.memory_footprint().resident();
+            if resident > limit {
+                return Err(ZdbError::memory_limit_exceeded(resident, limit));
+            }
+            zdb.resident_memory = resident;
+            zdb.options.max_memory = options.max_memory;
+            if !options.lazy_key_index {
+                zdb.load_key_block_indexes()?;
+            }
+        }
+        let key_order_start = Instant::now();
+        match zdb.check_key_order(options.key_order_check) {
+            Err(e @ ZdbError::KeyOrderMismatch { .. }) if !options.fail_on_key_order_mismatch => {
+                log::warn!("{} (locale \"{}\")", e, zdb.meta.db_info.locale_id);
+            }
+            result => result?,
+        }
+        zdb.open_report.key_order_check = key_order_start.elapsed();
+        zdb.options = options;
+        zdb.open_report.total = start.elapsed();
+        Ok(zdb)
+    }
+
+    /// Time spent opening the file, by step, see [`OpenReport`].
+    ///
+    /// Only the steps of this reader are timed, [`MdxReader::open_report`](crate::readers::MdxReader::open_report)
+    /// adds the MDD file(s) and the full-text search index.
+    pub fn open_report(&self) -> &OpenReport {
+        &self.open_report
+    }
+
+    /// Opens the companion file named in the header, see [`ReaderOptions::content_dir`].
+    fn open_content_file(&mut self, content_dir: Option<&Path>) -> Result<()> {
+        let file_name = &self.meta.db_info.content_file;
+        // The name comes from the file, it must not point outside the directory
+        if Path::new(file_name).file_name().is_none_or(|name| name != file_name.as_str()) {
+            return Err(ZdbError::invalid_data_format(format!("Invalid companion content file name \"{}\"", file_name)));
+        }
+        let content_dir = content_dir.ok_or_else(|| ZdbError::invalid_parameter(format!(
+            "The content of the dictionary is in the companion file \"{}\", set ReaderOptions::content_dir to its directory", file_name)))?;
+        let mut content_file = BufReader::new(std::fs::File::open(content_dir.join(file_name))?);
+        let data_section_length = self.content_block_index.block_index_entries.iter().map(|index| index.block_compressed_length).sum();
+        self.content.attach_content_file(&mut content_file, data_section_length)?;
+        self.content_file = Some(Box::new(content_file));
+        Ok(())
+    }
+
+    /// Verifies that adjacent keys are in the order of the collator of the header locale.
+    ///
+    /// Keys are compared like lookups compare them, normalized as recorded in the header.
+    /// Only V3 files are checked, older versions are searched by their own sort keys.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `KeyOrderMismatch` error for the first key found to sort before its predecessor.
+    pub fn check_key_order(&mut self, check: KeyOrderCheck) -> crate::Result<()> {
+        if check == KeyOrderCheck::Off || !self.meta.is_v3() || self.get_entry_count() == 0 {
+            return Ok(());
+        }
+        let block_count = if check == KeyOrderCheck::Full {
+            0
+        } else {
+            self.load_key_block_indexes()?;
+            let block_indexes = &self.key_block_indexes.block_indexes;
+            for (block, next_block) in block_indexes.iter().zip(block_indexes.iter().skip(1)) {
+                self.check_key_pair(&block.last_key, &next_block.first_key, next_block.first_entry_no_in_block as u64)?;
+            }
+            block_indexes.len()
+        };
+        let ranges: Vec<(u64, u64)> = if check == KeyOrderCheck::Full {
+            vec![(0, self.get_entry_count())]
+        } else {
+            // Blocks spread evenly over the file, including the first and the last
+            let sample_count = KEY_ORDER_SAMPLE_BLOCKS.min(block_count);
+            let mut blocks: Vec<usize> = (0..sample_count).map(|n| n * (block_count - 1) / (sample_count - 1).max(1)).collect();
+            blocks.dedup();
+            blocks.iter().map(|&n| {
+                let block = &self.key_block_indexes.block_indexes[n];
+                (block.first_entry_no_in_block as u64, block.first_entry_no_in_block as u64 + block.entry_count_in_block)
+            }).collect()
+        };
+        for (start, end) in ranges {
+            let mut previous = self.get_index(start as EntryNo)?.key;
+            for entry_no in start + 1..end {
+                let key = self.get_index(entry_no as EntryNo)?.key;
+                self.check_key_pair(&previous, &key, entry_no)?;
+                previous = key;
+            }
+        }
+        Ok(())
+    }
+
+    fn check_key_pair(&self, previous: &str, key: &str, entry_no: u64) -> crate::Result<()> {
+        if locale_compare(previous, key, false, &self.meta)? == Ordering::Greater {
+            return Err(ZdbError::key_order_mismatch(entry_no, previous, key));
+        }
+        Ok(())
+    }
+
+    fn open(reader: R, device_id: &str, license_data: &str, lazy_key_index: bool, partial: bool, source_path: Option<&Path>) -> Result<ZdbReader<R>> {
         let mut reader = reader;
+        let header_start = Instant::now();
         // First create a temporary MetaUnit with content_data_total_length = 0
-        let temp_meta = MetaUnit::from_reader(&mut reader, device_id, license_data, 0)?;
-        if temp_meta.is_v3(){
-            return ZdbReader::from_reader_v3(reader, temp_meta);
+        let (temp_meta, collator) = MetaUnit::from_reader_timed(&mut reader, device_id, license_data, 0)?;
+        let header = header_start.elapsed().saturating_sub(collator);
+        let has_license = !license_data.trim().is_empty() || !temp_meta.db_info.embedded_reg_code.trim().is_empty();
+        let mut zdb = if temp_meta.is_v3(){
+            ZdbReader::load_v3(reader, temp_meta, has_license, lazy_key_index, partial, source_path)?
         }else{
-            return ZdbReader::from_reader_v1_v2(reader, temp_meta);
-        }
+            ZdbReader::load_v1_v2(reader, temp_meta, has_license)?
+        };
+        zdb.open_report.header = header;
+        zdb.open_report.collator = collator;
+        Ok(zdb)
     }
 
     /// Loads ZDB file from V1/V2 format.
-    pub fn from_reader_v1_v2(mut reader: R, meta: MetaUnit) -> Result<ZdbReader<R>> {
+    pub fn from_reader_v1_v2(reader: R, meta: MetaUnit) -> Result<ZdbReader<R>> {
+        ZdbReader::load_v1_v2(reader, meta, false)
+    }
+
+    /// Reports a checksum mismatch of the first encrypted unit as a license for another device
+    /// if the file is opened with license data, since such a license decrypts to a wrong key.
+    /// Mismatches in later units are corruption and are returned as is.
+    fn check_license_key<T>(result: Result<T>, has_license: bool) -> Result<T> {
+        match result {
+            Err(ZdbError::CrcMismatch { .. }) if has_license => Err(ZdbError::license_error(
+                LicenseErrorKind::WrongDevice,
+                "Failed to decrypt the dictionary with the license data, it may be issued for another device",
+            )),
+            result => result,
+        }
+    }
+
+    fn load_v1_v2(mut reader: R, meta: MetaUnit, has_license: bool) -> Result<ZdbReader<R>> {
         let rc_meta = Rc::new(meta);
-        let key_block_indexes =  KeyBlockIndexUnit::from_reader_v1_v2(&mut reader, &rc_meta)?;
-        let key_blocks = KeyUnit::from_reader_v1_v2(&mut reader, &rc_meta, &key_block_indexes)?;
-        let content_block_indexes = ContentBlockIndexUnit::from_reader_v1_v2(&mut reader, &rc_meta)?;
-        let content = ContentUnit::from_reader_v1_v2(&mut reader, &rc_meta, &content_block_indexes)?;
+        let mut units = Vec::new();
+        // The key block index is the first encrypted unit
+        let key_block_indexes = Self::check_license_key(OpenReport::time_unit(&mut units, UnitType::KeyBlockIndex,
+            || KeyBlockIndexUnit::from_reader_v1_v2(&mut reader, &rc_meta)), has_license)?;
+        let key_blocks = OpenReport::time_unit(&mut units, UnitType::Key,
+            || KeyUnit::from_reader_v1_v2(&mut reader, &rc_meta, &key_block_indexes))?;
+        let content_block_indexes = OpenReport::time_unit(&mut units, UnitType::ContentBlockIndex,
+            || ContentBlockIndexUnit::from_reader_v1_v2(&mut reader, &rc_meta))?;
+        let content = OpenReport::time_unit(&mut units, UnitType::Content,
+            || ContentUnit::from_reader_v1_v2(&mut reader, &rc_meta, &content_block_indexes))?;
 
         // Create a new MetaUnit with the correct content_data_total_length
         let mut updated_meta = (*rc_meta).clone();
@@ -122,30 +560,122 @@ impl<R: Read + Seek> ZdbReader<R> {
             content_block_index: content_block_indexes,
             key_blocks,
             key_block_indexes,
+            bloom_filter: None,
+            entry_meta: None,
+            source_map: None,
             reader,
+            content_file: None,
             block_cache: LruCache::new(NonZeroUsize::new(10).unwrap()),
+            folded_index: None,
+            folded_index_memory: 0,
+            content_cache_memory: 0,
+            resident_memory: 0,
+            options: ReaderOptions::default(),
+            unavailable_units: Vec::new(),
+            open_report: OpenReport { units, ..Default::default() },
         })
     }
 
     /// Loads ZDB file from V3 format.
-    pub fn from_reader_v3(mut reader: R, meta: MetaUnit) -> Result<ZdbReader<R>> {
+    pub fn from_reader_v3(reader: R, meta: MetaUnit) -> Result<ZdbReader<R>> {
+        ZdbReader::load_v3(reader, meta, false, false, false, None)
+    }
+
+    /// Loads a V3 file, decoding the key block index on another thread that opens `source_path`
+    /// if it is given and the index isn't loaded lazily. If `partial` is set, incomplete
+    /// optional units are left out. `has_license` is set if the file is opened with license data,
+    /// see [`check_license_key`](Self::check_license_key).
+    fn load_v3(mut reader: R, meta: MetaUnit, has_license: bool, lazy_key_index: bool, partial: bool, source_path: Option<&Path>) -> Result<ZdbReader<R>> {
         let rc_meta = Rc::new(meta);
-        let content = ContentUnit::from_reader_v3(&mut reader, &rc_meta)?;
-        let content_block_index = ContentBlockIndexUnit::from_reader_v3(&mut reader, &rc_meta, content.block_count)?;
-        
-        // Create a new MetaUnit with the correct content_data_total_length
-        let mut updated_meta = (*rc_meta).clone();
-        updated_meta.content_data_total_length = content_block_index.total_original_data_length;
-        let rc_meta = Rc::new(updated_meta);
+        let mut units = Vec::new();
+        // The data info of the content unit is the first encrypted data
+        let content = Self::check_license_key(
+            OpenReport::time_unit(&mut units, UnitType::Content, || ContentUnit::from_reader_v3(&mut reader, &rc_meta)), has_license)?;
+        std::thread::scope(|scope| {
+            let key_block_index_worker = match source_path.filter(|_| !lazy_key_index) {
+                Some(path) => {
+                    // The content block index and key units come first, only their headers are read
+                    let content_block_index_pos = reader.stream_position()?;
+                    skip_unit_v3(&mut reader, &rc_meta)?;
+                    skip_unit_v3(&mut reader, &rc_meta)?;
+                    let key_block_index_pos = reader.stream_position()?;
+                    reader.seek(SeekFrom::Start(content_block_index_pos))?;
+                    let meta = (*rc_meta).clone();
+                    Some(scope.spawn(move || -> Result<_> {
+                        let start = Instant::now();
+                        let mut worker_reader = BufReader::new(std::fs::File::open(path)?);
+                        worker_reader.seek(SeekFrom::Start(key_block_index_pos))?;
+                        let decoded = KeyBlockIndexUnit::read_block_indexes_v3(&mut worker_reader, &meta)?;
+                        Ok((decoded, worker_reader.stream_position()?, start.elapsed()))
+                    }))
+                }
+                None => None,
+            };
+            let content_block_index = OpenReport::time_unit(&mut units, UnitType::ContentBlockIndex,
+                || ContentBlockIndexUnit::from_reader_v3(&mut reader, &rc_meta, content.block_count))?;
 
-        let entry_keys = KeyUnit::from_reader_v3(&mut reader, &rc_meta)?;
-        let key_block_index = KeyBlockIndexUnit::from_reader_v3(&mut reader, &rc_meta)?;
+            // Create a new MetaUnit with the correct content_data_total_length
+            let mut updated_meta = (*rc_meta).clone();
+            updated_meta.content_data_total_length = content_block_index.total_original_data_length;
+            let rc_meta = Rc::new(updated_meta);
+
+            let entry_keys = OpenReport::time_unit(&mut units, UnitType::Key, || KeyUnit::from_reader_v3(&mut reader, &rc_meta))?;
+            let key_block_index = if let Some(worker) = key_block_index_worker {
+                let ((block_indexes, total_key_count), end_of_unit, duration) = worker.join().unwrap_or_else(|e| std::panic::resume_unwind(e))?;
+                units.push(UnitTiming { unit_type: UnitType::KeyBlockIndex, duration });
+                reader.seek(SeekFrom::Start(end_of_unit))?;
+                KeyBlockIndexUnit::from_block_indexes(block_indexes, &rc_meta, total_key_count)
+            } else if lazy_key_index {
+                OpenReport::time_unit(&mut units, UnitType::KeyBlockIndex,
+                    || KeyBlockIndexUnit::from_reader_v3_lazy(&mut reader, &rc_meta, content.total_record_count))?
+            } else {
+                OpenReport::time_unit(&mut units, UnitType::KeyBlockIndex, || KeyBlockIndexUnit::from_reader_v3(&mut reader, &rc_meta))?
+            };
+            let mut zdb = ZdbReader::finish_load_v3(reader, rc_meta, content, content_block_index, entry_keys, key_block_index, partial)?;
+            // The optional units are read last
+            units.append(&mut zdb.open_report.units);
+            zdb.open_report.units = units;
+            Ok(zdb)
+        })
+    }
+
+    fn finish_load_v3(
+        mut reader: R,
+        rc_meta: Rc<MetaUnit>,
+        content: ContentUnit,
+        content_block_index: ContentBlockIndexUnit,
+        entry_keys: KeyUnit,
+        key_block_index: KeyBlockIndexUnit,
+        partial: bool,
+    ) -> Result<ZdbReader<R>> {
+        let mut unavailable_units = Vec::new();
+        let mut units = Vec::new();
+        let bloom_filter = Self::read_optional_unit(&mut reader, partial, UnitType::BloomFilter, &mut unavailable_units, &mut units,
+            |reader| BloomFilterUnit::try_from_reader_v3(reader, &rc_meta))?;
+        let bloom_filter = bloom_filter.filter(|bloom_filter| {
+            if !bloom_filter.matches_backend() {
+                log::warn!("Ignoring the Bloom filter built with collation backend \"{}\", sort keys of {} differ", bloom_filter.sort_key_backend, crate::utils::icu_wrapper::BACKEND);
+            }
+            bloom_filter.matches_backend()
+        });
+        let entry_meta = Self::read_optional_unit(&mut reader, partial, UnitType::EntryMeta, &mut unavailable_units, &mut units,
+            |reader| EntryMetaUnit::try_from_reader_v3(reader, &rc_meta))?;
+        let source_map = Self::read_optional_unit(&mut reader, partial, UnitType::SourceMap, &mut unavailable_units, &mut units,
+            |reader| SourceMapUnit::try_from_reader_v3(reader, &rc_meta))?;
 
         if content.total_record_count != key_block_index.total_key_count
             || entry_keys.total_key_count != content.total_record_count
         {
             return Err(ZdbError::invalid_data_format("Record count mismatch"));
         }
+        if let Some(entry_meta) = &entry_meta
+            && entry_meta.entry_count != content.total_record_count {
+            return Err(ZdbError::invalid_data_format("Entry metadata count mismatch"));
+        }
+        if let Some(source_map) = &source_map
+            && source_map.entry_count != content.total_record_count {
+            return Err(ZdbError::invalid_data_format("Source map count mismatch"));
+        }
 
         Ok(ZdbReader {
             meta: rc_meta,
@@ -153,11 +683,58 @@ impl<R: Read + Seek> ZdbReader<R> {
             content_block_index,
             key_blocks: entry_keys,
             key_block_indexes: key_block_index,
+            bloom_filter,
+            entry_meta,
+            source_map,
             reader,
+            content_file: None,
             block_cache: LruCache::new(NonZeroUsize::new(10).unwrap()),
+            folded_index: None,
+            folded_index_memory: 0,
+            content_cache_memory: 0,
+            resident_memory: 0,
+            options: ReaderOptions::default(),
+            unavailable_units,
+            open_report: OpenReport { units, ..Default::default() },
         })
     }
 
+    /// Reads an optional unit if the next unit is one, see [`open_partial`](Self::open_partial).
+    ///
+    /// If `partial` is set, a unit that fails to read is recorded in `unavailable_units` and
+    /// the position is left at its start, where the readers of the following units find
+    /// no unit of their type. The time spent reading a unit found is recorded in `units`.
+    fn read_optional_unit<T, F: FnOnce(&mut R) -> Result<Option<T>>>(
+        reader: &mut R,
+        partial: bool,
+        unit_type: UnitType,
+        unavailable_units: &mut Vec<UnavailableUnit>,
+        units: &mut Vec<UnitTiming>,
+        read: F,
+    ) -> Result<Option<T>> {
+        let unit_pos = reader.stream_position()?;
+        let start = Instant::now();
+        match read(reader) {
+            Ok(Some(unit)) => {
+                units.push(UnitTiming { unit_type, duration: start.elapsed() });
+                Ok(Some(unit))
+            }
+            Err(e) if partial => {
+                log::warn!("Incomplete {:?} unit left out: {}", unit_type, e);
+                reader.seek(SeekFrom::Start(unit_pos))?;
+                let capability = match unit_type {
+                    UnitType::BloomFilter => "Bloom filter",
+                    UnitType::EntryMeta => "entry metadata",
+                    UnitType::SourceMap => "source locations",
+                    _ => "",
+                };
+                unavailable_units.push(UnavailableUnit { unit_type, capability, reason: e.to_string() });
+                Ok(None)
+            }
+            result => result,
+        }
+    }
+
     pub fn get_entry_count(&self) -> u64 {
         self.content.total_record_count
     }
@@ -169,6 +746,7 @@ impl<R: Read + Seek> ZdbReader<R> {
         partial_match: bool,
         best_match: bool,
     ) -> crate::Result<Option<KeyIndex>> {
+        self.load_key_block_indexes()?;
         let key_block_index =
             self.key_block_indexes
                 .find_index(key, prefix_match, partial_match)?;
@@ -179,17 +757,11 @@ impl<R: Read + Seek> ZdbReader<R> {
                 prefix_match,
                 partial_match,
             )?;
+            self.enforce_memory_limit();
             if let Some(key_index) = key_index {
                 if best_match && key_index.key!=key{
-                    let sort_key = get_sort_key(key.as_bytes(), &self.meta)?;
-                    for i in key_index.entry_no+1..self.get_entry_count() as EntryNo{
-                        let index = self.get_index(i)?;
-                        if key==index.key{ //If this index is the same as the key, return it
-                            return Ok(Some(index));
-                        }else if index.compare_with(&key, &sort_key, false, &self.meta)? != Ordering::Equal {
-                            break;
-                        }
-                    }
+                    let sort_key = get_sort_key(&encode_string_to_bytes(key, self.meta.encoding_obj)?, &self.meta)?;
+                    return Ok(Some(self.find_exact_in_run(key, &sort_key, key_index)?));
                 }
                 return Ok(Some(key_index));
             }
@@ -197,30 +769,302 @@ impl<R: Read + Seek> ZdbReader<R> {
         return Ok(None);
     }
 
+    /// Finds the number of the entry with key `key`, like [`find_first_match`](Self::find_first_match)
+    /// with exact matching and `best_match` set, without building its [`KeyIndex`].
+    ///
+    /// Meant for callers needing only the entry number, e.g. to resolve `entryx` links or
+    /// to cross-check FTS hits. Keys the Bloom filter rules out aren't searched, and the
+    /// entries comparing equal to `key` are scanned in place for the exact key.
+    ///
+    /// # Returns
+    ///
+    /// Returns the entry number, `None` if no entry matches.
+    pub fn get_entry_no_by_key(&mut self, key: &str) -> crate::Result<Option<EntryNo>> {
+        if key.is_empty() || !self.may_contain(key)? {
+            return Ok(None);
+        }
+        self.load_key_block_indexes()?;
+        let Some(block_pos) = self.key_block_indexes.find_position(key, false, false)? else {
+            return Ok(None);
+        };
+        let key_block = self.key_blocks.get_key_block(&mut self.reader, &self.key_block_indexes.block_indexes[block_pos])?;
+        let sort_key = get_sort_key(&encode_string_to_bytes(key, self.meta.encoding_obj)?, &self.meta)?;
+        let (first_entry_no, exact_entry_no) = {
+            let key_block = key_block.borrow();
+            let Some(entry_pos) = key_block.find_position(key, false, false)? else {
+                self.enforce_memory_limit();
+                return Ok(None);
+            };
+            let run = &key_block.key_indexes[entry_pos..];
+            let mut exact_entry_no = None;
+            let mut run_ends_in_block = false;
+            for index in run {
+                if index.key == key {
+                    exact_entry_no = Some(index.entry_no);
+                    break;
+                }
+                if index.entry_no != run[0].entry_no && index.compare_with(key, &sort_key, false, &self.meta)? != Ordering::Equal {
+                    run_ends_in_block = true;
+                    break;
+                }
+            }
+            (run[0].entry_no, exact_entry_no.or(run_ends_in_block.then_some(run[0].entry_no)))
+        };
+        self.enforce_memory_limit();
+        match exact_entry_no {
+            Some(entry_no) => Ok(Some(entry_no)),
+            // The run goes on in the next block
+            None => {
+                let first = self.get_index(first_entry_no)?;
+                Ok(Some(self.find_exact_in_run(key, &sort_key, first)?.entry_no))
+            }
+        }
+    }
+
+    /// Scans the run of entries comparing equal to `key` from `first` for one with exactly the same key.
+    ///
+    /// Returns `first` if there is none.
+    fn find_exact_in_run(&mut self, key: &str, sort_key: &[u8], first: KeyIndex) -> crate::Result<KeyIndex> {
+        for i in first.entry_no+1..self.get_entry_count() as EntryNo{
+            let index = self.get_index(i)?;
+            if key==index.key{ //If this index is the same as the key, return it
+                return Ok(index);
+            }else if index.compare_with(key, sort_key, false, &self.meta)? != Ordering::Equal {
+                break;
+            }
+        }
+        Ok(first)
+    }
+
+    /// Looks up many keys at once, like [`find_first_match`](Self::find_first_match) with
+    /// exact matching and `best_match` set.
+    ///
+    /// The keys are sorted in collation order and matched in a single forward pass over the
+    /// key blocks, galloping from the previous match instead of searching from scratch, so
+    /// every key block is decoded at most once.
+    ///
+    /// # Arguments
+    ///
+    /// * `keys` - The keys to look up, in any order
+    ///
+    /// # Returns
+    ///
+    /// Returns the match of every key in the order of `keys`, `None` for keys not found.
+    pub fn lookup_many(&mut self, keys: &[&str]) -> crate::Result<Vec<Option<KeyIndex>>> {
+        self.load_key_block_indexes()?;
+        let meta = self.meta.clone();
+        let sort_keys = keys.iter()
+            .map(|key| if meta.is_v3() { Ok(Vec::new()) } else { get_sort_key(&encode_string_to_bytes(key, meta.encoding_obj)?, &meta) })
+            .collect::<crate::Result<Vec<Vec<u8>>>>()?;
+        let mut order: Vec<usize> = (0..keys.len()).filter(|&i| !keys[i].is_empty()).collect();
+        let mut sort_error = None;
+        order.sort_by(|&a, &b| key_compare(keys[a], &sort_keys[a], keys[b], &sort_keys[b], false, &meta).unwrap_or_else(|e| {
+            sort_error.get_or_insert(e);
+            Ordering::Equal
+        }));
+        if let Some(e) = sort_error {
+            return Err(e);
+        }
+
+        let mut results = vec![None; keys.len()];
+        let mut block_pos = 0;
+        let mut entry_pos = 0;
+        for i in order {
+            let (key, sort_key) = (keys[i], &sort_keys[i]);
+            let blocks = &self.key_block_indexes.block_indexes;
+            let next_block_pos = gallop(blocks, block_pos, |block| Ok(block.compare_with(key, sort_key, false, &meta)? == Ordering::Less))?;
+            if next_block_pos != block_pos {
+                entry_pos = 0;
+            }
+            block_pos = next_block_pos;
+            if block_pos == blocks.len() {
+                break;
+            }
+            let key_block = self.key_blocks.get_key_block(&mut self.reader, &blocks[block_pos])?;
+            let key_index = {
+                let key_block = key_block.borrow();
+                entry_pos = gallop(&key_block.key_indexes, entry_pos, |index| Ok(index.compare_with(key, sort_key, false, &meta)? == Ordering::Less))?;
+                match key_block.key_indexes.get(entry_pos) {
+                    Some(index) if index.compare_with(key, sort_key, false, &meta)? == Ordering::Equal => Some(index.clone()),
+                    _ => None,
+                }
+            };
+            results[i] = match key_index {
+                Some(key_index) if key_index.key != key => {
+                    let sort_key = if meta.is_v3() { get_sort_key(&encode_string_to_bytes(key, meta.encoding_obj)?, &meta)? } else { sort_key.clone() };
+                    Some(self.find_exact_in_run(key, &sort_key, key_index)?)
+                }
+                key_index => key_index,
+            };
+        }
+        self.enforce_memory_limit();
+        Ok(results)
+    }
+
+    /// Checks whether the dictionary may contain a key, using the Bloom filter of the file.
+    ///
+    /// A `false` result means an exact lookup of the key is certain to fail, so it can be skipped.
+    /// Files built without a Bloom filter always return `true`, as do files whose filter was
+    /// built with another collation backend, since their sort keys differ.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the key can't be encoded in the dictionary encoding.
+    pub fn may_contain(&self, key: &str) -> crate::Result<bool> {
+        match &self.bloom_filter {
+            Some(bloom_filter) => {
+                let sort_key = get_sort_key(&encode_string_to_bytes(key, self.meta.encoding_obj)?, &self.meta)?;
+                Ok(bloom_filter.filter.may_contain(&sort_key))
+            }
+            None => Ok(true),
+        }
+    }
+
+    /// Returns whether the file has a Bloom filter usable by [`may_contain`](Self::may_contain).
+    pub fn has_bloom_filter(&self) -> bool {
+        self.bloom_filter.is_some()
+    }
+
+    /// Gets the tags, frequency rank and part of speech of an entry.
+    ///
+    /// # Returns
+    ///
+    /// `None` if the file has no entry metadata, see
+    /// [`BuilderConfig::entry_meta_path`](crate::builder::BuilderConfig::entry_meta_path).
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if there is no entry with this number.
+    pub fn get_entry_meta_ext(&self, entry_no: EntryNo) -> crate::Result<Option<EntryMetaExt>> {
+        self.entry_meta.as_ref().map(|entry_meta| entry_meta.get(entry_no)).transpose()
+    }
+
+    /// Returns whether the file has entry metadata for [`get_entry_meta_ext`](Self::get_entry_meta_ext).
+    pub fn has_entry_meta(&self) -> bool {
+        self.entry_meta.is_some()
+    }
+
+    /// Names of the tags of [`EntryMetaExt::tags`], bit `n` is the `n`th name. Empty if the
+    /// file has no entry metadata.
+    pub fn entry_meta_tag_names(&self) -> &[String] {
+        self.entry_meta.as_ref().map(|entry_meta| entry_meta.tag_names.as_slice()).unwrap_or_default()
+    }
+
+    /// Gets the line and offset of an entry in the source file of the dictionary.
+    ///
+    /// # Returns
+    ///
+    /// `None` if the file has no source map, see
+    /// [`BuilderConfig::source_map`](crate::builder::BuilderConfig::source_map), or the
+    /// entry didn't come from a text source.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if there is no entry with this number.
+    pub fn get_source_location(&self, entry_no: EntryNo) -> crate::Result<Option<SourceLocation>> {
+        match &self.source_map {
+            Some(source_map) => source_map.get(entry_no),
+            None => Ok(None),
+        }
+    }
+
+    /// Gets the entries after `key_index` with the same key, or starting with it if
+    /// `start_with` is set, see [`get_similar_indexes_in_direction`](Self::get_similar_indexes_in_direction).
     pub fn get_similar_indexes(
         &mut self,
         key_index: &KeyIndex,
         start_with: bool,
         max_count: u64,
+    ) -> crate::Result<LinkedList<KeyIndex>> {
+        self.get_similar_indexes_in_direction(key_index, start_with, max_count, SearchDirection::Forward)
+    }
+
+    /// Gets the adjacent entries with the same key as `key_index`, walking in one direction.
+    ///
+    /// # Arguments
+    ///
+    /// * `key_index` - The anchor entry, included in the result
+    /// * `start_with` - Also match entries whose key starts with the key of the anchor
+    /// * `max_count` - Maximum number of entries, including the anchor
+    /// * `direction` - Whether to walk towards the last or the first entry
+    ///
+    /// # Returns
+    ///
+    /// The matching entries in entry order: the anchor comes first when walking forward
+    /// and last when walking backward.
+    pub fn get_similar_indexes_in_direction(
+        &mut self,
+        key_index: &KeyIndex,
+        start_with: bool,
+        max_count: u64,
+        direction: SearchDirection,
     ) -> crate::Result<LinkedList<KeyIndex>> {
         let mut key_indexes = LinkedList::new();
         key_indexes.push_back(key_index.clone());
-        let max_count = min(max_count, self.get_entry_count() - key_index.entry_no as u64);
-        let search_sort_key = get_sort_key(key_index.key.as_bytes(), &self.meta)?;
+        let available = match direction {
+            SearchDirection::Forward => self.get_entry_count().saturating_sub(key_index.entry_no as u64),
+            SearchDirection::Backward => key_index.entry_no as u64 + 1,
+        };
+        let max_count = min(max_count, available);
+        let search_sort_key = get_sort_key(&encode_string_to_bytes(&key_index.key, self.meta.encoding_obj)?, &self.meta)?;
         for i in 1..max_count {
-            let index = self.get_index(key_index.entry_no + i as EntryNo)?;
-            if index.compare_with(&key_index.key, &search_sort_key, start_with, &self.meta)? == Ordering::Equal {
-                key_indexes.push_back(index);
-            } else {
+            let entry_no = match direction {
+                SearchDirection::Forward => key_index.entry_no + i as EntryNo,
+                SearchDirection::Backward => key_index.entry_no - i as EntryNo,
+            };
+            let index = self.get_index(entry_no)?;
+            if index.compare_with(&key_index.key, &search_sort_key, start_with, &self.meta)? != Ordering::Equal {
                 break;
             }
+            match direction {
+                SearchDirection::Forward => key_indexes.push_back(index),
+                SearchDirection::Backward => key_indexes.push_front(index),
+            }
         }
         Ok(key_indexes)
     }
 
+    /// Gets the entries on both sides of `key_index` with the same key, e.g. all homographs
+    /// of a word when the anchor isn't the first of them.
+    ///
+    /// Pages through a long run of matches by anchoring the next call at the first or
+    /// last returned entry.
+    ///
+    /// # Arguments
+    ///
+    /// * `key_index` - The anchor entry, included in the result
+    /// * `start_with` - Also match entries whose key starts with the key of the anchor
+    /// * `max_before` - Maximum number of entries before the anchor
+    /// * `max_after` - Maximum number of entries after the anchor
+    ///
+    /// # Returns
+    ///
+    /// The matching entries in entry order.
+    pub fn get_similar_indexes_around(
+        &mut self,
+        key_index: &KeyIndex,
+        start_with: bool,
+        max_before: u64,
+        max_after: u64,
+    ) -> crate::Result<LinkedList<KeyIndex>> {
+        let mut key_indexes = self.get_similar_indexes_in_direction(key_index, start_with, max_before.saturating_add(1), SearchDirection::Backward)?;
+        let mut after = self.get_similar_indexes_in_direction(key_index, start_with, max_after.saturating_add(1), SearchDirection::Forward)?;
+        after.pop_front();
+        key_indexes.append(&mut after);
+        Ok(key_indexes)
+    }
+
     pub fn get_content_length(&mut self, entry_no: EntryNo) -> crate::Result<u64> {
+        // Files whose content isn't in key order store the lengths
+        if self.key_blocks.minor_version == KEY_UNIT_CONTENT_LENGTHS {
+            self.load_key_block_indexes()?;
+            let key_block_index = self.key_block_indexes.get_index(entry_no)?;
+            let key_block = self.key_blocks.get_key_block(&mut self.reader, key_block_index)?;
+            return key_block.borrow().get_content_length(entry_no)
+                .ok_or_else(|| ZdbError::invalid_parameter("entry_no is out of range"));
+        }
         let offset1 = self.get_index(entry_no)?.content_offset_in_source;
-        let offset2 = if entry_no < self.key_block_indexes.total_key_count as EntryNo - 1 {
+        let offset2 = if (entry_no as u64) + 1 < self.key_block_indexes.total_key_count {
             self.get_index(entry_no + 1)?.content_offset_in_source
         } else {
             self.meta.content_data_total_length
@@ -228,28 +1072,74 @@ impl<R: Read + Seek> ZdbReader<R> {
         Ok(offset2 - offset1)
     }
 
-    pub fn get_content_block(&mut self, key_index: &KeyIndex) -> crate::Result<Rc<ContentBlock>> {
+    pub fn get_content_block(&mut self, key_index: &KeyIndex) -> crate::Result<Arc<ContentBlock>> {
+        self.get_content_block_for_range(key_index, None)
+    }
+
+    /// Gets the block containing an entry, decoded at least up to the end of the entry.
+    ///
+    /// If `content_length` is given and the entry ends in the first quarter of the block,
+    /// only the start of the block is decoded. A later request beyond the decoded part
+    /// decodes the whole block, so reading a block entry by entry decodes it at most 1.25 times.
+    ///
+    /// Only encrypted blocks are decoded partially. The checksum of an unencrypted block
+    /// covers its uncompressed data, so the whole block is decoded to verify it.
+    fn get_content_block_for_range(&mut self, key_index: &KeyIndex, content_length: Option<u64>) -> crate::Result<Arc<ContentBlock>> {
         let content_block_index = self
             .content_block_index
             .get_index(key_index.content_offset_in_source)?;
-        let content_block = if let Some(block) = self
-            .block_cache
-            .peek(&content_block_index.block_offset_in_unit)
-        {
-            Rc::clone(&block)
-        } else {
-            // 读取数据块
-            let block = Rc::new(self.content.get_content_block(
-                &mut self.reader,
-                &content_block_index,
-            )?);
-            self.block_cache
-                .put(content_block_index.block_offset_in_unit, block.clone());
-            block
+        let cached = self.block_cache.peek(&content_block_index.block_offset_in_unit);
+        let content_block = match (cached, content_length) {
+            (Some(block), Some(length)) if block.contains(key_index.content_offset_in_source, length) => Arc::clone(block),
+            (Some(block), None) if block.is_complete() => Arc::clone(block),
+            (cached, _) => {
+                let shared_key = (self.shared_block_cache_id(), content_block_index.block_offset_in_unit);
+                let shared = self.options.shared_block_cache.as_ref()
+                    .and_then(|cache| cache.get(&shared_key))
+                    .filter(|block| block.block_index.block_offset_in_source == content_block_index.block_offset_in_source
+                        && block.block_index.block_original_length == content_block_index.block_original_length);
+                if let Some(block) = shared {
+                    self.cache_content_block(content_block_index.block_offset_in_unit, block.clone());
+                    return Ok(block);
+                }
+                // 读取数据块
+                let prefix_length = match content_length {
+                    Some(length) if cached.is_none() => {
+                        let end_in_block = key_index.content_offset_in_source + length - content_block_index.block_offset_in_source;
+                        if end_in_block <= content_block_index.block_original_length / 4 { end_in_block } else { u64::MAX }
+                    }
+                    _ => u64::MAX,
+                };
+                let block = match &mut self.content_file {
+                    Some(content_file) => self.content.get_content_block_prefix(content_file, &content_block_index, prefix_length)?,
+                    None => self.content.get_content_block_prefix(&mut self.reader, &content_block_index, prefix_length)?,
+                };
+                let block = Arc::new(block);
+                if let Some(cache) = &self.options.shared_block_cache && block.is_complete() {
+                    cache.put(shared_key, block.clone());
+                }
+                self.cache_content_block(content_block_index.block_offset_in_unit, block.clone());
+                block
+            }
         };
         Ok(content_block)
     }
 
+    fn cache_content_block(&mut self, block_offset_in_unit: u64, block: Arc<ContentBlock>) {
+        self.content_cache_memory += block.memory_size() as u64;
+        if let Some((_, evicted)) = self.block_cache.push(block_offset_in_unit, block) {
+            self.content_cache_memory -= evicted.memory_size() as u64;
+        }
+        self.enforce_memory_limit();
+    }
+
+    /// Identifies the dictionary in a [`SharedBlockCache`].
+    fn shared_block_cache_id(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        self.meta.db_info.uuid.hash(&mut hasher);
+        hasher.finish()
+    }
+
     fn resolve_link_target_with_visited(&mut self, start_index: &KeyIndex, visited: Option<&mut HashSet<u64>>) -> crate::Result<KeyIndex> {
         //TODO: this function will try to load the content of the target entry, but the content is not used if it's not a link.
         //It can be optimized by returning the content of the target entry if it's not a link. Or don't try to check if it's a link 
@@ -296,6 +1186,277 @@ impl<R: Read + Seek> ZdbReader<R> {
         }
     }
 
+    /// Checks whether an entry is a `@@@LINK=` redirect to another entry.
+    pub fn is_link_entry(&mut self, key_index: &KeyIndex) -> crate::Result<bool> {
+        let bin_content = self.get_data(key_index, false)?;
+        Ok(bin_content.starts_with(LINK_PREFIX) || bin_content.starts_with(LINK_PREFIX_W))
+    }
+
+    /// Finds the entries whose headword matches the query with headword folding.
+    ///
+    /// Punctuation, whitespace, case and diacritics are ignored, see [`fold_headword`].
+    /// Dictionaries built with the `FoldHeadword` key normalization are sorted by the folded
+    /// headwords, so the folded query is looked up by binary search. In other dictionaries
+    /// the folded headwords don't follow the collation order, so the first call folds all
+    /// headwords into an in-memory index.
+    ///
+    /// # Returns
+    ///
+    /// Returns the matching entries in entry order, empty if nothing matches.
+    pub fn find_folded(&mut self, query: &str) -> crate::Result<Vec<KeyIndex>> {
+        if self.meta.db_info.key_normalization.fold_headword {
+            return self.find_folded_sorted(query);
+        }
+        if self.folded_index.is_none() {
+            let mut folded_index = Vec::with_capacity(self.get_entry_count() as usize);
+            for entry_no in 0..self.get_entry_count() as EntryNo {
+                folded_index.push((fold_headword(&self.get_index(entry_no)?.key), entry_no));
+            }
+            folded_index.sort();
+            self.folded_index_memory = folded_index.iter().map(|(folded, _)| (size_of::<(String, EntryNo)>() + folded.len()) as u64).sum();
+            self.folded_index = Some(folded_index);
+        }
+        let folded_query = fold_headword(query);
+        let matches: Vec<EntryNo> = {
+            let folded_index = self.folded_index.as_ref().unwrap();
+            let start = folded_index.partition_point(|(folded, _)| folded.as_str() < folded_query.as_str());
+            folded_index[start..].iter()
+                .take_while(|(folded, _)| *folded == folded_query)
+                .map(|(_, entry_no)| *entry_no)
+                .collect()
+        };
+        let result = matches.into_iter().map(|entry_no| self.get_index(entry_no)).collect();
+        self.enforce_memory_limit();
+        result
+    }
+
+    /// Finds the entries matching the query in a dictionary sorted by folded headwords,
+    /// where they follow the first entry comparing equal to the query.
+    fn find_folded_sorted(&mut self, query: &str) -> crate::Result<Vec<KeyIndex>> {
+        let folded_query = fold_headword(query);
+        let mut matches = Vec::new();
+        let mut next = self.find_first_match(query, false, false, false)?;
+        while let Some(key_index) = next.take() {
+            if fold_headword(&key_index.key) != folded_query {
+                break;
+            }
+            let next_entry_no = key_index.entry_no + 1;
+            matches.push(key_index);
+            if (next_entry_no as u64) < self.get_entry_count() {
+                next = Some(self.get_index(next_entry_no)?);
+            }
+        }
+        self.enforce_memory_limit();
+        Ok(matches)
+    }
+
+    /// Checks whether an entry is a union entry listing the entries sharing its key.
+    ///
+    /// A union entry always precedes its members, so only entries followed by an entry with
+    /// the same key are checked, and only the start of their content is decoded.
+    pub fn is_union_entry(&mut self, key_index: &KeyIndex) -> crate::Result<bool> {
+        let next_entry_no = key_index.entry_no + 1;
+        if key_index.entry_no < 0 || next_entry_no as u64 >= self.get_entry_count() {
+            return Ok(false);
+        }
+        if self.get_index(next_entry_no)?.key != key_index.key {
+            return Ok(false);
+        }
+        let prefix = encode_string_to_bytes(UNION_PREFIX, self.content.meta_info.encoding_obj)?;
+        let prefix_length = prefix.len() as u64;
+        if self.get_content_length(key_index.entry_no)? < prefix_length {
+            return Ok(false);
+        }
+        let content_block = self.get_content_block_for_range(key_index, Some(prefix_length))?;
+        let content = content_block.get_content_as_slice(key_index.content_offset_in_source, prefix_length)?;
+        Ok(content == prefix.as_slice())
+    }
+
+    /// Expands a union entry into its member entries.
+    ///
+    /// # Arguments
+    ///
+    /// * `key_index` - The entry to expand
+    ///
+    /// # Returns
+    ///
+    /// Returns the member entries in entry order, or the entry itself if it isn't a union entry.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the member list is malformed or refers to entries out of range.
+    pub fn expand_union(&mut self, key_index: &KeyIndex) -> crate::Result<Vec<KeyIndex>> {
+        if !self.is_union_entry(key_index)? {
+            return Ok(vec![key_index.clone()]);
+        }
+        let content = self.get_string(key_index, false)?;
+        let entry_count = self.get_entry_count() as EntryNo;
+        let mut members = Vec::new();
+        for member in content[UNION_PREFIX.len()..].trim_end().split(',') {
+            let entry_no: EntryNo = member.trim().parse()
+                .map_err(|_| ZdbError::invalid_data_format(format!("Invalid union member \"{}\" in entry: {}", member, key_index.key)))?;
+            if entry_no < 0 || entry_no >= entry_count || entry_no == key_index.entry_no {
+                return Err(ZdbError::invalid_data_format(format!("Union member {} out of range in entry: {}", entry_no, key_index.key)));
+            }
+            members.push(self.get_index(entry_no)?);
+        }
+        Ok(members)
+    }
+
+    /// Checks whether an entry with a key equal to this one under the collation of the
+    /// dictionary exists, without reading its content.
+    ///
+    /// Keys are compared like [`find_first_match`](Self::find_first_match) compares them
+    /// without prefix matching, so depending on the collation strength and the key
+    /// normalization, a key differing only in e.g. case or punctuation matches too. It is
+    /// cheaper for probing many dictionaries: a Bloom filter, if present, answers most lookups
+    /// of missing keys without reading a key block, the query is encoded at most once, and
+    /// no `KeyIndex` is cloned.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the key can't be encoded or a key block can't be read.
+    pub fn contains_key(&mut self, key: &str) -> crate::Result<bool> {
+        if key.is_empty() {
+            return Ok(false);
+        }
+        // V3 keys are compared by collator, the sort key is only needed by the Bloom filter and older versions
+        let sort_key = if self.bloom_filter.is_some() || !self.meta.is_v3() {
+            get_sort_key(&encode_string_to_bytes(key, self.meta.encoding_obj)?, &self.meta)?
+        } else {
+            Vec::new()
+        };
+        if let Some(bloom_filter) = &self.bloom_filter
+            && !bloom_filter.filter.may_contain(&sort_key) {
+            return Ok(false);
+        }
+        self.load_key_block_indexes()?;
+        let meta = self.meta.clone();
+        let blocks = &self.key_block_indexes.block_indexes;
+        let block_pos = lower_bound(blocks, |block| Ok(block.compare_with(key, &sort_key, false, &meta)? == Ordering::Less))?;
+        let Some(block_index) = blocks.get(block_pos) else {
+            return Ok(false);
+        };
+        let key_block = self.key_blocks.get_key_block(&mut self.reader, block_index)?;
+        let found = {
+            let key_block = key_block.borrow();
+            let entry_pos = lower_bound(&key_block.key_indexes, |index| Ok(index.compare_with(key, &sort_key, false, &meta)? == Ordering::Less))?;
+            match key_block.key_indexes.get(entry_pos) {
+                Some(index) => index.compare_with(key, &sort_key, false, &meta)? == Ordering::Equal,
+                None => false,
+            }
+        };
+        self.enforce_memory_limit();
+        Ok(found)
+    }
+
+    /// Counts the entries whose key starts with `prefix`, e.g. for an alphabet scrollbar
+    /// or a result count.
+    ///
+    /// The first and last keys of the key block index bound the range, so at most the two
+    /// key blocks at its ends are decoded whatever the number of matches.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the prefix can't be encoded or a key block can't be read.
+    pub fn count_prefix(&mut self, prefix: &str) -> crate::Result<u64> {
+        if prefix.is_empty() {
+            return Ok(self.get_entry_count());
+        }
+        let sort_key = self.prefix_sort_key(prefix)?;
+        let (first, end) = self.prefix_block_range(prefix, &sort_key)?;
+        if first >= end {
+            return Ok(0);
+        }
+        let meta = self.meta.clone();
+        let blocks = &self.key_block_indexes.block_indexes;
+        let key_block = self.key_blocks.get_key_block(&mut self.reader, &blocks[first])?;
+        let start_entry = blocks[first].first_entry_no_in_block as u64
+            + lower_bound(&key_block.borrow().key_indexes, |index| Ok(index.compare_with(prefix, &sort_key, true, &meta)? == Ordering::Less))? as u64;
+        let key_block = self.key_blocks.get_key_block(&mut self.reader, &blocks[end - 1])?;
+        let end_entry = blocks[end - 1].first_entry_no_in_block as u64
+            + lower_bound(&key_block.borrow().key_indexes, |index| Ok(index.compare_with(prefix, &sort_key, true, &meta)? != Ordering::Greater))? as u64;
+        self.enforce_memory_limit();
+        Ok(end_entry.saturating_sub(start_entry))
+    }
+
+    fn prefix_sort_key(&self, prefix: &str) -> crate::Result<Vec<u8>> {
+        if self.meta.is_v3() {
+            Ok(Vec::new())
+        } else {
+            get_sort_key(&encode_string_to_bytes(prefix, self.meta.encoding_obj)?, &self.meta)
+        }
+    }
+
+    /// Finds the key blocks holding keys starting with `prefix`.
+    ///
+    /// Blocks from `first` to before `end` hold matches, all but the two at the ends only matches.
+    fn prefix_block_range(&mut self, prefix: &str, sort_key: &[u8]) -> crate::Result<(usize, usize)> {
+        self.load_key_block_indexes()?;
+        let meta = self.meta.clone();
+        let blocks = &self.key_block_indexes.block_indexes;
+        let first = lower_bound(blocks, |block| Ok(block.compare_with(prefix, sort_key, true, &meta)? == Ordering::Less))?;
+        let end = lower_bound(blocks, |block| Ok(block.compare_with(prefix, sort_key, true, &meta)? != Ordering::Greater))?;
+        Ok((first, end))
+    }
+
+    /// Finds the entries whose key matches a wildcard pattern, stopping at the limits.
+    ///
+    /// `*` matches any characters and `?` a single character, ignoring case. The literal
+    /// prefix before the first wildcard is also matched with the collation of the
+    /// dictionary, like [`count_prefix`](Self::count_prefix), to narrow the scan to the key
+    /// blocks of the keys starting with it. A pattern starting with a wildcard scans all
+    /// key blocks, so [`SearchLimits::max_scanned_blocks`] and [`SearchLimits::timeout`]
+    /// bound the work.
+    ///
+    /// # Arguments
+    ///
+    /// * `pattern` - The wildcard pattern
+    /// * `limits` - Limits of the scan, [`SearchLimits::max_matches`] bounds the results
+    ///
+    /// # Returns
+    ///
+    /// Returns the matching entries in entry order, and the limit that stopped the scan if any.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the prefix can't be encoded or a key block can't be read.
+    pub fn find_wildcard(&mut self, pattern: &str, limits: &SearchLimits) -> crate::Result<LimitedResults<KeyIndex>> {
+        let mut budget = SearchBudget::start(limits);
+        let prefix_len = pattern.find(['*', '?']).unwrap_or(pattern.len());
+        let prefix = &pattern[..prefix_len];
+        let (first, end) = if prefix.is_empty() {
+            self.load_key_block_indexes()?;
+            (0, self.key_block_indexes.block_indexes.len())
+        } else {
+            let sort_key = self.prefix_sort_key(prefix)?;
+            self.prefix_block_range(prefix, &sort_key)?
+        };
+        let pattern: Vec<char> = pattern.to_lowercase().chars().collect();
+        let mut results = Vec::new();
+        let mut truncated = None;
+        'blocks: for block_pos in first..end {
+            if let Some(limit) = budget.scan_block() {
+                truncated = Some(limit);
+                break;
+            }
+            let key_block_index = self.key_block_indexes.block_indexes[block_pos].clone();
+            let key_block = self.key_blocks.get_key_block(&mut self.reader, &key_block_index)?;
+            for key_index in &key_block.borrow().key_indexes {
+                if !wildcard_match(&pattern, &key_index.key.to_lowercase().chars().collect::<Vec<_>>()) {
+                    continue;
+                }
+                if let Some(limit) = budget.add_match() {
+                    truncated = Some(limit);
+                    break 'blocks;
+                }
+                results.push(key_index.clone());
+            }
+        }
+        self.enforce_memory_limit();
+        Ok(LimitedResults { results, truncated })
+    }
+
     pub fn get_data_by_key(&mut self, key: &str) -> crate::Result<Option<Vec<u8>>> {
         let key_index = self.find_first_match(key, false, false, true)?;
         if let Some(key_index) = key_index {
@@ -311,29 +1472,61 @@ impl<R: Read + Seek> ZdbReader<R> {
         } else {
             key_index.clone()
         };
-        let content_block = self.get_content_block(&resolved_index)?;
+        let content_length = self.get_content_length(resolved_index.entry_no)?;
+        // An empty entry at the end of the content unit has no block containing its offset
+        if content_length == 0 {
+            return Ok(Vec::new());
+        }
+        let content_block = self.get_content_block_for_range(&resolved_index, Some(content_length))?;
         let content = content_block.get_content_as_slice(
             resolved_index.content_offset_in_source,
-            self.get_content_length(resolved_index.entry_no)?,
+            content_length,
         )?;
         Ok(content.to_vec())
     }
 
     pub fn get_string(&mut self, key_index: &KeyIndex, resolve_link: bool) -> crate::Result<String> {
+        Ok(self.get_string_with_diagnostics(key_index, resolve_link)?.0)
+    }
+
+    /// Gets the content of an entry as a string, like [`get_string`](Self::get_string), with
+    /// the [`DecodeDiagnostics`] telling e.g. whether it fell back to another encoding.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `InvalidDataFormat` error naming the entry if its content is malformed and
+    /// the reader decodes in [`DecodeMode::Strict`], see [`set_decode_mode`](Self::set_decode_mode).
+    pub fn get_string_with_diagnostics(&mut self, key_index: &KeyIndex, resolve_link: bool) -> crate::Result<(String, DecodeDiagnostics)> {
         let resolved_index = if resolve_link {
             self.resolve_link_target_with_visited(key_index, None)?
         } else {
             key_index.clone()
         };
-        let content_block = self.get_content_block(&resolved_index)?;
-        content_block.get_string(
+        let encoding_obj = self.content.meta_info.encoding_obj;
+        let content_length = self.get_content_length(resolved_index.entry_no)?;
+        if content_length == 0 {
+            let diagnostics = DecodeDiagnostics { declared_encoding: encoding_obj.name(), encoding: encoding_obj.name(), bom_stripped: false, had_errors: false };
+            return Ok((String::new(), diagnostics));
+        }
+        let content_block = self.get_content_block_for_range(&resolved_index, Some(content_length))?;
+        content_block.get_string_with_diagnostics(
             resolved_index.content_offset_in_source,
-            self.get_content_length(resolved_index.entry_no)?,
-            &self.content.meta_info.encoding_obj
-        )
+            content_length,
+            encoding_obj,
+            self.options.decode_mode,
+        ).map_err(|e| match e {
+            ZdbError::InvalidDataFormat { message, .. } => ZdbError::invalid_data_format(format!("Entry {} ({}): {}", resolved_index.entry_no, resolved_index.key, message)),
+            e => e,
+        })
+    }
+
+    /// Sets how malformed text is handled by [`get_string`](Self::get_string), see [`ReaderOptions::decode_mode`].
+    pub fn set_decode_mode(&mut self, mode: DecodeMode) {
+        self.options.decode_mode = mode;
     }
 
     pub fn get_index(&mut self, entry_no: EntryNo) -> crate::Result<KeyIndex> {
+        self.load_key_block_indexes()?;
         let key_block_index = self.key_block_indexes.get_index(entry_no)?;
         let key_block =
             self.key_blocks
@@ -354,8 +1547,187 @@ impl<R: Read + Seek> ZdbReader<R> {
         Ok(indexes)
     }
 
+    /// Verifies the per-unit SHA-256 digests recorded by the builder.
+    ///
+    /// Only the raw unit bytes are hashed, no block is decoded.
+    ///
+    /// # Returns
+    ///
+    /// Returns the verification result of each unit.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file has no unit digests.
+    pub fn verify(&mut self) -> crate::Result<Vec<UnitDigestCheck>> {
+        verify_unit_digests(&mut self.reader)
+    }
+
+    /// Index entries of all content blocks, in file order.
+    pub fn content_block_indexes(&self) -> &[ContentBlockIndex] {
+        &self.content_block_index.block_index_entries
+    }
+
+    /// Reads a content block as stored, without decrypting or decompressing it.
+    ///
+    /// # Returns
+    ///
+    /// Returns the offset of the block in the file, which may be part of its nonce,
+    /// and the block starting with its length fields.
+    pub fn read_stored_content_block(&mut self, content_block_index: &ContentBlockIndex) -> crate::Result<(u64, Vec<u8>)> {
+        let (block_offset, block_length) = self.stored_block_position(content_block_index);
+        let stored_block = self.read_content_bytes(block_offset, block_length)?;
+        Ok((block_offset, stored_block))
+    }
+
+    /// Header fields and stored bytes of a content block, for debugging the format.
+    ///
+    /// The block isn't decoded, so this works for blocks that fail to decode, e.g. with
+    /// a crc mismatch.
+    ///
+    /// # Arguments
+    ///
+    /// * `block_no` - Number of the content block in file order, see [`content_block_indexes`](Self::content_block_indexes)
+    ///
+    /// # Errors
+    ///
+    /// Returns an `InvalidParameter` error if there's no such block.
+    pub fn debug_block(&mut self, block_no: usize) -> crate::Result<RawBlockInfo> {
+        let content_block_index = self.content_block_index.block_index_entries.get(block_no).cloned()
+            .ok_or_else(|| ZdbError::invalid_parameter(format!("Content block {} doesn't exist, the file has {} blocks",
+                block_no, self.content_block_index.block_index_entries.len())))?;
+        let (block_offset, stored_block) = self.read_stored_content_block(&content_block_index)?;
+        RawBlockInfo::from_stored(block_offset, &stored_block, content_block_index.block_original_length as u32, self.meta.version == ZdbVersion::V3)
+    }
+
+    /// Compression method of a content block, read from its header without decoding it.
+    pub fn content_block_compression(&mut self, content_block_index: &ContentBlockIndex) -> crate::Result<CompressionMethod> {
+        let (block_offset, _) = self.stored_block_position(content_block_index);
+        // The methods follow the two length fields
+        let compression_encryption = self.read_content_bytes(block_offset + 8, 1)?;
+        CompressionMethod::try_from(compression_encryption[0] & 0x0F)
+    }
+
+    /// Checksum algorithm of a content block, read from its header without decoding it.
+    pub fn content_block_checksum(&mut self, content_block_index: &ContentBlockIndex) -> crate::Result<ChecksumAlgorithm> {
+        let (block_offset, _) = self.stored_block_position(content_block_index);
+        // The flags follow the two length fields, the methods and the encrypted length
+        let flags = self.read_content_bytes(block_offset + 10, 2)?;
+        ChecksumAlgorithm::from_block_flags(u16::from_be_bytes([flags[0], flags[1]]))
+    }
+
+    /// Reads bytes of the content unit, from the companion content file if there's one.
+    fn read_content_bytes(&mut self, offset: u64, length: usize) -> crate::Result<Vec<u8>> {
+        match &mut self.content_file {
+            Some(content_file) => {
+                content_file.seek(SeekFrom::Start(offset))?;
+                read_exact_to_vec(content_file, length)
+            }
+            None => {
+                self.reader.seek(SeekFrom::Start(offset))?;
+                read_exact_to_vec(&mut self.reader, length)
+            }
+        }
+    }
+
+    fn stored_block_position(&self, content_block_index: &ContentBlockIndex) -> (u64, usize) {
+        (self.content.content_data_offset_in_file + content_block_index.block_offset_in_unit, content_block_index.block_compressed_length as usize)
+    }
+
     pub fn is_binary_content(&self) -> bool {
         self.meta.db_info.content_type == ContentType::Binary
     }
 
+    /// Reports the approximate number of bytes held by the reader.
+    pub fn memory_footprint(&self) -> MemoryFootprint {
+        let block_indexes = self.key_block_indexes.block_indexes.iter().map(|index| index.memory_size()).sum::<usize>()
+            + self.content_block_index.block_index_entries.len() * size_of::<ContentBlockIndex>();
+        let db_info = &self.meta.db_info;
+        MemoryFootprint {
+            block_indexes: block_indexes as u64,
+            key_block_cache: self.key_blocks.cache_memory(),
+            content_block_cache: self.content_cache_memory,
+            bloom_filter: self.bloom_filter.as_ref().map(|bloom_filter| bloom_filter.filter.bits.len() as u64).unwrap_or_default(),
+            entry_meta: self.entry_meta.as_ref()
+                .map(|entry_meta| (entry_meta.records.len() + entry_meta.tag_names.iter().map(String::len).sum::<usize>()) as u64)
+                .unwrap_or_default(),
+            source_map: self.source_map.as_ref()
+                .map(|source_map| (source_map.records.len() + source_map.source_file.len()) as u64)
+                .unwrap_or_default(),
+            folded_index: self.folded_index_memory,
+            metadata: (size_of::<MetaUnit>() + self.meta.raw_header_xml.len() + db_info.title.len() + db_info.description.len()) as u64,
+        }
+    }
+
+    /// Decodes the key block index if the reader was opened with `lazy_key_index`.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `MemoryLimitExceeded` error if the index would exceed `ReaderOptions::max_memory`.
+    /// The size declared in the unit is checked before decoding, and the decoded index is
+    /// dropped again if it turns out larger.
+    fn load_key_block_indexes(&mut self) -> crate::Result<()> {
+        if self.key_block_indexes.is_loaded() {
+            return Ok(());
+        }
+        if let Some(limit) = self.options.max_memory
+            && let Some(declared) = self.key_block_indexes.declared_memory_size() {
+            let estimated = self.resident_memory + declared;
+            if estimated > limit {
+                return Err(ZdbError::memory_limit_exceeded(estimated, limit));
+            }
+        }
+        self.key_block_indexes.load(&mut self.reader)?;
+        if let Some(limit) = self.options.max_memory {
+            let resident = self.memory_footprint().resident();
+            if resident > limit {
+                self.key_block_indexes.unload();
+                return Err(ZdbError::memory_limit_exceeded(resident, limit));
+            }
+            self.resident_memory = resident;
+        }
+        Ok(())
+    }
+
+    /// Bytes held by the reader according to the running totals, see [`MemoryFootprint::total`].
+    fn tracked_memory(&self) -> u64 {
+        self.resident_memory + self.key_blocks.cache_memory() + self.content_cache_memory + self.folded_index_memory
+    }
+
+    /// Evicts cached data until the reader is within `ReaderOptions::max_memory`.
+    ///
+    /// Content blocks go first, then key blocks, then the folded headword index. The most
+    /// recently used block of each cache is kept, since the caller may still be using it.
+    fn enforce_memory_limit(&mut self) {
+        let Some(limit) = self.options.max_memory else {
+            return;
+        };
+        while self.tracked_memory() > limit {
+            if self.block_cache.len() > 1 {
+                if let Some((_, evicted)) = self.block_cache.pop_lru() {
+                    self.content_cache_memory -= evicted.memory_size() as u64;
+                }
+            } else if self.key_blocks.block_cache.borrow().len() > 1 {
+                self.key_blocks.evict_lru_block();
+            } else if self.folded_index.is_some() {
+                self.folded_index = None;
+                self.folded_index_memory = 0;
+            } else {
+                break;
+            }
+        }
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_license_key() {
+        let check = |has_license| ZdbReader::<Cursor<Vec<u8>>>::check_license_key::<()>(Err(ZdbError::crc_mismatch(1, 2)), has_license);
+        assert_eq!(check(true).unwrap_err().license_error_kind(), Some(LicenseErrorKind::WrongDevice));
+        assert!(matches!(check(false), Err(ZdbError::CrcMismatch { .. })));
+        assert!(ZdbReader::<Cursor<Vec<u8>>>::check_license_key(Ok(()), true).is_ok());
+    }
 }
@@ -0,0 +1,176 @@
+//! Optional Bloom filter over the headwords of a V3 file.
+//!
+//! Applications that query many dictionaries for every word get "not found" from
+//! most of them. The builder can append a Bloom filter unit after the key block
+//! index unit, letting [`ZdbReader::may_contain`](crate::readers::zdb_reader::ZdbReader::may_contain)
+//! reject most missing keys without touching the key index.
+//!
+//! The filter is built over the collation sort keys of the headwords, so it agrees
+//! with exact lookups, which compare sort keys too. Sort keys depend on the collation
+//! backend, so the unit records the backend that built it, and readers built with
+//! another backend, or reading a file that doesn't record one, ignore the filter
+//! rather than reject keys that exist. Readers that don't know the unit stop after the
+//! key block index unit and never see it.
+
+use std::io::{ErrorKind, Read, Seek, SeekFrom};
+use std::rc::Rc;
+
+use byteorder::ReadBytesExt;
+use serde::{Deserialize, Serialize};
+
+use crate::storage::meta_unit::MetaUnit;
+use crate::storage::storage_block::StorageBlock;
+use crate::storage::unit_base::{read_data_info_section, UnitInfoSection, UnitType};
+use crate::utils::icu_wrapper::BACKEND;
+use crate::Result;
+
+/// Bits reserved per key, giving a false positive rate of about 1%.
+pub const BLOOM_FILTER_BITS_PER_KEY: u64 = 10;
+/// Number of hash functions, close to `BLOOM_FILTER_BITS_PER_KEY * ln 2`.
+pub const BLOOM_FILTER_HASH_COUNT: u32 = 7;
+
+#[derive(Debug, Clone, Deserialize, Serialize, Default)]
+#[serde(rename = "BloomFilter")]
+pub struct BloomFilterDataInfo {
+    #[serde(rename = "@keyCount")]
+    pub key_count: u64,
+    #[serde(rename = "@hashCount")]
+    pub hash_count: u32,
+    /// Collation backend that computed the sort keys, see [`BACKEND`](crate::utils::icu_wrapper::BACKEND)
+    #[serde(rename = "@sortKeyBackend", default)]
+    pub sort_key_backend: String,
+}
+// <BloomFilter keyCount="1000" hashCount="7" sortKeyBackend="ICU4X" />
+
+/// A Bloom filter over byte strings.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BloomFilter {
+    pub bits: Vec<u8>,
+    pub hash_count: u32,
+}
+
+/// 64-bit FNV-1a, fixed so filters stay valid across builds and platforms.
+fn fnv1a_64(data: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for &byte in data {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
+/// Finalizer of splitmix64, used to derive the second hash.
+fn mix64(mut value: u64) -> u64 {
+    value = (value ^ (value >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    value = (value ^ (value >> 27)).wrapping_mul(0x94d049bb133111eb);
+    value ^ (value >> 31)
+}
+
+impl BloomFilter {
+    /// Creates an empty filter sized for `key_count` keys.
+    pub fn with_key_count(key_count: u64) -> Self {
+        let byte_count = (key_count * BLOOM_FILTER_BITS_PER_KEY).div_ceil(8).max(8);
+        Self { bits: vec![0; byte_count as usize], hash_count: BLOOM_FILTER_HASH_COUNT }
+    }
+
+    /// Bit positions of a key, using double hashing.
+    fn bit_positions(bit_count: u64, hash_count: u32, key: &[u8]) -> impl Iterator<Item = u64> + use<> {
+        let h1 = fnv1a_64(key);
+        let h2 = mix64(h1) | 1;
+        (0..hash_count as u64).map(move |i| h1.wrapping_add(i.wrapping_mul(h2)) % bit_count)
+    }
+
+    pub fn insert(&mut self, key: &[u8]) {
+        if self.bits.is_empty() {
+            return;
+        }
+        for pos in Self::bit_positions(self.bits.len() as u64 * 8, self.hash_count, key) {
+            self.bits[(pos / 8) as usize] |= 1 << (pos % 8);
+        }
+    }
+
+    /// Returns `false` if the key is definitely absent, `true` if it may be present.
+    pub fn may_contain(&self, key: &[u8]) -> bool {
+        if self.bits.is_empty() {
+            return true;
+        }
+        Self::bit_positions(self.bits.len() as u64 * 8, self.hash_count, key).all(|pos| self.bits[(pos / 8) as usize] & (1 << (pos % 8)) != 0)
+    }
+}
+
+pub struct BloomFilterUnit {
+    pub filter: BloomFilter,
+    pub key_count: u64,
+    /// Collation backend that computed the sort keys, empty for files built before it was recorded
+    pub sort_key_backend: String,
+}
+
+impl BloomFilterUnit {
+    /// Returns whether the sort keys of the filter were computed by the collation backend
+    /// of this build, otherwise the filter may reject keys that exist.
+    pub fn matches_backend(&self) -> bool {
+        self.sort_key_backend == BACKEND
+    }
+
+    /// Reads the Bloom filter unit if the next unit in the reader is one.
+    ///
+    /// # Returns
+    ///
+    /// Returns `None` and leaves the position unchanged if the reader is at the end of
+    /// the units or at another kind of data, e.g. the unit digest trailer.
+    pub fn try_from_reader_v3<R: Read + Seek>(reader: &mut R, meta_info: &Rc<MetaUnit>) -> Result<Option<Self>> {
+        let unit_pos = reader.stream_position()?;
+        let unit_type = match reader.read_u8() {
+            Ok(unit_type) => unit_type,
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => {
+                reader.seek(SeekFrom::Start(unit_pos))?;
+                return Ok(None);
+            }
+            Err(e) => return Err(e.into()),
+        };
+        reader.seek(SeekFrom::Start(unit_pos))?;
+        if unit_type != UnitType::BloomFilter as u8 {
+            return Ok(None);
+        }
+
+        let unit_info = UnitInfoSection::from_reader(reader)?;
+        let data_pos = reader.stream_position()?;
+        reader.seek(SeekFrom::Current(unit_info.data_section_length as i64))?; //skip to the end of data section
+        let data_info = read_data_info_section::<BloomFilterDataInfo, R>(reader, meta_info)?;
+        let end_of_unit = reader.stream_position()?;
+        reader.seek(SeekFrom::Start(data_pos))?;
+        let bits = StorageBlock::from_reader_v3(reader, meta_info)?.data;
+        reader.seek(SeekFrom::Start(end_of_unit))?;
+        Ok(Some(Self {
+            filter: BloomFilter { bits, hash_count: data_info.hash_count },
+            key_count: data_info.key_count,
+            sort_key_backend: data_info.sort_key_backend,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bloom_filter() {
+        let mut filter = BloomFilter::with_key_count(1000);
+        for i in 0..1000 {
+            filter.insert(format!("word{}", i).as_bytes());
+        }
+        assert!((0..1000).all(|i| filter.may_contain(format!("word{}", i).as_bytes())));
+        let false_positives = (0..10000).filter(|i| filter.may_contain(format!("missing{}", i).as_bytes())).count();
+        assert!(false_positives < 300, "{} false positives", false_positives);
+        assert!(BloomFilter::default().may_contain(b"anything"));
+    }
+
+    #[test]
+    fn test_backend_mismatch() {
+        let unit = |sort_key_backend: &str| BloomFilterUnit { filter: BloomFilter::default(), key_count: 0, sort_key_backend: sort_key_backend.to_string() };
+        assert!(unit(BACKEND).matches_backend());
+        assert!(!unit("").matches_backend());
+        let other = if BACKEND == "ICU4X" { "ICU4C" } else { "ICU4X" };
+        assert!(!unit(other).matches_backend());
+    }
+}
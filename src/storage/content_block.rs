@@ -10,12 +10,13 @@ use encoding_rs::Encoding;
 
 use super::content_block_index_unit::ContentBlockIndex;
 use crate::storage::meta_unit::{MetaUnit, ZdbVersion};
-use crate::storage::reader_helper::decode_bytes_to_string;
+use crate::storage::reader_helper::{decode_bytes_to_string, decode_bytes_with_diagnostics, DecodeDiagnostics, DecodeMode};
 use super::storage_block::StorageBlock;
 
 /// A content block from a ZDB file.
 ///
 /// Contains the actual dictionary entry data, indexed by position in the source.
+/// A partially decoded block holds only a prefix of the data, see [`is_complete`](Self::is_complete).
 pub struct ContentBlock {
     pub block_index: ContentBlockIndex,
     pub block: Vec<u8>,
@@ -30,6 +31,20 @@ impl ContentBlock {
     /// * `meta_info` - Dictionary metadata
     /// * `block_index` - Index information for the block
     pub fn from_reader<R: Read+Seek>(reader: &mut R, meta_info: &MetaUnit, block_index: &ContentBlockIndex) -> crate::Result<Self> {
+        Self::from_reader_prefix(reader, meta_info, block_index, usize::MAX)
+    }
+
+    /// Reads a content block, decoding at least the first `prefix_length` bytes.
+    ///
+    /// Only V3 blocks are decoded partially, and only if the codec supports it.
+    ///
+    /// # Arguments
+    ///
+    /// * `reader` - The reader to read from
+    /// * `meta_info` - Dictionary metadata
+    /// * `block_index` - Index information for the block
+    /// * `prefix_length` - Number of bytes needed from the start of the block
+    pub fn from_reader_prefix<R: Read+Seek>(reader: &mut R, meta_info: &MetaUnit, block_index: &ContentBlockIndex, prefix_length: usize) -> crate::Result<Self> {
         let block_data=
             match meta_info.version {
                 ZdbVersion::V1|ZdbVersion::V2=>
@@ -40,11 +55,27 @@ impl ContentBlock {
                         block_index.block_compressed_length as u32,
                         block_index.block_original_length as u32
                     )?,
-                ZdbVersion::V3=>StorageBlock::from_reader_v3(reader, meta_info)?,
+                ZdbVersion::V3=>StorageBlock::from_reader_v3_prefix(reader, meta_info, prefix_length)?,
             };
         Ok(Self { block_index: block_index.clone(), block: block_data.data })
     }
 
+    /// Approximate number of bytes held by this block.
+    pub fn memory_size(&self) -> usize {
+        std::mem::size_of::<Self>() + self.block.capacity()
+    }
+
+    /// Whether the whole block is decoded.
+    pub fn is_complete(&self) -> bool {
+        self.block.len() as u64 >= self.block_index.block_original_length
+    }
+
+    /// Whether the decoded part of the block contains the given range.
+    pub fn contains(&self, offset: u64, length: u64) -> bool {
+        offset >= self.block_index.block_offset_in_source
+            && offset + length <= self.block_index.block_offset_in_source + self.block.len() as u64
+    }
+
     /// Gets a slice of content from this block.
     ///
     /// # Arguments
@@ -55,6 +86,9 @@ impl ContentBlock {
         if offset < self.block_index.block_offset_in_source || offset + length > self.block_index.block_offset_in_source + self.block_index.block_original_length {
             return Err(crate::ZdbError::invalid_parameter(format!("offset out of range: offset={}, length={}, block_offset_in_source={}, block_original_length={}", offset, length, self.block_index.block_offset_in_source, self.block_index.block_original_length)));
         }
+        if !self.contains(offset, length) {
+            return Err(crate::ZdbError::invalid_parameter(format!("offset beyond the decoded part of the block: offset={}, length={}, decoded_length={}", offset, length, self.block.len())));
+        }
         let block_offset = offset - self.block_index.block_offset_in_source ;
         Ok(&self.block[block_offset as usize..(block_offset + length) as usize])
     }
@@ -70,4 +104,10 @@ impl ContentBlock {
         let content = self.get_content_as_slice(offset, length)?;
         decode_bytes_to_string(content, encoding_obj)
     }
-}
\ No newline at end of file
+
+    /// Gets content as a decoded string from this block with its [`DecodeDiagnostics`].
+    pub fn get_string_with_diagnostics(&self, offset: u64, length: u64, encoding_obj: &'static Encoding, mode: DecodeMode) -> crate::Result<(String, DecodeDiagnostics)> {
+        let content = self.get_content_as_slice(offset, length)?;
+        decode_bytes_with_diagnostics(content, encoding_obj, mode)
+    }
+}
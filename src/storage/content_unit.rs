@@ -2,6 +2,12 @@
 //!
 //! This module manages the content unit of a ZDB file, which is responsible for
 //! organizing and providing access to all dictionary records.
+//!
+//! The blocks of the content unit can be stored in a companion file next to the
+//! dictionary, named by the `ContentFile` attribute of the header, so very large
+//! dictionaries fit on filesystems limiting files to 4 GB while the file with the
+//! indexes stays small. The companion file starts with [`CONTENT_FILE_MAGIC`] followed
+//! by the blocks, and the data section of the unit in the dictionary is empty.
 
 use std::io::{Read, Seek, SeekFrom};
 use std::rc::Rc;
@@ -12,7 +18,10 @@ use super::content_block::ContentBlock;
 use super::content_block_index_unit::{ContentBlockIndex, ContentBlockIndexUnit};
 use crate::storage::meta_unit::MetaUnit;
 use crate::storage::unit_base::{read_data_info_section, UnitInfoSection};
-use crate::Result;
+use crate::{Result, ZdbError};
+
+/// Signature and version at the start of a companion content file.
+pub const CONTENT_FILE_MAGIC: &[u8; 8] = b"ZDAT\0\0\0\x01";
 
 /// Metadata for dictionary record content.
 #[derive(Debug, Clone, Deserialize, Serialize, Default)]
@@ -49,9 +58,51 @@ impl ContentUnit {
         let block = ContentBlock::from_reader(reader, &self.meta_info, content_block_index)?;
         Ok(block)
     }
+
+    /// Reads a content block, decoding at least its first `prefix_length` bytes.
+    ///
+    /// Unencrypted blocks are always decoded whole, see
+    /// [`StorageBlock::decode_block_prefix`](crate::storage::storage_block::StorageBlock::decode_block_prefix).
+    ///
+    /// # Arguments
+    ///
+    /// * `reader` - The reader to read from
+    /// * `content_block_index` - Index information for the block
+    /// * `prefix_length` - Number of bytes needed from the start of the block
+    pub fn get_content_block_prefix<R: Read+Seek>(&self, reader: &mut R, content_block_index: &ContentBlockIndex, prefix_length: u64) -> Result<ContentBlock> {
+        reader.seek(SeekFrom::Start(content_block_index.block_offset_in_unit + self.content_data_offset_in_file))?;
+        let block = ContentBlock::from_reader_prefix(reader, &self.meta_info, content_block_index, prefix_length as usize)?;
+        Ok(block)
+    }
 }
 
 impl ContentUnit {
+    /// Reads the blocks from a companion content file instead of the dictionary.
+    ///
+    /// # Arguments
+    ///
+    /// * `reader` - The companion file
+    /// * `data_section_length` - Total stored length of the blocks, from the content block index
+    ///
+    /// # Errors
+    ///
+    /// Returns an `InvalidDataFormat` error if the file isn't a companion content file or
+    /// is shorter than the blocks.
+    pub fn attach_content_file<R: Read+Seek>(&mut self, reader: &mut R, data_section_length: u64) -> Result<()> {
+        let mut magic = [0u8; CONTENT_FILE_MAGIC.len()];
+        reader.seek(SeekFrom::Start(0))?;
+        if reader.read_exact(&mut magic).is_err() || &magic != CONTENT_FILE_MAGIC {
+            return Err(ZdbError::invalid_data_format("Not a companion content file"));
+        }
+        let file_length = reader.seek(SeekFrom::End(0))?;
+        if file_length < CONTENT_FILE_MAGIC.len() as u64 + data_section_length {
+            return Err(ZdbError::invalid_data_format(format!("Companion content file is truncated: {} bytes of blocks expected, {} found",
+                data_section_length, file_length - CONTENT_FILE_MAGIC.len() as u64)));
+        }
+        self.content_data_offset_in_file = CONTENT_FILE_MAGIC.len() as u64;
+        Ok(())
+    }
+
     /// Loads content unit from V3 format reader.
     pub fn from_reader_v3<R: Read+Seek>(reader: &mut R, meta_info: &Rc<MetaUnit>) -> crate::Result<Self> {
         let info = UnitInfoSection::from_reader(reader)?;
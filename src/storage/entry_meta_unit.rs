@@ -0,0 +1,228 @@
+//! Optional table of per-entry metadata of a V3 file: tags, frequency rank and part of speech.
+//!
+//! Applications sort and filter lookup results by frequency or part of speech, which
+//! otherwise means parsing the content of every entry. The builder can append an
+//! entry metadata unit, filled from a supplemental TSV file, after the key block index
+//! unit and the Bloom filter unit. It holds a fixed-size record for every entry in entry
+//! order, see [`ZdbReader::get_entry_meta_ext`](crate::readers::zdb_reader::ZdbReader::get_entry_meta_ext).
+//! Readers that don't know the unit stop after the key block index unit and never see it.
+
+use std::io::{ErrorKind, Read, Seek, SeekFrom};
+use std::rc::Rc;
+
+use byteorder::ReadBytesExt;
+use serde::{Deserialize, Serialize};
+
+use crate::storage::key_block::EntryNo;
+use crate::storage::meta_unit::MetaUnit;
+use crate::storage::storage_block::StorageBlock;
+use crate::storage::unit_base::{read_data_info_section, UnitInfoSection, UnitType};
+use crate::{Result, ZdbError};
+
+/// Length of an entry record: tags u32, frequency rank u32 and part of speech u8.
+pub const ENTRY_META_RECORD_SIZE: u32 = 9;
+/// Number of records per block of the unit.
+pub const ENTRY_META_BLOCK_ENTRIES: usize = 65536;
+/// Maximum number of distinct tags, one bit of the tag set each.
+pub const ENTRY_META_MAX_TAGS: usize = 32;
+
+#[derive(Debug, Clone, Deserialize, Serialize, Default)]
+#[serde(rename = "EntryMeta")]
+pub struct EntryMetaDataInfo {
+    #[serde(rename = "@entryCount")]
+    pub entry_count: u64,
+    #[serde(rename = "@recordSize")]
+    pub record_size: u32,
+    /// Names of the tags, comma-separated, the first name is bit 0 of the tag set
+    #[serde(rename = "@tagNames", default)]
+    pub tag_names: String,
+}
+// <EntryMeta entryCount="1000" recordSize="9" tagNames="archaic,slang" />
+
+/// Part of speech of an entry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize)]
+#[repr(u8)]
+pub enum PartOfSpeech {
+    #[default]
+    Unknown = 0,
+    Noun = 1,
+    Verb = 2,
+    Adjective = 3,
+    Adverb = 4,
+    Pronoun = 5,
+    Preposition = 6,
+    Conjunction = 7,
+    Interjection = 8,
+    Determiner = 9,
+    Numeral = 10,
+    Particle = 11,
+    Abbreviation = 12,
+    Phrase = 13,
+    /// Any other part of speech, also read for codes added by later versions
+    Other = 255,
+}
+
+impl PartOfSpeech {
+    /// Parses a part of speech from its name or a usual abbreviation, ignoring case.
+    ///
+    /// Returns `None` for an unknown name.
+    pub fn from_name(name: &str) -> Option<Self> {
+        let pos = match name.trim().trim_end_matches('.').to_lowercase().as_str() {
+            "" => PartOfSpeech::Unknown,
+            "n" | "noun" => PartOfSpeech::Noun,
+            "v" | "vt" | "vi" | "verb" => PartOfSpeech::Verb,
+            "a" | "adj" | "adjective" => PartOfSpeech::Adjective,
+            "adv" | "adverb" => PartOfSpeech::Adverb,
+            "pron" | "pronoun" => PartOfSpeech::Pronoun,
+            "prep" | "preposition" => PartOfSpeech::Preposition,
+            "conj" | "conjunction" => PartOfSpeech::Conjunction,
+            "int" | "interj" | "interjection" => PartOfSpeech::Interjection,
+            "det" | "art" | "article" | "determiner" => PartOfSpeech::Determiner,
+            "num" | "numeral" => PartOfSpeech::Numeral,
+            "part" | "particle" => PartOfSpeech::Particle,
+            "abbr" | "abbreviation" => PartOfSpeech::Abbreviation,
+            "phr" | "phrase" => PartOfSpeech::Phrase,
+            "other" => PartOfSpeech::Other,
+            _ => return None,
+        };
+        Some(pos)
+    }
+}
+
+impl From<u8> for PartOfSpeech {
+    fn from(value: u8) -> Self {
+        match value {
+            0 => PartOfSpeech::Unknown,
+            1 => PartOfSpeech::Noun,
+            2 => PartOfSpeech::Verb,
+            3 => PartOfSpeech::Adjective,
+            4 => PartOfSpeech::Adverb,
+            5 => PartOfSpeech::Pronoun,
+            6 => PartOfSpeech::Preposition,
+            7 => PartOfSpeech::Conjunction,
+            8 => PartOfSpeech::Interjection,
+            9 => PartOfSpeech::Determiner,
+            10 => PartOfSpeech::Numeral,
+            11 => PartOfSpeech::Particle,
+            12 => PartOfSpeech::Abbreviation,
+            13 => PartOfSpeech::Phrase,
+            _ => PartOfSpeech::Other,
+        }
+    }
+}
+
+/// Metadata of an entry, see the [module documentation](self).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize)]
+pub struct EntryMetaExt {
+    /// Set of tags, bit `n` is the `n`th name of [`EntryMetaUnit::tag_names`]
+    pub tags: u32,
+    /// Frequency rank, 1 is the most frequent word, 0 means unranked
+    pub frequency_rank: u32,
+    /// Part of speech
+    pub pos: PartOfSpeech,
+}
+
+impl EntryMetaExt {
+    /// Appends the record of the entry to `buffer`.
+    pub fn write_record(&self, buffer: &mut Vec<u8>) {
+        buffer.extend_from_slice(&self.tags.to_be_bytes());
+        buffer.extend_from_slice(&self.frequency_rank.to_be_bytes());
+        buffer.push(self.pos as u8);
+    }
+
+    fn from_record(record: &[u8]) -> Self {
+        Self {
+            tags: u32::from_be_bytes([record[0], record[1], record[2], record[3]]),
+            frequency_rank: u32::from_be_bytes([record[4], record[5], record[6], record[7]]),
+            pos: PartOfSpeech::from(record[8]),
+        }
+    }
+}
+
+pub struct EntryMetaUnit {
+    /// Records of all entries, `record_size` bytes each
+    pub records: Vec<u8>,
+    pub record_size: u32,
+    pub entry_count: u64,
+    pub tag_names: Vec<String>,
+}
+
+impl EntryMetaUnit {
+    /// Reads the entry metadata unit if the next unit in the reader is one.
+    ///
+    /// # Returns
+    ///
+    /// Returns `None` and leaves the position unchanged if the reader is at the end of
+    /// the units or at another kind of data, e.g. the unit digest trailer.
+    pub fn try_from_reader_v3<R: Read + Seek>(reader: &mut R, meta_info: &Rc<MetaUnit>) -> Result<Option<Self>> {
+        let unit_pos = reader.stream_position()?;
+        let unit_type = match reader.read_u8() {
+            Ok(unit_type) => unit_type,
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => {
+                reader.seek(SeekFrom::Start(unit_pos))?;
+                return Ok(None);
+            }
+            Err(e) => return Err(e.into()),
+        };
+        reader.seek(SeekFrom::Start(unit_pos))?;
+        if unit_type != UnitType::EntryMeta as u8 {
+            return Ok(None);
+        }
+
+        let unit_info = UnitInfoSection::from_reader(reader)?;
+        let data_pos = reader.stream_position()?;
+        reader.seek(SeekFrom::Current(unit_info.data_section_length as i64))?; //skip to the end of data section
+        let data_info = read_data_info_section::<EntryMetaDataInfo, R>(reader, meta_info)?;
+        let end_of_unit = reader.stream_position()?;
+        // Later versions may append fields to the records, only the known ones are read
+        if data_info.record_size < ENTRY_META_RECORD_SIZE {
+            return Err(ZdbError::invalid_data_format(format!("Entry metadata records of {} bytes are too short", data_info.record_size)));
+        }
+        let expected_length = data_info.entry_count * data_info.record_size as u64;
+        let mut records = Vec::with_capacity(expected_length.min(unit_info.data_section_length.saturating_mul(16)) as usize);
+        reader.seek(SeekFrom::Start(data_pos))?;
+        for _ in 0..unit_info.block_count {
+            records.extend_from_slice(&StorageBlock::from_reader_v3(reader, meta_info)?.data);
+        }
+        if records.len() as u64 != expected_length {
+            return Err(ZdbError::invalid_data_format(format!("Entry metadata has {} bytes, expected {}", records.len(), expected_length)));
+        }
+        reader.seek(SeekFrom::Start(end_of_unit))?;
+        Ok(Some(Self {
+            records,
+            record_size: data_info.record_size,
+            entry_count: data_info.entry_count,
+            tag_names: data_info.tag_names.split(',').filter(|name| !name.is_empty()).map(str::to_string).collect(),
+        }))
+    }
+
+    /// Gets the metadata of an entry.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if there is no entry with this number.
+    pub fn get(&self, entry_no: EntryNo) -> Result<EntryMetaExt> {
+        if entry_no < 0 || entry_no as u64 >= self.entry_count {
+            return Err(ZdbError::invalid_parameter(format!("Entry {} out of range, the dictionary has {} entries", entry_no, self.entry_count)));
+        }
+        let start = entry_no as usize * self.record_size as usize;
+        Ok(EntryMetaExt::from_record(&self.records[start..start + ENTRY_META_RECORD_SIZE as usize]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_entry_meta_record() {
+        let meta = EntryMetaExt { tags: 0b101, frequency_rank: 1234, pos: PartOfSpeech::Adverb };
+        let mut buffer = Vec::new();
+        meta.write_record(&mut buffer);
+        assert_eq!(buffer.len(), ENTRY_META_RECORD_SIZE as usize);
+        assert_eq!(EntryMetaExt::from_record(&buffer), meta);
+        assert_eq!(PartOfSpeech::from(200), PartOfSpeech::Other);
+        assert_eq!(PartOfSpeech::from_name("Adj."), Some(PartOfSpeech::Adjective));
+        assert_eq!(PartOfSpeech::from_name("gerundive"), None);
+    }
+}
@@ -2,6 +2,7 @@
 //!
 //! This module provides the core data structures for dictionary key management:
 //! - [`KeyIndex`]: Represents a single dictionary key with its metadata
+//! - [`CompactKeyIndex`]: A versioned, serializable form of [`KeyIndex`] for IPC
 //! - [`KeyBlock`]: A block of key indexes for efficient lookup
 //! - Entry number types and constants for key referencing
 //!
@@ -27,11 +28,12 @@ use byteorder::{BigEndian, LittleEndian, ReadBytesExt};
 use serde::{Deserialize, Serialize};
 
 use super::key_block_index::KeyBlockIndex;
+use crate::storage::key_unit::KEY_UNIT_CONTENT_LENGTHS;
 use crate::storage::meta_unit::{MetaUnit, ZdbVersion};
-use crate::storage::reader_helper::decode_bytes_to_string;
+use crate::storage::reader_helper::{decode_bytes_to_string, encode_string_to_bytes};
 use crate::utils::sort_key::get_sort_key;
 use crate::storage::storage_block::StorageBlock;
-use crate::utils::{binary_search_first, locale_compare, sort_key_compare, KeyComparable, RandomAccessable};
+use crate::utils::{binary_search_first, binary_search_first_position, locale_compare, sort_key_compare, KeyComparable, RandomAccessable};
 use crate::{Result, ZdbError};
 
 /// Type alias for dictionary entry numbers.
@@ -40,13 +42,20 @@ pub type EntryNo = i64;
 /// Constant representing an invalid entry number.
 pub const INVALID_ENTRY_NO: EntryNo = -1;
 
-/// Constant representing a union entry number (for merged entries).
-pub const UNION_ENTRY_NO: EntryNo = -2;
+/// Content prefix of a union entry, followed by the comma separated entry numbers of its members.
+///
+/// Union entries are created by the builder when `merge_duplicate_keys` is enabled. A union
+/// entry precedes the entries sharing its key, which remain ordinary entries.
+pub const UNION_PREFIX: &str = "@@@UNION=";
 
 /// Represents a single dictionary key with its associated metadata.
 ///
 /// This structure contains all information needed to locate and retrieve
 /// a dictionary entry's content.
+///
+/// Its serialized form follows the fields of the struct and isn't guaranteed to stay
+/// the same across versions, use [`CompactKeyIndex`] for payloads crossing a process
+/// or language boundary.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct KeyIndex {
     /// The dictionary key as a UTF-8 string
@@ -83,6 +92,94 @@ impl KeyComparable for KeyIndex {
     }
 }
 
+/// Version of the serialized form of [`CompactKeyIndex`], see [`CompactKeyIndexes`].
+pub const COMPACT_KEY_INDEX_SCHEMA_VERSION: u32 = 1;
+
+/// Serializable form of a [`KeyIndex`] without its raw and sort key bytes, for IPC and FFI
+/// payloads, e.g. search results sent to a front end.
+///
+/// # Stability
+///
+/// Fields are serialized as `key`, `entry_no` and `offset`. Within a schema version, see
+/// [`COMPACT_KEY_INDEX_SCHEMA_VERSION`], fields are never renamed, removed or retyped, and
+/// fields added later are optional so older payloads still deserialize. Any other change
+/// bumps the version.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CompactKeyIndex {
+    /// The dictionary key
+    pub key: String,
+    /// Entry number of the key
+    pub entry_no: EntryNo,
+    /// Offset of the content in the content data section, see [`KeyIndex::content_offset_in_source`]
+    pub offset: u64,
+}
+
+impl CompactKeyIndex {
+    /// Rebuilds the full [`KeyIndex`], encoding the key and computing its sort key as the
+    /// dictionary it came from does.
+    ///
+    /// # Arguments
+    ///
+    /// * `meta_info` - Metadata of the dictionary the key index came from
+    pub fn to_key_index(&self, meta_info: &MetaUnit) -> Result<KeyIndex> {
+        let key_raw = encode_string_to_bytes(&self.key, meta_info.encoding_obj)?;
+        let sort_key = get_sort_key(&key_raw, meta_info)?;
+        Ok(KeyIndex {
+            key: self.key.clone(),
+            key_raw,
+            sort_key,
+            content_offset_in_source: self.offset,
+            entry_no: self.entry_no,
+        })
+    }
+}
+
+impl From<&KeyIndex> for CompactKeyIndex {
+    fn from(key_index: &KeyIndex) -> Self {
+        Self { key: key_index.key.clone(), entry_no: key_index.entry_no, offset: key_index.content_offset_in_source }
+    }
+}
+
+impl From<KeyIndex> for CompactKeyIndex {
+    fn from(key_index: KeyIndex) -> Self {
+        Self { key: key_index.key, entry_no: key_index.entry_no, offset: key_index.content_offset_in_source }
+    }
+}
+
+/// A list of [`CompactKeyIndex`] tagged with the schema version it was serialized with.
+///
+/// Receivers check the version with [`into_indexes`](Self::into_indexes) before using the
+/// indexes, so a payload of a newer, incompatible schema fails instead of being misread.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CompactKeyIndexes {
+    /// Schema version of the indexes, [`COMPACT_KEY_INDEX_SCHEMA_VERSION`] when created
+    pub schema_version: u32,
+    pub indexes: Vec<CompactKeyIndex>,
+}
+
+impl CompactKeyIndexes {
+    /// Converts key indexes for serialization with the current schema version.
+    pub fn new<I: IntoIterator<Item = T>, T: Into<CompactKeyIndex>>(key_indexes: I) -> Self {
+        Self {
+            schema_version: COMPACT_KEY_INDEX_SCHEMA_VERSION,
+            indexes: key_indexes.into_iter().map(Into::into).collect(),
+        }
+    }
+
+    /// Returns the indexes of a deserialized payload.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `InvalidDataFormat` error if the payload has another schema version.
+    pub fn into_indexes(self) -> Result<Vec<CompactKeyIndex>> {
+        if self.schema_version != COMPACT_KEY_INDEX_SCHEMA_VERSION {
+            return Err(ZdbError::invalid_data_format(format!("Unsupported compact key index schema version {}, expected {}",
+                self.schema_version, COMPACT_KEY_INDEX_SCHEMA_VERSION)));
+        }
+        Ok(self.indexes)
+    }
+}
+
 /// A block of key indexes for efficient dictionary lookups.
 ///
 /// Key blocks group multiple key indexes together to reduce memory usage
@@ -95,6 +192,8 @@ pub struct KeyBlock {
     pub key_indexes: Vec<KeyIndex>,
     /// Metadata about the dictionary
     pub meta_info: Rc<MetaUnit>,
+    /// Content lengths of the entries, empty unless the key unit stores them, see [`KEY_UNIT_CONTENT_LENGTHS`]
+    pub content_lengths: Vec<u64>,
 }
 
 impl RandomAccessable<KeyIndex> for KeyBlock{
@@ -108,20 +207,21 @@ impl RandomAccessable<KeyIndex> for KeyBlock{
 
 fn key_str_from_cursor(cursor: &mut Cursor<&Vec<u8>>,meta_info: &MetaUnit) -> Result<(String, Vec<u8>)> {
     let start_pos=cursor.position();
-    let mut end_pos=start_pos;
+    let mut end_pos=None;
     while cursor.position()<cursor.get_ref().len() as u64 {
         if meta_info.db_info.is_utf16 {
             if cursor.read_u16::<LittleEndian>()?==0 {
-                end_pos=cursor.position()-2;
+                end_pos=Some(cursor.position()-2);
                 break;
             }
         }else{
             if cursor.read_u8()?==0 {
-                end_pos=cursor.position()-1;
+                end_pos=Some(cursor.position()-1);
                 break;
             }   
         }
     }
+    let end_pos = end_pos.ok_or_else(|| ZdbError::invalid_data_format(format!("Key at offset {} of key block isn't terminated", start_pos)))?;
     let key_bytes= &cursor.get_ref()[start_pos as usize..end_pos as usize];
     return Ok((decode_bytes_to_string(key_bytes, &meta_info.encoding_obj)?, key_bytes.to_vec()));
 }
@@ -130,17 +230,33 @@ impl KeyBlock {
 
     //TODO it's very time consuming to get sort_key for each key, need to optimize it
     pub fn from_reader<R: Read+Seek>(reader: &mut R, meta_info: &Rc<MetaUnit>, key_block_index: &KeyBlockIndex) -> Result<Self> {
+        Self::from_reader_with_minor_version(reader, meta_info, key_block_index, 0)
+    }
+
+    /// Reads a key block of a key unit with the given minor version, see [`KeyDataInfo::minor_version`](crate::storage::key_unit::KeyDataInfo::minor_version).
+    pub fn from_reader_with_minor_version<R: Read+Seek>(reader: &mut R, meta_info: &Rc<MetaUnit>, key_block_index: &KeyBlockIndex, minor_version: u32) -> Result<Self> {
+        let has_content_lengths = minor_version == KEY_UNIT_CONTENT_LENGTHS;
         let block_data = match meta_info.version {
             ZdbVersion::V3 => StorageBlock::from_reader_v3(reader, &meta_info)?,
             ZdbVersion::V2 | ZdbVersion::V1 => StorageBlock::from_reader_v1_v2(reader, &meta_info, &meta_info.crypto_key, key_block_index.block_length as u32, key_block_index.raw_data_length as u32)?,
         };
+        // Every entry takes at least its content offset and a key terminator
+        let min_entry_size = if meta_info.is_v1() { 4 } else { 8 } + if has_content_lengths { 8 } else { 0 } + if meta_info.db_info.is_utf16 { 2 } else { 1 };
+        if key_block_index.entry_count_in_block > (block_data.data.len() / min_entry_size) as u64 {
+            return Err(ZdbError::invalid_data_format(format!("Key block of {} bytes can't hold {} entries",
+                block_data.data.len(), key_block_index.entry_count_in_block)));
+        }
         let mut key_indexes = Vec::with_capacity(key_block_index.entry_count_in_block as usize);
+        let mut content_lengths = Vec::new();
         let mut cursor = Cursor::new(&block_data.data);
         for i in 0..key_block_index.entry_count_in_block {
             let content_offset_in_source = match meta_info.version {    
                 ZdbVersion::V3|ZdbVersion::V2 => cursor.read_u64::<BigEndian>()?,
                 ZdbVersion::V1=>cursor.read_u32::<BigEndian>()? as u64,
             };
+            if has_content_lengths {
+                content_lengths.push(cursor.read_u64::<BigEndian>()?);
+            }
             let (key, key_raw) = key_str_from_cursor(&mut cursor, &meta_info)?;
             let sort_key = get_sort_key(&key_raw, &meta_info)?;
             let key_index=KeyIndex{
@@ -151,7 +267,14 @@ impl KeyBlock {
             };
             key_indexes.push(key_index);
         }
-        Ok(Self { key_indexes, key_block_index: key_block_index.clone(), meta_info: meta_info.clone() })
+        Ok(Self { key_indexes, key_block_index: key_block_index.clone(), meta_info: meta_info.clone(), content_lengths })
+    }
+
+    /// Approximate number of bytes held by this block and its key indexes.
+    pub fn memory_size(&self) -> usize {
+        std::mem::size_of::<Self>() + self.key_block_index.memory_size() + self.content_lengths.len() * 8 + self.key_indexes.iter()
+            .map(|index| std::mem::size_of::<KeyIndex>() + index.key.len() + index.key_raw.len() + index.sort_key.len())
+            .sum::<usize>()
     }
 
     pub fn find_index(&self, key: &str, prefix_match: bool, partial_match: bool) -> Result<Option<KeyIndex>> {
@@ -159,6 +282,18 @@ impl KeyBlock {
         binary_search_first(self, key, &meta_info, prefix_match, partial_match)
     }
 
+    /// Finds the position in [`key_indexes`](Self::key_indexes) of the first entry matching
+    /// `key`, see [`find_index`](Self::find_index).
+    pub fn find_position(&self, key: &str, prefix_match: bool, partial_match: bool) -> Result<Option<usize>> {
+        binary_search_first_position(self, key, &self.meta_info, prefix_match, partial_match)
+    }
+
+    /// Gets the stored content length of an entry, `None` if the key unit doesn't store lengths.
+    pub fn get_content_length(&self, entry_no: EntryNo) -> Option<u64> {
+        let index = entry_no.checked_sub(self.key_block_index.first_entry_no_in_block)?;
+        self.content_lengths.get(usize::try_from(index).ok()?).copied()
+    }
+
     pub fn get_index(&self, entry_no: EntryNo) -> Result<KeyIndex> {
         if entry_no < self.key_block_index.first_entry_no_in_block
             || entry_no >= self.key_block_index.first_entry_no_in_block + self.key_block_index.entry_count_in_block as EntryNo {
@@ -9,7 +9,7 @@ use crate::storage::meta_unit::{MetaUnit, ZdbVersion};
 use crate::storage::reader_helper::decode_bytes_to_string;
 use crate::utils::sort_key::get_sort_key;
 use crate::utils::{key_compare, KeyComparable};
-use crate::Result;
+use crate::{Result, ZdbError};
 
 #[derive(Debug, Clone, Default)]
 pub struct KeyBlockIndex {
@@ -24,6 +24,13 @@ pub struct KeyBlockIndex {
     pub first_entry_no_in_block: EntryNo,
 }
 
+impl KeyBlockIndex {
+    /// Approximate number of bytes held by this index entry.
+    pub fn memory_size(&self) -> usize {
+        std::mem::size_of::<Self>() + self.first_key.len() + self.last_key.len() + self.first_sort_key.len() + self.last_sort_key.len()
+    }
+}
+
 impl KeyComparable for KeyBlockIndex {
     fn compare_with(&self, other:&str, other_sort_key:&[u8], prefix_match: bool, meta_info: &MetaUnit) -> Result<Ordering> {
         match key_compare(&self.first_key, &self.first_sort_key, other, other_sort_key, prefix_match, meta_info) {
@@ -54,14 +61,47 @@ fn read_key<R:Read>(reader: &mut R, meta_info: &MetaUnit)->Result<Vec<u8>> {
     //Key length dosen't include the terminating zero for all versions
     //We need to read the terminating zero for V2 and V3.
     let mut buffer = read_exact_to_vec(reader, length+has_terminating_zero)?;
-    // if length<has_terminating_zero {
-    //     return Err(ZdbError::InvalidDataFormat(format!("Key length {} is less than terminating zero size {}", length, has_terminating_zero)));
-    // }
+    // A key that isn't followed by a zero means the length is wrong, the rest would be misparsed
+    if buffer[length..].iter().any(|&byte| byte != 0) {
+        return Err(ZdbError::invalid_data_format(format!("Key of {} bytes in key block index isn't terminated", length)));
+    }
     buffer.truncate(length);
     Ok(buffer)
 }
 
+/// Reads a front-coded key: the length of the prefix shared with `prev_key` and the
+/// length of the remaining suffix, both u16 in bytes, followed by the suffix bytes.
+fn read_front_coded_key<R:Read>(reader: &mut R, prev_key: &[u8]) -> Result<Vec<u8>> {
+    let shared_len = reader.read_u16::<BigEndian>()? as usize;
+    let suffix_len = reader.read_u16::<BigEndian>()? as usize;
+    if shared_len > prev_key.len() {
+        return Err(ZdbError::invalid_data_format(format!("Shared key prefix length {} exceeds previous key length {}", shared_len, prev_key.len())));
+    }
+    let mut key = Vec::with_capacity(shared_len + suffix_len);
+    key.extend_from_slice(&prev_key[..shared_len]);
+    key.extend_from_slice(&read_exact_to_vec(reader, suffix_len)?);
+    Ok(key)
+}
+
 impl KeyBlockIndex {
+    /// Reads a V3 block index entry whose keys are front-coded.
+    ///
+    /// The first key shares its prefix with `prev_last_key`, the last key of the previous entry,
+    /// and the last key shares its prefix with the first key.
+    ///
+    /// # Returns
+    ///
+    /// Returns the entry and the raw bytes of its last key, to be passed for the next entry.
+    pub fn from_reader_front_coded<R:Read>(reader: &mut R, meta_info: &MetaUnit, prev_last_key: &[u8]) -> Result<(Self, Vec<u8>)> {
+        let entry_count = reader.read_u32::<BigEndian>()? as u64;
+        let first_key = read_front_coded_key(reader, prev_last_key)?;
+        let last_key = read_front_coded_key(reader, &first_key)?;
+        let block_length = reader.read_u32::<BigEndian>()? as u64;
+        let raw_data_length = reader.read_u32::<BigEndian>()? as u64;
+        let entry = Self::from_raw_keys(entry_count, &first_key, &last_key, block_length, raw_data_length, meta_info)?;
+        Ok((entry, last_key))
+    }
+
     pub fn from_reader<R:Read>(reader: &mut R, meta_info: &MetaUnit) -> Result<Self> {
         let (entry_count, first_key, last_key, block_length, raw_data_length) = match meta_info.version {
             ZdbVersion::V3|ZdbVersion::V1 => {
@@ -83,10 +123,14 @@ impl KeyBlockIndex {
                 )
             }
         };
-        let  first_sort_key=get_sort_key(&first_key, meta_info)?;
-        let  last_sort_key=get_sort_key(&last_key, meta_info)?;
-        let first_key = decode_bytes_to_string(&first_key, &meta_info.encoding_obj)?;
-        let last_key = decode_bytes_to_string(&last_key, &meta_info.encoding_obj)?;
+        Self::from_raw_keys(entry_count, &first_key, &last_key, block_length, raw_data_length, meta_info)
+    }
+
+    fn from_raw_keys(entry_count: u64, first_key: &[u8], last_key: &[u8], block_length: u64, raw_data_length: u64, meta_info: &MetaUnit) -> Result<Self> {
+        let first_sort_key = get_sort_key(first_key, meta_info)?;
+        let last_sort_key = get_sort_key(last_key, meta_info)?;
+        let first_key = decode_bytes_to_string(first_key, meta_info.encoding_obj)?;
+        let last_key = decode_bytes_to_string(last_key, meta_info.encoding_obj)?;
 
         Ok(Self {
             entry_count_in_block: entry_count,
@@ -101,3 +145,31 @@ impl KeyBlockIndex {
         })
     }
 }
+
+// The meta unit needs a collator, which needs compiled collation data
+#[cfg(all(test, any(feature = "icu", feature = "rust-icu")))]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::crypto::SecretBytes;
+    use crate::storage::meta_unit::DbInfo;
+    use crate::utils::icu_wrapper::shared_collator;
+
+    #[test]
+    fn test_read_key_length() {
+        let meta_info = MetaUnit {
+            db_info: DbInfo { version: ZdbVersion::V3, ..Default::default() },
+            crypto_key: SecretBytes::default(),
+            content_data_total_length: 0,
+            version: ZdbVersion::V3,
+            collator: shared_collator("root").unwrap(),
+            encoding_obj: encoding_rs::UTF_8,
+            raw_header_xml: String::new(),
+        };
+        assert_eq!(read_key(&mut Cursor::new(b"\x00\x03abc\x00next"), &meta_info).unwrap(), b"abc");
+        // A length running past the terminator, or past the data
+        assert!(matches!(read_key(&mut Cursor::new(b"\x00\x05abc\x00next"), &meta_info), Err(ZdbError::InvalidDataFormat { .. })));
+        assert!(read_key(&mut Cursor::new(b"\xff\xffabc\x00"), &meta_info).is_err());
+    }
+}
@@ -13,7 +13,7 @@ use super::key_block_index::KeyBlockIndex;
 use crate::storage::meta_unit::MetaUnit;
 use crate::storage::storage_block::StorageBlock;
 use crate::storage::unit_base::{read_data_info_section, UnitInfoSection};
-use crate::utils::{binary_search_first, RandomAccessable};
+use crate::utils::{binary_search_first, binary_search_first_position, RandomAccessable};
 use crate::{Result, ZdbError};
 
 #[derive(Debug, Clone, Deserialize, Serialize, Default)]
@@ -25,14 +25,38 @@ pub struct KeyBlockIndexDataInfo {
     pub encoding: String,
     #[serde(rename = "@locale", default)]
     pub locale_id: String,
+    /// Minor version of the entry layout, 0 for plain keys, see [`KEY_BLOCK_INDEX_FRONT_CODED`]
+    #[serde(rename = "@minorVersion", default, skip_serializing_if = "is_zero")]
+    pub minor_version: u32,
 }
 // <KeyBlockIndex BlockCount="5" encoding="utf-8" locale="zh-u-co-pinyin" />
 
+/// Minor version of key block index units whose keys are front-coded.
+///
+/// Each key is stored as the length of the prefix it shares with the preceding key
+/// and the remaining suffix, see [`KeyBlockIndex::from_reader_front_coded`].
+pub const KEY_BLOCK_INDEX_FRONT_CODED: u32 = 1;
+
+fn is_zero(value: &u32) -> bool {
+    *value == 0
+}
+
 pub struct KeyBlockIndexUnit {
+    /// Block indexes, empty until loaded if the unit was opened lazily
     pub block_indexes: Vec<KeyBlockIndex>,
     pub meta_info: Rc<MetaUnit>,
     pub total_key_count: u64,
     pub key_data_unit_size: u64, //Only used in V1 and V2
+    lazy_source: Option<LazyBlockIndexes>,
+    loaded: bool,
+}
+
+/// Location of the data section of a lazily opened V3 unit.
+struct LazyBlockIndexes {
+    data_offset: u64,
+    data_info: KeyBlockIndexDataInfo,
+    /// Length of the decoded data section, from the header of its storage block
+    decoded_length: u64,
 }
 
 impl RandomAccessable<KeyBlockIndex> for KeyBlockIndexUnit{
@@ -49,6 +73,12 @@ impl KeyBlockIndexUnit {
         let meta_info = self.meta_info.clone();
         binary_search_first(self, key, &meta_info, prefix_match, partial_match)
     }
+
+    /// Finds the position in [`block_indexes`](Self::block_indexes) of the first block that
+    /// may hold `key`, see [`find_index`](Self::find_index).
+    pub fn find_position(&self, key: &str, prefix_match: bool, partial_match: bool) -> Result<Option<usize>> {
+        binary_search_first_position(self, key, &self.meta_info, prefix_match, partial_match)
+    }
     pub fn get_index(&self, entry_no: EntryNo) -> Result<&KeyBlockIndex> {
         let mut left = 0;
         let mut right = self.block_indexes.len();
@@ -78,7 +108,7 @@ impl KeyBlockIndexUnit {
         if meta_info.is_v2(){
 
             if !meta_info.crypto_key.is_empty() && meta_info.db_info.encryption_type.is_para_encrypted() {
-                let mut decryptor = Salsa20Encryptor::new(meta_info.crypto_key.as_slice(), &vec![0;8]);
+                let mut decryptor = Salsa20Encryptor::new(meta_info.crypto_key.expose(), &vec![0;8]);
                 let mut decrypted_idx_para = vec![0; idx_para.len()];
                 decryptor.decrypt(&idx_para, &mut decrypted_idx_para)?;
                 idx_para = decrypted_idx_para;
@@ -103,19 +133,33 @@ impl KeyBlockIndexUnit {
                 let mut decryptor = SimpleEncryptor::new(&ripemd_digest(&enc_key)?, &[0;8]);
                 decryptor.inplace_decrypt(&mut raw_data[8..])?;
             }
-            StorageBlock::decode_block(&mut raw_data, &meta_info.crypto_key, original_data_length as u32)?.data
+            StorageBlock::decode_block(&mut raw_data, &meta_info.crypto_key, original_data_length as u32, 0)?.data
         } else {
             raw_data
         };
         Ok(block_index_data)
     }
 
-    fn read_block_index_entries(block_data: &Vec<u8>, meta_info: &MetaUnit, block_count: u32) -> Result<(Vec<KeyBlockIndex>, u64)> {
+    fn read_block_index_entries(block_data: &Vec<u8>, meta_info: &MetaUnit, block_count: u32, minor_version: u32) -> Result<(Vec<KeyBlockIndex>, u64)> {
         let mut cursor = Cursor::new(block_data); 
         let mut block_index_entries:Vec<KeyBlockIndex> = Vec::with_capacity(block_count as usize);
-        for _ in 0..block_count {
-            let entry = KeyBlockIndex::from_reader(&mut cursor, &meta_info)?;
-            block_index_entries.push(entry);
+        match minor_version {
+            0 => {
+                for _ in 0..block_count {
+                    let entry = KeyBlockIndex::from_reader(&mut cursor, &meta_info)?;
+                    block_index_entries.push(entry);
+                }
+            }
+            KEY_BLOCK_INDEX_FRONT_CODED => {
+                let mut prev_last_key = Vec::new();
+                for _ in 0..block_count {
+                    let (entry, last_key) = KeyBlockIndex::from_reader_front_coded(&mut cursor, meta_info, &prev_last_key)?;
+                    block_index_entries.push(entry);
+                    prev_last_key = last_key;
+                }
+            }
+            _ => return Err(ZdbError::unsupported_feature(format!("key block index minor version {}", minor_version),
+                crate::format::newer_version_hint(&format!("minor versions 0 to {}", KEY_BLOCK_INDEX_FRONT_CODED)))),
         }
     
         let mut block_offset_in_unit = 0;
@@ -129,6 +173,14 @@ impl KeyBlockIndexUnit {
         Ok((block_index_entries, first_entry_no_in_block as u64))
     }
 
+    /// Reads the record count of a V1/V2 file from the unit parameters, without decoding the block indexes.
+    pub fn read_record_count_v1_v2<R: Read+Seek>(reader: &mut R, meta_info: &MetaUnit) -> Result<u64> {
+        let idx_para = Self::read_idx_para_v1_v2(reader, meta_info)?;
+        let mut idx_para_reader = UintReader::new(Cursor::new(&idx_para), meta_info.version);
+        let _key_block_count = idx_para_reader.read_uint()?;
+        idx_para_reader.read_uint()
+    }
+
     pub fn from_reader_v1_v2<R: Read+Seek>(reader: &mut R, meta_info: &Rc<MetaUnit>) -> Result<Self> {
         let idx_para = Self::read_idx_para_v1_v2(reader, &meta_info)?;
         let mut idx_para_reader = UintReader::new(Cursor::new(&idx_para), meta_info.version);
@@ -140,15 +192,123 @@ impl KeyBlockIndexUnit {
         drop(idx_para_reader);
 
         let block_index_data = Self::read_block_index_data(reader, &meta_info, key_index_section_comp_size, key_index_section_orig_size)?;
-        let (block_index_entries, total_key_count) = Self::read_block_index_entries(&block_index_data, &meta_info, key_block_count as u32)?;
+        let (block_index_entries, total_key_count) = Self::read_block_index_entries(&block_index_data, &meta_info, key_block_count as u32, 0)?;
 
         if total_key_count != record_count {
             return Err(ZdbError::invalid_data_format(format!("Total key count {} does not match record count {}", total_key_count, record_count)));
         }
-        Ok(Self { block_indexes: block_index_entries, meta_info: meta_info.clone(), total_key_count, key_data_unit_size: key_data_section_comp_size })
+        Ok(Self { block_indexes: block_index_entries, meta_info: meta_info.clone(), total_key_count, key_data_unit_size: key_data_section_comp_size, lazy_source: None, loaded: true })
     }
     
     pub fn from_reader_v3<R: Read+Seek>(reader: &mut R, meta_info: &Rc<MetaUnit>) -> crate::Result<Self> {
+        let (block_indexes, total_key_count) = Self::read_block_indexes_v3(reader, meta_info)?;
+        Ok(Self::from_block_indexes(block_indexes, meta_info, total_key_count))
+    }
+
+    /// Decodes the block indexes of a V3 unit and leaves the reader at the end of the unit.
+    ///
+    /// Unlike [`from_reader_v3`](Self::from_reader_v3) it doesn't need the shared meta unit,
+    /// so it can run on another thread, see [`from_block_indexes`](Self::from_block_indexes).
+    ///
+    /// # Returns
+    ///
+    /// Returns the block indexes and the total key count.
+    pub fn read_block_indexes_v3<R: Read+Seek>(reader: &mut R, meta_info: &MetaUnit) -> crate::Result<(Vec<KeyBlockIndex>, u64)> {
+        let (data_offset, data_info, end_of_unit) = Self::read_unit_header_v3(reader, meta_info)?;
+        reader.seek(SeekFrom::Start(data_offset))?;
+        let storage_block = StorageBlock::from_reader_v3(reader, meta_info)?;
+        let decoded = Self::read_block_index_entries(&storage_block.data, meta_info, data_info.block_count, data_info.minor_version)?;
+        reader.seek(SeekFrom::Start(end_of_unit))?;
+        Ok(decoded)
+    }
+
+    /// Creates a loaded V3 unit from block indexes decoded by [`read_block_indexes_v3`](Self::read_block_indexes_v3).
+    pub fn from_block_indexes(block_indexes: Vec<KeyBlockIndex>, meta_info: &Rc<MetaUnit>, total_key_count: u64) -> Self {
+        Self {
+            block_indexes,
+            meta_info: meta_info.clone(),
+            total_key_count,
+            key_data_unit_size: 0, //Not used in V3
+            lazy_source: None,
+            loaded: true,
+        }
+    }
+
+    /// Reads only the unit info of a V3 unit, the block indexes are decoded by [`load`](Self::load).
+    ///
+    /// The data section is a single storage block, so it is skipped entirely at open and
+    /// decoded as a whole on first use.
+    ///
+    /// # Arguments
+    ///
+    /// * `reader` - The reader positioned at the start of the unit
+    /// * `meta_info` - The meta unit of the file
+    /// * `total_key_count` - The number of keys recorded in the other units, checked when the unit is loaded
+    pub fn from_reader_v3_lazy<R: Read+Seek>(reader: &mut R, meta_info: &Rc<MetaUnit>, total_key_count: u64) -> crate::Result<Self> {
+        let (data_offset, data_info, end_of_unit) = Self::read_unit_header_v3(reader, meta_info)?;
+        reader.seek(SeekFrom::Start(data_offset))?;
+        let decoded_length = reader.read_u32::<BigEndian>()? as u64;
+        reader.seek(SeekFrom::Start(end_of_unit))?;
+        Ok(Self {
+            block_indexes: Vec::new(),
+            meta_info: meta_info.clone(),
+            total_key_count,
+            key_data_unit_size: 0, //Not used in V3
+            lazy_source: Some(LazyBlockIndexes { data_offset, data_info, decoded_length }),
+            loaded: false,
+        })
+    }
+
+    /// Whether the block indexes are decoded.
+    pub fn is_loaded(&self) -> bool {
+        self.loaded
+    }
+
+    /// Estimated number of bytes held by the block indexes once decoded, from the block
+    /// count and the length of the data section. `None` if the unit isn't lazily opened
+    /// or is already loaded.
+    pub fn declared_memory_size(&self) -> Option<u64> {
+        let source = self.lazy_source.as_ref().filter(|_| !self.loaded)?;
+        Some(source.data_info.block_count as u64 * std::mem::size_of::<KeyBlockIndex>() as u64 + source.decoded_length)
+    }
+
+    /// Decodes the block indexes of a lazily opened unit, does nothing if they are loaded.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the data section can't be decoded or its key count doesn't match
+    /// the count given at open. The unit stays unloaded in that case.
+    pub fn load<R: Read+Seek>(&mut self, reader: &mut R) -> crate::Result<()> {
+        let Some(source) = self.lazy_source.as_ref().filter(|_| !self.loaded) else {
+            return Ok(());
+        };
+        reader.seek(SeekFrom::Start(source.data_offset))?;
+        let storage_block = StorageBlock::from_reader_v3(reader, &self.meta_info)?;
+        let (block_index_entries, total_key_count) = Self::read_block_index_entries(&storage_block.data, &self.meta_info, source.data_info.block_count, source.data_info.minor_version)?;
+        if total_key_count != self.total_key_count {
+            return Err(ZdbError::invalid_data_format("Record count mismatch"));
+        }
+        self.block_indexes = block_index_entries;
+        self.loaded = true;
+        Ok(())
+    }
+
+    /// Releases the block indexes of a lazily opened unit, they are decoded again by the next [`load`](Self::load).
+    ///
+    /// Does nothing for units loaded at open.
+    pub fn unload(&mut self) {
+        if self.lazy_source.is_some() {
+            self.block_indexes = Vec::new();
+            self.loaded = false;
+        }
+    }
+
+    /// Reads the unit info and data info of a V3 unit.
+    ///
+    /// # Returns
+    ///
+    /// Returns the offset of the data section, the data info and the offset of the end of the unit.
+    fn read_unit_header_v3<R: Read+Seek>(reader: &mut R, meta_info: &MetaUnit) -> crate::Result<(u64, KeyBlockIndexDataInfo, u64)> {
         let unit_info = UnitInfoSection::from_reader(reader)?;
         //Need to read data_info first for encoding information.
         let cur_pos=reader.seek( SeekFrom::Current(0))?;
@@ -161,16 +321,6 @@ impl KeyBlockIndexUnit {
             //     return Err(ZdbError::invalid_parameter("Empty locale ID"));
             // }
         }
-        //Rollback to the beginning of data section
-        reader.seek(SeekFrom::Start(cur_pos))?;
-        let storage_block = StorageBlock::from_reader_v3(reader, &meta_info)?;
-        let (block_index_entries, total_key_count) = Self::read_block_index_entries(&storage_block.data, &meta_info, data_info.block_count)?;
-        reader.seek(SeekFrom::Start(end_of_unit))?;
-        Ok(Self { 
-            block_indexes: block_index_entries, 
-            meta_info: meta_info.clone(),
-            total_key_count,
-            key_data_unit_size: 0, //Not used in V3
-        })
+        Ok((cur_pos, data_info, end_of_unit))
     }
 }
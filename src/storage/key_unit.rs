@@ -1,4 +1,4 @@
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
 use std::io::{Read, Seek, SeekFrom};
 use std::num::NonZeroUsize;
 use std::rc::Rc;
@@ -11,6 +11,7 @@ use crate::storage::key_block_index::KeyBlockIndex;
 use crate::storage::key_block_index_unit::KeyBlockIndexUnit;
 use crate::storage::meta_unit::MetaUnit;
 use crate::storage::unit_base::{read_data_info_section, UnitInfoSection};
+use crate::ZdbError;
 
 #[derive(Debug, Clone, Deserialize, Serialize, Default)]
 #[serde(rename = "KeyData")]
@@ -21,14 +22,32 @@ pub struct KeyDataInfo {
     pub encoding: String,
     #[serde(rename = "@locale", default)]
     pub locale_id: String,
+    /// Minor version of the entry layout, 0 for plain entries, see [`KEY_UNIT_CONTENT_LENGTHS`]
+    #[serde(rename = "@minorVersion", default, skip_serializing_if = "is_zero")]
+    pub minor_version: u32,
 }
 //<KeyData keyCount="123" encoding="utf-8" locale="zh-u-co-pinyin" />
 
+/// Minor version of key units storing the content length of every entry.
+///
+/// Each entry is stored as its content offset, its content length and its key. Files
+/// whose content isn't in key order, e.g. re-sorted by [`ZDBBuilder::resort`](crate::builder::ZDBBuilder::resort),
+/// use it, since the length of an entry can't be derived from the offset of the next one then.
+pub const KEY_UNIT_CONTENT_LENGTHS: u32 = 1;
+
+fn is_zero(value: &u32) -> bool {
+    *value == 0
+}
+
 pub struct KeyUnit {
     pub total_key_count: u64,
     pub key_data_offset: u64,
     pub block_cache: RefCell<LruCache<u64, Rc<RefCell<KeyBlock>>>>,
     pub meta_info: Rc<MetaUnit>,
+    /// Minor version of the entry layout, see [`KeyDataInfo::minor_version`]
+    pub minor_version: u32,
+    /// Bytes held by the blocks in `block_cache`, kept up to date as blocks are cached and evicted
+    cache_memory: Cell<u64>,
 }
 
 
@@ -43,6 +62,8 @@ impl KeyUnit {
             block_cache: RefCell::new(LruCache::new(NonZeroUsize::new(16).unwrap())), 
             meta_info: meta_info.clone(),
             key_data_offset,
+            minor_version: 0,
+            cache_memory: Cell::new(0),
         })
     }
     pub fn from_reader_v3<R: Read+Seek>(reader: &mut R, meta_info: &Rc<MetaUnit>) -> crate::Result<Self> {
@@ -57,12 +78,18 @@ impl KeyUnit {
             //     return Err(ZdbError::invalid_parameter("Empty locale ID"));
             // }
         }
+        if data_info.minor_version > KEY_UNIT_CONTENT_LENGTHS {
+            return Err(ZdbError::unsupported_feature(format!("key unit minor version {}", data_info.minor_version),
+                crate::format::newer_version_hint(&format!("minor versions 0 to {}", KEY_UNIT_CONTENT_LENGTHS))));
+        }
         let key_count = data_info.key_count;
         Ok(Self { 
             total_key_count: key_count, 
             block_cache: RefCell::new(LruCache::new(NonZeroUsize::new(16).unwrap())), 
             meta_info: meta_info.clone(),
             key_data_offset,
+            minor_version: data_info.minor_version,
+            cache_memory: Cell::new(0),
         })
     }
 
@@ -76,9 +103,29 @@ impl KeyUnit {
             return Ok(Rc::clone(key_block));
         }
         reader.seek(SeekFrom::Start(block_offset + self.key_data_offset))?;
-        let key_block = Rc::new(RefCell::new(KeyBlock::from_reader(reader, &self.meta_info, key_block_index)?));
-        self.block_cache.borrow_mut().put(block_offset, key_block.clone());
+        let key_block = Rc::new(RefCell::new(KeyBlock::from_reader_with_minor_version(reader, &self.meta_info, key_block_index, self.minor_version)?));
+        let mut cache_memory = self.cache_memory.get() + key_block.borrow().memory_size() as u64;
+        if let Some((_, evicted)) = self.block_cache.borrow_mut().push(block_offset, key_block.clone()) {
+            cache_memory -= evicted.borrow().memory_size() as u64;
+        }
+        self.cache_memory.set(cache_memory);
         Ok(key_block)
     }
+
+    /// Bytes held by the cached key blocks.
+    pub fn cache_memory(&self) -> u64 {
+        self.cache_memory.get()
+    }
+
+    /// Evicts the least recently used key block, returns false if the cache is empty.
+    pub fn evict_lru_block(&self) -> bool {
+        match self.block_cache.borrow_mut().pop_lru() {
+            Some((_, evicted)) => {
+                self.cache_memory.set(self.cache_memory.get() - evicted.borrow().memory_size() as u64);
+                true
+            }
+            None => false,
+        }
+    }
 }
         
@@ -21,18 +21,23 @@
 //! # Ok::<(), Box<dyn std::error::Error>>(())
 //! ```
 
+use std::collections::{BTreeMap, HashMap};
 use std::io::{Read, Seek};
-use std::rc::Rc;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 use byteorder::{BigEndian, ReadBytesExt};
 use encoding_rs::Encoding;
 use quick_xml::events::Event;
 use serde::{Deserialize, Serialize};
 
-use crate::crypto::digest::{fast_hash_digest, ripemd_digest};
+use crate::crypto::digest::{ripemd_digest, DigestAlgorithm};
 use crate::crypto::encryption::decrypt_salsa20;
-use crate::utils::icu_wrapper::UCollator;
+use crate::crypto::secret::SecretBytes;
+use crate::utils::icu_wrapper::{shared_collator, UCollator};
+use crate::utils::key_normalization::KeyNormalization;
 use crate::storage::reader_helper::{decode_bytes_to_string, get_encoding_object_by_label};
+use crate::error::LicenseErrorKind;
 use crate::{Result, ZdbError};
 
 /// ZDB file format version.
@@ -75,7 +80,8 @@ impl ZdbVersion {
             1 => ZdbVersion::V1,
             2 => ZdbVersion::V2,
             3 => ZdbVersion::V3,
-            _ => return Err(ZdbError::invalid_data_format(format!("Unsupported engine version: {}", version))),
+            _ => return Err(ZdbError::unsupported_feature(format!("engine version {}.{}", version / 100, version % 100 / 10),
+                crate::format::newer_version_hint("engine versions 1.0 to 3.x"))),
         };
         Ok(version)
     }
@@ -218,7 +224,19 @@ pub struct DbInfo {
     //For version 3.0
     pub uuid: String,
     pub locale_id: String,
+    pub key_digest: String,
     pub content_type: ContentType,
+    pub key_normalization: KeyNormalization,
+    /// MIME types of resources by lower case extension, from the `MediaTypes` attribute
+    pub media_types: HashMap<String, String>,
+    /// How entries were grouped into content blocks, from the `ContentBlockLayout` attribute, for diagnostics
+    pub content_block_layout: String,
+    /// Expansions of the labels used in the content, from the `Labels` attribute, see [`LabelExpander`](crate::utils::LabelExpander)
+    pub labels: BTreeMap<String, String>,
+    /// Classes of the elements left out of entry previews, from the `PreviewSkipClasses` attribute, see [`PreviewOptions`](crate::utils::PreviewOptions)
+    pub preview_skip_classes: Vec<String>,
+    /// File name of the companion file holding the content blocks, from the `ContentFile` attribute, empty if they're in the dictionary
+    pub content_file: String,
     
     //For version <3.0
     pub encryption_type: KeyBlockIndexEncrytionType, //Only used in version <300
@@ -256,6 +274,26 @@ fn get_node_attr_u32(attrs: &[(String, String)], key: &str) -> u32 {
     get_node_attr_str(attrs, key).parse::<u32>().unwrap_or_default()
 }
 
+/// Parses the JSON object of labels and expansions, a malformed table is ignored.
+fn parse_labels(value: &str) -> BTreeMap<String, String> {
+    if value.is_empty() {
+        return BTreeMap::new();
+    }
+    serde_json::from_str(value).unwrap_or_else(|e| {
+        log::warn!("Label table in the header can't be parsed: {}", e);
+        BTreeMap::new()
+    })
+}
+
+/// Parses `ext=mime;ext=mime` pairs, skipping malformed ones.
+fn parse_media_types(value: &str) -> HashMap<String, String> {
+    value.split(';')
+        .filter_map(|pair| pair.split_once('='))
+        .map(|(extension, mime)| (extension.trim().to_lowercase(), mime.trim().to_string()))
+        .filter(|(extension, mime)| !extension.is_empty() && !mime.is_empty())
+        .collect()
+}
+
 fn generate_locale_id(encoding_label: &str, key_case_sensitive:bool, strip_key:bool) -> String {
     let mut locale_id = String::new();
     match encoding_label.to_lowercase().as_str(){
@@ -275,6 +313,11 @@ fn generate_locale_id(encoding_label: &str, key_case_sensitive:bool, strip_key:b
     locale_id
 }
 impl DbInfo {
+    /// Reads the header of a ZDB file, without decoding any key or checking the license.
+    pub fn from_reader<R: Read>(reader: &mut R) -> Result<Self> {
+        DbInfo::from_xml(&read_cstr_with_crc(reader)?)
+    }
+
     pub fn from_xml(xml: &str) -> Result<Self> {
         let mut db_info = DbInfo::default();
         let mut reader = quick_xml::Reader::from_str(xml);
@@ -301,9 +344,12 @@ impl DbInfo {
                             let key = std::str::from_utf8(attr.key.as_ref())
                                 .map_err(|e| ZdbError::invalid_data_format(format!("Invalid UTF-8 in attribute key: {}", e)))?
                                 .to_string();
-                            let value = std::str::from_utf8(attr.value.as_ref())
-                                .map_err(|e| ZdbError::invalid_data_format(format!("Invalid UTF-8 in attribute value: {}", e)))?
-                                .to_string();
+                            let raw_value = std::str::from_utf8(attr.value.as_ref())
+                                .map_err(|e| ZdbError::invalid_data_format(format!("Invalid UTF-8 in attribute value: {}", e)))?;
+                            // Headers written by other tools may contain HTML entities unknown to XML, those values are kept as is
+                            let value = quick_xml::escape::unescape(raw_value)
+                                .map(|value| value.into_owned())
+                                .unwrap_or_else(|_| raw_value.to_string());
                             root_attrs.push((key, value));
                         }
                         break;
@@ -321,6 +367,15 @@ impl DbInfo {
         db_info.version = ZdbVersion::from_version_number((get_node_attr_str(&root_attrs, "RequiredEngineVersion").parse::<f32>().unwrap_or_default()*100.0) as u32)?;
         db_info.encryption_type = get_node_attr_u32(&root_attrs, "Encrypted").try_into().unwrap_or_default();
         db_info.uuid = get_node_attr_str(&root_attrs,"UUID");
+        db_info.key_digest = get_node_attr_str(&root_attrs,"KeyDigest");
+        if db_info.version == ZdbVersion::V3 {
+            db_info.key_normalization = KeyNormalization::from_header_value(&get_node_attr_str(&root_attrs,"KeyNormalization"))?;
+            db_info.media_types = parse_media_types(&get_node_attr_str(&root_attrs,"MediaTypes"));
+            db_info.content_block_layout = get_node_attr_str(&root_attrs,"ContentBlockLayout");
+            db_info.labels = parse_labels(&get_node_attr_str(&root_attrs,"Labels"));
+            db_info.preview_skip_classes = get_node_attr_str(&root_attrs,"PreviewSkipClasses").split_whitespace().map(str::to_string).collect();
+            db_info.content_file = get_node_attr_str(&root_attrs,"ContentFile");
+        }
 
         let mut content_type= if db_info.version != ZdbVersion::V3 {
             get_node_attr_str(&root_attrs,"Format")
@@ -376,15 +431,19 @@ impl DbInfo {
 #[derive(Clone,Debug)]
 pub struct MetaUnit {
     pub db_info: DbInfo,
-    pub crypto_key: Vec<u8>,
+    pub crypto_key: SecretBytes,
     pub content_data_total_length: u64,
     pub version: ZdbVersion,
-    pub collator: Rc<UCollator>,
+    pub collator: Arc<UCollator>,
     pub encoding_obj: &'static Encoding,
     pub raw_header_xml:String,
 }
 
-fn read_cstr_with_crc<R: Read>(reader: &mut R) -> Result<String> {
+/// Salsa20 keys are 128 bits, shorter license keys can't be valid.
+const MIN_LICENSE_KEY_LENGTH: usize = 16;
+
+/// Reads the header XML preceded by its length and followed by its checksum.
+pub(crate) fn read_cstr_with_crc<R: Read>(reader: &mut R) -> Result<String> {
     let length = reader.read_u32::<BigEndian>()?;
     let mut data = vec![0u8; length as usize];
     reader.read_exact(&mut data)?;
@@ -412,37 +471,48 @@ impl MetaUnit {
     }
 
     pub fn from_reader<R: Read + Seek>(reader: &mut R, device_id: &str, license_data: &str, content_data_total_length: u64) -> crate::Result<Self> {
+        Ok(Self::from_reader_timed(reader, device_id, license_data, content_data_total_length)?.0)
+    }
+
+    /// Reads the header like [`from_reader`](Self::from_reader), also returning the time spent creating the collator.
+    pub(crate) fn from_reader_timed<R: Read + Seek>(reader: &mut R, device_id: &str, license_data: &str, content_data_total_length: u64) -> crate::Result<(Self, Duration)> {
         let raw_xml = read_cstr_with_crc(reader)?;
         //debug!("Zdb raw header:{}",raw_xml);
         let db_info: DbInfo = DbInfo::from_xml(&raw_xml)?;
         let version = db_info.version;
-        let db_reg_code = if license_data.is_empty() { &db_info.embedded_reg_code } else { license_data };
+        let license_data = license_data.trim();
+        let db_reg_code = if license_data.is_empty() { db_info.embedded_reg_code.trim() } else { license_data };
         if db_reg_code.is_empty() && db_info.encryption_type.is_para_encrypted(){
-            return Err(ZdbError::invalid_data_format("DB needs registration but no license data is provided"));
+            return Err(ZdbError::license_error(LicenseErrorKind::Required, "DB needs registration but no license data is provided"));
         }
     
-        let crypto_key = if !db_reg_code.is_empty() {
+        let crypto_key = SecretBytes::new(if !db_reg_code.is_empty() {
             let encrypted_key = hex::decode(db_reg_code)
-                .map_err(|e| ZdbError::invalid_data_format(format!("Failed to convert hex str:{}",e.to_string())))?;
-            decrypt_salsa20(&encrypted_key, &ripemd_digest(device_id.as_bytes())?.as_slice())?
+                .map_err(|e| ZdbError::license_error(LicenseErrorKind::Invalid, format!("Failed to convert hex str:{}",e.to_string())))?;
+            if encrypted_key.len() < MIN_LICENSE_KEY_LENGTH {
+                return Err(ZdbError::license_error(LicenseErrorKind::Invalid, format!("License key is too short: {} bytes", encrypted_key.len())));
+            }
+            decrypt_salsa20(&encrypted_key, &SecretBytes::new(ripemd_digest(device_id.as_bytes())?))?
         } else {
             if version == ZdbVersion::V3 {
-                fast_hash_digest(&db_info.uuid.as_bytes())?
+                DigestAlgorithm::from_name(&db_info.key_digest)?.digest(db_info.uuid.as_bytes())?
             } else {
                 vec![]
             }
-        };
+        });
 
-        let collator = UCollator::try_from(db_info.locale_id.as_str())?;
-        Ok(Self { 
+        let collator_start = Instant::now();
+        let collator = shared_collator(&db_info.locale_id)?;
+        let collator_duration = collator_start.elapsed();
+        Ok((Self { 
             crypto_key,
             encoding_obj: get_encoding_object_by_label(&db_info.encoding_label)?,
             db_info, 
             content_data_total_length,
             version,
-            collator: Rc::new(collator),
+            collator,
             raw_header_xml: raw_xml,
-        })
+        }, collator_duration))
     }
 }
 
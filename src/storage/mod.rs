@@ -15,15 +15,23 @@ pub mod content_block_index_unit;
 pub mod content_unit;
 pub mod zip_directory;
 pub mod reader_helper;
+pub mod unit_digest;
+pub mod bloom_filter_unit;
+pub mod entry_meta_unit;
+pub mod source_map_unit;
 
 pub use meta_unit::MetaUnit;
 pub use unit_base::UnitType;
-pub use key_block::{KeyIndex, KeyBlock, EntryNo};
+pub use key_block::{KeyIndex, KeyBlock, EntryNo, CompactKeyIndex, CompactKeyIndexes, COMPACT_KEY_INDEX_SCHEMA_VERSION};
 pub use key_block_index::KeyBlockIndex;
 pub use key_block_index_unit::KeyBlockIndexUnit;
-pub use storage_block::StorageBlock;
+pub use storage_block::{RawBlockInfo, StorageBlock};
 pub use content_block::ContentBlock;
 pub use content_block_index_unit::ContentBlockIndex;
-pub use content_unit::ContentUnit;
-pub use zip_directory::ZipDirectory;
-pub use reader_helper::{UintReader};
\ No newline at end of file
+pub use content_unit::{ContentUnit, CONTENT_FILE_MAGIC};
+pub use zip_directory::{ZipDirectory, ZipEntryReader};
+pub use unit_digest::{UnitDigestTrailer, UnitDigestCheck};
+pub use bloom_filter_unit::{BloomFilter, BloomFilterUnit};
+pub use entry_meta_unit::{EntryMetaExt, EntryMetaUnit, PartOfSpeech};
+pub use source_map_unit::{SourceLocation, SourceMapUnit};
+pub use reader_helper::{DecodeDiagnostics, DecodeMode, UintReader};
\ No newline at end of file
@@ -4,11 +4,13 @@
 //! - Character encoding detection and conversion
 //! - String encoding/decoding from various encodings (UTF-8, UTF-16LE, etc.)
 //! - C-string parsing (null-terminated strings)
+//! - Byte order mark stripping and decode diagnostics
 //! - Multi-byte and wide character handling
 
 use encoding_rs::Encoding;
 use log::debug;
 use byteorder::{BigEndian, ReadBytesExt};
+use serde::Serialize;
 
 use crate::storage::meta_unit::ZdbVersion;
 use crate::{Result, ZdbError};
@@ -96,8 +98,54 @@ pub fn encode_string_to_bytes(str: &str, encoding_obj: &'static Encoding) -> Res
     }
 }
 
+/// How malformed byte sequences are handled when decoding text, see [`decode_bytes_with_diagnostics`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DecodeMode {
+    /// Malformed sequences are replaced with U+FFFD
+    #[default]
+    Lossy,
+    /// Malformed sequences are an error
+    Strict,
+}
+
+/// How a byte string was decoded, see [`decode_bytes_with_diagnostics`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct DecodeDiagnostics {
+    /// Name of the encoding declared by the dictionary
+    pub declared_encoding: &'static str,
+    /// Name of the encoding the text was decoded with
+    pub encoding: &'static str,
+    /// Whether a byte order mark was stripped
+    pub bom_stripped: bool,
+    /// Whether malformed sequences were replaced with U+FFFD
+    pub had_errors: bool,
+}
+
+impl DecodeDiagnostics {
+    /// Returns whether the text wasn't decoded with the declared encoding, because of a
+    /// byte order mark or a fallback to UTF-8.
+    pub fn fell_back(&self) -> bool {
+        self.encoding != self.declared_encoding
+    }
+}
+
+/// Strips a leading byte order mark.
+///
+/// # Returns
+///
+/// Returns the bytes after the BOM and the encoding the BOM indicates, or the bytes
+/// unchanged and `encoding_obj` if there is no BOM.
+pub fn strip_bom<'a>(bytes: &'a [u8], encoding_obj: &'static Encoding) -> (&'a [u8], &'static Encoding) {
+    match Encoding::for_bom(bytes) {
+        Some((bom_encoding, bom_length)) => (&bytes[bom_length..], bom_encoding),
+        None => (bytes, encoding_obj),
+    }
+}
+
 /// Decodes bytes to a string using the specified encoding.
 ///
+/// Like [`decode_bytes_with_diagnostics`] in [`DecodeMode::Lossy`].
+///
 /// # Arguments
 ///
 /// * `cstr` - The bytes to decode
@@ -107,12 +155,54 @@ pub fn encode_string_to_bytes(str: &str, encoding_obj: &'static Encoding) -> Res
 ///
 /// Returns the decoded UTF-8 string.
 pub fn decode_bytes_to_string(cstr:&[u8], encoding_obj: &'static Encoding) -> Result<String> {
-    let cstr=bytes_from_cstr(cstr, encoding_obj.name().to_lowercase().starts_with("utf-16") );
-    let (decoded, _, had_errors) = encoding_obj.decode(cstr);
-    if had_errors {
-        debug!("Decoding error with: {}", encoding_obj.name());
+    let (decoded, diagnostics) = decode_bytes_with_diagnostics(cstr, encoding_obj, DecodeMode::Lossy)?;
+    if diagnostics.had_errors {
+        debug!("Decoding error with: {}", diagnostics.encoding);
     }
-    Ok(decoded.into_owned())
+    Ok(decoded)
+}
+
+/// Decodes bytes to a string, reporting how they were decoded.
+///
+/// A leading byte order mark is stripped and overrides `encoding_obj`, and the trailing
+/// zero of a C-string is ignored. Bytes that are malformed in a declared legacy encoding
+/// but valid UTF-8, as left by dictionaries mixing encodings, are decoded as UTF-8.
+///
+/// # Arguments
+///
+/// * `cstr` - The bytes to decode
+/// * `encoding_obj` - The declared source encoding
+/// * `mode` - How the remaining malformed sequences are handled
+///
+/// # Returns
+///
+/// Returns the decoded UTF-8 string and its [`DecodeDiagnostics`].
+///
+/// # Errors
+///
+/// Returns an `InvalidDataFormat` error for malformed sequences in [`DecodeMode::Strict`].
+pub fn decode_bytes_with_diagnostics(cstr: &[u8], encoding_obj: &'static Encoding, mode: DecodeMode) -> Result<(String, DecodeDiagnostics)> {
+    let (bytes, encoding) = strip_bom(cstr, encoding_obj);
+    let mut diagnostics = DecodeDiagnostics {
+        declared_encoding: encoding_obj.name(),
+        encoding: encoding.name(),
+        bom_stripped: bytes.len() != cstr.len(),
+        had_errors: false,
+    };
+    let bytes = bytes_from_cstr(bytes, encoding == encoding_rs::UTF_16LE || encoding == encoding_rs::UTF_16BE);
+    if let Some(decoded) = encoding.decode_without_bom_handling_and_without_replacement(bytes) {
+        return Ok((decoded.into_owned(), diagnostics));
+    }
+    if encoding != encoding_rs::UTF_8 && let Ok(decoded) = std::str::from_utf8(bytes) {
+        diagnostics.encoding = encoding_rs::UTF_8.name();
+        return Ok((decoded.to_string(), diagnostics));
+    }
+    if mode == DecodeMode::Strict {
+        return Err(ZdbError::invalid_data_format(format!("Malformed {} data", encoding.name())));
+    }
+    let (decoded, _) = encoding.decode_without_bom_handling(bytes);
+    diagnostics.had_errors = true;
+    Ok((decoded.into_owned(), diagnostics))
 }
 
 pub struct UintReader<R: ReadBytesExt> {
@@ -132,4 +222,42 @@ impl<R: ReadBytesExt> UintReader<R> {
             Ok(self.reader.read_u64::<BigEndian>()?)
         }
     }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bom_is_stripped_and_overrides_the_declared_encoding() {
+        let (decoded, diagnostics) = decode_bytes_with_diagnostics(b"\xEF\xBB\xBFcaf\xC3\xA9", encoding_rs::UTF_8, DecodeMode::Strict).unwrap();
+        assert_eq!(decoded, "café");
+        assert!(diagnostics.bom_stripped);
+        assert!(!diagnostics.fell_back());
+
+        let (decoded, diagnostics) = decode_bytes_with_diagnostics(b"\xFF\xFEa\0b\0\0\0", encoding_rs::GBK, DecodeMode::Strict).unwrap();
+        assert_eq!(decoded, "ab");
+        assert_eq!(diagnostics.encoding, "UTF-16LE");
+        assert!(diagnostics.fell_back());
+        assert_eq!(decode_bytes_to_string(b"\xEF\xBB\xBFword\0", encoding_rs::UTF_8).unwrap(), "word");
+    }
+
+    #[test]
+    fn malformed_legacy_text_falls_back_to_utf8() {
+        // "中文" in GBK, then in UTF-8, which isn't valid GBK
+        let (decoded, diagnostics) = decode_bytes_with_diagnostics(b"\xD6\xD0\xCE\xC4", encoding_rs::GBK, DecodeMode::Strict).unwrap();
+        assert_eq!(decoded, "中文");
+        assert!(!diagnostics.fell_back());
+        let (decoded, diagnostics) = decode_bytes_with_diagnostics("中文".as_bytes(), encoding_rs::SHIFT_JIS, DecodeMode::Strict).unwrap();
+        assert_eq!(decoded, "中文");
+        assert_eq!(diagnostics.declared_encoding, "Shift_JIS");
+        assert_eq!(diagnostics.encoding, "UTF-8");
+    }
+
+    #[test]
+    fn strict_mode_rejects_malformed_text() {
+        assert!(decode_bytes_with_diagnostics(b"ab\xFFcd", encoding_rs::UTF_8, DecodeMode::Strict).is_err());
+        let (decoded, diagnostics) = decode_bytes_with_diagnostics(b"ab\xFFcd", encoding_rs::UTF_8, DecodeMode::Lossy).unwrap();
+        assert_eq!(decoded, "ab\u{FFFD}cd");
+        assert!(diagnostics.had_errors);
+    }
 }
\ No newline at end of file
@@ -0,0 +1,137 @@
+//! Optional table of the source locations of the entries of a V3 file.
+//!
+//! Maintainers fix a dictionary in its source text, not in the built file. The builder
+//! can append a source map unit, see [`BuilderConfig::source_map`](crate::builder::BuilderConfig::source_map),
+//! which records for every entry in entry order the line of its key and the byte offset
+//! of its content in the source file, so a problem found in an entry can be traced back
+//! to the source, see [`ZdbReader::get_source_location`](crate::readers::zdb_reader::ZdbReader::get_source_location).
+//! It follows the entry metadata unit, and readers that don't know it never see it.
+//!
+//! Only text sources have lines, entries of other sources and entries created by the
+//! builder, e.g. union entries, have no location.
+
+use std::io::{ErrorKind, Read, Seek, SeekFrom};
+use std::rc::Rc;
+
+use byteorder::ReadBytesExt;
+use serde::{Deserialize, Serialize};
+
+use crate::storage::key_block::EntryNo;
+use crate::storage::meta_unit::MetaUnit;
+use crate::storage::storage_block::StorageBlock;
+use crate::storage::unit_base::{read_data_info_section, UnitInfoSection, UnitType};
+use crate::{Result, ZdbError};
+
+/// Length of an entry record: line number u64 and byte offset u64.
+pub const SOURCE_MAP_RECORD_SIZE: u32 = 16;
+/// Number of records per block of the unit.
+pub const SOURCE_MAP_BLOCK_ENTRIES: usize = 65536;
+
+#[derive(Debug, Clone, Deserialize, Serialize, Default)]
+#[serde(rename = "SourceMap")]
+pub struct SourceMapDataInfo {
+    #[serde(rename = "@entryCount")]
+    pub entry_count: u64,
+    #[serde(rename = "@recordSize")]
+    pub record_size: u32,
+    /// File name of the source, without its directory
+    #[serde(rename = "@sourceFile", default)]
+    pub source_file: String,
+}
+// <SourceMap entryCount="1000" recordSize="16" sourceFile="dict.txt" />
+
+/// Location of an entry in the source file, see the [module documentation](self).
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize)]
+pub struct SourceLocation {
+    /// File name of the source
+    pub source_file: String,
+    /// Line of the key, starting at 1
+    pub line_no: u64,
+    /// Byte offset of the content
+    pub offset: u64,
+}
+
+/// Appends the record of an entry to `buffer`, a line number of 0 for an entry without location.
+pub fn write_source_map_record(line_no: u64, offset: u64, buffer: &mut Vec<u8>) {
+    buffer.extend_from_slice(&line_no.to_be_bytes());
+    buffer.extend_from_slice(&offset.to_be_bytes());
+}
+
+pub struct SourceMapUnit {
+    /// Records of all entries, `record_size` bytes each
+    pub records: Vec<u8>,
+    pub record_size: u32,
+    pub entry_count: u64,
+    pub source_file: String,
+}
+
+impl SourceMapUnit {
+    /// Reads the source map unit if the next unit in the reader is one.
+    ///
+    /// # Returns
+    ///
+    /// Returns `None` and leaves the position unchanged if the reader is at the end of
+    /// the units or at another kind of data, e.g. the unit digest trailer.
+    pub fn try_from_reader_v3<R: Read + Seek>(reader: &mut R, meta_info: &Rc<MetaUnit>) -> Result<Option<Self>> {
+        let unit_pos = reader.stream_position()?;
+        let unit_type = match reader.read_u8() {
+            Ok(unit_type) => unit_type,
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => {
+                reader.seek(SeekFrom::Start(unit_pos))?;
+                return Ok(None);
+            }
+            Err(e) => return Err(e.into()),
+        };
+        reader.seek(SeekFrom::Start(unit_pos))?;
+        if unit_type != UnitType::SourceMap as u8 {
+            return Ok(None);
+        }
+
+        let unit_info = UnitInfoSection::from_reader(reader)?;
+        let data_pos = reader.stream_position()?;
+        reader.seek(SeekFrom::Current(unit_info.data_section_length as i64))?; //skip to the end of data section
+        let data_info = read_data_info_section::<SourceMapDataInfo, R>(reader, meta_info)?;
+        let end_of_unit = reader.stream_position()?;
+        if data_info.record_size < SOURCE_MAP_RECORD_SIZE {
+            return Err(ZdbError::invalid_data_format(format!("Source map records of {} bytes are too short", data_info.record_size)));
+        }
+        let expected_length = data_info.entry_count * data_info.record_size as u64;
+        let mut records = Vec::with_capacity(expected_length.min(unit_info.data_section_length.saturating_mul(16)) as usize);
+        reader.seek(SeekFrom::Start(data_pos))?;
+        for _ in 0..unit_info.block_count {
+            records.extend_from_slice(&StorageBlock::from_reader_v3(reader, meta_info)?.data);
+        }
+        if records.len() as u64 != expected_length {
+            return Err(ZdbError::invalid_data_format(format!("Source map has {} bytes, expected {}", records.len(), expected_length)));
+        }
+        reader.seek(SeekFrom::Start(end_of_unit))?;
+        Ok(Some(Self {
+            records,
+            record_size: data_info.record_size,
+            entry_count: data_info.entry_count,
+            source_file: data_info.source_file,
+        }))
+    }
+
+    /// Gets the source location of an entry, `None` if the entry has no location.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if there is no entry with this number.
+    pub fn get(&self, entry_no: EntryNo) -> Result<Option<SourceLocation>> {
+        if entry_no < 0 || entry_no as u64 >= self.entry_count {
+            return Err(ZdbError::invalid_parameter(format!("Entry {} out of range, the dictionary has {} entries", entry_no, self.entry_count)));
+        }
+        let start = entry_no as usize * self.record_size as usize;
+        let record = &self.records[start..start + SOURCE_MAP_RECORD_SIZE as usize];
+        let line_no = u64::from_be_bytes(record[0..8].try_into().unwrap());
+        if line_no == 0 {
+            return Ok(None);
+        }
+        Ok(Some(SourceLocation {
+            source_file: self.source_file.clone(),
+            line_no,
+            offset: u64::from_be_bytes(record[8..16].try_into().unwrap()),
+        }))
+    }
+}
@@ -9,13 +9,24 @@ use std::io::{Cursor, Read, Seek, SeekFrom, Write};
 
 use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
 
-use crate::utils::compression::{get_compressor, CompressionMethod};
+use crate::utils::checksum::ChecksumAlgorithm;
+use crate::utils::compression::{get_compressor, get_compressor_with_level, CompressionMethod};
 use crate::crypto::digest::ripemd_digest;
-use crate::crypto::encryption::{get_encryptor, EncryptionMethod};
+use crate::crypto::encryption::{block_nonce, get_encryptor, EncryptionMethod, ZERO_NONCE};
+use crate::crypto::secret::{ct_eq, SecretBytes};
 use crate::utils::io_utils::read_exact_to_vec;
 use crate::storage::meta_unit::MetaUnit;
 use crate::ZdbError;
 
+/// Flag in the reserved header field: the block is encrypted with a nonce derived from its offset.
+pub const BLOCK_FLAG_OFFSET_NONCE: u16 = 0x0001;
+
+/// Length of the block header following the length fields: methods, encrypted length, flags and crc.
+///
+/// Besides [`BLOCK_FLAG_OFFSET_NONCE`], the flags hold the checksum algorithm of the crc,
+/// see [`ChecksumAlgorithm::from_block_flags`].
+const BLOCK_HEADER_LENGTH: usize = 8;
+
 /// A storage block from a ZDB file.
 ///
 /// Storage blocks contain compressed and/or encrypted data that is then decompressed
@@ -26,6 +37,83 @@ pub struct StorageBlock {
     pub data: Vec<u8>,
 }
 
+/// Header fields and stored bytes of a storage block, for debugging files.
+///
+/// Nothing is decrypted, decompressed or checked, so it can be read from blocks that
+/// fail to decode.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RawBlockInfo {
+    /// Offset of the block in the file
+    pub offset: u64,
+    /// Length of the block in the file, including the length fields of V3 blocks
+    pub stored_length: u64,
+    /// Length of the uncompressed data
+    pub original_length: u32,
+    /// Compression method from the block header
+    pub compression: CompressionMethod,
+    /// Encryption method from the block header
+    pub encryption: EncryptionMethod,
+    /// Number of encrypted bytes at the start of the compressed data
+    pub encrypted_length: u8,
+    /// Flags of the block, e.g. [`BLOCK_FLAG_OFFSET_NONCE`]
+    pub flags: u16,
+    /// Checksum of the compressed data if the block is encrypted, of the uncompressed data otherwise,
+    /// computed with the algorithm in `flags`
+    pub crc: u32,
+    /// The compressed data as stored, its encrypted prefix still encrypted
+    pub compressed_data: Vec<u8>,
+}
+
+impl RawBlockInfo {
+    /// Parses the header of a block as stored.
+    ///
+    /// # Arguments
+    ///
+    /// * `offset` - Offset of the block in the file
+    /// * `stored_block` - The block as stored
+    /// * `original_length` - Uncompressed length from the block index, V3 blocks record their own
+    /// * `has_length_fields` - Whether the block starts with its length fields, as V3 blocks do
+    ///
+    /// # Errors
+    ///
+    /// Returns an `InvalidDataFormat` error if the block is shorter than its header or
+    /// the methods aren't known.
+    pub fn from_stored(offset: u64, stored_block: &[u8], original_length: u32, has_length_fields: bool) -> crate::Result<Self> {
+        let mut cursor = Cursor::new(stored_block);
+        let original_length = if has_length_fields {
+            let original_length = cursor.read_u32::<BigEndian>()?;
+            let _block_length = cursor.read_u32::<BigEndian>()?;
+            original_length
+        } else {
+            original_length
+        };
+        let header_start = cursor.position() as usize;
+        if stored_block.len() < header_start + BLOCK_HEADER_LENGTH {
+            return Err(ZdbError::invalid_data_format(format!("Storage block of {} bytes is truncated", stored_block.len())));
+        }
+        let compression_encryption = cursor.read_u8()?;
+        let encrypted_length = cursor.read_u8()?;
+        let flags = cursor.read_u16::<BigEndian>()?;
+        let crc = cursor.read_u32::<BigEndian>()?;
+        Ok(Self {
+            offset,
+            stored_length: stored_block.len() as u64,
+            original_length,
+            compression: CompressionMethod::try_from(compression_encryption & 0x0F)?,
+            encryption: EncryptionMethod::try_from((compression_encryption & 0xF0) >> 4)?,
+            encrypted_length,
+            flags,
+            crc,
+            compressed_data: stored_block[header_start + BLOCK_HEADER_LENGTH..].to_vec(),
+        })
+    }
+}
+
+/// Compares block checksums in constant time, a mismatch of an encrypted block means a wrong key.
+fn crc_matches(expected: u32, actual: u32) -> bool {
+    ct_eq(&expected.to_be_bytes(), &actual.to_be_bytes())
+}
+
 impl StorageBlock {
     /// Reads and decodes a storage block from a reader (V1/V2 format).
     ///
@@ -39,10 +127,11 @@ impl StorageBlock {
     pub fn from_reader_v1_v2<R: Read+Seek>(reader: &mut R, meta_info: &MetaUnit, crypto_key: &[u8], data_block_length: u32, original_data_length: u32) -> crate::Result<Self> {
         let mut raw_data=read_exact_to_vec(reader, data_block_length as usize)?;
         if meta_info.is_v2(){
-            let crypto_key = ripemd_digest(&ripemd_digest(&crypto_key)?.as_slice())?;
-            return Self::decode_block(&mut raw_data.as_mut_slice(), &crypto_key, original_data_length);
+            let digest = SecretBytes::new(ripemd_digest(crypto_key)?);
+            let crypto_key = SecretBytes::new(ripemd_digest(&digest)?);
+            return Self::decode_block(&mut raw_data.as_mut_slice(), &crypto_key, original_data_length, 0);
         }else{
-            return Self::decode_block(&mut raw_data.as_mut_slice(), &crypto_key, original_data_length);
+            return Self::decode_block(&mut raw_data.as_mut_slice(), &crypto_key, original_data_length, 0);
         }
     }
     
@@ -53,80 +142,183 @@ impl StorageBlock {
     /// * `block_data` - The raw block data
     /// * `crypto_key` - Encryption key (if applicable)
     /// * `original_data_length` - Expected uncompressed length
-    pub fn decode_block(block_data: &mut [u8], crypto_key: &[u8], original_data_length: u32) -> crate::Result<Self> {
+    /// * `block_offset` - Offset of the block in the file, used to derive per-block nonces
+    pub fn decode_block(block_data: &mut [u8], crypto_key: &[u8], original_data_length: u32, block_offset: u64) -> crate::Result<Self> {
+        Self::decode_block_prefix(block_data, crypto_key, original_data_length, block_offset, original_data_length as usize)
+    }
+
+    /// Decodes at least the first `prefix_length` bytes of a storage block.
+    ///
+    /// Decoding stops early only if the codec supports it and the crc covers the
+    /// compressed data, i.e. the block is encrypted. Otherwise the whole block is
+    /// decoded so the crc of the uncompressed data can be verified.
+    ///
+    /// # Returns
+    ///
+    /// Returns the block with at least `prefix_length` bytes of data.
+    pub fn decode_block_prefix(block_data: &mut [u8], crypto_key: &[u8], original_data_length: u32, block_offset: u64, prefix_length: usize) -> crate::Result<Self> {
+        let (compression_method, encryption_method, checksum, data_crc) = Self::decrypt_in_place(block_data, crypto_key, block_offset)?;
+        let raw_data = &mut block_data[BLOCK_HEADER_LENGTH..];
+
+        let crc_is_for_compressed_data=encryption_method != EncryptionMethod::None;
+        if crc_is_for_compressed_data  {
+            //Crc is for compressed data, not for encrypted data
+            let actual_crc = checksum.checksum(raw_data);
+            if !crc_matches(data_crc, actual_crc) {
+                return Err(ZdbError::crc_mismatch(data_crc, actual_crc));
+            }
+        }
+
+        let decompressor =  get_compressor(compression_method);
+        let data = if crc_is_for_compressed_data && prefix_length < original_data_length as usize {
+            decompressor.decompress_prefix(raw_data, original_data_length as usize, prefix_length)?
+        } else {
+            decompressor.decompress(raw_data, original_data_length as usize)?
+        };
+        if !crc_is_for_compressed_data {
+            let actual_crc = checksum.checksum(&data);
+            if !crc_matches(data_crc, actual_crc) {
+                return Err(ZdbError::crc_mismatch(data_crc, actual_crc));
+            }
+        }
+//        Ok(Self { original_data_length, compressed_data_length, next_data_section_length, compression_encryption, encrypted_data_length, reserved, crc: compressed_data_crc, data })
+        Ok(Self { data })
+    }
+
+    /// Decrypts the compressed data of a block in place, leaving the block header as is.
+    ///
+    /// # Returns
+    ///
+    /// Returns the compression and encryption method, the checksum algorithm and the crc from the block header.
+    fn decrypt_in_place(block_data: &mut [u8], crypto_key: &[u8], block_offset: u64) -> crate::Result<(CompressionMethod, EncryptionMethod, ChecksumAlgorithm, u32)> {
         let mut cursor = Cursor::new(&block_data);
         let compression_encryption = cursor.read_u8()?;
         let encrypted_data_length = cursor.read_u8()?;
-        let _reserved = cursor.read_u16::<BigEndian>()?;
+        let flags = cursor.read_u16::<BigEndian>()?;
         let data_crc = cursor.read_u32::<BigEndian>()?;
-        let header_length = cursor.position() as usize;
         drop(cursor);
-        //let raw_data_length = block_data.len() - header_length;
-        let raw_data = &mut block_data[header_length..];
-        
+        let raw_data = &mut block_data[BLOCK_HEADER_LENGTH..];
+
         let encryption_method = EncryptionMethod::try_from((compression_encryption&0xF0)>>4)?;
         if encryption_method != EncryptionMethod::None {
+            let derived_key;
             let crypto_key = if crypto_key.is_empty() {
-                ripemd_digest(&data_crc.to_be_bytes())?
+                derived_key = SecretBytes::new(ripemd_digest(&data_crc.to_be_bytes())?);
+                derived_key.expose()
             } else {
-                crypto_key.to_vec()
+                crypto_key
             };
             
-            let mut decryptor = get_encryptor(encryption_method, &crypto_key, &[0; 8])?;
-            let input = &mut raw_data[0..encrypted_data_length as usize];
+            let nonce = if flags & BLOCK_FLAG_OFFSET_NONCE != 0 { block_nonce(block_offset) } else { ZERO_NONCE };
+            let mut decryptor = get_encryptor(encryption_method, crypto_key, &nonce)?;
+            let input = raw_data.get_mut(0..encrypted_data_length as usize)
+                .ok_or_else(|| ZdbError::invalid_data_format("Encrypted length exceeds the block"))?;
             let mut output = vec![0u8; input.len() as usize];
             decryptor.decrypt(&input, &mut output)?;
             input.copy_from_slice(&output); //input is part of raw_data, now raw_data is decrypted        
         }
-
-        let crc_is_for_compressed_data=encryption_method != EncryptionMethod::None;
-        if crc_is_for_compressed_data  {
-            //Crc is for compressed data, not for encrypted data
-            let alder_crc = adler::adler32_slice(&raw_data);
-            if data_crc != alder_crc {
-                return Err(ZdbError::crc_mismatch(data_crc, alder_crc));
-            }
-        }
-
         let compression_method = CompressionMethod::try_from(compression_encryption&0x0F)?;
-        let decompressor =  get_compressor(compression_method);
-        let data= decompressor.decompress(&raw_data, original_data_length as usize)?;
-        if !crc_is_for_compressed_data {
-            let alder_crc = adler::adler32_slice(&data);
-            if data_crc != alder_crc {
-                return Err(ZdbError::crc_mismatch(data_crc, alder_crc));
-            }
-        }
-//        Ok(Self { original_data_length, compressed_data_length, next_data_section_length, compression_encryption, encrypted_data_length, reserved, crc: compressed_data_crc, data })
-        Ok(Self { data })
+        Ok((compression_method, encryption_method, ChecksumAlgorithm::from_block_flags(flags)?, data_crc))
     }
 
     pub fn from_reader_v3<R: Read+Seek>(reader: &mut R, meta_info: &MetaUnit) -> crate::Result<Self> {
+        Self::from_reader_v3_prefix(reader, meta_info, usize::MAX)
+    }
+
+    /// Reads a storage block (V3 format), decoding at least the first `prefix_length` bytes.
+    ///
+    /// See [`decode_block_prefix`](Self::decode_block_prefix).
+    pub fn from_reader_v3_prefix<R: Read+Seek>(reader: &mut R, meta_info: &MetaUnit, prefix_length: usize) -> crate::Result<Self> {
+        let block_offset = reader.stream_position()?;
         let original_data_length = reader.read_u32::<BigEndian>()?; //original_data_length is the length of uncompressed data length
         let data_block_length = reader.read_u32::<BigEndian>()?; //Data block length is raw compressed data length + header length
         let mut raw_data=read_exact_to_vec(reader, data_block_length as usize)?;
-        return Self::decode_block(&mut raw_data, &meta_info.crypto_key, original_data_length);
+        let prefix_length = prefix_length.min(original_data_length as usize);
+        Self::decode_block_prefix(&mut raw_data, &meta_info.crypto_key, original_data_length, block_offset, prefix_length)
     }
 
-    pub fn to_writer<W: Write+Seek>(writer: &mut W, data:&[u8], crypto_key:&[u8], compression_method:CompressionMethod, encryption_method:EncryptionMethod) -> crate::Result<u64> {
+    /// Compresses, encrypts and writes a storage block (V3 format).
+    ///
+    /// If `per_block_nonce` is set, the block is encrypted with a nonce derived from
+    /// its offset in the file and flagged in the header, otherwise the legacy all-zero
+    /// nonce is used. The crc is computed with `checksum`, which is flagged in the header
+    /// unless it's Adler-32.
+    ///
+    /// # Returns
+    ///
+    /// Returns the number of bytes written.
+    #[allow(clippy::too_many_arguments)]
+    pub fn to_writer<W: Write+Seek>(writer: &mut W, data:&[u8], crypto_key:&[u8], compression_method:CompressionMethod, encryption_method:EncryptionMethod, per_block_nonce: bool, checksum: ChecksumAlgorithm) -> crate::Result<u64> {
+        Self::to_writer_with_level(writer, data, crypto_key, compression_method, None, encryption_method, per_block_nonce, checksum)
+    }
+
+    /// Writes a block like [`to_writer`](Self::to_writer), compressed with `compression_level`
+    /// instead of the default level of the method.
+    #[allow(clippy::too_many_arguments)]
+    pub fn to_writer_with_level<W: Write+Seek>(writer: &mut W, data:&[u8], crypto_key:&[u8], compression_method:CompressionMethod, compression_level: Option<u32>, encryption_method:EncryptionMethod, per_block_nonce: bool, checksum: ChecksumAlgorithm) -> crate::Result<u64> {
+        let compressed_data = get_compressor_with_level(compression_method, compression_level).compress(data)?;
+        Self::write_compressed(writer, data.len() as u32, compressed_data, compression_method, |_| Ok(checksum.checksum(data)), crypto_key, encryption_method, per_block_nonce, checksum)
+    }
+
+    /// Writes a block as stored in another V3 file, without recompressing it.
+    ///
+    /// The compressed data is kept and only its encrypted prefix is decrypted with the
+    /// key and offset of the source and encrypted again for the new position. The data
+    /// is decompressed only if the crc has to be computed over the uncompressed data,
+    /// i.e. an encrypted block is written unencrypted or with another checksum algorithm.
+    ///
+    /// # Arguments
+    ///
+    /// * `stored_block` - The block as stored, starting with its length fields
+    /// * `source_offset` - Offset of the block in the source file
+    /// * `source_key` - Crypto key of the source file
+    ///
+    /// # Returns
+    ///
+    /// Returns the number of bytes written.
+    #[allow(clippy::too_many_arguments)]
+    pub fn copy_to_writer<W: Write+Seek>(writer: &mut W, stored_block: &[u8], source_offset: u64, source_key: &[u8], crypto_key:&[u8], encryption_method:EncryptionMethod, per_block_nonce: bool, checksum: ChecksumAlgorithm) -> crate::Result<u64> {
+        let mut cursor = Cursor::new(stored_block);
+        let original_data_length = cursor.read_u32::<BigEndian>()?;
+        let block_length = cursor.read_u32::<BigEndian>()? as usize;
+        let mut block_data = stored_block.get(8..8 + block_length)
+            .filter(|block_data| block_data.len() >= BLOCK_HEADER_LENGTH)
+            .ok_or_else(|| ZdbError::invalid_data_format("Storage block is truncated"))?
+            .to_vec();
+        let (compression_method, source_encryption, source_checksum, source_crc) = Self::decrypt_in_place(&mut block_data, source_key, source_offset)?;
+        let compressed_data = block_data.split_off(BLOCK_HEADER_LENGTH);
+        let data_crc = |compressed_data: &[u8]| if source_encryption == EncryptionMethod::None && source_checksum == checksum {
+            Ok(source_crc)
+        } else {
+            let data = get_compressor(compression_method).decompress(compressed_data, original_data_length as usize)?;
+            Ok(checksum.checksum(&data))
+        };
+        Self::write_compressed(writer, original_data_length, compressed_data, compression_method, data_crc, crypto_key, encryption_method, per_block_nonce, checksum)
+    }
+
+    /// Encrypts and writes compressed data as a block.
+    ///
+    /// `data_crc` gives the crc of the uncompressed data from the compressed data, it's only
+    /// called for unencrypted blocks.
+    #[allow(clippy::too_many_arguments)]
+    fn write_compressed<W: Write+Seek, F: FnOnce(&[u8]) -> crate::Result<u32>>(writer: &mut W, original_data_length: u32, mut compressed_data: Vec<u8>, compression_method:CompressionMethod, data_crc: F, crypto_key:&[u8], encryption_method:EncryptionMethod, per_block_nonce: bool, checksum: ChecksumAlgorithm) -> crate::Result<u64> {
         let pos = writer.seek(SeekFrom::Current(0))?;
-        let compressor =  get_compressor(compression_method);
-        let mut encryptor = get_encryptor(encryption_method, &crypto_key, &[0; 8])?;
+        let nonce = if per_block_nonce { block_nonce(pos) } else { ZERO_NONCE };
+        let mut encryptor = get_encryptor(encryption_method, &crypto_key, &nonce)?;
 
         let mut compression_encryption = (compression_method as u8) | (encryption_method as u8)<<4;
-        let mut compressed_data = compressor.compress(data)?;
         let mut encrypted_data_length=min(32,compressed_data.len());
         
         // Determine if we will actually encrypt data
-        let will_encrypt = data.len() >= encrypted_data_length && encryption_method != EncryptionMethod::None;
+        let will_encrypt = original_data_length as usize >= encrypted_data_length && encryption_method != EncryptionMethod::None;
         
         // Calculate CRC based on whether encryption will be applied
         // If encryption is applied, CRC is for compressed data (matching reader's logic at line 54)
         // If no encryption, CRC is for original uncompressed data (matching reader's logic at line 67)
         let data_crc = if will_encrypt {
-            adler::adler32_slice(&compressed_data)
+            checksum.checksum(&compressed_data)
         } else {
-            adler::adler32_slice(data)
+            data_crc(&compressed_data)?
         };
         
         if will_encrypt {
@@ -137,14 +329,65 @@ impl StorageBlock {
             encrypted_data_length=0;
             compression_encryption=compression_method as u8;
         }
-        const HEADER_LENGTH:u32=8;
-        writer.write_u32::<BigEndian>(data.len() as u32)?; //original_data_length
-        writer.write_u32::<BigEndian>(compressed_data.len() as u32+HEADER_LENGTH)?; //compressed_data_length
+        let flags = checksum.block_flags() | if will_encrypt && per_block_nonce { BLOCK_FLAG_OFFSET_NONCE } else { 0 };
+        writer.write_u32::<BigEndian>(original_data_length)?; //original_data_length
+        writer.write_u32::<BigEndian>(compressed_data.len() as u32+BLOCK_HEADER_LENGTH as u32)?; //compressed_data_length
         writer.write_u8(compression_encryption)?;
         writer.write_u8(encrypted_data_length as u8)?;
-        writer.write_u16::<BigEndian>(0)?; //reserved
+        writer.write_u16::<BigEndian>(flags)?; //flags, previously reserved
         writer.write_u32::<BigEndian>(data_crc)?; //data_crc
         writer.write_all(&compressed_data)?;
         Ok(writer.seek(SeekFrom::Current(0))? - pos)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_and_decode(data: &[u8], key: &[u8], prefix_len: usize, per_block_nonce: bool) -> (Vec<u8>, Vec<u8>) {
+        let mut cursor = Cursor::new(vec![0u8; prefix_len]);
+        cursor.seek(SeekFrom::End(0)).unwrap();
+        StorageBlock::to_writer(&mut cursor, data, key, CompressionMethod::None, EncryptionMethod::Salsa20, per_block_nonce, ChecksumAlgorithm::Adler32).unwrap();
+        let written = cursor.into_inner()[prefix_len..].to_vec();
+        let mut block_data = written[8..].to_vec();
+        let decoded = StorageBlock::decode_block(&mut block_data, key, data.len() as u32, prefix_len as u64).unwrap();
+        (written, decoded.data)
+    }
+
+    #[test]
+    fn test_per_block_nonce() {
+        let key = [7u8; 16];
+        let data = [0x5au8; 64];
+        let (block_a, decoded_a) = write_and_decode(&data, &key, 0, true);
+        let (block_b, decoded_b) = write_and_decode(&data, &key, 100, true);
+        assert_eq!(decoded_a, data);
+        assert_eq!(decoded_b, data);
+        assert_ne!(block_a[16..], block_b[16..]);
+
+        let (block_a, decoded_a) = write_and_decode(&data, &key, 0, false);
+        let (block_b, _) = write_and_decode(&data, &key, 100, false);
+        assert_eq!(decoded_a, data);
+        assert_eq!(block_a, block_b);
+    }
+
+    #[test]
+    fn test_decode_block_prefix() {
+        let key = [7u8; 16];
+        let data: Vec<u8> = (0..200_000u32).flat_map(|i| (i % 251).to_le_bytes()).collect();
+        for (compression_method, encryption_method, partial) in [
+            (CompressionMethod::Lz4, EncryptionMethod::Salsa20, true),
+            (CompressionMethod::Deflate, EncryptionMethod::Salsa20, true),
+            (CompressionMethod::Lzma, EncryptionMethod::Salsa20, false),
+            // Without encryption the crc covers the uncompressed data, so the whole block is decoded
+            (CompressionMethod::Lz4, EncryptionMethod::None, false),
+        ] {
+            let mut cursor = Cursor::new(Vec::new());
+            StorageBlock::to_writer(&mut cursor, &data, &key, compression_method, encryption_method, true, ChecksumAlgorithm::Crc32c).unwrap();
+            let mut block_data = cursor.into_inner()[8..].to_vec();
+            let decoded = StorageBlock::decode_block_prefix(&mut block_data, &key, data.len() as u32, 0, 1000).unwrap();
+            assert_eq!(decoded.data.len() == 1000, partial, "{:?} {:?}", compression_method, encryption_method);
+            assert_eq!(decoded.data[..], data[..decoded.data.len()]);
+        }
+    }
+}
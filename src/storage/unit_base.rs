@@ -1,18 +1,20 @@
-use std::io::{Read, Seek, Write};
+use std::io::{Read, Seek, SeekFrom, Write};
 
 use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
 use serde::de::DeserializeOwned;
 use serde::Serialize;
 
+use crate::utils::checksum::ChecksumAlgorithm;
 use crate::utils::compression::CompressionMethod;
 use crate::crypto::encryption::EncryptionMethod;
 use crate::storage::meta_unit::MetaUnit;
 use crate::storage::reader_helper::bytes_from_cstr;
 use crate::storage::storage_block::StorageBlock;
+use crate::utils::named_enum::{named_enum_serde, NamedEnum};
 use crate::utils::remove_xml_declaration;
 use crate::{Result, ZdbError};
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
 #[repr(u8)]
 pub enum UnitType {
     #[default]
@@ -21,8 +23,27 @@ pub enum UnitType {
     ContentBlockIndex = 2,
     Key = 3,
     KeyBlockIndex = 4,
+    BloomFilter = 5,
+    EntryMeta = 6,
+    SourceMap = 7,
 }
 
+impl NamedEnum for UnitType {
+    const KIND: &'static str = "unit type";
+    const VARIANTS: &'static [(Self, &'static str, u64)] = &[
+        (UnitType::Invalid, "Invalid", 0),
+        (UnitType::Content, "Content", 1),
+        (UnitType::ContentBlockIndex, "ContentBlockIndex", 2),
+        (UnitType::Key, "Key", 3),
+        (UnitType::KeyBlockIndex, "KeyBlockIndex", 4),
+        (UnitType::BloomFilter, "BloomFilter", 5),
+        (UnitType::EntryMeta, "EntryMeta", 6),
+        (UnitType::SourceMap, "SourceMap", 7),
+    ];
+}
+
+named_enum_serde!(UnitType);
+
 impl TryFrom<u8> for UnitType {
     type Error = ZdbError;
     
@@ -32,7 +53,10 @@ impl TryFrom<u8> for UnitType {
             2 => Ok(UnitType::ContentBlockIndex),
             3 => Ok(UnitType::Key),
             4 => Ok(UnitType::KeyBlockIndex),
-            _ => Err(ZdbError::invalid_parameter(format!("Invalid unit type:{}",value))),
+            5 => Ok(UnitType::BloomFilter),
+            6 => Ok(UnitType::EntryMeta),
+            7 => Ok(UnitType::SourceMap),
+            _ => Err(ZdbError::unsupported_feature(format!("unit type {}", value), crate::format::newer_version_hint("unit types 1 to 7"))),
         }
     }
 }
@@ -40,7 +64,7 @@ impl TryFrom<u8> for UnitType {
 
 #[derive(Debug, Clone, Default)]
 pub struct UnitInfoSection {
-    pub unit_type: UnitType, //1: content, 2: content block index, 3: key, 4: key block index
+    pub unit_type: UnitType, //1: content, 2: content block index, 3: key, 4: key block index, 5: bloom filter, 6: entry metadata
     pub _reserved1: [u8; 3],
     pub _reserved2: u64, //Total unit length - 12, redundant data
     pub block_count: u32, //block count in unit
@@ -73,6 +97,20 @@ impl UnitInfoSection {
     }
 }
 
+/// Moves a reader positioned at the start of a V3 unit to the end of the unit.
+///
+/// Only the data info section is decoded, the data section is skipped.
+///
+/// # Returns
+///
+/// Returns the unit info of the skipped unit.
+pub fn skip_unit_v3<R: Read+Seek>(reader: &mut R, meta_info: &MetaUnit) -> Result<UnitInfoSection> {
+    let info = UnitInfoSection::from_reader(reader)?;
+    reader.seek(SeekFrom::Current(info.data_section_length as i64))?;
+    StorageBlock::from_reader_v3(reader, meta_info)?;
+    Ok(info)
+}
+
 pub fn read_data_info_section<T, R>(reader: &mut R, meta_info: &MetaUnit) -> Result<T>
 where
     T: DeserializeOwned,
@@ -84,13 +122,13 @@ where
     Ok(data_info)
 }
 
-pub fn write_data_info_section<T, W>(writer: &mut W, data_info: &T, crypto_key:&[u8], compression_method:CompressionMethod, encryption_method:EncryptionMethod) -> crate::Result<()>
+pub fn write_data_info_section<T, W>(writer: &mut W, data_info: &T, crypto_key:&[u8], compression_method:CompressionMethod, encryption_method:EncryptionMethod, per_block_nonce: bool, checksum: ChecksumAlgorithm) -> crate::Result<()>
 where
     T: Serialize,
     W: Write+Seek,
 {
     let mut raw_xml = serde_xml_rs::to_string(data_info)?;
     remove_xml_declaration(&mut raw_xml);
-    StorageBlock::to_writer(writer, &raw_xml.as_bytes(), crypto_key, compression_method, encryption_method)?;
+    StorageBlock::to_writer(writer, &raw_xml.as_bytes(), crypto_key, compression_method, encryption_method, per_block_nonce, checksum)?;
     Ok(())
 }
\ No newline at end of file
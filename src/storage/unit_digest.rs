@@ -0,0 +1,189 @@
+//! Per-unit SHA-256 digests stored in a trailer at the end of V3 files.
+//!
+//! The builder can append a small trailer that records the offset, length and
+//! SHA-256 digest of every unit in the file. Verifying the trailer only hashes
+//! the raw bytes, so corruption can be localized to a unit without decoding any
+//! block, and downloaded files can be validated quickly.
+//!
+//! Trailer layout (all integers big-endian):
+//!
+//! ```text
+//! repeated: unit_type u8 | offset u64 | length u64 | sha256 [u8; 32]
+//! count u32
+//! magic "ZDBUNSHA"
+//! ```
+//!
+//! Readers that don't know the trailer ignore it, since units are located from
+//! the start of the file.
+
+use std::io::{Read, Seek, SeekFrom, Write};
+
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+
+use crate::crypto::digest::sha256_reader;
+use crate::crypto::secret::ct_eq;
+use crate::storage::unit_base::UnitType;
+use crate::{Result, ZdbError};
+
+const TRAILER_MAGIC: &[u8; 8] = b"ZDBUNSHA";
+const TRAILER_FOOTER_LENGTH: u64 = 4 + 8;
+const UNIT_DIGEST_RECORD_LENGTH: u64 = 1 + 8 + 8 + 32;
+
+/// Location and SHA-256 digest of one unit.
+#[derive(Debug, Clone, PartialEq)]
+pub struct UnitDigest {
+    pub unit_type: UnitType,
+    /// Offset of the unit in the file
+    pub offset: u64,
+    /// Length of the unit in bytes, including its info and data-info sections
+    pub length: u64,
+    pub sha256: [u8; 32],
+}
+
+/// Result of verifying one unit.
+#[derive(Debug, Clone, PartialEq)]
+pub struct UnitDigestCheck {
+    pub unit_type: UnitType,
+    pub offset: u64,
+    pub length: u64,
+    /// Whether the unit data matches the recorded digest
+    pub valid: bool,
+}
+
+/// The per-unit digest trailer of a file.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct UnitDigestTrailer {
+    pub digests: Vec<UnitDigest>,
+}
+
+impl UnitDigestTrailer {
+    /// Hashes the given unit ranges of a file.
+    ///
+    /// # Arguments
+    ///
+    /// * `reader` - Reader over the file
+    /// * `units` - Unit type, offset and length of each unit
+    pub fn compute<R: Read + Seek>(reader: &mut R, units: &[(UnitType, u64, u64)]) -> Result<Self> {
+        let mut digests = Vec::with_capacity(units.len());
+        for &(unit_type, offset, length) in units {
+            reader.seek(SeekFrom::Start(offset))?;
+            let sha256 = sha256_reader(reader, length)?;
+            digests.push(UnitDigest { unit_type, offset, length, sha256 });
+        }
+        Ok(Self { digests })
+    }
+
+    /// Writes the trailer at the current position of the writer, which should be the end of the file.
+    pub fn to_writer<W: Write>(&self, writer: &mut W) -> Result<()> {
+        for digest in &self.digests {
+            writer.write_u8(digest.unit_type as u8)?;
+            writer.write_u64::<BigEndian>(digest.offset)?;
+            writer.write_u64::<BigEndian>(digest.length)?;
+            writer.write_all(&digest.sha256)?;
+        }
+        writer.write_u32::<BigEndian>(self.digests.len() as u32)?;
+        writer.write_all(TRAILER_MAGIC)?;
+        Ok(())
+    }
+
+    /// Reads the trailer from the end of a file.
+    ///
+    /// # Returns
+    ///
+    /// Returns `None` if the file has no digest trailer.
+    pub fn from_reader<R: Read + Seek>(reader: &mut R) -> Result<Option<Self>> {
+        let file_length = reader.seek(SeekFrom::End(0))?;
+        if file_length < TRAILER_FOOTER_LENGTH {
+            return Ok(None);
+        }
+        reader.seek(SeekFrom::Start(file_length - TRAILER_FOOTER_LENGTH))?;
+        let count = reader.read_u32::<BigEndian>()? as u64;
+        let mut magic = [0u8; 8];
+        reader.read_exact(&mut magic)?;
+        if &magic != TRAILER_MAGIC {
+            return Ok(None);
+        }
+        let trailer_length = count * UNIT_DIGEST_RECORD_LENGTH + TRAILER_FOOTER_LENGTH;
+        if trailer_length > file_length {
+            return Err(ZdbError::invalid_data_format("Unit digest trailer is larger than the file"));
+        }
+        reader.seek(SeekFrom::Start(file_length - trailer_length))?;
+        let mut digests = Vec::with_capacity(count as usize);
+        for _ in 0..count {
+            let unit_type = UnitType::try_from(reader.read_u8()?)?;
+            let offset = reader.read_u64::<BigEndian>()?;
+            let length = reader.read_u64::<BigEndian>()?;
+            let mut sha256 = [0u8; 32];
+            reader.read_exact(&mut sha256)?;
+            digests.push(UnitDigest { unit_type, offset, length, sha256 });
+        }
+        Ok(Some(Self { digests }))
+    }
+
+    /// Length of the trailer in the file.
+    pub fn stored_length(&self) -> u64 {
+        self.digests.len() as u64 * UNIT_DIGEST_RECORD_LENGTH + TRAILER_FOOTER_LENGTH
+    }
+
+    /// Re-hashes every unit and compares it with the recorded digest.
+    ///
+    /// Units whose range lies outside the file are reported as invalid.
+    pub fn verify<R: Read + Seek>(&self, reader: &mut R) -> Result<Vec<UnitDigestCheck>> {
+        let file_length = reader.seek(SeekFrom::End(0))?;
+        let mut checks = Vec::with_capacity(self.digests.len());
+        for digest in &self.digests {
+            let in_range = digest.offset.checked_add(digest.length).is_some_and(|end| end <= file_length);
+            let valid = in_range && {
+                reader.seek(SeekFrom::Start(digest.offset))?;
+                ct_eq(&sha256_reader(reader, digest.length)?, &digest.sha256)
+            };
+            checks.push(UnitDigestCheck {
+                unit_type: digest.unit_type,
+                offset: digest.offset,
+                length: digest.length,
+                valid,
+            });
+        }
+        Ok(checks)
+    }
+}
+
+/// Verifies the unit digests of a file without parsing it.
+///
+/// # Errors
+///
+/// Returns an error if the file has no digest trailer or can not be read.
+pub fn verify_unit_digests<R: Read + Seek>(reader: &mut R) -> Result<Vec<UnitDigestCheck>> {
+    let trailer = UnitDigestTrailer::from_reader(reader)?
+        .ok_or_else(|| ZdbError::invalid_data_format("File has no unit digests"))?;
+    trailer.verify(reader)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_trailer_round_trip() {
+        let mut file = Cursor::new(Vec::new());
+        file.write_all(&[1u8; 100]).unwrap();
+        file.write_all(&[2u8; 50]).unwrap();
+        let units = [(UnitType::Content, 0, 100), (UnitType::Key, 100, 50)];
+        let trailer = UnitDigestTrailer::compute(&mut file, &units).unwrap();
+        file.seek(SeekFrom::End(0)).unwrap();
+        trailer.to_writer(&mut file).unwrap();
+
+        assert_eq!(UnitDigestTrailer::from_reader(&mut file).unwrap(), Some(trailer));
+        let checks = verify_unit_digests(&mut file).unwrap();
+        assert!(checks.iter().all(|check| check.valid));
+
+        file.get_mut()[120] = 3;
+        let checks = verify_unit_digests(&mut file).unwrap();
+        assert!(checks[0].valid);
+        assert!(!checks[1].valid);
+
+        let mut plain = Cursor::new(vec![0u8; 64]);
+        assert_eq!(UnitDigestTrailer::from_reader(&mut plain).unwrap(), None);
+    }
+}
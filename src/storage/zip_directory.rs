@@ -1,6 +1,6 @@
 use std::collections::HashMap;
 use std::fs;
-use std::io;
+use std::io::{self, Read, Seek, SeekFrom};
 use std::path::PathBuf;
 use std::sync::{Arc, Mutex};
 
@@ -8,27 +8,75 @@ use tantivy::directory::error::LockError;
 use tantivy::directory::{self, DirectoryLock, FileHandle, Lock};
 
 use crate::error::{Result, ZdbError};
+use crate::utils::io_utils::ReadSeek;
 
 // ZIP entry metadata for direct file access
 #[derive(Debug, Clone)]
 struct ZipEntryInfo {
     offset: u64,
     size: u64,
+    /// Whether the entry is stored uncompressed, only those can be read in place
+    stored: bool,
 }
 
 // Cache to store entry information 
 type EntryCache = Arc<Mutex<Option<HashMap<String, ZipEntryInfo>>>>;
 
+// Where the archive bytes come from
+#[derive(Clone, Debug)]
+enum ZipSource {
+    File(PathBuf),
+    Memory(Arc<[u8]>),
+}
+
+impl ZipSource {
+    fn open_archive(&self) -> Result<zip::ZipArchive<Box<dyn ReadSeek>>> {
+        let reader: Box<dyn ReadSeek> = match self {
+            ZipSource::File(path) => Box::new(fs::File::open(path)
+                .map_err(|e| ZdbError::general_error(format!("Failed to open zip: {}", e)))?),
+            ZipSource::Memory(bytes) => Box::new(io::Cursor::new(bytes.clone())),
+        };
+        zip::ZipArchive::new(reader)
+            .map_err(|e| ZdbError::general_error(format!("Failed to read zip: {}", e)))
+    }
+
+    fn read_at(&self, offset: u64, len: usize) -> io::Result<Vec<u8>> {
+        match self {
+            ZipSource::File(path) => {
+                let mut file = fs::File::open(path)?;
+                file.seek(SeekFrom::Start(offset))?;
+                let mut buffer = vec![0u8; len];
+                file.read_exact(&mut buffer)?;
+                Ok(buffer)
+            }
+            ZipSource::Memory(bytes) => usize::try_from(offset).ok()
+                .and_then(|start| bytes.get(start..start.checked_add(len)?))
+                .map(<[u8]>::to_vec)
+                .ok_or_else(|| io::Error::new(io::ErrorKind::UnexpectedEof, "Entry exceeds zip data")),
+        }
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct ZipDirectory {
-    zip_path: PathBuf,
+    source: ZipSource,
     entry_cache: EntryCache,
 }
 
 impl ZipDirectory {
     pub fn open(zip_path: PathBuf) -> Self { 
+        Self::with_source(ZipSource::File(zip_path))
+    }
+
+    /// Opens an archive held in memory, e.g. one read through a
+    /// [`UrlResolver`](crate::utils::io_utils::UrlResolver).
+    pub fn from_bytes(bytes: Arc<[u8]>) -> Self {
+        Self::with_source(ZipSource::Memory(bytes))
+    }
+
+    fn with_source(source: ZipSource) -> Self {
         Self { 
-            zip_path,
+            source,
             entry_cache: Arc::new(Mutex::new(None)),
         } 
     }
@@ -39,20 +87,17 @@ impl ZipDirectory {
             return Ok(());
         }
 
-        let file = fs::File::open(&self.zip_path)
-            .map_err(|e| ZdbError::general_error(format!("Failed to open zip: {}", e)))?;
-        let mut archive = zip::ZipArchive::new(file)
-            .map_err(|e| ZdbError::general_error(format!("Failed to read zip: {}", e)))?;
+        let mut archive = self.source.open_archive()?;
         
         let mut entries = HashMap::new();
         for i in 0..archive.len() {
             if let Ok(entry) = archive.by_index(i) {
-                if !entry.is_dir() && entry.compression() == zip::CompressionMethod::Stored {
-                    // Only support uncompressed entries for direct access
+                if !entry.is_dir() {
                     let name = entry.name().to_string();
                     let info = ZipEntryInfo {
                         offset: entry.data_start(),
                         size: entry.size(),
+                        stored: entry.compression() == zip::CompressionMethod::Stored,
                     };
                     entries.insert(name, info);
                 }
@@ -69,50 +114,150 @@ impl ZipDirectory {
         let entries = cache.as_ref().unwrap();
         let name = path.to_string_lossy().replace('\\', "/");
         
+        // Only uncompressed entries support direct access
         entries.get(&name)
+            .filter(|info| info.stored)
             .cloned()
             .ok_or_else(|| ZdbError::general_error(format!("Entry not found in zip: {}", name)))
     }
 
+    /// Whether the archive has a file with the given name, e.g. `dicts/oxford.mdx`.
+    pub fn contains(&self, name: &str) -> bool {
+        self.ensure_cache_loaded().is_ok()
+            && self.entry_cache.lock().unwrap().as_ref().is_some_and(|entries| entries.contains_key(name))
+    }
+
+    /// Opens a file of the archive for reading and seeking.
+    ///
+    /// Uncompressed entries are read in place from the archive, compressed entries are
+    /// decompressed into memory first.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the archive can't be read or has no file with the given name.
+    pub fn open_entry(&self, name: &str) -> Result<ZipEntryReader> {
+        self.ensure_cache_loaded()?;
+        let info = self.entry_cache.lock().unwrap().as_ref().and_then(|entries| entries.get(name).cloned())
+            .ok_or_else(|| ZdbError::general_error(format!("Entry not found in zip: {}", name)))?;
+        if info.stored {
+            match &self.source {
+                ZipSource::File(path) => {
+                    let mut file = io::BufReader::new(fs::File::open(path)?);
+                    file.seek(SeekFrom::Start(info.offset))?;
+                    return Ok(ZipEntryReader::Stored { file, start: info.offset, len: info.size, pos: 0 });
+                }
+                ZipSource::Memory(_) => {
+                    let data = self.source.read_at(info.offset, info.size as usize)?;
+                    return Ok(ZipEntryReader::Inflated(io::Cursor::new(data)));
+                }
+            }
+        }
+        let mut archive = self.source.open_archive()?;
+        let mut entry = archive.by_name(name)
+            .map_err(|e| ZdbError::general_error(format!("Failed to read {} from zip: {}", name, e)))?;
+        let mut data = Vec::with_capacity(info.size as usize);
+        entry.read_to_end(&mut data)?;
+        Ok(ZipEntryReader::Inflated(io::Cursor::new(data)))
+    }
+
     fn has_entry(&self, path: &std::path::Path) -> io::Result<bool> {
         match self.ensure_cache_loaded() {
             Ok(()) => {
                 let cache = self.entry_cache.lock().unwrap();
                 let entries = cache.as_ref().unwrap();
                 let name = path.to_string_lossy().replace('\\', "/");
-                Ok(entries.contains_key(&name))
+                Ok(entries.get(&name).is_some_and(|info| info.stored))
             }
             Err(_) => Ok(false)
         }
     }
 }
 
+/// Reader of a file in a zip archive, see [`ZipDirectory::open_entry`].
+#[derive(Debug)]
+pub enum ZipEntryReader {
+    /// Uncompressed entry, read in place from the archive
+    Stored {
+        file: io::BufReader<fs::File>,
+        /// Offset of the entry data in the archive
+        start: u64,
+        len: u64,
+        /// Position in the entry
+        pos: u64,
+    },
+    /// Compressed entry, decompressed into memory
+    Inflated(io::Cursor<Vec<u8>>),
+}
+
+impl ZipEntryReader {
+    /// Uncompressed size of the entry.
+    pub fn len(&self) -> u64 {
+        match self {
+            ZipEntryReader::Stored { len, .. } => *len,
+            ZipEntryReader::Inflated(data) => data.get_ref().len() as u64,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl Read for ZipEntryReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            ZipEntryReader::Stored { file, len, pos, .. } => {
+                let max = len.saturating_sub(*pos).min(buf.len() as u64) as usize;
+                let read = file.read(&mut buf[..max])?;
+                *pos += read as u64;
+                Ok(read)
+            }
+            ZipEntryReader::Inflated(data) => data.read(buf),
+        }
+    }
+}
+
+impl Seek for ZipEntryReader {
+    fn seek(&mut self, seek_from: SeekFrom) -> io::Result<u64> {
+        match self {
+            ZipEntryReader::Stored { file, start, len, pos } => {
+                let target = match seek_from {
+                    SeekFrom::Start(offset) => Some(offset),
+                    SeekFrom::End(delta) => len.checked_add_signed(delta),
+                    SeekFrom::Current(delta) => pos.checked_add_signed(delta),
+                }.ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "invalid seek to a negative position"))?;
+                // Keep the buffer for position queries
+                if target != *pos {
+                    file.seek(SeekFrom::Start(*start + target))?;
+                    *pos = target;
+                }
+                Ok(target)
+            }
+            ZipEntryReader::Inflated(data) => data.seek(seek_from),
+        }
+    }
+}
+
 #[derive(Debug)]
 struct ZipFileHandle {
-    zip_path: PathBuf,
+    source: ZipSource,
     entry_info: ZipEntryInfo,
 }
 
 impl ZipFileHandle {
-    fn new(zip_path: PathBuf, entry_info: ZipEntryInfo) -> Self {
-        Self { zip_path, entry_info }
+    fn new(source: ZipSource, entry_info: ZipEntryInfo) -> Self {
+        Self { source, entry_info }
     }
 }
 
 impl FileHandle for ZipFileHandle {
     fn read_bytes(&self, range: std::ops::Range<usize>) -> io::Result<directory::OwnedBytes> {
-        use std::io::{Read, Seek, SeekFrom};
-        
         if range.end > self.entry_info.size as usize {
             return Err(io::Error::new(io::ErrorKind::InvalidInput, "Range exceeds file size"));
         }
         
-        let mut file = fs::File::open(&self.zip_path)?;
-        file.seek(SeekFrom::Start(self.entry_info.offset + range.start as u64))?;
-        
         let len = range.end - range.start;
-        let mut buffer = vec![0u8; len];
-        file.read_exact(&mut buffer)?;
+        let buffer = self.source.read_at(self.entry_info.offset + range.start as u64, len)?;
         
         let owned_bytes = directory::OwnedBytes::new(buffer);
         Ok(owned_bytes)
@@ -133,7 +278,7 @@ impl directory::Directory for ZipDirectory {
                 path.to_path_buf()
             ))?;
         
-        let handle = ZipFileHandle::new(self.zip_path.clone(), entry_info);
+        let handle = ZipFileHandle::new(self.source.clone(), entry_info);
         Ok(Arc::new(handle))
     }
 
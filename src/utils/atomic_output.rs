@@ -0,0 +1,150 @@
+//! Atomic creation of output files.
+//!
+//! [`AtomicOutput`] writes to a temporary file in the destination directory, or another
+//! directory of the caller's choice, and moves it over the destination only when
+//! [`AtomicOutput::commit`] is called.
+//! If the guard is dropped without committing, e.g. because building failed or
+//! was cancelled, the temporary file is removed and the destination is left
+//! untouched.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use std::io::Write;
+//! use mdx::utils::atomic_output::AtomicOutput;
+//!
+//! # fn main() -> mdx::Result<()> {
+//! let (output, mut file) = AtomicOutput::create("dict.mdx")?;
+//! file.write_all(b"data")?;
+//! drop(file);
+//! output.commit()?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::fs::{self, File, OpenOptions};
+use std::path::{Path, PathBuf};
+
+use crate::{Result, ZdbError};
+
+/// Guard for a temporary output file that replaces the destination on commit.
+#[derive(Debug)]
+pub struct AtomicOutput {
+    dest_path: PathBuf,
+    temp_path: PathBuf,
+    committed: bool,
+}
+
+impl AtomicOutput {
+    /// Creates a temporary file next to `dest_path`.
+    ///
+    /// The file is opened for reading and writing.
+    ///
+    /// # Returns
+    ///
+    /// Returns the guard and the opened temporary file.
+    pub fn create<P: AsRef<Path>>(dest_path: P) -> Result<(Self, File)> {
+        let dest_path = dest_path.as_ref().to_path_buf();
+        let temp_path = Self::temp_path_for(&dest_path)?;
+        let file = OpenOptions::new().read(true).write(true).create_new(true).open(&temp_path)?;
+        Ok((Self { dest_path, temp_path, committed: false }, file))
+    }
+
+    /// Creates a temporary file in `temp_dir`, or next to `dest_path` if it's `None`.
+    ///
+    /// A temporary file on another filesystem than the destination is copied next to the
+    /// destination on commit, which is still atomic but needs room for both copies.
+    ///
+    /// # Returns
+    ///
+    /// Returns the guard and the opened temporary file.
+    pub fn create_in<P: AsRef<Path>>(dest_path: P, temp_dir: Option<&Path>) -> Result<(Self, File)> {
+        let Some(temp_dir) = temp_dir else {
+            return Self::create(dest_path);
+        };
+        let dest_path = dest_path.as_ref().to_path_buf();
+        let temp_path = temp_dir.join(Self::temp_path_for(&dest_path)?.file_name().unwrap_or_default());
+        let file = OpenOptions::new().read(true).write(true).create_new(true).open(&temp_path)?;
+        Ok((Self { dest_path, temp_path, committed: false }, file))
+    }
+
+    /// Returns a path for a temporary sibling of `path`, e.g. `dir/.dict.mdx.1234-5678.tmp`.
+    pub fn temp_path_for(path: &Path) -> Result<PathBuf> {
+        let file_name = path.file_name()
+            .ok_or_else(|| ZdbError::invalid_path(format!("{}", path.display())))?;
+        let temp_name = format!(".{}.{}-{:08x}.tmp", file_name.to_string_lossy(), std::process::id(), rand::random::<u32>());
+        Ok(path.with_file_name(temp_name))
+    }
+
+    /// Path of the temporary file.
+    pub fn temp_path(&self) -> &Path {
+        &self.temp_path
+    }
+
+    /// Path of the destination file.
+    pub fn dest_path(&self) -> &Path {
+        &self.dest_path
+    }
+
+    /// Renames the temporary file over the destination.
+    ///
+    /// All handles to the temporary file should be flushed and closed before committing.
+    pub fn commit(mut self) -> Result<()> {
+        if let Err(e) = fs::rename(&self.temp_path, &self.dest_path) {
+            if e.kind() != std::io::ErrorKind::CrossesDevices {
+                return Err(e.into());
+            }
+            // A sibling of the destination is renamed over it, so the destination is never partial
+            let sibling = Self::temp_path_for(&self.dest_path)?;
+            let copied = fs::copy(&self.temp_path, &sibling).and_then(|_| fs::rename(&sibling, &self.dest_path));
+            if let Err(e) = copied {
+                let _ = fs::remove_file(&sibling);
+                return Err(e.into());
+            }
+            fs::remove_file(&self.temp_path)?;
+        }
+        self.committed = true;
+        Ok(())
+    }
+}
+
+impl Drop for AtomicOutput {
+    fn drop(&mut self) {
+        if !self.committed
+            && self.temp_path.exists()
+            && let Err(e) = fs::remove_file(&self.temp_path)
+        {
+            log::warn!("Failed to remove temporary file {}: {}", self.temp_path.display(), e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    #[test]
+    fn test_commit_and_discard() {
+        let dir = std::env::temp_dir().join(format!("mdx_atomic_output_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let dest_path = dir.join("out.bin");
+        fs::write(&dest_path, b"old").unwrap();
+
+        let (output, mut file) = AtomicOutput::create(&dest_path).unwrap();
+        let temp_path = output.temp_path().to_path_buf();
+        file.write_all(b"partial").unwrap();
+        drop(file);
+        drop(output);
+        assert!(!temp_path.exists());
+        assert_eq!(fs::read(&dest_path).unwrap(), b"old");
+
+        let (output, mut file) = AtomicOutput::create(&dest_path).unwrap();
+        file.write_all(b"new").unwrap();
+        drop(file);
+        output.commit().unwrap();
+        assert_eq!(fs::read(&dest_path).unwrap(), b"new");
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,96 @@
+//! Checksums of storage blocks.
+//!
+//! Every storage block records a 32-bit checksum of its data. Files always used Adler-32,
+//! which is fast but misses many corruptions of large blocks. New files can use CRC32C or
+//! XXH3 instead, see [`BuilderConfig::block_checksum`](crate::builder::BuilderConfig::block_checksum).
+//! The algorithm is recorded in the flags of each block header, so blocks without the
+//! flag bits are read as Adler-32 and files of mixed blocks, e.g. from copied blocks,
+//! are verified block by block.
+//!
+//! CRC32C uses the SSE4.2 or ARMv8 CRC instructions where the CPU supports them, XXH3
+//! uses SIMD, so both verify faster than Adler-32 on most CPUs.
+
+use crate::utils::named_enum::{named_enum_serde, NamedEnum};
+use crate::{Result, ZdbError};
+
+/// Bits of the block header flags holding the checksum algorithm.
+pub const BLOCK_FLAG_CHECKSUM_MASK: u16 = 0x0006;
+const BLOCK_FLAG_CHECKSUM_SHIFT: u16 = 1;
+
+/// Algorithm of the checksums of storage blocks, see the [module documentation](self).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[repr(u8)]
+pub enum ChecksumAlgorithm {
+    /// Adler-32, readable by all versions
+    #[default]
+    Adler32 = 0,
+    /// CRC-32C (Castagnoli)
+    Crc32c = 1,
+    /// Lower 32 bits of XXH3-64
+    Xxh3 = 2,
+}
+
+impl NamedEnum for ChecksumAlgorithm {
+    const KIND: &'static str = "block checksum";
+    const VARIANTS: &'static [(Self, &'static str, u64)] = &[
+        (ChecksumAlgorithm::Adler32, "Adler32", 0),
+        (ChecksumAlgorithm::Crc32c, "Crc32c", 1),
+        (ChecksumAlgorithm::Xxh3, "Xxh3", 2),
+    ];
+}
+
+named_enum_serde!(ChecksumAlgorithm);
+
+impl TryFrom<u8> for ChecksumAlgorithm {
+    type Error = ZdbError;
+
+    fn try_from(value: u8) -> Result<Self> {
+        match value {
+            0 => Ok(ChecksumAlgorithm::Adler32),
+            1 => Ok(ChecksumAlgorithm::Crc32c),
+            2 => Ok(ChecksumAlgorithm::Xxh3),
+            _ => Err(crate::format::unsupported_value::<ChecksumAlgorithm>(value as u64)),
+        }
+    }
+}
+
+impl ChecksumAlgorithm {
+    /// Computes the checksum of `data`.
+    pub fn checksum(&self, data: &[u8]) -> u32 {
+        match self {
+            ChecksumAlgorithm::Adler32 => adler::adler32_slice(data),
+            ChecksumAlgorithm::Crc32c => crc32c::crc32c(data),
+            ChecksumAlgorithm::Xxh3 => xxhash_rust::xxh3::xxh3_64(data) as u32,
+        }
+    }
+
+    /// Reads the algorithm from the flags of a block header.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `UnsupportedFeature` error for an algorithm this version doesn't know.
+    pub fn from_block_flags(flags: u16) -> Result<Self> {
+        Self::try_from(((flags & BLOCK_FLAG_CHECKSUM_MASK) >> BLOCK_FLAG_CHECKSUM_SHIFT) as u8)
+    }
+
+    /// Flag bits recording the algorithm in a block header, `0` for Adler-32.
+    pub fn block_flags(&self) -> u16 {
+        (*self as u16) << BLOCK_FLAG_CHECKSUM_SHIFT
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_block_checksums() {
+        let data = b"123456789";
+        assert_eq!(ChecksumAlgorithm::Adler32.checksum(data), 0x091E01DE);
+        assert_eq!(ChecksumAlgorithm::Crc32c.checksum(data), 0xE3069283);
+        for algorithm in [ChecksumAlgorithm::Adler32, ChecksumAlgorithm::Crc32c, ChecksumAlgorithm::Xxh3] {
+            assert_eq!(ChecksumAlgorithm::from_block_flags(algorithm.block_flags() | 0x0001).unwrap(), algorithm);
+        }
+        assert_eq!(ChecksumAlgorithm::from_block_flags(0x0006).unwrap_err().code(), crate::ErrorCode::UnsupportedFeature);
+    }
+}
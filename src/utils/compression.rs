@@ -10,14 +10,16 @@
 //! - LZ4 compression
 
 use std::io::{Read, Write};
+use std::ops::RangeInclusive;
 use flate2::{read::ZlibDecoder, write::ZlibEncoder, Compression};
 use crate::{ZdbError, Result};
+use crate::utils::named_enum::{named_enum_serde, NamedEnum};
 
 /// Compression methods supported by ZDB files.
 ///
 /// Each variant corresponds to a specific compression algorithm that can be
 /// used for compressing dictionary data blocks.
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
 #[repr(u8)]
 pub enum CompressionMethod {
     /// No compression
@@ -35,6 +37,20 @@ pub enum CompressionMethod {
     Lz4 = 5,
 }
 
+impl NamedEnum for CompressionMethod {
+    const KIND: &'static str = "compression method";
+    const VARIANTS: &'static [(Self, &'static str, u64)] = &[
+        (CompressionMethod::None, "None", 0),
+        (CompressionMethod::Lzo, "Lzo", 1),
+        (CompressionMethod::Deflate, "Deflate", 2),
+        (CompressionMethod::Lzma, "Lzma", 3),
+        (CompressionMethod::Bzip2, "Bzip2", 4),
+        (CompressionMethod::Lz4, "Lz4", 5),
+    ];
+}
+
+named_enum_serde!(CompressionMethod);
+
 impl TryFrom<u8> for CompressionMethod {
     type Error = ZdbError;
 
@@ -46,7 +62,21 @@ impl TryFrom<u8> for CompressionMethod {
             3 => Ok(CompressionMethod::Lzma),
             4 => Ok(CompressionMethod::Bzip2),
             5 => Ok(CompressionMethod::Lz4),
-            _ => Err(ZdbError::invalid_parameter(format!("Invalid compression method:{}",value))),
+            _ => Err(crate::format::unsupported_value::<CompressionMethod>(value as u64)),
+        }
+    }
+}
+
+impl CompressionMethod {
+    /// Levels accepted by [`get_compressor_with_level`], `None` if the method has no levels.
+    ///
+    /// Higher levels compress better and slower.
+    pub fn level_range(self) -> Option<RangeInclusive<u32>> {
+        match self {
+            CompressionMethod::Deflate => Some(0..=9),
+            CompressionMethod::Bzip2 => Some(1..=9),
+            CompressionMethod::Lz4 => Some(0..=16),
+            CompressionMethod::None | CompressionMethod::Lzo | CompressionMethod::Lzma => None,
         }
     }
 }
@@ -85,6 +115,30 @@ pub trait Compressor {
     ///
     /// Returns an error if decompression fails or the output size doesn't match.
     fn decompress(&self, data: &[u8], original_size: usize) -> Result<Vec<u8>>;
+
+    /// Decompresses at least the first `prefix_size` bytes of the input data.
+    ///
+    /// Streaming codecs stop once the prefix is decoded, which makes reading an entry
+    /// near the start of a large block cheaper. The default decompresses everything.
+    ///
+    /// # Returns
+    ///
+    /// Returns the decompressed data, which may be longer than `prefix_size`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if decompression fails or the data is shorter than the prefix.
+    fn decompress_prefix(&self, data: &[u8], original_size: usize, _prefix_size: usize) -> Result<Vec<u8>> {
+        self.decompress(data, original_size)
+    }
+}
+
+/// Decodes the first `prefix_size` bytes from a streaming decoder.
+fn read_prefix<R: Read>(mut decoder: R, prefix_size: usize, codec: &str) -> Result<Vec<u8>> {
+    let mut decompressed = vec![0; prefix_size];
+    decoder.read_exact(&mut decompressed)
+        .map_err(|e| ZdbError::decompression_error(format!("{} Err:{}", codec, e)))?;
+    Ok(decompressed)
 }
 
 /// No-op compressor that passes data through unchanged.
@@ -98,6 +152,10 @@ impl Compressor for NoCompression {
     fn decompress(&self, data: &[u8], _original_size: usize) -> Result<Vec<u8>> {
         Ok(data.to_vec())
     }
+
+    fn decompress_prefix(&self, data: &[u8], _original_size: usize, prefix_size: usize) -> Result<Vec<u8>> {
+        Ok(data[..prefix_size.min(data.len())].to_vec())
+    }
 }
 
 /// LZO compression implementation.
@@ -105,7 +163,8 @@ pub struct LzoCompressor;
 
 impl Compressor for LzoCompressor {
     fn compress(&self, data: &[u8]) -> Result<Vec<u8>> {
-        let mut compressed = Vec::with_capacity(data.len());
+        // LZO writes up to the capacity, incompressible data grows beyond the input length
+        let mut compressed = Vec::with_capacity(rust_lzo::worst_compress(data.len()));
         let mut ctx = rust_lzo::LZOContext::new();
         let error = ctx.compress(data, &mut compressed);
         match error {
@@ -124,12 +183,16 @@ impl Compressor for LzoCompressor {
     }
 }
 
-/// Deflate (zlib) compression implementation.
-pub struct DeflateCompressor;
+/// Deflate (zlib) compression implementation, with the default level unless `level` is set.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DeflateCompressor {
+    pub level: Option<u32>,
+}
 
 impl Compressor for DeflateCompressor {
     fn compress(&self, data: &[u8]) -> Result<Vec<u8>> {
-        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        let level = self.level.map_or_else(Compression::default, Compression::new);
+        let mut encoder = ZlibEncoder::new(Vec::new(), level);
         encoder.write_all(data)
             .map_err(|e| ZdbError::compression_error(format!("Deflate error: {}", e)))?;
         Ok(encoder.finish()?)
@@ -145,6 +208,10 @@ impl Compressor for DeflateCompressor {
         }
         Ok(decompressed)
     }
+
+    fn decompress_prefix(&self, data: &[u8], _original_size: usize, prefix_size: usize) -> Result<Vec<u8>> {
+        read_prefix(ZlibDecoder::new(data), prefix_size, "Inflate")
+    }
 }
 
 /// LZMA compression implementation.
@@ -166,11 +233,16 @@ impl Compressor for LzmaCompressor {
     }
 }
 
-pub struct Bzip2Compressor;
+/// Bzip2 compression implementation, with the default level unless `level` is set.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Bzip2Compressor {
+    pub level: Option<u32>,
+}
 
 impl Compressor for Bzip2Compressor {
     fn compress(&self, data: &[u8]) -> Result<Vec<u8>> {
-        let mut encoder = bzip2::write::BzEncoder::new(Vec::new(), bzip2::Compression::default());
+        let level = self.level.map_or_else(bzip2::Compression::default, bzip2::Compression::new);
+        let mut encoder = bzip2::write::BzEncoder::new(Vec::new(), level);
         encoder.write_all(data)
             .map_err(|e| ZdbError::compression_error(format!("Bzip2 Err:{}", e)))?;
         Ok(encoder.finish()?)
@@ -183,14 +255,26 @@ impl Compressor for Bzip2Compressor {
             .map_err(|e| ZdbError::decompression_error(format!("Bzip2 Err:{}", e)))?;
         Ok(decompressed)
     }
+
+    fn decompress_prefix(&self, data: &[u8], _original_size: usize, prefix_size: usize) -> Result<Vec<u8>> {
+        read_prefix(bzip2::read::BzDecoder::new(data), prefix_size, "Bzip2")
+    }
 }
 
-pub struct Lz4Compressor;
+/// LZ4 compression implementation, with the default level unless `level` is set.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Lz4Compressor {
+    pub level: Option<u32>,
+}
 
 impl Compressor for Lz4Compressor {
     fn compress(&self, data: &[u8]) -> Result<Vec<u8>> {
         let mut compressed = Vec::new();
-        let mut encoder = lz4::EncoderBuilder::new().build(&mut compressed)?;
+        let mut builder = lz4::EncoderBuilder::new();
+        if let Some(level) = self.level {
+            builder.level(level);
+        }
+        let mut encoder = builder.build(&mut compressed)?;
         encoder.write_all(data)
             .map_err(|e| ZdbError::compression_error(format!("Lz4 Err:{}", e)))?;
         let (_, result) = encoder.finish();
@@ -205,15 +289,26 @@ impl Compressor for Lz4Compressor {
             .map_err(|e| ZdbError::decompression_error(format!("Lz4 Err:{}", e)))?;
     Ok(decompressed)
     }
+
+    fn decompress_prefix(&self, data: &[u8], _original_size: usize, prefix_size: usize) -> Result<Vec<u8>> {
+        read_prefix(lz4::Decoder::new(data)?, prefix_size, "Lz4")
+    }
 }
 
 pub fn get_compressor(method: CompressionMethod) -> Box<dyn Compressor> {
+    get_compressor_with_level(method, None)
+}
+
+/// Gets a compressor compressing with `level`, see [`CompressionMethod::level_range`].
+///
+/// The level is ignored by methods without levels, and only affects compression.
+pub fn get_compressor_with_level(method: CompressionMethod, level: Option<u32>) -> Box<dyn Compressor> {
     match method {
         CompressionMethod::None => Box::new(NoCompression),
         CompressionMethod::Lzo => Box::new(LzoCompressor),
-        CompressionMethod::Deflate => Box::new(DeflateCompressor),
+        CompressionMethod::Deflate => Box::new(DeflateCompressor { level }),
         CompressionMethod::Lzma => Box::new(LzmaCompressor),
-        CompressionMethod::Bzip2 => Box::new(Bzip2Compressor),
-        CompressionMethod::Lz4 => Box::new(Lz4Compressor),
+        CompressionMethod::Bzip2 => Box::new(Bzip2Compressor { level }),
+        CompressionMethod::Lz4 => Box::new(Lz4Compressor { level }),
     }
 } 
\ No newline at end of file
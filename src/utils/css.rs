@@ -0,0 +1,80 @@
+//! Helpers for the stylesheets shipped with dictionaries.
+
+/// Characters around which whitespace isn't needed.
+const CSS_PUNCTUATION: &[char] = &['{', '}', ';', ',', '>'];
+
+/// Minifies a stylesheet: comments are removed, whitespace is collapsed and dropped
+/// around punctuation, and the last semicolon of a rule is dropped.
+///
+/// Strings are kept as they are, and whitespace before a `:` is kept since it is
+/// significant in selectors, e.g. `a :hover`.
+///
+/// # Examples
+///
+/// ```rust
+/// use mdx::utils::minify_css;
+///
+/// assert_eq!(minify_css("/* entry */\n.hw ,  .pos {\n  color: red;\n}\n"), ".hw,.pos{color:red}");
+/// ```
+pub fn minify_css(css: &str) -> String {
+    let mut minified = String::with_capacity(css.len());
+    let mut pending_space = false;
+    let mut chars = css.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '/' if chars.peek() == Some(&'*') => {
+                chars.next();
+                let mut previous = '\0';
+                for c in chars.by_ref() {
+                    if previous == '*' && c == '/' {
+                        break;
+                    }
+                    previous = c;
+                }
+                pending_space = true;
+                continue;
+            }
+            c if c.is_whitespace() => {
+                pending_space = true;
+                continue;
+            }
+            _ => {}
+        }
+        if pending_space && !CSS_PUNCTUATION.contains(&c)
+            && minified.chars().last().is_some_and(|last| !CSS_PUNCTUATION.contains(&last) && last != ':') {
+            minified.push(' ');
+        }
+        pending_space = false;
+        if c == '}' && minified.ends_with(';') {
+            minified.pop();
+        }
+        minified.push(c);
+        if c == '"' || c == '\'' {
+            let mut escaped = false;
+            for s in chars.by_ref() {
+                minified.push(s);
+                if escaped {
+                    escaped = false;
+                } else if s == '\\' {
+                    escaped = true;
+                } else if s == c {
+                    break;
+                }
+            }
+        }
+    }
+    minified
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_minify_css() {
+        assert_eq!(minify_css("a :hover > b { margin : 0  1px ; }"), "a :hover>b{margin :0 1px}");
+        assert_eq!(minify_css(".x::before { content: \"a  /* b */ ;}\" }"), ".x::before{content:\"a  /* b */ ;}\"}");
+        assert_eq!(minify_css("@media (max-width: 600px) {\n  .a { color: red; }\n}\n"), "@media (max-width:600px){.a{color:red}}");
+        assert_eq!(minify_css("  /* only a comment */  "), "");
+    }
+}
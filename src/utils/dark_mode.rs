@@ -0,0 +1,84 @@
+//! Dark mode for the HTML of entries.
+//!
+//! Many dictionaries hard-code black text on a white background. [`DarkMode`] adds a
+//! `<style>` element to the HTML of an entry, either a stylesheet chosen by the
+//! application or rules inverting the colors of the page. Inverted pages invert images
+//! and videos a second time, so they keep their colors.
+//!
+//! The style is appended to the `<head>` element if there is one, since it must come
+//! after the styles of the dictionary to override them, and prepended to the HTML otherwise.
+
+use std::borrow::Cow;
+use std::cell::Cell;
+
+use lol_html::html_content::ContentType;
+use lol_html::{element, rewrite_str, Settings};
+
+use crate::{Result, ZdbError};
+
+/// Rules of [`DarkMode::Invert`].
+const INVERT_CSS: &str = "html{filter:invert(1) hue-rotate(180deg);background:#fff}\
+    img,video,picture,canvas,svg image,[style*=\"background-image\"]{filter:invert(1) hue-rotate(180deg)}";
+
+/// Dark mode applied to the HTML of entries, see the [module documentation](self).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DarkMode {
+    /// Inverts the colors of the page except for images
+    Invert,
+    /// Adds a stylesheet, e.g. one overriding the colors of a dictionary's classes
+    Stylesheet(String),
+}
+
+impl DarkMode {
+    /// The CSS added to the HTML, which can also be injected once into the head of a page
+    /// showing several entries.
+    pub fn css(&self) -> Cow<'_, str> {
+        match self {
+            DarkMode::Invert => Cow::Borrowed(INVERT_CSS),
+            DarkMode::Stylesheet(css) => Cow::Borrowed(css),
+        }
+    }
+
+    /// Adds the dark mode style to the HTML of an entry.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `GeneralError` if the HTML can't be rewritten.
+    pub fn apply(&self, html: &str) -> Result<String> {
+        // `</` would end the style element early
+        let style = format!("<style>{}</style>", self.css().replace("</", "<\\/"));
+        let injected = Cell::new(false);
+        let settings = Settings {
+            element_content_handlers: vec![element!("head", |el| {
+                if !injected.get() {
+                    el.append(&style, ContentType::Html);
+                    injected.set(true);
+                }
+                Ok(())
+            })],
+            ..Settings::default()
+        };
+        let rewritten = rewrite_str(html, settings).map_err(|e| ZdbError::general_error(format!("HTML rewriting error: {}", e)))?;
+        if injected.get() {
+            Ok(rewritten)
+        } else {
+            Ok(style + &rewritten)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dark_mode_style_placement() {
+        let dark_mode = DarkMode::Stylesheet("body{color:#eee}".to_string());
+        assert_eq!(dark_mode.apply("<p>run</p>").unwrap(), "<style>body{color:#eee}</style><p>run</p>");
+        assert_eq!(dark_mode.apply("<html><head><link rel=\"stylesheet\" href=\"d.css\"></head><body>run</body></html>").unwrap(),
+            "<html><head><link rel=\"stylesheet\" href=\"d.css\"><style>body{color:#eee}</style></head><body>run</body></html>");
+        let dark_mode = DarkMode::Stylesheet("a{}</style><script>".to_string());
+        assert_eq!(dark_mode.apply("").unwrap(), "<style>a{}<\\/style><script></style>");
+        assert!(DarkMode::Invert.apply("<img src=\"a.png\">").unwrap().starts_with("<style>html{filter:invert(1)"));
+    }
+}
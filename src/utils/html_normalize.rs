@@ -0,0 +1,232 @@
+//! Normalization of legacy markup in the HTML of entries.
+//!
+//! Dictionaries made for old readers use markup modern WebViews render poorly or not
+//! at all. [`HtmlNormalizer`] fixes the common cases:
+//!
+//! - Ruby in Aozora Bunko notation, `｜漢字《かんじ》` or `漢字《かんじ》` where the base
+//!   is the run of kanji before the reading, becomes `<ruby>漢字<rt>かんじ</rt></ruby>`
+//! - `<embed>` and `<bgsound>` elements playing audio become `sound://` links, which
+//!   readers play on click instead of on load
+//! - `<font>` elements become `<span>` elements styled with the same color, size and face
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use lol_html::html_content::ContentType;
+use lol_html::{doc_text, element, rewrite_str, EndTagHandler, Settings};
+
+use crate::{Result, ZdbError};
+
+/// Elements whose text isn't searched for ruby notation.
+const SKIPPED_ELEMENTS: &str = "script, style, textarea, ruby";
+/// Extensions of the sources of `<embed>` elements turned into sound links.
+const AUDIO_EXTENSIONS: &[&str] = &["mp3", "wav", "ogg", "oga", "spx", "m4a", "aac", "opus", "mid", "midi", "wma"];
+/// CSS font sizes of the `size` attribute of `<font>`, from 1 to 7.
+const FONT_SIZES: &[&str] = &["x-small", "small", "medium", "large", "x-large", "xx-large", "xxx-large"];
+
+/// Passes of [`HtmlNormalizer`], all enabled by default.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NormalizeOptions {
+    /// Convert ruby in Aozora Bunko notation to `<ruby>` elements
+    pub ruby: bool,
+    /// Replace `<embed>` and `<bgsound>` audio with `sound://` links
+    pub media: bool,
+    /// Replace `<font>` elements with styled `<span>` elements
+    pub font: bool,
+}
+
+impl Default for NormalizeOptions {
+    fn default() -> Self {
+        Self { ruby: true, media: true, font: true }
+    }
+}
+
+#[derive(Default)]
+struct NormalizeState {
+    skipped_depth: u32,
+    text_node: String,
+}
+
+/// Fixes legacy markup in HTML, see the [module documentation](self).
+#[derive(Debug, Clone, Default)]
+pub struct HtmlNormalizer {
+    options: NormalizeOptions,
+}
+
+/// Returns whether a character can be the base of ruby without an explicit `｜`.
+fn is_kanji(c: char) -> bool {
+    matches!(c, '\u{4E00}'..='\u{9FFF}' | '\u{3400}'..='\u{4DBF}' | '\u{F900}'..='\u{FAFF}' | '\u{20000}'..='\u{2FA1F}' | '々' | '〆' | 'ヶ')
+}
+
+/// Escapes text for a double-quoted attribute.
+fn escape_attribute(text: &str) -> String {
+    text.replace('&', "&amp;").replace('"', "&quot;").replace('<', "&lt;")
+}
+
+/// Converts the `size` attribute of `<font>` to a CSS font size.
+fn font_size(size: &str) -> Option<&'static str> {
+    let size = size.trim();
+    if size.starts_with('+') {
+        return Some("larger");
+    }
+    if size.starts_with('-') {
+        return Some("smaller");
+    }
+    let size = size.parse::<usize>().ok()?;
+    Some(FONT_SIZES[size.clamp(1, FONT_SIZES.len()) - 1])
+}
+
+impl HtmlNormalizer {
+    pub fn new(options: NormalizeOptions) -> Self {
+        Self { options }
+    }
+
+    /// Normalizes the markup of an HTML document.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `GeneralError` if the HTML can't be rewritten.
+    pub fn normalize_html(&self, html: &str) -> Result<String> {
+        let state = Rc::new(RefCell::new(NormalizeState::default()));
+        let skipped_state = state.clone();
+        let text_state = state.clone();
+        let mut element_content_handlers = Vec::new();
+        let mut document_content_handlers = Vec::new();
+        if self.options.ruby {
+            element_content_handlers.push(element!(SKIPPED_ELEMENTS, move |el| {
+                if let Some(handlers) = el.end_tag_handlers() {
+                    skipped_state.borrow_mut().skipped_depth += 1;
+                    let state = skipped_state.clone();
+                    let handler: EndTagHandler<'static> = Box::new(move |_| {
+                        let mut state = state.borrow_mut();
+                        state.skipped_depth = state.skipped_depth.saturating_sub(1);
+                        Ok(())
+                    });
+                    handlers.push(handler);
+                }
+                Ok(())
+            }));
+            document_content_handlers.push(doc_text!(move |chunk| {
+                let mut state = text_state.borrow_mut();
+                if state.skipped_depth > 0 {
+                    return Ok(());
+                }
+                // A text node may come in several chunks, it's replaced as a whole by the last one
+                state.text_node.push_str(chunk.as_str());
+                if chunk.last_in_text_node() {
+                    let raw = std::mem::take(&mut state.text_node);
+                    chunk.replace(&Self::convert_ruby(&raw), ContentType::Html);
+                } else {
+                    chunk.remove();
+                }
+                Ok(())
+            }));
+        }
+        if self.options.media {
+            element_content_handlers.push(element!("embed[src], bgsound[src]", |el| {
+                let src = el.get_attribute("src").unwrap_or_default();
+                let path = src.split(['?', '#']).next().unwrap_or_default();
+                let is_audio = path.rsplit_once('.')
+                    .is_some_and(|(_, extension)| AUDIO_EXTENSIONS.contains(&extension.to_ascii_lowercase().as_str()));
+                if is_audio {
+                    let target = if src.contains("://") { src.clone() } else { format!("sound://{}", src.trim_start_matches('/')) };
+                    el.replace(&format!("<a class=\"mdx-sound\" href=\"{}\">\u{1F50A}</a>", escape_attribute(&target)), ContentType::Html);
+                }
+                Ok(())
+            }));
+        }
+        if self.options.font {
+            element_content_handlers.push(element!("font", |el| {
+                let mut style = Vec::new();
+                if let Some(color) = el.get_attribute("color").filter(|color| !color.trim().is_empty()) {
+                    style.push(format!("color:{}", color.trim()));
+                }
+                if let Some(size) = el.get_attribute("size").as_deref().and_then(font_size) {
+                    style.push(format!("font-size:{}", size));
+                }
+                if let Some(face) = el.get_attribute("face").filter(|face| !face.trim().is_empty()) {
+                    style.push(format!("font-family:{}", face.trim()));
+                }
+                // An existing style attribute comes last to keep precedence
+                if let Some(existing) = el.get_attribute("style") {
+                    style.push(existing);
+                }
+                for name in ["color", "size", "face"] {
+                    el.remove_attribute(name);
+                }
+                if !style.is_empty() {
+                    el.set_attribute("style", &style.join(";"))
+                        .map_err(|e| ZdbError::general_error(format!("HTML rewriting error: {}", e)))?;
+                }
+                el.set_tag_name("span")
+                    .map_err(|e| ZdbError::general_error(format!("HTML rewriting error: {}", e)))?;
+                Ok(())
+            }));
+        }
+        if element_content_handlers.is_empty() && document_content_handlers.is_empty() {
+            return Ok(html.to_string());
+        }
+        let settings = Settings { element_content_handlers, document_content_handlers, ..Settings::default() };
+        rewrite_str(html, settings).map_err(|e| ZdbError::general_error(format!("HTML rewriting error: {}", e)))
+    }
+
+    /// Converts ruby in Aozora Bunko notation in a text node as it appears in HTML.
+    fn convert_ruby(raw: &str) -> String {
+        if !raw.contains('《') {
+            return raw.to_string();
+        }
+        let mut converted = String::with_capacity(raw.len() + 32);
+        let mut rest = raw;
+        while let Some(open) = rest.find('《') {
+            let Some(close) = rest[open..].find('》').map(|close| open + close) else {
+                break;
+            };
+            let reading = &rest[open + '《'.len_utf8()..close];
+            let before = &rest[..open];
+            let base_start = match before.rfind('｜') {
+                Some(bar) if !before[bar..].contains('》') => Some(bar),
+                _ => None,
+            };
+            let (kept, base) = match base_start {
+                Some(bar) => (&before[..bar], &before[bar + '｜'.len_utf8()..]),
+                None => {
+                    let base_len: usize = before.chars().rev().take_while(|c| is_kanji(*c)).map(char::len_utf8).sum();
+                    before.split_at(before.len() - base_len)
+                }
+            };
+            converted.push_str(kept);
+            if base.is_empty() || reading.is_empty() {
+                converted.push_str(&rest[kept.len()..close + '》'.len_utf8()]);
+            } else {
+                converted.push_str(&format!("<ruby>{}<rt>{}</rt></ruby>", base, reading));
+            }
+            rest = &rest[close + '》'.len_utf8()..];
+        }
+        converted.push_str(rest);
+        converted
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ruby_notation() {
+        let normalizer = HtmlNormalizer::default();
+        assert_eq!(normalizer.normalize_html("<p>東京《とうきょう》へ｜行く《いく》</p>").unwrap(),
+            "<p><ruby>東京<rt>とうきょう</rt></ruby>へ<ruby>行く<rt>いく</rt></ruby></p>");
+        assert_eq!(normalizer.normalize_html("かな《かな》 <ruby>漢<rt>かん</rt></ruby>《x》").unwrap(), "かな《かな》 <ruby>漢<rt>かん</rt></ruby>《x》");
+    }
+
+    #[test]
+    fn test_media_and_font() {
+        let normalizer = HtmlNormalizer::default();
+        assert_eq!(normalizer.normalize_html("<embed src=\"/snd/run.MP3\"><bgsound src=\"a.swf\">").unwrap(),
+            "<a class=\"mdx-sound\" href=\"sound://snd/run.MP3\">\u{1F50A}</a><bgsound src=\"a.swf\">");
+        assert_eq!(normalizer.normalize_html("<font color=\"red\" size=\"5\" style=\"margin:0\">n.</font>").unwrap(),
+            "<span style=\"color:red;font-size:x-large;margin:0\">n.</span>");
+        let normalizer = HtmlNormalizer::new(NormalizeOptions { font: false, ..Default::default() });
+        assert_eq!(normalizer.normalize_html("<font face=\"SimSun\">字</font>").unwrap(), "<font face=\"SimSun\">字</font>");
+    }
+}
@@ -0,0 +1,364 @@
+//! Short previews of entries for list views.
+//!
+//! [`HtmlPreviewer`] cuts the HTML of an entry after its first sentences or characters,
+//! as plain text or as simplified HTML keeping only inline formatting. Elements of the
+//! classes a dictionary marks as secondary, e.g. examples or etymologies, are left out,
+//! see [`PreviewOptions::skip_classes`]. Like [`HtmlTextExtractor`](super::HtmlTextExtractor),
+//! the HTML is fed in pieces and parsing stops once the preview is complete.
+//!
+//! Simplified HTML is always well formed: tags left open by the cut are closed.
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use lol_html::{element, text, EndTagHandler, HtmlRewriter, Selector, Settings};
+
+use crate::{Result, ZdbError};
+
+/// Elements starting and ending a line.
+const BLOCK_ELEMENTS: &str = "address, article, aside, blockquote, br, dd, div, dl, dt, figcaption, figure, footer, \
+    h1, h2, h3, h4, h5, h6, header, hr, li, main, nav, ol, p, pre, section, table, td, th, tr, ul";
+/// Elements whose text isn't shown.
+const HIDDEN_ELEMENTS: &str = "head, script, style, template";
+/// Elements kept by [`PreviewFormat::Html`], without their attributes.
+const INLINE_ELEMENTS: &str = "b, i, em, strong, sub, sup, u";
+/// Characters ending a sentence when followed by whitespace or the end of a block.
+const SENTENCE_TERMINATORS: &[char] = &['.', '!', '?', ';'];
+/// Characters ending a sentence wherever they appear.
+const CJK_SENTENCE_TERMINATORS: &[char] = &['。', '！', '？', '；'];
+/// Appended to a preview cut by [`PreviewOptions::max_chars`].
+const ELLIPSIS: char = '…';
+
+/// Output of [`HtmlPreviewer`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PreviewFormat {
+    /// Plain text with a line break between blocks
+    #[default]
+    Text,
+    /// Escaped text keeping `b`, `i`, `em`, `strong`, `sub`, `sup` and `u` elements, `<br>` between blocks
+    Html,
+}
+
+/// Options of an entry preview, see [`MdxReader::get_preview`](crate::MdxReader::get_preview).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PreviewOptions {
+    pub format: PreviewFormat,
+    /// Number of sentences of the preview, 0 for no limit (default: 2)
+    pub max_sentences: usize,
+    /// Maximum number of characters of text, counting line breaks but not markup (default: 200)
+    pub max_chars: usize,
+    /// Classes of the elements left out, `None` for the classes stored in the dictionary,
+    /// see [`BuilderConfig::preview_skip_classes`](crate::builder::BuilderConfig::preview_skip_classes)
+    pub skip_classes: Option<Vec<String>>,
+}
+
+impl Default for PreviewOptions {
+    fn default() -> Self {
+        Self { format: PreviewFormat::Text, max_sentences: 2, max_chars: 200, skip_classes: None }
+    }
+}
+
+/// Returns whether a class name can be used by [`PreviewOptions::skip_classes`].
+pub fn is_valid_class_name(class: &str) -> bool {
+    !class.is_empty()
+        && class.chars().all(|c| c.is_alphanumeric() || c == '-' || c == '_')
+        && format!(".{}", class).parse::<Selector>().is_ok()
+}
+
+#[derive(Default)]
+struct PreviewState {
+    html: bool,
+    output: String,
+    char_count: usize,
+    max_chars: usize,
+    sentence_count: usize,
+    max_sentences: usize,
+    /// Set once the preview is complete, further input is ignored
+    full: bool,
+    /// Whether the preview was cut by `max_chars`
+    cut: bool,
+    /// Whether the last character ended a sentence if followed by whitespace
+    pending_sentence_end: bool,
+    /// Separator to write before the next visible character
+    pending_separator: Option<&'static str>,
+    /// Number of open hidden or skipped elements
+    hidden_depth: usize,
+    /// Inline elements written and not closed yet
+    open_elements: Vec<String>,
+    /// Text of the current text node, which may arrive in several chunks
+    text_node: String,
+}
+
+impl PreviewState {
+    fn end_sentence(&mut self) {
+        self.pending_sentence_end = false;
+        self.sentence_count += 1;
+        if self.max_sentences > 0 && self.sentence_count >= self.max_sentences {
+            self.full = true;
+        }
+    }
+
+    fn break_line(&mut self) {
+        if self.pending_sentence_end {
+            self.end_sentence();
+        }
+        if self.char_count > 0 {
+            self.pending_separator = Some(if self.html { "<br>" } else { "\n" });
+        }
+    }
+
+    fn push_text(&mut self, text: &str) {
+        for c in text.chars() {
+            if self.full {
+                return;
+            }
+            if c.is_whitespace() {
+                if self.pending_sentence_end {
+                    self.end_sentence();
+                }
+                if self.char_count > 0 && self.pending_separator.is_none() {
+                    self.pending_separator = Some(" ");
+                }
+                continue;
+            }
+            self.pending_sentence_end = false;
+            let needed = if self.pending_separator.is_some() { 2 } else { 1 };
+            if self.char_count + needed > self.max_chars {
+                self.full = true;
+                self.cut = true;
+                return;
+            }
+            self.flush_separator();
+            match c {
+                '&' if self.html => self.output.push_str("&amp;"),
+                '<' if self.html => self.output.push_str("&lt;"),
+                '>' if self.html => self.output.push_str("&gt;"),
+                _ => self.output.push(c),
+            }
+            self.char_count += 1;
+            if CJK_SENTENCE_TERMINATORS.contains(&c) {
+                self.end_sentence();
+            } else if SENTENCE_TERMINATORS.contains(&c) {
+                self.pending_sentence_end = true;
+            }
+        }
+    }
+
+    /// Writes the pending separator, which counts as one character.
+    fn flush_separator(&mut self) {
+        if let Some(separator) = self.pending_separator.take() {
+            self.output.push_str(separator);
+            self.char_count += 1;
+        }
+    }
+
+    fn open_element(&mut self, tag_name: String) {
+        self.flush_separator();
+        self.output.push_str(&format!("<{}>", tag_name));
+        self.open_elements.push(tag_name);
+    }
+
+    fn close_element(&mut self) {
+        if let Some(tag_name) = self.open_elements.pop() {
+            // An element left empty, e.g. by skipped content, is dropped
+            let start_tag = format!("<{}>", tag_name);
+            if self.output.ends_with(&start_tag) {
+                self.output.truncate(self.output.len() - start_tag.len());
+            } else {
+                self.output.push_str(&format!("</{}>", tag_name));
+            }
+        }
+    }
+}
+
+/// Streaming generator of an entry preview, see the [module documentation](self).
+pub struct HtmlPreviewer {
+    state: Rc<RefCell<PreviewState>>,
+    rewriter: HtmlRewriter<'static, fn(&[u8])>,
+}
+
+fn discard_output(_: &[u8]) {}
+
+impl HtmlPreviewer {
+    /// Creates a previewer.
+    ///
+    /// # Arguments
+    ///
+    /// * `options` - Options of the preview, its `skip_classes` are ignored
+    /// * `skip_classes` - Classes of the elements left out
+    ///
+    /// # Errors
+    ///
+    /// Returns an `InvalidParameter` error if a class name isn't valid, see [`is_valid_class_name`].
+    pub fn new(options: &PreviewOptions, skip_classes: &[String]) -> Result<Self> {
+        if let Some(class) = skip_classes.iter().find(|class| !is_valid_class_name(class)) {
+            return Err(ZdbError::invalid_parameter(format!("Invalid class name: {:?}", class)));
+        }
+        let hidden_selector = std::iter::once(HIDDEN_ELEMENTS.to_string())
+            .chain(skip_classes.iter().map(|class| format!(".{}", class)))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let html = options.format == PreviewFormat::Html;
+        let state = Rc::new(RefCell::new(PreviewState {
+            html,
+            max_chars: options.max_chars,
+            max_sentences: options.max_sentences,
+            full: options.max_chars == 0,
+            ..Default::default()
+        }));
+        let block_state = state.clone();
+        let hidden_state = state.clone();
+        let inline_state = state.clone();
+        let text_state = state.clone();
+        let mut element_content_handlers = vec![
+            element!(BLOCK_ELEMENTS, move |el| {
+                block_state.borrow_mut().break_line();
+                if let Some(handlers) = el.end_tag_handlers() {
+                    let state = block_state.clone();
+                    let handler: EndTagHandler<'static> = Box::new(move |_| {
+                        state.borrow_mut().break_line();
+                        Ok(())
+                    });
+                    handlers.push(handler);
+                }
+                Ok(())
+            }),
+            element!(hidden_selector, move |el| {
+                if let Some(handlers) = el.end_tag_handlers() {
+                    hidden_state.borrow_mut().hidden_depth += 1;
+                    let state = hidden_state.clone();
+                    let handler: EndTagHandler<'static> = Box::new(move |_| {
+                        let mut state = state.borrow_mut();
+                        state.hidden_depth = state.hidden_depth.saturating_sub(1);
+                        Ok(())
+                    });
+                    handlers.push(handler);
+                }
+                Ok(())
+            }),
+            text!("*", move |chunk| {
+                let mut state = text_state.borrow_mut();
+                state.text_node.push_str(chunk.as_str());
+                if chunk.last_in_text_node() {
+                    let raw = std::mem::take(&mut state.text_node);
+                    if state.hidden_depth == 0 {
+                        let decoded = htmlescape::decode_html(&raw).unwrap_or(raw);
+                        state.push_text(&decoded);
+                    }
+                }
+                Ok(())
+            }),
+        ];
+        if html {
+            element_content_handlers.push(element!(INLINE_ELEMENTS, move |el| {
+                {
+                    let state = inline_state.borrow();
+                    if state.full || state.hidden_depth > 0 {
+                        return Ok(());
+                    }
+                }
+                let tag_name = el.tag_name();
+                if let Some(handlers) = el.end_tag_handlers() {
+                    inline_state.borrow_mut().open_element(tag_name);
+                    let state = inline_state.clone();
+                    let handler: EndTagHandler<'static> = Box::new(move |_| {
+                        // Once the preview is complete, the open elements are closed by `finish`
+                        let mut state = state.borrow_mut();
+                        if !state.full {
+                            state.close_element();
+                        }
+                        Ok(())
+                    });
+                    handlers.push(handler);
+                }
+                Ok(())
+            }));
+        }
+        let settings = Settings { element_content_handlers, ..Settings::default() };
+        let rewriter = HtmlRewriter::new(settings, discard_output as fn(&[u8]));
+        Ok(Self { state, rewriter })
+    }
+
+    /// Parses the next piece of HTML.
+    ///
+    /// # Returns
+    ///
+    /// Returns true once the preview is complete, further input is ignored.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `GeneralError` if the HTML can't be parsed.
+    pub fn write(&mut self, html: &[u8]) -> Result<bool> {
+        if self.state.borrow().full {
+            return Ok(true);
+        }
+        self.rewriter.write(html)
+            .map_err(|e| ZdbError::general_error(format!("HTML rewriting error: {}", e)))?;
+        Ok(self.state.borrow().full)
+    }
+
+    /// Finishes parsing and returns the preview, ending with an ellipsis if it was cut
+    /// by [`PreviewOptions::max_chars`].
+    ///
+    /// # Errors
+    ///
+    /// Returns a `GeneralError` if the HTML can't be parsed.
+    pub fn finish(self) -> Result<String> {
+        if !self.state.borrow().full {
+            self.rewriter.end()
+                .map_err(|e| ZdbError::general_error(format!("HTML rewriting end error: {}", e)))?;
+        }
+        let mut state = self.state.borrow_mut();
+        if state.cut {
+            state.output.push(ELLIPSIS);
+        }
+        while !state.open_elements.is_empty() {
+            state.close_element();
+        }
+        Ok(std::mem::take(&mut state.output))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn preview(html: &str, options: &PreviewOptions, skip_classes: &[&str]) -> String {
+        let skip_classes: Vec<String> = skip_classes.iter().map(|class| class.to_string()).collect();
+        let mut previewer = HtmlPreviewer::new(options, &skip_classes).unwrap();
+        for chunk in html.as_bytes().chunks(3) {
+            if previewer.write(chunk).unwrap() {
+                break;
+            }
+        }
+        previewer.finish().unwrap()
+    }
+
+    #[test]
+    fn test_sentences_and_skipped_classes() {
+        let html = "<h1>run</h1><p>To move <b>fast</b>. <span class=\"ex\">She runs daily.</span> To flow; e.g. water.</p><p>To manage.</p>";
+        let options = PreviewOptions::default();
+        assert_eq!(preview(html, &options, &[]), "run\nTo move fast. She runs daily.");
+        assert_eq!(preview(html, &options, &["ex"]), "run\nTo move fast. To flow;");
+        let options = PreviewOptions { max_sentences: 0, ..Default::default() };
+        assert_eq!(preview(html, &options, &["ex"]), "run\nTo move fast. To flow; e.g. water.\nTo manage.");
+        assert_eq!(preview("<p>一。二。三。</p>", &PreviewOptions::default(), &[]), "一。二。");
+    }
+
+    #[test]
+    fn test_html_preview_is_well_formed() {
+        let html = "<div class=\"def\">A <b>bold <i>claim &amp; more</i></b> text</div><div>next</div>";
+        let options = PreviewOptions { format: PreviewFormat::Html, max_chars: 12, ..Default::default() };
+        assert_eq!(preview(html, &options, &[]), "A <b>bold <i>claim…</i></b>");
+        let options = PreviewOptions { format: PreviewFormat::Html, ..Default::default() };
+        assert_eq!(preview(html, &options, &[]), "A <b>bold <i>claim &amp; more</i></b> text<br>next");
+        assert_eq!(preview("<p><b><span class=\"ex\">x</span></b>y</p>", &options, &["ex"]), "y");
+    }
+
+    #[test]
+    fn test_invalid_class_name() {
+        assert!(is_valid_class_name("ex-1"));
+        assert!(!is_valid_class_name("a b"));
+        assert!(HtmlPreviewer::new(&PreviewOptions::default(), &["x{".to_string()]).is_err());
+    }
+}
@@ -0,0 +1,184 @@
+//! Plain text previews of HTML content.
+//!
+//! Unlike [`extract_text_from_html`](super::extract_text_from_html), which joins all text
+//! with spaces for indexing, [`HtmlTextExtractor`] keeps the line structure: every block
+//! element starts a new line. Scripts and stylesheets are skipped and entities decoded.
+//! The HTML is fed in pieces and the extractor reports when it has collected enough
+//! text, so a short preview of a long entry doesn't need to parse all of it.
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use lol_html::{element, text, EndTagHandler, HtmlRewriter, Settings};
+
+use crate::{Result, ZdbError};
+
+/// Elements starting and ending a line.
+const BLOCK_ELEMENTS: &str = "address, article, aside, blockquote, br, dd, div, dl, dt, figcaption, figure, footer, \
+    h1, h2, h3, h4, h5, h6, header, hr, li, main, nav, ol, p, pre, section, table, td, th, tr, ul";
+/// Elements whose text isn't shown.
+const HIDDEN_ELEMENTS: &str = "head, script, style, template";
+
+#[derive(Default)]
+struct TextState {
+    text: String,
+    char_count: usize,
+    max_chars: usize,
+    /// Separator to write before the next visible character
+    pending_separator: Option<char>,
+    /// Number of open hidden elements
+    hidden_depth: usize,
+    /// Text of the current text node, which may arrive in several chunks
+    text_node: String,
+}
+
+impl TextState {
+    fn is_full(&self) -> bool {
+        self.char_count >= self.max_chars
+    }
+
+    fn break_line(&mut self) {
+        if !self.text.is_empty() {
+            self.pending_separator = Some('\n');
+        }
+    }
+
+    fn push_text(&mut self, text: &str) {
+        for c in text.chars() {
+            if self.is_full() {
+                return;
+            }
+            if c.is_whitespace() {
+                if !self.text.is_empty() && self.pending_separator.is_none() {
+                    self.pending_separator = Some(' ');
+                }
+                continue;
+            }
+            if let Some(separator) = self.pending_separator.take() {
+                self.text.push(separator);
+                self.char_count += 1;
+                if self.is_full() {
+                    return;
+                }
+            }
+            self.text.push(c);
+            self.char_count += 1;
+        }
+    }
+}
+
+/// Streaming converter of HTML to plain text with line breaks between blocks.
+pub struct HtmlTextExtractor {
+    state: Rc<RefCell<TextState>>,
+    rewriter: HtmlRewriter<'static, fn(&[u8])>,
+}
+
+fn discard_output(_: &[u8]) {}
+
+impl HtmlTextExtractor {
+    /// Creates an extractor collecting at most `max_chars` characters of text.
+    pub fn new(max_chars: usize) -> Self {
+        let state = Rc::new(RefCell::new(TextState { max_chars, ..Default::default() }));
+        let block_state = state.clone();
+        let hidden_state = state.clone();
+        let text_state = state.clone();
+        let settings = Settings {
+            element_content_handlers: vec![
+                element!(BLOCK_ELEMENTS, move |el| {
+                    block_state.borrow_mut().break_line();
+                    if let Some(handlers) = el.end_tag_handlers() {
+                        let state = block_state.clone();
+                        let handler: EndTagHandler<'static> = Box::new(move |_| {
+                            state.borrow_mut().break_line();
+                            Ok(())
+                        });
+                        handlers.push(handler);
+                    }
+                    Ok(())
+                }),
+                element!(HIDDEN_ELEMENTS, move |el| {
+                    if let Some(handlers) = el.end_tag_handlers() {
+                        hidden_state.borrow_mut().hidden_depth += 1;
+                        let state = hidden_state.clone();
+                        let handler: EndTagHandler<'static> = Box::new(move |_| {
+                            let mut state = state.borrow_mut();
+                            state.hidden_depth = state.hidden_depth.saturating_sub(1);
+                            Ok(())
+                        });
+                        handlers.push(handler);
+                    }
+                    Ok(())
+                }),
+                text!("*", move |chunk| {
+                    let mut state = text_state.borrow_mut();
+                    state.text_node.push_str(chunk.as_str());
+                    if chunk.last_in_text_node() {
+                        let raw = std::mem::take(&mut state.text_node);
+                        if state.hidden_depth == 0 {
+                            let decoded = htmlescape::decode_html(&raw).unwrap_or(raw);
+                            state.push_text(&decoded);
+                        }
+                    }
+                    Ok(())
+                }),
+            ],
+            ..Settings::default()
+        };
+        let rewriter = HtmlRewriter::new(settings, discard_output as fn(&[u8]));
+        Self { state, rewriter }
+    }
+
+    /// Parses the next piece of HTML.
+    ///
+    /// # Returns
+    ///
+    /// Returns true once `max_chars` characters are collected, further input is ignored.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `GeneralError` if the HTML can't be parsed.
+    pub fn write(&mut self, html: &[u8]) -> Result<bool> {
+        if self.state.borrow().is_full() {
+            return Ok(true);
+        }
+        self.rewriter.write(html)
+            .map_err(|e| ZdbError::general_error(format!("HTML rewriting error: {}", e)))?;
+        Ok(self.state.borrow().is_full())
+    }
+
+    /// Finishes parsing and returns the collected text.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `GeneralError` if the HTML can't be parsed.
+    pub fn finish(self) -> Result<String> {
+        if !self.state.borrow().is_full() {
+            self.rewriter.end()
+                .map_err(|e| ZdbError::general_error(format!("HTML rewriting end error: {}", e)))?;
+        }
+        Ok(std::mem::take(&mut self.state.borrow_mut().text))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn extract(html: &str, max_chars: usize) -> String {
+        let mut extractor = HtmlTextExtractor::new(max_chars);
+        for chunk in html.as_bytes().chunks(3) {
+            if extractor.write(chunk).unwrap() {
+                break;
+            }
+        }
+        extractor.finish().unwrap()
+    }
+
+    #[test]
+    fn test_block_aware_text() {
+        let html = "<style>b{}</style><h1>caf&eacute;</h1><p>a  <b>cup</b>\nof   coffee</p><ol><li>one</li><li>two<br>lines</li></ol>";
+        assert_eq!(extract(html, 100), "café\na cup of coffee\none\ntwo\nlines");
+        assert_eq!(extract(html, 8), "café\na c");
+        assert_eq!(extract(html, 0), "");
+    }
+}
@@ -9,10 +9,16 @@
 //! The actual implementation is selected at compile time based on feature flags:
 //! - `rust-icu` feature: Uses the rust_icu crate (requires system ICU library)
 //! - `icu` feature: Uses the pure Rust icu crate (default)
+//! - `icu-core` feature: Uses the pure Rust icu crate without its compiled collation data,
+//!   the application provides data for its locales with `UCollator::try_from_provider`
+//!   and [`register_collator`]
 
 // ICU abstraction layer to support both rust-icu and icu crates
 // This module provides a unified interface for ICU functionality
 
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, OnceLock};
+
 use crate::Result;
 
 #[cfg(feature = "rust-icu")]
@@ -27,6 +33,11 @@ mod rust_icu_impl {
     pub struct UCollator {
         inner: RustIcuCollator,
     }
+
+    // SAFETY: the collator is never mutated after creation, and ICU documents
+    // ucol_strcoll and ucol_getSortKey as safe to call concurrently on one UCollator.
+    unsafe impl Send for UCollator {}
+    unsafe impl Sync for UCollator {}
     
     /// Unicode character wrapper.
     #[derive(Debug)]
@@ -67,18 +78,36 @@ mod rust_icu_impl {
     pub type IcuError = RustIcuError;
 }
 
-#[cfg(feature = "icu")]
+#[cfg(feature = "icu-core")]
 mod icu_impl {
     use super::*;
-    use icu_collator::{Collator, CollatorBorrowed};
+    use icu_collator::Collator;
+    #[cfg(feature = "icu")]
+    use icu_collator::CollatorBorrowed;
     use icu_collator::options::CollatorOptions;
+    use icu_collator::provider::{
+        CollationDiacriticsV1, CollationJamoV1, CollationMetadataV1, CollationReorderingV1,
+        CollationRootV1, CollationSpecialPrimariesV1, CollationTailoringV1,
+    };
+    use icu_normalizer::provider::{NormalizerNfdDataV1, NormalizerNfdTablesV1};
+    use icu_provider::DataProvider;
+
+    /// Collation data of a collator.
+    #[derive(Debug)]
+    enum CollatorData {
+        /// Borrows the data compiled into the library
+        #[cfg(feature = "icu")]
+        Compiled(CollatorBorrowed<'static>),
+        /// Owns data loaded from a provider of the application
+        Provided(Box<Collator>),
+    }
     
     /// Unicode collator using pure Rust icu backend.
     ///
     /// Provides locale-aware string comparison and sort key generation.
     #[derive(Debug)]
     pub struct UCollator {
-        collator: CollatorBorrowed<'static>,
+        collator: CollatorData,
         #[allow(dead_code)]
         locale_str: String,
     }
@@ -149,150 +178,86 @@ mod icu_impl {
         /// let collator = UCollator::try_from("")?;
         /// ```
         pub fn try_from(locale_str: &str) -> Result<Self> {
-            use icu_locale::Locale;
-            use icu_collator::CollatorPreferences;
-            use icu_collator::options::{Strength, AlternateHandling, CaseLevel};
-            
             log::info!("Creating collator for locale: {}", locale_str);
-            if locale_str.is_empty() {
-                return Ok(Self {
-                    collator: Collator::try_new(CollatorPreferences::default(), CollatorOptions::default())
-                        .map_err(|e| {
-                            log::error!("Failed to create default collator: {:?}", e);
-                            crate::error::ZdbError::invalid_parameter(
-                                format!("Failed to create default ICU collator: {:?}", e)
-                            )
-                        })?,
-                    locale_str: "".to_string(),
+            #[cfg(feature = "icu")]
+            {
+                let (prefs, options) = collator_preferences(locale_str)?;
+                log::info!("Creating collator with preferences: {:?} and options: {:?}", prefs, options);
+
+                // Compiled data gives a collator borrowing static data
+                let collator = Collator::try_new(prefs, options)
+                    .map_err(|e| {
+                        log::error!("Failed to create collator: {:?}", e);
+                        crate::error::ZdbError::invalid_parameter(
+                            format!("Failed to create ICU collator: {:?}", e)
+                        )
+                    })?;
+
+                log::info!("Successfully created collator for locale: {}", locale_str);
+
+                Ok(Self {
+                    collator: CollatorData::Compiled(collator),
+                    locale_str: locale_str.to_string(),
                 })
             }
-            // Parse the BCP-47 locale string
-            let locale: Locale = locale_str.parse()
-                .map_err(|e| {
-                    log::error!("Failed to parse locale '{}': {:?}", locale_str, e);
-                    crate::error::ZdbError::invalid_parameter(
-                        format!("Invalid BCP-47 locale string: {}", locale_str)
-                    )
-                })?;
-            
-            log::debug!("Parsed locale: {:?}", locale);
-            
-            // Create CollatorPreferences from the locale
-            // This automatically extracts collation type (co) and other locale-based preferences
-            let prefs = CollatorPreferences::from(&locale);
-            
-            // Create CollatorOptions from the locale's Unicode extensions
-            let mut options = CollatorOptions::default();
-            
-            // Extract Unicode extension keywords from the locale
-            // The keywords field is a Keywords struct, we iterate over it
-            for (key, value) in locale.extensions.unicode.keywords.iter() {
-                let key_str = key.as_str();
-                let value_str = value.to_string();
-                
-                log::debug!("Processing Unicode extension: {}={}", key_str, value_str);
-                
-                match key_str {
-                    // ks: Collation strength
-                    "ks" => {
-                        options.strength = Some(match value_str.as_str() {
-                            "level1" => Strength::Primary,
-                            "level2" => Strength::Secondary,
-                            "level3" => Strength::Tertiary,
-                            "level4" => Strength::Quaternary,
-                            "identic" => Strength::Identical,
-                            _ => {
-                                log::warn!("Unknown strength value: {}, using Primary", value_str);
-                                Strength::Primary
-                            }
-                        });
-                    },
-                    
-                    // ka: Alternate handling (for punctuation and whitespace)
-                    "ka" => {
-                        options.alternate_handling = Some(match value_str.as_str() {
-                            "shifted" => AlternateHandling::Shifted,
-                            "noignore" | "non-ignorable" => AlternateHandling::NonIgnorable,
-                            _ => {
-                                log::warn!("Unknown alternate handling value: {}, using NonIgnorable", value_str);
-                                AlternateHandling::NonIgnorable
-                            }
-                        });
-                    },
-                    
-                    // kc: Case level
-                    "kc" => {
-                        options.case_level = Some(match value_str.as_str() {
-                            "true" | "yes" | "on" => CaseLevel::On,
-                            "false" | "no" | "off" => CaseLevel::Off,
-                            _ => {
-                                log::warn!("Unknown case level value: {}, using On", value_str);
-                                CaseLevel::On
-                            }
-                        }); 
-                    },
-                    
-                    // co: Collation type - handled by CollatorPreferences
-                    "co" => {
-                        log::debug!("Collation type '{}' is handled by CollatorPreferences", value_str);
-                    },
-                    
-                    // Other extensions like kf, kn, kb are handled by CollatorPreferences
-                    // from the locale, not CollatorOptions in ICU4X 2.0
-                    "kf" | "kn" | "kb" => {
-                        log::debug!("Extension '{}={}' is handled by CollatorPreferences", key_str, value_str);
-                    },
-                    
-                    // Not supported keywords in ICU4X 2.0
-                    "kr" => {
-                        log::warn!("Unicode extension 'kr' (script reordering) is NOT supported in ICU4X 2.0, will be ignored");
-                    },
-                    "kv" => {
-                        log::warn!("Unicode extension 'kv' (variable top) is NOT supported in ICU4X 2.0, will be ignored");
-                    },
-                    
-                    _ => {
-                        log::debug!("Ignoring unsupported or unknown Unicode extension key: {}", key_str);
-                    }
-                }
-            }
-            
-            log::info!("Creating collator with preferences: {:?} and options: {:?}", prefs, options);
-            
-            // Create the collator (this returns an owned Collator in ICU4X 2.0)
-            let collator = Collator::try_new(prefs, options)
-                .map_err(|e| {
-                    log::error!("Failed to create collator: {:?}", e);
-                    crate::error::ZdbError::invalid_parameter(
-                        format!("Failed to create ICU collator: {:?}", e)
-                    )
-                })?;
-            
-            log::info!("Successfully created collator for locale: {}", locale_str);
-            
+            #[cfg(not(feature = "icu"))]
+            Err(crate::error::ZdbError::invalid_parameter(format!(
+                "No collation data is compiled in for locale \"{}\", register a collator created by UCollator::try_from_provider", locale_str
+            )))
+        }
+
+        /// Creates a collator for a BCP-47 locale string from the data of a provider.
+        ///
+        /// Lets applications built with the `icu-core` feature, which leaves out the compiled
+        /// collation data, supply data for the locales they need only, e.g. baked data generated
+        /// by `icu4x-datagen`. Blob data works through `BlobDataProvider::as_deserializing`, with
+        /// the `serde` feature of `icu_collator` and `deserialize_postcard_1` of `icu_provider`.
+        ///
+        /// Register the collator with [`register_collator`](crate::utils::icu_wrapper::register_collator)
+        /// to have readers of dictionaries with this locale use it.
+        ///
+        /// # Errors
+        ///
+        /// Returns an error if the locale string is invalid or the provider lacks its data.
+        pub fn try_from_provider<D>(locale_str: &str, provider: &D) -> Result<Self>
+        where
+            D: DataProvider<CollationSpecialPrimariesV1>
+                + DataProvider<CollationRootV1>
+                + DataProvider<CollationTailoringV1>
+                + DataProvider<CollationDiacriticsV1>
+                + DataProvider<CollationJamoV1>
+                + DataProvider<CollationMetadataV1>
+                + DataProvider<CollationReorderingV1>
+                + DataProvider<NormalizerNfdDataV1>
+                + DataProvider<NormalizerNfdTablesV1>
+                + ?Sized,
+        {
+            let (prefs, options) = collator_preferences(locale_str)?;
+            let collator = Collator::try_new_unstable(provider, prefs, options)
+                .map_err(|e| crate::error::ZdbError::invalid_parameter(
+                    format!("Failed to create ICU collator for \"{}\" from the data provider: {}", locale_str, e)
+                ))?;
             Ok(Self {
-                collator,
+                collator: CollatorData::Provided(Box::new(collator)),
                 locale_str: locale_str.to_string(),
             })
         }
         
         /// Generate a sort key for the given string
-        /// 
-        /// Note: ICU4X 2.0 doesn't expose a public sort key API.
-        /// This implementation returns the UTF-8 bytes of the string.
-        /// For actual sorting, use strcoll_utf8() for proper locale-aware comparison.
-        /// 
-        /// Sort keys from this function will NOT produce correct locale-aware ordering
-        /// when compared byte-wise. Use this only for basic caching purposes and
-        /// always verify order with strcoll_utf8().
-        /// 
-        /// Because we don't really use sortkey for local-comparison, we just return the UTF-8 bytes of the string.
+        ///
+        /// Sort keys compare byte-wise in the same order as [`strcoll_utf8`](Self::strcoll_utf8),
+        /// and strings equal under the collation strength have equal sort keys.
+        ///
+        /// The key bytes are specific to the collation backend, keys generated by ICU4C
+        /// and ICU4X for the same string differ.
         pub fn get_sort_key(&self, uchar: &UChar) -> Vec<u8> {
-            // ICU4X 2.0 doesn't provide a public sort key generation API
-            // Return the raw UTF-8 bytes as a fallback
-            // Note: This will NOT produce correct collation order when compared directly
-
-            uchar.data.as_bytes().to_vec()
+            let mut key = Vec::new();
+            let Ok(()) = match &self.collator {
+                #[cfg(feature = "icu")]
+                CollatorData::Compiled(collator) => collator.write_sort_key_to(&uchar.data, &mut key),
+                CollatorData::Provided(collator) => collator.as_borrowed().write_sort_key_to(&uchar.data, &mut key),
+            };
+            key
         }
         
         /// Compare two UTF-8 strings according to the collation rules
@@ -300,11 +265,118 @@ mod icu_impl {
         /// Returns Ordering indicating the relationship between the strings
         pub fn strcoll_utf8(&self, left: &str, right: &str) -> Result<std::cmp::Ordering> {
             // ICU4X 2.0 works directly with UTF-8 strings
-            let ordering = self.collator.compare(left, right);
+            let ordering = match &self.collator {
+                #[cfg(feature = "icu")]
+                CollatorData::Compiled(collator) => collator.compare(left, right),
+                CollatorData::Provided(collator) => collator.as_borrowed().compare(left, right),
+            };
             Ok(ordering)
         }
     }
     
+    /// Collation preferences and options requested by the keywords of a BCP-47 locale string.
+    fn collator_preferences(locale_str: &str) -> Result<(icu_collator::CollatorPreferences, CollatorOptions)> {
+        use icu_locale::Locale;
+        use icu_collator::CollatorPreferences;
+        use icu_collator::options::{Strength, AlternateHandling, CaseLevel};
+        
+        // "root" is the CLDR name of the root collation, like ICU4C accepts it
+        if locale_str.is_empty() || locale_str.eq_ignore_ascii_case("root") {
+            return Ok((CollatorPreferences::default(), CollatorOptions::default()));
+        }
+        // Parse the BCP-47 locale string
+        let locale: Locale = locale_str.parse()
+            .map_err(|e| {
+                log::error!("Failed to parse locale '{}': {:?}", locale_str, e);
+                crate::error::ZdbError::invalid_parameter(
+                    format!("Invalid BCP-47 locale string: {}", locale_str)
+                )
+            })?;
+        
+        log::debug!("Parsed locale: {:?}", locale);
+        
+        // Create CollatorPreferences from the locale
+        // This automatically extracts collation type (co) and other locale-based preferences
+        let prefs = CollatorPreferences::from(&locale);
+        
+        // Create CollatorOptions from the locale's Unicode extensions
+        let mut options = CollatorOptions::default();
+        
+        // Extract Unicode extension keywords from the locale
+        // The keywords field is a Keywords struct, we iterate over it
+        for (key, value) in locale.extensions.unicode.keywords.iter() {
+            let key_str = key.as_str();
+            let value_str = value.to_string();
+            
+            log::debug!("Processing Unicode extension: {}={}", key_str, value_str);
+            
+            match key_str {
+                // ks: Collation strength
+                "ks" => {
+                    options.strength = Some(match value_str.as_str() {
+                        "level1" => Strength::Primary,
+                        "level2" => Strength::Secondary,
+                        "level3" => Strength::Tertiary,
+                        "level4" => Strength::Quaternary,
+                        "identic" => Strength::Identical,
+                        _ => {
+                            log::warn!("Unknown strength value: {}, using Primary", value_str);
+                            Strength::Primary
+                        }
+                    });
+                },
+                
+                // ka: Alternate handling (for punctuation and whitespace)
+                "ka" => {
+                    options.alternate_handling = Some(match value_str.as_str() {
+                        "shifted" => AlternateHandling::Shifted,
+                        "noignore" | "non-ignorable" => AlternateHandling::NonIgnorable,
+                        _ => {
+                            log::warn!("Unknown alternate handling value: {}, using NonIgnorable", value_str);
+                            AlternateHandling::NonIgnorable
+                        }
+                    });
+                },
+                
+                // kc: Case level
+                "kc" => {
+                    options.case_level = Some(match value_str.as_str() {
+                        "true" | "yes" | "on" => CaseLevel::On,
+                        "false" | "no" | "off" => CaseLevel::Off,
+                        _ => {
+                            log::warn!("Unknown case level value: {}, using On", value_str);
+                            CaseLevel::On
+                        }
+                    }); 
+                },
+                
+                // co: Collation type - handled by CollatorPreferences
+                "co" => {
+                    log::debug!("Collation type '{}' is handled by CollatorPreferences", value_str);
+                },
+                
+                // Other extensions like kf, kn, kb are handled by CollatorPreferences
+                // from the locale, not CollatorOptions in ICU4X 2.0
+                "kf" | "kn" | "kb" => {
+                    log::debug!("Extension '{}={}' is handled by CollatorPreferences", key_str, value_str);
+                },
+                
+                // Not supported keywords in ICU4X 2.0
+                "kr" => {
+                    log::warn!("Unicode extension 'kr' (script reordering) is NOT supported in ICU4X 2.0, will be ignored");
+                },
+                "kv" => {
+                    log::warn!("Unicode extension 'kv' (variable top) is NOT supported in ICU4X 2.0, will be ignored");
+                },
+                
+                _ => {
+                    log::debug!("Ignoring unsupported or unknown Unicode extension key: {}", key_str);
+                }
+            }
+        }
+        Ok((prefs, options))
+    }
+
     impl UChar {
         pub fn try_from(s: &str) -> Result<Self> {
             Ok(Self { data: s.to_string() })
@@ -333,7 +405,8 @@ mod icu_impl {
     
     impl std::error::Error for IcuError {}
 
-    #[cfg(test)]
+    // The tests use the compiled collation data, icu-core alone has none
+    #[cfg(all(test, feature = "icu"))]
     mod tests {
         use super::*;
 
@@ -419,6 +492,33 @@ mod icu_impl {
             );
         }
 
+        /// Sort keys compare byte-wise like strcoll_utf8
+        #[test]
+        fn test_sort_key_order() {
+            let collator = UCollator::try_from("en-US-u-ks-level1").expect("Failed to create collator");
+            let words = ["a", "B", "café", "cafe", "Äpfel", "z", ""];
+            for left in words {
+                for right in words {
+                    let left_key = collator.get_sort_key(&UChar::try_from(left).unwrap());
+                    let right_key = collator.get_sort_key(&UChar::try_from(right).unwrap());
+                    assert_eq!(left_key.cmp(&right_key), collator.strcoll_utf8(left, right).unwrap(), "{} vs {}", left, right);
+                }
+            }
+        }
+
+        /// Collators of the same locale are created once and usable from other threads
+        #[test]
+        fn test_shared_collator() {
+            let collator = shared_collator("de").expect("Failed to create collator");
+            assert!(Arc::ptr_eq(&collator, &shared_collator("de").unwrap()));
+            assert!(!Arc::ptr_eq(&collator, &shared_collator("sv").unwrap()));
+            let ordering = std::thread::spawn(move || collator.strcoll_utf8("Äpfel", "Birne").unwrap()).join().unwrap();
+            assert_eq!(ordering, std::cmp::Ordering::Less);
+
+            let registered = register_collator("sv-x-registered", UCollator::try_from("sv").unwrap());
+            assert!(Arc::ptr_eq(&registered, &shared_collator("sv-x-registered").unwrap()));
+        }
+
         /// Test kc (case level) parameter verification
         /// This test verifies that case level produces expected ordering
         #[test]
@@ -695,12 +795,54 @@ mod icu_impl {
 #[cfg(feature = "rust-icu")]
 pub use rust_icu_impl::{UCollator, UChar, IcuError};
 
-#[cfg(feature = "icu")]
+#[cfg(feature = "icu-core")]
 pub use icu_impl::{UCollator, UChar, IcuError};
 
+/// Collators created so far, by locale string.
+static SHARED_COLLATORS: OnceLock<Mutex<HashMap<String, Arc<UCollator>>>> = OnceLock::new();
+
+/// Returns the collator of a locale, shared by all callers in the process.
+///
+/// Creating a collator loads and parses its tailoring data, reusing one makes opening
+/// several dictionaries with the same locale cheaper. Collators are immutable, so they
+/// can be used from any thread.
+///
+/// # Errors
+///
+/// Returns an error if the locale isn't supported by the collation backend.
+pub fn shared_collator(locale: &str) -> Result<Arc<UCollator>> {
+    let collators = SHARED_COLLATORS.get_or_init(Default::default);
+    if let Some(collator) = collators.lock().unwrap_or_else(|e| e.into_inner()).get(locale) {
+        return Ok(collator.clone());
+    }
+    // Created outside the lock, a concurrent caller may create the same collator, the first one inserted wins
+    let collator = Arc::new(UCollator::try_from(locale)?);
+    Ok(collators.lock().unwrap_or_else(|e| e.into_inner()).entry(locale.to_string()).or_insert(collator).clone())
+}
+
+/// Makes [`shared_collator`] return `collator` for `locale` from now on.
+///
+/// Builds without compiled collation data register the collators of the locales they
+/// support before opening dictionaries, see `UCollator::try_from_provider`. Replaces a
+/// collator registered or created before for the locale, readers opened already keep theirs.
+pub fn register_collator(locale: &str, collator: UCollator) -> Arc<UCollator> {
+    let collator = Arc::new(collator);
+    SHARED_COLLATORS.get_or_init(Default::default).lock().unwrap_or_else(|e| e.into_inner())
+        .insert(locale.to_string(), collator.clone());
+    collator
+}
+
+/// Name of the collation implementation, the backends may order some keys differently.
+#[cfg(feature = "rust-icu")]
+pub const BACKEND: &str = "ICU4C";
+
+/// Name of the collation implementation, the backends may order some keys differently.
+#[cfg(feature = "icu-core")]
+pub const BACKEND: &str = "ICU4X";
+
 // Compile-time check to ensure exactly one ICU implementation is selected
-#[cfg(all(feature = "rust-icu", feature = "icu"))]
+#[cfg(all(feature = "rust-icu", feature = "icu-core"))]
 compile_error!("Cannot enable both 'rust-icu' and 'icu' features at the same time");
 
-#[cfg(not(any(feature = "rust-icu", feature = "icu")))]
-compile_error!("Must enable either 'rust-icu' or 'icu' feature");
+#[cfg(not(any(feature = "rust-icu", feature = "icu-core")))]
+compile_error!("Must enable either 'rust-icu', 'icu' or 'icu-core' feature");
@@ -22,22 +22,32 @@
 //! string_from_file_url(&url, &mut content).unwrap();
 //! ```
 
-use std::collections::LinkedList;
+use std::collections::{HashMap, LinkedList};
 use std::fs::File;
-use std::io::{BufRead, BufReader, Read, Write};
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom, Write};
 use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock, RwLock};
+use std::time::SystemTime;
 
 use regex::Regex;
 use url::Url;
 use walkdir::WalkDir;
 
+use crate::storage::zip_directory::{ZipDirectory, ZipEntryReader};
 use crate::utils::url_utils;
 use crate::{Result, ZdbError};
 
+/// Maximum length of a Windows path that doesn't use the extended-length `\\?\` prefix.
+pub const WINDOWS_MAX_PATH: usize = 260;
+
+/// Prefix of extended-length Windows paths.
+const WINDOWS_EXTENDED_PREFIX: &str = r"\\?\";
+
 /// Fixes Windows file paths by removing the leading slash.
 ///
 /// Under Windows, file URLs look like "file:///C:/Users/test/Desktop/test.txt",
-/// so we need to remove the leading "/" to get a valid Windows path.
+/// so we need to remove the leading "/" to get a valid Windows path. Paths that
+/// exceed [`WINDOWS_MAX_PATH`] are converted to their extended-length form.
 ///
 /// # Arguments
 ///
@@ -47,14 +57,41 @@ use crate::{Result, ZdbError};
 ///
 /// Returns the fixed path string (unchanged on non-Windows platforms).
 pub fn fix_windows_path(path: &str) -> String {
-    #[cfg(target_os = "windows")]
-    if path.len()>4 {
-        let chars: Vec<char> = path.chars().take(4).collect();
-        if chars[0]=='/' && chars[1].is_alphabetic() && chars[2]==':'{
-            path = path.strip_prefix("/").unwrap();
-        }
+    if !cfg!(windows) {
+        return path.to_string();
+    }
+    let bytes = path.as_bytes();
+    let path = if bytes.len() > 2 && bytes[0] == b'/' && bytes[1].is_ascii_alphabetic() && bytes[2] == b':' {
+        &path[1..]
+    } else {
+        path
+    };
+    if path.chars().count() >= WINDOWS_MAX_PATH {
+        windows_extended_length_path(path)
+    } else {
+        path.to_string()
+    }
+}
+
+/// Converts an absolute Windows path to its extended-length form.
+///
+/// Drive paths become `\\?\C:\...` and UNC paths (`\\server\share\...`) become
+/// `\\?\UNC\server\share\...`. Forward slashes are replaced by backslashes since
+/// extended-length paths are not normalized by Windows. Relative paths and paths
+/// that already have the prefix are returned unchanged.
+pub fn windows_extended_length_path(path: &str) -> String {
+    if path.starts_with(WINDOWS_EXTENDED_PREFIX) {
+        return path.to_string();
+    }
+    let path = path.replace('/', "\\");
+    let bytes = path.as_bytes();
+    if let Some(unc_path) = path.strip_prefix(r"\\") {
+        format!(r"{}UNC\{}", WINDOWS_EXTENDED_PREFIX, unc_path)
+    } else if bytes.len() > 2 && bytes[0].is_ascii_alphabetic() && bytes[1] == b':' && bytes[2] == b'\\' {
+        format!("{}{}", WINDOWS_EXTENDED_PREFIX, path)
+    } else {
+        path
     }
-    path.to_string()
 }
 
 /// Fixes Windows file paths in a PathBuf.
@@ -66,6 +103,35 @@ pub fn fix_windows_path_buf(path: PathBuf) -> PathBuf {
     path
 }
 
+/// Applies `f` to every item on up to `threads` threads, returning the results in the order of the items.
+///
+/// Meant for IO bound work like reading many small files, the items are split into
+/// one contiguous run per thread.
+pub fn parallel_map<T: Sync, R: Send, F: Fn(&T) -> R + Sync>(items: &[T], threads: usize, f: F) -> Vec<R> {
+    let threads = threads.clamp(1, items.len().max(1));
+    if threads == 1 {
+        return items.iter().map(&f).collect();
+    }
+    let run_len = items.len().div_ceil(threads);
+    std::thread::scope(|scope| {
+        let workers: Vec<_> = items.chunks(run_len)
+            .map(|run| scope.spawn(|| run.iter().map(&f).collect::<Vec<R>>()))
+            .collect();
+        workers.into_iter()
+            .flat_map(|worker| worker.join().unwrap_or_else(|panic| std::panic::resume_unwind(panic)))
+            .collect()
+    })
+}
+
+/// Number of threads for IO bound work, `0` selects the number of available CPUs.
+pub fn io_thread_count(threads: usize) -> usize {
+    if threads > 0 {
+        threads
+    } else {
+        std::thread::available_parallelism().map_or(1, |count| count.get())
+    }
+}
+
 /// Converts Windows-style backslashes to Unix-style forward slashes.
 pub fn windows_path_to_unix_path(path: &str) -> String {
     let mut result = String::new();
@@ -79,29 +145,195 @@ pub fn windows_path_to_unix_path(path: &str) -> String {
     result
 }
 
+/// Scheme of URLs of files in a zip archive, e.g. `zip:///path/to/pack.zip!/dict.mdx`.
+pub const ZIP_URL_SCHEME: &str = "zip";
+
+/// Separator of the archive path and the file name in zip URLs.
+const ZIP_ENTRY_SEPARATOR: &str = "!/";
+
+/// Size and modification time of an archive when its directory was read.
+type ArchiveStamp = (u64, Option<SystemTime>);
+
+/// Zip archives opened so far, by path.
+static ZIP_DIRECTORIES: OnceLock<Mutex<HashMap<PathBuf, (ArchiveStamp, ZipDirectory)>>> = OnceLock::new();
+
+/// Splits a zip URL into the path of the archive and the name of the file in it.
+///
+/// # Errors
+///
+/// Returns an error if the URL isn't a zip URL or has no `!/` separator.
+pub fn split_zip_url(url: &Url) -> Result<(PathBuf, String)> {
+    let path = url_utils::get_decoded_path_str(url)?;
+    match (url.scheme() == ZIP_URL_SCHEME).then(|| path.split_once(ZIP_ENTRY_SEPARATOR)).flatten() {
+        Some((archive, name)) if !name.is_empty() => Ok((fix_windows_path_buf(PathBuf::from(archive)), name.to_string())),
+        _ => Err(ZdbError::invalid_path(format!("Not a zip URL like zip:///path/to/pack.zip!/dict.mdx: {}", url))),
+    }
+}
+
+/// Returns the directory of a zip archive, reusing the one of an earlier call if the archive is unchanged.
+fn zip_directory(archive: PathBuf) -> Result<ZipDirectory> {
+    let metadata = std::fs::metadata(&archive)?;
+    let stamp = (metadata.len(), metadata.modified().ok());
+    let mut directories = ZIP_DIRECTORIES.get_or_init(Default::default).lock().unwrap_or_else(|e| e.into_inner());
+    match directories.get(&archive) {
+        Some((cached_stamp, directory)) if *cached_stamp == stamp => Ok(directory.clone()),
+        _ => {
+            let directory = ZipDirectory::open(archive.clone());
+            directories.insert(archive, (stamp, directory.clone()));
+            Ok(directory)
+        }
+    }
+}
+
+/// A seekable stream returned by a [`UrlResolver`].
+pub trait ReadSeek: Read + Seek + Send {}
+
+impl<T: Read + Seek + Send> ReadSeek for T {}
+
+/// Opens the files of a URL scheme, e.g. `content://` or `asset://` on Android.
+///
+/// Registered with [`register_url_resolver`], it is used for dictionary files and their
+/// `.mdd`, `.key` and `.idx` companions, whose URLs are derived from the dictionary URL.
+pub trait UrlResolver: Send + Sync {
+    /// Opens the file of a URL.
+    fn open(&self, url: &Url) -> Result<Box<dyn ReadSeek>>;
+
+    /// Whether the file of a URL exists, by default whether it can be opened.
+    fn exists(&self, url: &Url) -> bool {
+        self.open(url).is_ok()
+    }
+}
+
+/// Resolvers of URL schemes, by scheme.
+static URL_RESOLVERS: OnceLock<RwLock<HashMap<String, Arc<dyn UrlResolver>>>> = OnceLock::new();
+
+/// Makes [`open_file_url_as_reader`] and [`file_url_exists`] use `resolver` for URLs of `scheme`.
+///
+/// Replaces the resolver registered before for the scheme. Registered resolvers take
+/// precedence over the built-in `file` and `zip` schemes.
+pub fn register_url_resolver(scheme: &str, resolver: Arc<dyn UrlResolver>) {
+    URL_RESOLVERS.get_or_init(Default::default).write().unwrap_or_else(|e| e.into_inner())
+        .insert(scheme.to_ascii_lowercase(), resolver);
+}
+
+/// Returns the resolver registered for the scheme of a URL.
+pub fn url_resolver(url: &Url) -> Option<Arc<dyn UrlResolver>> {
+    URL_RESOLVERS.get()?.read().unwrap_or_else(|e| e.into_inner()).get(url.scheme()).cloned()
+}
+
 /// Checks if a file URL points to an existing file.
+///
+/// Zip URLs are checked for the file in the archive, URLs of registered schemes by their resolver.
 pub fn file_url_exists(url: &Url) -> bool {
+    if let Some(resolver) = url_resolver(url) {
+        return resolver.exists(url);
+    }
+    if url.scheme() == ZIP_URL_SCHEME {
+        return split_zip_url(url)
+            .and_then(|(archive, name)| Ok(zip_directory(archive)?.contains(&name)))
+            .unwrap_or(false);
+    }
     url_utils::get_decoded_path(url).is_ok_and(
         |path| 
             Path::new(&path).exists()
     )
 }
 
+/// A file opened by [`open_file_url_as_reader`].
+pub enum DictFile {
+    /// A file of the file system
+    File(BufReader<File>),
+    /// A file in a zip archive
+    Zip(ZipEntryReader),
+    /// A file opened by a registered [`UrlResolver`]
+    Resolved(Box<dyn ReadSeek>),
+}
+
+impl std::fmt::Debug for DictFile {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DictFile::File(file) => f.debug_tuple("File").field(file).finish(),
+            DictFile::Zip(entry) => f.debug_tuple("Zip").field(entry).finish(),
+            DictFile::Resolved(_) => f.write_str("Resolved"),
+        }
+    }
+}
+
+impl DictFile {
+    /// Size of the file in bytes.
+    pub fn len(&mut self) -> Result<u64> {
+        match self {
+            DictFile::File(file) => Ok(file.get_ref().metadata()?.len()),
+            DictFile::Zip(entry) => Ok(entry.len()),
+            DictFile::Resolved(stream) => {
+                let pos = stream.stream_position()?;
+                let len = stream.seek(SeekFrom::End(0))?;
+                stream.seek(SeekFrom::Start(pos))?;
+                Ok(len)
+            }
+        }
+    }
+
+    pub fn is_empty(&mut self) -> Result<bool> {
+        Ok(self.len()? == 0)
+    }
+}
+
+impl Read for DictFile {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match self {
+            DictFile::File(file) => file.read(buf),
+            DictFile::Zip(entry) => entry.read(buf),
+            DictFile::Resolved(stream) => stream.read(buf),
+        }
+    }
+}
+
+impl Seek for DictFile {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        match self {
+            DictFile::File(file) => file.seek(pos),
+            DictFile::Zip(entry) => entry.seek(pos),
+            DictFile::Resolved(stream) => stream.seek(pos),
+        }
+    }
+}
+
 /// Opens a file URL and returns a buffered reader.
 ///
+/// Files in zip archives are opened by zip URLs like `zip:///path/to/pack.zip!/dict.mdx`,
+/// without extracting them, see [`ZipDirectory::open_entry`]. Other schemes are opened by
+/// the resolver registered for them, see [`register_url_resolver`].
+///
 /// # Errors
 ///
-/// Returns an error if the URL scheme is not "file" or the file cannot be opened.
-pub fn open_file_url_as_reader(url: &Url) -> Result<BufReader<std::fs::File>> {
+/// Returns an error if the URL scheme is not "file", "zip" or a registered scheme, or the
+/// file cannot be opened.
+pub fn open_file_url_as_reader(url: &Url) -> Result<DictFile> {
+    if let Some(resolver) = url_resolver(url) {
+        return Ok(DictFile::Resolved(resolver.open(url)?));
+    }
+    if url.scheme() == ZIP_URL_SCHEME {
+        let (archive, name) = split_zip_url(url)?;
+        return Ok(DictFile::Zip(zip_directory(archive)?.open_entry(&name)?));
+    }
+    let file = File::open(file_url_to_path(url)?)?;
+    Ok(DictFile::File(BufReader::new(file)))
+}
+
+/// Converts a file URL to a local path.
+///
+/// # Errors
+///
+/// Returns an error if the URL scheme is not "file" or the path can't be decoded.
+pub fn file_url_to_path(url: &Url) -> Result<PathBuf> {
     if url.scheme() != "file" {
         return Err(ZdbError::invalid_data_format(format!(
             "Unsupported scheme: {}",
             url.scheme()
         )));
     }
-    let path = fix_windows_path_buf(url_utils::get_decoded_path(url)?);
-    let file = File::open(path)?;
-    Ok(BufReader::new(file))
+    Ok(fix_windows_path_buf(url_utils::get_decoded_path(url)?))
 }
 
 /// Reads all bytes from a file URL.
@@ -139,14 +371,25 @@ pub fn load_string_from_file_with_ext(base_url: &Url, ext: &str) -> Result<Strin
     }
 }
 
+/// Bytes allocated up front by [`read_exact_to_vec`], longer reads grow the buffer as data arrives.
+const MAX_PREALLOCATED_READ: usize = 16 * 1024 * 1024;
+
+/// Reads exactly `len` bytes into a new vector.
+///
+/// Lengths are often read from the file itself, so a crafted length can't allocate
+/// more memory than there is data to read.
+///
+/// # Errors
+///
+/// Returns an `UnexpectedEof` I/O error if the reader ends before `len` bytes.
 pub fn read_exact_to_vec<R: Read>(reader: &mut R, len:usize) -> crate::Result<Vec<u8>> {
-    let mut buf = Vec::with_capacity(len as usize);
-    unsafe {
-        buf.set_len(buf.capacity()); // Set length without initializing memory
+    let mut buf = Vec::with_capacity(len.min(MAX_PREALLOCATED_READ));
+    reader.take(len as u64).read_to_end(&mut buf)?;
+    if buf.len() < len {
+        return Err(std::io::Error::new(std::io::ErrorKind::UnexpectedEof, format!("{} bytes expected, {} bytes read", len, buf.len())).into());
     }
-    reader.read_exact(buf.as_mut_slice())?;
     Ok(buf)
-} 
+}
 
 pub fn copy_optimized<R: BufRead, W: Write>(reader: &mut R, writer: &mut W) -> Result<u64> {
     let mut total_bytes = 0;
@@ -164,6 +407,23 @@ pub fn copy_optimized<R: BufRead, W: Write>(reader: &mut R, writer: &mut W) -> R
     Ok(total_bytes)
 }
 
+/// Options of [`scan_dir_with_options`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ScanOptions {
+    /// Descend into subdirectories
+    pub recursive: bool,
+    /// Follow symbolic links, loops are reported as errors. Links are skipped otherwise.
+    pub follow_links: bool,
+    /// Skip files and directories whose name starts with a dot
+    pub skip_hidden: bool,
+}
+
+impl Default for ScanOptions {
+    fn default() -> Self {
+        Self { recursive: true, follow_links: true, skip_hidden: false }
+    }
+}
+
 /// Scan a directory for files matching the given regex pattern
 /// 
 /// # Arguments
@@ -180,27 +440,36 @@ pub fn scan_dir<P: AsRef<Path>>(
     recursive: bool,
     files: &mut LinkedList<PathBuf>,
 ) -> Result<bool> {
-    let walker = if recursive {
-        WalkDir::new(&target_dir).follow_links(true).into_iter()
-    } else {
-        WalkDir::new(&target_dir).follow_links(true).max_depth(1).into_iter()
-    };
+    scan_dir_with_options(target_dir, pattern, &ScanOptions { recursive, ..ScanOptions::default() }, files)
+}
+
+/// Collects the files below `target_dir` whose names match `pattern`.
+///
+/// Hidden directories are skipped with their contents if `skip_hidden` is set.
+pub fn scan_dir_with_options<P: AsRef<Path>>(
+    target_dir: P,
+    pattern: &Regex,
+    options: &ScanOptions,
+    files: &mut LinkedList<PathBuf>,
+) -> Result<bool> {
+    let walker = WalkDir::new(&target_dir).follow_links(options.follow_links);
+    let walker = if options.recursive { walker } else { walker.max_depth(1) };
+    // The root itself is never hidden, even if its name starts with a dot
+    let walker = walker.into_iter()
+        .filter_entry(|entry| !options.skip_hidden || entry.depth() == 0 || !entry.file_name().to_string_lossy().starts_with('.'));
     
     for entry in walker {
         let entry = entry.map_err(|e| ZdbError::invalid_data_format(format!("Walk directory error: {}", e)))?;
         
-        // Skip directories, only process files (follow_links(true) will automatically handle symbolic links)
+        // Skip directories, only process files (with follow_links the type of the link target is reported)
         if entry.file_type().is_file() {
-            // In non-recursive mode, only process files at depth 1 (directly in the target directory)
-            if !recursive && entry.depth() > 1 {
-                continue;
+            // Non UTF-8 names are matched lossily, the path itself is kept as is
+            let file_name = entry.file_name().to_string_lossy();
+            if entry.file_name().to_str().is_none() {
+                log::warn!("File name is not valid UTF-8: {}", entry.path().display());
             }
             
-            let file_name = entry.file_name().to_str().ok_or_else(|| 
-                ZdbError::invalid_data_format("Invalid file name encoding")
-            )?;
-            
-            if pattern.is_match(file_name) {
+            if pattern.is_match(&file_name) {
                 files.push_back(entry.path().to_path_buf());
             }
         }
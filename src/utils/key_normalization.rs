@@ -0,0 +1,158 @@
+//! Key normalization applied before collation.
+//!
+//! A V3 dictionary may be built with keys compared in a normalized form, e.g. with
+//! punctuation stripped. The policy is stored in the header as the `KeyNormalization`
+//! attribute, so the reader applies exactly the transform the builder sorted by.
+//! Stored keys are never changed, only the form used for comparison.
+//!
+//! V1/V2 dictionaries apply `StripKey` and `KeyCaseSensitive` to the encoded bytes
+//! when computing sort keys, see [`get_sort_key`](crate::utils::sort_key::get_sort_key).
+//!
+//! [`fold_headword`] implements a looser matching for lookups that ignore punctuation,
+//! whitespace, case and diacritics. Dictionaries built with `FoldHeadword` sort by it,
+//! so such lookups are binary searches.
+
+use std::borrow::Cow;
+
+use icu_casemap::CaseMapper;
+use icu_normalizer::DecomposingNormalizerBorrowed;
+use icu_properties::props::{GeneralCategory, GeneralCategoryGroup};
+use icu_properties::CodePointMapData;
+use serde::{Deserialize, Serialize};
+
+use crate::{Result, ZdbError};
+
+const STRIP_KEY_TOKEN: &str = "StripKey";
+const FOLD_CASE_TOKEN: &str = "FoldCase";
+const FOLD_HEADWORD_TOKEN: &str = "FoldHeadword";
+
+/// Normalization applied to keys before they are compared.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, Serialize)]
+pub struct KeyNormalization {
+    /// Remove ASCII characters other than letters and digits, like `StripKey` of V1/V2 files
+    #[serde(default)]
+    pub strip_key: bool,
+    /// Convert keys to lowercase
+    #[serde(default)]
+    pub fold_case: bool,
+    /// Fold keys with [`fold_headword`], which implies the other transforms
+    #[serde(default)]
+    pub fold_headword: bool,
+}
+
+impl KeyNormalization {
+    /// Whether keys are compared unchanged.
+    pub fn is_identity(&self) -> bool {
+        !self.strip_key && !self.fold_case && !self.fold_headword
+    }
+
+    /// Parses the value of the `KeyNormalization` header attribute, a comma separated list.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error for unknown transforms, since lookups would silently fail if the
+    /// reader compared keys differently from the builder.
+    pub fn from_header_value(value: &str) -> Result<Self> {
+        let mut normalization = Self::default();
+        for token in value.split(',').map(str::trim).filter(|token| !token.is_empty()) {
+            if token.eq_ignore_ascii_case(STRIP_KEY_TOKEN) {
+                normalization.strip_key = true;
+            } else if token.eq_ignore_ascii_case(FOLD_CASE_TOKEN) {
+                normalization.fold_case = true;
+            } else if token.eq_ignore_ascii_case(FOLD_HEADWORD_TOKEN) {
+                normalization.fold_headword = true;
+            } else {
+                return Err(ZdbError::invalid_data_format(format!("Unsupported key normalization: {}", token)));
+            }
+        }
+        Ok(normalization)
+    }
+
+    /// Formats the policy as the value of the `KeyNormalization` header attribute, empty for identity.
+    pub fn to_header_value(&self) -> String {
+        let mut tokens = Vec::new();
+        if self.strip_key {
+            tokens.push(STRIP_KEY_TOKEN);
+        }
+        if self.fold_case {
+            tokens.push(FOLD_CASE_TOKEN);
+        }
+        if self.fold_headword {
+            tokens.push(FOLD_HEADWORD_TOKEN);
+        }
+        tokens.join(",")
+    }
+}
+
+/// Normalizes a key or query according to the policy.
+///
+/// Used by the builder for sorting and by the reader for lookups, so both agree.
+/// A key consisting only of stripped characters is compared unchanged, otherwise
+/// all such keys would collapse to the empty string.
+pub fn normalize_query<'a>(key: &'a str, normalization: &KeyNormalization) -> Cow<'a, str> {
+    if normalization.is_identity() {
+        return Cow::Borrowed(key);
+    }
+    if normalization.fold_headword {
+        return Cow::Owned(fold_headword(key));
+    }
+    let stripped: Cow<str> = if normalization.strip_key {
+        let stripped: String = key.chars().filter(|ch| !ch.is_ascii() || ch.is_ascii_alphanumeric()).collect();
+        if stripped.is_empty() { Cow::Borrowed(key) } else { Cow::Owned(stripped) }
+    } else {
+        Cow::Borrowed(key)
+    };
+    if normalization.fold_case {
+        Cow::Owned(stripped.to_lowercase())
+    } else {
+        stripped
+    }
+}
+
+/// Folds a headword for lookups ignoring punctuation, whitespace, case and diacritics.
+///
+/// The text is case folded with full Unicode case folding, so "Straße" folds like "strasse",
+/// and decomposed (NFD), then combining marks, punctuation and whitespace are removed, so
+/// "Café-au-lait" and "cafe au lait" fold to the same string. A headword consisting only
+/// of removed characters folds to its case folded form.
+pub fn fold_headword(key: &str) -> String {
+    let case_folded = CaseMapper::new().fold_string(key);
+    let decomposed = DecomposingNormalizerBorrowed::new_nfd().normalize(&case_folded);
+    let categories = CodePointMapData::<GeneralCategory>::new();
+    let folded: String = decomposed.chars()
+        .filter(|&ch| {
+            let category = categories.get(ch);
+            !ch.is_whitespace() && !GeneralCategoryGroup::Mark.contains(category) && !GeneralCategoryGroup::Punctuation.contains(category)
+        })
+        .collect();
+    if folded.is_empty() { case_folded.into_owned() } else { folded }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_query() {
+        let normalization = KeyNormalization::from_header_value("StripKey, FoldCase").unwrap();
+        assert_eq!(normalization.to_header_value(), "StripKey,FoldCase");
+        assert_eq!(normalize_query("Rock-'n'-Roll", &normalization), "rocknroll");
+        assert_eq!(normalize_query("Café au lait", &normalization), "caféaulait");
+        assert_eq!(normalize_query("...", &normalization), "...");
+        assert_eq!(normalize_query("A-b", &KeyNormalization::default()), "A-b");
+        assert!(KeyNormalization::from_header_value("").unwrap().is_identity());
+        assert!(KeyNormalization::from_header_value("Unknown").is_err());
+        let folding = KeyNormalization::from_header_value("FoldHeadword").unwrap();
+        assert_eq!(normalize_query("Rock-'n'-Roll", &folding), "rocknroll");
+    }
+
+    #[test]
+    fn test_fold_headword() {
+        assert_eq!(fold_headword("Café-au-lait"), "cafeaulait");
+        assert_eq!(fold_headword("  naïve  "), "naive");
+        assert_eq!(fold_headword("Ångström"), "angstrom");
+        assert_eq!(fold_headword("你好，世界"), "你好世界");
+        assert_eq!(fold_headword("--"), "--");
+        assert_eq!(fold_headword("Straße"), fold_headword("STRASSE"));
+    }
+}
@@ -0,0 +1,165 @@
+//! Expansion of terse labels in HTML content, e.g. `n.` or `〔方〕`.
+//!
+//! Dictionaries mark entries with abbreviated labels that readers may not know. A
+//! dictionary can embed a table of its labels and their expansions in the header, see
+//! [`BuilderConfig::labels_path`](crate::builder::BuilderConfig::labels_path), and
+//! [`LabelExpander`] wraps every label found in the text of an entry in
+//! `<abbr title="expansion">`, which browsers show as a tooltip.
+//!
+//! Labels are matched in text only, not in tags, scripts, styles or existing `<abbr>`
+//! elements. The longest label starting at a position wins, and a label starting or
+//! ending with a letter or digit must not be part of a longer word.
+
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap};
+use std::rc::Rc;
+
+use lol_html::html_content::ContentType;
+use lol_html::{doc_text, element, rewrite_str, EndTagHandler, Settings};
+
+use crate::{Result, ZdbError};
+
+/// Elements whose text isn't searched for labels.
+const SKIPPED_ELEMENTS: &str = "script, style, abbr, title, textarea";
+
+/// Wraps the labels of a dictionary in `<abbr>` elements, see the [module documentation](self).
+#[derive(Debug, Clone, Default)]
+pub struct LabelExpander {
+    /// Labels as they appear in HTML text with their escaped expansions, by first character,
+    /// longest label first
+    labels: HashMap<char, Vec<(String, String)>>,
+}
+
+#[derive(Default)]
+struct ExpandState {
+    skipped_depth: u32,
+    text_node: String,
+}
+
+/// Escapes text for an HTML text node or a double-quoted attribute.
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+impl LabelExpander {
+    /// Creates an expander for a table of labels and their expansions.
+    pub fn new(labels: &BTreeMap<String, String>) -> Self {
+        let mut by_first_char: HashMap<char, Vec<(String, String)>> = HashMap::new();
+        for (label, expansion) in labels {
+            let label = escape_html(label.trim());
+            if let Some(first) = label.chars().next() {
+                by_first_char.entry(first).or_default().push((label, escape_html(expansion)));
+            }
+        }
+        for labels in by_first_char.values_mut() {
+            labels.sort_by_key(|(label, _)| std::cmp::Reverse(label.len()));
+        }
+        Self { labels: by_first_char }
+    }
+
+    /// Returns whether the table has no labels.
+    pub fn is_empty(&self) -> bool {
+        self.labels.is_empty()
+    }
+
+    /// Wraps the labels in the text of an HTML document.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `GeneralError` if the HTML can't be rewritten.
+    pub fn expand_html(&self, html: &str) -> Result<String> {
+        if self.is_empty() {
+            return Ok(html.to_string());
+        }
+        let state = Rc::new(RefCell::new(ExpandState::default()));
+        let skipped_state = state.clone();
+        let text_state = state.clone();
+        let settings = Settings {
+            element_content_handlers: vec![
+                element!(SKIPPED_ELEMENTS, move |el| {
+                    if let Some(handlers) = el.end_tag_handlers() {
+                        skipped_state.borrow_mut().skipped_depth += 1;
+                        let state = skipped_state.clone();
+                        let handler: EndTagHandler<'static> = Box::new(move |_| {
+                            let mut state = state.borrow_mut();
+                            state.skipped_depth = state.skipped_depth.saturating_sub(1);
+                            Ok(())
+                        });
+                        handlers.push(handler);
+                    }
+                    Ok(())
+                }),
+            ],
+            document_content_handlers: vec![
+                doc_text!(move |chunk| {
+                    let mut state = text_state.borrow_mut();
+                    if state.skipped_depth > 0 {
+                        return Ok(());
+                    }
+                    // A text node may come in several chunks, it's replaced as a whole by the last one
+                    state.text_node.push_str(chunk.as_str());
+                    if chunk.last_in_text_node() {
+                        let raw = std::mem::take(&mut state.text_node);
+                        chunk.replace(&self.expand_text(&raw), ContentType::Html);
+                    } else {
+                        chunk.remove();
+                    }
+                    Ok(())
+                }),
+            ],
+            ..Settings::default()
+        };
+        rewrite_str(html, settings).map_err(|e| ZdbError::general_error(format!("HTML rewriting error: {}", e)))
+    }
+
+    /// Wraps the labels in a text node as it appears in HTML, with entities not decoded.
+    fn expand_text(&self, raw: &str) -> String {
+        let mut expanded = String::with_capacity(raw.len());
+        let mut copied = 0;
+        let mut prev_char: Option<char> = None;
+        let mut chars = raw.char_indices();
+        while let Some((pos, ch)) = chars.next() {
+            let found = self.labels.get(&ch).and_then(|labels| {
+                labels.iter().find(|(label, _)| {
+                    raw[pos..].starts_with(label.as_str())
+                        && !(ch.is_alphanumeric() && prev_char.is_some_and(char::is_alphanumeric))
+                        && !(label.ends_with(char::is_alphanumeric) && raw[pos + label.len()..].starts_with(char::is_alphanumeric))
+                })
+            });
+            match found {
+                Some((label, expansion)) => {
+                    expanded.push_str(&raw[copied..pos]);
+                    expanded.push_str(&format!("<abbr title=\"{}\">{}</abbr>", expansion, label));
+                    copied = pos + label.len();
+                    prev_char = label.chars().last();
+                    while chars.offset() < copied {
+                        chars.next();
+                    }
+                }
+                None => prev_char = Some(ch),
+            }
+        }
+        expanded.push_str(&raw[copied..]);
+        expanded
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expand_labels() {
+        let labels: BTreeMap<String, String> = [("n.", "noun"), ("adj.", "adjective"), ("〔方〕", "dialect"), ("R&B", "rhythm & blues")]
+            .into_iter()
+            .map(|(label, expansion)| (label.to_string(), expansion.to_string()))
+            .collect();
+        let expander = LabelExpander::new(&labels);
+        assert_eq!(expander.expand_html("<i>n.</i> run. adj.").unwrap(),
+            r#"<i><abbr title="noun">n.</abbr></i> run. <abbr title="adjective">adj.</abbr>"#);
+        assert_eq!(expander.expand_html("〔方〕说话 R&amp;B").unwrap(),
+            r#"<abbr title="dialect">〔方〕</abbr>说话 <abbr title="rhythm &amp; blues">R&amp;B</abbr>"#);
+        assert_eq!(expander.expand_html(r#"<abbr title="x">n.</abbr><script>n.</script><a href="n.">an. n.</a>"#).unwrap(),
+            r#"<abbr title="x">n.</abbr><script>n.</script><a href="n.">an. <abbr title="noun">n.</abbr></a>"#);
+    }
+}
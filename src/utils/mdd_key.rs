@@ -0,0 +1,58 @@
+//! Keys of resources in MDD files.
+//!
+//! Resources are stored under their path with a leading separator. V1/V2 files use
+//! Windows separators (`\img\cat.png`), V3 files use forward slashes (`/img/cat.png`).
+//! HTML references resources either way, with or without the leading separator, so
+//! paths are normalized before they are looked up or stored.
+//!
+//! Many MDD files were packed on case-insensitive file systems and reference their
+//! resources with inconsistent case, [`fold_case`] gives the form they are matched by
+//! when case is ignored.
+
+use crate::storage::meta_unit::ZdbVersion;
+
+/// Separator of path components in keys of the given file version.
+pub fn separator(version: ZdbVersion) -> char {
+    match version {
+        ZdbVersion::V1 | ZdbVersion::V2 => '\\',
+        ZdbVersion::V3 => '/',
+    }
+}
+
+/// Converts a resource path to the key it's stored under in a file of the given version.
+///
+/// Both separators are replaced by the one of the version, and the path gets exactly
+/// one leading separator.
+///
+/// # Examples
+///
+/// ```
+/// use mdx::storage::meta_unit::ZdbVersion;
+/// use mdx::utils::mdd_key;
+///
+/// assert_eq!(mdd_key::normalize("img/cat.png", ZdbVersion::V3), "/img/cat.png");
+/// assert_eq!(mdd_key::normalize("\\img\\cat.png", ZdbVersion::V3), "/img/cat.png");
+/// assert_eq!(mdd_key::normalize("/img/cat.png", ZdbVersion::V2), "\\img\\cat.png");
+/// ```
+pub fn normalize(path: &str, version: ZdbVersion) -> String {
+    let separator = separator(version);
+    let path = path.trim_start_matches(['/', '\\']);
+    let mut key = String::with_capacity(path.len() + 1);
+    key.push(separator);
+    key.extend(path.chars().map(|c| if c == '/' || c == '\\' { separator } else { c }));
+    key
+}
+
+/// Form of a key used to match resources ignoring case, independent of the file version.
+pub fn fold_case(path: &str) -> String {
+    normalize(path, ZdbVersion::V3).to_lowercase()
+}
+
+/// Extension of the file name in a key, in lower case, `None` if it has none.
+pub fn extension(path: &str) -> Option<String> {
+    let file_name = path.rsplit(['/', '\\']).next().unwrap_or(path);
+    match file_name.rsplit_once('.') {
+        Some((stem, extension)) if !stem.is_empty() && !extension.is_empty() => Some(extension.to_lowercase()),
+        _ => None,
+    }
+}
@@ -11,6 +11,27 @@
 //! - `source://` → `mdx://mdict.cn/service/source?profile_id=&entry_no=`
 //! - `file://` or no protocol → `mdx://mdict.cn/service/mdd?profile_id=&key=`
 //!
+//! Links are rewritten in the link attributes of all elements, e.g. `<source src>` of
+//! audio, video and picture elements and `xlink:href` of inline SVG, in every candidate
+//! of `srcset`, and in the `url()` references of style attributes and `<style>` elements,
+//! including the fonts of `@font-face` rules.
+//!
+//! When the key of the entry being rendered is known, see
+//! [`MdxHtmlRewriter::rewrite_html_for_entry`], links to a fragment of that same entry,
+//! like `entry://word#sense2` in the entry `word`, become same-page anchors (`#sense2`)
+//! so following them scrolls instead of reloading the page.
+//!
+//! Keys are encoded as query values with spaces as `+` and the escapes of keys
+//! containing `%` decoded first. [`UrlEncodingOptions`] changes this per scheme, e.g.
+//! `%20` for URL handlers that don't decode `+`, or keeping `%` for keys like `100%.png`.
+//!
+//! [`MdxServiceUrl`](crate::utils::MdxServiceUrl) parses the rewritten URLs back.
+//!
+//! Some dictionaries link to entries of other dictionaries with `entry://DictName/word`.
+//! Given the names of the installed dictionaries, see
+//! [`MdxHtmlRewriterInstance::with_dictionary_names`], such links name their target in a
+//! `profile_name` parameter.
+//!
 //! # Examples
 //! 
 //! ```rust
@@ -27,51 +48,203 @@
 //! // Result: <img src="custom://my-domain.com/entry?profile_id=123&key=test.png">...
 //! ```
 
-use lol_html::{element, rewrite_str, Settings};
+use std::borrow::Cow;
+use std::sync::OnceLock;
+
+use lol_html::html_content::{ContentType, Element, TextChunk};
+use lol_html::{rewrite_str, ElementContentHandlers, Selector, Settings};
 use percent_encoding;
 use url::Url;
 
+use crate::storage::meta_unit::ZdbVersion;
+use crate::utils::io_utils::{io_thread_count, parallel_map};
+use crate::utils::mdd_key;
 use crate::Result;
 
-const DEFAULT_BASE_URL: &'static str = "mdx://mdict.cn/service/";
+pub(crate) const DEFAULT_BASE_URL: &'static str = "mdx://mdict.cn/service/";
+
+/// Handling of percent signs in the keys of links.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum EscapeHandling {
+    /// Decodes the escapes of keys containing `%` before encoding them, so links
+    /// written encoded aren't encoded twice
+    #[default]
+    Decode,
+    /// Keeps percent signs as part of the key, for keys like `100%.png`
+    Keep,
+    /// Copies the key into the rewritten URL as it is, for keys already encoded for the
+    /// URL handler. Only characters invalid in URLs, like spaces, are escaped
+    Raw,
+}
+
+/// Encoding of the key of a rewritten link.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct UrlEncodingPolicy {
+    /// Encodes spaces as `+` like HTML forms rather than `%20`, which some WebView URL
+    /// handlers don't decode. Ignored by [`EscapeHandling::Raw`]
+    pub space_as_plus: bool,
+    pub escapes: EscapeHandling,
+}
+
+impl Default for UrlEncodingPolicy {
+    fn default() -> Self {
+        Self { space_as_plus: true, escapes: EscapeHandling::Decode }
+    }
+}
+
+impl UrlEncodingPolicy {
+    /// Spaces as `%20`, escapes decoded.
+    pub fn percent_spaces() -> Self {
+        Self { space_as_plus: false, ..Self::default() }
+    }
+
+    /// Spaces as `%20`, percent signs kept as part of the key.
+    pub fn literal() -> Self {
+        Self { space_as_plus: false, escapes: EscapeHandling::Keep }
+    }
+
+    /// Keys copied as they are.
+    pub fn raw() -> Self {
+        Self { space_as_plus: false, escapes: EscapeHandling::Raw }
+    }
+
+    /// Decodes the escapes of a key if the policy decodes them.
+    fn decode(&self, key: &str) -> String {
+        if self.escapes == EscapeHandling::Decode && key.contains('%') {
+            percent_encoding::percent_decode_str(key)
+                .decode_utf8()
+                .unwrap_or_else(|_| key.into())
+                .to_string()
+        } else {
+            key.to_string()
+        }
+    }
+
+    /// Encodes a key as a query value.
+    fn encode(&self, key: &str) -> String {
+        if self.escapes == EscapeHandling::Raw {
+            return key.to_string();
+        }
+        let encoded: String = url::form_urlencoded::byte_serialize(key.as_bytes()).collect();
+        // Plus signs of the key are encoded as %2B, so every remaining one is a space
+        if self.space_as_plus { encoded } else { encoded.replace('+', "%20") }
+    }
+}
+
+/// Encoding policies of the keys of rewritten links, per scheme of the original link.
+///
+/// The schemes are `entry`, `entryx`, `sound`, `source` and `file`, relative paths use
+/// the policy of `file`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct UrlEncodingOptions {
+    /// Policy of the schemes without their own
+    pub default: UrlEncodingPolicy,
+    pub schemes: Vec<(String, UrlEncodingPolicy)>,
+}
+
+impl UrlEncodingOptions {
+    /// Creates options with the same policy for all schemes.
+    pub fn new(default: UrlEncodingPolicy) -> Self {
+        Self { default, schemes: Vec::new() }
+    }
+
+    /// Sets the policy of a scheme, e.g. `sound`.
+    pub fn with_scheme(mut self, scheme: &str, policy: UrlEncodingPolicy) -> Self {
+        self.schemes.retain(|(name, _)| name != scheme);
+        self.schemes.push((scheme.to_string(), policy));
+        self
+    }
+
+    /// Gets the policy of a scheme.
+    pub fn policy(&self, scheme: &str) -> UrlEncodingPolicy {
+        self.schemes.iter().find(|(name, _)| name == scheme).map_or(self.default, |(_, policy)| *policy)
+    }
+}
 
 /// HTML rewriter for MDX dictionary content.
 pub struct MdxHtmlRewriter;
 
+/// Link attributes that need to be processed
+const LINK_ATTRIBUTES: &[&str] = &[
+    "href", "src", "background", "background-image", "poster", "data",
+    "action", "cite", "codebase", "usemap", "longdesc", "archive", "classid", "xlink:href"
+];
+
+/// Selectors of the rewriting handlers, parsed once and shared by all rewrites.
+struct HandlerSelectors {
+    links: Selector,
+    srcset: Selector,
+    style_attribute: Selector,
+    style_element: Selector,
+}
+
+static HANDLER_SELECTORS: OnceLock<HandlerSelectors> = OnceLock::new();
+
+impl HandlerSelectors {
+    fn get() -> &'static HandlerSelectors {
+        HANDLER_SELECTORS.get_or_init(|| {
+            // Selector for all attributes, e.g., "*[href], *[src], *[background], ..."
+            let links = LINK_ATTRIBUTES.iter()
+                .map(|attr| format!("*[{}]", attr.replace(':', "\\:")))
+                .collect::<Vec<_>>()
+                .join(", ");
+            HandlerSelectors {
+                links: links.parse().unwrap(),
+                srcset: "*[srcset]".parse().unwrap(),
+                style_attribute: "*[style]".parse().unwrap(),
+                style_element: "style".parse().unwrap(),
+            }
+        })
+    }
+}
+
 /// Macro to create element handlers, avoiding code duplication.
 macro_rules! create_handlers {
-    ($profile_id:expr, $base_url:expr) => {{
-        // Link attributes that need to be processed
-        const LINK_ATTRIBUTES: &[&str] = &[
-            "href", "src", "background", "background-image", "poster", "data",
-            "action", "cite", "codebase", "usemap", "longdesc", "archive", "classid"
-        ];
-        
-        // Generate selector for all attributes, e.g., "*[href], *[src], *[background], ..."
-        let selector = LINK_ATTRIBUTES.iter()
-            .map(|attr| format!("*[{}]", attr))
-            .collect::<Vec<_>>()
-            .join(", ");
-                
+    ($profile_id:expr, $base_url:expr, $current_key:expr, $encoding:expr, $dictionary_names:expr) => {{
+        let selectors = HandlerSelectors::get();
         vec![
             // Unified processing for all link attributes
-            element!(&selector, move |el| {
+            (Cow::Borrowed(&selectors.links), ElementContentHandlers::default().element(move |el: &mut Element| {
                 for &attr in LINK_ATTRIBUTES {
                     if let Some(value) = el.get_attribute(attr) {
-                        let new_value = MdxHtmlRewriter::rewrite_url(&value, $profile_id, &$base_url);
+                        let new_value = MdxHtmlRewriter::rewrite_link(&value, $profile_id, &$base_url, $current_key, $encoding, $dictionary_names);
                         el.set_attribute(attr, &new_value)?;
                     }
                 }
                 Ok(())
-            }),
+            })),
+            // Responsive image candidates of img and source elements
+            (Cow::Borrowed(&selectors.srcset), ElementContentHandlers::default().element(move |el: &mut Element| {
+                if let Some(srcset) = el.get_attribute("srcset") {
+                    let new_srcset = MdxHtmlRewriter::rewrite_srcset_with(&srcset, $profile_id, &$base_url, $encoding);
+                    el.set_attribute("srcset", &new_srcset)?;
+                }
+                Ok(())
+            })),
             // Separate handling for CSS style attribute
-            element!("*[style]", move |el| {
+            (Cow::Borrowed(&selectors.style_attribute), ElementContentHandlers::default().element(move |el: &mut Element| {
                 if let Some(style) = el.get_attribute("style") {
-                    let new_style = MdxHtmlRewriter::rewrite_css_urls(&style, $profile_id, &$base_url);
+                    let new_style = MdxHtmlRewriter::rewrite_css_urls_with(&style, $profile_id, &$base_url, $encoding);
                     el.set_attribute("style", &new_style)?;
                 }
                 Ok(())
-            }),
+            })),
+            // Style sheets of style elements, which may arrive in several chunks
+            {
+                let mut style_sheet = String::new();
+                (Cow::Borrowed(&selectors.style_element), ElementContentHandlers::default().text(move |chunk: &mut TextChunk| {
+                    style_sheet.push_str(chunk.as_str());
+                    if chunk.last_in_text_node() {
+                        let new_style_sheet = MdxHtmlRewriter::rewrite_css_urls_with(&style_sheet, $profile_id, &$base_url, $encoding);
+                        // The content of a style element is raw text, it must not be escaped
+                        chunk.replace(&new_style_sheet, ContentType::Html);
+                        style_sheet.clear();
+                    } else {
+                        chunk.remove();
+                    }
+                    Ok(())
+                }))
+            },
         ]
     }};
 }
@@ -89,10 +262,27 @@ impl MdxHtmlRewriter {
     /// 
     /// 将HTML内容中的各种链接协议转换为mdx协议格式
     pub fn rewrite_html_with_base_url(html: &str, profile_id: i32, base_url: &str) -> Result<String> {
+        Self::rewrite_links(html, profile_id, base_url, None, &UrlEncodingOptions::default(), &[])
+    }
+
+    /// Rewrites the links of the HTML of an entry, turning links to fragments of the
+    /// entry itself into same-page anchors, see the [module documentation](self).
+    ///
+    /// # Arguments
+    ///
+    /// * `html` - The HTML of the entry
+    /// * `profile_id` - Profile id of the dictionary of the entry
+    /// * `base_url` - Base URL of the rewritten links
+    /// * `current_key` - Key of the entry
+    pub fn rewrite_html_for_entry(html: &str, profile_id: i32, base_url: &str, current_key: &str) -> Result<String> {
+        Self::rewrite_links(html, profile_id, base_url, Some(current_key), &UrlEncodingOptions::default(), &[])
+    }
+
+    fn rewrite_links(html: &str, profile_id: i32, base_url: &str, current_key: Option<&str>, encoding: &UrlEncodingOptions, dictionary_names: &[String]) -> Result<String> {
         let rewritten = rewrite_str(
             html, 
             Settings {
-                element_content_handlers: create_handlers!(profile_id, base_url),
+                element_content_handlers: create_handlers!(profile_id, base_url, current_key, encoding, dictionary_names),
                 ..Settings::default()
             }
         ).map_err(|e| {
@@ -102,8 +292,58 @@ impl MdxHtmlRewriter {
         Ok(rewritten)
     }
 
+    /// Rewrites a link attribute, returning the fragment of links to a fragment of the current entry.
+    fn rewrite_link(url: &str, profile_id: i32, base_url: &str, current_key: Option<&str>, encoding: &UrlEncodingOptions, dictionary_names: &[String]) -> String {
+        if let Some(cross_link) = Self::rewrite_cross_dictionary_link(url, profile_id, base_url, dictionary_names, encoding) {
+            return cross_link;
+        }
+        if let Some(current_key) = current_key
+            && let Some(fragment) = Self::same_entry_fragment(url.trim(), profile_id, base_url, current_key) {
+            return format!("#{}", fragment);
+        }
+        Self::rewrite_url_with_options(url, profile_id, base_url, encoding)
+    }
+
+    /// Gets the fragment of an `entry://` link, or of a rewritten entry link of the same
+    /// profile, pointing into the entry with key `current_key`.
+    fn same_entry_fragment<'a>(url: &'a str, profile_id: i32, base_url: &str, current_key: &str) -> Option<&'a str> {
+        let (target, fragment) = url.split_once('#')?;
+        if let Some(path) = target.strip_prefix("entry://") {
+            let key = percent_encoding::percent_decode_str(path).decode_utf8().ok()?;
+            return (key.trim_start_matches('/') == current_key).then_some(fragment);
+        }
+        // Links rewritten before, e.g. in cached HTML
+        let entry_url = format!("{}/entry", base_url.trim_end_matches('/'));
+        let parsed = Url::parse(target).ok()?;
+        if !target.starts_with(&entry_url) || parsed.path() != Url::parse(&entry_url).ok()?.path() {
+            return None;
+        }
+        let mut same_profile = false;
+        let mut same_key = false;
+        for (name, value) in parsed.query_pairs() {
+            match name.as_ref() {
+                "profile_id" => same_profile = value == profile_id.to_string(),
+                "key" => same_key = value == current_key,
+                _ => {}
+            }
+        }
+        (same_profile && same_key).then_some(fragment)
+    }
+
     /// 重写单个URL，使用URL库进行标准化解析和编码
     pub fn rewrite_url(url: &str, profile_id: i32, base_url: &str) -> String {
+        Self::rewrite_url_with_options(url, profile_id, base_url, &UrlEncodingOptions::default())
+    }
+
+    /// Rewrites a single URL, encoding the key with the policy of its scheme.
+    ///
+    /// # Arguments
+    ///
+    /// * `url` - The URL to rewrite
+    /// * `profile_id` - Profile id of the dictionary
+    /// * `base_url` - Base URL of the rewritten links
+    /// * `encoding` - Encoding policies of the keys, relative paths use the policy of `file`
+    pub fn rewrite_url_with_options(url: &str, profile_id: i32, base_url: &str, encoding: &UrlEncodingOptions) -> String {
         let url = url.trim();
         
         // 空字符串或空白字符
@@ -125,64 +365,25 @@ impl MdxHtmlRewriter {
             return url[9..].to_string(); // 去掉 "entry:///" 保留 "#fragment"
         }
 
-        // 需要转换的协议映射表：(前缀, 目标路径, 参数名)
+        // 需要转换的协议映射表：(协议, 目标路径, 参数名)
         const PROTOCOL_MAPPINGS: &[(&str, &str, &str)] = &[
-            ("entry://", "entry", "key"),
-            ("entryx://", "entryx", "entry_no"),
-            ("sound://", "sound", "key"),
-            ("source://", "source", "entry_no"),
-            ("file://", "mdd", "key"),
+            ("entry", "entry", "key"),
+            ("entryx", "entryx", "entry_no"),
+            ("sound", "sound", "key"),
+            ("source", "source", "entry_no"),
+            ("file", "mdd", "key"),
         ];
         
         // 检查转换映射表
         for (scheme, action, param_name) in PROTOCOL_MAPPINGS {
-            if url.starts_with(scheme) {
-                // 直接处理原始路径，避免URL库的双重编码
-                let path_with_fragment = &url[scheme.len()..];
-                
-                // 分离路径和fragment
-                let (path_part, fragment_part) = if let Some(hash_pos) = path_with_fragment.find('#') {
-                    (&path_with_fragment[..hash_pos], Some(&path_with_fragment[hash_pos + 1..]))
-                } else {
-                    (path_with_fragment, None)
-                };
-                
-                // 如果路径已经包含编码字符，先解码再重新编码，避免双重编码
-                let decoded_path = if path_part.contains('%') {
-                    // 尝试URL解码
-                    percent_encoding::percent_decode_str(path_part)
-                        .decode_utf8()
-                        .unwrap_or_else(|_| path_part.into())
-                        .to_string()
-                } else {
-                    path_part.to_string()
+            if let Some(path_with_fragment) = url.strip_prefix(scheme).and_then(|rest| rest.strip_prefix("://")) {
+                let (path_part, fragment_part) = match path_with_fragment.split_once('#') {
+                    Some((path, fragment)) => (path, Some(fragment)),
+                    None => (path_with_fragment, None),
                 };
-                
-                let clean_path = if action == &"mdd" || action == &"sound" {
-                    // mdd类型添加前导斜杠
-                    if decoded_path.starts_with('/') {
-                        decoded_path
-                    } else {
-                        format!("/{}", decoded_path)
-                    }
-                } else {
-                    // 非mdd类型去掉前导斜杠
-                    decoded_path.trim_start_matches('/').to_string()
-                };
-                
-                let base_url_trimmed = base_url.trim_end_matches('/');
-                
-                // 使用URL库构造结果URL，但直接传入原始路径让URL库只编码一次
-                if let Ok(mut result_url) = Url::parse(&format!("{}/{}?profile_id={}", base_url_trimmed, action, profile_id)) {
-                    result_url.query_pairs_mut()
-                        .append_pair(param_name, &clean_path);
-                    
-                    // 设置fragment（URL库会自动编码）
-                    if let Some(frag) = fragment_part {
-                        result_url.set_fragment(Some(frag));
-                    }
-                    
-                    return result_url.to_string();
+                let policy = encoding.policy(scheme);
+                if let Some(result_url) = Self::service_url(base_url, action, profile_id, param_name, path_part, fragment_part, policy) {
+                    return result_url;
                 }
             }
         }
@@ -193,54 +394,240 @@ impl MdxHtmlRewriter {
             return url.to_string();
         }
         
-        // 没有协议的相对路径，默认使用mdd
-        // 构造file:///格式让URL库解析
-        let file_url = if url.starts_with('/') {
-            format!("file://{}", url)
+        // 没有协议的相对路径，默认使用mdd，查询参数不属于资源键
+        let (path_part, fragment_part) = match url.split_once('#') {
+            Some((path, fragment)) => (path, Some(fragment)),
+            None => (url, None),
+        };
+        let path_part = path_part.split_once('?').map_or(path_part, |(path, _)| path);
+        Self::service_url(base_url, "mdd", profile_id, "key", path_part, fragment_part, encoding.policy("file"))
+            // 如果所有URL库操作都失败，回退到原始URL
+            .unwrap_or_else(|| url.to_string())
+    }
+
+    /// Rewrites an `entry://DictName/word` link to an entry of another dictionary.
+    ///
+    /// The rewritten URL keeps the profile id of the linking dictionary and names the
+    /// target in a `profile_name` parameter, which the application resolves, e.g. with
+    /// [`DictRegistry::resolve_profile_name`](crate::readers::DictRegistry::resolve_profile_name).
+    ///
+    /// # Arguments
+    ///
+    /// * `url` - The link
+    /// * `profile_id` - Profile id of the dictionary of the link
+    /// * `base_url` - Base URL of the rewritten links
+    /// * `dictionary_names` - Names of the dictionaries links may target, matched exactly
+    /// * `encoding` - Encoding policies of the keys, the policy of `entry` is used
+    ///
+    /// # Returns
+    ///
+    /// Returns the rewritten URL, `None` if the link is not an entry link whose first
+    /// path segment is one of `dictionary_names` followed by a key.
+    pub fn rewrite_cross_dictionary_link(url: &str, profile_id: i32, base_url: &str, dictionary_names: &[String], encoding: &UrlEncodingOptions) -> Option<String> {
+        if dictionary_names.is_empty() {
+            return None;
+        }
+        let path_with_fragment = url.trim().strip_prefix("entry://")?;
+        let (path, fragment) = match path_with_fragment.split_once('#') {
+            Some((path, fragment)) => (path, Some(fragment)),
+            None => (path_with_fragment, None),
+        };
+        let policy = encoding.policy("entry");
+        let decoded_path = policy.decode(path);
+        let (name, key) = decoded_path.trim_start_matches('/').split_once('/')?;
+        if key.is_empty() || !dictionary_names.iter().any(|dictionary_name| dictionary_name == name) {
+            return None;
+        }
+        let mut result_url = Url::parse(&format!("{}/entry", base_url.trim_end_matches('/'))).ok()?;
+        result_url.set_query(Some(&format!("profile_id={}&profile_name={}&key={}", profile_id, policy.encode(name), policy.encode(key))));
+        if let Some(fragment) = fragment {
+            result_url.set_fragment(Some(fragment));
+        }
+        Some(result_url.to_string())
+    }
+
+    /// Builds the service URL of a link, `None` if the base URL is invalid.
+    fn service_url(base_url: &str, action: &str, profile_id: i32, param_name: &str, path: &str, fragment: Option<&str>, policy: UrlEncodingPolicy) -> Option<String> {
+        // 如果路径已经包含编码字符，先解码再重新编码，避免双重编码
+        let decoded_path = policy.decode(path);
+
+        let clean_path = if action == "mdd" || action == "sound" {
+            // mdd类型使用MDD资源键格式
+            mdd_key::normalize(&decoded_path, ZdbVersion::V3)
         } else {
-            format!("file:///{}", url)
+            // 非mdd类型去掉前导斜杠
+            decoded_path.trim_start_matches('/').to_string()
         };
-        
-        if let Ok(parsed_url) = Url::parse(&file_url) {
-            let path = parsed_url.path();
-            let fragment = parsed_url.fragment();
-            
-            let base_url_trimmed = base_url.trim_end_matches('/');
-            
-            // 使用URL库构造结果
-            if let Ok(mut result_url) = Url::parse(&format!("{}/mdd?profile_id={}", base_url_trimmed, profile_id)) {
-                result_url.query_pairs_mut()
-                    .append_pair("key", path);
-                
-                // 设置fragment
-                if let Some(frag) = fragment {
-                    result_url.set_fragment(Some(frag));
-                }
-                
-                return result_url.to_string();
+
+        let mut result_url = Url::parse(&format!("{}/{}", base_url.trim_end_matches('/'), action)).ok()?;
+        result_url.set_query(Some(&format!("profile_id={}&{}={}", profile_id, param_name, policy.encode(&clean_path))));
+        // 设置fragment（URL库会自动编码）
+        if let Some(fragment) = fragment {
+            result_url.set_fragment(Some(fragment));
+        }
+        Some(result_url.to_string())
+    }
+
+    /// Rewrites the URL of every image candidate of a `srcset` attribute, keeping the
+    /// width and density descriptors.
+    pub fn rewrite_srcset(srcset: &str, profile_id: i32, base_url: &str) -> String {
+        Self::rewrite_srcset_with(srcset, profile_id, base_url, &UrlEncodingOptions::default())
+    }
+
+    fn rewrite_srcset_with(srcset: &str, profile_id: i32, base_url: &str, encoding: &UrlEncodingOptions) -> String {
+        let mut candidates = Vec::new();
+        let mut rest = srcset;
+        loop {
+            rest = rest.trim_start_matches(|c: char| c.is_whitespace() || c == ',');
+            if rest.is_empty() {
+                break;
             }
+            // The URL runs to the next whitespace, trailing commas end the candidate
+            let url_end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+            let url = &rest[..url_end];
+            let trimmed_url = url.trim_end_matches(',');
+            let descriptor = if trimmed_url.len() < url.len() {
+                rest = &rest[url_end..];
+                ""
+            } else {
+                let descriptor_end = rest[url_end..].find(',').map_or(rest.len(), |pos| url_end + pos);
+                let descriptor = rest[url_end..descriptor_end].trim();
+                rest = &rest[descriptor_end..];
+                descriptor
+            };
+            let new_url = Self::rewrite_url_with_options(trimmed_url, profile_id, base_url, encoding);
+            candidates.push(if descriptor.is_empty() { new_url } else { format!("{} {}", new_url, descriptor) });
         }
-        
-        // 如果所有URL库操作都失败，回退到原始URL
-        url.to_string()
+        candidates.join(", ")
     }
 
     /// 重写CSS中的url()引用
     pub fn rewrite_css_urls(css: &str, profile_id: i32, base_url: &str) -> String {
-        use regex::Regex;
-        
-        let url_regex = Regex::new(r#"url\s*\(\s*(['"]?)([^'")]+)(['"]?)\s*\)"#).unwrap();
-        
+        Self::rewrite_css_urls_with(css, profile_id, base_url, &UrlEncodingOptions::default())
+    }
+
+    fn rewrite_css_urls_with(css: &str, profile_id: i32, base_url: &str, encoding: &UrlEncodingOptions) -> String {
+        static URL_REGEX: OnceLock<regex::Regex> = OnceLock::new();
+        let url_regex = URL_REGEX.get_or_init(|| regex::Regex::new(r#"url\s*\(\s*(['"]?)([^'")]+)(['"]?)\s*\)"#).unwrap());
+
         url_regex.replace_all(css, |caps: &regex::Captures| {
             let quote1 = &caps[1];
             let url = &caps[2];
             let quote2 = &caps[3];
-            let new_url = Self::rewrite_url(url, profile_id, base_url);
+            let new_url = Self::rewrite_url_with_options(url, profile_id, base_url, encoding);
             format!("url({}{}{})", quote1, new_url, quote2)
         }).to_string()
     }
 }
 
+/// Rewriter of the HTML of one dictionary, reused for many entries, e.g. when exporting
+/// or prefetching.
+///
+/// The selectors of the handlers are parsed once for all rewriters; an instance keeps
+/// the profile id and base URL, and can rewrite batches of entries on several threads.
+///
+/// # Examples
+///
+/// ```rust
+/// use mdx::utils::MdxHtmlRewriterInstance;
+///
+/// let rewriter = MdxHtmlRewriterInstance::new(123);
+/// let html = rewriter.rewrite(r#"<a href="sound://hello.mp3">Play</a>"#).unwrap();
+/// assert_eq!(html, r#"<a href="mdx://mdict.cn/service/sound?profile_id=123&key=%2Fhello.mp3">Play</a>"#);
+///
+/// let pages = rewriter.rewrite_batch(&["<img src=\"a.png\">", "<img src=\"b.png\">"], 0);
+/// assert_eq!(pages.len(), 2);
+/// ```
+#[derive(Clone, Debug)]
+pub struct MdxHtmlRewriterInstance {
+    profile_id: i32,
+    base_url: String,
+    encoding: UrlEncodingOptions,
+    /// Dictionaries `entry://DictName/word` links may target
+    dictionary_names: Vec<String>,
+}
+
+impl MdxHtmlRewriterInstance {
+    /// Creates a rewriter with the default base URL.
+    pub fn new(profile_id: i32) -> Self {
+        Self::with_base_url(profile_id, DEFAULT_BASE_URL)
+    }
+
+    /// Creates a rewriter with a custom base URL.
+    pub fn with_base_url(profile_id: i32, base_url: &str) -> Self {
+        Self { profile_id, base_url: base_url.to_string(), encoding: UrlEncodingOptions::default(), dictionary_names: Vec::new() }
+    }
+
+    pub fn profile_id(&self) -> i32 {
+        self.profile_id
+    }
+
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    /// Sets the encoding policies of the keys of rewritten links.
+    pub fn with_encoding(mut self, encoding: UrlEncodingOptions) -> Self {
+        self.encoding = encoding;
+        self
+    }
+
+    pub fn encoding(&self) -> &UrlEncodingOptions {
+        &self.encoding
+    }
+
+    /// Sets the names of the dictionaries links of the form `entry://DictName/word` may
+    /// target, see [`MdxHtmlRewriter::rewrite_cross_dictionary_link`]. Other entry links
+    /// are rewritten as usual, so keys containing `/` aren't mistaken for such links.
+    pub fn with_dictionary_names<S: Into<String>>(mut self, names: impl IntoIterator<Item = S>) -> Self {
+        self.dictionary_names = names.into_iter().map(Into::into).collect();
+        self
+    }
+
+    pub fn dictionary_names(&self) -> &[String] {
+        &self.dictionary_names
+    }
+
+    /// Rewrites the links of an HTML string, see [`MdxHtmlRewriter::rewrite_html_with_base_url`].
+    pub fn rewrite(&self, html: &str) -> Result<String> {
+        MdxHtmlRewriter::rewrite_links(html, self.profile_id, &self.base_url, None, &self.encoding, &self.dictionary_names)
+    }
+
+    /// Rewrites the links of the HTML of the entry with key `current_key`, see
+    /// [`MdxHtmlRewriter::rewrite_html_for_entry`].
+    pub fn rewrite_entry(&self, html: &str, current_key: &str) -> Result<String> {
+        MdxHtmlRewriter::rewrite_links(html, self.profile_id, &self.base_url, Some(current_key), &self.encoding, &self.dictionary_names)
+    }
+
+    /// Rewrites many HTML strings in parallel.
+    ///
+    /// # Arguments
+    ///
+    /// * `htmls` - The HTML strings
+    /// * `threads` - Number of threads, `0` for the number of available CPUs
+    ///
+    /// # Returns
+    ///
+    /// Returns the result of each string, in the order of `htmls`.
+    pub fn rewrite_batch<T: AsRef<str> + Sync>(&self, htmls: &[T], threads: usize) -> Vec<Result<String>> {
+        parallel_map(htmls, io_thread_count(threads), |html| self.rewrite(html.as_ref()))
+    }
+
+    /// Rewrites the HTML of many entries in parallel, see [`rewrite_entry`](Self::rewrite_entry).
+    ///
+    /// # Arguments
+    ///
+    /// * `entries` - The key and HTML of each entry
+    /// * `threads` - Number of threads, `0` for the number of available CPUs
+    ///
+    /// # Returns
+    ///
+    /// Returns the result of each entry, in the order of `entries`.
+    pub fn rewrite_entry_batch<K: AsRef<str> + Sync, T: AsRef<str> + Sync>(&self, entries: &[(K, T)], threads: usize) -> Vec<Result<String>> {
+        parallel_map(entries, io_thread_count(threads), |(key, html)| self.rewrite_entry(html.as_ref(), key.as_ref()))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -315,6 +702,32 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_url_encoding_policies() {
+        let base_url = "mdx://mdict.cn/service/";
+        let rewrite = |url, encoding: &UrlEncodingOptions| MdxHtmlRewriter::rewrite_url_with_options(url, 123, base_url, encoding);
+        let percent_spaces = UrlEncodingOptions::new(UrlEncodingPolicy::percent_spaces());
+        assert_eq!(rewrite("entry://abc def+1", &percent_spaces), "mdx://mdict.cn/service/entry?profile_id=123&key=abc%20def%2B1");
+        assert_eq!(rewrite("entry://hello%20world", &percent_spaces), "mdx://mdict.cn/service/entry?profile_id=123&key=hello%20world");
+
+        let literal = UrlEncodingOptions::new(UrlEncodingPolicy::literal());
+        assert_eq!(rewrite("entry://100%25 sure", &literal), "mdx://mdict.cn/service/entry?profile_id=123&key=100%2525%20sure");
+        assert_eq!(rewrite("100%.png", &literal), "mdx://mdict.cn/service/mdd?profile_id=123&key=%2F100%25.png");
+
+        let raw = UrlEncodingOptions::default().with_scheme("sound", UrlEncodingPolicy::raw());
+        assert_eq!(rewrite("sound://a%2Bb.mp3", &raw), "mdx://mdict.cn/service/sound?profile_id=123&key=/a%2Bb.mp3");
+        assert_eq!(rewrite("entry://a%2Bb", &raw), "mdx://mdict.cn/service/entry?profile_id=123&key=a%2Bb");
+        assert_eq!(rewrite("entry://a b", &raw), "mdx://mdict.cn/service/entry?profile_id=123&key=a+b");
+
+        // Relative paths are decoded like file links, not encoded twice
+        assert_eq!(MdxHtmlRewriter::rewrite_url("images/a b.png?v=2#top", 123, base_url),
+            "mdx://mdict.cn/service/mdd?profile_id=123&key=%2Fimages%2Fa+b.png#top");
+
+        let rewriter = MdxHtmlRewriterInstance::new(1).with_encoding(percent_spaces);
+        assert_eq!(rewriter.rewrite(r#"<img src="a b.png">"#).unwrap(),
+            r#"<img src="mdx://mdict.cn/service/mdd?profile_id=1&key=%2Fa%20b.png">"#);
+    }
+
     #[test]
     fn test_rewrite_css_urls() {
         let base_url = "mdx://mdict.cn/service/";
@@ -325,6 +738,74 @@ mod tests {
 
 
 
+    #[test]
+    fn test_rewrite_srcset() {
+        let base_url = "mdx://mdict.cn/service/";
+        assert_eq!(MdxHtmlRewriter::rewrite_srcset("a.png 1x,b.png  2x", 123, base_url),
+            "mdx://mdict.cn/service/mdd?profile_id=123&key=%2Fa.png 1x, mdx://mdict.cn/service/mdd?profile_id=123&key=%2Fb.png 2x");
+        assert_eq!(MdxHtmlRewriter::rewrite_srcset(" small.png, https://example.com/big.png 800w ", 123, base_url),
+            "mdx://mdict.cn/service/mdd?profile_id=123&key=%2Fsmall.png, https://example.com/big.png 800w");
+    }
+
+    #[test]
+    fn test_rewrite_modern_html() -> Result<()> {
+        let html = concat!(
+            r#"<picture><source srcset="wide.png 2x, narrow.png" media="(min-width: 600px)"><img src="a.png" srcset="a2.png 2x"></picture>"#,
+            r#"<audio><source src="sound://word.mp3" type="audio/mpeg"></audio>"#,
+            r#"<svg><use xlink:href="icons.svg#star"></use></svg>"#,
+            r#"<style>@font-face { src: url("fonts/kai.ttf"); } b > i { background: url(dot.png) }</style>"#,
+        );
+        let result = MdxHtmlRewriter::rewrite_html(html, 123)?;
+        for expected in [
+            r#"srcset="mdx://mdict.cn/service/mdd?profile_id=123&key=%2Fwide.png 2x, mdx://mdict.cn/service/mdd?profile_id=123&key=%2Fnarrow.png""#,
+            r#"srcset="mdx://mdict.cn/service/mdd?profile_id=123&key=%2Fa2.png 2x""#,
+            r#"<source src="mdx://mdict.cn/service/sound?profile_id=123&key=%2Fword.mp3""#,
+            r#"xlink:href="mdx://mdict.cn/service/mdd?profile_id=123&key=%2Ficons.svg#star""#,
+            r#"src: url("mdx://mdict.cn/service/mdd?profile_id=123&key=%2Ffonts%2Fkai.ttf");"#,
+            r#"b > i { background: url(mdx://mdict.cn/service/mdd?profile_id=123&key=%2Fdot.png) }</style>"#,
+        ] {
+            assert!(result.contains(expected), "Missing {} in {}", expected, result);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_same_entry_anchors() -> Result<()> {
+        let html = concat!(
+            r#"<a href="entry://run#verb">verb</a><a href="entry://%72un#noun">noun</a><a href="entry://walk#verb">walk</a>"#,
+            r#"<a href="entry://run">run</a>"#,
+            r#"<a href="mdx://mdict.cn/service/entry?profile_id=123&key=run#idioms">idioms</a>"#,
+            r#"<a href="mdx://mdict.cn/service/entry?profile_id=7&key=run#idioms">other dictionary</a>"#,
+        );
+        let result = MdxHtmlRewriter::rewrite_html_for_entry(html, 123, DEFAULT_BASE_URL, "run")?;
+        assert!(result.contains(r##"<a href="#verb">verb</a><a href="#noun">noun</a>"##), "{}", result);
+        assert!(result.contains(r#"href="mdx://mdict.cn/service/entry?profile_id=123&key=walk#verb""#));
+        assert!(result.contains(r#"href="mdx://mdict.cn/service/entry?profile_id=123&key=run">run"#));
+        assert!(result.contains(r##"<a href="#idioms">idioms</a>"##));
+        assert!(result.contains(r#"href="mdx://mdict.cn/service/entry?profile_id=7&key=run#idioms""#));
+        // Without the current key every entry link is kept
+        let result = MdxHtmlRewriter::rewrite_html(html, 123)?;
+        assert!(result.contains(r#"href="mdx://mdict.cn/service/entry?profile_id=123&key=run#verb""#));
+        Ok(())
+    }
+
+    #[test]
+    fn test_rewriter_instance_batch() -> Result<()> {
+        let rewriter = MdxHtmlRewriterInstance::with_base_url(7, "app://dict");
+        let htmls: Vec<String> = (0..20).map(|i| format!(r#"<img src="img{}.png"><a href="entry://word{}#sense">x</a>"#, i, i % 2)).collect();
+        let results = rewriter.rewrite_batch(&htmls, 4);
+        assert_eq!(results.len(), htmls.len());
+        for (html, result) in htmls.iter().zip(results) {
+            assert_eq!(result?, MdxHtmlRewriter::rewrite_html_with_base_url(html, 7, "app://dict")?);
+        }
+
+        let entries = [("word0", htmls[0].as_str()), ("word0", htmls[1].as_str())];
+        let results = rewriter.rewrite_entry_batch(&entries, 0).into_iter().collect::<Result<Vec<_>>>()?;
+        assert!(results[0].contains(r##"href="#sense""##));
+        assert!(!results[1].contains(r##"href="#sense""##));
+        Ok(())
+    }
+
     #[test]
     fn test_custom_base_url() -> Result<()> {
         let html = r#"<img src="entry://test.png">"#;
@@ -0,0 +1,270 @@
+//! Parser of the service URLs produced by [`MdxHtmlRewriter`].
+//!
+//! A WebView showing rewritten entries asks the application to load URLs like
+//! `mdx://mdict.cn/service/sound?profile_id=1&key=%2Fhello.mp3`. [`MdxServiceUrl::parse`]
+//! decodes them the way the rewriter encoded them, so navigation and resource callbacks
+//! don't need their own query parsing.
+//!
+//! Keys are decoded as form values: `+` and `%20` are spaces and escapes are decoded once.
+//! Keys rewritten with [`EscapeHandling::Raw`](crate::utils::EscapeHandling::Raw) are
+//! decoded too, they can only be parsed if they didn't contain such sequences.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use mdx::utils::{MdxServiceUrl, ServiceAction, ServiceTarget};
+//!
+//! let url = MdxServiceUrl::parse("mdx://mdict.cn/service/entry?profile_id=3&key=kick+the+bucket#idiom").unwrap();
+//! assert_eq!(url.action, ServiceAction::Entry);
+//! assert_eq!(url.profile_id, 3);
+//! assert_eq!(url.target, ServiceTarget::Key("kick the bucket".to_string()));
+//! assert_eq!(url.fragment.as_deref(), Some("idiom"));
+//! ```
+
+use std::fmt;
+
+use percent_encoding::percent_decode_str;
+use url::Url;
+
+use crate::storage::key_block::EntryNo;
+use crate::utils::mdx_html_rewriter::{MdxHtmlRewriter, UrlEncodingOptions, DEFAULT_BASE_URL};
+use crate::{Result, ZdbError};
+
+/// Service of a rewritten link, the last segment of the path of its URL.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ServiceAction {
+    /// Entry by key, from `entry://` links
+    Entry,
+    /// Entry by number, from `entryx://` links
+    EntryX,
+    /// Audio resource, from `sound://` links
+    Sound,
+    /// Source of an entry by number, from `source://` links
+    Source,
+    /// MDD resource, from `file://` links and relative paths
+    Mdd,
+}
+
+impl ServiceAction {
+    const ACTIONS: [(ServiceAction, &'static str); 5] = [
+        (ServiceAction::Entry, "entry"),
+        (ServiceAction::EntryX, "entryx"),
+        (ServiceAction::Sound, "sound"),
+        (ServiceAction::Source, "source"),
+        (ServiceAction::Mdd, "mdd"),
+    ];
+
+    /// Gets the path segment of the action.
+    pub fn as_str(&self) -> &'static str {
+        Self::ACTIONS.iter().find(|(action, _)| action == self).map(|(_, name)| *name).unwrap_or_default()
+    }
+
+    /// Gets the action of a path segment.
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ACTIONS.iter().find(|(_, action_name)| *action_name == name).map(|(action, _)| *action)
+    }
+
+    /// Checks whether the target of the action is an entry number rather than a key.
+    pub fn targets_entry_no(&self) -> bool {
+        matches!(self, ServiceAction::EntryX | ServiceAction::Source)
+    }
+}
+
+impl fmt::Display for ServiceAction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Entry or resource a service URL points to.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ServiceTarget {
+    /// Key of an entry, or of a resource starting with `/`
+    Key(String),
+    EntryNo(EntryNo),
+}
+
+/// Decoded service URL, see the [module documentation](self).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MdxServiceUrl {
+    pub action: ServiceAction,
+    /// Profile id of the dictionary of the link
+    pub profile_id: i32,
+    /// Name of the dictionary of the target of cross-dictionary links, see
+    /// [`MdxHtmlRewriter::rewrite_cross_dictionary_link`]
+    pub profile_name: Option<String>,
+    pub target: ServiceTarget,
+    /// Decoded fragment, without the `#`
+    pub fragment: Option<String>,
+}
+
+impl MdxServiceUrl {
+    /// Parses a service URL with any base URL, the action being the last segment of its path.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `InvalidParameter` error if `url` is not a service URL or lacks the
+    /// profile id or target of its action.
+    pub fn parse(url: &str) -> Result<Self> {
+        let parsed = Url::parse(url.trim())
+            .map_err(|e| ZdbError::invalid_parameter(format!("Invalid service URL '{}': {}", url, e)))?;
+        Self::from_url(&parsed)
+    }
+
+    /// Parses a service URL, checking it starts with `base_url`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `InvalidParameter` error if `url` doesn't start with `base_url` or isn't
+    /// a service URL, see [`parse`](Self::parse).
+    pub fn parse_with_base_url(url: &str, base_url: &str) -> Result<Self> {
+        let prefix = format!("{}/", base_url.trim_end_matches('/'));
+        let url = url.trim();
+        // Schemes and hosts are case-insensitive
+        if !url.get(..prefix.len()).is_some_and(|start| start.eq_ignore_ascii_case(&prefix)) {
+            return Err(ZdbError::invalid_parameter(format!("Service URL '{}' doesn't start with '{}'", url, prefix)));
+        }
+        Self::parse(url)
+    }
+
+    /// Parses a parsed service URL, see [`parse`](Self::parse).
+    ///
+    /// # Errors
+    ///
+    /// Returns an `InvalidParameter` error if `url` is not a service URL.
+    pub fn from_url(url: &Url) -> Result<Self> {
+        let invalid = |reason: &str| ZdbError::invalid_parameter(format!("Invalid service URL '{}': {}", url, reason));
+        let action_name = url.path_segments().and_then(|mut segments| segments.next_back()).unwrap_or_default();
+        let action = ServiceAction::from_name(action_name)
+            .ok_or_else(|| invalid(&format!("unknown action '{}'", action_name)))?;
+
+        let mut profile_id = None;
+        let mut profile_name = None;
+        let mut target_value = None;
+        let target_param = if action.targets_entry_no() { "entry_no" } else { "key" };
+        for (name, value) in url.query_pairs() {
+            if name == "profile_id" && profile_id.is_none() {
+                profile_id = Some(value.trim().parse::<i32>().map_err(|_| invalid(&format!("invalid profile id '{}'", value)))?);
+            } else if name == "profile_name" && profile_name.is_none() && !value.is_empty() {
+                profile_name = Some(value.into_owned());
+            } else if name == target_param && target_value.is_none() {
+                target_value = Some(value.into_owned());
+            }
+        }
+        let profile_id = profile_id.ok_or_else(|| invalid("missing profile_id"))?;
+        let target_value = target_value.ok_or_else(|| invalid(&format!("missing {}", target_param)))?;
+        let target = if action.targets_entry_no() {
+            ServiceTarget::EntryNo(target_value.trim().parse().map_err(|_| invalid(&format!("invalid entry number '{}'", target_value)))?)
+        } else {
+            ServiceTarget::Key(target_value)
+        };
+        let fragment = url.fragment().map(|fragment| percent_decode_str(fragment).decode_utf8_lossy().into_owned());
+        Ok(Self { action, profile_id, profile_name, target, fragment })
+    }
+
+    /// Gets the key of the target, `None` for entry numbers.
+    pub fn key(&self) -> Option<&str> {
+        match &self.target {
+            ServiceTarget::Key(key) => Some(key),
+            ServiceTarget::EntryNo(_) => None,
+        }
+    }
+
+    /// Gets the entry number of the target, `None` for keys.
+    pub fn entry_no(&self) -> Option<EntryNo> {
+        match self.target {
+            ServiceTarget::EntryNo(entry_no) => Some(entry_no),
+            ServiceTarget::Key(_) => None,
+        }
+    }
+
+    /// Builds the URL with the default base URL, see [`to_url_with_base_url`](Self::to_url_with_base_url).
+    pub fn to_url(&self) -> String {
+        self.to_url_with_base_url(DEFAULT_BASE_URL)
+    }
+
+    /// Builds the URL the rewriter produces for the target, with the default encoding.
+    pub fn to_url_with_base_url(&self, base_url: &str) -> String {
+        let mut url = format!("{}://", self.original_scheme());
+        if let Some(profile_name) = &self.profile_name {
+            url.push_str(&percent_encoding::utf8_percent_encode(profile_name, KEY_ESCAPES).to_string());
+            url.push('/');
+        }
+        match &self.target {
+            ServiceTarget::Key(key) => url.push_str(&percent_encoding::utf8_percent_encode(key, KEY_ESCAPES).to_string()),
+            ServiceTarget::EntryNo(entry_no) => url.push_str(&entry_no.to_string()),
+        }
+        if let Some(fragment) = &self.fragment {
+            url.push('#');
+            url.push_str(fragment);
+        }
+        let encoding = UrlEncodingOptions::default();
+        match &self.profile_name {
+            Some(profile_name) => MdxHtmlRewriter::rewrite_cross_dictionary_link(&url, self.profile_id, base_url, std::slice::from_ref(profile_name), &encoding)
+                .unwrap_or_else(|| MdxHtmlRewriter::rewrite_url(&url, self.profile_id, base_url)),
+            None => MdxHtmlRewriter::rewrite_url(&url, self.profile_id, base_url),
+        }
+    }
+
+    /// Scheme of the links rewritten to the action.
+    fn original_scheme(&self) -> &'static str {
+        match self.action {
+            ServiceAction::Mdd => "file",
+            action => action.as_str(),
+        }
+    }
+}
+
+/// Characters of keys escaped before rewriting, so they are decoded back to themselves.
+const KEY_ESCAPES: &percent_encoding::AsciiSet = &percent_encoding::CONTROLS.add(b'%').add(b'#');
+
+impl fmt::Display for MdxServiceUrl {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.to_url())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_rewritten_urls() {
+        let links = [
+            ("entry://kick the bucket#idiom", ServiceAction::Entry, ServiceTarget::Key("kick the bucket".to_string()), Some("idiom")),
+            ("entry://a+b & c", ServiceAction::Entry, ServiceTarget::Key("a+b & c".to_string()), None),
+            ("entry://测试#第一章", ServiceAction::Entry, ServiceTarget::Key("测试".to_string()), Some("第一章")),
+            ("entryx://42", ServiceAction::EntryX, ServiceTarget::EntryNo(42), None),
+            ("source://7", ServiceAction::Source, ServiceTarget::EntryNo(7), None),
+            ("sound://audio file.mp3", ServiceAction::Sound, ServiceTarget::Key("/audio file.mp3".to_string()), None),
+            ("images/a.png", ServiceAction::Mdd, ServiceTarget::Key("/images/a.png".to_string()), None),
+        ];
+        for (link, action, target, fragment) in links {
+            let rewritten = MdxHtmlRewriter::rewrite_url(link, 5, "app://dict/");
+            let parsed = MdxServiceUrl::parse_with_base_url(&rewritten, "app://dict").unwrap();
+            assert_eq!(parsed, MdxServiceUrl { action, profile_id: 5, profile_name: None, target, fragment: fragment.map(str::to_string) }, "{}", link);
+            assert_eq!(parsed.to_url_with_base_url("app://dict/"), rewritten, "{}", link);
+        }
+    }
+
+    #[test]
+    fn test_parse_cross_dictionary_url() {
+        let names = ["Oxford Idioms".to_string()];
+        let rewritten = MdxHtmlRewriter::rewrite_cross_dictionary_link("entry://Oxford%20Idioms/kick the bucket#usage", 2, DEFAULT_BASE_URL, &names, &UrlEncodingOptions::default()).unwrap();
+        assert_eq!(rewritten, "mdx://mdict.cn/service/entry?profile_id=2&profile_name=Oxford+Idioms&key=kick+the+bucket#usage");
+        let parsed = MdxServiceUrl::parse(&rewritten).unwrap();
+        assert_eq!(parsed.profile_name.as_deref(), Some("Oxford Idioms"));
+        assert_eq!(parsed.key(), Some("kick the bucket"));
+        assert_eq!(parsed.to_url(), rewritten);
+    }
+
+    #[test]
+    fn test_parse_invalid_urls() {
+        assert!(MdxServiceUrl::parse("not a url").is_err());
+        assert!(MdxServiceUrl::parse("mdx://mdict.cn/service/unknown?profile_id=1&key=a").is_err());
+        assert!(MdxServiceUrl::parse("mdx://mdict.cn/service/entry?key=a").is_err());
+        assert!(MdxServiceUrl::parse("mdx://mdict.cn/service/entry?profile_id=1").is_err());
+        assert!(MdxServiceUrl::parse("mdx://mdict.cn/service/entryx?profile_id=1&entry_no=x").is_err());
+        assert!(MdxServiceUrl::parse_with_base_url("mdx://other/entry?profile_id=1&key=a", "mdx://mdict.cn/service/").is_err());
+    }
+}
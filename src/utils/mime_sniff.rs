@@ -0,0 +1,111 @@
+//! MIME types of resources inferred from their content.
+//!
+//! Resources in dictionaries are often misnamed, e.g. a JPEG stored as `cat.png` or a
+//! sound without extension, and browsers refuse some content served with a wrong type.
+//! [`sniff_mime_type`] recognizes the common formats by their magic bytes, and
+//! [`infer_mime_type`] combines it with the type recorded in the header and the extension.
+
+use mime_guess::MimeGuess;
+
+/// Magic bytes at the start of a file and its MIME type.
+const SIGNATURES: &[(&[u8], &str)] = &[
+    (b"\x89PNG\r\n\x1a\n", "image/png"),
+    (b"\xFF\xD8\xFF", "image/jpeg"),
+    (b"GIF87a", "image/gif"),
+    (b"GIF89a", "image/gif"),
+    (b"BM", "image/bmp"),
+    (b"\x00\x00\x01\x00", "image/x-icon"),
+    (b"%PDF-", "application/pdf"),
+    (b"PK\x03\x04", "application/zip"),
+    (b"\x1F\x8B", "application/gzip"),
+    (b"OggS", "audio/ogg"),
+    (b"fLaC", "audio/flac"),
+    (b"ID3", "audio/mpeg"),
+    (b"#!AMR", "audio/amr"),
+    (b"\x1A\x45\xDF\xA3", "video/webm"),
+    (b"wOFF", "font/woff"),
+    (b"wOF2", "font/woff2"),
+    (b"OTTO", "font/otf"),
+    (b"\x00\x01\x00\x00\x00", "font/ttf"),
+];
+
+/// Recognizes the format of a resource by its magic bytes.
+///
+/// # Returns
+///
+/// Returns `None` if the format isn't recognized, e.g. for plain text.
+///
+/// # Examples
+///
+/// ```
+/// use mdx::utils::mime_sniff::sniff_mime_type;
+///
+/// assert_eq!(sniff_mime_type(b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR"), Some("image/png"));
+/// assert_eq!(sniff_mime_type(b"hello"), None);
+/// ```
+pub fn sniff_mime_type(data: &[u8]) -> Option<&'static str> {
+    if let Some((_, mime_type)) = SIGNATURES.iter().find(|(magic, _)| data.starts_with(magic)) {
+        return Some(mime_type);
+    }
+    if data.len() >= 12 && data.starts_with(b"RIFF") {
+        return match &data[8..12] {
+            b"WEBP" => Some("image/webp"),
+            b"WAVE" => Some("audio/wav"),
+            b"AVI " => Some("video/x-msvideo"),
+            _ => None,
+        };
+    }
+    // ISO media files start with the size of the `ftyp` box
+    if data.len() >= 12 && &data[4..8] == b"ftyp" {
+        return match &data[8..12] {
+            b"M4A " | b"M4B " => Some("audio/mp4"),
+            b"avif" => Some("image/avif"),
+            b"heic" | b"heix" => Some("image/heic"),
+            _ => Some("video/mp4"),
+        };
+    }
+    // MPEG audio frames without ID3 tag start with a frame sync
+    if data.len() >= 2 && data[0] == 0xFF && data[1] & 0xE0 == 0xE0 {
+        return Some("audio/mpeg");
+    }
+    let head = String::from_utf8_lossy(&data[..data.len().min(256)]).trim_start_matches('\u{FEFF}').trim_start().to_lowercase();
+    if head.starts_with("<svg") || (head.starts_with("<?xml") && head.contains("<svg")) {
+        return Some("image/svg+xml");
+    }
+    None
+}
+
+/// Infers the MIME type of a resource.
+///
+/// The type recorded in the header when the file was built wins, then the type
+/// recognized from the content, then the type guessed from the extension of the path.
+///
+/// # Arguments
+///
+/// * `path` - Path or key of the resource
+/// * `data` - Content of the resource
+/// * `recorded` - Type recorded for the extension, see [`MddReader::media_type`](crate::readers::MddReader::media_type)
+pub fn infer_mime_type(path: &str, data: &[u8], recorded: Option<&str>) -> String {
+    if let Some(recorded) = recorded {
+        return recorded.to_string();
+    }
+    match sniff_mime_type(data) {
+        Some(mime_type) => mime_type.to_string(),
+        None => MimeGuess::from_path(path).first_or_octet_stream().to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_infer_mime_type() {
+        assert_eq!(infer_mime_type("/cat.png", b"\xFF\xD8\xFF\xE0\0\x10JFIF", None), "image/jpeg");
+        assert_eq!(infer_mime_type("/sound", b"RIFF\0\0\0\0WAVEfmt ", None), "audio/wav");
+        assert_eq!(infer_mime_type("/logo.svg", b"<?xml version=\"1.0\"?>\n<svg></svg>", None), "image/svg+xml");
+        assert_eq!(infer_mime_type("/style.css", b"body { }", None), "text/css");
+        assert_eq!(infer_mime_type("/data.bin", b"\x89PNG\r\n\x1a\n", Some("application/x-custom")), "application/x-custom");
+        assert_eq!(infer_mime_type("/unknown", b"\x01\x02", None), "application/octet-stream");
+    }
+}
@@ -7,21 +7,47 @@ pub mod utils;
 pub mod io_utils;
 pub mod sort_key;
 pub mod mdx_html_rewriter;
+pub mod mdx_service_url;
 pub mod progress_report;
 pub mod compression;
 pub mod icu_wrapper;
 pub mod url_utils;
+pub mod atomic_output;
+pub mod sharded_cache;
+pub mod key_normalization;
+pub mod named_enum;
+pub mod html_text;
+pub mod html_preview;
+pub mod css;
+pub mod dark_mode;
+pub mod html_normalize;
+pub mod mdd_key;
+pub mod label_expander;
+pub mod mime_sniff;
+pub mod checksum;
+pub mod romanize;
 
 pub use utils::{
     remove_xml_declaration,
     KeyComparable, RandomAccessable, sort_key_compare, locale_compare, 
-    binary_search_first, key_compare, html_escape_mdx_text, extract_text_from_html,
+    binary_search_first, binary_search_first_position, key_compare, html_escape_mdx_text, extract_text_from_html,
     move_element
 };
-pub use io_utils::{read_exact_to_vec, scan_dir, windows_path_to_unix_path, fix_windows_path_buf};
+pub use io_utils::{read_exact_to_vec, scan_dir, scan_dir_with_options, ScanOptions, windows_path_to_unix_path, fix_windows_path_buf};
 pub use sort_key::get_sort_key;
-pub use mdx_html_rewriter::MdxHtmlRewriter;
+pub use mdx_html_rewriter::{EscapeHandling, MdxHtmlRewriter, MdxHtmlRewriterInstance, UrlEncodingOptions, UrlEncodingPolicy};
+pub use mdx_service_url::{MdxServiceUrl, ServiceAction, ServiceTarget};
+pub use html_text::HtmlTextExtractor;
+pub use html_preview::{HtmlPreviewer, PreviewFormat, PreviewOptions};
+pub use css::minify_css;
+pub use dark_mode::DarkMode;
+pub use html_normalize::{HtmlNormalizer, NormalizeOptions};
+pub use label_expander::LabelExpander;
 pub use progress_report::{ProgressState, ProgressReportFn};
-pub use compression::{CompressionMethod, get_compressor};
+pub use compression::{CompressionMethod, get_compressor, get_compressor_with_level};
+pub use checksum::ChecksumAlgorithm;
+pub use romanize::Romanization;
 pub use icu_wrapper::*;
 pub use url_utils::*;
+pub use key_normalization::{KeyNormalization, normalize_query, fold_headword};
+pub use sharded_cache::{ShardedLruCache, BlockCacheConfig};
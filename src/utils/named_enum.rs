@@ -0,0 +1,87 @@
+//! Serde support for enums configured by name or by number.
+//!
+//! Hand-written config files use names like `"Deflate"`, while older configs and
+//! other tools write the numeric value of the enum. [`named_enum_serde!`] implements
+//! `Serialize` writing the name and `Deserialize` accepting both, names matched
+//! case-insensitively. Unknown values are reported together with the valid ones,
+//! instead of serde's generic "unknown variant" message.
+
+use std::fmt;
+use std::marker::PhantomData;
+
+use serde::de::{self, Visitor};
+
+/// Enum with a name and a numeric value per variant.
+pub trait NamedEnum: Sized + Copy + PartialEq + 'static {
+    /// Description of the enum in error messages, e.g. "compression method"
+    const KIND: &'static str;
+    /// All variants with their names and numeric values
+    const VARIANTS: &'static [(Self, &'static str, u64)];
+
+    fn name(&self) -> &'static str {
+        Self::VARIANTS.iter().find(|(variant, _, _)| variant == self).map(|(_, name, _)| *name).unwrap_or_default()
+    }
+
+    /// Describes the accepted values, e.g. `None (0), Deflate (2)`.
+    fn expected() -> String {
+        let values: Vec<String> = Self::VARIANTS.iter().map(|(_, name, number)| format!("{} ({})", name, number)).collect();
+        values.join(", ")
+    }
+}
+
+/// Visitor accepting the name or the number of a variant.
+pub struct NamedEnumVisitor<T>(pub PhantomData<T>);
+
+impl<T: NamedEnum> NamedEnumVisitor<T> {
+    fn unknown<E: de::Error>(value: &dyn fmt::Display) -> E {
+        E::custom(format!("unknown {} \"{}\", expected one of: {}", T::KIND, value, T::expected()))
+    }
+}
+
+impl<'de, T: NamedEnum> Visitor<'de> for NamedEnumVisitor<T> {
+    type Value = T;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(formatter, "a {} name or number", T::KIND)
+    }
+
+    fn visit_str<E: de::Error>(self, value: &str) -> Result<T, E> {
+        T::VARIANTS.iter()
+            .find(|(_, name, _)| name.eq_ignore_ascii_case(value.trim()))
+            .map(|(variant, _, _)| *variant)
+            .ok_or_else(|| Self::unknown(&value))
+    }
+
+    fn visit_u64<E: de::Error>(self, value: u64) -> Result<T, E> {
+        T::VARIANTS.iter()
+            .find(|(_, _, number)| *number == value)
+            .map(|(variant, _, _)| *variant)
+            .ok_or_else(|| Self::unknown(&value))
+    }
+
+    fn visit_i64<E: de::Error>(self, value: i64) -> Result<T, E> {
+        match u64::try_from(value) {
+            Ok(value) => self.visit_u64(value),
+            Err(_) => Err(Self::unknown(&value)),
+        }
+    }
+}
+
+/// Implements `Serialize` and `Deserialize` for a [`NamedEnum`].
+macro_rules! named_enum_serde {
+    ($type:ty) => {
+        impl serde::Serialize for $type {
+            fn serialize<S: serde::Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+                serializer.serialize_str($crate::utils::named_enum::NamedEnum::name(self))
+            }
+        }
+
+        impl<'de> serde::Deserialize<'de> for $type {
+            fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+                deserializer.deserialize_any($crate::utils::named_enum::NamedEnumVisitor::<$type>(std::marker::PhantomData))
+            }
+        }
+    };
+}
+
+pub(crate) use named_enum_serde;
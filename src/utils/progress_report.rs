@@ -23,12 +23,35 @@
 //! }
 //! ```
 
+use std::time::{Duration, Instant};
+
+/// Longest time between two reports while items are processed, so that slow items
+/// still update the display.
+pub const REPORT_PERIOD: Duration = Duration::from_millis(200);
+
 /// Function type for progress reporting callbacks.
 ///
 /// The function receives a mutable reference to the progress state and
 /// returns `true` to cancel the operation, or `false` to continue.
 pub type ProgressReportFn= fn(&mut ProgressState) -> bool;
 
+/// How the progress of a loader is reported.
+///
+/// Converts from an optional reporter, which is called at intervals.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ProgressOptions {
+    /// Optional reporter function to call
+    pub reporter: Option<ProgressReportFn>,
+    /// Call the reporter for every item instead of at intervals, e.g. to show the key being processed
+    pub every_item: bool,
+}
+
+impl From<Option<ProgressReportFn>> for ProgressOptions {
+    fn from(reporter: Option<ProgressReportFn>) -> Self {
+        Self { reporter, every_item: false }
+    }
+}
+
 /// State information for progress reporting.
 ///
 /// This struct tracks the progress of a long-running operation and
@@ -48,6 +71,17 @@ pub struct ProgressState{
     pub report_interval: u64,
     /// Optional reporter function to call
     pub reporter: Option<ProgressReportFn>,
+    /// Human readable name of the current phase, e.g. "Scanning source file", empty if not set
+    pub phase: String,
+    /// Key of the item being processed when the reporter is called, empty if items have no key
+    pub current_key: String,
+    /// Bytes of the input processed so far, for byte based progress
+    pub processed_bytes: u64,
+    /// Total bytes of the input, `0` if unknown or the progress isn't byte based
+    pub total_bytes: u64,
+    /// Call the reporter for every item instead of at intervals
+    pub every_item: bool,
+    last_report_time: Instant,
 }
 
 impl ProgressState {
@@ -77,13 +111,36 @@ impl ProgressState {
             last: 0,
             report_interval: total*report_interval_percent/100,
             reporter,
+            phase: String::new(),
+            current_key: String::new(),
+            processed_bytes: 0,
+            total_bytes: 0,
+            every_item: false,
+            last_report_time: Instant::now(),
         }
     }
 
+    /// Creates a new progress state with a phase name and reporting options.
+    ///
+    /// # Arguments
+    ///
+    /// * `state_id` - Identifier for this progress state
+    /// * `phase` - Human readable name of the phase
+    /// * `total` - Total number of items to process
+    /// * `report_interval_percent` - Percentage of items between reports (0-100), ignored if every item is reported
+    /// * `options` - Reporter and whether every item is reported
+    pub fn with_options(state_id: &str, phase: &str, total: u64, report_interval_percent: u64, options: ProgressOptions) -> Self {
+        let mut state = Self::new(state_id, total, report_interval_percent, options.reporter);
+        state.phase = phase.to_string();
+        state.every_item = options.every_item;
+        state
+    }
+
     /// Reports progress for the current item.
     ///
-    /// This method checks if enough items have been processed since the last
-    /// report, and if so, calls the reporter function.
+    /// This method checks if enough items have been processed or enough time has
+    /// passed since the last report, see [`REPORT_PERIOD`], and if so, calls the
+    /// reporter function.
     ///
     /// # Arguments
     ///
@@ -96,13 +153,40 @@ impl ProgressState {
         if self.reporter.is_none() {
             return false;
         }
-        if (current-self.last) > self.report_interval || current == self.total-1 {
-            self.current = current;
-            let cancelled= (self.reporter.unwrap())(self); 
-            self.last = current;
-            return cancelled;
+        if self.is_due(current) {
+            self.call_reporter(current)
         }else{
+            false
+        }
+    }
+
+    /// Reports progress for the current item with its key and the bytes processed so far.
+    ///
+    /// Same as [`report`](Self::report), the key is only copied if the reporter is called.
+    ///
+    /// # Returns
+    ///
+    /// Returns `true` if the operation should be cancelled, `false` otherwise.
+    pub fn report_item(&mut self, current: u64, processed_bytes: u64, key: &str) -> bool {
+        self.processed_bytes = processed_bytes;
+        if self.reporter.is_none() || !self.is_due(current) {
             return false;
         }
+        self.current_key.clear();
+        self.current_key.push_str(key);
+        self.call_reporter(current)
+    }
+
+    fn is_due(&self, current: u64) -> bool {
+        self.every_item || (current-self.last) > self.report_interval || current == self.total-1
+            || self.last_report_time.elapsed() >= REPORT_PERIOD
+    }
+
+    fn call_reporter(&mut self, current: u64) -> bool {
+        self.current = current;
+        let cancelled = (self.reporter.unwrap())(self);
+        self.last = current;
+        self.last_report_time = Instant::now();
+        cancelled
     }
 }
@@ -0,0 +1,206 @@
+//! Romanized forms of headwords for full-text search.
+//!
+//! Users of Chinese and Japanese dictionaries often type the romanization of a word
+//! instead of switching input methods, e.g. "shuang" for 双 or "shinbun" for しんぶん.
+//! The FTS index can store the romanized keys in an extra field, see
+//! [`FtsIndexOptions::romanization`](crate::builder::fts_index_builder::FtsIndexOptions::romanization).
+//!
+//! - Romaji: hiragana and katakana in modified Hepburn, always available. Kanji are left alone.
+//! - Pinyin: Han characters in toneless pinyin, one syllable per character. The
+//!   readings come from the `any_ascii` crate, which requires the `pinyin` feature.
+
+use crate::utils::named_enum::{named_enum_serde, NamedEnum};
+use crate::{Result, ZdbError};
+
+/// Romanization of the headwords stored in the FTS index, see the [module documentation](self).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Romanization {
+    /// Han characters in toneless pinyin, requires the `pinyin` feature
+    Pinyin = 1,
+    /// Kana in modified Hepburn romaji
+    Romaji = 2,
+}
+
+impl NamedEnum for Romanization {
+    const KIND: &'static str = "romanization";
+    const VARIANTS: &'static [(Self, &'static str, u64)] = &[
+        (Romanization::Pinyin, "Pinyin", 1),
+        (Romanization::Romaji, "Romaji", 2),
+    ];
+}
+
+named_enum_serde!(Romanization);
+
+impl Romanization {
+    /// Checks the romanization is supported by this build.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `UnsupportedFeature` error for pinyin without the `pinyin` feature.
+    pub fn check_supported(&self) -> Result<()> {
+        if *self == Romanization::Pinyin && !cfg!(feature = "pinyin") {
+            return Err(ZdbError::unsupported_feature("pinyin romanization", "build with the `pinyin` feature"));
+        }
+        Ok(())
+    }
+
+    /// Romanizes the words of `text` for indexing.
+    ///
+    /// Every romanized syllable is a word of the result, followed by the syllables of each
+    /// run of romanized characters joined, so both "shuang" and "shuangren" find 双人.
+    /// Characters that aren't romanized separate the runs and are left out.
+    ///
+    /// # Returns
+    ///
+    /// Returns the lowercase words separated by spaces, `None` if nothing was romanized.
+    pub fn romanize(&self, text: &str) -> Option<String> {
+        let runs = match self {
+            Romanization::Pinyin => pinyin_runs(text),
+            Romanization::Romaji => romaji_runs(text),
+        };
+        let mut words: Vec<String> = runs.iter().flatten().cloned().collect();
+        if words.is_empty() {
+            return None;
+        }
+        words.extend(runs.iter().filter(|run| run.len() > 1).map(|run| run.concat()));
+        Some(words.join(" "))
+    }
+}
+
+#[cfg(feature = "pinyin")]
+fn pinyin_runs(text: &str) -> Vec<Vec<String>> {
+    let mut runs = vec![Vec::new()];
+    for c in text.chars() {
+        let syllable = if is_han(c) { any_ascii::any_ascii_char(c) } else { "" };
+        if !syllable.is_empty() && syllable.bytes().all(|b| b.is_ascii_alphabetic()) {
+            runs.last_mut().unwrap().push(syllable.to_ascii_lowercase());
+        } else if !runs.last().unwrap().is_empty() {
+            runs.push(Vec::new());
+        }
+    }
+    runs
+}
+
+#[cfg(not(feature = "pinyin"))]
+fn pinyin_runs(_text: &str) -> Vec<Vec<String>> {
+    Vec::new()
+}
+
+#[cfg(feature = "pinyin")]
+fn is_han(c: char) -> bool {
+    matches!(c, '\u{3400}'..='\u{4DBF}' | '\u{4E00}'..='\u{9FFF}' | '\u{F900}'..='\u{FAFF}' | '\u{20000}'..='\u{3134F}')
+}
+
+/// Hepburn romaji of the hiragana from U+3041 to U+3096, empty for the small tsu.
+const HIRAGANA_ROMAJI: [&str; 86] = [
+    "a", "a", "i", "i", "u", "u", "e", "e", "o", "o",
+    "ka", "ga", "ki", "gi", "ku", "gu", "ke", "ge", "ko", "go",
+    "sa", "za", "shi", "ji", "su", "zu", "se", "ze", "so", "zo",
+    "ta", "da", "chi", "ji", "", "tsu", "zu", "te", "de", "to", "do",
+    "na", "ni", "nu", "ne", "no",
+    "ha", "ba", "pa", "hi", "bi", "pi", "fu", "bu", "pu", "he", "be", "pe", "ho", "bo", "po",
+    "ma", "mi", "mu", "me", "mo",
+    "ya", "ya", "yu", "yu", "yo", "yo",
+    "ra", "ri", "ru", "re", "ro",
+    "wa", "wa", "i", "e", "o", "n", "vu", "ka", "ke",
+];
+
+/// Folds katakana to hiragana, keeping the prolonged sound mark.
+fn to_hiragana(c: char) -> Option<char> {
+    match c {
+        '\u{3041}'..='\u{3096}' | 'ー' => Some(c),
+        '\u{30A1}'..='\u{30F6}' => char::from_u32(c as u32 - 0x60),
+        _ => None,
+    }
+}
+
+fn is_vowel(c: char) -> bool {
+    matches!(c, 'a' | 'i' | 'u' | 'e' | 'o')
+}
+
+/// Romanizes runs of kana, each run is a single word.
+fn romaji_runs(text: &str) -> Vec<Vec<String>> {
+    let mut runs = Vec::new();
+    let mut word = String::new();
+    let mut geminate = false;
+    for c in text.chars() {
+        let Some(kana) = to_hiragana(c) else {
+            if !word.is_empty() {
+                runs.push(vec![std::mem::take(&mut word)]);
+            }
+            geminate = false;
+            continue;
+        };
+        match kana {
+            'ー' => {
+                if let Some(vowel) = word.chars().last().filter(|c| is_vowel(*c)) {
+                    word.push(vowel);
+                }
+            }
+            'っ' => geminate = true,
+            // Small ya, yu and yo after an i-row kana, e.g. きょ kyo, しゃ sha
+            'ゃ' | 'ゅ' | 'ょ' if word.ends_with('i') && word.len() > 1 => {
+                word.pop();
+                let romaji = HIRAGANA_ROMAJI[kana as usize - 0x3041];
+                if word.ends_with("sh") || word.ends_with("ch") || word.ends_with('j') {
+                    word.push_str(&romaji[1..]);
+                } else {
+                    word.push_str(romaji);
+                }
+            }
+            // Small vowels replace the vowel of the previous kana, e.g. ファ fa, ティ ti, ウィ wi
+            'ぁ' | 'ぃ' | 'ぅ' | 'ぇ' | 'ぉ' if word.ends_with(is_vowel) => {
+                let previous = word.pop();
+                if previous == Some('u') && !word.ends_with(|c: char| c.is_ascii_alphabetic() && !is_vowel(c)) {
+                    word.push('w');
+                }
+                word.push_str(HIRAGANA_ROMAJI[kana as usize - 0x3041]);
+            }
+            _ => {
+                let romaji = HIRAGANA_ROMAJI[kana as usize - 0x3041];
+                if geminate {
+                    if romaji.starts_with("ch") {
+                        word.push('t');
+                    } else if let Some(consonant) = romaji.chars().next().filter(|c| !is_vowel(*c)) {
+                        word.push(consonant);
+                    }
+                    geminate = false;
+                }
+                word.push_str(romaji);
+            }
+        }
+    }
+    if !word.is_empty() {
+        runs.push(vec![word]);
+    }
+    runs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_romaji() {
+        let romaji = |text| Romanization::Romaji.romanize(text);
+        assert_eq!(romaji("しんぶん").as_deref(), Some("shinbun"));
+        assert_eq!(romaji("きょうと").as_deref(), Some("kyouto"));
+        assert_eq!(romaji("ちょっと").as_deref(), Some("chotto"));
+        assert_eq!(romaji("マッチ").as_deref(), Some("matchi"));
+        assert_eq!(romaji("コーヒー").as_deref(), Some("koohii"));
+        assert_eq!(romaji("パーティー").as_deref(), Some("paatii"));
+        assert_eq!(romaji("ウィキ").as_deref(), Some("wiki"));
+        assert_eq!(romaji("東京タワー").as_deref(), Some("tawaa"));
+        assert_eq!(romaji("すし と てんぷら").as_deref(), Some("sushi to tenpura"));
+        assert_eq!(romaji("dictionary"), None);
+    }
+
+    #[cfg(feature = "pinyin")]
+    #[test]
+    fn test_pinyin() {
+        assert_eq!(Romanization::Pinyin.romanize("双").as_deref(), Some("shuang"));
+        assert_eq!(Romanization::Pinyin.romanize("双人床").as_deref(), Some("shuang ren chuang shuangrenchuang"));
+        assert_eq!(Romanization::Pinyin.romanize("abc"), None);
+    }
+}
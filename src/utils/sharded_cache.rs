@@ -0,0 +1,159 @@
+//! Sharded LRU cache for readers shared between threads.
+//!
+//! A single LRU cache behind one lock serializes every lookup of a concurrent reader,
+//! even cache hits, since an LRU hit updates the recency list. [`ShardedLruCache`]
+//! splits the capacity over several independently locked shards selected by the hash
+//! of the key, e.g. the offset of a block, so lookups of different blocks rarely
+//! contend for the same lock.
+//!
+//! A [`ZdbReader`](crate::ZdbReader) is used by one thread at a time, a server opens one
+//! reader per worker thread and lets them share decoded content blocks through a
+//! [`SharedBlockCache`](crate::readers::SharedBlockCache) set in
+//! [`ReaderOptions::shared_block_cache`](crate::readers::ReaderOptions::shared_block_cache).
+
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::num::NonZeroUsize;
+use std::sync::{Mutex, MutexGuard};
+
+use lru::LruCache;
+use serde::{Deserialize, Serialize};
+
+use crate::Result;
+
+/// Default number of shards of a block cache.
+pub const DEFAULT_BLOCK_CACHE_SHARDS: usize = 8;
+/// Default total number of blocks held by a block cache.
+pub const DEFAULT_BLOCK_CACHE_CAPACITY: usize = 64;
+
+/// Size of a sharded block cache.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub struct BlockCacheConfig {
+    /// Number of independently locked shards, more shards reduce lock contention (default: 8)
+    #[serde(default = "default_shard_count")]
+    pub shard_count: usize,
+    /// Total number of cached blocks, split evenly over the shards (default: 64)
+    #[serde(default = "default_capacity")]
+    pub capacity: usize,
+}
+
+fn default_shard_count() -> usize {
+    DEFAULT_BLOCK_CACHE_SHARDS
+}
+
+fn default_capacity() -> usize {
+    DEFAULT_BLOCK_CACHE_CAPACITY
+}
+
+impl Default for BlockCacheConfig {
+    fn default() -> Self {
+        BlockCacheConfig {
+            shard_count: DEFAULT_BLOCK_CACHE_SHARDS,
+            capacity: DEFAULT_BLOCK_CACHE_CAPACITY,
+        }
+    }
+}
+
+/// LRU cache split into shards with one lock each.
+///
+/// Recency is tracked per shard, so the cache as a whole is only approximately LRU.
+pub struct ShardedLruCache<K: Hash + Eq, V: Clone> {
+    shards: Vec<Mutex<LruCache<K, V>>>,
+}
+
+impl<K: Hash + Eq, V: Clone> std::fmt::Debug for ShardedLruCache<K, V> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ShardedLruCache").field("shard_count", &self.shards.len()).finish()
+    }
+}
+
+impl<K: Hash + Eq, V: Clone> ShardedLruCache<K, V> {
+    /// Creates a cache with the given number of shards and total capacity.
+    ///
+    /// Both values are raised to at least 1, and every shard holds at least one entry.
+    pub fn new(config: &BlockCacheConfig) -> Self {
+        let shard_count = config.shard_count.max(1);
+        let shard_capacity = NonZeroUsize::new(config.capacity.div_ceil(shard_count).max(1)).unwrap();
+        let shards = (0..shard_count).map(|_| Mutex::new(LruCache::new(shard_capacity))).collect();
+        Self { shards }
+    }
+
+    pub fn shard_count(&self) -> usize {
+        self.shards.len()
+    }
+
+    fn shard(&self, key: &K) -> MutexGuard<'_, LruCache<K, V>> {
+        let mut hasher = DefaultHasher::new();
+        key.hash(&mut hasher);
+        let shard = &self.shards[(hasher.finish() % self.shards.len() as u64) as usize];
+        // A panic while holding the lock can't leave the cache inconsistent, so ignore poisoning
+        shard.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub fn get(&self, key: &K) -> Option<V> {
+        self.shard(key).get(key).cloned()
+    }
+
+    pub fn put(&self, key: K, value: V) {
+        self.shard(&key).put(key, value);
+    }
+
+    /// Returns the cached value, or loads and caches it.
+    ///
+    /// The shard isn't locked while `load` runs, so a slow load doesn't block other
+    /// lookups of the shard. Two threads missing the same key may both load it.
+    ///
+    /// # Errors
+    ///
+    /// Returns the error of `load`, nothing is cached in that case.
+    pub fn get_or_try_insert_with<F: FnOnce() -> Result<V>>(&self, key: K, load: F) -> Result<V> {
+        if let Some(value) = self.get(&key) {
+            return Ok(value);
+        }
+        let value = load()?;
+        self.put(key, value.clone());
+        Ok(value)
+    }
+
+    /// Total number of cached entries over all shards.
+    pub fn len(&self) -> usize {
+        self.shards.iter().map(|shard| shard.lock().unwrap_or_else(|e| e.into_inner()).len()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn clear(&self) {
+        for shard in &self.shards {
+            shard.lock().unwrap_or_else(|e| e.into_inner()).clear();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_sharded_cache_concurrent_access() {
+        let cache = Arc::new(ShardedLruCache::<u64, Arc<Vec<u8>>>::new(&BlockCacheConfig { shard_count: 4, capacity: 1000 }));
+        let threads: Vec<_> = (0..4u64).map(|t| {
+            let cache = cache.clone();
+            std::thread::spawn(move || {
+                for offset in 0..200u64 {
+                    let block = cache.get_or_try_insert_with(offset, || Ok(Arc::new(vec![offset as u8; 16]))).unwrap();
+                    assert_eq!(block[0], offset as u8, "thread {}", t);
+                }
+            })
+        }).collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+        assert_eq!(cache.shard_count(), 4);
+        assert_eq!(cache.len(), 200);
+        cache.clear();
+        assert!(cache.is_empty());
+    }
+}
@@ -20,7 +20,9 @@ use std::io::Cursor;
 use byteorder::{LittleEndian, NativeEndian, ReadBytesExt, WriteBytesExt};
 
 use crate::utils::icu_wrapper::UChar;
+use crate::utils::key_normalization::normalize_query;
 use crate::storage::meta_unit::{MetaUnit, ZdbVersion};
+use crate::storage::reader_helper::decode_bytes_to_string;
 use crate::{Result, ZdbError};
 
 /// Checks if two bytes form a valid Big5 character.
@@ -127,7 +129,14 @@ pub fn wc_get_sort_key( wc_str: &[u8], fold_case: bool, alpha_and_digit_only: bo
 
 pub fn get_sort_key(key: &[u8], meta_info: &MetaUnit) -> Result<Vec<u8>> {
     if meta_info.version==ZdbVersion::V3{
-        let key_uchar = UChar::try_from(String::from_utf8_lossy(key).into_owned().as_str())?;
+        // Keys are stored in the encoding recorded in the header, utf-8 unless configured otherwise
+        let key_str = if meta_info.encoding_obj == encoding_rs::UTF_8 {
+            String::from_utf8_lossy(key).into_owned()
+        } else {
+            decode_bytes_to_string(key, meta_info.encoding_obj)?
+        };
+        let key_str = normalize_query(&key_str, &meta_info.db_info.key_normalization);
+        let key_uchar = UChar::try_from(key_str.as_ref())?;
         Ok(meta_info.collator.get_sort_key(&key_uchar))
     }else{
         let fold_case = !meta_info.db_info.key_case_sensitive || meta_info.db_info.is_mdd;
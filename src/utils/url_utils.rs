@@ -35,16 +35,37 @@ use crate::utils::io_utils::fix_windows_path;
 use crate::{Result, ZdbError};
 
 
+/// Decodes the file system path of a file URL.
+///
+/// On Unix the percent-decoded bytes are used as is, so paths that are not valid
+/// UTF-8 can be opened.
+#[cfg(unix)]
+pub fn get_decoded_path(url: &Url) -> Result<PathBuf> {
+    use std::os::unix::ffi::OsStringExt;
+    let bytes: Vec<u8> = percent_decode_str(&get_url_path_with_host(url)).collect();
+    Ok(PathBuf::from(std::ffi::OsString::from_vec(bytes)))
+}
+
+/// Decodes the file system path of a file URL.
+#[cfg(not(unix))]
 pub fn get_decoded_path(url: &Url) -> Result<PathBuf> {
     let path_str = get_decoded_path_str(url)?;
     Ok(PathBuf::from(path_str))
 }
 
 pub fn get_decoded_path_str(url: &Url) -> Result<String> {
-    let path = fix_windows_path(url.path());
+    let path = get_url_path_with_host(url);
     let decoded_path = percent_decode_str(&path)
         .decode_utf8()?;
-    Ok(decoded_path.to_string())
+    Ok(fix_windows_path(&decoded_path))
+}
+
+/// Returns the path of a URL, prefixed with `//host` for UNC URLs like `file://server/share/file.mdx`.
+fn get_url_path_with_host(url: &Url) -> String {
+    match url.host_str() {
+        Some(host) if !host.is_empty() && !host.eq_ignore_ascii_case("localhost") => format!("//{}{}", host, url.path()),
+        _ => url.path().to_string(),
+    }
 }
 
 
@@ -272,6 +293,20 @@ pub fn join_path(base_path: &str, second_path: &str) -> String {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::utils::io_utils::windows_extended_length_path;
+
+    #[test]
+    fn test_unc_and_long_paths() {
+        let url = Url::parse("file://server/share/my%20dict.mdx").unwrap();
+        assert_eq!(get_url_path_with_host(&url), "//server/share/my%20dict.mdx");
+        let url = Url::parse("file:///dict/a.mdx").unwrap();
+        assert_eq!(get_url_path_with_host(&url), "/dict/a.mdx");
+
+        assert_eq!(windows_extended_length_path("C:/dict/a.mdx"), r"\\?\C:\dict\a.mdx");
+        assert_eq!(windows_extended_length_path(r"\\server\share\a.mdx"), r"\\?\UNC\server\share\a.mdx");
+        assert_eq!(windows_extended_length_path(r"\\?\C:\a.mdx"), r"\\?\C:\a.mdx");
+        assert_eq!(windows_extended_length_path("dict/a.mdx"), r"dict\a.mdx");
+    }
 
     #[test]
     fn test_join_path() {
@@ -33,6 +33,7 @@ use serde_json::{Map, Value};
 
 use crate::storage::meta_unit::MetaUnit;
 use crate::storage::reader_helper;
+use super::key_normalization::normalize_query;
 use super::sort_key::get_sort_key;
 use crate::{Result, ZdbError};
 
@@ -168,6 +169,9 @@ pub fn sort_key_compare( first: &[u8], second: &[u8], start_with: bool) -> Resul
 }
 
 pub fn locale_compare(first: &str, second: &str, start_with: bool, meta_info: &MetaUnit) -> Result<Ordering> {
+    let first = normalize_query(first, &meta_info.db_info.key_normalization);
+    let second = normalize_query(second, &meta_info.db_info.key_normalization);
+    let (first, second) = (first.as_ref(), second.as_ref());
     let first = if start_with && first.len() > second.len() {
         let char_count = second.chars().count();
         let end = first.char_indices()
@@ -199,6 +203,20 @@ pub fn binary_search_first<T: KeyComparable+Clone, C:RandomAccessable<T>>(
     prefix_match: bool,
     partial_match: bool,
 ) -> Result<Option<T>> {
+    binary_search_first_position(container, key, meta_info, prefix_match, partial_match)?
+        .map(|position| container.get_item(position).cloned())
+        .transpose()
+}
+
+/// Finds the position of the first item matching `key`, like [`binary_search_first`]
+/// without cloning the item.
+pub fn binary_search_first_position<T: KeyComparable, C:RandomAccessable<T>>(
+    container: &C,
+    key: &str,
+    meta_info: &MetaUnit,
+    prefix_match: bool,
+    partial_match: bool,
+) -> Result<Option<usize>> {
     let mut search_key = key.to_string();
     let search_key_bytes = reader_helper::encode_string_to_bytes(&search_key, meta_info.encoding_obj)?;
     let mut search_sort_key = get_sort_key(&search_key_bytes, meta_info)?;
@@ -233,12 +251,12 @@ pub fn binary_search_first<T: KeyComparable+Clone, C:RandomAccessable<T>>(
                     _ => break,
                 }
             }
-            result = Some(container.get_item(leftmost_index)?.clone());
+            result = Some(leftmost_index);
         } else if partial_match {
             // If no match found and partial_match is enabled, try with a shorter key
             if search_key.len() > 0 {
                 search_key.pop();
-                search_sort_key = get_sort_key(&search_key.as_bytes(), meta_info)?;
+                search_sort_key = get_sort_key(&reader_helper::encode_string_to_bytes(&search_key, meta_info.encoding_obj)?, meta_info)?;
             } else {
                 break;
             }
@@ -0,0 +1,445 @@
+//! Building dictionaries: record sources, entry sinks, block layouts and build settings.
+
+// Building dictionaries needs compiled collation data
+#![cfg(any(feature = "icu", feature = "rust-icu"))]
+
+mod common;
+
+use std::fs::File;
+use std::io::{BufReader, Cursor};
+
+use mdx::builder::{preflight, BuildPhase, BuildWarning, BuilderConfig, ContentBlockLayout, DataLoader, SourceMetadata, SourceType, ZDBBuilder, ZdbRecord, RecordErrorPolicy};
+use mdx::crypto::encryption::EncryptionMethod;
+use mdx::crypto::DigestAlgorithm;
+use mdx::inspect::{dump, Verbosity};
+use mdx::storage::UnitType;
+use mdx::utils::compression::CompressionMethod;
+use mdx::utils::progress_report::ProgressState;
+use mdx::{ErrorCode, MdxReader, ZdbError, ZdbReader};
+use url::Url;
+
+use common::{build_in_memory, english_config, keyed_records, read_entries, resource_config, source_config, work_dir, RecordContentLoader};
+
+#[test]
+fn in_memory_round_trip() {
+    let records = keyed_records(&["cherry", "apple", "banana"], |key| format!("<b>{}</b>", key));
+    let data = build_in_memory(&english_config(), records);
+
+    let mut reader = ZdbReader::from_reader(Cursor::new(data), "", "").unwrap();
+    assert_eq!(reader.get_entry_count(), 3);
+    let key_index = reader.find_first_match("banana", false, false, true).unwrap().unwrap();
+    assert_eq!(reader.get_string(&key_index, false).unwrap(), "<b>banana</b>");
+}
+
+#[test]
+fn streamed_records() {
+    let config = english_config();
+    // Records generated on the fly in reverse key order, never collected by the caller
+    let records = (0..1000).rev().map(|i| ZdbRecord { key: format!("key{:04}", i), content: format!("<p>{}</p>", i), ..Default::default() });
+    let mut reader = ZdbReader::from_reader(Cursor::new(build_in_memory(&config, records)), "", "").unwrap();
+    assert_eq!(reader.get_entry_count(), 1000);
+    let key_index = reader.get_index(0).unwrap();
+    assert_eq!(key_index.key, "key0000");
+    assert_eq!(reader.get_string(&key_index, false).unwrap(), "<p>0</p>");
+
+    // A bad key stops reading the source
+    let mut consumed = 0;
+    let records = (0..1000).inspect(|_| consumed += 1)
+        .map(|i| ZdbRecord { key: if i == 10 { "k".repeat(300) } else { format!("key{}", i) }, ..Default::default() });
+    let result = ZDBBuilder::build_records_to_writer(&config, &mut Cursor::new(Vec::new()), RecordContentLoader, records, None);
+    assert!(matches!(result, Err(ZdbError::KeyTooLong { length: 300, .. })));
+    assert_eq!(consumed, 11);
+}
+
+#[test]
+fn union_entries_round_trip() {
+    let config = BuilderConfig { merge_duplicate_keys: true, ..english_config() };
+    let records: Vec<ZdbRecord> = [("run", "verb"), ("walk", "verb"), ("run", "noun"), ("run", "idiom")].iter()
+        .map(|(key, content)| ZdbRecord { key: key.to_string(), content: content.to_string(), ..Default::default() })
+        .collect();
+    let mut reader = ZdbReader::from_reader(Cursor::new(build_in_memory(&config, records)), "", "").unwrap();
+    assert_eq!(reader.get_entry_count(), 5);
+    let union_index = reader.find_first_match("run", false, false, true).unwrap().unwrap();
+    assert!(reader.is_union_entry(&union_index).unwrap());
+    let mut contents: Vec<String> = reader.expand_union(&union_index).unwrap().iter()
+        .map(|member| reader.get_string(member, false).unwrap())
+        .collect();
+    contents.sort();
+    assert_eq!(contents, ["idiom", "noun", "verb"]);
+
+    let walk_index = reader.find_first_match("walk", false, false, true).unwrap().unwrap();
+    assert!(!reader.is_union_entry(&walk_index).unwrap());
+    let expanded = reader.expand_union(&walk_index).unwrap();
+    assert_eq!(expanded.len(), 1);
+    assert_eq!(expanded[0].entry_no, walk_index.entry_no);
+}
+
+#[test]
+fn build_with_entry_sink() {
+    let dir = work_dir();
+    let mut config = source_config(&dir, "b\r\nsecond\r\n</>\r\na\r\nfirst\r\n</>\r\nc\r\nthird\r\n</>\r\n", "dict.mdx");
+
+    let mut written = Vec::new();
+    let report = ZDBBuilder::build_with_sink(&config, &mut |entry: &ZdbRecord, content: &[u8]| {
+        written.push((entry.key.clone(), content.to_vec()));
+        Ok(())
+    }, None).unwrap();
+    assert_eq!(report.entry_count, 3);
+    let mut reader = ZdbReader::<BufReader<File>>::from_file(&config.output_file, "", "").unwrap();
+    assert_eq!(written, read_entries(&mut reader));
+    assert_eq!(written[0], ("a".to_string(), b"first\r\n".to_vec()));
+
+    // An error of the sink aborts the build without writing the output
+    config.output_file = dir.join("aborted.mdx").to_string_lossy().to_string();
+    let result = ZDBBuilder::build_with_sink(&config, &mut |_: &ZdbRecord, _: &[u8]| Err(ZdbError::general_error("full disk")), None);
+    assert!(result.is_err());
+    assert!(!dir.join("aborted.mdx").exists());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn content_block_layouts() {
+    let records: Vec<ZdbRecord> = ["apple", "apply", "bank", "banner", "cat", "catalog"].iter()
+        .flat_map(|key| (0..20).map(move |i| ZdbRecord { key: format!("{}{:02}", key, i), content: format!("{}{:02} sense", key, i), ..Default::default() }))
+        .collect();
+    let build = |layout: ContentBlockLayout| {
+        let config = BuilderConfig { preferred_content_block_size: 300, content_block_layout: layout, ..english_config() };
+        let mut reader = ZdbReader::<Cursor<Vec<u8>>>::from_bytes(build_in_memory(&config, records.clone()), "", "").unwrap();
+        for entry_no in 0..reader.get_entry_count() {
+            let key_index = reader.get_index(entry_no as _).unwrap();
+            assert_eq!(reader.get_string(&key_index, false).unwrap(), format!("{} sense", key_index.key));
+        }
+        assert_eq!(reader.meta.db_info.content_block_layout, layout.to_header_value());
+        reader.content_block_indexes().len()
+    };
+    assert_eq!(build(ContentBlockLayout::Count(10)), 12);
+    assert!(build(ContentBlockLayout::Size) >= 5);
+    // Blocks end where the first three letters change: app, ban, cat
+    assert_eq!(build(ContentBlockLayout::KeyPrefix(3)), 3);
+}
+
+/// Fails to load the entry "bad", like a source file removed after it was listed.
+struct UnreadableLoader;
+
+impl DataLoader for UnreadableLoader {
+    fn load_data(&mut self, entry: &ZdbRecord) -> mdx::Result<Vec<u8>> {
+        match entry.key.as_str() {
+            "bad" => Err(ZdbError::invalid_data_format("unreadable")),
+            key => Ok(key.as_bytes().to_vec()),
+        }
+    }
+}
+
+#[test]
+fn preflight_checks() {
+    let dir = work_dir();
+    // An entry that can't be loaded fails the build before the content is written
+    let config = english_config();
+    let mut builder = ZDBBuilder::new(&config);
+    builder.entries = keyed_records(&["good", "bad"], str::to_string);
+    let error = preflight(&builder, &mut UnreadableLoader).unwrap_err().to_string();
+    assert!(error.contains("\"bad\" of the source can't be loaded"), "{}", error);
+
+    // The estimate is above the actual size, but in its range
+    let records: Vec<ZdbRecord> = (0..2000)
+        .map(|i| ZdbRecord { key: format!("word{:04}", i), content: format!("<p>entry {} of the dictionary</p>", i), ..Default::default() })
+        .collect();
+    let config = english_config();
+    let mut builder = ZDBBuilder::new(&config);
+    builder.entries = records.clone();
+    builder.output_path = Some(dir.join("estimated.mdx"));
+    let report = preflight(&builder, &mut RecordContentLoader).unwrap();
+    assert_eq!(report.sampled_entries, 64);
+    assert!(report.compression_ratio < 1.0);
+    assert!(report.available_space.is_some());
+    let actual = build_in_memory(&config, records).len() as u64;
+    assert!(report.estimated_output_size >= actual && report.estimated_output_size < actual * 5, "{} {}", report.estimated_output_size, actual);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+/// Phase, key and bytes of every report made while scanning a source.
+static SCAN_REPORTS: std::sync::Mutex<Vec<(String, String, u64, u64)>> = std::sync::Mutex::new(Vec::new());
+
+fn record_scan_report(state: &mut ProgressState) -> bool {
+    if !state.phase.is_empty() {
+        SCAN_REPORTS.lock().unwrap().push((state.phase.clone(), state.current_key.clone(), state.processed_bytes, state.total_bytes));
+    }
+    false
+}
+
+#[test]
+fn progress_for_every_record() {
+    let dir = work_dir();
+    let keys = ["zebra", "apple", "mango", "kiwi", "pear"];
+    let source: String = keys.iter().map(|key| format!("{}\r\nthe {}\r\n</>\r\n", key, key)).collect();
+    let config = BuilderConfig { progress_every_record: true, ..source_config(&dir, &source, "dict.mdx") };
+    ZDBBuilder::build_with_config(&config, Some(record_scan_report)).unwrap();
+
+    let reports = std::mem::take(&mut *SCAN_REPORTS.lock().unwrap());
+    let reported_keys: Vec<&str> = reports.iter().map(|(_, key, _, _)| key.as_str()).collect();
+    assert_eq!(reported_keys, keys);
+    assert!(reports.iter().all(|(phase, _, _, total)| phase == "Scanning source file" && *total == source.len() as u64));
+    assert!(reports.windows(2).all(|pair| pair[0].2 < pair[1].2));
+    assert_eq!(reports.last().unwrap().2, source.len() as u64);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn entry_limits() {
+    let long_key = "k".repeat(300);
+    let records = || vec![
+        ZdbRecord { key: long_key.clone(), content: "long key".to_string(), ..Default::default() },
+        ZdbRecord { key: "large".to_string(), content: "x".repeat(200), ..Default::default() },
+        ZdbRecord { key: "small".to_string(), content: "fits".to_string(), ..Default::default() },
+    ];
+    let mut config = english_config();
+    let result = ZDBBuilder::build_records_to_writer(&config, &mut Cursor::new(Vec::new()), RecordContentLoader, records(), None);
+    match result {
+        Err(ZdbError::KeyTooLong { key, length: 300, limit: 255, .. }) => assert!(long_key.starts_with(key.trim_end_matches("..."))),
+        other => panic!("unexpected result: {:?}", other.map(|_| ())),
+    }
+
+    config.max_key_length = 512;
+    config.max_content_size = 100;
+    let result = ZDBBuilder::build_records_to_writer(&config, &mut Cursor::new(Vec::new()), RecordContentLoader, records(), None);
+    assert!(matches!(result, Err(ZdbError::ContentTooLarge { ref key, size: 200, limit: 100, .. }) if key == "large"));
+    config.record_error_policy = RecordErrorPolicy::Skip;
+    let report = ZDBBuilder::build_records_to_writer(&config, &mut Cursor::new(Vec::new()), RecordContentLoader, records(), None).unwrap();
+    assert_eq!(report.skipped_keys(), ["large"]);
+    config.max_content_size = u32::MAX as u64;
+    assert!(config.validate().unwrap_err().iter().any(|problem| problem.starts_with("max_content_size")));
+}
+
+#[test]
+fn cross_reference_resolution() {
+    let dir = work_dir();
+    let source = concat!(
+        "run\r\n<a href=\"entry://Walk#sense\">walk</a> <a href=\"entry://fly\">fly</a> <a href=\"entry://#top\">top</a>\r\n</>\r\n",
+        "walk\r\n<a href=\"entry://run\">run</a>\r\n</>\r\n",
+    );
+    let config = BuilderConfig {
+        // Case-insensitive, like the lookups of the reader, so `Walk` resolves to `walk`
+        default_sorting_locale: "en-u-ks-level2".to_string(),
+        resolve_cross_references: true,
+        ..source_config(&dir, source, "links.mdx")
+    };
+    let report = ZDBBuilder::build_with_config(&config, None).unwrap();
+    assert_eq!(report.warnings, [BuildWarning::BrokenCrossReference { key: "run".to_string(), target: "fly".to_string() }]);
+
+    let mut reader = MdxReader::from_url(&Url::from_file_path(&config.output_file).unwrap(), "").unwrap();
+    let run = reader.get_index(0).unwrap();
+    assert_eq!(reader.get_html(&run).unwrap(),
+        "<a href=\"entryx://1#sense\">walk</a> <a href=\"entry://fly\">fly</a> <a href=\"entry://#top\">top</a>\r\n");
+    let walk = reader.get_index(1).unwrap();
+    assert_eq!(reader.get_html(&walk).unwrap(), "<a href=\"entryx://0\">run</a>\r\n");
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+/// A source format outside the crate, entries as `key=content` lines.
+struct KeyValueLoader {
+    lines: Vec<String>,
+}
+
+impl DataLoader for KeyValueLoader {
+    fn metadata(&mut self) -> SourceMetadata {
+        SourceMetadata {
+            locale_id: "en".to_string(),
+            labels: [("n.".to_string(), "noun".to_string())].into(),
+            warnings: vec![BuildWarning::EmptyContent { key: "ignored".to_string() }],
+            ..Default::default()
+        }
+    }
+
+    fn records(&mut self) -> Box<dyn Iterator<Item = mdx::Result<ZdbRecord>> + '_> {
+        Box::new(self.lines.iter().enumerate().map(|(position, line)| {
+            let (key, content) = line.split_once('=').ok_or_else(|| ZdbError::invalid_data_format(format!("No '=' in line {}", position + 1)))?;
+            Ok(ZdbRecord { key: key.to_string(), position: position as u64, content_len: content.len() as u64, ..Default::default() })
+        }))
+    }
+
+    fn load_data(&mut self, entry: &ZdbRecord) -> mdx::Result<Vec<u8>> {
+        let (_, content) = self.lines[entry.position as usize].split_once('=').unwrap();
+        Ok(content.as_bytes().to_vec())
+    }
+}
+
+#[test]
+fn boxed_custom_loader() {
+    let dir = work_dir();
+    let config = BuilderConfig {
+        output_file: dir.join("custom.mdx").to_string_lossy().to_string(),
+        // The locale of the source is used since the config has none
+        default_sorting_locale: String::new(),
+        ..Default::default()
+    };
+    let lines = ["pear=<p>a fruit</p>", "apple=<p>n. a fruit</p>"].map(str::to_string).to_vec();
+    let report = ZDBBuilder::build(&config, Box::new(KeyValueLoader { lines }), None).unwrap();
+    assert_eq!(report.entry_count, 2);
+    assert!(report.warnings.contains(&BuildWarning::EmptyContent { key: "ignored".to_string() }));
+
+    let mut reader = ZdbReader::<BufReader<File>>::from_file(&config.output_file, "", "").unwrap();
+    assert_eq!(reader.meta.db_info.locale_id, "en");
+    assert_eq!(reader.meta.db_info.labels.get("n."), Some(&"noun".to_string()));
+    let key_index = reader.get_index(0).unwrap();
+    assert_eq!(key_index.key, "apple");
+    assert_eq!(reader.get_string(&key_index, false).unwrap(), "<p>n. a fruit</p>");
+
+    let lines = vec!["broken".to_string()];
+    let error = ZDBBuilder::build(&config, Box::new(KeyValueLoader { lines }), None).unwrap_err();
+    assert_eq!(error.code(), ErrorCode::InvalidDataFormat);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn parallel_directory_build() {
+    let dir = work_dir();
+    let resource_dir = dir.join("resources");
+    for i in 0..300 {
+        let sub_dir = resource_dir.join(format!("d{}", i % 7));
+        std::fs::create_dir_all(&sub_dir).unwrap();
+        std::fs::write(sub_dir.join(format!("f{:03}.bin", i)), format!("content {}", i).repeat(i % 5 + 1)).unwrap();
+    }
+    let mut entries = Vec::new();
+    for io_threads in [1, 4] {
+        let config = BuilderConfig { io_threads, ..resource_config(&resource_dir, &dir.join(format!("{}.mdd", io_threads))) };
+        ZDBBuilder::build_with_config(&config, None).unwrap();
+        let mut reader = ZdbReader::<BufReader<File>>::from_file(&config.output_file, "", "").unwrap();
+        entries.push(read_entries(&mut reader));
+    }
+    assert_eq!(entries[0].len(), 300);
+    assert_eq!(entries[0], entries[1]);
+    assert!(entries[0].iter().any(|(key, data)| key == "/d3/f010.bin" && data == b"content 10"));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn build_warnings() {
+    let dir = work_dir();
+    // "empty" has no content and the truncated last entry lacks its end marker
+    let config = BuilderConfig {
+        data_source_format: SourceType::MdictHtml,
+        content_type: "Text".to_string(),
+        ..source_config(&dir, "apple\r\nred\r\n</>\r\nempty\r\n</>\r\nzebra\r\nstripes\r\n", "warnings.mdx")
+    };
+    let report = ZDBBuilder::build_with_config(&config, None).unwrap();
+
+    assert_eq!(report.entry_count, 3);
+    assert_eq!(report.warnings, vec![
+        BuildWarning::MissingEntryTerminator { key: "zebra".to_string(), line_no: 7 },
+        BuildWarning::EmptyContent { key: "empty".to_string() },
+    ]);
+    assert_eq!(report.warnings[0].phase(), BuildPhase::Loading);
+    assert_eq!(report.warnings[1].phase(), BuildPhase::Content);
+    assert_eq!(report.warnings[1].key(), Some("empty"));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn builder_tuning() {
+    let dir = work_dir();
+    let source: String = (0..200).map(|n| format!("word{}\r\n<p>entry {} of the tuning test, {}</p>\r\n</>\r\n", n, n, n * 7919 % 1000)).collect();
+    let temp_dir = dir.join("temp");
+    std::fs::create_dir_all(&temp_dir).unwrap();
+    let config = BuilderConfig {
+        compression_levels: [(CompressionMethod::Lzma, 5), (CompressionMethod::Deflate, 10)].into_iter().collect(),
+        write_buffer_size: 0,
+        ..source_config(&dir, &source, "dict.mdx")
+    };
+    let problems = config.validate().unwrap_err();
+    assert!(problems.iter().any(|problem| problem.contains("Lzma has no levels")), "{:?}", problems);
+    assert!(problems.iter().any(|problem| problem.contains("level 10 of Deflate")), "{:?}", problems);
+    assert!(problems.iter().any(|problem| problem.starts_with("write_buffer_size")), "{:?}", problems);
+
+    let build = |level: u32, encrypt_keys: bool, name: &str| {
+        let config = BuilderConfig {
+            compression_levels: [(CompressionMethod::Deflate, level)].into_iter().collect(),
+            encrypt_key_units: encrypt_keys,
+            encrypt_content_units: !encrypt_keys,
+            write_buffer_size: 1024 * 1024,
+            temp_dir: temp_dir.to_string_lossy().to_string(),
+            output_file: dir.join(name).to_string_lossy().to_string(),
+            ..config.clone()
+        };
+        ZDBBuilder::build_with_config(&config, None).unwrap();
+        config.output_file
+    };
+    let stored = build(0, true, "stored.mdx");
+    let compressed = build(9, false, "compressed.mdx");
+    assert_eq!(std::fs::read_dir(&temp_dir).unwrap().count(), 0);
+    assert!(std::fs::metadata(&stored).unwrap().len() > std::fs::metadata(&compressed).unwrap().len());
+
+    let mut reader = ZdbReader::<BufReader<File>>::from_file(&stored, "", "").unwrap();
+    assert_eq!(reader.debug_block(0).unwrap().encryption, EncryptionMethod::None);
+    assert_eq!(reader.get_data_by_key("word62").unwrap().unwrap(), b"<p>entry 62 of the tuning test, 978</p>\r\n");
+    let mut reader = ZdbReader::<BufReader<File>>::from_file(&compressed, "", "").unwrap();
+    assert_eq!(reader.debug_block(0).unwrap().encryption, EncryptionMethod::Salsa20);
+    assert_eq!(reader.get_entry_count(), 200);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn per_unit_methods() {
+    let dir = work_dir();
+    let source_path = dir.join("source.txt");
+    let source: String = (0..300).map(|n| format!("word{}\r\n<p>entry {} with per-unit methods, {}</p>\r\n</>\r\n", n, n, n * 7919 % 1000)).collect();
+    std::fs::write(&source_path, source).unwrap();
+    let output_file = dir.join("methods.mdx");
+    let config = BuilderConfig::from_json(&format!(r#"{{"input_path": {:?}, "output_file": {:?}, "default_sorting_locale": "en",
+        "preferred_key_block_size": 512, "unit_methods": {{"Key": {{"compression_method": "Lz4"}}, "Content": {{"compression_method": "Lzma"}},
+        "KeyBlockIndex": {{"encryption_method": "None"}}, "ContentBlockIndex": {{"encryption_method": 0}}}}}}"#,
+        source_path.to_string_lossy(), output_file.to_string_lossy())).unwrap();
+    ZDBBuilder::build_with_config(&config, None).unwrap();
+
+    let file_dump = dump(&output_file, Verbosity::Blocks).unwrap();
+    let methods = |unit_type: UnitType| {
+        let unit = file_dump.units.iter().find(|unit| unit.unit_type == unit_type).unwrap();
+        assert!(!unit.blocks.is_empty());
+        let mut methods: Vec<_> = unit.blocks.iter().map(|block| (block.compression, block.encryption)).collect();
+        methods.dedup();
+        methods
+    };
+    assert_eq!(methods(UnitType::Key), [(CompressionMethod::Lz4, EncryptionMethod::Salsa20)]);
+    assert_eq!(methods(UnitType::Content), [(CompressionMethod::Lzma, EncryptionMethod::Salsa20)]);
+    assert_eq!(methods(UnitType::KeyBlockIndex), [(CompressionMethod::Deflate, EncryptionMethod::None)]);
+    assert_eq!(methods(UnitType::ContentBlockIndex), [(CompressionMethod::Deflate, EncryptionMethod::None)]);
+
+    let mut reader = ZdbReader::<BufReader<File>>::from_file(&output_file, "", "").unwrap();
+    assert_eq!(reader.get_entry_count(), 300);
+    assert_eq!(reader.get_data_by_key("word250").unwrap().unwrap(), b"<p>entry 250 with per-unit methods, 750</p>\r\n");
+
+    let mut invalid = config.clone();
+    invalid.unit_methods.insert(UnitType::Invalid, Default::default());
+    assert!(invalid.validate().unwrap_err().iter().any(|problem| problem.starts_with("unit_methods")));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn content_dedup() {
+    // Every third resource is a copy of the same icon
+    let icon = "<svg>".repeat(2000);
+    let records = || (0..60)
+        .map(|i| ZdbRecord {
+            key: format!("/img/{:02}.svg", i),
+            content: if i % 3 == 0 { icon.clone() } else { format!("<svg id=\"{}\"/>", i) },
+            ..Default::default()
+        })
+        .collect::<Vec<_>>();
+    let config = BuilderConfig { compression_method: CompressionMethod::None, ..english_config() };
+    let plain = build_in_memory(&config, records());
+    let mut algorithms = vec![DigestAlgorithm::FastHash];
+    if cfg!(feature = "blake3") {
+        algorithms.push(DigestAlgorithm::Blake3);
+    }
+    for algorithm in algorithms {
+        let config = BuilderConfig { content_digest: Some(algorithm), key_digest: algorithm, ..config.clone() };
+        let data = build_in_memory(&config, records());
+        assert!(data.len() + icon.len() * 18 < plain.len(), "{:?}: {} {}", algorithm, data.len(), plain.len());
+        let entries = read_entries(&mut ZdbReader::from_reader(Cursor::new(data), "", "").unwrap());
+        let expected: Vec<(String, Vec<u8>)> = records().into_iter().map(|record| (record.key, record.content.into_bytes())).collect();
+        assert_eq!(entries, expected, "{:?}", algorithm);
+    }
+
+    let config = BuilderConfig { content_digest: Some(DigestAlgorithm::Blake3), ..english_config() };
+    let problems = config.validate().unwrap_err();
+    assert_eq!(problems.iter().any(|problem| problem.starts_with("content_digest")), !cfg!(feature = "blake3"));
+}
@@ -0,0 +1,90 @@
+//! Setup shared by the integration tests.
+
+// Every test binary includes this module but uses only part of it
+#![allow(dead_code)]
+
+use std::fs::File;
+use std::io::{Cursor, Read, Seek};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use mdx::builder::{BuilderConfig, DataLoader, SourceType, ZDBBuilder, ZdbRecord};
+use mdx::ZdbReader;
+
+static CASE_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// Creates an empty directory for one test case, removed by the test when it passes.
+pub fn work_dir() -> PathBuf {
+    let case_no = CASE_COUNTER.fetch_add(1, Ordering::SeqCst);
+    let dir = std::env::temp_dir().join(format!("mdx_test_{}_{}", std::process::id(), case_no));
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// Loads the content stored in the records themselves.
+pub struct RecordContentLoader;
+
+impl DataLoader for RecordContentLoader {
+    fn load_data(&mut self, entry: &ZdbRecord) -> mdx::Result<Vec<u8>> {
+        Ok(entry.content.as_bytes().to_vec())
+    }
+}
+
+/// Default config with the keys sorted for English.
+pub fn english_config() -> BuilderConfig {
+    BuilderConfig { default_sorting_locale: "en".to_string(), ..Default::default() }
+}
+
+/// Writes MDict source text to `source.txt` in `dir` and returns a config building it
+/// to `output_name` in `dir`, sorted for English.
+pub fn source_config(dir: &Path, source: &str, output_name: &str) -> BuilderConfig {
+    let source_path = dir.join("source.txt");
+    std::fs::write(&source_path, source).unwrap();
+    BuilderConfig {
+        input_path: source_path.to_string_lossy().to_string(),
+        output_file: dir.join(output_name).to_string_lossy().to_string(),
+        ..english_config()
+    }
+}
+
+/// Returns a config building the files of `resource_dir` as binary entries to `output`, sorted for English.
+pub fn resource_config(resource_dir: &Path, output: &Path) -> BuilderConfig {
+    BuilderConfig {
+        input_path: resource_dir.to_string_lossy().to_string(),
+        output_file: output.to_string_lossy().to_string(),
+        data_source_format: SourceType::Directory,
+        content_type: "Binary".to_string(),
+        ..english_config()
+    }
+}
+
+/// Records with the given keys, the content of each made from its key.
+pub fn keyed_records(keys: &[&str], content: impl Fn(&str) -> String) -> Vec<ZdbRecord> {
+    keys.iter()
+        .map(|key| ZdbRecord { key: key.to_string(), content: content(key), ..Default::default() })
+        .collect()
+}
+
+/// Builds the records into a dictionary held in memory.
+pub fn build_in_memory(config: &BuilderConfig, records: impl IntoIterator<Item = ZdbRecord>) -> Vec<u8> {
+    let mut writer = Cursor::new(Vec::new());
+    ZDBBuilder::build_records_to_writer(config, &mut writer, RecordContentLoader, records, None).unwrap();
+    writer.into_inner()
+}
+
+/// Builds the records into the dictionary file at `path`.
+pub fn build_file(config: &BuilderConfig, path: &Path, records: impl IntoIterator<Item = ZdbRecord>) {
+    let mut writer = File::create(path).unwrap();
+    ZDBBuilder::build_records_to_writer(config, &mut writer, RecordContentLoader, records, None).unwrap();
+}
+
+/// Key and data of every entry, in entry order.
+pub fn read_entries<R: Read + Seek>(reader: &mut ZdbReader<R>) -> Vec<(String, Vec<u8>)> {
+    (0..reader.get_entry_count())
+        .map(|entry_no| {
+            let key_index = reader.get_index(entry_no as _).unwrap();
+            let data = reader.get_data(&key_index, false).unwrap();
+            (key_index.key, data)
+        })
+        .collect()
+}
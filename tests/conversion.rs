@@ -0,0 +1,175 @@
+//! Converting and re-sorting existing dictionaries.
+
+// Building dictionaries needs compiled collation data
+#![cfg(any(feature = "icu", feature = "rust-icu"))]
+
+mod common;
+
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+
+use mdx::builder::{BuilderConfig, RecordErrorPolicy, SourceType, ZDBBuilder, ZdbRecord};
+use mdx::crypto::encryption::EncryptionMethod;
+use mdx::utils::compression::CompressionMethod;
+use mdx::{MdxReader, ZdbReader};
+use url::Url;
+
+use common::{build_file, english_config, keyed_records, work_dir};
+
+/// Returns a config converting the dictionary at `source_path` to `output`, sorted for English.
+fn conversion_config(source_path: &Path, output: &Path) -> BuilderConfig {
+    BuilderConfig {
+        input_path: source_path.to_string_lossy().to_string(),
+        output_file: output.to_string_lossy().to_string(),
+        data_source_format: SourceType::Zdb,
+        ..english_config()
+    }
+}
+
+#[test]
+fn record_error_policies() {
+    let dir = work_dir();
+    let source_path = dir.join("source.mdx");
+    let records: Vec<ZdbRecord> = (0..100)
+        .map(|i| ZdbRecord { key: format!("word{:03}", i), content: format!("entry {} of the source", i), ..Default::default() })
+        .collect();
+    build_file(&BuilderConfig { preferred_content_block_size: 256, ..english_config() }, &source_path, records);
+
+    // Corrupt the second content block
+    let mut reader = ZdbReader::<BufReader<File>>::from_file(&source_path, "", "").unwrap();
+    let block_index = reader.content_block_indexes()[1].clone();
+    let (block_offset, stored_block) = reader.read_stored_content_block(&block_index).unwrap();
+    let key_indexes: Vec<_> = (0..reader.get_entry_count())
+        .map(|entry_no| reader.get_index(entry_no as _).unwrap())
+        .collect();
+    let broken: Vec<String> = key_indexes.into_iter()
+        .filter(|key_index| reader.content_block_indexes().iter().position(|block| block.block_offset_in_source + block.block_original_length > key_index.content_offset_in_source) == Some(1))
+        .map(|key_index| key_index.key)
+        .collect();
+    drop(reader);
+    let mut data = std::fs::read(&source_path).unwrap();
+    for byte in &mut data[block_offset as usize + 16..block_offset as usize + stored_block.len()] {
+        *byte ^= 0x5a;
+    }
+    std::fs::write(&source_path, data).unwrap();
+
+    let convert = |record_error_policy: RecordErrorPolicy| {
+        let config = BuilderConfig {
+            // A different compression method keeps the blocks from being copied as stored
+            compression_method: CompressionMethod::Lz4,
+            record_error_policy,
+            ..conversion_config(&source_path, &dir.join("converted.mdx"))
+        };
+        ZDBBuilder::build_with_config(&config, None)
+            .map(|report| (report, MdxReader::from_url(&Url::from_file_path(&config.output_file).unwrap(), "").unwrap()))
+    };
+    assert!(convert(RecordErrorPolicy::Fail).is_err());
+
+    let (report, mut reader) = convert(RecordErrorPolicy::Skip).unwrap();
+    assert!(!broken.is_empty());
+    assert_eq!(report.skipped_keys(), broken);
+    assert_eq!(reader.get_entry_count(), 100 - broken.len() as u64);
+    assert!(reader.find_index(&broken[0], false, false, true).unwrap().is_none());
+    let key_index = reader.find_index("word099", false, false, true).unwrap().unwrap();
+    assert_eq!(reader.get_string(&key_index, false).unwrap(), "entry 99 of the source");
+
+    let (report, mut reader) = convert(RecordErrorPolicy::Placeholder("<p>missing</p>".to_string())).unwrap();
+    assert_eq!(report.skipped_keys(), broken);
+    assert_eq!(reader.get_entry_count(), 100);
+    let key_index = reader.find_index(&broken[0], false, false, true).unwrap().unwrap();
+    assert_eq!(reader.get_string(&key_index, false).unwrap(), "<p>missing</p>");
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn zdb_conversion_copies_content_blocks() {
+    let dir = work_dir();
+    let records: Vec<ZdbRecord> = (0..500)
+        .map(|i| ZdbRecord { key: format!("key{:04}", i), content: format!("content of entry {} ", i).repeat(10), ..Default::default() })
+        .collect();
+    let source_path = dir.join("source.mdx");
+    build_file(&BuilderConfig { preferred_content_block_size: 1024, ..english_config() }, &source_path, records.clone());
+    let source_blocks = ZdbReader::<BufReader<File>>::from_file(&source_path, "", "").unwrap().content_block_indexes().len();
+
+    let convert = |config: &BuilderConfig| {
+        ZDBBuilder::build_with_config(config, None).unwrap();
+        let mut reader = ZdbReader::<BufReader<File>>::from_file(&config.output_file, "", "").unwrap();
+        for (entry_no, record) in records.iter().enumerate() {
+            let key_index = reader.get_index(entry_no as _).unwrap();
+            assert_eq!(key_index.key, record.key);
+            assert_eq!(reader.get_string(&key_index, false).unwrap(), record.content);
+        }
+        reader.content_block_indexes().len()
+    };
+    // Blocks are copied with the boundaries of the source, encrypted with the key of the new file or not at all
+    let mut config = BuilderConfig { content_type: "Html".to_string(), ..conversion_config(&source_path, &dir.join("copied.mdx")) };
+    assert_eq!(convert(&config), source_blocks);
+    config.encryption_method = EncryptionMethod::None;
+    config.output_file = dir.join("unencrypted.mdx").to_string_lossy().to_string();
+    assert_eq!(convert(&config), source_blocks);
+    // Another compression method requires rebuilding the blocks
+    config.compression_method = CompressionMethod::Lz4;
+    config.output_file = dir.join("recompressed.mdx").to_string_lossy().to_string();
+    assert!(convert(&config) < source_blocks);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn resort_with_another_locale() {
+    let dir = work_dir();
+    let records = keyed_records(&["zebra", "öl", "oak", "apple", "apple"], |key| format!("<b>{}</b>", key));
+    let path = dir.join("dict.mdx");
+    let meta_path = dir.join("meta.tsv");
+    std::fs::write(&meta_path, "oak\t120\tnoun\ttree\n").unwrap();
+    let config = BuilderConfig {
+        merge_duplicate_keys: true,
+        encryption_method: EncryptionMethod::None,
+        bloom_filter: true,
+        preview_skip_classes: vec!["ex".to_string()],
+        entry_meta_path: meta_path.to_string_lossy().into_owned(),
+        ..english_config()
+    };
+    build_file(&config, &path, records);
+    let read_all = |path: &Path| {
+        let mut reader = ZdbReader::<BufReader<File>>::from_file(path, "", "").unwrap();
+        let entries = (0..reader.get_entry_count()).map(|entry_no| {
+            let key_index = reader.get_index(entry_no as _).unwrap();
+            let content = reader.get_string(&key_index, false).unwrap();
+            (key_index.key, content)
+        }).collect::<Vec<_>>();
+        (reader.meta.db_info.locale_id.clone(), entries)
+    };
+    let (_, original) = read_all(&path);
+    assert_eq!(original.iter().map(|(key, _)| key.as_str()).collect::<Vec<_>>(), ["apple", "apple", "apple", "oak", "öl", "zebra"]);
+
+    // Swedish sorts ö after z, the union entry of the duplicates is kept
+    let swedish_path = dir.join("swedish.mdx");
+    let report = ZDBBuilder::resort(&path.to_string_lossy(), &swedish_path.to_string_lossy(), "sv", None).unwrap();
+    assert_eq!(report.entry_count, 6);
+    let (locale_id, swedish) = read_all(&swedish_path);
+    assert_eq!(locale_id, "sv");
+    assert_eq!(swedish.iter().map(|(key, _)| key.as_str()).collect::<Vec<_>>(), ["apple", "apple", "apple", "oak", "zebra", "öl"]);
+    assert_eq!(swedish[0].1, original[0].1);
+    assert!(swedish[1..].iter().all(|(key, content)| *content == format!("<b>{}</b>", key)));
+
+    // The settings of the source are kept and its content blocks are copied as stored
+    let mut source = ZdbReader::<BufReader<File>>::from_file(&path, "", "").unwrap();
+    let mut reader = ZdbReader::<BufReader<File>>::from_file(&swedish_path, "", "").unwrap();
+    let copied_block = reader.debug_block(0).unwrap();
+    assert_eq!(copied_block.encryption, EncryptionMethod::None);
+    assert_eq!(copied_block.compressed_data, source.debug_block(0).unwrap().compressed_data);
+    assert_eq!(reader.meta.db_info.preview_skip_classes, ["ex"]);
+    assert!(reader.has_bloom_filter() && reader.may_contain("öl").unwrap());
+    let oak = reader.find_first_match("oak", false, false, true).unwrap().unwrap();
+    assert_eq!(reader.get_entry_meta_ext(oak.entry_no).unwrap().map(|meta| meta.frequency_rank), Some(120));
+    assert_eq!(reader.entry_meta_tag_names(), ["tree"]);
+    let sizes: Vec<u64> = (0..reader.get_entry_count()).map(|entry_no| reader.get_content_length(entry_no as _).unwrap()).collect();
+    assert_eq!(sizes[3..], [10, 12, 10]);
+
+    // Re-sorting in place
+    ZDBBuilder::resort(&swedish_path.to_string_lossy(), &swedish_path.to_string_lossy(), "en", None).unwrap();
+    assert_eq!(read_all(&swedish_path), ("en".to_string(), original));
+    assert!(ZDBBuilder::resort(&path.to_string_lossy(), &swedish_path.to_string_lossy(), "not a locale!", None).is_err());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
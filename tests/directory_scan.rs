@@ -0,0 +1,73 @@
+//! Selection of the files of directory sources.
+
+// Building dictionaries needs compiled collation data
+#![cfg(any(feature = "icu", feature = "rust-icu"))]
+
+mod common;
+
+use std::fs::File;
+use std::io::BufReader;
+use std::path::PathBuf;
+
+use mdx::builder::{BuilderConfig, DirScanConfig, SourceType, ZDBBuilder};
+use mdx::ZdbReader;
+
+use common::work_dir;
+
+#[test]
+fn directory_scan_selection() {
+    let dir = work_dir();
+    let resource_dir = dir.join("resources");
+    std::fs::create_dir_all(resource_dir.join("img")).unwrap();
+    std::fs::create_dir_all(resource_dir.join(".git")).unwrap();
+    std::fs::write(resource_dir.join("img/cat.png"), "cat").unwrap();
+    std::fs::write(resource_dir.join("img/cat.psd"), "layers").unwrap();
+    std::fs::write(resource_dir.join("style.css"), "css").unwrap();
+    std::fs::write(resource_dir.join("notes.txt"), "notes").unwrap();
+    std::fs::write(resource_dir.join(".DS_Store"), "junk").unwrap();
+    std::fs::write(resource_dir.join(".git/HEAD"), "ref").unwrap();
+    let outside_dir = dir.join("outside");
+    std::fs::create_dir_all(&outside_dir).unwrap();
+    std::fs::write(outside_dir.join("linked.css"), "linked").unwrap();
+    #[cfg(unix)]
+    std::os::unix::fs::symlink(&outside_dir, resource_dir.join("shared")).unwrap();
+
+    let build = |dir_scan: DirScanConfig, name: &str| {
+        let config = BuilderConfig {
+            default_sorting_locale: "en".to_string(),
+            input_path: resource_dir.to_string_lossy().to_string(),
+            output_file: dir.join(name).to_string_lossy().to_string(),
+            data_source_format: SourceType::Directory,
+            content_type: "Binary".to_string(),
+            dir_scan,
+            ..Default::default()
+        };
+        ZDBBuilder::build_with_config(&config, None).unwrap();
+        let mut reader = ZdbReader::<BufReader<File>>::from_file(PathBuf::from(&config.output_file), "", "").unwrap();
+        let mut keys = (0..reader.get_entry_count())
+            .map(|entry_no| reader.get_index(entry_no as _).unwrap().key)
+            .collect::<Vec<_>>();
+        keys.sort();
+        keys
+    };
+
+    let all = build(DirScanConfig::default(), "all.mdd");
+    assert!(all.contains(&"/.DS_Store".to_string()));
+    assert!(all.contains(&"/.git/HEAD".to_string()));
+    #[cfg(unix)]
+    assert!(all.contains(&"/shared/linked.css".to_string()));
+
+    let selected = build(DirScanConfig {
+        follow_symlinks: false,
+        skip_hidden: true,
+        include: vec!["img/**".to_string(), "*.css".to_string()],
+        exclude: vec!["**/*.psd".to_string()],
+    }, "selected.mdd");
+    assert_eq!(selected, vec!["/img/cat.png".to_string(), "/style.css".to_string()]);
+
+    let mut invalid = BuilderConfig::default();
+    invalid.dir_scan.include = vec!["img/[".to_string()];
+    let problems = invalid.validate().unwrap_err();
+    assert!(problems.iter().any(|problem| problem.starts_with("dir_scan.include")));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,158 @@
+//! Rendering entry content: label expansion, dark mode, previews, compact stylesheets
+//! and binary entries.
+
+// Building dictionaries needs compiled collation data
+#![cfg(any(feature = "icu", feature = "rust-icu"))]
+
+mod common;
+
+use std::fs::File;
+use std::io::BufReader;
+
+use mdx::builder::{BuilderConfig, SourceType, ZDBBuilder};
+use mdx::utils::{DarkMode, PreviewFormat, PreviewOptions};
+use mdx::{ErrorCode, MdxReader, ZdbError, ZdbReader};
+use url::Url;
+
+use common::{resource_config, source_config, work_dir};
+
+#[test]
+fn label_expansion() {
+    let dir = work_dir();
+    let labels_path = dir.join("labels.tsv");
+    std::fs::write(&labels_path, "# label\texpansion\nn.\tnoun\nv.\tverb\n〔方〕\tdialect \"regional\"\n").unwrap();
+    let source = "run\r\n<i>v.</i> to move fast; <i>n.</i> a race, 〔方〕 a trip\r\n</>\r\n";
+    let mut config = BuilderConfig { labels_path: labels_path.to_string_lossy().to_string(), ..source_config(&dir, source, "labels.mdx") };
+    ZDBBuilder::build_with_config(&config, None).unwrap();
+
+    let mut reader = MdxReader::from_url(&Url::from_file_path(&config.output_file).unwrap(), "").unwrap();
+    assert_eq!(reader.labels().get("n.").map(String::as_str), Some("noun"));
+    let key_index = reader.get_index(0).unwrap();
+    assert_eq!(reader.get_html(&key_index).unwrap(), "<i>v.</i> to move fast; <i>n.</i> a race, 〔方〕 a trip\r\n");
+    reader.set_expand_labels(true);
+    assert_eq!(reader.get_html(&key_index).unwrap(), concat!(
+        r#"<i><abbr title="verb">v.</abbr></i> to move fast; <i><abbr title="noun">n.</abbr></i> a race, "#,
+        r#"<abbr title="dialect &quot;regional&quot;">〔方〕</abbr> a trip"#, "\r\n"));
+    reader.set_dark_mode(Some(DarkMode::Stylesheet("body{color:#ddd}".to_string())));
+    let html = reader.get_html(&key_index).unwrap();
+    assert!(html.starts_with(r#"<style>body{color:#ddd}</style><i><abbr title="verb">"#), "{}", html);
+    reader.set_dark_mode(None);
+    assert!(reader.get_html(&key_index).unwrap().starts_with("<i><abbr"));
+
+    // Converting the dictionary carries the labels over
+    config.input_path = config.output_file.clone();
+    config.output_file = dir.join("converted.mdx").to_string_lossy().to_string();
+    config.labels_path = String::new();
+    config.data_source_format = SourceType::Zdb;
+    ZDBBuilder::build_with_config(&config, None).unwrap();
+    let reader = MdxReader::from_url(&Url::from_file_path(&config.output_file).unwrap(), "").unwrap();
+    assert_eq!(reader.labels().len(), 3);
+
+    std::fs::write(&labels_path, "n.\n").unwrap();
+    config.labels_path = labels_path.to_string_lossy().to_string();
+    assert!(matches!(ZDBBuilder::build_with_config(&config, None), Err(ZdbError::InvalidDataFormat { ref message, .. }) if message.contains("line 1")));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn entry_preview() {
+    let dir = work_dir();
+    let source = concat!("run\r\n<div class=\"def\">To move <b>fast</b>. <div class=\"ex\">She runs daily.</div>",
+        "To flow. To manage.</div>\r\n</>\r\n");
+    let mut config = BuilderConfig { preview_skip_classes: vec!["ex".to_string()], ..source_config(&dir, source, "preview.mdx") };
+    ZDBBuilder::build_with_config(&config, None).unwrap();
+
+    let mut reader = MdxReader::from_url(&Url::from_file_path(&config.output_file).unwrap(), "").unwrap();
+    let key_index = reader.get_index(0).unwrap();
+    assert_eq!(reader.get_preview(&key_index, &PreviewOptions::default()).unwrap(), "To move fast.\nTo flow.");
+    let options = PreviewOptions { format: PreviewFormat::Html, max_sentences: 1, skip_classes: Some(Vec::new()), ..Default::default() };
+    assert_eq!(reader.get_preview(&key_index, &options).unwrap(), "To move <b>fast</b>.");
+    let options = PreviewOptions { max_chars: 4, ..Default::default() };
+    assert_eq!(reader.get_preview(&key_index, &options).unwrap(), "To m…");
+
+    config.preview_skip_classes = vec!["not a class".to_string()];
+    assert!(config.validate().is_err());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn compact_style_sheet() {
+    let dir = work_dir();
+    let style_sheet_path = dir.join("style.txt");
+    std::fs::write(&style_sheet_path, "1\n<b>\n</b>\n2\n<i class=\"x\">\n</i>\n").unwrap();
+    // A token applies up to the next token, including the line break
+    let expanded = "<b>bold</b><i class=\"x\">italic\r\n</i>";
+
+    // Expanded during the build
+    let mut config = BuilderConfig {
+        style_sheet_path: style_sheet_path.to_string_lossy().to_string(),
+        ..source_config(&dir, "word\r\n`1`bold`2`italic\r\n</>\r\n", "expanded.mdx")
+    };
+    ZDBBuilder::build_with_config(&config, None).unwrap();
+    let mut reader = ZdbReader::<BufReader<File>>::from_file(&config.output_file, "", "").unwrap();
+    assert!(!reader.meta.db_info.is_compact_format);
+    let key_index = reader.get_index(0).unwrap();
+    assert_eq!(reader.get_string(&key_index, false).unwrap(), expanded);
+
+    // Kept compact, expanded by the reader
+    let compact_path = dir.join("compact.mdx");
+    config.output_file = compact_path.to_string_lossy().to_string();
+    config.keep_compact = true;
+    ZDBBuilder::build_with_config(&config, None).unwrap();
+    let mut reader = MdxReader::from_url(&Url::from_file_path(&compact_path).unwrap(), "").unwrap();
+    let key_index = reader.get_index(0).unwrap();
+    assert_eq!(reader.get_string(&key_index, false).unwrap(), "`1`bold`2`italic\r\n");
+    assert_eq!(reader.get_string(&key_index, true).unwrap(), expanded);
+    assert_eq!(reader.get_text(&key_index, 100).unwrap(), "bolditalic");
+    assert_eq!(reader.get_text(&key_index, 6).unwrap(), "boldit");
+
+    // Overridden at runtime, invalid stylesheets keep the one in use
+    let override_path = dir.join("override.txt");
+    std::fs::write(&override_path, "1\r\n<strong>\r\n</strong>\r\n").unwrap();
+    reader.set_compact_stylesheet_from_file(&override_path).unwrap();
+    assert_eq!(reader.get_string(&key_index, true).unwrap(), "<strong>bold</strong>italic\r\n");
+    assert!(reader.set_compact_stylesheet("x\n<b>\n</b>\n").is_err());
+    assert_eq!(reader.get_string(&key_index, true).unwrap(), "<strong>bold</strong>italic\r\n");
+    reader.set_compact_stylesheet("").unwrap();
+    assert_eq!(reader.get_string(&key_index, true).unwrap(), "`1`bold`2`italic\r\n");
+    reader.reset_compact_stylesheet().unwrap();
+    assert_eq!(reader.get_string(&key_index, true).unwrap(), expanded);
+
+    // Converting the compact dictionary carries the stylesheet over
+    config.input_path = compact_path.to_string_lossy().to_string();
+    config.data_source_format = SourceType::Zdb;
+    config.output_file = dir.join("converted.mdx").to_string_lossy().to_string();
+    ZDBBuilder::build_with_config(&config, None).unwrap();
+    let mut reader = MdxReader::from_url(&Url::from_file_path(dir.join("converted.mdx")).unwrap(), "").unwrap();
+    let key_index = reader.get_index(0).unwrap();
+    assert_eq!(reader.get_string(&key_index, true).unwrap(), expanded);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn binary_entry_helpers() {
+    let dir = work_dir();
+    let resource_dir = dir.join("resources");
+    std::fs::create_dir_all(&resource_dir).unwrap();
+    std::fs::write(resource_dir.join("cat.png"), b"\xFF\xD8\xFF\xE0\0\x10JFIF\0").unwrap();
+    std::fs::write(resource_dir.join("hello"), b"RIFF\x24\0\0\0WAVEfmt ").unwrap();
+    ZDBBuilder::build_with_config(&resource_config(&resource_dir, &dir.join("blobs.mdx")), None).unwrap();
+
+    let mut reader = MdxReader::from_url(&Url::from_file_path(dir.join("blobs.mdx")).unwrap(), "").unwrap();
+    let cat = reader.content_db.find_first_match("/cat.png", false, false, false).unwrap().unwrap();
+    let (data, mime_type) = reader.get_blob(&cat).unwrap();
+    assert_eq!((data.len(), mime_type.as_str()), (11, "image/jpeg"));
+    let hello = reader.content_db.find_first_match("/hello", false, false, false).unwrap().unwrap();
+    assert_eq!(reader.get_blob(&hello).unwrap().1, "audio/wav");
+    let error = reader.get_html(&cat).unwrap_err();
+    assert_eq!(error.code(), ErrorCode::ContentTypeMismatch);
+    assert_eq!(error.to_string(), "get_html is not available for Binary content, use get_blob");
+    assert!(matches!(reader.get_string(&cat, false), Err(ZdbError::ContentTypeMismatch { .. })));
+
+    let config = source_config(&dir, "run\r\n<p>to move fast</p>\r\n</>\r\n", "text.mdx");
+    ZDBBuilder::build_with_config(&config, None).unwrap();
+    let mut reader = MdxReader::from_url(&Url::from_file_path(dir.join("text.mdx")).unwrap(), "").unwrap();
+    let run = reader.get_index(0).unwrap();
+    assert!(matches!(reader.get_blob(&run), Err(ZdbError::ContentTypeMismatch { ref content_type, .. }) if content_type == "Html"));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
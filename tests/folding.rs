@@ -0,0 +1,57 @@
+//! Headword folding.
+//!
+//! The queries and expected matches in `fixtures/headword_folding.tsv` are checked in a
+//! dictionary sorted by the stored headwords and in one sorted by the folded headwords.
+
+// Building dictionaries needs compiled collation data
+#![cfg(any(feature = "icu", feature = "rust-icu"))]
+
+use std::io::Cursor;
+
+use mdx::builder::{BuilderConfig, DataLoader, ZDBBuilder, ZdbRecord};
+use mdx::utils::KeyNormalization;
+use mdx::ZdbReader;
+
+const HEADWORDS: &[&str] = &[
+    "café au lait", "Café-au-lait", "naïve", "rock 'n' roll", "Ångström", "O'Brien",
+    "résumé", "resume", "你好，世界", "Straße",
+];
+
+struct KeyLoader;
+
+impl DataLoader for KeyLoader {
+    fn load_data(&mut self, entry: &ZdbRecord) -> mdx::Result<Vec<u8>> {
+        Ok(entry.key.as_bytes().to_vec())
+    }
+}
+
+fn check_folding_fixtures(key_normalization: KeyNormalization) {
+    let config = BuilderConfig { default_sorting_locale: "en".to_string(), key_normalization, ..Default::default() };
+    let records: Vec<ZdbRecord> = HEADWORDS.iter()
+        .map(|key| ZdbRecord { key: key.to_string(), ..Default::default() })
+        .collect();
+    let mut writer = Cursor::new(Vec::new());
+    ZDBBuilder::build_records_to_writer(&config, &mut writer, KeyLoader, records, None).unwrap();
+    writer.set_position(0);
+    let mut reader = ZdbReader::from_reader(writer, "", "").unwrap();
+
+    let fixtures = include_str!("fixtures/headword_folding.tsv");
+    for line in fixtures.lines().filter(|line| !line.starts_with('#') && !line.is_empty()) {
+        let (query, expected) = line.split_once('\t').unwrap();
+        let mut expected: Vec<&str> = expected.split('|').filter(|key| !key.is_empty()).collect();
+        let mut actual: Vec<String> = reader.find_folded(query).unwrap().into_iter().map(|key_index| key_index.key).collect();
+        expected.sort();
+        actual.sort();
+        assert_eq!(actual, expected, "query {:?}", query);
+    }
+}
+
+#[test]
+fn folding_fixtures() {
+    check_folding_fixtures(KeyNormalization::default());
+}
+
+#[test]
+fn folding_fixtures_sorted_by_folded_headwords() {
+    check_folding_fixtures(KeyNormalization { fold_headword: true, ..Default::default() });
+}
@@ -0,0 +1,114 @@
+//! Inspecting the structure of dictionary files: unit dumps, raw blocks and block checksums.
+
+// Building dictionaries needs compiled collation data
+#![cfg(any(feature = "icu", feature = "rust-icu"))]
+
+mod common;
+
+use std::fs::File;
+use std::io::{BufReader, Cursor};
+
+use mdx::builder::{BuilderConfig, ZDBBuilder, ZdbRecord};
+use mdx::crypto::encryption::EncryptionMethod;
+use mdx::inspect::{dump, Verbosity};
+use mdx::storage::storage_block::BLOCK_FLAG_OFFSET_NONCE;
+use mdx::storage::UnitType;
+use mdx::utils::checksum::ChecksumAlgorithm;
+use mdx::utils::compression::CompressionMethod;
+use mdx::{ZdbError, ZdbReader};
+
+use common::{build_in_memory, english_config, keyed_records, source_config, work_dir};
+
+fn fruit_records() -> Vec<ZdbRecord> {
+    keyed_records(&["cherry", "apple", "banana"], |key| format!("<b>{}</b>", key))
+}
+
+#[test]
+fn structural_dump() {
+    let dir = work_dir();
+    let source: String = (0..20).map(|i| format!("word{:02}\r\n<p>entry {}</p>\r\n</>\r\n", i, i)).collect();
+    let config = BuilderConfig {
+        preferred_content_block_size: 64,
+        bloom_filter: true,
+        write_unit_digests: true,
+        ..source_config(&dir, &source, "dict.mdx")
+    };
+    ZDBBuilder::build_with_config(&config, None).unwrap();
+    let path = dir.join("dict.mdx");
+
+    let file_dump = dump(&path, Verbosity::Units).unwrap();
+    assert_eq!(file_dump.version, 3);
+    assert!(file_dump.has_unit_digests);
+    let unit_types: Vec<UnitType> = file_dump.units.iter().map(|unit| unit.unit_type).collect();
+    assert_eq!(unit_types, [UnitType::Content, UnitType::ContentBlockIndex, UnitType::Key, UnitType::KeyBlockIndex, UnitType::BloomFilter]);
+    assert!(file_dump.units.iter().all(|unit| unit.blocks.is_empty()));
+    assert!(file_dump.units[0].data_info_xml.as_deref().unwrap().contains("recordCount=\"20\""));
+    let verified = ZdbReader::<BufReader<File>>::from_file(&path, "", "").unwrap().verify().unwrap();
+    let unit_ranges: Vec<(u64, u64)> = file_dump.units.iter().map(|unit| (unit.offset, unit.length)).collect();
+    assert_eq!(unit_ranges, verified.iter().map(|check| (check.offset, check.length)).collect::<Vec<_>>());
+
+    let file_dump = dump(&path, Verbosity::Blocks).unwrap();
+    let content_unit = &file_dump.units[0];
+    assert!(content_unit.block_count > 1);
+    assert_eq!(content_unit.blocks.len(), content_unit.block_count as usize);
+    assert_eq!(content_unit.blocks.iter().map(|block| block.stored_length).sum::<u64>(), content_unit.data_section_length);
+    let json = serde_json::to_value(&file_dump).unwrap();
+    assert_eq!(json["units"][0]["unit_type"], "Content");
+    assert_eq!(json["units"][0]["blocks"][0]["compression"], "Deflate");
+    assert!(file_dump.to_string().contains("ContentBlockIndex"));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn debug_raw_block() {
+    let mut config = BuilderConfig { compression_method: CompressionMethod::Lz4, ..english_config() };
+    let mut reader = ZdbReader::<Cursor<Vec<u8>>>::from_bytes(build_in_memory(&config, fruit_records()), "", "").unwrap();
+    let block = reader.debug_block(0).unwrap();
+    let block_index = reader.content_block_indexes()[0].clone();
+    assert_eq!(block.original_length as u64, block_index.block_original_length);
+    assert_eq!(block.stored_length, block_index.block_compressed_length);
+    assert_eq!((block.compression, block.encryption), (CompressionMethod::Lz4, EncryptionMethod::Salsa20));
+    assert_eq!(block.flags & BLOCK_FLAG_OFFSET_NONCE, BLOCK_FLAG_OFFSET_NONCE);
+    assert_eq!(block.compressed_data.len() as u64, block.stored_length - 16);
+    assert!(matches!(reader.debug_block(1), Err(ZdbError::InvalidParameter { .. })));
+
+    // Unencrypted and uncompressed, the data is stored as is and the crc covers it
+    config.compression_method = CompressionMethod::None;
+    config.encryption_method = EncryptionMethod::None;
+    let mut reader = ZdbReader::<Cursor<Vec<u8>>>::from_bytes(build_in_memory(&config, fruit_records()), "", "").unwrap();
+    let block = reader.debug_block(0).unwrap();
+    assert_eq!(block.compressed_data, b"<b>apple</b><b>banana</b><b>cherry</b>");
+    assert_eq!(block.crc, adler::adler32_slice(&block.compressed_data));
+}
+
+#[test]
+fn block_checksums() {
+    for block_checksum in [ChecksumAlgorithm::Adler32, ChecksumAlgorithm::Crc32c, ChecksumAlgorithm::Xxh3] {
+        let config = BuilderConfig {
+            block_checksum,
+            compression_method: CompressionMethod::None,
+            encryption_method: EncryptionMethod::None,
+            ..english_config()
+        };
+        let mut data = build_in_memory(&config, fruit_records());
+        let mut reader = ZdbReader::<Cursor<Vec<u8>>>::from_bytes(data.clone(), "", "").unwrap();
+        let block = reader.debug_block(0).unwrap();
+        assert_eq!(ChecksumAlgorithm::from_block_flags(block.flags).unwrap(), block_checksum);
+        assert_eq!(block.crc, block_checksum.checksum(b"<b>apple</b><b>banana</b><b>cherry</b>"));
+        let key_index = reader.get_index(2).unwrap();
+        assert_eq!(reader.get_string(&key_index, false).unwrap(), "<b>cherry</b>");
+
+        // A corrupted byte of the content is detected
+        let position = block.offset as usize + 16 + 3;
+        data[position] ^= 0x20;
+        let mut reader = ZdbReader::<Cursor<Vec<u8>>>::from_bytes(data, "", "").unwrap();
+        let key_index = reader.get_index(0).unwrap();
+        assert!(matches!(reader.get_string(&key_index, false), Err(ZdbError::CrcMismatch { .. })), "{:?}", block_checksum);
+
+        // Encrypted blocks checksum the compressed data
+        let config = BuilderConfig { compression_method: CompressionMethod::Deflate, encryption_method: EncryptionMethod::Salsa20, ..config };
+        let mut reader = ZdbReader::<Cursor<Vec<u8>>>::from_bytes(build_in_memory(&config, fruit_records()), "", "").unwrap();
+        let key_index = reader.get_index(1).unwrap();
+        assert_eq!(reader.get_string(&key_index, false).unwrap(), "<b>banana</b>");
+    }
+}
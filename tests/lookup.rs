@@ -0,0 +1,215 @@
+//! Looking up entries: normalized keys, key probes, neighbours, prefix counts and the
+//! data stored alongside the entries.
+
+// Building dictionaries needs compiled collation data
+#![cfg(any(feature = "icu", feature = "rust-icu"))]
+
+mod common;
+
+use std::io::Cursor;
+
+use mdx::builder::{BuildWarning, BuilderConfig, ZDBBuilder, ZdbRecord};
+use mdx::crypto::encryption::EncryptionMethod;
+use mdx::inspect::Verbosity;
+use mdx::readers::SearchDirection;
+use mdx::storage::{CompactKeyIndexes, EntryMetaExt, KeyIndex, PartOfSpeech, UnitType, COMPACT_KEY_INDEX_SCHEMA_VERSION};
+use mdx::utils::compression::CompressionMethod;
+use mdx::utils::KeyNormalization;
+use mdx::{ErrorCode, MdxReader, ZdbError, ZdbReader};
+use url::Url;
+
+use common::{build_in_memory, english_config, keyed_records, source_config, work_dir, RecordContentLoader};
+
+#[test]
+fn normalized_key_lookup() {
+    let config = BuilderConfig {
+        key_normalization: KeyNormalization { strip_key: true, fold_case: true, ..Default::default() },
+        bloom_filter: true,
+        ..english_config()
+    };
+    let records = keyed_records(&["Rock-'n'-Roll", "rocket", "O'Brien", "obi"], str::to_string);
+    let mut reader = ZdbReader::from_reader(Cursor::new(build_in_memory(&config, records)), "", "").unwrap();
+    assert_eq!(reader.meta.db_info.key_normalization, config.key_normalization);
+    for (query, key) in [("rock n roll", "Rock-'n'-Roll"), ("ROCKNROLL", "Rock-'n'-Roll"), ("obrien", "O'Brien"), ("Rocket", "rocket")] {
+        assert!(reader.may_contain(query).unwrap(), "{}", query);
+        let key_index = reader.find_first_match(query, false, false, true).unwrap().unwrap();
+        assert_eq!(key_index.key, key, "lookup of {:?}", query);
+        assert_eq!(reader.get_entry_no_by_key(query).unwrap(), Some(key_index.entry_no), "entry number of {:?}", query);
+    }
+    assert_eq!(reader.get_entry_no_by_key("rockabilly").unwrap(), None);
+}
+
+#[test]
+fn contains_key_probe() {
+    let records = || (0..400)
+        .map(|i| ZdbRecord { key: format!("Word{:04}", i * 2), content: format!("entry {}", i), ..Default::default() })
+        .collect::<Vec<_>>();
+    for bloom_filter in [false, true] {
+        let config = BuilderConfig { preferred_key_block_size: 256, bloom_filter, ..english_config() };
+        let mut reader = ZdbReader::<Cursor<Vec<u8>>>::from_bytes(build_in_memory(&config, records()), "", "").unwrap();
+        assert_eq!(reader.has_bloom_filter(), bloom_filter);
+        for key in ["Word0000", "Word0402", "Word0798"] {
+            assert!(reader.contains_key(key).unwrap(), "{}", key);
+        }
+        for key in ["Word0001", "Word0799", "Word", "Zebra", "", "A"] {
+            assert!(!reader.contains_key(key).unwrap(), "{}", key);
+        }
+        // Same answer as an exact lookup, including keys equal by collation
+        for key in ["word0402", "WORD0010"] {
+            assert_eq!(reader.contains_key(key).unwrap(), reader.find_first_match(key, false, false, true).unwrap().is_some(), "{}", key);
+        }
+    }
+}
+
+#[test]
+fn compact_key_index_payload() {
+    let records = keyed_records(&["apple", "Äpfel", "banana"], |key| format!("about {}", key));
+    let mut reader = ZdbReader::<Cursor<Vec<u8>>>::from_bytes(build_in_memory(&english_config(), records), "", "").unwrap();
+
+    let key_indexes: Vec<KeyIndex> = reader.get_indexes(0, 3).unwrap().into_iter().collect();
+    let payload = serde_json::to_string(&CompactKeyIndexes::new(&key_indexes)).unwrap();
+    assert!(payload.starts_with(&format!("{{\"schema_version\":{},\"indexes\":[{{\"key\":", COMPACT_KEY_INDEX_SCHEMA_VERSION)), "{}", payload);
+    assert!(!payload.contains("sort_key"));
+
+    let indexes = serde_json::from_str::<CompactKeyIndexes>(&payload).unwrap().into_indexes().unwrap();
+    for (compact, original) in indexes.iter().zip(&key_indexes) {
+        let key_index = compact.to_key_index(&reader.meta).unwrap();
+        assert_eq!((&key_index.key, &key_index.key_raw, &key_index.sort_key), (&original.key, &original.key_raw, &original.sort_key));
+        assert_eq!(reader.get_string(&key_index, false).unwrap(), format!("about {}", original.key));
+    }
+
+    let newer = CompactKeyIndexes { schema_version: COMPACT_KEY_INDEX_SCHEMA_VERSION + 1, indexes };
+    assert_eq!(newer.into_indexes().unwrap_err().code(), ErrorCode::InvalidDataFormat);
+}
+
+#[test]
+fn similar_indexes_both_directions() {
+    let mut records: Vec<ZdbRecord> = (0..4)
+        .map(|i| ZdbRecord { key: "bank".to_string(), content: format!("bank {}", i), ..Default::default() })
+        .collect();
+    records.extend(keyed_records(&["apple", "banker", "cherry"], str::to_string));
+    let mut reader = ZdbReader::<Cursor<Vec<u8>>>::from_bytes(build_in_memory(&english_config(), records), "", "").unwrap();
+    let entry_nos = |list: &std::collections::LinkedList<KeyIndex>| list.iter().map(|index| index.entry_no).collect::<Vec<_>>();
+
+    // Entries: apple, bank x4, banker, cherry; anchor on the third "bank"
+    let anchor = reader.get_index(3).unwrap();
+    assert_eq!(anchor.key, "bank");
+    assert_eq!(entry_nos(&reader.get_similar_indexes(&anchor, false, 10).unwrap()), [3, 4]);
+    assert_eq!(entry_nos(&reader.get_similar_indexes_in_direction(&anchor, false, 10, SearchDirection::Backward).unwrap()), [1, 2, 3]);
+    assert_eq!(entry_nos(&reader.get_similar_indexes_in_direction(&anchor, false, 2, SearchDirection::Backward).unwrap()), [2, 3]);
+    assert_eq!(entry_nos(&reader.get_similar_indexes_around(&anchor, false, 10, 10).unwrap()), [1, 2, 3, 4]);
+    assert_eq!(entry_nos(&reader.get_similar_indexes_around(&anchor, true, 10, 10).unwrap()), [1, 2, 3, 4, 5]);
+    assert_eq!(entry_nos(&reader.get_similar_indexes_around(&anchor, false, 1, 0).unwrap()), [2, 3]);
+
+    let first = reader.get_index(0).unwrap();
+    assert_eq!(entry_nos(&reader.get_similar_indexes_in_direction(&first, false, 10, SearchDirection::Backward).unwrap()), [0]);
+    let last = reader.get_index(6).unwrap();
+    assert_eq!(entry_nos(&reader.get_similar_indexes_around(&last, false, 10, 10).unwrap()), [6]);
+}
+
+#[test]
+fn prefix_count() {
+    let letters = ['a', 'b', 'c', 'd', 'e', 'f', 'g'];
+    let keys: Vec<String> = (0..700).map(|i| format!("{}{}{:03}", letters[i % 5], letters[(i / 5) % 7], i)).collect();
+    let records = keys.iter()
+        .map(|key| ZdbRecord { key: key.clone(), content: key.clone(), ..Default::default() })
+        .collect::<Vec<_>>();
+    let config = BuilderConfig { preferred_key_block_size: 256, ..english_config() };
+    let mut reader = ZdbReader::<Cursor<Vec<u8>>>::from_bytes(build_in_memory(&config, records), "", "").unwrap();
+    for prefix in ["a", "ab", "e", "eg", "ag1", "cc35", "a0", "g", "z", "", "ee699"] {
+        let expected = keys.iter().filter(|key| key.starts_with(prefix)).count() as u64;
+        assert_eq!(reader.count_prefix(prefix).unwrap(), expected, "{}", prefix);
+    }
+}
+
+#[test]
+fn entry_metadata_unit() {
+    let dir = work_dir();
+    let meta_path = dir.join("entry_meta.tsv");
+    std::fs::write(&meta_path, "# key\trank\tpos\ttags\nrun\t120\tverb\tcommon\nrunner\t4200\tn.\tcommon, sports\nrunning\n\nsprint\t\tadj\tsports\nmissing\t1\n").unwrap();
+    let records = || keyed_records(&["run", "runner", "running", "walk", "run"], |key| format!("<b>{}</b>", key));
+    let mut config = BuilderConfig { bloom_filter: true, entry_meta_path: meta_path.to_string_lossy().into_owned(), ..english_config() };
+    let mut writer = Cursor::new(Vec::new());
+    let report = ZDBBuilder::build_records_to_writer(&config, &mut writer, RecordContentLoader, records(), None).unwrap();
+    assert_eq!(report.warnings, [
+        BuildWarning::UnmatchedEntryMeta { key: "sprint".to_string(), line_no: 6 },
+        BuildWarning::UnmatchedEntryMeta { key: "missing".to_string(), line_no: 7 },
+    ]);
+    let data = writer.into_inner();
+    let file_dump = mdx::inspect::dump_reader(&mut Cursor::new(&data), Verbosity::Units).unwrap();
+    assert_eq!(file_dump.units.last().unwrap().unit_type, UnitType::EntryMeta);
+
+    let mut reader = ZdbReader::<Cursor<Vec<u8>>>::from_bytes(data, "", "").unwrap();
+    assert!(reader.has_bloom_filter());
+    assert_eq!(reader.entry_meta_tag_names(), ["common", "sports"]);
+    let meta_of = |reader: &mut ZdbReader<Cursor<Vec<u8>>>, entry_no| {
+        let key = reader.get_index(entry_no).unwrap().key;
+        (key, reader.get_entry_meta_ext(entry_no).unwrap().unwrap())
+    };
+    let run = EntryMetaExt { tags: 0b01, frequency_rank: 120, pos: PartOfSpeech::Verb };
+    assert_eq!(meta_of(&mut reader, 0), ("run".to_string(), run));
+    assert_eq!(meta_of(&mut reader, 1), ("run".to_string(), run));
+    assert_eq!(meta_of(&mut reader, 2), ("runner".to_string(), EntryMetaExt { tags: 0b11, frequency_rank: 4200, pos: PartOfSpeech::Noun }));
+    assert_eq!(meta_of(&mut reader, 3), ("running".to_string(), EntryMetaExt::default()));
+    assert_eq!(meta_of(&mut reader, 4), ("walk".to_string(), EntryMetaExt::default()));
+    assert!(reader.get_entry_meta_ext(5).is_err());
+    assert!(reader.memory_footprint().entry_meta >= 45);
+
+    // Files without the unit, and invalid files
+    config.entry_meta_path = String::new();
+    let reader = ZdbReader::<Cursor<Vec<u8>>>::from_bytes(build_in_memory(&config, records()), "", "").unwrap();
+    assert_eq!(reader.get_entry_meta_ext(0).unwrap(), None);
+    std::fs::write(&meta_path, "run\t120\tgerundive\n").unwrap();
+    config.entry_meta_path = meta_path.to_string_lossy().into_owned();
+    let result = ZDBBuilder::build_records_to_writer(&config, &mut Cursor::new(Vec::new()), RecordContentLoader, records(), None);
+    assert!(matches!(result, Err(ZdbError::InvalidDataFormat { ref message, .. }) if message.contains("line 1")));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn source_map() {
+    let dir = work_dir();
+    let source = "walk\r\n<p>to move on foot</p>\r\n</>\r\nrun\r\n<p>to move fast</p>\r\n<p>quickly</p>\r\n</>\r\n";
+    let mut config = BuilderConfig { source_map: true, ..source_config(&dir, source, "mapped.mdx") };
+    ZDBBuilder::build_with_config(&config, None).unwrap();
+
+    let url = Url::from_file_path(&config.output_file).unwrap();
+    let reader = MdxReader::from_url(&url, "").unwrap();
+    // Entries are sorted, `run` comes first
+    let run = reader.get_source_location(0).unwrap().unwrap();
+    assert_eq!((run.source_file.as_str(), run.line_no), ("source.txt", 4));
+    assert!(source[run.offset as usize..].starts_with("<p>to move fast</p>"));
+    let walk = reader.get_source_location(1).unwrap().unwrap();
+    assert_eq!((walk.line_no, walk.offset), (1, 6));
+    assert!(reader.get_source_location(2).is_err());
+
+    config.source_map = false;
+    ZDBBuilder::build_with_config(&config, None).unwrap();
+    let reader = MdxReader::from_url(&url, "").unwrap();
+    assert_eq!(reader.get_source_location(0).unwrap(), None);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn partial_block_decoding() {
+    let records: Vec<ZdbRecord> = (0..1500)
+        .map(|i| ZdbRecord { key: format!("word{:04}", i), content: format!("<p>entry {} of a large block</p>", i), ..Default::default() })
+        .collect();
+    let decoded_after_first_entry = |encryption_method: EncryptionMethod| {
+        let config = BuilderConfig { compression_method: CompressionMethod::Lz4, encryption_method, ..english_config() };
+        let mut reader = ZdbReader::<Cursor<Vec<u8>>>::from_bytes(build_in_memory(&config, records.clone()), "", "").unwrap();
+        assert_eq!(reader.content_block_indexes().len(), 1);
+        let key_index = reader.get_index(0).unwrap();
+        assert_eq!(reader.get_string(&key_index, false).unwrap(), "<p>entry 0 of a large block</p>");
+        let partial = reader.memory_footprint().content_block_cache;
+        // The rest of the block is decoded when needed
+        let key_index = reader.get_index(1499).unwrap();
+        assert_eq!(reader.get_string(&key_index, false).unwrap(), "<p>entry 1499 of a large block</p>");
+        (partial, reader.memory_footprint().content_block_cache)
+    };
+    let (partial, complete) = decoded_after_first_entry(EncryptionMethod::Salsa20);
+    assert!(partial * 4 < complete, "{} {}", partial, complete);
+    // The checksum of an unencrypted block covers the uncompressed data, it's decoded whole
+    let (partial, complete) = decoded_after_first_entry(EncryptionMethod::None);
+    assert_eq!(partial, complete);
+}
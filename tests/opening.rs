@@ -0,0 +1,326 @@
+//! Opening dictionaries: from memory, archives and custom URLs, under memory limits,
+//! from incomplete files or with companion content files, and their open timings.
+
+// Building dictionaries needs compiled collation data
+#![cfg(any(feature = "icu", feature = "rust-icu"))]
+
+mod common;
+
+use std::fs::File;
+use std::io::{BufReader, Cursor};
+use std::sync::Arc;
+use std::time::Duration;
+
+use mdx::builder::{BuilderConfig, SourceType, ZDBBuilder, ZdbRecord};
+use mdx::readers::{KeyOrderCheck, ReaderOptions};
+use mdx::storage::UnitType;
+use mdx::utils::compression::CompressionMethod;
+use mdx::{ErrorCode, MdxReader, ZdbError, ZdbReader};
+use url::Url;
+
+use common::{build_file, build_in_memory, english_config, keyed_records, source_config, work_dir};
+
+/// Records `word000`, `word001`… with the content `entry 0`, `entry 1`…
+fn numbered_records(count: usize, key_digits: usize) -> Vec<ZdbRecord> {
+    (0..count)
+        .map(|i| ZdbRecord { key: format!("word{:0width$}", i, width = key_digits), content: format!("entry {}", i), ..Default::default() })
+        .collect()
+}
+
+#[test]
+fn open_partial_file() {
+    let records = (0..200).map(|i| ZdbRecord { key: format!("word{:03}", i), content: format!("<p>{}</p>", i), ..Default::default() });
+    let data = build_in_memory(&BuilderConfig { bloom_filter: true, ..english_config() }, records);
+
+    // The Bloom filter unit comes last, cut off while the file is still copied
+    let truncated = data[..data.len() - 10].to_vec();
+    assert!(ZdbReader::<Cursor<Vec<u8>>>::from_bytes(truncated.clone(), "", "").is_err());
+    let mut reader = ZdbReader::open_partial(Cursor::new(truncated), "", "").unwrap();
+    assert!(!reader.has_bloom_filter());
+    let unavailable = reader.unavailable_units();
+    assert_eq!(unavailable.len(), 1);
+    assert_eq!((unavailable[0].unit_type, unavailable[0].capability), (UnitType::BloomFilter, "Bloom filter"));
+    assert!(reader.contains_key("word150").unwrap());
+    let key_index = reader.find_first_match("word042", false, false, true).unwrap().unwrap();
+    assert_eq!(reader.get_string(&key_index, false).unwrap(), "<p>42</p>");
+
+    // A complete file has nothing unavailable
+    let reader = ZdbReader::open_partial(Cursor::new(data.clone()), "", "").unwrap();
+    assert!(reader.has_bloom_filter() && reader.unavailable_units().is_empty());
+    // Without the key units nothing can be looked up
+    assert!(ZdbReader::open_partial(Cursor::new(data[..data.len() / 2].to_vec()), "", "").is_err());
+}
+
+#[test]
+fn companion_content_file() {
+    let dir = work_dir();
+    let content = "x".repeat(5000);
+    let mut config = source_config(&dir, &format!("apple\r\nred {}\r\n</>\r\nzebra\r\nstripes\r\n</>\r\n", content), "split.mdx");
+    config.compression_method = CompressionMethod::None;
+    config.content_file_extension = "mdx".to_string();
+    assert!(config.validate().unwrap_err()[0].contains("must differ"));
+    config.content_file_extension = "zdat".to_string();
+    ZDBBuilder::build_with_config(&config, None).unwrap();
+    let content_path = dir.join("split.zdat");
+    assert!(std::fs::metadata(&content_path).unwrap().len() > std::fs::metadata(&config.output_file).unwrap().len());
+
+    let mut reader = ZdbReader::<BufReader<File>>::from_file(&config.output_file, "", "").unwrap();
+    assert_eq!(reader.meta.db_info.content_file, "split.zdat");
+    let key_index = reader.get_index(0).unwrap();
+    assert_eq!(reader.get_string(&key_index, false).unwrap(), format!("red {}\r\n", content));
+    let mut reader = MdxReader::from_url(&Url::from_file_path(&config.output_file).unwrap(), "").unwrap();
+    let key_index = reader.get_index(1).unwrap();
+    assert_eq!(reader.get_html(&key_index).unwrap(), "stripes\r\n");
+
+    // A reader without the directory of the dictionary can't find the companion file
+    let bytes = std::fs::read(&config.output_file).unwrap();
+    let error = ZdbReader::<Cursor<Vec<u8>>>::from_bytes(bytes.clone(), "", "").err().unwrap();
+    assert_eq!(error.code(), ErrorCode::InvalidParameter);
+    let options = ReaderOptions { content_dir: Some(dir.clone()), ..Default::default() };
+    let mut reader = ZdbReader::from_reader_with_options(Cursor::new(bytes.clone()), "", "", options.clone()).unwrap();
+    assert!(reader.get_data_by_key("apple").unwrap().is_some());
+
+    // Converting keeps the content in the new file unless asked otherwise
+    let convert_config = BuilderConfig {
+        input_path: config.output_file.clone(),
+        data_source_format: SourceType::Zdb,
+        output_file: dir.join("joined.mdx").to_string_lossy().to_string(),
+        ..Default::default()
+    };
+    ZDBBuilder::build_with_config(&convert_config, None).unwrap();
+    let mut reader = ZdbReader::<BufReader<File>>::from_file(&convert_config.output_file, "", "").unwrap();
+    assert!(reader.meta.db_info.content_file.is_empty());
+    let key_index = reader.get_index(1).unwrap();
+    assert_eq!(reader.get_string(&key_index, false).unwrap(), "stripes\r\n");
+
+    let companion = std::fs::read(&content_path).unwrap();
+    std::fs::write(&content_path, &companion[..companion.len() - 1]).unwrap();
+    let error = ZdbReader::from_reader_with_options(Cursor::new(bytes), "", "", options).err().unwrap();
+    assert!(error.to_string().contains("truncated"), "{}", error);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn open_report() {
+    let dir = work_dir();
+    let source: String = (0..500).map(|n| format!("word{}\r\n<p>entry {}</p>\r\n</>\r\n", n, n)).collect();
+    let mut config = source_config(&dir, &source, "timed.mdx");
+    config.bloom_filter = true;
+    ZDBBuilder::build_with_config(&config, None).unwrap();
+
+    let reader = MdxReader::from_url(&Url::from_file_path(&config.output_file).unwrap(), "").unwrap();
+    let report = reader.open_report();
+    let unit_types: Vec<UnitType> = report.units.iter().map(|timing| timing.unit_type).collect();
+    assert_eq!(unit_types, [UnitType::Content, UnitType::ContentBlockIndex, UnitType::Key, UnitType::KeyBlockIndex, UnitType::BloomFilter]);
+    assert!(report.total >= report.header + report.collator + report.mdd + report.fts);
+    assert!(report.total >= report.units.iter().map(|timing| timing.duration).sum::<Duration>());
+    assert_eq!(report.unit(UnitType::EntryMeta), Duration::ZERO);
+    assert!(report.to_string().contains("KeyBlockIndex unit: "));
+    let json = serde_json::to_value(report).unwrap();
+    assert_eq!(json["units"][4]["unit_type"], "BloomFilter");
+
+    // The key block index is decoded on another thread, the reader only reports its own steps
+    let options = ReaderOptions { parallel_open: true, key_order_check: KeyOrderCheck::Full, ..Default::default() };
+    let reader = ZdbReader::<BufReader<File>>::from_file_with_options(&config.output_file, "", "", options).unwrap();
+    let report = reader.open_report();
+    assert_eq!(report.units.len(), 5);
+    assert!(report.key_order_check > Duration::ZERO);
+    assert_eq!((report.mdd, report.fts), (Duration::ZERO, Duration::ZERO));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn open_from_bytes() {
+    let records = keyed_records(&["cherry", "apple", "banana"], |key| format!("<b>{}</b>", key));
+
+    // Readers sharing one buffer
+    let bytes: Arc<[u8]> = build_in_memory(&english_config(), records).into();
+    for _ in 0..2 {
+        let mut reader = ZdbReader::<Cursor<Arc<[u8]>>>::from_bytes(bytes.clone(), "", "").unwrap();
+        let key_index = reader.find_first_match("cherry", false, false, true).unwrap().unwrap();
+        assert_eq!(reader.get_string(&key_index, false).unwrap(), "<b>cherry</b>");
+    }
+    assert_eq!(Arc::strong_count(&bytes), 1);
+}
+
+#[test]
+fn memory_limit() {
+    let config = BuilderConfig { preferred_key_block_size: 1024, preferred_content_block_size: 4096, ..english_config() };
+    let records: Vec<ZdbRecord> = (0..2000)
+        .map(|i| ZdbRecord { key: format!("key{:05}", i), content: format!("content of entry {} ", i).repeat(20), ..Default::default() })
+        .collect();
+    let data = build_in_memory(&config, records);
+
+    let mut reader = ZdbReader::from_reader(Cursor::new(data.clone()), "", "").unwrap();
+    for i in (0..2000).step_by(7) {
+        let key_index = reader.find_first_match(&format!("key{:05}", i), false, false, true).unwrap().unwrap();
+        reader.get_string(&key_index, false).unwrap();
+    }
+    let unlimited = reader.memory_footprint();
+    assert!(unlimited.key_block_cache > 0 && unlimited.content_block_cache > 0);
+
+    let options = ReaderOptions { max_memory: Some(unlimited.block_indexes / 2), ..Default::default() };
+    let result = ZdbReader::from_reader_with_options(Cursor::new(data.clone()), "", "", options);
+    assert!(matches!(result, Err(ZdbError::MemoryLimitExceeded { .. })));
+    // Opened lazily, the index is refused on first use and never kept
+    let options = ReaderOptions { lazy_key_index: true, ..Default::default() };
+    let unloaded = ZdbReader::from_reader_with_options(Cursor::new(data.clone()), "", "", options.clone()).unwrap().memory_footprint();
+    let options = ReaderOptions { max_memory: Some(unloaded.total() + 1), ..options };
+    let mut reader = ZdbReader::from_reader_with_options(Cursor::new(data.clone()), "", "", options).unwrap();
+    assert!(matches!(reader.find_first_match("key00007", false, false, true), Err(ZdbError::MemoryLimitExceeded { .. })));
+    assert_eq!(reader.memory_footprint(), unloaded);
+
+    let limit = unlimited.total() / 2;
+    let options = ReaderOptions { max_memory: Some(limit), ..Default::default() };
+    let mut reader = ZdbReader::from_reader_with_options(Cursor::new(data), "", "", options).unwrap();
+    for i in (0..2000).step_by(7) {
+        let key = format!("key{:05}", i);
+        let key_index = reader.find_first_match(&key, false, false, true).unwrap().unwrap();
+        assert_eq!(key_index.key, key);
+        assert!(reader.get_string(&key_index, false).unwrap().starts_with(&format!("content of entry {} ", i)));
+        assert!(reader.memory_footprint().total() <= limit);
+    }
+}
+
+#[test]
+fn lazy_key_index() {
+    let data = build_in_memory(&BuilderConfig { preferred_key_block_size: 256, ..english_config() }, numbered_records(500, 4));
+
+    let options = ReaderOptions { lazy_key_index: true, ..Default::default() };
+    let mut reader = ZdbReader::from_reader_with_options(Cursor::new(data.clone()), "", "", options).unwrap();
+    let unloaded = reader.memory_footprint().block_indexes;
+    assert_eq!(reader.get_entry_count(), 500);
+    let key_index = reader.find_first_match("word0123", false, false, true).unwrap().unwrap();
+    assert_eq!(reader.get_string(&key_index, false).unwrap(), "entry 123");
+    assert_eq!(reader.get_index(499).unwrap().key, "word0499");
+    let eager = ZdbReader::from_reader(Cursor::new(data), "", "").unwrap();
+    assert!(unloaded < eager.memory_footprint().block_indexes);
+    assert_eq!(reader.memory_footprint().block_indexes, eager.memory_footprint().block_indexes);
+}
+
+#[test]
+fn parallel_open() {
+    let dir = work_dir();
+    let path = dir.join("parallel.mdx");
+    build_file(&BuilderConfig { preferred_key_block_size: 256, bloom_filter: true, ..english_config() }, &path, numbered_records(500, 4));
+
+    let options = ReaderOptions { parallel_open: true, ..Default::default() };
+    let mut reader = ZdbReader::<BufReader<File>>::from_file_with_options(&path, "", "", options).unwrap();
+    let sequential = ZdbReader::<BufReader<File>>::from_file(&path, "", "").unwrap();
+    assert_eq!(reader.memory_footprint(), sequential.memory_footprint());
+    assert!(reader.has_bloom_filter());
+    assert!(reader.may_contain("word0007").unwrap());
+    let key_index = reader.find_first_match("word0321", false, false, true).unwrap().unwrap();
+    assert_eq!(reader.get_string(&key_index, false).unwrap(), "entry 321");
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn open_from_zip_archive() {
+    use std::io::Write;
+    use zip::write::SimpleFileOptions;
+
+    let data = build_in_memory(&english_config(), numbered_records(300, 3));
+    let dir = work_dir();
+    let archive_path = dir.join("pack.zip");
+    let mut archive = zip::ZipWriter::new(File::create(&archive_path).unwrap());
+    archive.start_file("stored/dict.mdx", SimpleFileOptions::default().compression_method(zip::CompressionMethod::Stored)).unwrap();
+    archive.write_all(&data).unwrap();
+    archive.start_file("deflated.mdx", SimpleFileOptions::default().compression_method(zip::CompressionMethod::Deflated)).unwrap();
+    archive.write_all(&data).unwrap();
+    archive.finish().unwrap();
+
+    let archive_url = Url::from_file_path(&archive_path).unwrap();
+    for name in ["stored/dict.mdx", "deflated.mdx"] {
+        let url = Url::parse(&format!("zip://{}!/{}", archive_url.path(), name)).unwrap();
+        let mut reader = MdxReader::from_url(&url, "").unwrap();
+        assert_eq!(reader.get_entry_count(), 300);
+        let key_index = reader.find_index("word123", false, false, true).unwrap().unwrap();
+        assert_eq!(reader.get_string(&key_index, false).unwrap(), "entry 123");
+        assert_eq!(MdxReader::open_metadata_only(&url).unwrap().entry_count, Some(300));
+    }
+    let missing = Url::parse(&format!("zip://{}!/missing.mdx", archive_url.path())).unwrap();
+    assert!(MdxReader::from_url(&missing, "").is_err());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn open_through_url_resolver() {
+    use std::collections::HashMap;
+    use mdx::utils::io_utils::{register_url_resolver, ReadSeek, UrlResolver};
+
+    /// Serves files from memory by URL path, like an Android content provider would.
+    struct MemoryResolver(HashMap<String, Arc<[u8]>>);
+
+    impl UrlResolver for MemoryResolver {
+        fn open(&self, url: &Url) -> mdx::Result<Box<dyn ReadSeek>> {
+            let data = self.0.get(url.path())
+                .ok_or_else(|| ZdbError::general_error(format!("No such file: {}", url)))?;
+            Ok(Box::new(Cursor::new(data.clone())))
+        }
+
+        fn exists(&self, url: &Url) -> bool {
+            self.0.contains_key(url.path())
+        }
+    }
+
+    let data = build_in_memory(&english_config(), numbered_records(300, 3));
+    let files = HashMap::from([("/books/dict.mdx".to_string(), Arc::from(data))]);
+    register_url_resolver("Mem", Arc::new(MemoryResolver(files)));
+
+    let url = Url::parse("mem:///books/dict.mdx").unwrap();
+    let mut reader = MdxReader::from_url(&url, "").unwrap();
+    assert_eq!(reader.get_entry_count(), 300);
+    let key_index = reader.find_index("word123", false, false, true).unwrap().unwrap();
+    assert_eq!(reader.get_string(&key_index, false).unwrap(), "entry 123");
+    assert_eq!(MdxReader::open_metadata_only(&url).unwrap().entry_count, Some(300));
+    assert!(MdxReader::from_url(&Url::parse("mem:///books/missing.mdx").unwrap(), "").is_err());
+    assert!(MdxReader::from_url(&Url::parse("unknown:///books/dict.mdx").unwrap(), "").is_err());
+}
+
+#[test]
+fn metadata_only() {
+    let dir = work_dir();
+    let path = dir.join("metadata.mdx");
+    let records: Vec<ZdbRecord> = (0..42)
+        .map(|i| ZdbRecord { key: format!("word{:02}", i), content: format!("entry {}", i), ..Default::default() })
+        .collect();
+    build_file(&english_config(), &path, records);
+
+    let metadata = MdxReader::open_metadata_only(&Url::from_file_path(&path).unwrap()).unwrap();
+    assert_eq!(metadata.db_name, "metadata");
+    assert_eq!(metadata.locale_id, "en");
+    assert_eq!(metadata.version, "V3");
+    assert_eq!(metadata.entry_count, Some(42));
+    assert_eq!(metadata.file_size, std::fs::metadata(&path).unwrap().len());
+    assert_eq!(metadata.resource_file_size, None);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn key_order_check() {
+    let config = BuilderConfig { default_sorting_locale: "sv".to_string(), preferred_key_block_size: 256, ..Default::default() };
+    let records: Vec<ZdbRecord> = (0..300).map(|i| format!("word{:03}", i)).chain(["öl".to_string(), "zebra".to_string()])
+        .map(|key| ZdbRecord { content: key.clone(), key, ..Default::default() })
+        .collect();
+    let sorted = build_in_memory(&config, records);
+    let open = |data: &[u8], key_order_check: KeyOrderCheck, fail_on_key_order_mismatch: bool| {
+        let options = ReaderOptions { key_order_check, fail_on_key_order_mismatch, ..Default::default() };
+        ZdbReader::from_reader_with_options(Cursor::new(data.to_vec()), "", "", options).map(|_| ())
+    };
+    assert!(open(&sorted, KeyOrderCheck::Full, true).is_ok());
+
+    // Relabel the keys sorted for Swedish as English, where "öl" sorts before "zebra"
+    let header_len = u32::from_be_bytes(sorted[..4].try_into().unwrap()) as usize;
+    let header = String::from_utf8(sorted[4..4 + header_len].to_vec()).unwrap()
+        .replace("DefaultSortingLocale=\"sv\"", "DefaultSortingLocale=\"en\"");
+    let mut relabeled = sorted.clone();
+    relabeled[4..4 + header_len].copy_from_slice(header.as_bytes());
+    relabeled[4 + header_len..8 + header_len].copy_from_slice(&adler::adler32_slice(header.as_bytes()).to_le_bytes());
+    for check in [KeyOrderCheck::Sampled, KeyOrderCheck::Full] {
+        let error = open(&relabeled, check, true).unwrap_err();
+        assert!(matches!(&error, ZdbError::KeyOrderMismatch { previous, key, .. } if previous == "zebra" && key == "öl"), "{}", error);
+        assert!(open(&relabeled, check, false).is_ok());
+    }
+    assert!(open(&relabeled, KeyOrderCheck::Off, true).is_ok());
+}
@@ -0,0 +1,135 @@
+//! Sets of dictionaries: dictionary packs, persistent profile ids and links between
+//! dictionaries.
+
+// Building dictionaries needs compiled collation data
+#![cfg(any(feature = "icu", feature = "rust-icu"))]
+
+mod common;
+
+use std::path::Path;
+
+use mdx::builder::ZDBBuilder;
+use mdx::readers::{DictPackEntry, DictPackManifest, DictRegistry};
+use mdx::utils::{MdxHtmlRewriterInstance, MdxServiceUrl};
+use mdx::ZdbError;
+
+use common::{source_config, work_dir};
+
+/// Builds the same single entry to each of the `names` in the `dicts` directory of `dir`.
+fn build_dicts(dir: &Path, names: &[&str]) {
+    std::fs::create_dir_all(dir.join("dicts")).unwrap();
+    for name in names {
+        let config = source_config(dir, "run\r\n<p>to move fast</p>\r\n</>\r\n", &format!("dicts/{}", name));
+        ZDBBuilder::build_with_config(&config, None).unwrap();
+    }
+}
+
+#[test]
+fn dict_pack_registry() {
+    let dir = work_dir();
+    build_dicts(&dir, &["first.mdx", "second.mdx"]);
+
+    let entry = |path: &str, title: Option<&str>, order: i32, enabled: bool| DictPackEntry {
+        path: path.to_string(), title: title.map(str::to_string), icon: None, order, enabled,
+    };
+    let manifest = DictPackManifest {
+        name: "Library".to_string(),
+        dictionaries: vec![
+            entry("dicts/second.mdx", Some("Second"), 2, true),
+            entry("dicts/missing.mdx", None, 0, true),
+            entry("dicts/first.mdx", None, 1, true),
+            entry("dicts/first.mdx", None, 3, false),
+        ],
+    };
+    let manifest_path = dir.join("library.toml");
+    manifest.save(&manifest_path).unwrap();
+    let manifest = DictPackManifest::load(&manifest_path).unwrap();
+
+    let mut registry = DictRegistry::new();
+    let failures = registry.open_pack(&manifest, &dir, "");
+    assert_eq!(failures.len(), 1);
+    assert_eq!(failures[0].0, "dicts/missing.mdx");
+    let titles: Vec<(u32, &str)> = registry.iter().map(|dict| (dict.profile_id, dict.title.as_str())).collect();
+    assert_eq!(titles, [(0, "first"), (1, "Second")]);
+    let dict = registry.get_mut(1).unwrap();
+    let key_index = dict.reader.get_index(0).unwrap();
+    assert_eq!(dict.reader.get_html(&key_index).unwrap(), "<p>to move fast</p>\r\n");
+    registry.remove(0).unwrap();
+    assert!(matches!(registry.get(0), Err(ZdbError::ProfileNotFound { profile_id: 0, .. })));
+    assert_eq!(registry.len(), 1);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn stable_profile_ids() {
+    let dir = work_dir();
+    build_dicts(&dir, &["b.mdx", "c.mdx"]);
+    let id_path = dir.join("profiles.json");
+    let profile_ids = |registry: &DictRegistry| registry.iter()
+        .map(|dict| (dict.title.clone(), dict.profile_id)).collect::<Vec<_>>();
+    let titles = |ids: &[(&str, u32)]| ids.iter().map(|(title, id)| (title.to_string(), *id)).collect::<Vec<_>>();
+
+    let mut registry = DictRegistry::with_profile_ids(&id_path).unwrap();
+    assert!(registry.open_dir(&dir.join("dicts"), "").unwrap().is_empty());
+    assert_eq!(profile_ids(&registry), titles(&[("b", 0), ("c", 1)]));
+    registry.save_profile_ids().unwrap();
+    drop(registry);
+
+    // A dictionary listed before the others gets a new id, the others keep theirs
+    build_dicts(&dir, &["a.mdx"]);
+    std::fs::remove_file(dir.join("dicts").join("b.mdx")).unwrap();
+    let mut registry = DictRegistry::with_profile_ids(&id_path).unwrap();
+    registry.open_dir(&dir.join("dicts"), "").unwrap();
+    assert_eq!(profile_ids(&registry), titles(&[("a", 2), ("c", 1)]));
+    registry.save_profile_ids().unwrap();
+    assert_eq!(registry.profile_ids().profiles.len(), 3);
+    drop(registry);
+
+    let mut registry = DictRegistry::with_profile_ids(&id_path).unwrap();
+    registry.open_dir(&dir.join("dicts"), "").unwrap();
+    assert_eq!(profile_ids(&registry), titles(&[("a", 2), ("c", 1)]));
+    // Without persisted ids they are given in order
+    let mut registry = DictRegistry::new();
+    registry.open_dir(&dir.join("dicts"), "").unwrap();
+    assert_eq!(profile_ids(&registry), titles(&[("a", 0), ("c", 1)]));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn cross_dictionary_links() {
+    let dir = work_dir();
+    std::fs::create_dir(dir.join("dicts")).unwrap();
+    let sources = [
+        ("idioms", "kick the bucket\r\n<p>to die</p>\r\n</>\r\n"),
+        ("main", "bucket\r\n<p>a pail, see <a href=\"entry://idioms/kick the bucket#usage\">idiom</a> and <a href=\"entry://a/b\">b</a></p>\r\n</>\r\n"),
+    ];
+    for (name, source) in sources {
+        let config = source_config(&dir, source, &format!("dicts/{}.mdx", name));
+        ZDBBuilder::build_with_config(&config, None).unwrap();
+    }
+    let mut registry = DictRegistry::new();
+    assert!(registry.open_dir(&dir.join("dicts"), "").unwrap().is_empty());
+    assert_eq!(registry.dictionary_names(), ["idioms", "main"]);
+    assert_eq!(registry.resolve_profile_name("Idioms"), Some(0));
+    assert_eq!(registry.resolve_profile_name("unknown"), None);
+
+    let main = registry.get_mut(1).unwrap();
+    let key_index = main.reader.get_index(0).unwrap();
+    let html = main.reader.get_html(&key_index).unwrap();
+    let rewriter = MdxHtmlRewriterInstance::new(1).with_dictionary_names(registry.dictionary_names());
+    let rewritten = rewriter.rewrite_entry(&html, "bucket").unwrap();
+    let link = "mdx://mdict.cn/service/entry?profile_id=1&profile_name=idioms&key=kick+the+bucket#usage";
+    assert!(rewritten.contains(link), "{}", rewritten);
+    // Keys with a slash not starting with a dictionary name are ordinary links
+    assert!(rewritten.contains("mdx://mdict.cn/service/entry?profile_id=1&key=a%2Fb"), "{}", rewritten);
+
+    let service_url = MdxServiceUrl::parse(link).unwrap();
+    let profile_id = registry.resolve_service_url(&service_url).unwrap();
+    assert_eq!(profile_id, 0);
+    let idioms = registry.get_mut(profile_id).unwrap();
+    let key_index = idioms.reader.find_index(service_url.key().unwrap(), false, false, false).unwrap().unwrap();
+    assert_eq!(idioms.reader.get_html(&key_index).unwrap(), "<p>to die</p>\r\n");
+    let unknown = MdxServiceUrl { profile_name: Some("unknown".to_string()), ..service_url };
+    assert!(registry.resolve_service_url(&unknown).is_err());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
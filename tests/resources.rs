@@ -0,0 +1,143 @@
+//! Resources of dictionaries: lookups in the resource file, overrides from the dictionary
+//! folder, stylesheets and media types.
+
+// Building dictionaries needs compiled collation data
+#![cfg(any(feature = "icu", feature = "rust-icu"))]
+
+mod common;
+
+use std::path::Path;
+
+use mdx::builder::{BuilderConfig, MediaTypeConfig, ZDBBuilder, ZdbRecord};
+use mdx::readers::{StylesheetCache, StylesheetOptions, StylesheetSource};
+use mdx::{ErrorCode, MdxReader};
+use url::Url;
+
+use common::{build_file, english_config, resource_config, work_dir};
+
+/// Builds `dict.mdx` in `dict_dir` with a single entry showing `content`, next to the
+/// `dict.mdd` built from the resources, and opens it.
+fn open_with_resources(dict_dir: &Path, content: &str) -> MdxReader {
+    let records = [ZdbRecord { key: "cat".to_string(), content: content.to_string(), ..Default::default() }];
+    let path = dict_dir.join("dict.mdx");
+    build_file(&english_config(), &path, records);
+    MdxReader::from_url(&Url::from_file_path(path).unwrap(), "").unwrap()
+}
+
+#[test]
+fn resource_existence() {
+    let dir = work_dir();
+    let resource_dir = dir.join("resources");
+    std::fs::create_dir_all(resource_dir.join("img")).unwrap();
+    std::fs::write(resource_dir.join("img").join("cat.png"), b"not really a png").unwrap();
+    ZDBBuilder::build_with_config(&resource_config(&resource_dir, &dir.join("dict.mdd")), None).unwrap();
+
+    let mut reader = open_with_resources(&dir, "<img src=\"/img/cat.png\">");
+    assert!(reader.has_resource("/img/cat.png").unwrap());
+    assert!(!reader.has_resource("/img/dog.png").unwrap());
+    assert!(reader.get_data("/img/dog.png").unwrap().is_none());
+    assert_eq!(reader.get_data("/img/cat.png").unwrap().unwrap().0, b"not really a png");
+
+    // Separators and the leading slash are normalized, case only if enabled
+    assert!(reader.has_resource("img\\cat.png").unwrap());
+    assert!(!reader.has_resource("/IMG/Cat.png").unwrap());
+    reader.set_case_insensitive_resources(true);
+    assert!(reader.has_resource("/IMG/Cat.png").unwrap());
+    assert_eq!(reader.get_data("Img/CAT.png").unwrap().unwrap().0, b"not really a png");
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn resource_override_sandbox() {
+    let dir = work_dir();
+    let resource_dir = dir.join("resources");
+    let dict_dir = dir.join("dict");
+    std::fs::create_dir_all(resource_dir.join("img")).unwrap();
+    std::fs::create_dir_all(dict_dir.join("img")).unwrap();
+    std::fs::write(resource_dir.join("img").join("cat.png"), b"packed").unwrap();
+    std::fs::write(dict_dir.join("img").join("cat.png"), b"override").unwrap();
+    std::fs::write(dir.join("secret.txt"), b"secret").unwrap();
+    ZDBBuilder::build_with_config(&resource_config(&resource_dir, &dict_dir.join("dict.mdd")), None).unwrap();
+
+    let mut reader = open_with_resources(&dict_dir, "<img src=\"/img/cat.png\">");
+    assert_eq!(reader.get_data("/img/cat.png").unwrap().unwrap().0, b"override");
+    // Dot segments are resolved within the folder, encoded separators can't leave it
+    assert!(reader.get_data("/../secret.txt").unwrap().is_none());
+    for path in ["/..%2Fsecret.txt", "/img%5C..%5C..%5Csecret.txt", "img/%2e%2e%2f%2e%2e%2fsecret.txt"] {
+        assert_eq!(reader.get_data(path).unwrap_err().code(), ErrorCode::PathOutsideDictionary, "{}", path);
+        assert_eq!(reader.has_resource(path).unwrap_err().code(), ErrorCode::PathOutsideDictionary, "{}", path);
+    }
+    #[cfg(unix)]
+    {
+        // Links out of the folder are ignored whether their target exists or not
+        std::os::unix::fs::symlink(dir.join("secret.txt"), dict_dir.join("link.txt")).unwrap();
+        std::os::unix::fs::symlink(dir.join("missing.txt"), dict_dir.join("dangling.txt")).unwrap();
+        assert!(reader.get_data("/link.txt").unwrap().is_none());
+        assert!(reader.get_data("/dangling.txt").unwrap().is_none());
+    }
+
+    reader.set_resource_overrides(false);
+    assert_eq!(reader.get_data("/img/cat.png").unwrap().unwrap().0, b"packed");
+    assert!(reader.get_data("/..%2Fsecret.txt").unwrap().is_none());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn dictionary_stylesheet() {
+    let dir = work_dir();
+    let resource_dir = dir.join("resources");
+    std::fs::create_dir_all(&resource_dir).unwrap();
+    std::fs::write(resource_dir.join("dict.css"), "/* packed */ .hw { background: url(bg.png); }").unwrap();
+    ZDBBuilder::build_with_config(&resource_config(&resource_dir, &dir.join("dict.mdd")), None).unwrap();
+
+    let mut reader = open_with_resources(&dir, "<link rel=\"stylesheet\" href=\"dict.css\">cat");
+    let options = StylesheetOptions { minify: true, ..Default::default() };
+    let stylesheet = reader.get_stylesheet(7, &options).unwrap().unwrap();
+    assert_eq!(stylesheet.source, StylesheetSource::Resource("/dict.css".to_string()));
+    assert_eq!(stylesheet.css, ".hw{background:url(mdx://mdict.cn/service/mdd?profile_id=7&key=%2Fbg.png)}");
+
+    // A stylesheet next to the dictionary comes first
+    std::fs::write(dir.join("dict.css"), "\u{FEFF}.hw { color: red; }").unwrap();
+    let mut cache = StylesheetCache::new(StylesheetOptions::default());
+    let stylesheet = cache.get(&mut reader, 7).unwrap().unwrap();
+    assert_eq!(stylesheet.source, StylesheetSource::File(Url::from_file_path(dir.join("dict.css")).unwrap()));
+    assert_eq!(stylesheet.css, ".hw { color: red; }");
+    std::fs::write(dir.join("dict.css"), ".hw { color: blue; }").unwrap();
+    assert!(std::sync::Arc::ptr_eq(&stylesheet, &cache.get(&mut reader, 7).unwrap().unwrap()));
+    cache.invalidate(&reader);
+    assert_eq!(cache.get(&mut reader, 7).unwrap().unwrap().css, ".hw { color: blue; }");
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn media_types() {
+    let dir = work_dir();
+    let resource_dir = dir.join("resources");
+    std::fs::create_dir_all(resource_dir.join("img")).unwrap();
+    let photo = b"jpeg".repeat(50_000);
+    std::fs::write(resource_dir.join("img").join("a.css"), "b { color: red }".repeat(1000)).unwrap();
+    std::fs::write(resource_dir.join("img").join("b.jpg"), &photo).unwrap();
+    std::fs::write(resource_dir.join("img").join("c.svgz"), b"compressed").unwrap();
+    std::fs::write(resource_dir.join("img").join("d.css"), "i { color: blue }".repeat(1000)).unwrap();
+    let mut config = BuilderConfig {
+        media_types: [
+            ("JPG".to_string(), MediaTypeConfig { mime: String::new(), no_compress: true }),
+            (".svgz".to_string(), MediaTypeConfig { mime: "image/svg+xml".to_string(), no_compress: false }),
+        ].into_iter().collect(),
+        ..resource_config(&resource_dir, &dir.join("dict.mdd"))
+    };
+    ZDBBuilder::build_with_config(&config, None).unwrap();
+    // The repetitive photo is stored as is, the stylesheets around it are still compressed
+    let mdd_len = std::fs::metadata(&config.output_file).unwrap().len() as usize;
+    assert!(mdd_len > photo.len() && mdd_len < photo.len() + 10_000);
+
+    let mut reader = open_with_resources(&dir, "<img src=\"/img/b.jpg\">");
+    assert_eq!(reader.get_data("/img/b.jpg").unwrap().unwrap(), (photo, "image/jpeg".to_string()));
+    assert_eq!(reader.get_data("/img/c.svgz").unwrap().unwrap(), (b"compressed".to_vec(), "image/svg+xml".to_string()));
+    assert_eq!(reader.get_data("/img/d.css").unwrap().unwrap().0, "i { color: blue }".repeat(1000).into_bytes());
+    assert_eq!(reader.get_data("/img/d.css").unwrap().unwrap().1, "text/css");
+
+    config.media_types.insert("mp3".to_string(), MediaTypeConfig { mime: "audio/mpeg;x".to_string(), no_compress: true });
+    assert!(config.validate().unwrap_err().iter().any(|problem| problem.starts_with("media_types.mp3")));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
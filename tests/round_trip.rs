@@ -0,0 +1,132 @@
+//! Property-based build → read round trips.
+//!
+//! Random entry sets are written as MDict source text, built with every combination
+//! of compression and encryption method, then read back. Every entry must come back
+//! with its content unchanged and every key must be found by an exact lookup.
+
+// Building dictionaries needs compiled collation data
+#![cfg(any(feature = "icu", feature = "rust-icu"))]
+
+mod common;
+
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+
+use proptest::prelude::*;
+
+use mdx::builder::{BuilderConfig, SourceType, ZDBBuilder};
+use mdx::crypto::encryption::EncryptionMethod;
+use mdx::utils::compression::CompressionMethod;
+use mdx::ZdbReader;
+
+use common::{english_config, read_entries, work_dir};
+
+const COMPRESSION_METHODS: &[CompressionMethod] = &[
+    CompressionMethod::None,
+    CompressionMethod::Lzo,
+    CompressionMethod::Deflate,
+    CompressionMethod::Lzma,
+    CompressionMethod::Bzip2,
+    CompressionMethod::Lz4,
+];
+
+const ENCRYPTION_METHODS: &[EncryptionMethod] = &[
+    EncryptionMethod::None,
+    EncryptionMethod::Simple,
+    EncryptionMethod::Salsa20,
+];
+
+/// Keys that collide by case, normalization form or width, picked often to produce duplicates.
+const TRICKY_KEYS: &[&str] = &["a", "A", "é", "e\u{301}", "ａ", "同", "🙂", "a b", " a", "ß", "ss"];
+
+/// Keys of the source text: no line breaks, no NUL since keys are stored zero-terminated,
+/// no BOM which is skipped at the start of the file, at most 255 bytes.
+fn key_strategy() -> impl Strategy<Value = String> {
+    prop_oneof![
+        3 => "[^\r\n\u{0}\u{FEFF}]{1,24}".prop_filter("key too long", |key| key.len() <= 255),
+        1 => proptest::sample::select(TRICKY_KEYS).prop_map(str::to_string),
+    ]
+}
+
+/// Content as lines of the source text, a line can't be blank or the `</>` end marker.
+fn content_strategy() -> impl Strategy<Value = Vec<String>> {
+    let line = "[^\r\n]{0,60}".prop_filter("end marker", |line| !line.trim().is_empty() && line.trim() != "</>");
+    prop_oneof![
+        6 => proptest::collection::vec(line, 1..5),
+        1 => Just(Vec::new()),
+        // Larger than a content block
+        1 => (1usize..4).prop_map(|n| vec!["huge entry ".repeat(n * 8_000)]),
+    ]
+}
+
+fn expected_content(lines: &[String]) -> Vec<u8> {
+    lines.iter().map(|line| format!("{}\r\n", line)).collect::<String>().into_bytes()
+}
+
+fn build_and_check(dir: &Path, entries: &[(String, Vec<String>)], compression_method: CompressionMethod, encryption_method: EncryptionMethod) {
+    let source_path = dir.join("source.txt");
+    let source: String = entries.iter()
+        .map(|(key, lines)| format!("{}\r\n{}</>\r\n", key, String::from_utf8(expected_content(lines)).unwrap()))
+        .collect();
+    std::fs::write(&source_path, source).unwrap();
+
+    let output_path = dir.join(format!("{:?}_{:?}.mdx", compression_method, encryption_method));
+    let config = BuilderConfig {
+        input_path: source_path.to_string_lossy().to_string(),
+        output_file: output_path.to_string_lossy().to_string(),
+        data_source_format: SourceType::MdictHtml,
+        content_type: "Text".to_string(),
+        preferred_key_block_size: 256,
+        compression_method,
+        encryption_method,
+        ..english_config()
+    };
+    ZDBBuilder::build_with_config(&config, None).unwrap();
+
+    let mut reader = ZdbReader::<BufReader<File>>::from_file(&output_path, "", "").unwrap();
+    assert_eq!(reader.get_entry_count(), entries.len() as u64);
+
+    let mut expected: Vec<(String, Vec<u8>)> = entries.iter().map(|(key, lines)| (key.clone(), expected_content(lines))).collect();
+    let mut actual = read_entries(&mut reader);
+    expected.sort();
+    actual.sort();
+    assert_eq!(actual, expected);
+
+    for (key, _) in entries {
+        let key_index = reader.find_first_match(key, false, false, true).unwrap();
+        assert_eq!(key_index.map(|key_index| key_index.key).as_ref(), Some(key), "lookup of {:?}", key);
+    }
+
+    // Bulk lookups must agree with single lookups, including for missing keys
+    let mut queries: Vec<String> = entries.iter().map(|(key, _)| key.clone()).collect();
+    queries.extend(entries.iter().map(|(key, _)| format!("{}~", key)));
+    queries.extend(["", "zzz", "\u{0}"].map(str::to_string));
+    let queries: Vec<&str> = queries.iter().map(String::as_str).collect();
+    let bulk = reader.lookup_many(&queries).unwrap();
+    for (query, result) in queries.iter().zip(bulk) {
+        let single = reader.find_first_match(query, false, false, true).unwrap().map(|key_index| key_index.entry_no);
+        assert_eq!(result.map(|key_index| key_index.entry_no), single, "bulk lookup of {:?}", query);
+        assert_eq!(reader.get_entry_no_by_key(query).unwrap(), single, "entry number of {:?}", query);
+    }
+}
+
+proptest! {
+    // Every case builds 18 files, so keep the number of cases low
+    #![proptest_config(ProptestConfig {
+        cases: 6,
+        failure_persistence: Some(Box::new(proptest::test_runner::FileFailurePersistence::WithSource("regressions"))),
+        ..ProptestConfig::default()
+    })]
+
+    #[test]
+    fn build_read_round_trip(entries in proptest::collection::vec((key_strategy(), content_strategy()), 1..40)) {
+        let dir = work_dir();
+        for &compression_method in COMPRESSION_METHODS {
+            for &encryption_method in ENCRYPTION_METHODS {
+                build_and_check(&dir, &entries, compression_method, encryption_method);
+            }
+        }
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
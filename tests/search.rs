@@ -0,0 +1,245 @@
+//! Searching dictionaries: the full-text index, its health checks, romanized and headword
+//! token searches, ranked results and search limits.
+
+// Building dictionaries needs compiled collation data
+#![cfg(any(feature = "icu", feature = "rust-icu"))]
+
+mod common;
+
+use std::path::Path;
+
+use mdx::builder::{make_index, make_index_with_options, BuilderConfig, FtsIndexOptions, ZdbRecord};
+use mdx::readers::{FtsIndexProblem, FtsSearchOptions, SearchLimits, SearchOptions, SearchSource, Truncation};
+use mdx::utils::Romanization;
+use mdx::{ErrorCode, MdxReader, ZdbError};
+use url::Url;
+
+use common::{build_file, english_config, keyed_records, work_dir};
+
+/// Builds `count` entries `word00`, `word01`… with the content made from their number to `path`.
+fn build_numbered(path: &Path, count: usize, content: impl Fn(usize) -> String) {
+    let records: Vec<ZdbRecord> = (0..count)
+        .map(|i| ZdbRecord { key: format!("word{:02}", i), content: content(i), ..Default::default() })
+        .collect();
+    build_file(&english_config(), path, records);
+}
+
+#[test]
+fn union_entries_html_and_fts() {
+    let config = BuilderConfig { merge_duplicate_keys: true, ..english_config() };
+    let records: Vec<ZdbRecord> = [("run", "<p>verb</p>"), ("walk", "<p>stroll</p>"), ("run", "<p>noun</p>")].iter()
+        .map(|(key, content)| ZdbRecord { key: key.to_string(), content: content.to_string(), ..Default::default() })
+        .collect();
+    let dir = work_dir();
+    let path = dir.join("union.mdx");
+    build_file(&config, &path, records);
+    make_index(&path, None).unwrap();
+
+    let mut reader = MdxReader::from_url(&Url::from_file_path(&path).unwrap(), "").unwrap();
+    let union_index = reader.find_index("run", false, false, true).unwrap().unwrap();
+    let html = reader.get_html(&union_index).unwrap();
+    assert!(!html.contains("@@@UNION"));
+    assert!(html.contains("<p>verb</p>") && html.contains("<p>noun</p>"));
+    // Only the members are indexed
+    let results = reader.fts_search("noun", 10).unwrap();
+    assert_eq!(results.len(), 1);
+    assert_ne!(results[0].1, union_index.entry_no);
+    drop(reader);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn fts_index_schema_check() {
+    let build = |path: &Path, count: usize| build_numbered(path, count, |i| format!("<p>meaning number{}</p>", i));
+    let dir = work_dir();
+    let path = dir.join("fts.mdx");
+    let url = Url::from_file_path(&path).unwrap();
+    build(&path, 20);
+    make_index(&path, None).unwrap();
+
+    let reader = MdxReader::from_url(&url, "").unwrap();
+    assert!(!reader.needs_reindex());
+    let results = reader.fts_search("number7", 10).unwrap();
+    assert_eq!(results.iter().map(|(_, entry_no, _)| *entry_no).collect::<Vec<_>>(), vec![7]);
+    let searcher = reader.fts_searcher().unwrap();
+    let results = std::thread::spawn(move || searcher.search("number3", 10).unwrap()).join().unwrap();
+    assert_eq!(results.iter().map(|(_, entry_no, _)| *entry_no).collect::<Vec<_>>(), vec![3]);
+    let suggestions = reader.suggest("Wrod07", 1, 5).unwrap();
+    assert_eq!(suggestions.iter().map(|s| (s.key.as_str(), s.entry_no, s.distance)).collect::<Vec<_>>(), vec![("word07", 7, 1)]);
+    let suggestions = reader.suggest("word07", 2, 3).unwrap();
+    assert_eq!(suggestions.len(), 3);
+    assert_eq!((suggestions[0].key.as_str(), suggestions[0].distance), ("word07", 0));
+    assert!(suggestions[1..].iter().all(|s| s.distance == 1));
+    assert!(reader.suggest("word07", 3, 3).is_err());
+    drop(reader);
+
+    // The dictionary changed after it was indexed
+    build(&path, 21);
+    let reader = MdxReader::from_url(&url, "").unwrap();
+    assert!(reader.needs_reindex());
+    assert!(matches!(reader.fts_search("number7", 10), Err(ZdbError::FtsIndexOutdated { .. })));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn fts_index_health() {
+    let build = |path: &Path, count: usize| build_numbered(path, count, |i| format!("<p>meaning {}</p>", i));
+    let dir = work_dir();
+    let path = dir.join("health.mdx");
+    let url = Url::from_file_path(&path).unwrap();
+    build(&path, 5);
+    make_index(&path, None).unwrap();
+
+    let reader = MdxReader::from_url(&url, "").unwrap();
+    let stats = reader.fts_index_stats().unwrap();
+    assert_eq!((stats.doc_count, stats.entry_count, stats.segment_count), (5, 5, 1));
+    assert!(stats.total_size > 0);
+    assert_eq!(stats.metadata.unwrap().entry_count, 5);
+    let key_field = stats.fields.iter().find(|field| field.name == "key").unwrap();
+    assert!(key_field.stored);
+    assert_eq!(key_field.tokenizer.as_deref(), Some("default"));
+    assert!(reader.check_fts_index().unwrap().is_empty());
+    drop(reader);
+
+    // Rebuilt with the same number of entries, the index still opens
+    build(&path, 5);
+    let reader = MdxReader::from_url(&url, "").unwrap();
+    let problems = reader.check_fts_index().unwrap();
+    assert!(matches!(problems[..], [FtsIndexProblem::UuidMismatch { .. }]));
+    assert!(problems[0].is_stale());
+    drop(reader);
+
+    build(&path, 6);
+    let reader = MdxReader::from_url(&url, "").unwrap();
+    assert!(reader.needs_reindex());
+    assert_eq!(reader.fts_index_stats().unwrap().doc_count, 5);
+    let problems = reader.check_fts_index().unwrap();
+    assert!(problems.contains(&FtsIndexProblem::EntryCount { indexed: 5, dictionary: 6 }));
+    assert!(problems.contains(&FtsIndexProblem::DocCount { doc_count: 5, entry_count: 6 }));
+    assert!(problems.contains(&FtsIndexProblem::MissingEntries { count: 1, first: 5 }));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn fts_romanized_keys() {
+    let config = BuilderConfig { default_sorting_locale: "ja".to_string(), ..Default::default() };
+    let records = keyed_records(&["しんぶん", "コーヒー", "東京"], |key| format!("<p>{}</p>", key));
+    let dir = work_dir();
+    let path = dir.join("romaji.mdx");
+    build_file(&config, &path, records);
+    let options = FtsIndexOptions { romanization: Some(Romanization::Romaji), ..Default::default() };
+    make_index_with_options(&path, &options, None).unwrap();
+
+    let reader = MdxReader::from_url(&Url::from_file_path(&path).unwrap(), "").unwrap();
+    let romanized = FtsSearchOptions { match_romanized: true };
+    let results = reader.fts_search_with_options("shinbun", 10, &romanized).unwrap();
+    assert_eq!(results.iter().map(|(_, _, key)| key.as_str()).collect::<Vec<_>>(), vec!["しんぶん"]);
+    assert_eq!(reader.fts_search_with_options("KOOHII", 10, &romanized).unwrap().len(), 1);
+    assert!(reader.fts_search("shinbun", 10).unwrap().is_empty());
+    assert!(reader.fts_searcher().unwrap().has_romanized_keys());
+    drop(reader);
+
+    // Without romanized keys the option is rejected
+    make_index(&path, None).unwrap();
+    let reader = MdxReader::from_url(&Url::from_file_path(&path).unwrap(), "").unwrap();
+    assert_eq!(reader.fts_search_with_options("shinbun", 10, &romanized).unwrap_err().code(), ErrorCode::InvalidParameter);
+    if !cfg!(feature = "pinyin") {
+        let options = FtsIndexOptions { romanization: Some(Romanization::Pinyin), ..Default::default() };
+        assert_eq!(make_index_with_options(&path, &options, None).unwrap_err().code(), ErrorCode::UnsupportedFeature);
+    }
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn fts_headword_tokens() {
+    let records = keyed_records(&["bucket", "bucket list", "Kick the bucket", "kick off", "the"], |key| format!("<p>{}</p>", key));
+    let dir = work_dir();
+    let path = dir.join("idioms.mdx");
+    build_file(&english_config(), &path, records);
+    make_index_with_options(&path, &FtsIndexOptions { headword_tokens: true, ..Default::default() }, None).unwrap();
+
+    let reader = MdxReader::from_url(&Url::from_file_path(&path).unwrap(), "").unwrap();
+    let keys = |word| reader.search_headword_tokens(word).unwrap().into_iter().map(|(_, key)| key).collect::<Vec<_>>();
+    // Single-word headwords aren't indexed as tokens
+    assert_eq!(keys("Bucket"), vec!["bucket list", "Kick the bucket"]);
+    assert_eq!(keys("kick"), vec!["kick off", "Kick the bucket"]);
+    assert_eq!(keys("bucket kick"), vec!["Kick the bucket"]);
+    assert!(keys("pail").is_empty());
+    drop(reader);
+
+    make_index(&path, None).unwrap();
+    let reader = MdxReader::from_url(&Url::from_file_path(&path).unwrap(), "").unwrap();
+    assert_eq!(reader.search_headword_tokens("kick").unwrap_err().code(), ErrorCode::InvalidParameter);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn ranked_search() {
+    // Headwords differing in case compare equal, like in most dictionaries
+    let config = BuilderConfig { default_sorting_locale: "en-u-ks-level2".to_string(), ..Default::default() };
+    let records: Vec<ZdbRecord> = [("Bucket", "pail"), ("bucket list", "things to do"), ("buckets", "plural"), ("kick the bucket", "die"), ("pail", "a bucket")].iter()
+        .map(|(key, content)| ZdbRecord { key: key.to_string(), content: format!("<p>{}</p>", content), ..Default::default() })
+        .collect();
+    let dir = work_dir();
+    let path = dir.join("ranked.mdx");
+    let url = Url::from_file_path(&path).unwrap();
+    build_file(&config, &path, records);
+    let ranked = |reader: &mut MdxReader, options: &SearchOptions| reader.search("bucket", options).unwrap().into_iter()
+        .map(|hit| (hit.key, hit.source)).collect::<Vec<_>>();
+
+    // Only headword hits without an FTS index
+    let mut reader = MdxReader::from_url(&url, "").unwrap();
+    let key_hits = vec![
+        ("Bucket".to_string(), SearchSource::Exact),
+        ("buckets".to_string(), SearchSource::Prefix),
+        ("bucket list".to_string(), SearchSource::Prefix),
+    ];
+    assert_eq!(ranked(&mut reader, &SearchOptions::default()), key_hits);
+    drop(reader);
+
+    make_index(&path, None).unwrap();
+    let mut reader = MdxReader::from_url(&url, "").unwrap();
+    let mut hits = ranked(&mut reader, &SearchOptions::default());
+    assert_eq!(hits[..3], key_hits[..]);
+    assert_eq!(hits[3..].iter().map(|(key, _)| key.as_str()).collect::<Vec<_>>(), vec!["kick the bucket", "pail"]);
+    hits = ranked(&mut reader, &SearchOptions { fts_weight: 10.0, max_prefix_hits: 0, max_results: 2, ..Default::default() });
+    assert!(hits.iter().all(|(_, source)| *source == SearchSource::FullText));
+    assert!(reader.search("  ", &SearchOptions::default()).unwrap().is_empty());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn limited_searches() {
+    let config = BuilderConfig { preferred_key_block_size: 256, ..english_config() };
+    let records: Vec<ZdbRecord> = (0..300)
+        .map(|i| ZdbRecord { key: format!("word{:03}", i), content: format!("<p>common meaning {}</p>", i), ..Default::default() })
+        .collect();
+    let dir = work_dir();
+    let path = dir.join("limits.mdx");
+    build_file(&config, &path, records);
+    make_index(&path, None).unwrap();
+    let mut reader = MdxReader::from_url(&Url::from_file_path(&path).unwrap(), "").unwrap();
+
+    let found = reader.find_wildcard("*1?", &SearchLimits::default()).unwrap();
+    assert_eq!(found.results.len(), 30);
+    assert_eq!(found.truncated, None);
+    let found = reader.find_wildcard("word2*", &SearchLimits::default()).unwrap();
+    assert_eq!((found.results.len(), found.results[0].key.as_str()), (100, "word200"));
+    let found = reader.find_wildcard("word2*", &SearchLimits { max_matches: Some(5), ..Default::default() }).unwrap();
+    assert_eq!(found.results.iter().map(|key_index| key_index.key.as_str()).collect::<Vec<_>>(), vec!["word200", "word201", "word202", "word203", "word204"]);
+    assert_eq!(found.truncated, Some(Truncation::MaxMatches));
+    let found = reader.find_wildcard("*", &SearchLimits { max_scanned_blocks: Some(1), ..Default::default() }).unwrap();
+    assert!(!found.results.is_empty() && found.results.len() < 300);
+    assert_eq!(found.truncated, Some(Truncation::MaxScannedBlocks));
+    let timed_out = SearchLimits { timeout: Some(std::time::Duration::ZERO), ..Default::default() };
+    let found = reader.find_wildcard("*a*", &timed_out).unwrap();
+    assert_eq!((found.results.len(), found.truncated), (0, Some(Truncation::Timeout)));
+
+    let found = reader.fts_search_limited("common", 10, &SearchLimits::default()).unwrap();
+    assert_eq!((found.results.len(), found.truncated), (10, None));
+    let found = reader.fts_search_limited("common", 10, &SearchLimits { max_matches: Some(50), ..Default::default() }).unwrap();
+    assert_eq!((found.results.len(), found.truncated), (10, Some(Truncation::MaxMatches)));
+    let found = reader.fts_search_limited("meaning 7", 10, &timed_out).unwrap();
+    assert_eq!((found.results.len(), found.truncated), (0, Some(Truncation::Timeout)));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,63 @@
+//! Content blocks shared by readers on several threads.
+
+// Building dictionaries needs compiled collation data
+#![cfg(any(feature = "icu", feature = "rust-icu"))]
+
+mod common;
+
+use std::io::Cursor;
+use std::sync::Arc;
+
+use mdx::builder::{BuilderConfig, ZdbRecord};
+use mdx::readers::{ReaderOptions, SharedBlockCache};
+use mdx::utils::compression::CompressionMethod;
+use mdx::utils::BlockCacheConfig;
+use mdx::ZdbReader;
+
+use common::{build_in_memory, english_config};
+
+fn build(prefix: &str) -> Vec<u8> {
+    let records = (0..300).map(|i| ZdbRecord { key: format!("word{:03}", i), content: format!("<p>{} {}</p>", prefix, i), ..Default::default() });
+    let config = BuilderConfig { preferred_content_block_size: 1024, compression_method: CompressionMethod::None, ..english_config() };
+    build_in_memory(&config, records)
+}
+
+fn read_all(data: Vec<u8>, cache: Arc<SharedBlockCache>, prefix: String) {
+    let options = ReaderOptions { shared_block_cache: Some(cache), ..Default::default() };
+    let mut reader = ZdbReader::from_reader_with_options(Cursor::new(data), "", "", options).unwrap();
+    for entry_no in 0..reader.get_entry_count() {
+        let key_index = reader.get_index(entry_no as _).unwrap();
+        assert_eq!(reader.get_string(&key_index, false).unwrap(), format!("<p>{} {}</p>", prefix, entry_no));
+    }
+}
+
+#[test]
+fn readers_share_content_blocks() {
+    let cache = Arc::new(SharedBlockCache::new(&BlockCacheConfig { shard_count: 4, capacity: 1000 }));
+    assert_eq!(cache.shard_count(), 4);
+    let apple = build("apple");
+    let threads: Vec<_> = (0..4).map(|_| {
+        let (data, cache) = (apple.clone(), cache.clone());
+        std::thread::spawn(move || read_all(data, cache, "apple".to_string()))
+    }).collect();
+    for thread in threads {
+        thread.join().unwrap();
+    }
+    let block_count = cache.len();
+    assert!(block_count > 1);
+
+    // Another dictionary with the same layout gets its own blocks
+    read_all(build("peach"), cache.clone(), "peach".to_string());
+    assert_eq!(cache.len(), 2 * block_count);
+
+    // The stored blocks of a reader finding them all in the cache aren't read
+    let mut garbled = apple.clone();
+    for offset in 0..garbled.len() - 5 {
+        if &garbled[offset..offset + 5] == b"apple" {
+            garbled[offset..offset + 5].copy_from_slice(b"APPLE");
+        }
+    }
+    assert_ne!(garbled, apple);
+    read_all(garbled, cache.clone(), "apple".to_string());
+    assert_eq!(cache.len(), 2 * block_count);
+}
@@ -0,0 +1,63 @@
+//! Building and reading dictionaries without entries or with a single entry.
+
+// Building dictionaries needs compiled collation data
+#![cfg(any(feature = "icu", feature = "rust-icu"))]
+
+mod common;
+
+use std::fs::File;
+use std::io::BufReader;
+
+use mdx::builder::{BuildWarning, ZDBBuilder};
+use mdx::inspect::{dump, Verbosity};
+use mdx::readers::DictStatistics;
+use mdx::storage::UnitType;
+use mdx::{MdxReader, ZdbReader};
+use url::Url;
+
+use common::{source_config, work_dir};
+
+#[test]
+fn empty_dictionary() {
+    let dir = work_dir();
+    let mut config = source_config(&dir, "", "empty.mdx");
+    config.bloom_filter = true;
+    let report = ZDBBuilder::build_with_config(&config, None).unwrap();
+    assert_eq!(report.entry_count, 0);
+    assert_eq!(report.warnings, [BuildWarning::NoEntries]);
+    let mut reader = ZdbReader::<BufReader<File>>::from_file(&config.output_file, "", "").unwrap();
+    assert_eq!(reader.get_entry_count(), 0);
+    assert!(reader.get_index(0).is_err());
+    assert!(reader.get_content_length(0).is_err());
+    assert_eq!(DictStatistics::collect(&mut reader, None).unwrap().content_sizes.total, 0);
+    assert!(dump(&config.output_file, Verbosity::Blocks).unwrap().units.iter().any(|unit| unit.unit_type == UnitType::Key));
+    let resorted = dir.join("resorted.mdx").to_string_lossy().to_string();
+    assert_eq!(ZDBBuilder::resort(&config.output_file, &resorted, "de", None).unwrap().entry_count, 0);
+    assert!(reader.get_data_by_key("apple").unwrap().is_none());
+    assert!(reader.get_indexes(0, 10).unwrap().is_empty());
+    let mut reader = MdxReader::from_url(&Url::from_file_path(&config.output_file).unwrap(), "").unwrap();
+    assert_eq!(reader.get_entry_count(), 0);
+    assert!(reader.find_index("apple", true, true, true).unwrap().is_none());
+    assert!(reader.lookup_many(&["apple"]).unwrap()[0].is_none());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn single_entry_dictionary() {
+    let dir = work_dir();
+    let mut config = source_config(&dir, "apple\r\n<p>red</p>\r\n</>\r\n", "single.mdx");
+    config.bloom_filter = true;
+    let report = ZDBBuilder::build_with_config(&config, None).unwrap();
+    assert_eq!(report.entry_count, 1);
+    let mut reader = ZdbReader::<BufReader<File>>::from_file(&config.output_file, "", "").unwrap();
+    assert_eq!(reader.get_entry_count(), 1);
+    assert_eq!(reader.get_content_length(0).unwrap(), 12);
+    assert!(reader.get_index(1).is_err());
+    assert_eq!(reader.get_data_by_key("apple").unwrap().unwrap(), b"<p>red</p>\r\n");
+    assert!(reader.get_data_by_key("zebra").unwrap().is_none());
+    let mut reader = MdxReader::from_url(&Url::from_file_path(&config.output_file).unwrap(), "").unwrap();
+    let key_index = reader.find_index("apple", false, false, false).unwrap().unwrap();
+    assert!(reader.find_index("zebra", false, false, false).unwrap().is_none());
+    assert_eq!(reader.get_html(&key_index).unwrap(), "<p>red</p>\r\n");
+    std::fs::remove_dir_all(&dir).unwrap();
+}